@@ -0,0 +1,112 @@
+//! Builds, sorts and submits the draw list of a frame with the headless backend, so only the CPU
+//! side of the renderer is measured.
+//!
+//! Run with `cargo bench --features headless`.
+use cac_renderer::{
+    AttributeSemantic, Backend, Buffer, BufferAttributes, BufferUsage, Handle, HeadlessContext,
+    Material, MaterialProperty, Mesh, Primitive, Renderer, ShaderProgram, VertexLayout,
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+const VS_SOURCE: &str = "
+    layout(location = 0) in vec3 pos;
+    uniform mat4 model;
+    void main() { gl_Position = model * vec4(pos, 1.0); }";
+
+const FS_SOURCE: &str = "
+    out vec4 result;
+    uniform vec4 color;
+    uniform float alpha;
+    void main() { result = vec4(color.rgb, alpha); }";
+
+struct Scene {
+    renderer: Renderer<HeadlessContext>,
+    mesh: Mesh,
+    materials: Vec<Handle<Material>>,
+}
+
+fn scene() -> Scene {
+    let mut renderer = Renderer::new_headless().unwrap();
+    let vertices = [0.0_f32; 9];
+    let indices = [0_u8, 1, 2];
+    let vertex_buffer =
+        Buffer::with_vertex(&mut renderer, &vertices, BufferUsage::StaticRead).unwrap();
+    let index_buffer =
+        Buffer::with_index(&mut renderer, &indices, BufferUsage::StaticRead).unwrap();
+    let vertex_layout = VertexLayout::new(
+        &mut renderer,
+        &[
+            BufferAttributes::with_semantics(vertex_buffer, 0, &[AttributeSemantic::Position]),
+            BufferAttributes::with_index(index_buffer, 0),
+        ],
+    )
+    .unwrap();
+
+    let program = ShaderProgram::from_sources(&mut renderer, VS_SOURCE, FS_SOURCE).unwrap();
+    let materials = (0..16)
+        .map(|_| renderer.create_material(program, &[]).unwrap())
+        .collect();
+
+    Scene {
+        renderer,
+        mesh: Mesh::new(vertex_layout, 3, Primitive::Triangles),
+        materials,
+    }
+}
+
+fn frame(scene: &mut Scene, draws: usize, properties: &[MaterialProperty]) {
+    for i in 0..draws {
+        let material = scene.materials[i % scene.materials.len()];
+        scene
+            .renderer
+            .draw_at_depth(scene.mesh, material, (draws - i) as f32, properties);
+    }
+    scene.renderer.update();
+    scene.renderer.clear_recorded_commands();
+}
+
+fn draw_list(c: &mut Criterion) {
+    let mut group = c.benchmark_group("draw_list");
+    let color = [1.0_f32, 0.5, 0.25, 1.0];
+    let alpha = 0.5_f32;
+    let model = [0.0_f32; 16];
+
+    for draws in [1_000, 10_000, 50_000] {
+        group.throughput(Throughput::Elements(draws as u64));
+
+        let overrides: [(&str, Vec<MaterialProperty>); 3] = [
+            ("no overrides", vec![]),
+            (
+                "2 overrides",
+                vec![
+                    MaterialProperty::new("color", &color),
+                    MaterialProperty::new("alpha", &alpha),
+                ],
+            ),
+            (
+                "4 overrides",
+                vec![
+                    MaterialProperty::new("color", &color),
+                    MaterialProperty::new("alpha", &alpha),
+                    MaterialProperty::new("model", &model),
+                    MaterialProperty::new(0, &model),
+                ],
+            ),
+        ];
+
+        for (name, properties) in &overrides {
+            let mut scene = scene();
+            // grows the buffers to their steady state size
+            frame(&mut scene, draws, properties);
+
+            group.bench_with_input(BenchmarkId::new(*name, draws), &draws, |b, draws| {
+                b.iter(|| frame(&mut scene, *draws, properties))
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, draw_list);
+criterion_main!(benches);
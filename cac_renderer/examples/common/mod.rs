@@ -0,0 +1,58 @@
+//! Window and event loop shared by the examples.
+use std::time::Instant;
+
+use cac_renderer::{OpenGLContext, Renderer, RendererBuilder};
+use winit::{
+    dpi::LogicalSize,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::WindowBuilder,
+};
+
+pub const WIDTH: u32 = 1024;
+pub const HEIGHT: u32 = 768;
+
+pub fn init_logger(example: &str) {
+    pretty_env_logger::env_logger::init_from_env(
+        pretty_env_logger::env_logger::Env::default()
+            .default_filter_or(format!("{example},cac_renderer=info")),
+    );
+}
+
+/// Opens the window and creates the scene with `setup`.
+/// `frame` is called with the seconds since the start until the window is closed, and has to
+/// [update][cac_renderer::Backend::update] the renderer itself.
+pub fn run<S: 'static>(
+    example: &str,
+    title: &str,
+    setup: impl FnOnce(&mut Renderer<OpenGLContext>) -> anyhow::Result<S>,
+    mut frame: impl FnMut(&mut S, &mut Renderer<OpenGLContext>, f32) + 'static,
+) -> anyhow::Result<()> {
+    init_logger(example);
+
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title(format!("Example: {title}"))
+        .with_resizable(false)
+        .with_inner_size(LogicalSize::new(WIDTH, HEIGHT))
+        .build(&event_loop)?;
+
+    // OpenGL 4.5, or 3.3 if it is not available
+    let mut ctx = RendererBuilder::new().msaa(4).build_opengl(&window)?;
+    log::info!("{}", cac_renderer::Backend::context_description(&ctx));
+
+    let mut scene = setup(&mut ctx)?;
+    let start = Instant::now();
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+
+        match event {
+            Event::MainEventsCleared => frame(&mut scene, &mut ctx, start.elapsed().as_secs_f32()),
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => window.request_redraw(),
+        }
+    });
+}
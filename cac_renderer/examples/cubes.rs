@@ -0,0 +1,46 @@
+//! Stress test that draws 100k rotating cubes and logs the frame times and stats every second.
+//!
+//! There are no instanced draws yet, so every cube is a draw call with its own model matrix.
+mod common;
+#[path = "scenes/cubes.rs"]
+mod cubes;
+
+use cac_renderer::{Backend, Color32, FrameTimer};
+use cubes::Cubes;
+
+const CUBES: usize = 100_000;
+
+fn main() -> anyhow::Result<()> {
+    let mut timer = FrameTimer::with_repeated(1.0);
+    let mut frames = 0;
+
+    common::run(
+        "cubes",
+        "100k Cubes",
+        |ctx| {
+            ctx.screen_target()
+                .set_clear_color(Color32::DARK_JUNGLE_GREEN);
+            Ok(Cubes::new(
+                ctx,
+                CUBES,
+                common::WIDTH as f32 / common::HEIGHT as f32,
+            )?)
+        },
+        move |cubes, ctx, time| {
+            cubes.draw(ctx, time);
+            ctx.begin_gpu_scope("cubes");
+            ctx.update();
+            ctx.end_gpu_scope();
+
+            frames += 1;
+            if timer.tick_done() {
+                log::info!(
+                    "{:.2} ms/frame, {}",
+                    1000.0 / frames as f32,
+                    ctx.frame_stats()
+                );
+                frames = 0;
+            }
+        },
+    )
+}
@@ -0,0 +1,23 @@
+//! Records a frame of the cubes and the textured quad with the headless backend and prints the
+//! issued commands and the frame stats, e.g. to inspect the draw order without a GPU.
+#[path = "scenes/capture.rs"]
+mod capture;
+#[path = "scenes/cubes.rs"]
+mod cubes;
+#[path = "scenes/textured_quad.rs"]
+mod textured_quad;
+
+fn main() -> anyhow::Result<()> {
+    pretty_env_logger::env_logger::init_from_env(
+        pretty_env_logger::env_logger::Env::default()
+            .default_filter_or("headless_capture,cac_renderer=info"),
+    );
+
+    let ctx = capture::capture()?;
+    for command in ctx.recorded_commands() {
+        println!("{command:?}");
+    }
+    println!("{:#?}", ctx.frame_stats());
+
+    Ok(())
+}
@@ -93,12 +93,7 @@ fn main() -> anyhow::Result<(), anyhow::Error> {
         ],
     )?;
 
-    let mut triangle_mesh = Mesh {
-        vertex_layout,
-        start_index: 0,
-        count: 3,
-        primitive: cac_renderer::Primitive::Triangles,
-    };
+    let mut triangle_mesh = Mesh::new(vertex_layout, 3, cac_renderer::Primitive::Triangles);
 
     let vertex_shader = Shader::with_vertex(&mut ctx, VS_SOURCE)?;
     let fragment_shader = Shader::with_fragment(&mut ctx, FS_SOURCE)?;
@@ -123,9 +118,9 @@ fn main() -> anyhow::Result<(), anyhow::Error> {
             Event::MainEventsCleared => {
                 //render
                 if timer.tick_done() {
-                    triangle_mesh.start_index += 1;
-                    if triangle_mesh.start_index >= 4 {
-                        triangle_mesh.start_index = 0;
+                    triangle_mesh.index_offset += 1;
+                    if triangle_mesh.index_offset >= 4 {
+                        triangle_mesh.index_offset = 0;
                     }
                 }
 
@@ -0,0 +1,43 @@
+//! Renders rotating cubes into a texture with the size of the window, and draws it to the
+//! screen with a fullscreen pass that fades the colors in and out.
+//!
+//! Texture targets are only supported by the OpenGL backend, so `tests/examples.rs` records the
+//! two updates of a frame into the screen target of the headless backend instead.
+mod common;
+#[path = "scenes/cubes.rs"]
+mod cubes;
+#[path = "scenes/post_process.rs"]
+mod post_process;
+
+use cac_renderer::{Backend, Color32, Texture, TextureFormat};
+use cubes::Cubes;
+use post_process::PostProcess;
+
+fn main() -> anyhow::Result<()> {
+    common::run(
+        "post_processing",
+        "Post-Processing",
+        |ctx| {
+            let (width, height) = (common::WIDTH, common::HEIGHT);
+            let texels = vec![0; (width * height * 4) as usize];
+            let texture = Texture::new(ctx, &texels, width, height, TextureFormat::Rgba8)?;
+            let target = ctx.texture_target(texture)?;
+            if let Some(target) = ctx.external_target(target) {
+                target.set_clear_color(Color32::DARK_JUNGLE_GREEN);
+            }
+
+            let cubes = Cubes::new(ctx, 1000, width as f32 / height as f32)?;
+            let post_process = PostProcess::new(ctx, texture)?;
+            Ok((target, cubes, post_process))
+        },
+        |(target, cubes, post_process), ctx, time| {
+            ctx.set_external_target(Some(*target));
+            cubes.draw(ctx, time);
+            ctx.update();
+
+            ctx.set_external_target(None);
+            post_process.draw(ctx, time.sin() * 0.5 + 0.5);
+            ctx.update();
+        },
+    )
+}
@@ -0,0 +1,46 @@
+//! Renders rotating cubes into a texture and shows the texture on a quad.
+//!
+//! Texture targets are only supported by the OpenGL backend, so `tests/examples.rs` records the
+//! two updates of a frame into the screen target of the headless backend instead.
+mod common;
+#[path = "scenes/cubes.rs"]
+mod cubes;
+#[path = "scenes/textured_quad.rs"]
+mod textured_quad;
+
+use cac_renderer::{Backend, Color32, Texture, TextureFormat};
+use cubes::Cubes;
+use textured_quad::TexturedQuad;
+
+const SIZE: u32 = 512;
+
+fn main() -> anyhow::Result<()> {
+    common::run(
+        "render_to_texture",
+        "Render to Texture",
+        |ctx| {
+            ctx.screen_target()
+                .set_clear_color(Color32::DARK_JUNGLE_GREEN);
+
+            let texels = vec![0; (SIZE * SIZE * 4) as usize];
+            let texture = Texture::new(ctx, &texels, SIZE, SIZE, TextureFormat::Rgba8)?;
+            let target = ctx.texture_target(texture)?;
+            if let Some(target) = ctx.external_target(target) {
+                target.set_clear_color(Color32::PERSIAN_INDIGO);
+            }
+
+            let cubes = Cubes::new(ctx, 27, 1.0)?;
+            let quad = TexturedQuad::new(ctx, texture, None)?;
+            Ok((target, cubes, quad))
+        },
+        |(target, cubes, quad), ctx, time| {
+            ctx.set_external_target(Some(*target));
+            cubes.draw(ctx, time);
+            ctx.update();
+
+            ctx.set_external_target(None);
+            quad.draw(ctx, 1.5, time * 0.2);
+            ctx.update();
+        },
+    )
+}
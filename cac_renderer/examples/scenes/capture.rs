@@ -0,0 +1,28 @@
+//! A frame of the cubes and the textured quad, recorded with the headless backend.
+//! Shared by the `headless_capture` example and `tests/examples.rs`.
+
+use cac_renderer::{Backend, HeadlessContext, Renderer, Texture, TextureFormat};
+
+use super::{
+    cubes::Cubes,
+    textured_quad::{checkerboard, TexturedQuad},
+};
+
+/// Number of cubes in the captured frame.
+pub const CUBES: usize = 8;
+
+/// Records a frame of the cubes and the quad, without the uploads of the setup.
+pub fn capture() -> anyhow::Result<Renderer<HeadlessContext>> {
+    let mut ctx = Renderer::new_headless()?;
+    let cubes = Cubes::new(&mut ctx, CUBES, 4.0 / 3.0)?;
+    let texture = Texture::new(&mut ctx, &checkerboard(4, 2), 4, 4, TextureFormat::Rgba8)?;
+    let quad = TexturedQuad::new(&mut ctx, texture, None)?;
+
+    // only capture the frame, not the uploads of the setup
+    ctx.clear_recorded_commands();
+    cubes.draw(&mut ctx, 1.0);
+    quad.draw(&mut ctx, 1.0, 0.0);
+    ctx.update();
+
+    Ok(ctx)
+}
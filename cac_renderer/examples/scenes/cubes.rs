@@ -0,0 +1,146 @@
+//! A grid of rotating cubes, each drawn with its own model matrix.
+//! Shared by the examples and `tests/examples.rs`, which don't use all of it.
+#![allow(dead_code)]
+
+use cac_renderer::{
+    camera::Camera,
+    math::*,
+    AttributeSemantic::{Color, Position},
+    Backend, Buffer, BufferAttributes, BufferUsage, Context, Handle, Material, MaterialProperty,
+    Mesh, Primitive, RenderQueue, Renderer, RendererError, Shader, ShaderProgram, VertexLayout,
+};
+
+const VS_SOURCE: &str = r##"
+    #version 330 core
+    layout(location = 0) in vec3 pos;
+    layout(location = 5) in vec4 color;
+
+    uniform mat4 view_projection;
+    uniform mat4 model;
+
+    out vec4 frag_color;
+    void main()
+    {
+        frag_color = color;
+        gl_Position = view_projection * model * vec4(pos, 1.0);
+    }"##;
+
+const FS_SOURCE: &str = r##"
+    #version 330 core
+    in vec4 frag_color;
+    out vec4 result;
+
+    void main()
+    {
+        // there is no depth test, the cubes are drawn back to front and only their front faces
+        // are visible
+        if (!gl_FrontFacing) discard;
+        result = frag_color;
+    }"##;
+
+const SPACING: f32 = 3.0;
+
+pub struct Cubes {
+    pub mesh: Mesh,
+    pub material: Handle<Material>,
+    pub positions: Vec<Vec3>,
+    eye: Vec3,
+}
+
+impl Cubes {
+    /// Places the cubes on a grid and looks at it from the front.
+    pub fn new<C: Context>(
+        ctx: &mut Renderer<C>,
+        count: usize,
+        aspect_ratio: f32,
+    ) -> Result<Self, RendererError> {
+        let (vertices, indices) = cube();
+        let vertex_buffer = Buffer::with_vertex(ctx, &vertices, BufferUsage::StaticRead)?;
+        let index_buffer = Buffer::with_index(ctx, &indices, BufferUsage::StaticRead)?;
+        let vertex_layout = VertexLayout::new(
+            ctx,
+            &[
+                BufferAttributes::with_semantics(vertex_buffer, 0, &[Position, Color(0)]),
+                BufferAttributes::with_index(index_buffer, 0),
+            ],
+        )?;
+
+        let side = (count as f32).cbrt().ceil().max(1.0) as usize;
+        let center = Vec3::splat((side - 1) as f32 * SPACING * 0.5);
+        let positions: Vec<Vec3> = (0..count)
+            .map(|index| {
+                let (x, y, z) = (index % side, index / side % side, index / (side * side));
+                vec3(x as f32, y as f32, z as f32) * SPACING - center
+            })
+            .collect();
+
+        let extent = side as f32 * SPACING;
+        let eye = vec3(0.3, 0.4, 1.0).normalize() * extent * 1.5;
+        let view_projection = Camera::perspective(1.0, aspect_ratio, 0.1, extent * 4.0)
+            .with_look_at(eye, Vec3::ZERO, Vec3::Y)
+            .view_projection();
+
+        let vertex_shader = Shader::with_vertex(ctx, VS_SOURCE)?;
+        let fragment_shader = Shader::with_fragment(ctx, FS_SOURCE)?;
+        let program = ShaderProgram::new(ctx, vertex_shader, fragment_shader)?;
+        let material = ctx.create_material(
+            program,
+            &[MaterialProperty::new("view_projection", &view_projection)],
+        )?;
+        ctx.set_render_queue(material, RenderQueue::Transparent);
+
+        Ok(Self {
+            mesh: Mesh::new(vertex_layout, indices.len() as u32, Primitive::Triangles),
+            material,
+            positions,
+            eye,
+        })
+    }
+
+    /// Draws every cube, rotated by the seconds since the start.
+    pub fn draw<C: Context>(&self, ctx: &mut Renderer<C>, time: f32)
+    where
+        Renderer<C>: Backend,
+    {
+        let rotation = Quat::from_rotation_y(time) * Quat::from_rotation_x(time * 0.7);
+        for position in &self.positions {
+            let model = Mat4::from_rotation_translation(rotation, *position);
+            ctx.draw_at_depth(
+                self.mesh,
+                self.material,
+                position.distance(self.eye),
+                &[MaterialProperty::new("model", &model)],
+            );
+        }
+    }
+}
+
+/// Vertices with position and color, and the indices of a unit cube with a color per face.
+fn cube() -> (Vec<f32>, Vec<u8>) {
+    let faces = [
+        (Vec3::X, vec4(0.9, 0.3, 0.3, 1.0)),
+        (Vec3::NEG_X, vec4(0.5, 0.1, 0.1, 1.0)),
+        (Vec3::Y, vec4(0.3, 0.9, 0.3, 1.0)),
+        (Vec3::NEG_Y, vec4(0.1, 0.5, 0.1, 1.0)),
+        (Vec3::Z, vec4(0.3, 0.3, 0.9, 1.0)),
+        (Vec3::NEG_Z, vec4(0.1, 0.1, 0.5, 1.0)),
+    ];
+
+    let mut vertices = Vec::with_capacity(faces.len() * 4 * 7);
+    let mut indices = Vec::with_capacity(faces.len() * 6);
+    for (face, (normal, color)) in faces.into_iter().enumerate() {
+        // u x v = normal, so the corners are counter-clockwise seen from the outside
+        let u = normal.any_orthonormal_vector();
+        let v = normal.cross(u);
+        for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+            let position = (normal + u * x + v * y) * 0.5;
+            vertices.extend_from_slice(&position.to_array());
+            vertices.extend_from_slice(&color.to_array());
+        }
+
+        let first = face as u8 * 4;
+        indices.extend_from_slice(&[first, first + 1, first + 2, first + 2, first + 3, first]);
+    }
+
+    (vertices, indices)
+}
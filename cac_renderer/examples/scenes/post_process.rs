@@ -0,0 +1,96 @@
+//! Fullscreen pass that draws a texture to the screen with a grayscale and vignette effect.
+//! Shared by the examples and `tests/examples.rs`, which don't use all of it.
+#![allow(dead_code)]
+
+use cac_renderer::{
+    AttributeSemantic::Position, Backend, Buffer, BufferAttributes, BufferUsage, Context, Handle,
+    Material, MaterialProperty, Mesh, Primitive, RenderQueue, Renderer, RendererError, Shader,
+    ShaderProgram, Texture, VertexLayout,
+};
+
+const VS_SOURCE: &str = r##"
+    #version 330 core
+    layout(location = 0) in vec3 pos;
+
+    out vec2 frag_uv;
+    void main()
+    {
+        frag_uv = pos.xy * 0.5 + 0.5;
+        gl_Position = vec4(pos, 1.0);
+    }"##;
+
+const FS_SOURCE: &str = r##"
+    #version 330 core
+    in vec2 frag_uv;
+    out vec4 result;
+
+    uniform sampler2D source;
+    uniform float strength;
+
+    void main()
+    {
+        vec3 color = texture(source, frag_uv).rgb;
+        vec3 gray = vec3(dot(color, vec3(0.2126, 0.7152, 0.0722)));
+        float vignette = 1.0 - dot(frag_uv - 0.5, frag_uv - 0.5) * 1.5;
+        result = vec4(mix(color, gray, strength) * vignette, 1.0);
+    }"##;
+
+/// A single triangle that covers the whole screen.
+#[rustfmt::skip]
+const VERTICES: [f32; 9] = [
+    -1.0, -1.0, 0.0,
+     3.0, -1.0, 0.0,
+    -1.0,  3.0, 0.0,
+];
+
+pub struct PostProcess {
+    pub mesh: Mesh,
+    pub material: Handle<Material>,
+}
+
+impl PostProcess {
+    pub fn new<C: Context>(
+        ctx: &mut Renderer<C>,
+        source: Handle<Texture>,
+    ) -> Result<Self, RendererError> {
+        let vertex_buffer = Buffer::with_vertex(ctx, &VERTICES, BufferUsage::StaticRead)?;
+        let vertex_layout = VertexLayout::new(
+            ctx,
+            &[BufferAttributes::with_semantics(
+                vertex_buffer,
+                0,
+                &[Position],
+            )],
+        )?;
+
+        let vertex_shader = Shader::with_vertex(ctx, VS_SOURCE)?;
+        let fragment_shader = Shader::with_fragment(ctx, FS_SOURCE)?;
+        let program = ShaderProgram::new(ctx, vertex_shader, fragment_shader)?;
+        let material = ctx.create_material(
+            program,
+            &[
+                MaterialProperty::new("source", &source),
+                MaterialProperty::new("strength", &1.0_f32),
+            ],
+        )?;
+        // on top of whatever else is drawn to the screen
+        ctx.set_render_queue(material, RenderQueue::Overlay);
+
+        Ok(Self {
+            mesh: Mesh::new(vertex_layout, 3, Primitive::Triangles),
+            material,
+        })
+    }
+
+    /// Draws the effect, blending from the original colors at strength 0 to grayscale at 1.
+    pub fn draw<C: Context>(&self, ctx: &mut Renderer<C>, strength: f32)
+    where
+        Renderer<C>: Backend,
+    {
+        ctx.draw(
+            self.mesh,
+            self.material,
+            &[MaterialProperty::new("strength", &strength)],
+        );
+    }
+}
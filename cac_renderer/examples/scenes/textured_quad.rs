@@ -0,0 +1,121 @@
+//! A quad showing a texture, e.g. a checkerboard or what was rendered into a texture target.
+//! Shared by the examples and `tests/examples.rs`, which don't use all of it.
+#![allow(dead_code)]
+
+use cac_renderer::{
+    math::*,
+    AttributeSemantic::{Position, UV},
+    Backend, Buffer, BufferAttributes, BufferUsage, Context, Handle, Material, MaterialProperty,
+    Mesh, Primitive, Renderer, RendererError, Sampler, Shader, ShaderProgram, Texture,
+    VertexLayout,
+};
+
+const VS_SOURCE: &str = r##"
+    #version 330 core
+    layout(location = 0) in vec3 pos;
+    layout(location = 1) in vec2 uv;
+
+    uniform mat4 model;
+
+    out vec2 frag_uv;
+    void main()
+    {
+        frag_uv = uv;
+        gl_Position = model * vec4(pos, 1.0);
+    }"##;
+
+const FS_SOURCE: &str = r##"
+    #version 330 core
+    in vec2 frag_uv;
+    out vec4 result;
+
+    uniform sampler2D albedo;
+
+    void main()
+    {
+        result = texture(albedo, frag_uv);
+    }"##;
+
+#[rustfmt::skip]
+const VERTICES: [f32; 20] = [
+    // position       uv
+    -0.5, -0.5, 0.0,  0.0, 0.0,
+    -0.5,  0.5, 0.0,  0.0, 1.0,
+     0.5,  0.5, 0.0,  1.0, 1.0,
+     0.5, -0.5, 0.0,  1.0, 0.0,
+];
+
+const INDICES: [u8; 6] = [0, 1, 2, 2, 3, 0];
+
+pub struct TexturedQuad {
+    pub mesh: Mesh,
+    pub material: Handle<Material>,
+}
+
+impl TexturedQuad {
+    /// Without a sampler, the texture is sampled with its own filtering.
+    pub fn new<C: Context>(
+        ctx: &mut Renderer<C>,
+        texture: Handle<Texture>,
+        sampler: Option<Handle<Sampler>>,
+    ) -> Result<Self, RendererError> {
+        let vertex_buffer = Buffer::with_vertex(ctx, &VERTICES, BufferUsage::StaticRead)?;
+        let index_buffer = Buffer::with_index(ctx, &INDICES, BufferUsage::StaticRead)?;
+        let vertex_layout = VertexLayout::new(
+            ctx,
+            &[
+                BufferAttributes::with_semantics(vertex_buffer, 0, &[Position, UV(0)]),
+                BufferAttributes::with_index(index_buffer, 0),
+            ],
+        )?;
+
+        let vertex_shader = Shader::with_vertex(ctx, VS_SOURCE)?;
+        let fragment_shader = Shader::with_fragment(ctx, FS_SOURCE)?;
+        let program = ShaderProgram::new(ctx, vertex_shader, fragment_shader)?;
+
+        let material = match sampler {
+            Some(sampler) => ctx.create_material(
+                program,
+                &[MaterialProperty::new("albedo", &(texture, sampler))],
+            )?,
+            None => ctx.create_material(program, &[MaterialProperty::new("albedo", &texture)])?,
+        };
+
+        Ok(Self {
+            mesh: Mesh::new(vertex_layout, INDICES.len() as u32, Primitive::Triangles),
+            material,
+        })
+    }
+
+    /// Draws the quad scaled and rotated by the angle around the view direction.
+    pub fn draw<C: Context>(&self, ctx: &mut Renderer<C>, scale: f32, angle: f32)
+    where
+        Renderer<C>: Backend,
+    {
+        let model = Mat4::from_scale_rotation_translation(
+            Vec3::splat(scale),
+            Quat::from_rotation_z(angle),
+            Vec3::ZERO,
+        );
+        ctx.draw(
+            self.mesh,
+            self.material,
+            &[MaterialProperty::new("model", &model)],
+        );
+    }
+}
+
+/// RGBA8 texels of a size * size checkerboard with cells * cells squares.
+pub fn checkerboard(size: u32, cells: u32) -> Vec<u8> {
+    let cell_size = (size / cells).max(1);
+    (0..size * size)
+        .flat_map(|index| {
+            let (x, y) = (index % size / cell_size, index / size / cell_size);
+            if (x + y) % 2 == 0 {
+                [230, 230, 230, 255]
+            } else {
+                [40, 40, 60, 255]
+            }
+        })
+        .collect()
+}
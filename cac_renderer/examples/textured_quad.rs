@@ -0,0 +1,29 @@
+//! A rotating quad with a checkerboard texture, sampled without filtering.
+mod common;
+#[path = "scenes/textured_quad.rs"]
+mod textured_quad;
+
+use cac_renderer::{Backend, Color32, Sampler, SamplerDescription, Texture, TextureFormat};
+use textured_quad::{checkerboard, TexturedQuad};
+
+fn main() -> anyhow::Result<()> {
+    common::run(
+        "textured_quad",
+        "Textured Quad",
+        |ctx| {
+            ctx.screen_target()
+                .set_clear_color(Color32::DARK_JUNGLE_GREEN);
+
+            let texels = checkerboard(256, 8);
+            let texture = Texture::new(ctx, &texels, 256, 256, TextureFormat::Rgba8)?;
+            // the texture has no mip levels, so the sampler must not use them
+            let sampler = Sampler::new(ctx, &SamplerDescription::nearest())?;
+
+            Ok(TexturedQuad::new(ctx, texture, Some(sampler))?)
+        },
+        |quad, ctx, time| {
+            quad.draw(ctx, 1.2, time * 0.5);
+            ctx.update();
+        },
+    )
+}
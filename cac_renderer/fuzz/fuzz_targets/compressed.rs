@@ -0,0 +1,14 @@
+#![no_main]
+//! Reads arbitrary bytes as a KTX2 or DDS file and uploads it to the headless backend.
+
+use cac_renderer::{compressed::CompressedImage, Renderer, Texture};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(image) = CompressedImage::from_bytes(data.to_vec()) else {
+        return;
+    };
+    // the loader only accepts images that are valid textures
+    let mut ctx = Renderer::new_headless().unwrap();
+    Texture::from_compressed_image(&mut ctx, &image).unwrap();
+});
@@ -0,0 +1,19 @@
+#![no_main]
+//! Loads arbitrary bytes as a .gltf or .glb file with the headless backend, and adds the loaded
+//! nodes to a scene.
+
+use cac_renderer::{gltf::GltfScene, scene::Scene, Renderer, ShaderProgram};
+use libfuzzer_sys::fuzz_target;
+
+const VS_SOURCE: &str = "layout(location = 0) in vec3 pos;\nvoid main() {}";
+const FS_SOURCE: &str =
+    "uniform vec4 base_color_factor;\nuniform sampler2D base_color_texture;\nvoid main() {}";
+
+fuzz_target!(|data: &[u8]| {
+    let mut renderer = Renderer::new_headless().unwrap();
+    let program = ShaderProgram::from_sources(&mut renderer, VS_SOURCE, FS_SOURCE).unwrap();
+    let Ok(gltf) = GltfScene::from_slice(&mut renderer, data, program) else {
+        return;
+    };
+    gltf.add_to(&mut Scene::default());
+});
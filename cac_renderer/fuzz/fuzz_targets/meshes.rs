@@ -0,0 +1,108 @@
+#![no_main]
+//! Builds a vertex layout from arbitrary buffers and draws arbitrary ranges of it with the headless
+//! backend, including draws of a layout that was removed in the meantime.
+
+use cac_renderer::{
+    AttributeSemantic, Backend, Buffer, BufferAttributes, BufferUsage, Mesh, Primitive, Renderer,
+    ShaderProgram, VertexLayout,
+};
+use libfuzzer_sys::{arbitrary::Arbitrary, fuzz_target};
+
+const VS_SOURCE: &str = "layout(location = 0) in vec3 pos;\nvoid main() {}";
+const FS_SOURCE: &str = "uniform vec4 color;\nvoid main() {}";
+
+#[derive(Arbitrary, Debug)]
+enum Semantic {
+    Position,
+    UV(u8),
+    Color(u8),
+    Normals(u8),
+    Tangent,
+    Weights(u8),
+    Joints(u8),
+}
+
+impl From<&Semantic> for AttributeSemantic {
+    fn from(semantic: &Semantic) -> Self {
+        match *semantic {
+            Semantic::Position => AttributeSemantic::Position,
+            Semantic::UV(n) => AttributeSemantic::UV(n.into()),
+            Semantic::Color(n) => AttributeSemantic::Color(n.into()),
+            Semantic::Normals(n) => AttributeSemantic::Normals(n.into()),
+            Semantic::Tangent => AttributeSemantic::Tangent,
+            Semantic::Weights(n) => AttributeSemantic::Weights(n.into()),
+            Semantic::Joints(n) => AttributeSemantic::Joints(n.into()),
+        }
+    }
+}
+
+#[derive(Arbitrary, Debug)]
+struct Draw {
+    start_index: usize,
+    count: u32,
+    primitive: u8,
+    depth: f32,
+    /// Removes the layout after the draw was recorded.
+    remove_layout: bool,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    vertices: Vec<f32>,
+    indices: Option<Vec<u8>>,
+    semantics: Vec<Semantic>,
+    offset: usize,
+    draws: Vec<Draw>,
+}
+
+fuzz_target!(|input: Input| {
+    let mut renderer = Renderer::new_headless().unwrap();
+
+    let Ok(vertex_buffer) =
+        Buffer::with_vertex(&mut renderer, &input.vertices, BufferUsage::StaticRead)
+    else {
+        return;
+    };
+    let semantics: Vec<AttributeSemantic> = input.semantics.iter().map(Into::into).collect();
+    let mut attributes = vec![BufferAttributes::with_semantics(
+        vertex_buffer,
+        input.offset,
+        &semantics,
+    )];
+    if let Some(indices) = &input.indices {
+        let Ok(index_buffer) = Buffer::with_index(&mut renderer, indices, BufferUsage::StaticRead)
+        else {
+            return;
+        };
+        attributes.push(BufferAttributes::with_index(index_buffer, 0));
+    }
+    let Ok(vertex_layout) = VertexLayout::new(&mut renderer, &attributes) else {
+        return;
+    };
+
+    let program = ShaderProgram::from_sources(&mut renderer, VS_SOURCE, FS_SOURCE).unwrap();
+    let material = renderer.create_material(program, &[]).unwrap();
+
+    for draw in &input.draws {
+        let primitive = match draw.primitive % 6 {
+            0 => Primitive::Triangles,
+            1 => Primitive::TriangleStrip,
+            2 => Primitive::Lines,
+            3 => Primitive::LineStrip,
+            4 => Primitive::Points,
+            _ => Primitive::Patches(u32::from(draw.primitive / 6)),
+        };
+        let mesh = Mesh {
+            vertex_layout,
+            start_index: draw.start_index,
+            count: draw.count,
+            primitive,
+        };
+        renderer.draw_at_depth(mesh, material, draw.depth, &[]);
+
+        if draw.remove_layout {
+            renderer.layouts.remove(vertex_layout);
+        }
+    }
+    renderer.update();
+});
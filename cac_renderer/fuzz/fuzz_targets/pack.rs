@@ -0,0 +1,14 @@
+#![no_main]
+//! Reads arbitrary bytes as a pack file and looks up all of its entries.
+
+use cac_renderer::pack::PackFile;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(pack) = PackFile::from_bytes(data.to_vec()) else {
+        return;
+    };
+    for name in pack.names() {
+        let _ = pack.get(name);
+    }
+});
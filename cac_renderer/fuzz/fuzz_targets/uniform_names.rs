@@ -0,0 +1,26 @@
+#![no_main]
+//! Reflects the uniforms of arbitrary sources and program binaries with the headless backend, and
+//! strips the `[0]` suffix from arbitrary names like the GL reflection does.
+
+use cac_renderer::{fuzzing::reflected_uniform_name, ProgramBinary, Renderer, ShaderProgram};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let name = reflected_uniform_name(data);
+    assert!(name.len() <= data.len() * 3);
+
+    let Ok(source) = std::str::from_utf8(data) else {
+        return;
+    };
+    let mut renderer = Renderer::new_headless().unwrap();
+    let _ = ShaderProgram::from_sources(&mut renderer, source, source);
+
+    // cached binaries are read from disk, so they are just as untrusted
+    let binary = ProgramBinary {
+        format: 0,
+        data: data.to_vec(),
+    };
+    if let Ok(program) = ShaderProgram::from_binary(&mut renderer, &binary) {
+        let _ = renderer.create_material(program, &[]);
+    }
+});
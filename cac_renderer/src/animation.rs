@@ -0,0 +1,357 @@
+//! Minimal skeletal animation.
+//!
+//! A [Skeleton] holds the joint hierarchy and the inverse bind matrices, an [AnimationClip] the
+//! keyframe tracks of the joints. Sampling a clip produces a local [JointTransform] per joint,
+//! which the skeleton turns into the bone matrix palette for the skinning shader:
+//!
+//! ```
+//! # use cac_renderer::{animation::*, math::*};
+//! let skeleton = Skeleton::new(vec![
+//!     Joint::new("root", None, Mat4::IDENTITY),
+//!     Joint::new("arm", Some(0), Mat4::IDENTITY),
+//! ]);
+//!
+//! let mut clip = AnimationClip::default();
+//! clip.add_track(1, JointTrack {
+//!     translation: Some(Track::linear(vec![0.0, 1.0], vec![Vec3::ZERO, Vec3::X])),
+//!     ..Default::default()
+//! });
+//!
+//! let mut pose = skeleton.bind_pose();
+//! clip.sample(0.5, &mut pose);
+//!
+//! let mut palette = Vec::new();
+//! skeleton.palette(&pose, &mut palette);
+//! assert_eq!(palette[1].w_axis.x, 0.5);
+//! ```
+use crate::math::{Mat4, Quat, Vec3};
+
+/// Local transform of a joint, relative to its parent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JointTransform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for JointTransform {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+        }
+    }
+}
+
+impl JointTransform {
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+/// A single joint of a [Skeleton].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Joint {
+    pub name: String,
+    /// Index of the parent joint. Parents always come before their children.
+    pub parent: Option<usize>,
+    /// Transforms from model space into the joint's space at bind time.
+    pub inverse_bind: Mat4,
+    /// Local transform at bind time, used for joints that aren't animated.
+    pub bind_transform: JointTransform,
+}
+
+impl Joint {
+    pub fn new(name: &str, parent: Option<usize>, inverse_bind: Mat4) -> Self {
+        Self {
+            name: name.to_string(),
+            parent,
+            inverse_bind,
+            bind_transform: JointTransform::default(),
+        }
+    }
+}
+
+/// Joint hierarchy of a skinned mesh.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Skeleton {
+    joints: Vec<Joint>,
+}
+
+impl Skeleton {
+    /// The joints need to be sorted so that parents come before their children, which is the
+    /// case for a depth-first traversal of the hierarchy.
+    /// Joints that violate this are treated as roots.
+    pub fn new(mut joints: Vec<Joint>) -> Self {
+        for (index, joint) in joints.iter_mut().enumerate() {
+            if matches!(joint.parent, Some(parent) if parent >= index) {
+                log::warn!("Parent of joint {} comes after it", joint.name);
+                joint.parent = None;
+            }
+        }
+
+        Self { joints }
+    }
+
+    pub fn joints(&self) -> &[Joint] {
+        &self.joints
+    }
+
+    /// Index of the joint with the name, if there is one.
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.joints.iter().position(|joint| joint.name == name)
+    }
+
+    /// The local transforms at bind time, as the starting point for sampling clips.
+    pub fn bind_pose(&self) -> Vec<JointTransform> {
+        self.joints
+            .iter()
+            .map(|joint| joint.bind_transform)
+            .collect()
+    }
+
+    /// Fills the palette with one skinning matrix per joint for the pose.
+    /// The palette is reused, so it doesn't need to allocate every frame.
+    pub fn palette(&self, pose: &[JointTransform], palette: &mut Vec<Mat4>) {
+        palette.clear();
+
+        // first the model space transform of every joint...
+        for (index, joint) in self.joints.iter().enumerate() {
+            let local = pose.get(index).unwrap_or(&joint.bind_transform).matrix();
+
+            let global = match joint.parent {
+                Some(parent) => palette[parent] * local,
+                None => local,
+            };
+            palette.push(global);
+        }
+
+        // ...then relative to the bind pose
+        for (matrix, joint) in palette.iter_mut().zip(&self.joints) {
+            *matrix *= joint.inverse_bind;
+        }
+    }
+}
+
+/// How the values between two keyframes are calculated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    /// Keeps the value of the previous keyframe.
+    Step,
+    /// Lerps vectors and slerps rotations.
+    #[default]
+    Linear,
+}
+
+/// Values that can be interpolated between keyframes.
+pub trait Interpolate: Copy {
+    fn interpolate(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for Vec3 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self.lerp(*other, t)
+    }
+}
+
+impl Interpolate for Quat {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self.slerp(*other, t)
+    }
+}
+
+/// Keyframes of a single value. Times are in seconds and need to be ascending.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track<T> {
+    pub times: Vec<f32>,
+    pub values: Vec<T>,
+    pub interpolation: Interpolation,
+}
+
+impl<T: Interpolate> Track<T> {
+    pub fn linear(times: Vec<f32>, values: Vec<T>) -> Self {
+        Self {
+            times,
+            values,
+            interpolation: Interpolation::Linear,
+        }
+    }
+
+    pub fn step(times: Vec<f32>, values: Vec<T>) -> Self {
+        Self {
+            times,
+            values,
+            interpolation: Interpolation::Step,
+        }
+    }
+
+    /// Time of the last keyframe.
+    pub fn duration(&self) -> f32 {
+        self.times.last().copied().unwrap_or(0.0)
+    }
+
+    /// Samples the value at the time. Times outside of the keyframes are clamped.
+    /// Returns None if the track has no keyframes.
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let len = self.times.len().min(self.values.len());
+        if len == 0 {
+            return None;
+        }
+
+        // index of the first keyframe after the time
+        let next = self.times[..len].partition_point(|keyframe| *keyframe <= time);
+        if next == 0 {
+            return Some(self.values[0]);
+        }
+        if next == len {
+            return Some(self.values[len - 1]);
+        }
+
+        let previous = next - 1;
+        match self.interpolation {
+            Interpolation::Step => Some(self.values[previous]),
+            Interpolation::Linear => {
+                let (start, end) = (self.times[previous], self.times[next]);
+                let t = (time - start) / (end - start);
+                Some(self.values[previous].interpolate(&self.values[next], t))
+            }
+        }
+    }
+}
+
+/// The tracks animating a single joint. Missing tracks keep the current value of the pose.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JointTrack {
+    pub translation: Option<Track<Vec3>>,
+    pub rotation: Option<Track<Quat>>,
+    pub scale: Option<Track<Vec3>>,
+}
+
+/// Animation of a [Skeleton], e.g. a walk cycle.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnimationClip {
+    tracks: Vec<(usize, JointTrack)>,
+    duration: f32,
+}
+
+impl AnimationClip {
+    /// Adds the tracks for the joint index.
+    pub fn add_track(&mut self, joint: usize, track: JointTrack) {
+        let durations = [
+            track.translation.as_ref().map(Track::duration),
+            track.rotation.as_ref().map(Track::duration),
+            track.scale.as_ref().map(Track::duration),
+        ];
+        self.duration = durations
+            .into_iter()
+            .flatten()
+            .fold(self.duration, f32::max);
+
+        self.tracks.push((joint, track));
+    }
+
+    /// Time of the last keyframe over all tracks.
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    /// Overwrites the animated parts of the pose with the values at the time.
+    pub fn sample(&self, time: f32, pose: &mut [JointTransform]) {
+        for (joint, track) in &self.tracks {
+            if let Some(transform) = pose.get_mut(*joint) {
+                if let Some(translation) = track.translation.as_ref().and_then(|t| t.sample(time)) {
+                    transform.translation = translation;
+                }
+                if let Some(rotation) = track.rotation.as_ref().and_then(|t| t.sample(time)) {
+                    transform.rotation = rotation;
+                }
+                if let Some(scale) = track.scale.as_ref().and_then(|t| t.sample(time)) {
+                    transform.scale = scale;
+                }
+            }
+        }
+    }
+
+    /// Like [sample][Self::sample], but wraps the time around the duration.
+    pub fn sample_looped(&self, time: f32, pose: &mut [JointTransform]) {
+        let time = if self.duration > 0.0 {
+            time.rem_euclid(self.duration)
+        } else {
+            0.0
+        };
+        self.sample(time, pose);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::vec3;
+
+    #[test]
+    fn sample_track() {
+        let track = Track::linear(vec![1.0, 2.0, 4.0], vec![Vec3::ZERO, Vec3::X, Vec3::Y]);
+
+        assert_eq!(track.sample(0.0), Some(Vec3::ZERO));
+        assert_eq!(track.sample(1.5), Some(vec3(0.5, 0.0, 0.0)));
+        assert_eq!(track.sample(3.0), Some(vec3(0.5, 0.5, 0.0)));
+        assert_eq!(track.sample(10.0), Some(Vec3::Y));
+
+        let track = Track::step(track.times, track.values);
+        assert_eq!(track.sample(1.9), Some(Vec3::ZERO));
+        assert_eq!(track.sample(2.0), Some(Vec3::X));
+
+        let empty: Track<Vec3> = Track::linear(Vec::new(), Vec::new());
+        assert_eq!(empty.sample(1.0), None);
+    }
+
+    #[test]
+    fn sample_rotation() {
+        let quarter = Quat::from_rotation_y(std::f32::consts::FRAC_PI_2);
+        let track = Track::linear(vec![0.0, 1.0], vec![Quat::IDENTITY, quarter]);
+
+        let half = track.sample(0.5).unwrap();
+        let expected = Quat::from_rotation_y(std::f32::consts::FRAC_PI_4);
+        assert!(half.angle_between(expected) < 0.0001);
+    }
+
+    #[test]
+    fn hierarchy_palette() {
+        // the arm is bound one unit above the root
+        let arm_bind = Mat4::from_translation(Vec3::Y);
+        let mut arm = Joint::new("arm", Some(0), arm_bind.inverse());
+        arm.bind_transform.translation = Vec3::Y;
+
+        let skeleton = Skeleton::new(vec![Joint::new("root", None, Mat4::IDENTITY), arm]);
+        assert_eq!(skeleton.find("arm"), Some(1));
+
+        // the bind pose doesn't move any vertex
+        let mut pose = skeleton.bind_pose();
+        let mut palette = Vec::new();
+        skeleton.palette(&pose, &mut palette);
+        assert!(palette[1].abs_diff_eq(Mat4::IDENTITY, 0.0001));
+
+        // moving the root moves the arm with it
+        let mut clip = AnimationClip::default();
+        clip.add_track(
+            0,
+            JointTrack {
+                translation: Some(Track::linear(vec![0.0, 2.0], vec![Vec3::ZERO, Vec3::X])),
+                ..Default::default()
+            },
+        );
+        assert_eq!(clip.duration(), 2.0);
+
+        clip.sample_looped(3.0, &mut pose);
+        skeleton.palette(&pose, &mut palette);
+        let vertex = palette[1].transform_point3(vec3(0.0, 1.0, 0.0));
+        assert!(vertex.abs_diff_eq(vec3(0.5, 1.0, 0.0), 0.0001));
+    }
+
+    #[test]
+    fn invalid_parent() {
+        let skeleton = Skeleton::new(vec![Joint::new("root", Some(0), Mat4::IDENTITY)]);
+        assert_eq!(skeleton.joints()[0].parent, None);
+    }
+}
@@ -0,0 +1,205 @@
+//! Cameras with the view and projection matrices of the draws.
+//!
+//! A [Camera] is built from a projection and moved with a look-at view, and its
+//! [uniforms][Camera::uniforms] are passed to the programs, either as overrides of the
+//! `view`, `projection`, `view_projection` and `camera_position` uniforms, or as a `std140`
+//! uniform block:
+//!
+//! ```no_run
+//! # use cac_renderer::{camera::Camera, math::*, Backend, Handle, Material, MaterialProperty, Mesh, Renderer};
+//! # fn frame<C: cac_renderer::Context>(renderer: &mut Renderer<C>, mesh: Mesh, material: Handle<Material>)
+//! # where Renderer<C>: Backend {
+//! let mut camera = Camera::perspective(1.0, 16.0 / 9.0, 0.1, 100.0)
+//!     .with_look_at(vec3(0.0, 2.0, 5.0), Vec3::ZERO, Vec3::Y);
+//! camera.set_viewport(1920, 1080);
+//!
+//! let uniforms = camera.uniforms();
+//! renderer.draw(mesh, material, &uniforms.properties());
+//!
+//! // layout(std140) uniform Camera { mat4 view; mat4 projection; mat4 view_projection; vec4 camera_position; };
+//! let block = renderer.alloc_uniforms(&uniforms);
+//! renderer.draw(mesh, material, &[MaterialProperty::new("Camera", &block)]);
+//! # }
+//! ```
+use crate::{
+    math::{Mat4, Vec3, Vec4},
+    MaterialProperty,
+};
+
+/// The matrices and position of a [Camera], laid out like a `std140` uniform block with the
+/// fields in this order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct CameraUniforms {
+    pub view: Mat4,
+    pub projection: Mat4,
+    pub view_projection: Mat4,
+    /// Position in world space, w is 1.
+    pub position: Vec4,
+}
+
+impl CameraUniforms {
+    /// Overrides of the `view`, `projection`, `view_projection` and `camera_position` uniforms.
+    /// Materials without some of them skip those.
+    pub fn properties(&self) -> [MaterialProperty<'_>; 4] {
+        [
+            MaterialProperty::new("view", &self.view),
+            MaterialProperty::new("projection", &self.projection),
+            MaterialProperty::new("view_projection", &self.view_projection),
+            MaterialProperty::new("camera_position", &self.position),
+        ]
+    }
+}
+
+/// Where a scene is seen from, see the [module documentation][self].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    /// Transforms world space into view space.
+    pub view: Mat4,
+    /// Transforms view space into OpenGL clip space, with depths from -1 to 1.
+    pub projection: Mat4,
+}
+
+impl Camera {
+    /// Camera at the origin that looks down -Z, with a perspective projection of the vertical
+    /// field of view in radians.
+    pub fn perspective(fov_y: f32, aspect: f32, near: f32, far: f32) -> Self {
+        Self {
+            view: Mat4::IDENTITY,
+            projection: Mat4::perspective_rh_gl(fov_y, aspect, near, far),
+        }
+    }
+
+    /// Camera at the origin that looks down -Z, with an orthographic projection of the box, e.g.
+    /// of the screen in pixels for 2D.
+    pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Self {
+        Self {
+            view: Mat4::IDENTITY,
+            projection: Mat4::orthographic_rh_gl(left, right, bottom, top, near, far),
+        }
+    }
+
+    /// Moves the camera to the eye, looking at the target.
+    pub fn with_look_at(mut self, eye: Vec3, target: Vec3, up: Vec3) -> Self {
+        self.look_at(eye, target, up);
+        self
+    }
+
+    /// Moves the camera to the eye, looking at the target.
+    pub fn look_at(&mut self, eye: Vec3, target: Vec3, up: Vec3) {
+        self.view = Mat4::look_at_rh(eye, target, up);
+    }
+
+    /// Width over height of the projection.
+    pub fn aspect(&self) -> f32 {
+        self.projection.y_axis.y / self.projection.x_axis.x
+    }
+
+    /// Changes the width of the projection to the aspect, width over height, and keeps its
+    /// height, e.g. after the window was resized.
+    pub fn set_aspect(&mut self, aspect: f32) {
+        self.projection.x_axis.x = self.projection.y_axis.y / aspect;
+    }
+
+    /// [Sets the aspect][Self::set_aspect] of a viewport of width * height pixels. Empty
+    /// viewports, like the ones of minimized windows, keep the aspect.
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.set_aspect(width as f32 / height as f32);
+        }
+    }
+
+    /// The matrices and position of the camera for the draws.
+    pub fn uniforms(&self) -> CameraUniforms {
+        CameraUniforms {
+            view: self.view,
+            projection: self.projection,
+            view_projection: self.view_projection(),
+            position: self.position().extend(1.0),
+        }
+    }
+
+    pub fn view_projection(&self) -> Mat4 {
+        self.projection * self.view
+    }
+
+    /// Position of the camera in world space.
+    pub fn position(&self) -> Vec3 {
+        self.view.inverse().w_axis.truncate()
+    }
+
+    /// Cameras at the position that see the faces of a cubemap, in the order +X, -X, +Y, -Y, +Z,
+    /// -Z of [Texture::cubemap][crate::Texture::cubemap], with 90 degree square frustums and the
+    /// up vectors OpenGL samples cubemaps with.
+    pub fn cubemap_faces(position: Vec3, near: f32, far: f32) -> [Camera; 6] {
+        let projection = Mat4::perspective_rh_gl(std::f32::consts::FRAC_PI_2, 1.0, near, far);
+        [
+            (Vec3::X, -Vec3::Y),
+            (-Vec3::X, -Vec3::Y),
+            (Vec3::Y, Vec3::Z),
+            (-Vec3::Y, -Vec3::Z),
+            (Vec3::Z, -Vec3::Y),
+            (-Vec3::Z, -Vec3::Y),
+        ]
+        .map(|(direction, up)| Camera {
+            view: Mat4::look_at_rh(position, position + direction, up),
+            projection,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::{vec3, Vec2};
+
+    #[test]
+    fn cubemap_cameras() {
+        let position = vec3(1.0, 2.0, 3.0);
+        let faces = Camera::cubemap_faces(position, 0.1, 10.0);
+        for (camera, direction) in
+            faces
+                .iter()
+                .zip([Vec3::X, -Vec3::X, Vec3::Y, -Vec3::Y, Vec3::Z, -Vec3::Z])
+        {
+            assert!(camera.position().abs_diff_eq(position, 1e-5));
+            // the center of the face is in the middle of the view
+            let center = camera
+                .view_projection()
+                .project_point3(position + direction);
+            assert!(center.truncate().abs_diff_eq(Vec2::ZERO, 1e-5));
+        }
+    }
+
+    #[test]
+    fn aspect() {
+        let mut camera = Camera::perspective(1.0, 1.0, 0.1, 10.0);
+        camera.set_viewport(1600, 900);
+        let expected = Camera::perspective(1.0, 16.0 / 9.0, 0.1, 10.0);
+        assert!(camera.projection.abs_diff_eq(expected.projection, 1e-6));
+        assert!((camera.aspect() - 16.0 / 9.0).abs() < 1e-6);
+        // a minimized window keeps the aspect
+        camera.set_viewport(0, 0);
+        assert!((camera.aspect() - 16.0 / 9.0).abs() < 1e-6);
+
+        let mut camera = Camera::orthographic(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0);
+        camera.set_aspect(2.0);
+        let expected = Camera::orthographic(-2.0, 2.0, -1.0, 1.0, -1.0, 1.0);
+        assert!(camera.projection.abs_diff_eq(expected.projection, 1e-6));
+    }
+
+    #[test]
+    fn look_at_uniforms() {
+        let eye = vec3(0.0, 2.0, 5.0);
+        let camera =
+            Camera::perspective(1.0, 1.0, 0.1, 10.0).with_look_at(eye, Vec3::ZERO, Vec3::Y);
+        let uniforms = camera.uniforms();
+        assert!(uniforms.position.abs_diff_eq(eye.extend(1.0), 1e-5));
+        assert_eq!(uniforms.view_projection, camera.projection * camera.view);
+        // the target is in the center of the view
+        let center = uniforms.view_projection.project_point3(Vec3::ZERO);
+        assert!(center.truncate().abs_diff_eq(Vec2::ZERO, 1e-5));
+        // three mat4 and a vec4, like the std140 block
+        assert_eq!(std::mem::size_of::<CameraUniforms>(), 3 * 64 + 16);
+    }
+}
@@ -1,7 +1,7 @@
 /// Color struct with 8 bits per channel, ideally to save space compared to the 4x bigger [Color32]
 /// struct
 /// range is 0 - 255
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
 pub struct Color8 {
     /// red component. Range [0 - 255]
     pub r: u8,
@@ -0,0 +1,407 @@
+//! Loads block-compressed textures from KTX2 and DDS files.
+//!
+//! Only 2D textures in one of the [CompressedFormat]s are supported, without supercompression,
+//! array layers or cubemap faces. Whether the driver can sample the format is reported by
+//! [supports_compressed_format][crate::Backend::supports_compressed_format], so unsupported
+//! images can be decoded into RGBA8 textures instead.
+//!
+//! ```no_run
+//! # use cac_renderer::{compressed::CompressedImage, Backend, Context, Renderer, Texture};
+//! # fn load<C: Context>(ctx: &mut Renderer<C>) -> Result<(), cac_renderer::RendererError>
+//! # where
+//! #     Renderer<C>: Backend,
+//! # {
+//! let image = CompressedImage::open("textures/bricks.ktx2")?;
+//! if ctx.supports_compressed_format(image.format) {
+//!     let texture = Texture::from_compressed_image(ctx, &image)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use std::{ops::Range, path::Path};
+
+use crate::{
+    pack::PackFile, renderer::Context, CompressedFormat, Handle, Renderer, RendererError, Texture,
+};
+
+const KTX2_MAGIC: &[u8; 12] = b"\xABKTX 20\xBB\r\n\x1A\n";
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+const DX10: &[u8; 4] = b"DX10";
+
+/// Larger textures don't fit into any driver.
+const MAX_SIZE: u32 = 1 << 15;
+
+/// Compressed image that was read into memory.
+#[derive(Debug, Clone)]
+pub struct CompressedImage {
+    pub format: CompressedFormat,
+    pub width: u32,
+    pub height: u32,
+    bytes: Vec<u8>,
+    levels: Vec<Range<usize>>,
+}
+
+impl CompressedImage {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, RendererError> {
+        let path = path.as_ref();
+        let read_error = |error: String| RendererError::FailedToReadFile {
+            path: path.display().to_string(),
+            error,
+        };
+
+        let bytes = std::fs::read(path).map_err(|error| read_error(error.to_string()))?;
+        Self::read(bytes).map_err(read_error)
+    }
+
+    /// Reads a KTX2 or DDS file, depending on its magic bytes.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, RendererError> {
+        Self::read(bytes).map_err(|error| RendererError::FailedToReadFile {
+            path: "compressed image".to_string(),
+            error,
+        })
+    }
+
+    /// Reads the KTX2 or DDS file stored in the pack.
+    pub fn from_pack(pack: &PackFile, name: &str) -> Result<Self, RendererError> {
+        Self::read(pack.entry(name)?.to_vec()).map_err(|error| RendererError::FailedToReadFile {
+            path: name.to_string(),
+            error,
+        })
+    }
+
+    fn read(bytes: Vec<u8>) -> Result<Self, String> {
+        let (format, width, height, levels) = if bytes.starts_with(KTX2_MAGIC) {
+            read_ktx2(&bytes)?
+        } else if bytes.starts_with(DDS_MAGIC) {
+            read_dds(&bytes)?
+        } else {
+            return Err("Neither a KTX2 nor a DDS file".to_string());
+        };
+
+        Ok(Self {
+            format,
+            width,
+            height,
+            bytes,
+            levels,
+        })
+    }
+
+    /// The mip chain, starting with the width * height level.
+    pub fn levels(&self) -> impl Iterator<Item = &[u8]> {
+        self.levels.iter().map(|range| &self.bytes[range.clone()])
+    }
+}
+
+impl Texture {
+    /// Creates the texture from all levels of the image.
+    pub fn from_compressed_image<C: Context>(
+        ctx: &mut Renderer<C>,
+        image: &CompressedImage,
+    ) -> Result<Handle<Self>, RendererError> {
+        let levels: Vec<&[u8]> = image.levels().collect();
+        Self::compressed(ctx, &levels, image.width, image.height, image.format)
+    }
+}
+
+type Header = (CompressedFormat, u32, u32, Vec<Range<usize>>);
+
+fn read_ktx2(bytes: &[u8]) -> Result<Header, String> {
+    let header = |index: usize| read_u32(bytes, 12 + index * 4).ok_or("Truncated KTX2 header");
+    let vk_format = header(0)?;
+    let (width, height, depth) = (header(2)?, header(3)?, header(4)?);
+    let (layers, faces, level_count) = (header(5)?, header(6)?, header(7)?);
+    let supercompression = header(8)?;
+
+    let format = match vk_format {
+        131..=134 if vk_format % 2 == 1 => CompressedFormat::Bc1,
+        131..=134 => CompressedFormat::Bc1Srgb,
+        135 => CompressedFormat::Bc2,
+        136 => CompressedFormat::Bc2Srgb,
+        137 => CompressedFormat::Bc3,
+        138 => CompressedFormat::Bc3Srgb,
+        139 => CompressedFormat::Bc4,
+        141 => CompressedFormat::Bc5,
+        143 => CompressedFormat::Bc6hUfloat,
+        145 => CompressedFormat::Bc7,
+        146 => CompressedFormat::Bc7Srgb,
+        147 => CompressedFormat::Etc2Rgb8,
+        148 => CompressedFormat::Etc2Srgb8,
+        151 => CompressedFormat::Etc2Rgba8,
+        152 => CompressedFormat::Etc2Srgb8Alpha8,
+        _ => return Err(format!("Unsupported VkFormat {vk_format}")),
+    };
+    if height == 0 || depth > 1 || layers > 1 {
+        return Err("Only 2D textures are supported".to_string());
+    }
+    if faces != 1 {
+        return Err("Cubemaps are not supported".to_string());
+    }
+    if supercompression != 0 {
+        return Err(format!("Unsupported supercompression {supercompression}"));
+    }
+
+    // 0 asks the loader to generate the mip chain, only the first level is stored
+    let level_count = (level_count as usize).max(1);
+    check_shape(width, height, level_count)?;
+
+    // the level index follows the 80 byte header, 3 u64 per level
+    let mut levels = Vec::with_capacity(level_count);
+    for level in 0..level_count {
+        let entry = 80 + level * 24;
+        let (offset, length) = read_u64(bytes, entry)
+            .zip(read_u64(bytes, entry + 8))
+            .ok_or("Truncated KTX2 level index")?;
+        let offset = usize::try_from(offset).map_err(|error| error.to_string())?;
+        let length = usize::try_from(length).map_err(|error| error.to_string())?;
+
+        let range = offset..offset.checked_add(length).ok_or("Invalid KTX2 level")?;
+        check_level(bytes, &range, format, width, height, level)?;
+        levels.push(range);
+    }
+
+    Ok((format, width, height, levels))
+}
+
+fn read_dds(bytes: &[u8]) -> Result<Header, String> {
+    let field = |offset: usize| read_u32(bytes, offset).ok_or("Truncated DDS header");
+    if field(4)? != 124 {
+        return Err("Invalid DDS header size".to_string());
+    }
+    let (height, width) = (field(12)?, field(16)?);
+    let level_count = (field(28)? as usize).max(1);
+    let four_cc = bytes.get(84..88).ok_or("Truncated DDS header")?;
+    const CUBEMAP: u32 = 0x200;
+    if field(112)? & CUBEMAP != 0 {
+        return Err("Cubemaps are not supported".to_string());
+    }
+
+    let (format, mut offset) = if four_cc == DX10 {
+        let dxgi_format = field(128)?;
+        const TEXTURE_2D: u32 = 3;
+        const TEXTURE_CUBE: u32 = 0x4;
+        if field(132)? != TEXTURE_2D || field(136)? & TEXTURE_CUBE != 0 || field(140)? > 1 {
+            return Err("Only 2D textures are supported".to_string());
+        }
+
+        let format = match dxgi_format {
+            71 => CompressedFormat::Bc1,
+            72 => CompressedFormat::Bc1Srgb,
+            74 => CompressedFormat::Bc2,
+            75 => CompressedFormat::Bc2Srgb,
+            77 => CompressedFormat::Bc3,
+            78 => CompressedFormat::Bc3Srgb,
+            80 => CompressedFormat::Bc4,
+            83 => CompressedFormat::Bc5,
+            95 => CompressedFormat::Bc6hUfloat,
+            98 => CompressedFormat::Bc7,
+            99 => CompressedFormat::Bc7Srgb,
+            _ => return Err(format!("Unsupported DXGI format {dxgi_format}")),
+        };
+        (format, 148)
+    } else {
+        let format = match four_cc {
+            b"DXT1" => CompressedFormat::Bc1,
+            b"DXT2" | b"DXT3" => CompressedFormat::Bc2,
+            b"DXT4" | b"DXT5" => CompressedFormat::Bc3,
+            b"ATI1" | b"BC4U" => CompressedFormat::Bc4,
+            b"ATI2" | b"BC5U" => CompressedFormat::Bc5,
+            _ => {
+                return Err(format!(
+                    "Unsupported FourCC {}",
+                    String::from_utf8_lossy(four_cc)
+                ))
+            }
+        };
+        (format, 128)
+    };
+
+    if width == 0 || height == 0 {
+        return Err("Only 2D textures are supported".to_string());
+    }
+    check_shape(width, height, level_count)?;
+
+    // the levels follow each other without padding
+    let mut levels = Vec::with_capacity(level_count);
+    for level in 0..level_count {
+        let size = format.image_size((width >> level).max(1), (height >> level).max(1));
+        let range = offset..offset + size;
+        check_level(bytes, &range, format, width, height, level)?;
+        offset = range.end;
+        levels.push(range);
+    }
+
+    Ok((format, width, height, levels))
+}
+
+fn check_shape(width: u32, height: u32, level_count: usize) -> Result<(), String> {
+    if width > MAX_SIZE || height > MAX_SIZE {
+        return Err(format!(
+            "{width}x{height} is larger than any driver supports"
+        ));
+    }
+
+    let max_levels = 32 - width.max(height).leading_zeros() as usize;
+    if level_count > max_levels {
+        return Err(format!(
+            "{width}x{height} has at most {max_levels} levels, got {level_count}"
+        ));
+    }
+    Ok(())
+}
+
+fn check_level(
+    bytes: &[u8],
+    range: &Range<usize>,
+    format: CompressedFormat,
+    width: u32,
+    height: u32,
+    level: usize,
+) -> Result<(), String> {
+    let size = format.image_size((width >> level).max(1), (height >> level).max(1));
+    if range.len() != size {
+        return Err(format!(
+            "Level {level} has {} bytes, but {format:?} needs {size}",
+            range.len()
+        ));
+    }
+    if range.end > bytes.len() {
+        return Err(format!("Level {level} is out of bounds"));
+    }
+    Ok(())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset.checked_add(4)?)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        bytes.get(offset..offset.checked_add(8)?)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// 8x8 image with a 4x4 and a 2x2 level.
+    fn ktx2(vk_format: u32, levels: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = KTX2_MAGIC.to_vec();
+        for value in [vk_format, 1, 8, 8, 0, 0, 1, levels.len() as u32, 0] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        // no data format descriptor, key/values or supercompression data
+        bytes.resize(80, 0);
+
+        let mut offset = (80 + levels.len() * 24) as u64;
+        for level in levels {
+            for value in [offset, level.len() as u64, level.len() as u64] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            offset += level.len() as u64;
+        }
+        for level in levels {
+            bytes.extend_from_slice(level);
+        }
+        bytes
+    }
+
+    fn dds(four_cc: &[u8; 4], level_count: u32, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0; 128];
+        bytes[..4].copy_from_slice(DDS_MAGIC);
+        bytes[4..8].copy_from_slice(&124_u32.to_le_bytes());
+        bytes[12..16].copy_from_slice(&8_u32.to_le_bytes());
+        bytes[16..20].copy_from_slice(&8_u32.to_le_bytes());
+        bytes[28..32].copy_from_slice(&level_count.to_le_bytes());
+        bytes[84..88].copy_from_slice(four_cc);
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn ktx2_levels() {
+        let (level_0, level_1, level_2) = ([1; 64], [2; 16], [3; 16]);
+        let image = CompressedImage::from_bytes(ktx2(145, &[&level_0, &level_1, &level_2]));
+        let image = image.unwrap();
+
+        assert_eq!(image.format, CompressedFormat::Bc7);
+        assert_eq!((image.width, image.height), (8, 8));
+        let levels: Vec<_> = image.levels().collect();
+        assert_eq!(levels, [&level_0[..], &level_1, &level_2]);
+
+        let image = CompressedImage::from_bytes(ktx2(131, &[&[0; 32]])).unwrap();
+        assert_eq!(image.format, CompressedFormat::Bc1);
+        assert_eq!(image.levels().count(), 1);
+    }
+
+    #[test]
+    fn ktx2_invalid() {
+        // R8G8B8A8_UNORM isn't compressed
+        assert!(CompressedImage::from_bytes(ktx2(37, &[&[0; 256]])).is_err());
+        // a level that's too small for the format
+        assert!(CompressedImage::from_bytes(ktx2(145, &[&[0; 32]])).is_err());
+
+        let bytes = ktx2(145, &[&[0; 64]]);
+        assert!(CompressedImage::from_bytes(bytes[..bytes.len() - 1].to_vec()).is_err());
+        assert!(CompressedImage::from_bytes(bytes[..40].to_vec()).is_err());
+
+        let mut supercompressed = bytes.clone();
+        supercompressed[44..48].copy_from_slice(&2_u32.to_le_bytes());
+        assert!(CompressedImage::from_bytes(supercompressed).is_err());
+
+        let mut many_levels = bytes;
+        many_levels[40..44].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(CompressedImage::from_bytes(many_levels).is_err());
+    }
+
+    #[test]
+    fn dds_levels() {
+        // 8x8, 4x4, 2x2 and 1x1 levels of 4, 1, 1 and 1 blocks
+        let data: Vec<u8> = (0..7 * 16).map(|index| (index / 16) as u8).collect();
+        let image = CompressedImage::from_bytes(dds(b"DXT5", 4, &data)).unwrap();
+
+        assert_eq!(image.format, CompressedFormat::Bc3);
+        let levels: Vec<_> = image.levels().collect();
+        assert_eq!(levels.len(), 4);
+        assert_eq!(levels[0], &data[..64]);
+        assert_eq!(levels[3], &data[96..]);
+
+        let mut dx10 = dds(DX10, 1, &[]);
+        for value in [99_u32, 3, 0, 1, 0] {
+            dx10.extend_from_slice(&value.to_le_bytes());
+        }
+        dx10.extend_from_slice(&[0; 64]);
+        let image = CompressedImage::from_bytes(dx10).unwrap();
+        assert_eq!(image.format, CompressedFormat::Bc7Srgb);
+    }
+
+    #[test]
+    fn from_pack() {
+        let mut writer = crate::pack::PackWriter::default();
+        writer.add("textures/bricks.dds", &dds(b"DXT1", 1, &[0; 32]));
+        writer.add("textures/broken.dds", &dds(b"DXT1", 1, &[0; 31]));
+        let pack = PackFile::from_bytes(writer.finish().unwrap()).unwrap();
+
+        let image = CompressedImage::from_pack(&pack, "textures/bricks.dds").unwrap();
+        assert_eq!(image.format, CompressedFormat::Bc1);
+        assert!(matches!(
+            CompressedImage::from_pack(&pack, "textures/broken.dds"),
+            Err(RendererError::FailedToReadFile { path, .. }) if path == "textures/broken.dds"
+        ));
+        assert!(matches!(
+            CompressedImage::from_pack(&pack, "textures/missing.dds"),
+            Err(RendererError::ResourceNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn dds_invalid() {
+        assert!(CompressedImage::from_bytes(dds(b"DXT1", 1, &[0; 31])).is_err());
+        assert!(CompressedImage::from_bytes(dds(b"RGBA", 1, &[0; 256])).is_err());
+        assert!(CompressedImage::from_bytes(dds(b"DXT1", 2, &[0; 32])).is_err());
+        assert!(CompressedImage::from_bytes(b"NOTADDS".to_vec()).is_err());
+    }
+}
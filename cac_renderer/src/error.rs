@@ -1,12 +1,15 @@
 use std::error::Error;
 
-use crate::AttributeSemantic;
+use crate::{AttributeSemantic, BackendKind};
 
 #[derive(Debug)]
 /// Possible Errors returned by the Renderer.
 pub enum RendererError {
-    /// The selected target or features don't have any available backends, except the headless renderer.
-    NoAvailableBackend,
+    /// None of the backends of the [RendererBuilder][crate::RendererBuilder] could be created.
+    NoAvailableBackend {
+        /// Why each backend failed, in the order they were tried.
+        failures: Vec<(BackendKind, String)>,
+    },
     /// The backend was unable to create a valid context.
     FailedToCreateContext {
         /// Error Message returned by the backend.
@@ -35,6 +38,51 @@ pub enum RendererError {
     UniformNotFound {
         property: String,
     },
+    FailedToReadFile {
+        path: String,
+        error: String,
+    },
+    FailedToWriteFile {
+        path: String,
+        error: String,
+    },
+    FailedToCreateRenderTarget {
+        error: String,
+    },
+    FailedToCreateTexture {
+        error: String,
+    },
+    FailedToUpdateTexture {
+        error: String,
+    },
+    FailedToUpdateBuffer {
+        error: String,
+    },
+    FailedToReadBuffer {
+        error: String,
+    },
+    /// The backend or its version doesn't support the feature.
+    UnsupportedFeature {
+        feature: String,
+    },
+    /// The value of a material property doesn't fit its uniform, see
+    /// [PropertyValidation][crate::PropertyValidation].
+    PropertyMismatch {
+        property: String,
+        expected: String,
+        got: String,
+    },
+    /// The vertex layout of a mesh doesn't feed all vertex inputs of the program it's drawn with,
+    /// see [VertexLayout::validate][crate::VertexLayout::validate].
+    LayoutMismatch {
+        /// Vertex inputs of the program without a fitting attribute at their location.
+        missing: Vec<String>,
+    },
+    /// A rendered image differs from its reference image, e.g. in the `testing` module.
+    ImageMismatch {
+        name: String,
+        error: String,
+    },
 }
 
 impl Error for RendererError {}
@@ -42,8 +90,12 @@ impl Error for RendererError {}
 impl std::fmt::Display for RendererError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            RendererError::NoAvailableBackend => {
-                write!(f, "No backend available for target platform/features")
+            RendererError::NoAvailableBackend { failures } => {
+                write!(f, "No backend available")?;
+                for (backend, error) in failures {
+                    write!(f, "\n  {backend:?}: {error}")?;
+                }
+                Ok(())
             }
             RendererError::FailedToCreateContext { error } => {
                 write!(f, "Failed to Create Context: {error}")
@@ -67,6 +119,45 @@ impl std::fmt::Display for RendererError {
             RendererError::UniformNotFound { property } => {
                 write!(f, "Couldn't find Uniform for property {property}")
             }
+            RendererError::FailedToReadFile { path, error } => {
+                write!(f, "Failed to read {path}: {error}")
+            }
+            RendererError::FailedToWriteFile { path, error } => {
+                write!(f, "Failed to write {path}: {error}")
+            }
+            RendererError::FailedToCreateRenderTarget { error } => {
+                write!(f, "Failed to create render target: {error}")
+            }
+            RendererError::FailedToCreateTexture { error } => {
+                write!(f, "Failed to create texture: {error}")
+            }
+            RendererError::FailedToUpdateTexture { error } => {
+                write!(f, "Failed to update texture: {error}")
+            }
+            RendererError::FailedToUpdateBuffer { error } => {
+                write!(f, "Failed to update buffer: {error}")
+            }
+            RendererError::FailedToReadBuffer { error } => {
+                write!(f, "Failed to read buffer: {error}")
+            }
+            RendererError::UnsupportedFeature { feature } => {
+                write!(f, "{feature} is not supported by the backend")
+            }
+            RendererError::PropertyMismatch {
+                property,
+                expected,
+                got,
+            } => write!(f, "Property {property} expected {expected}, got {got}"),
+            RendererError::LayoutMismatch { missing } => {
+                write!(
+                    f,
+                    "Vertex layout has no attributes for {}",
+                    missing.join(", ")
+                )
+            }
+            RendererError::ImageMismatch { name, error } => {
+                write!(f, "Image {name} doesn't match its reference: {error}")
+            }
         }
     }
 }
@@ -1,34 +1,98 @@
-use std::marker::PhantomData;
+use std::{
+    cmp::Ordering as CmpOrdering,
+    fmt,
+    hash::Hash,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
-#[derive(Debug, PartialEq, Eq)]
 /// Resource handle that is returned by the [Renderer] whenever a graphics resource, like a mesh,
 /// shader or texture is created. It is similar to a normal Vec, with the difference that it
 /// carries the generation data, in case a resource is released and another take the spot.
+///
+/// The index and generation are packed into a single u64, the index in the upper 32 bits, so
+/// handles order by their index first. [to_bits][Self::to_bits] exposes it for sort keys and
+/// serialization.
 pub struct Handle<T> {
-    pub(crate) index: usize,
-    pub(crate) generation: usize,
+    bits: u64,
     phantom: PhantomData<T>,
 }
 
 impl<T> Clone for Handle<T> {
     fn clone(&self) -> Self {
-        Self {
-            index: self.index,
-            generation: self.generation,
-            phantom: PhantomData,
-        }
+        *self
     }
 }
 impl<T> Copy for Handle<T> {}
 
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits == other.bits
+    }
+}
+impl<T> Eq for Handle<T> {}
+
+impl<T> PartialOrd for Handle<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Handle<T> {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.bits.cmp(&other.bits)
+    }
+}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.bits.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handle")
+            .field("index", &self.index())
+            .field("generation", &self.generation())
+            .finish()
+    }
+}
+
 impl<T> Handle<T> {
     pub fn new() -> Self {
+        Self::from_bits(0)
+    }
+
+    pub(crate) fn from_parts(index: u32, generation: u32) -> Self {
+        Self::from_bits((index as u64) << 32 | generation as u64)
+    }
+
+    /// The packed index and generation. Handles with the same bits refer to the same resource,
+    /// as long as they are of the same type.
+    pub fn to_bits(self) -> u64 {
+        self.bits
+    }
+
+    /// Handle of previously packed [bits][Self::to_bits]. The renderer treats handles that it
+    /// didn't create as invalid.
+    pub fn from_bits(bits: u64) -> Self {
         Self {
-            index: 0,
-            generation: 0,
+            bits,
             phantom: PhantomData,
         }
     }
+
+    pub(crate) fn index(&self) -> usize {
+        (self.bits >> 32) as usize
+    }
+
+    pub(crate) fn generation(&self) -> u32 {
+        self.bits as u32
+    }
 }
 
 impl<T: Copy + Eq> Default for Handle<T> {
@@ -37,26 +101,78 @@ impl<T: Copy + Eq> Default for Handle<T> {
     }
 }
 
+/// Reference counted [Handle], for resources that should be released once nothing refers to them
+/// anymore, instead of releasing them manually. See [share][crate::Renderer::share].
+///
+/// Dropping the last clone doesn't release the resource right away, but the next
+/// [collect_garbage][crate::Renderer::collect_garbage] does, which every
+/// [update][crate::Backend::update] calls. Plain handles to it are invalid afterwards.
+#[derive(Debug)]
+pub struct RcHandle<T> {
+    handle: Handle<T>,
+    count: Arc<AtomicUsize>,
+}
+
+impl<T> RcHandle<T> {
+    pub(crate) fn new(handle: Handle<T>, count: Arc<AtomicUsize>) -> Self {
+        count.fetch_add(1, Ordering::Relaxed);
+        Self { handle, count }
+    }
+
+    /// The handle to pass to the renderer. It is valid as long as a clone of this exists.
+    pub fn handle(&self) -> Handle<T> {
+        self.handle
+    }
+}
+
+impl<T> Clone for RcHandle<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.handle, self.count.clone())
+    }
+}
+
+impl<T> Drop for RcHandle<T> {
+    fn drop(&mut self) {
+        self.count.fetch_sub(1, Ordering::Release);
+    }
+}
+
+impl<T> From<&RcHandle<T>> for Handle<T> {
+    fn from(rc: &RcHandle<T>) -> Self {
+        rc.handle
+    }
+}
+
 #[derive(Debug)]
-struct Resource<R> {
-    value: Option<R>,
-    generation: usize,
+struct Slot {
+    generation: u32,
+    /// Position of the value in the dense storage, None if the slot is free.
+    dense: Option<usize>,
 }
 
+/// Storage for resources that are referred to by [Handle]s.
+///
+/// The handles point to slots that never move, while the values themselves are kept contiguous,
+/// so iterating over them doesn't skip holes. The position of a value in the contiguous storage is
+/// its sort index. It is assigned at creation and only changes when other values are removed or
+/// with [compact][Self::compact], which orders the values by how often they were used.
 #[derive(Debug)]
 pub struct GenerationVec<K, V> {
-    values: Vec<Resource<V>>,
+    slots: Vec<Slot>,
+    values: Vec<V>,
+    /// Slot of each value.
+    owners: Vec<usize>,
+    /// Recorded uses of each value since the last compaction.
+    uses: Vec<u32>,
+    /// Number of [RcHandle]s of each value, None for values that are released manually.
+    ref_counts: Vec<Option<Arc<AtomicUsize>>>,
     free: Vec<usize>,
     phantom: PhantomData<K>,
 }
 
 impl<K, V> Default for GenerationVec<K, V> {
     fn default() -> Self {
-        Self {
-            values: Vec::with_capacity(10),
-            free: Vec::with_capacity(10),
-            phantom: PhantomData,
-        }
+        Self::with_capacity(10)
     }
 }
 
@@ -69,7 +185,11 @@ impl<K, V> GenerationVec<K, V> {
     /// reserves the capacity to avoid reallocation until it is reached
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
+            slots: Vec::with_capacity(capacity),
             values: Vec::with_capacity(capacity),
+            owners: Vec::with_capacity(capacity),
+            uses: Vec::with_capacity(capacity),
+            ref_counts: Vec::with_capacity(capacity),
             free: Vec::with_capacity(capacity),
             phantom: PhantomData,
         }
@@ -77,82 +197,196 @@ impl<K, V> GenerationVec<K, V> {
 
     /// Removes the resource from the GenerationVec and pushes its index into the free list.
     /// The freelist will take the last entry as the index for a new value.
+    ///
+    /// The last value takes the place of the removed one, so its sort index changes.
     pub fn remove(&mut self, handle: Handle<K>) {
-        if let Some(resource) = self.values.get_mut(handle.index) {
-            if resource.generation == handle.generation {
-                resource.value = None;
-                self.free.push(handle.index);
-            }
+        let Some(dense) = self.dense(handle) else {
+            return;
+        };
+
+        self.values.swap_remove(dense);
+        self.owners.swap_remove(dense);
+        self.uses.swap_remove(dense);
+        self.ref_counts.swap_remove(dense);
+        if let Some(moved) = self.owners.get(dense) {
+            self.slots[*moved].dense = Some(dense);
         }
+
+        self.slots[handle.index()].dense = None;
+        self.free.push(handle.index());
     }
 
     /// Returns an immutable reference to the value associated with the handle, or None if there is
     /// none.
     pub fn get(&self, handle: Handle<K>) -> Option<&V> {
-        self.values
-            .get(handle.index)
-            .filter(|r| r.generation == handle.generation)
-            .and_then(|r| r.value.as_ref())
+        self.dense(handle).map(|dense| &self.values[dense])
     }
 
     /// Returns a mutable reference to the value associated with the handle, or None if there is
     /// none.
     pub fn get_mut(&mut self, handle: Handle<K>) -> Option<&mut V> {
-        self.values
-            .get_mut(handle.index)
-            .filter(|r| r.generation == handle.generation)
-            .and_then(|r| r.value.as_mut())
+        self.dense(handle).map(|dense| &mut self.values[dense])
     }
 
     /// Updates the value the handle is refering to, without invalidating existing handles to it.
     /// It shouldn't be used to create entire different values, but rather change the existing one
     /// while keeping the same meaning.
     pub fn update(&mut self, handle: Handle<K>) -> Option<&mut V> {
-        self.values.get_mut(handle.index).and_then(|resource| {
-            if resource.generation == handle.generation {
-                resource.value.as_mut()
-            } else {
-                None
-            }
-        })
+        self.get_mut(handle)
     }
 
     /// Pushes a value into the GenerationVec and returns a handle to it.
     pub fn push(&mut self, value: V) -> Handle<K> {
-        let index = self.free.pop().unwrap_or(self.values.len());
+        let index = self.free.pop().unwrap_or(self.slots.len());
+        let dense = Some(self.values.len());
+        self.values.push(value);
+        self.owners.push(index);
+        self.uses.push(0);
+        self.ref_counts.push(None);
+
+        let generation = match self.slots.get_mut(index) {
+            Some(slot) => {
+                // 0 is the generation of the default handle
+                slot.generation = slot.generation.wrapping_add(1).max(1);
+                slot.dense = dense;
+                slot.generation
+            }
+            None => {
+                self.slots.insert(
+                    index,
+                    Slot {
+                        generation: 1,
+                        dense,
+                    },
+                );
+                1
+            }
+        };
 
-        if let Some(resource) = self.values.get_mut(index) {
-            resource.value = Some(value);
-            resource.generation += 1;
+        let index = u32::try_from(index).expect("more than u32::MAX values");
+        Handle::from_parts(index, generation)
+    }
 
-            Handle::<K> {
-                index,
-                generation: resource.generation,
-                phantom: PhantomData,
-            }
-        } else {
-            let resource = Resource::<V> {
-                value: Some(value),
-                generation: 1,
-            };
-
-            self.values.insert(index, resource);
-
-            Handle::<K> {
-                index,
-                generation: 1,
-                phantom: PhantomData,
+    /// Stores the value in the slot of a removed value, with the handle it had. Returns false and
+    /// drops the value if the slot was taken since, or the handle is from another generation.
+    pub(crate) fn restore(&mut self, handle: Handle<K>, value: V) -> bool {
+        let index = handle.index();
+        match self.slots.get_mut(index) {
+            Some(slot) if slot.dense.is_none() && slot.generation == handle.generation() => {
+                slot.dense = Some(self.values.len());
             }
+            _ => return false,
         }
+
+        self.values.push(value);
+        self.owners.push(index);
+        self.uses.push(0);
+        self.ref_counts.push(None);
+        self.free.retain(|free| *free != index);
+        true
+    }
+
+    /// Iterates over all values that are currently stored, in the order of their sort indices.
+    pub fn iter(&self) -> impl Iterator<Item = &V> {
+        self.values.iter()
+    }
+
+    /// Handles of all values that are currently stored, in the order of their sort indices.
+    pub fn handles(&self) -> impl Iterator<Item = Handle<K>> + '_ {
+        self.owners
+            .iter()
+            .map(|index| Handle::from_parts(*index as u32, self.slots[*index].generation))
+    }
+
+    /// Iterates mutably over all values that are currently stored, in the order of their sort
+    /// indices.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.values.iter_mut()
     }
 
     pub fn clear(&mut self) {
+        self.values.clear();
+        self.owners.clear();
+        self.uses.clear();
+        self.ref_counts.clear();
         self.free.clear();
-        self.values.iter_mut().enumerate().for_each(|(index, v)| {
-            v.value = None;
+        self.slots.iter_mut().enumerate().for_each(|(index, slot)| {
+            slot.dense = None;
             self.free.push(index);
         });
     }
+
+    /// Position of the value in the contiguous storage, or None if the handle is invalid.
+    /// Values with close sort indices are close in memory.
+    pub fn sort_index(&self, handle: Handle<K>) -> Option<usize> {
+        self.dense(handle)
+    }
+
+    /// Counts a use of the value, e.g. a draw with it. See [compact][Self::compact].
+    pub fn record_use(&mut self, handle: Handle<K>) {
+        if let Some(dense) = self.dense(handle) {
+            self.uses[dense] = self.uses[dense].saturating_add(1);
+        }
+    }
+
+    /// Reorders the values by their uses since the last compaction, the most used first, and
+    /// resets the counters. Values with the same number of uses keep their order.
+    /// Handles stay valid, only the sort indices change.
+    pub fn compact(&mut self) {
+        let mut order: Vec<usize> = (0..self.values.len()).collect();
+        order.sort_by_key(|dense| std::cmp::Reverse(self.uses[*dense]));
+
+        let mut values: Vec<Option<V>> = self.values.drain(..).map(Some).collect();
+        let owners = std::mem::take(&mut self.owners);
+        let mut ref_counts = std::mem::take(&mut self.ref_counts);
+        for (dense, previous) in order.into_iter().enumerate() {
+            if let Some(value) = values[previous].take() {
+                self.values.push(value);
+            }
+            self.ref_counts.push(ref_counts[previous].take());
+            self.owners.push(owners[previous]);
+            self.slots[owners[previous]].dense = Some(dense);
+        }
+
+        self.uses.iter_mut().for_each(|uses| *uses = 0);
+    }
+
+    /// The reference count of the value, which is created with the first [RcHandle] of it.
+    /// None if the handle is invalid.
+    pub(crate) fn ref_count(&mut self, handle: Handle<K>) -> Option<Arc<AtomicUsize>> {
+        let dense = self.dense(handle)?;
+        Some(
+            self.ref_counts[dense]
+                .get_or_insert_with(Default::default)
+                .clone(),
+        )
+    }
+
+    /// Removes the values whose [RcHandle]s were all dropped and returns how many.
+    pub fn collect_garbage(&mut self) -> usize {
+        let garbage: Vec<_> = self
+            .handles()
+            .zip(&self.ref_counts)
+            .filter(|(_, count)| {
+                count
+                    .as_ref()
+                    .is_some_and(|count| count.load(Ordering::Acquire) == 0)
+            })
+            .map(|(handle, _)| handle)
+            .collect();
+
+        for handle in &garbage {
+            self.remove(*handle);
+        }
+        garbage.len()
+    }
+
+    fn dense(&self, handle: Handle<K>) -> Option<usize> {
+        self.slots
+            .get(handle.index())
+            .filter(|slot| slot.generation == handle.generation())
+            .and_then(|slot| slot.dense)
+    }
 }
 
 #[cfg(test)]
@@ -172,33 +406,55 @@ mod test {
         gen_vec.clear();
 
         assert_eq!(gen_vec.free.len(), 5);
-        // they still hold None
-        assert_eq!(gen_vec.values.len(), 5);
-
-        let none_count = gen_vec.values.iter().filter(|v| v.value == None).count();
+        // the slots are kept for their generations
+        assert_eq!(gen_vec.slots.len(), 5);
+
+        let none_count = gen_vec
+            .slots
+            .iter()
+            .filter(|slot| slot.dense.is_none())
+            .count();
         assert_eq!(none_count, 5);
+        assert_eq!(gen_vec.iter().count(), 0);
         let next_handle = gen_vec.push(5);
-        assert_eq!(next_handle.generation, 2);
-        assert_eq!(next_handle.index, 4);
+        assert_eq!(next_handle.generation(), 2);
+        assert_eq!(next_handle.index(), 4);
 
         assert_eq!(*gen_vec.get(next_handle).unwrap(), 5);
     }
 
+    #[test]
+    fn restore() {
+        let mut gen_vec: GenerationVec<usize, i32> = GenerationVec::with_capacity(2);
+        let first = gen_vec.push(1);
+        let second = gen_vec.push(2);
+        gen_vec.clear();
+
+        assert!(gen_vec.restore(second, 3));
+        assert_eq!(gen_vec.get(second), Some(&3));
+        // the restored slot isn't handed out again
+        let pushed = gen_vec.push(4);
+        assert_eq!(pushed.index(), first.index());
+        assert!(!gen_vec.restore(first, 5));
+        assert!(!gen_vec.restore(second, 6));
+        assert_eq!(gen_vec.get(second), Some(&3));
+    }
+
     #[test]
     fn insert() {
         let mut gen_vec: GenerationVec<usize, &str> = GenerationVec::with_capacity(2);
         let some_resource = "farty";
         let handle = gen_vec.push(some_resource);
 
-        assert!(handle.index == 0);
-        assert!(handle.generation == 1);
+        assert!(handle.index() == 0);
+        assert!(handle.generation() == 1);
 
         let resource = gen_vec.get(handle);
         assert_eq!(resource.unwrap(), &"farty");
 
         let other_resource = "twart";
         let other_handle = gen_vec.push(other_resource);
-        assert!(other_handle.index == 1);
+        assert!(other_handle.index() == 1);
 
         let resource = gen_vec.get(other_handle).unwrap();
         assert_eq!(resource, &"twart");
@@ -220,8 +476,8 @@ mod test {
         let no_handle = gen_vec.get(handle);
         assert_eq!(no_handle, None);
 
-        assert_eq!(new_handle.generation, 2);
-        assert_eq!(new_handle.index, 0);
+        assert_eq!(new_handle.generation(), 2);
+        assert_eq!(new_handle.index(), 0);
     }
 
     #[test]
@@ -238,4 +494,86 @@ mod test {
         let resource = gen_vec.get(handle).unwrap();
         assert_eq!(resource, &"party");
     }
+
+    #[test]
+    fn contiguous_values() {
+        let mut gen_vec: GenerationVec<usize, &str> = GenerationVec::with_capacity(2);
+        let first = gen_vec.push("first");
+        let second = gen_vec.push("second");
+        let third = gen_vec.push("third");
+
+        // the last value fills the hole
+        gen_vec.remove(first);
+        assert_eq!(
+            gen_vec.iter().copied().collect::<Vec<_>>(),
+            ["third", "second"]
+        );
+        assert_eq!(gen_vec.sort_index(third), Some(0));
+        assert_eq!(gen_vec.sort_index(first), None);
+        assert_eq!(gen_vec.get(second), Some(&"second"));
+        assert_eq!(gen_vec.handles().collect::<Vec<_>>(), [third, second]);
+    }
+
+    #[test]
+    fn handle_bits() {
+        assert_eq!(std::mem::size_of::<Handle<String>>(), 8);
+
+        let mut gen_vec: GenerationVec<usize, &str> = GenerationVec::with_capacity(2);
+        let first = gen_vec.push("first");
+        let second = gen_vec.push("second");
+        assert!(first < second);
+
+        let bits = second.to_bits();
+        assert_eq!(Handle::from_bits(bits), second);
+        assert_eq!(gen_vec.get(Handle::from_bits(bits)), Some(&"second"));
+
+        // a reused slot orders after its previous handles
+        gen_vec.remove(first);
+        let reused = gen_vec.push("reused");
+        assert_eq!(reused.index(), first.index());
+        assert!(first < reused && reused < second);
+    }
+
+    #[test]
+    fn collect_garbage() {
+        let mut gen_vec: GenerationVec<usize, &str> = GenerationVec::with_capacity(2);
+        let manual = gen_vec.push("manual");
+        let counted = gen_vec.push("counted");
+
+        let rc = RcHandle::new(counted, gen_vec.ref_count(counted).unwrap());
+        let clone = rc.clone();
+        drop(rc);
+        assert_eq!(gen_vec.collect_garbage(), 0);
+
+        // values without rc handles are never collected
+        drop(clone);
+        assert_eq!(gen_vec.collect_garbage(), 1);
+        assert_eq!(gen_vec.get(counted), None);
+        assert_eq!(gen_vec.get(manual), Some(&"manual"));
+    }
+
+    #[test]
+    fn compact_by_uses() {
+        let mut gen_vec: GenerationVec<usize, &str> = GenerationVec::with_capacity(2);
+        let rare = gen_vec.push("rare");
+        let unused = gen_vec.push("unused");
+        let frequent = gen_vec.push("frequent");
+
+        gen_vec.record_use(rare);
+        for _ in 0..3 {
+            gen_vec.record_use(frequent);
+        }
+        gen_vec.compact();
+
+        assert_eq!(
+            gen_vec.iter().copied().collect::<Vec<_>>(),
+            ["frequent", "rare", "unused"]
+        );
+        assert_eq!(gen_vec.sort_index(frequent), Some(0));
+        assert_eq!(gen_vec.get(unused), Some(&"unused"));
+
+        // the uses were reset, so without new ones the order stays the same
+        gen_vec.compact();
+        assert_eq!(gen_vec.sort_index(rare), Some(1));
+    }
 }
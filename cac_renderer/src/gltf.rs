@@ -0,0 +1,709 @@
+//! Loads meshes, textures and PBR materials from glTF 2.0 files into renderer resources.
+//!
+//! [GltfScene::open] reads a .gltf or .glb file with its buffers and images, and returns the
+//! meshes and materials it created together with the node hierarchy of the file. The nodes can
+//! be added to a retained [Scene] in one go:
+//!
+//! ```no_run
+//! # use cac_renderer::{gltf::GltfScene, scene::Scene, Context, Handle, Renderer, ShaderProgram};
+//! # fn load<C: Context>(ctx: &mut Renderer<C>, pbr: Handle<ShaderProgram>) -> Result<(), cac_renderer::RendererError> {
+//! let helmet = GltfScene::open(ctx, "models/DamagedHelmet.glb", pbr)?;
+//! let mut scene = Scene::default();
+//! helmet.add_to(&mut scene);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Every material is created with the given program. The parameters of the metallic-roughness
+//! model are set as the properties `base_color_factor`, `metallic_factor`, `roughness_factor`,
+//! `emissive_factor`, `normal_scale`, `occlusion_strength` and `alpha_cutoff`, and the textures
+//! as `base_color_texture`, `metallic_roughness_texture`, `normal_texture`, `occlusion_texture`
+//! and `emissive_texture`. Properties the program has no uniform for are left out, so simple
+//! programs can use a subset of them. Blended materials are drawn in the
+//! [Transparent][RenderQueue::Transparent] queue.
+//!
+//! The vertex attributes are mapped to the [AttributeSemantic]s, e.g. `TEXCOORD_1` to
+//! [UV(1)][AttributeSemantic::UV], and attributes without a location in the
+//! [layout config][Renderer::layout_config] of the renderer are skipped. Tangents keep only
+//! their direction, like [Tangent][AttributeSemantic::Tangent]. Since index buffers hold `u8`
+//! indices, the primitives are expanded into plain vertex lists.
+//!
+//! The images are uploaded with their first row at v = 0, which is how glTF expects them to be
+//! sampled, so the texture coordinates are used as they are.
+use std::{collections::HashMap, path::Path};
+
+use crate::{
+    math::{Mat4, Vec3},
+    renderer::Uniform,
+    scene::{BoundingSphere, Node, Scene},
+    AttributeSemantic, Buffer, BufferAttributes, BufferUsage, Context, Filter, Handle, Material,
+    MaterialProperty, Mesh, MultiMesh, Primitive, PropertyId, PropertyValue, RenderQueue, Renderer,
+    RendererError, Sampler, SamplerDescription, ShaderProgram, Texture, TextureFormat,
+    VertexLayout, WrapMode,
+};
+
+/// The resources created from a glTF file and its node hierarchy.
+#[derive(Debug, Clone, Default)]
+pub struct GltfScene {
+    /// In the order of the meshes of the file.
+    pub meshes: Vec<GltfMesh>,
+    /// In the order of the materials of the file. Primitives without a material use one with
+    /// the default parameters, which isn't part of the list.
+    pub materials: Vec<Handle<Material>>,
+    /// In the order of the nodes of the file.
+    pub nodes: Vec<GltfNode>,
+    /// Indices of the nodes of the default scene, or of the first scene if the file has no
+    /// default. Files without scenes use all nodes without parent.
+    pub roots: Vec<usize>,
+}
+
+/// A glTF mesh, with a submesh for each of its primitives.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GltfMesh {
+    pub name: Option<String>,
+    pub mesh: MultiMesh,
+    /// Around the positions of all primitives.
+    pub bounds: BoundingSphere,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GltfNode {
+    pub name: Option<String>,
+    /// Transform relative to the parent.
+    pub local: Mat4,
+    /// Transform into world space, the local transforms of the node and all its ancestors.
+    pub world: Mat4,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    /// Index into [meshes][GltfScene::meshes].
+    pub mesh: Option<usize>,
+}
+
+impl GltfScene {
+    /// Loads a .gltf file with its external buffers and images, or a .glb file.
+    pub fn open<C: Context>(
+        ctx: &mut Renderer<C>,
+        path: impl AsRef<Path>,
+        program: Handle<ShaderProgram>,
+    ) -> Result<Self, RendererError> {
+        let path = path.as_ref().display().to_string();
+        let (document, buffers, images) =
+            ::gltf::import(&path).map_err(|error| read_error(&path, error))?;
+        Loader::new(ctx, &path, &buffers, &images, program)?.load(&document)
+    }
+
+    /// Loads a .gltf or .glb file from memory. External buffers and images can't be resolved,
+    /// they have to be embedded.
+    pub fn from_slice<C: Context>(
+        ctx: &mut Renderer<C>,
+        bytes: &[u8],
+        program: Handle<ShaderProgram>,
+    ) -> Result<Self, RendererError> {
+        let path = "glTF";
+        let (document, buffers, images) =
+            ::gltf::import_slice(bytes).map_err(|error| read_error(path, error))?;
+        Loader::new(ctx, path, &buffers, &images, program)?.load(&document)
+    }
+
+    /// Adds a [Node] for each submesh of the nodes below the [roots][Self::roots], at the world
+    /// transform of its node and with the bounds of its mesh.
+    pub fn add_to(&self, scene: &mut Scene) -> Vec<Handle<Node>> {
+        let mut added = Vec::new();
+        let mut stack = self.roots.clone();
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            stack.extend(&node.children);
+            if let Some(mesh) = node.mesh.map(|mesh| &self.meshes[mesh]) {
+                for submesh in &mesh.mesh.submeshes {
+                    added.push(
+                        scene.add(
+                            Node::new(submesh.mesh, submesh.material)
+                                .with_transform(node.world)
+                                .with_bounds(mesh.bounds),
+                        ),
+                    );
+                }
+            }
+        }
+        added
+    }
+}
+
+fn read_error(path: &str, error: impl ToString) -> RendererError {
+    RendererError::FailedToReadFile {
+        path: path.to_string(),
+        error: error.to_string(),
+    }
+}
+
+/// Creates the resources of one file, and keeps track of the ones that are shared.
+struct Loader<'a, C: Context> {
+    ctx: &'a mut Renderer<C>,
+    path: &'a str,
+    buffers: &'a [::gltf::buffer::Data],
+    images: &'a [::gltf::image::Data],
+    program: Handle<ShaderProgram>,
+    /// Uniforms of the program, properties without one are left out.
+    uniforms: Vec<String>,
+    /// By texture index and whether it's sRGB encoded.
+    textures: HashMap<(usize, bool), Handle<Texture>>,
+    samplers: HashMap<usize, Handle<Sampler>>,
+    default_material: Option<Handle<Material>>,
+}
+
+impl<'a, C: Context> Loader<'a, C> {
+    fn new(
+        ctx: &'a mut Renderer<C>,
+        path: &'a str,
+        buffers: &'a [::gltf::buffer::Data],
+        images: &'a [::gltf::image::Data],
+        program: Handle<ShaderProgram>,
+    ) -> Result<Self, RendererError> {
+        let uniforms = ctx
+            .programs
+            .get(program)
+            .ok_or_else(|| RendererError::ResourceNotFound {
+                resource: format!("ShaderProgram: {program:?}"),
+            })?
+            .uniforms()
+            .iter()
+            .map(|uniform| uniform.name.clone())
+            .collect();
+
+        Ok(Self {
+            ctx,
+            path,
+            buffers,
+            images,
+            program,
+            uniforms,
+            textures: HashMap::new(),
+            samplers: HashMap::new(),
+            default_material: None,
+        })
+    }
+
+    fn load(mut self, document: &::gltf::Document) -> Result<GltfScene, RendererError> {
+        let materials = document
+            .materials()
+            .map(|material| self.material(&material))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut meshes = Vec::new();
+        for mesh in document.meshes() {
+            let mut multi_mesh = MultiMesh::new();
+            let mut positions = Vec::new();
+            for primitive in mesh.primitives() {
+                let material = match primitive.material().index() {
+                    Some(index) => materials[index],
+                    None => self.default_material(&primitive.material())?,
+                };
+                multi_mesh.push(self.primitive(&primitive, &mut positions)?, material);
+            }
+            meshes.push(GltfMesh {
+                name: mesh.name().map(str::to_string),
+                mesh: multi_mesh,
+                bounds: BoundingSphere::from_positions(&positions),
+            });
+        }
+
+        let mut nodes: Vec<_> = document
+            .nodes()
+            .map(|node| GltfNode {
+                name: node.name().map(str::to_string),
+                local: Mat4::from_cols_array_2d(&node.transform().matrix()),
+                world: Mat4::IDENTITY,
+                parent: None,
+                children: node.children().map(|child| child.index()).collect(),
+                mesh: node.mesh().map(|mesh| mesh.index()),
+            })
+            .collect();
+        for index in 0..nodes.len() {
+            for child in nodes[index].children.clone() {
+                nodes[child].parent = Some(index);
+            }
+        }
+        world_transforms(&mut nodes).map_err(|error| read_error(self.path, error))?;
+
+        let roots = match document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+        {
+            Some(scene) => scene.nodes().map(|node| node.index()).collect(),
+            None => (0..nodes.len())
+                .filter(|index| nodes[*index].parent.is_none())
+                .collect(),
+        };
+
+        Ok(GltfScene {
+            meshes,
+            materials,
+            nodes,
+            roots,
+        })
+    }
+
+    /// Vertex buffer and layout of the primitive, its positions are added to the bounds.
+    fn primitive(
+        &mut self,
+        primitive: &::gltf::Primitive,
+        bounds: &mut Vec<Vec3>,
+    ) -> Result<Mesh, RendererError> {
+        let reader =
+            primitive.reader(|buffer| self.buffers.get(buffer.index()).map(|data| &**data));
+        let positions: Vec<[f32; 3]> = reader
+            .read_positions()
+            .ok_or_else(|| read_error(self.path, "primitive without positions"))?
+            .collect();
+        bounds.extend(positions.iter().copied().map(Vec3::from));
+
+        let mut streams = vec![(AttributeSemantic::Position, positions.concat())];
+        if let Some(normals) = reader.read_normals() {
+            streams.push((AttributeSemantic::Normals(0), normals.flatten().collect()));
+        }
+        if let Some(tangents) = reader.read_tangents() {
+            let tangents = tangents.flat_map(|[x, y, z, _]| [x, y, z]).collect();
+            streams.push((AttributeSemantic::Tangent, tangents));
+        }
+        for set in 0.. {
+            let Some(uvs) = reader.read_tex_coords(set) else {
+                break;
+            };
+            let uvs = uvs.into_f32().flatten().collect();
+            streams.push((AttributeSemantic::UV(set as usize), uvs));
+        }
+        for set in 0.. {
+            let Some(colors) = reader.read_colors(set) else {
+                break;
+            };
+            let colors = colors.into_rgba_f32().flatten().collect();
+            streams.push((AttributeSemantic::Color(set as usize), colors));
+        }
+        for set in 0.. {
+            let Some(joints) = reader.read_joints(set) else {
+                break;
+            };
+            let joints = joints.into_u16().flatten().map(f32::from).collect();
+            streams.push((AttributeSemantic::Joints(set as usize), joints));
+        }
+        for set in 0.. {
+            let Some(weights) = reader.read_weights(set) else {
+                break;
+            };
+            let weights = weights.into_f32().flatten().collect();
+            streams.push((AttributeSemantic::Weights(set as usize), weights));
+        }
+
+        let layout_config = self.ctx.layout_config();
+        streams.retain(|(semantic, _)| {
+            let located = layout_config.location(*semantic).is_some();
+            if !located {
+                log::warn!("{}: skipped {semantic}, it has no location", self.path);
+            }
+            located
+        });
+
+        let vertex_count = streams[0].1.len() / 3;
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..vertex_count as u32).collect(),
+        };
+        let vertices = expand_vertices(&streams, &indices, vertex_count)
+            .map_err(|error| read_error(self.path, error))?;
+
+        let semantics: Vec<_> = streams.iter().map(|(semantic, _)| *semantic).collect();
+        let buffer = Buffer::with_vertex(self.ctx, &vertices, BufferUsage::StaticRead)?;
+        let vertex_layout = VertexLayout::new(
+            self.ctx,
+            &[BufferAttributes::with_semantics(buffer, 0, &semantics)],
+        )?;
+        Ok(Mesh::new(
+            vertex_layout,
+            indices.len() as u32,
+            primitive_kind(primitive.mode()),
+        ))
+    }
+
+    fn material(&mut self, material: &::gltf::Material) -> Result<Handle<Material>, RendererError> {
+        let pbr = material.pbr_metallic_roughness();
+        let base_color = pbr.base_color_factor();
+        let metallic = pbr.metallic_factor();
+        let roughness = pbr.roughness_factor();
+        let emissive = material.emissive_factor();
+        let normal_scale = material
+            .normal_texture()
+            .map_or(1.0, |normal| normal.scale());
+        let occlusion_strength = material
+            .occlusion_texture()
+            .map_or(1.0, |occlusion| occlusion.strength());
+        let alpha_cutoff = material.alpha_cutoff().unwrap_or(0.5);
+
+        let mut properties = vec![
+            MaterialProperty::new("base_color_factor", &base_color),
+            MaterialProperty::new("metallic_factor", &metallic),
+            MaterialProperty::new("roughness_factor", &roughness),
+            MaterialProperty::new("emissive_factor", &emissive),
+            MaterialProperty::new("normal_scale", &normal_scale),
+            MaterialProperty::new("occlusion_strength", &occlusion_strength),
+        ];
+        if material.alpha_mode() == ::gltf::material::AlphaMode::Mask {
+            properties.push(MaterialProperty::new("alpha_cutoff", &alpha_cutoff));
+        }
+
+        let textures = [
+            (
+                "base_color_texture",
+                pbr.base_color_texture().map(|info| info.texture()),
+                true,
+            ),
+            (
+                "metallic_roughness_texture",
+                pbr.metallic_roughness_texture().map(|info| info.texture()),
+                false,
+            ),
+            (
+                "normal_texture",
+                material.normal_texture().map(|normal| normal.texture()),
+                false,
+            ),
+            (
+                "occlusion_texture",
+                material
+                    .occlusion_texture()
+                    .map(|occlusion| occlusion.texture()),
+                false,
+            ),
+            (
+                "emissive_texture",
+                material.emissive_texture().map(|info| info.texture()),
+                true,
+            ),
+        ];
+        for (name, texture, srgb) in textures {
+            if let Some(texture) = texture.filter(|_| self.has_uniform(name)) {
+                properties.push(MaterialProperty {
+                    property: PropertyId::Name(name),
+                    value: PropertyValue::Texture(
+                        self.texture(&texture, srgb)?,
+                        self.sampler(&texture)?,
+                    ),
+                });
+            }
+        }
+
+        properties.retain(|property| match property.property {
+            PropertyId::Name(name) => self.has_uniform(name),
+            PropertyId::Location(_) => true,
+        });
+        let handle = self.ctx.create_material(self.program, &properties)?;
+        if material.alpha_mode() == ::gltf::material::AlphaMode::Blend {
+            self.ctx.set_render_queue(handle, RenderQueue::Transparent);
+        }
+        Ok(handle)
+    }
+
+    fn default_material(
+        &mut self,
+        material: &::gltf::Material,
+    ) -> Result<Handle<Material>, RendererError> {
+        match self.default_material {
+            Some(handle) => Ok(handle),
+            None => {
+                let handle = self.material(material)?;
+                self.default_material = Some(handle);
+                Ok(handle)
+            }
+        }
+    }
+
+    fn has_uniform(&self, name: &str) -> bool {
+        self.uniforms.iter().any(|uniform| uniform == name)
+    }
+
+    /// Mipmapped RGBA8 texture of the image, sRGB encoded for colors.
+    fn texture(
+        &mut self,
+        texture: &::gltf::Texture,
+        srgb: bool,
+    ) -> Result<Handle<Texture>, RendererError> {
+        let key = (texture.index(), srgb);
+        if let Some(handle) = self.textures.get(&key) {
+            return Ok(*handle);
+        }
+
+        let image = self
+            .images
+            .get(texture.source().index())
+            .ok_or_else(|| read_error(self.path, "texture without image"))?;
+        let texels = rgba8(image).map_err(|error| read_error(self.path, error))?;
+        let format = match srgb {
+            true => TextureFormat::Srgb8Alpha8,
+            false => TextureFormat::Rgba8,
+        };
+        let handle = Texture::mipmapped(self.ctx, &texels, image.width, image.height, format)?;
+        self.textures.insert(key, handle);
+        Ok(handle)
+    }
+
+    /// Sampler of the texture, None for the default sampler, which samples the textures with
+    /// their own filtering.
+    fn sampler(
+        &mut self,
+        texture: &::gltf::Texture,
+    ) -> Result<Option<Handle<Sampler>>, RendererError> {
+        use ::gltf::texture::{MagFilter, MinFilter};
+
+        let sampler = texture.sampler();
+        let Some(index) = sampler.index() else {
+            return Ok(None);
+        };
+        if let Some(handle) = self.samplers.get(&index) {
+            return Ok(Some(*handle));
+        }
+
+        let (min_filter, mipmap_filter) = match sampler.min_filter() {
+            Some(MinFilter::Nearest) => (Filter::Nearest, None),
+            Some(MinFilter::Linear) => (Filter::Linear, None),
+            Some(MinFilter::NearestMipmapNearest) => (Filter::Nearest, Some(Filter::Nearest)),
+            Some(MinFilter::LinearMipmapNearest) => (Filter::Linear, Some(Filter::Nearest)),
+            Some(MinFilter::NearestMipmapLinear) => (Filter::Nearest, Some(Filter::Linear)),
+            Some(MinFilter::LinearMipmapLinear) | None => (Filter::Linear, Some(Filter::Linear)),
+        };
+        let description = SamplerDescription {
+            min_filter,
+            mag_filter: match sampler.mag_filter() {
+                Some(MagFilter::Nearest) => Filter::Nearest,
+                Some(MagFilter::Linear) | None => Filter::Linear,
+            },
+            mipmap_filter,
+            wrap_u: wrap_mode(sampler.wrap_s()),
+            wrap_v: wrap_mode(sampler.wrap_t()),
+            ..Default::default()
+        };
+        let handle = Sampler::new(self.ctx, &description)?;
+        self.samplers.insert(index, handle);
+        Ok(Some(handle))
+    }
+}
+
+/// Sets the world transforms of the nodes from their local ones, parents first.
+///
+/// glTF requires the nodes to form disjoint trees. Fails if a node is reached twice, or can't be
+/// reached from a node without parent, which both mean the file has a cycle or shared children.
+fn world_transforms(nodes: &mut [GltfNode]) -> Result<(), String> {
+    let mut visited = vec![false; nodes.len()];
+    let mut stack: Vec<_> = (0..nodes.len())
+        .filter(|index| nodes[*index].parent.is_none())
+        .map(|index| (index, Mat4::IDENTITY))
+        .collect();
+    while let Some((index, parent)) = stack.pop() {
+        if std::mem::replace(&mut visited[index], true) {
+            return Err(format!("node {index} has more than one parent"));
+        }
+        let node = &mut nodes[index];
+        node.world = parent * node.local;
+        let world = node.world;
+        stack.extend(node.children.iter().map(|child| (*child, world)));
+    }
+
+    match visited.iter().position(|visited| !visited) {
+        Some(index) => Err(format!("node {index} is part of a cycle")),
+        None => Ok(()),
+    }
+}
+
+/// Interleaves the attribute streams of the vertices in the order of the indices.
+fn expand_vertices(
+    streams: &[(AttributeSemantic, Vec<f32>)],
+    indices: &[u32],
+    vertex_count: usize,
+) -> Result<Vec<f32>, String> {
+    let vertex_size: usize = streams
+        .iter()
+        .map(|(semantic, _)| semantic.kind().components() as usize)
+        .sum();
+    let mut vertices = Vec::with_capacity(indices.len() * vertex_size);
+    for index in indices {
+        let index = *index as usize;
+        if index >= vertex_count {
+            return Err(format!(
+                "index {index} is out of range for {vertex_count} vertices"
+            ));
+        }
+        for (semantic, values) in streams {
+            let components = semantic.kind().components() as usize;
+            let start = index * components;
+            let value = values
+                .get(start..start + components)
+                .ok_or_else(|| format!("{semantic} has less than {vertex_count} values"))?;
+            vertices.extend_from_slice(value);
+        }
+    }
+    Ok(vertices)
+}
+
+/// Texels of the image as RGBA8, 16 bit channels are cut to their high byte.
+fn rgba8(image: &::gltf::image::Data) -> Result<Vec<u8>, String> {
+    use ::gltf::image::Format;
+
+    let (channels, channel_size) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        format => return Err(format!("{format:?} images aren't supported")),
+    };
+    Ok(image
+        .pixels
+        .chunks_exact(channels * channel_size)
+        .flat_map(|texel| {
+            let mut rgba = [0, 0, 0, 255];
+            for (channel, value) in rgba.iter_mut().take(channels).enumerate() {
+                *value = texel[channel * channel_size + channel_size - 1];
+            }
+            rgba
+        })
+        .collect())
+}
+
+fn primitive_kind(mode: ::gltf::mesh::Mode) -> Primitive {
+    use ::gltf::mesh::Mode;
+
+    match mode {
+        Mode::Points => Primitive::Points,
+        Mode::Lines => Primitive::Lines,
+        Mode::LineLoop => Primitive::LineLoop,
+        Mode::LineStrip => Primitive::LineStrip,
+        Mode::Triangles => Primitive::Triangles,
+        Mode::TriangleStrip => Primitive::TriangleStrip,
+        Mode::TriangleFan => Primitive::TriangleFan,
+    }
+}
+
+fn wrap_mode(mode: ::gltf::texture::WrappingMode) -> WrapMode {
+    use ::gltf::texture::WrappingMode;
+
+    match mode {
+        WrappingMode::ClampToEdge => WrapMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => WrapMode::MirroredRepeat,
+        WrappingMode::Repeat => WrapMode::Repeat,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn expand_indexed_vertices() {
+        let streams = [
+            (
+                AttributeSemantic::Position,
+                vec![0.0, 0.0, 0.0, 1.0, 1.0, 1.0],
+            ),
+            (AttributeSemantic::UV(0), vec![0.0, 0.5, 1.0, 0.5]),
+        ];
+        let vertices = expand_vertices(&streams, &[1, 0, 1], 2).unwrap();
+        assert_eq!(
+            vertices,
+            [
+                1.0, 1.0, 1.0, 1.0, 0.5, //
+                0.0, 0.0, 0.0, 0.0, 0.5, //
+                1.0, 1.0, 1.0, 1.0, 0.5,
+            ]
+        );
+        assert!(expand_vertices(&streams, &[2], 2).is_err());
+
+        let image = ::gltf::image::Data {
+            pixels: vec![10, 20, 30],
+            format: ::gltf::image::Format::R8G8B8,
+            width: 1,
+            height: 1,
+        };
+        assert_eq!(rgba8(&image).unwrap(), [10, 20, 30, 255]);
+    }
+
+    #[test]
+    fn reject_node_cycles() {
+        let node = |parent, children: &[usize]| GltfNode {
+            name: None,
+            local: Mat4::IDENTITY,
+            world: Mat4::IDENTITY,
+            parent,
+            children: children.to_vec(),
+            mesh: None,
+        };
+
+        let mut tree = [node(None, &[1]), node(Some(0), &[])];
+        assert!(world_transforms(&mut tree).is_ok());
+        // a child of two parents is reached twice
+        let mut shared = [node(None, &[2]), node(None, &[2]), node(Some(1), &[])];
+        assert!(world_transforms(&mut shared).is_err());
+        // a cycle below a root reaches its first node again
+        let mut below_root = [node(None, &[1]), node(Some(2), &[2]), node(Some(1), &[1])];
+        assert!(world_transforms(&mut below_root).is_err());
+        // a cycle without any root is never reached
+        let mut unreachable = [node(Some(1), &[1]), node(Some(0), &[0])];
+        assert!(world_transforms(&mut unreachable).is_err());
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn load_nodes_and_meshes() {
+        const TRIANGLE: &str = r#"{
+            "asset": { "version": "2.0" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [
+                { "name": "parent", "translation": [1.0, 0.0, 0.0], "children": [1] },
+                { "name": "child", "translation": [0.0, 2.0, 0.0], "mesh": 0 }
+            ],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1, "material": 0 }] }],
+            "materials": [{ "pbrMetallicRoughness": { "baseColorFactor": [1.0, 0.0, 0.0, 1.0] } }],
+            "buffers": [{
+                "byteLength": 44,
+                "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIAAAA="
+            }],
+            "bufferViews": [
+                { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+                { "buffer": 0, "byteOffset": 36, "byteLength": 6 }
+            ],
+            "accessors": [
+                { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                  "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0] },
+                { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }
+            ]
+        }"#;
+
+        let mut renderer = Renderer::new_headless().unwrap();
+        // only a subset of the material properties, the others are left out
+        let program = ShaderProgram::from_sources(
+            &mut renderer,
+            "#version 330 core\nvoid main() {}",
+            "#version 330 core\nuniform vec4 base_color_factor;\nvoid main() {}",
+        )
+        .unwrap();
+        let gltf = GltfScene::from_slice(&mut renderer, TRIANGLE.as_bytes(), program).unwrap();
+
+        assert_eq!(gltf.roots, [0]);
+        assert_eq!(gltf.nodes[1].parent, Some(0));
+        assert_eq!(
+            gltf.nodes[1].world,
+            Mat4::from_translation(Vec3::new(1.0, 2.0, 0.0))
+        );
+        assert_eq!(gltf.nodes[1].mesh, Some(0));
+
+        let submeshes = &gltf.meshes[0].mesh.submeshes;
+        assert_eq!(submeshes.len(), 1);
+        assert_eq!(submeshes[0].mesh.count, 3);
+        assert_eq!(submeshes[0].mesh.primitive, Primitive::Triangles);
+        assert_eq!(submeshes[0].material, gltf.materials[0]);
+
+        let mut scene = Scene::default();
+        assert_eq!(gltf.add_to(&mut scene).len(), 1);
+    }
+}
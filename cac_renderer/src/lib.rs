@@ -9,14 +9,60 @@ mod error;
 pub use error::RendererError;
 
 mod renderer;
+pub use renderer::fog;
 pub use renderer::{
-    AttributeSemantic, Backend, Buffer, BufferAttributes, BufferData, BufferStorage, BufferUsage,
-    ClearFlags, Material, MaterialProperty, Mesh, Primitive, ProgramStorage, PropertyId,
-    PropertyValue, RenderTarget, Renderer, Shader, ShaderProgram, Texture, VertexAttribute,
-    VertexAttributeKind, VertexLayout,
+    quad_indices, AttributeDescription, AttributeSemantic, Backend, BackendKind, Buffer,
+    BufferAttributes, BufferData, BufferDescription, BufferSlice, BufferStorage, BufferUsage,
+    Capabilities, ClearFlags, CompareFunction, CompressedFormat, ComputeProgram, Context,
+    CreationReport, DebugDraw, DebugNumbers, DebugView, DepthStencilMode, DrawBatch, DynRenderer,
+    EmulatedFeatures, ExternalTarget, Filter, FrameStats, GpuAsset, GpuScope, Image,
+    IndirectCommand, LayerStats, Material, MaterialProperty, MaterialStats, MemoryBarriers, Mesh,
+    MotionBlur, MultiMesh, OverdrawStats, PendingRenderer, PendingUpload, Plot, PlotArea,
+    PlotStyle, Primitive, ProgramBinary, ProgramCache, ProgramStorage, PropertyId,
+    PropertyValidation, PropertyValue, RenderQueue, RenderTarget, Renderer, RendererBuilder,
+    RendererFuture, Resource, ResourceId, ResourceKind, Sampler, SamplerDescription, Shader,
+    ShaderPreprocessor, ShaderProgram, ShaderVariants, Shared, SpriteBatch, SpriteRegion,
+    StreamBuffer, Submesh, Swizzle, SwizzleSource, TestPattern, Texture, TextureData,
+    TextureFormat, TextureKind, TextureRegion, TextureSource, TextureViewDescription,
+    UniformDescription, UniformKind, UniformSlice, UploadBudget, UploadSender, VertexAttribute,
+    VertexAttributeKind, VertexLayout, VertexLayoutConfig, WrapMode, MAX_DEBUG_GLYPHS,
+    MAX_MOTION_BLUR_SAMPLES, PARALLEL_THRESHOLD, SPHERE_SEGMENTS,
 };
+#[cfg(feature = "opengl")]
+pub use renderer::{
+    AutoExposure, AutoExposureSettings, ForeignGl, GpuReduction, LuminanceHistogram,
+    MotionVectorTarget, OpenGLContext, ReductionOp, ReductionResult, CAPTURE_FAR, CAPTURE_NEAR,
+    EXPOSURE_PROPERTY, MAX_HISTOGRAM_BINS,
+};
+#[cfg(all(feature = "opengl", unix))]
+pub use renderer::{ExternalMemory, ExternalSemaphores, SharedTarget};
+#[cfg(feature = "headless")]
+pub use renderer::{HeadlessContext, RecordedCommand};
+
+/// Internals that parse untrusted data, only exposed to the fuzz targets in `fuzz/`.
+#[cfg(fuzzing)]
+pub mod fuzzing {
+    pub use crate::renderer::reflected_uniform_name;
+}
 
 mod generation_vec;
-pub use generation_vec::Handle;
+pub use generation_vec::{Handle, RcHandle};
+
+mod lz4;
 
+pub mod animation;
+pub mod camera;
+#[cfg(feature = "compressed-textures")]
+pub mod compressed;
+#[cfg(feature = "gltf")]
+pub mod gltf;
 pub mod math;
+pub mod pack;
+#[cfg(feature = "png")]
+mod png;
+pub mod probes;
+pub mod quantization;
+pub mod scene;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod transform;
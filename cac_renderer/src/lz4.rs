@@ -0,0 +1,208 @@
+//! Compression in the LZ4 block format, for the CPU copies of retained resources.
+//!
+//! Only single blocks without the frame format are supported. The size of the uncompressed data
+//! isn't part of the block, so it has to be stored next to it.
+
+const MIN_MATCH: usize = 4;
+/// Matches have to start at least 12 bytes before the end of the block.
+const MATCH_LIMIT: usize = 12;
+/// The last 5 bytes are always literals.
+const LAST_LITERALS: usize = 5;
+const MAX_OFFSET: usize = u16::MAX as usize;
+const HASH_LOG: u32 = 12;
+
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+}
+
+fn hash(sequence: u32) -> usize {
+    (sequence.wrapping_mul(2654435761) >> (32 - HASH_LOG)) as usize
+}
+
+/// Appends the part of the length that doesn't fit in the 4 bits of the token.
+fn write_length(out: &mut Vec<u8>, length: usize) {
+    if length < 15 {
+        return;
+    }
+    let mut rest = length - 15;
+    while rest >= 255 {
+        out.push(255);
+        rest -= 255;
+    }
+    out.push(rest as u8);
+}
+
+fn write_sequence(out: &mut Vec<u8>, literals: &[u8], matched: Option<(usize, usize)>) {
+    let match_token = matched.map_or(0, |(_, length)| (length - MIN_MATCH).min(15));
+    out.push((literals.len().min(15) << 4 | match_token) as u8);
+    write_length(out, literals.len());
+    out.extend_from_slice(literals);
+
+    if let Some((offset, length)) = matched {
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        write_length(out, length - MIN_MATCH);
+    }
+}
+
+/// Compresses the data into a single LZ4 block.
+pub(crate) fn compress(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() / 2 + 16);
+    let mut table = vec![usize::MAX; 1 << HASH_LOG];
+    let mut anchor = 0;
+    let mut pos = 0;
+
+    if input.len() > MATCH_LIMIT {
+        let limit = input.len() - MATCH_LIMIT;
+        let match_end = input.len() - LAST_LITERALS;
+        while pos < limit {
+            let sequence = read_u32(input, pos);
+            let slot = &mut table[hash(sequence)];
+            let candidate = std::mem::replace(slot, pos);
+
+            if candidate == usize::MAX
+                || pos - candidate > MAX_OFFSET
+                || read_u32(input, candidate) != sequence
+            {
+                pos += 1;
+                continue;
+            }
+
+            let mut length = MIN_MATCH;
+            while pos + length < match_end && input[candidate + length] == input[pos + length] {
+                length += 1;
+            }
+            write_sequence(
+                &mut out,
+                &input[anchor..pos],
+                Some((pos - candidate, length)),
+            );
+            pos += length;
+            anchor = pos;
+        }
+    }
+
+    write_sequence(&mut out, &input[anchor..], None);
+    out
+}
+
+/// Reads the part of the length that didn't fit in the 4 bits of the token.
+fn read_length(input: &[u8], pos: &mut usize) -> Result<usize, String> {
+    let mut length = 0usize;
+    loop {
+        let byte = *input.get(*pos).ok_or("LZ4 block ends inside a length")?;
+        *pos += 1;
+        length = length
+            .checked_add(byte as usize)
+            .ok_or("LZ4 length overflows")?;
+        if byte != 255 {
+            return Ok(length);
+        }
+    }
+}
+
+/// Decompresses a single LZ4 block into the size bytes it was compressed from.
+pub(crate) fn decompress(input: &[u8], size: usize) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(size);
+    let mut pos = 0;
+
+    loop {
+        let token = *input.get(pos).ok_or("LZ4 block is empty")?;
+        pos += 1;
+
+        let mut literals = (token >> 4) as usize;
+        if literals == 15 {
+            literals += read_length(input, &mut pos)?;
+        }
+        let literals = pos
+            .checked_add(literals)
+            .and_then(|end| input.get(pos..end))
+            .filter(|literals| out.len() + literals.len() <= size)
+            .ok_or("LZ4 literals exceed the block")?;
+        out.extend_from_slice(literals);
+        pos += literals.len();
+
+        if pos == input.len() {
+            break;
+        }
+
+        let offset = input
+            .get(pos..pos + 2)
+            .map(|offset| u16::from_le_bytes([offset[0], offset[1]]) as usize)
+            .ok_or("LZ4 block ends inside an offset")?;
+        pos += 2;
+        if offset == 0 || offset > out.len() {
+            return Err(format!("LZ4 offset {offset} is out of bounds"));
+        }
+
+        let mut length = (token & 15) as usize + MIN_MATCH;
+        if token & 15 == 15 {
+            length += read_length(input, &mut pos)?;
+        }
+        if out.len() + length > size {
+            return Err("LZ4 match exceeds the block".to_string());
+        }
+
+        // the match can overlap the bytes it copies, so they are copied one by one
+        let start = out.len() - offset;
+        for index in start..start + length {
+            out.push(out[index]);
+        }
+    }
+
+    if out.len() != size {
+        return Err(format!(
+            "LZ4 block has {} bytes instead of {size}",
+            out.len()
+        ));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn roundtrip(data: &[u8]) -> Vec<u8> {
+        let compressed = compress(data);
+        assert_eq!(decompress(&compressed, data.len()).unwrap(), data);
+        compressed
+    }
+
+    #[test]
+    fn compresses_repetitions() {
+        let data: Vec<u8> = (0..4096).map(|index| (index % 7) as u8).collect();
+        let compressed = roundtrip(&data);
+        assert!(compressed.len() < data.len() / 10);
+
+        roundtrip(&[0; 100_000]);
+    }
+
+    #[test]
+    fn incompressible() {
+        let mut state = 0x1234_5678u32;
+        let data: Vec<u8> = (0..1000)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect();
+        roundtrip(&data);
+
+        roundtrip(&[]);
+        roundtrip(&[1, 2, 3]);
+        roundtrip(b"abcabcabcabcabc");
+    }
+
+    #[test]
+    fn rejects_corrupt_blocks() {
+        let data = [7; 64];
+        let compressed = compress(&data);
+
+        assert!(decompress(&compressed, 63).is_err());
+        assert!(decompress(&compressed, 65).is_err());
+        assert!(decompress(&compressed[..compressed.len() - 1], 64).is_err());
+        assert!(decompress(&[], 0).is_err());
+        // match before the start of the block
+        assert!(decompress(&[0x00, 0x01, 0x00], 4).is_err());
+    }
+}
@@ -0,0 +1,288 @@
+//! Simple archive format to ship shaders, meshes and other resources in a single file.
+//!
+//! Shaders are loaded with [ShaderProgram::from_pack] and
+//! [ShaderPreprocessor::add_includes_from_pack], KTX2 and DDS textures with
+//! `CompressedImage::from_pack` and other images with `Texture::from_pack`, with the
+//! `compressed-textures` and `image` features.
+//!
+//! All numbers are little endian:
+//!
+//! | Field   | Size         | Description                                       |
+//! |---------|--------------|---------------------------------------------------|
+//! | magic   | 8            | `CACPACK\0`                                       |
+//! | version | 4            | [PACK_VERSION]                                    |
+//! | count   | 4            | number of entries                                 |
+//! | entries | per entry    | u16 name length, UTF-8 name, u64 offset, u64 size |
+//! | data    | sum of sizes | content of the entries, offsets are from the start of the file |
+//!
+//! ```
+//! # use cac_renderer::pack::{PackFile, PackWriter};
+//! let mut writer = PackWriter::default();
+//! writer.add("shaders/common.glsl", b"float saturate(float x) { return clamp(x, 0.0, 1.0); }");
+//! writer.add("meshes/quad.bin", &[0u8; 48]);
+//!
+//! let pack = PackFile::from_bytes(writer.finish().unwrap()).unwrap();
+//! assert_eq!(pack.get("meshes/quad.bin").map(|data| data.len()), Some(48));
+//! ```
+use std::{collections::HashMap, ops::Range, path::Path};
+
+use crate::{
+    renderer::Context, Handle, Renderer, RendererError, ShaderPreprocessor, ShaderProgram,
+};
+
+const MAGIC: &[u8; 8] = b"CACPACK\0";
+pub const PACK_VERSION: u32 = 1;
+
+/// Builds a pack in memory.
+#[derive(Debug, Default, Clone)]
+pub struct PackWriter {
+    entries: Vec<(String, Vec<u8>)>,
+}
+
+impl PackWriter {
+    /// Adds the entry, replacing an existing one with the same name.
+    pub fn add(&mut self, name: &str, data: &[u8]) {
+        match self.entries.iter_mut().find(|(entry, _)| entry == name) {
+            Some((_, previous)) => *previous = data.to_vec(),
+            None => self.entries.push((name.to_string(), data.to_vec())),
+        }
+    }
+
+    /// Returns the bytes of the pack. Fails if a name is longer than 65535 bytes or there are
+    /// more than 2^32 entries, which the format can't store.
+    pub fn finish(&self) -> Result<Vec<u8>, RendererError> {
+        let index_size: usize = self
+            .entries
+            .iter()
+            .map(|(name, _)| 2 + name.len() + 8 + 8)
+            .sum();
+        let data_size: usize = self.entries.iter().map(|(_, data)| data.len()).sum();
+
+        let mut bytes = Vec::with_capacity(16 + index_size + data_size);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&PACK_VERSION.to_le_bytes());
+        let count =
+            u32::try_from(self.entries.len()).map_err(|_| RendererError::ConversionError {
+                error: format!("{} entries don't fit into a pack", self.entries.len()),
+            })?;
+        bytes.extend_from_slice(&count.to_le_bytes());
+
+        let mut offset = (16 + index_size) as u64;
+        for (name, data) in &self.entries {
+            let length = u16::try_from(name.len()).map_err(|_| RendererError::ConversionError {
+                error: format!("The pack entry name {name} is longer than 65535 bytes"),
+            })?;
+            bytes.extend_from_slice(&length.to_le_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(&offset.to_le_bytes());
+            bytes.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            offset += data.len() as u64;
+        }
+
+        for (_, data) in &self.entries {
+            bytes.extend_from_slice(data);
+        }
+
+        Ok(bytes)
+    }
+}
+
+/// A pack that was read into memory. See the [module docs][self] for the format.
+#[derive(Debug, Clone)]
+pub struct PackFile {
+    bytes: Vec<u8>,
+    entries: HashMap<String, Range<usize>>,
+}
+
+impl PackFile {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, RendererError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|error| RendererError::FailedToReadFile {
+            path: path.display().to_string(),
+            error: error.to_string(),
+        })?;
+
+        Self::from_bytes(bytes).map_err(|error| match error {
+            RendererError::FailedToReadFile { error, .. } => RendererError::FailedToReadFile {
+                path: path.display().to_string(),
+                error,
+            },
+            error => error,
+        })
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, RendererError> {
+        let entries = Self::read_index(&bytes).ok_or_else(|| RendererError::FailedToReadFile {
+            path: "pack".to_string(),
+            error: "Invalid or unsupported pack file".to_string(),
+        })?;
+
+        Ok(Self { bytes, entries })
+    }
+
+    fn read_index(bytes: &[u8]) -> Option<HashMap<String, Range<usize>>> {
+        let mut reader = Reader { bytes, position: 0 };
+
+        if reader.take(8)? != MAGIC || reader.u32()? != PACK_VERSION {
+            return None;
+        }
+
+        let count = reader.u32()?;
+        // the count is untrusted, but every entry takes at least 18 bytes of the index
+        let mut entries = HashMap::with_capacity((count as usize).min(bytes.len() / 18));
+        for _ in 0..count {
+            let length = reader.u16()? as usize;
+            let name = std::str::from_utf8(reader.take(length)?).ok()?;
+            let offset = usize::try_from(reader.u64()?).ok()?;
+            let size = usize::try_from(reader.u64()?).ok()?;
+
+            let range = offset..offset.checked_add(size)?;
+            bytes.get(range.clone())?;
+            entries.insert(name.to_string(), range);
+        }
+
+        Some(entries)
+    }
+
+    /// Content of the entry, or None if the pack doesn't contain it.
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.entries
+            .get(name)
+            .map(|range| &self.bytes[range.clone()])
+    }
+
+    /// Content of the entry, or an error if the pack doesn't contain it, for the loaders.
+    pub(crate) fn entry(&self, name: &str) -> Result<&[u8], RendererError> {
+        self.get(name)
+            .ok_or_else(|| RendererError::ResourceNotFound {
+                resource: format!("Pack entry: {name}"),
+            })
+    }
+
+    /// Content of the entry as UTF-8 text, e.g. for shader sources.
+    pub fn get_str(&self, name: &str) -> Result<&str, RendererError> {
+        let bytes = self.entry(name)?;
+
+        std::str::from_utf8(bytes).map_err(|error| RendererError::ConversionError {
+            error: format!("{name} is not UTF-8: {error}"),
+        })
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Option<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position.checked_add(count)?)?;
+        self.position += count;
+        Some(bytes)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+}
+
+impl ShaderPreprocessor {
+    /// Registers all entries with the extension as includes, under their name in the pack.
+    pub fn add_includes_from_pack(&mut self, pack: &PackFile, extension: &str) {
+        for name in pack.names() {
+            if Path::new(name).extension().and_then(|e| e.to_str()) != Some(extension) {
+                continue;
+            }
+
+            match pack.get_str(name) {
+                Ok(source) => self.add_include(name, source),
+                Err(error) => log::warn!("Skipped include {name}: {error}"),
+            }
+        }
+    }
+}
+
+impl ShaderProgram {
+    /// Compiles and links the program from the sources stored in the pack.
+    pub fn from_pack<C: Context>(
+        ctx: &mut Renderer<C>,
+        pack: &PackFile,
+        vertex_shader: &str,
+        fragment_shader: &str,
+    ) -> Result<Handle<Self>, RendererError> {
+        Self::from_sources(
+            ctx,
+            pack.get_str(vertex_shader)?,
+            pack.get_str(fragment_shader)?,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let mut writer = PackWriter::default();
+        writer.add("a.glsl", b"old");
+        writer.add("b.bin", &[1, 2, 3]);
+        writer.add("a.glsl", b"new");
+        writer.add("empty", &[]);
+
+        let pack = PackFile::from_bytes(writer.finish().unwrap()).unwrap();
+        assert_eq!(pack.get("a.glsl"), Some(&b"new"[..]));
+        assert_eq!(pack.get("b.bin"), Some(&[1, 2, 3][..]));
+        assert_eq!(pack.get("empty"), Some(&[][..]));
+        assert_eq!(pack.get("missing"), None);
+        assert_eq!(pack.names().count(), 3);
+    }
+
+    #[test]
+    fn invalid() {
+        let mut writer = PackWriter::default();
+        writer.add("a", &[1, 2, 3]);
+        let bytes = writer.finish().unwrap();
+
+        assert!(PackFile::from_bytes(bytes[..bytes.len() - 1].to_vec()).is_err());
+        assert!(PackFile::from_bytes(b"NOTAPACK".to_vec()).is_err());
+
+        let mut writer = PackWriter::default();
+        writer.add(&"a".repeat(u16::MAX as usize + 1), &[]);
+        assert!(writer.finish().is_err());
+
+        // a huge entry count without the entries doesn't allocate for them
+        let mut header = bytes[..12].to_vec();
+        header.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(PackFile::from_bytes(header).is_err());
+    }
+
+    #[test]
+    fn includes_from_pack() {
+        let mut writer = PackWriter::default();
+        writer.add("common.glsl", b"const float PI = 3.14159;");
+        writer.add("mesh.bin", &[0xff]);
+        let pack = PackFile::from_bytes(writer.finish().unwrap()).unwrap();
+
+        let mut preprocessor = ShaderPreprocessor::default();
+        preprocessor.add_includes_from_pack(&pack, "glsl");
+
+        let source = preprocessor.process("#include \"common.glsl\"").unwrap();
+        assert_eq!(source, "const float PI = 3.14159;\n");
+        assert!(preprocessor.process("#include \"mesh.bin\"").is_err());
+    }
+}
@@ -0,0 +1,256 @@
+//! Minimal PNG encoder for [Image]s, e.g. to attach screenshots to bug reports.
+//!
+//! The pixel data is stored without compression, which keeps the encoder free of dependencies.
+//! The decoder only reads these uncompressed PNGs back, e.g. the reference images of the
+//! `testing` module.
+use std::path::Path;
+
+use crate::{Image, RendererError};
+
+const SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1A\n";
+/// Deflate limits stored blocks to u16::MAX bytes.
+const MAX_BLOCK: usize = u16::MAX as usize;
+
+impl Image {
+    /// Encodes the image as an 8 bit RGBA PNG.
+    pub fn to_png(&self) -> Vec<u8> {
+        // PNG rows start at the top, each with a filter byte
+        let row_size = self.width as usize * 4;
+        let mut scanlines = Vec::with_capacity((row_size + 1) * self.height as usize);
+        if row_size > 0 {
+            for row in self.pixels.chunks_exact(row_size).rev() {
+                scanlines.push(0);
+                scanlines.extend_from_slice(row);
+            }
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&self.width.to_be_bytes());
+        header.extend_from_slice(&self.height.to_be_bytes());
+        // 8 bits per channel, RGBA, deflate, adaptive filters and no interlacing
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        let mut png = SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", &header);
+        write_chunk(&mut png, b"IDAT", &zlib_stored(&scanlines));
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    /// Decodes a PNG written by [to_png][Self::to_png]. Compressed PNGs of other encoders are
+    /// rejected.
+    pub fn from_png(bytes: &[u8]) -> Result<Self, RendererError> {
+        decode(bytes).ok_or_else(|| RendererError::FailedToReadFile {
+            path: "png".to_string(),
+            error: "Not an uncompressed 8 bit RGBA PNG".to_string(),
+        })
+    }
+
+    pub fn open_png(path: impl AsRef<Path>) -> Result<Self, RendererError> {
+        let path = path.as_ref();
+        let read_error = |error: String| RendererError::FailedToReadFile {
+            path: path.display().to_string(),
+            error,
+        };
+
+        let bytes = std::fs::read(path).map_err(|error| read_error(error.to_string()))?;
+        Self::from_png(&bytes).map_err(|error| match error {
+            RendererError::FailedToReadFile { error, .. } => read_error(error),
+            error => error,
+        })
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>) -> Result<(), RendererError> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_png()).map_err(|error| RendererError::FailedToWriteFile {
+            path: path.display().to_string(),
+            error: error.to_string(),
+        })
+    }
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Zlib stream with the data in stored deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let blocks = data.len().div_ceil(MAX_BLOCK).max(1);
+    let mut stream = Vec::with_capacity(2 + blocks * 5 + data.len() + 4);
+    // deflate with a 32K window, no preset dictionary and the check bits for the header
+    stream.extend_from_slice(&[0x78, 0x01]);
+
+    let mut chunks = data.chunks(MAX_BLOCK).peekable();
+    if chunks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(chunk) = chunks.next() {
+        let last = chunks.peek().is_none();
+        let length = chunk.len() as u16;
+        stream.push(u8::from(last));
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(chunk);
+    }
+
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn decode(bytes: &[u8]) -> Option<Image> {
+    let mut chunks = bytes.strip_prefix(SIGNATURE)?;
+    let mut header = None;
+    let mut stream = Vec::new();
+    while !chunks.is_empty() {
+        let length = u32::from_be_bytes(chunks.get(..4)?.try_into().ok()?) as usize;
+        let kind = chunks.get(4..8)?;
+        let data = chunks.get(8..8usize.checked_add(length)?)?;
+        match kind {
+            b"IHDR" => header = Some(data),
+            b"IDAT" => stream.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {}
+        }
+        chunks = chunks.get(12 + length..)?;
+    }
+
+    let header = header?;
+    let width = u32::from_be_bytes(header.get(..4)?.try_into().ok()?);
+    let height = u32::from_be_bytes(header.get(4..8)?.try_into().ok()?);
+    if header.get(8..13)? != [8, 6, 0, 0, 0] {
+        return None;
+    }
+
+    let scanlines = inflate_stored(&stream)?;
+    let row_size = width as usize * 4;
+    if scanlines.len() != (row_size + 1) * height as usize {
+        return None;
+    }
+
+    let mut pixels = Vec::with_capacity(row_size * height as usize);
+    // without pixels, there are no rows to reverse
+    if row_size > 0 {
+        for row in scanlines.chunks_exact(row_size + 1).rev() {
+            // only unfiltered rows
+            if row[0] != 0 {
+                return None;
+            }
+            pixels.extend_from_slice(&row[1..]);
+        }
+    }
+
+    Some(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Data of a zlib stream that only has stored deflate blocks.
+fn inflate_stored(stream: &[u8]) -> Option<Vec<u8>> {
+    let mut blocks = stream.get(2..stream.len().checked_sub(4)?)?;
+    let mut data = Vec::with_capacity(blocks.len());
+    loop {
+        let (&flags, rest) = blocks.split_first()?;
+        // bits 1 and 2 are the block type, 0 is stored
+        if flags & 0b110 != 0 {
+            return None;
+        }
+        let length = u16::from_le_bytes(rest.get(..2)?.try_into().ok()?) as usize;
+        data.extend_from_slice(rest.get(4..4 + length)?);
+        blocks = &rest[4 + length..];
+
+        if flags & 1 == 1 {
+            break;
+        }
+    }
+
+    let checksum = u32::from_be_bytes(stream[stream.len() - 4..].try_into().ok()?);
+    (checksum == adler32(&data)).then_some(data)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MODULO: u32 = 65521;
+    let (mut a, mut b) = (1, 0);
+    for byte in data {
+        a = (a + u32::from(*byte)) % MODULO;
+        b = (b + a) % MODULO;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn decodes() {
+        // red at the bottom left, so it's the first pixel of the last PNG row
+        let image = Image {
+            width: 2,
+            height: 2,
+            pixels: vec![
+                255, 0, 0, 255, 0, 255, 0, 255, //
+                0, 0, 255, 255, 255, 255, 255, 128,
+            ],
+        };
+
+        let decoded = image::load_from_memory(&image.to_png()).unwrap().to_rgba8();
+        assert_eq!(decoded.dimensions(), (2, 2));
+        assert_eq!(decoded.get_pixel(0, 1).0, [255, 0, 0, 255]);
+        assert_eq!(decoded.get_pixel(1, 0).0, [255, 255, 255, 128]);
+
+        // larger than a single stored block
+        let large = Image {
+            width: 200,
+            height: 100,
+            pixels: (0..200 * 100 * 4).map(|index| index as u8).collect(),
+        };
+        let decoded = image::load_from_memory(&large.to_png()).unwrap().to_rgba8();
+        assert_eq!(decoded.get_pixel(199, 99).0, large.pixel(199, 0).unwrap());
+    }
+
+    #[test]
+    fn roundtrip() {
+        let image = Image {
+            width: 130,
+            height: 140,
+            pixels: (0..130 * 140 * 4).map(|index| (index * 7) as u8).collect(),
+        };
+        assert_eq!(Image::from_png(&image.to_png()).unwrap(), image);
+        assert_eq!(
+            Image::from_png(&Image::default().to_png()).unwrap(),
+            Image::default()
+        );
+
+        let mut corrupted = image.to_png();
+        let middle = corrupted.len() / 2;
+        corrupted[middle] ^= 0xFF;
+        assert!(Image::from_png(&corrupted).is_err());
+        assert!(Image::from_png(&corrupted[..middle]).is_err());
+        assert!(Image::from_png(b"\x89PNG").is_err());
+    }
+}
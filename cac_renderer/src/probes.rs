@@ -0,0 +1,376 @@
+//! Reflection probes: cubemaps of the surroundings, captured at points of the scene and blended
+//! per draw for image-based reflections.
+//!
+//! Each [ReflectionProbe] covers a [ProbeVolume]. For every draw,
+//! [select][ReflectionProbes::select] picks up to [MAX_BLENDED_PROBES] probes around its position
+//! and weights them by how far inside their volumes it is. Since per-draw overrides can't carry
+//! textures, the cubemaps are set on a [clone][crate::Renderer::clone_material] of the material,
+//! see [material][ReflectionProbes::material], and only the weights are passed per draw:
+//!
+//! ```no_run
+//! # use cac_renderer::{math::*, probes::*, scene::Scene, Backend, Handle, Material, Mesh, OpenGLContext, Renderer};
+//! # fn frame(renderer: &mut Renderer<OpenGLContext>, scene: &mut Scene, mesh: Mesh, material: Handle<Material>) {
+//! let mut probes = ReflectionProbes::default();
+//! probes.add(ReflectionProbe::new(vec3(0.0, 1.0, 0.0), ProbeVolume::new(Vec3::ZERO, Vec3::splat(5.0))));
+//! probes
+//!     .bake(renderer, |renderer, probe| {
+//!         renderer.capture_cubemap(probe.position, probe.resolution, |renderer, camera| {
+//!             scene.draw(renderer, camera)
+//!         })
+//!     })
+//!     .unwrap();
+//!
+//! let blend = probes.select(vec3(1.0, 0.0, 2.0));
+//! let material = probes.material(renderer, material, &blend).unwrap();
+//! renderer.draw(mesh, material, &[blend.property()]);
+//! # }
+//! ```
+//!
+//! The programs sample the probes with `uniform samplerCube probe_cubemap_0;` and
+//! `probe_cubemap_1;` and weight them with `uniform vec2 probe_weights;`. Weights sum up to at
+//! most 1, the rest is left for the sky or another fallback.
+use std::collections::HashMap;
+
+use crate::{
+    generation_vec::GenerationVec, math::Vec3, Context, Handle, Material, MaterialProperty,
+    Renderer, RendererError, Texture,
+};
+
+/// Most probes a draw is blended from.
+pub const MAX_BLENDED_PROBES: usize = 2;
+/// Names of the cubemap uniforms of the blended probes.
+pub const PROBE_CUBEMAPS: [&str; MAX_BLENDED_PROBES] = ["probe_cubemap_0", "probe_cubemap_1"];
+/// Name of the `vec2` uniform with the weights of the blended probes.
+pub const PROBE_WEIGHTS: &str = "probe_weights";
+
+/// Box a probe reflects, with a margin around it where its weight fades out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProbeVolume {
+    pub center: Vec3,
+    pub half_extents: Vec3,
+    /// Distance outside of the box at which the weight reaches 0.
+    pub blend_distance: f32,
+}
+
+impl ProbeVolume {
+    /// Box without margin.
+    pub fn new(center: Vec3, half_extents: Vec3) -> Self {
+        Self {
+            center,
+            half_extents,
+            blend_distance: 0.0,
+        }
+    }
+
+    pub fn with_blend_distance(mut self, blend_distance: f32) -> Self {
+        self.blend_distance = blend_distance;
+        self
+    }
+
+    /// 1 inside the box, fading linearly to 0 at the blend distance outside of it.
+    pub fn weight(&self, point: Vec3) -> f32 {
+        let outside = ((point - self.center).abs() - self.half_extents)
+            .max(Vec3::ZERO)
+            .length();
+        match self.blend_distance > 0.0 {
+            true => (1.0 - outside / self.blend_distance).max(0.0),
+            false => (outside == 0.0) as u8 as f32,
+        }
+    }
+
+    fn size(&self) -> f32 {
+        let size = self.half_extents * 2.0;
+        size.x * size.y * size.z
+    }
+}
+
+/// A probe of [ReflectionProbes].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReflectionProbe {
+    /// Where the cubemap is captured.
+    pub position: Vec3,
+    pub volume: ProbeVolume,
+    /// Size of the faces of the cubemap.
+    pub resolution: u32,
+    cubemap: Option<Handle<Texture>>,
+    dirty: bool,
+}
+
+impl ReflectionProbe {
+    /// Probe with 128 * 128 faces, baked with the next [bake][ReflectionProbes::bake].
+    pub fn new(position: Vec3, volume: ProbeVolume) -> Self {
+        Self {
+            position,
+            volume,
+            resolution: 128,
+            cubemap: None,
+            dirty: true,
+        }
+    }
+
+    pub fn with_resolution(mut self, resolution: u32) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    /// The cubemap of the last bake, None until the probe was baked.
+    pub fn cubemap(&self) -> Option<Handle<Texture>> {
+        self.cubemap
+    }
+
+    /// Whether the probe is baked with the next [bake][ReflectionProbes::bake].
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+}
+
+/// The probes a draw is blended from, see [ReflectionProbes::select].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ProbeBlend {
+    pub probes: [Option<Handle<ReflectionProbe>>; MAX_BLENDED_PROBES],
+    /// Weight of each probe, 0 for the missing ones.
+    pub weights: [f32; MAX_BLENDED_PROBES],
+}
+
+impl ProbeBlend {
+    /// The weights as per-draw override of [PROBE_WEIGHTS].
+    pub fn property(&self) -> MaterialProperty<'_> {
+        MaterialProperty::new(PROBE_WEIGHTS, &self.weights)
+    }
+}
+
+/// Cubemaps of the probes of a [ProbeBlend].
+type BlendCubemaps = [Option<Handle<Texture>>; MAX_BLENDED_PROBES];
+
+/// Places, bakes and selects reflection probes, see the [module documentation][self].
+#[derive(Default)]
+pub struct ReflectionProbes {
+    probes: GenerationVec<ReflectionProbe, ReflectionProbe>,
+    /// Clones of materials with the cubemaps of a blend.
+    materials: HashMap<(Handle<Material>, BlendCubemaps), Handle<Material>>,
+}
+
+impl ReflectionProbes {
+    pub fn add(&mut self, probe: ReflectionProbe) -> Handle<ReflectionProbe> {
+        self.probes.push(probe)
+    }
+
+    /// Removes the probe. Its cubemap and the materials with it are removed with the next
+    /// [bake][Self::bake].
+    pub fn remove(&mut self, probe: Handle<ReflectionProbe>) {
+        self.probes.remove(probe);
+    }
+
+    pub fn probe(&self, probe: Handle<ReflectionProbe>) -> Option<&ReflectionProbe> {
+        self.probes.get(probe)
+    }
+
+    pub fn probes(&self) -> impl Iterator<Item = (Handle<ReflectionProbe>, &ReflectionProbe)> {
+        self.probes.handles().zip(self.probes.iter())
+    }
+
+    /// Moves the capture position of the probe and bakes it again with the next
+    /// [bake][Self::bake].
+    pub fn set_position(&mut self, probe: Handle<ReflectionProbe>, position: Vec3) {
+        if let Some(probe) = self.probes.get_mut(probe) {
+            probe.position = position;
+            probe.dirty = true;
+        }
+    }
+
+    /// Changes the volume, the cubemap stays valid.
+    pub fn set_volume(&mut self, probe: Handle<ReflectionProbe>, volume: ProbeVolume) {
+        if let Some(probe) = self.probes.get_mut(probe) {
+            probe.volume = volume;
+        }
+    }
+
+    /// Bakes the probe again with the next [bake][Self::bake], e.g. after its surroundings
+    /// changed.
+    pub fn invalidate(&mut self, probe: Handle<ReflectionProbe>) {
+        if let Some(probe) = self.probes.get_mut(probe) {
+            probe.dirty = true;
+        }
+    }
+
+    pub fn invalidate_all(&mut self) {
+        self.probes.iter_mut().for_each(|probe| probe.dirty = true);
+    }
+
+    /// Captures the cubemaps of the dirty probes, usually with
+    /// [capture_cubemap][crate::Renderer::capture_cubemap], and returns how many were baked.
+    ///
+    /// The previous cubemaps of the probes are removed, together with the materials created
+    /// for them by [material][Self::material]. A failed capture leaves the probe dirty.
+    pub fn bake<C: Context>(
+        &mut self,
+        ctx: &mut Renderer<C>,
+        mut capture: impl FnMut(
+            &mut Renderer<C>,
+            &ReflectionProbe,
+        ) -> Result<Handle<Texture>, RendererError>,
+    ) -> Result<usize, RendererError> {
+        let mut baked = 0;
+        for probe in self.probes.iter_mut().filter(|probe| probe.dirty) {
+            let cubemap = capture(ctx, probe)?;
+            if let Some(previous) = probe.cubemap.replace(cubemap) {
+                ctx.textures.remove(previous);
+            }
+            probe.dirty = false;
+            baked += 1;
+        }
+
+        // materials with cubemaps of re-baked or removed probes
+        let cubemaps: Vec<_> = self
+            .probes
+            .iter()
+            .filter_map(|probe| probe.cubemap)
+            .collect();
+        self.materials.retain(|(_, textures), material| {
+            let current = textures
+                .iter()
+                .flatten()
+                .all(|texture| cubemaps.contains(texture));
+            if !current {
+                ctx.materials.remove(*material);
+            }
+            current
+        });
+        Ok(baked)
+    }
+
+    /// The baked probes with the highest weights at the position. The weights are scaled down
+    /// to sum up to 1 where the volumes overlap. Probes with the same weight are ordered by the
+    /// size of their volume, so the smaller, more detailed one comes first.
+    pub fn select(&self, position: Vec3) -> ProbeBlend {
+        let mut candidates: Vec<_> = self
+            .probes()
+            .filter(|(_, probe)| probe.cubemap.is_some())
+            .map(|(handle, probe)| (handle, probe.volume.weight(position), probe.volume.size()))
+            .filter(|(_, weight, _)| *weight > 0.0)
+            .collect();
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.2.total_cmp(&b.2)));
+
+        let mut blend = ProbeBlend::default();
+        for (index, (handle, weight, _)) in
+            candidates.into_iter().take(MAX_BLENDED_PROBES).enumerate()
+        {
+            blend.probes[index] = Some(handle);
+            blend.weights[index] = weight;
+        }
+        let sum: f32 = blend.weights.iter().sum();
+        if sum > 1.0 {
+            blend.weights.iter_mut().for_each(|weight| *weight /= sum);
+        }
+        blend
+    }
+
+    /// Clone of the material with the cubemaps of the blend in [PROBE_CUBEMAPS]. The clones are
+    /// kept for later draws with the same probes, blends without probes use the material as it
+    /// is.
+    pub fn material<C: Context>(
+        &mut self,
+        ctx: &mut Renderer<C>,
+        material: Handle<Material>,
+        blend: &ProbeBlend,
+    ) -> Result<Handle<Material>, RendererError> {
+        let textures = blend.probes.map(|probe| {
+            probe
+                .and_then(|probe| self.probes.get(probe))
+                .and_then(|probe| probe.cubemap)
+        });
+        if textures.iter().all(Option::is_none) {
+            return Ok(material);
+        }
+        if let Some(clone) = self.materials.get(&(material, textures)) {
+            return Ok(*clone);
+        }
+
+        let properties: Vec<_> = PROBE_CUBEMAPS
+            .iter()
+            .zip(&textures)
+            .filter_map(|(name, texture)| {
+                texture
+                    .as_ref()
+                    .map(|texture| MaterialProperty::new(*name, texture))
+            })
+            .collect();
+        let clone = ctx.clone_material(material, &properties)?;
+        self.materials.insert((material, textures), clone);
+        Ok(clone)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn volume_weights() {
+        let volume = ProbeVolume::new(Vec3::ZERO, Vec3::ONE).with_blend_distance(2.0);
+        assert_eq!(volume.weight(Vec3::new(0.5, -1.0, 0.0)), 1.0);
+        assert_eq!(volume.weight(Vec3::new(2.0, 0.0, 0.0)), 0.5);
+        assert_eq!(volume.weight(Vec3::new(0.0, 0.0, -4.0)), 0.0);
+
+        let hard = ProbeVolume::new(Vec3::ZERO, Vec3::ONE);
+        assert_eq!(hard.weight(Vec3::ONE), 1.0);
+        assert_eq!(hard.weight(Vec3::new(1.1, 0.0, 0.0)), 0.0);
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn bake_and_blend() {
+        use crate::{ShaderProgram, TextureFormat};
+
+        let mut renderer = Renderer::new_headless().unwrap();
+        let program = ShaderProgram::from_sources(
+            &mut renderer,
+            "#version 330 core\nvoid main() {}",
+            "#version 330 core\nuniform samplerCube probe_cubemap_0;\nuniform samplerCube probe_cubemap_1;\nuniform vec2 probe_weights;\nvoid main() {}",
+        )
+        .unwrap();
+        let material = renderer.create_material(program, &[]).unwrap();
+
+        let mut probes = ReflectionProbes::default();
+        let room = probes.add(ReflectionProbe::new(
+            Vec3::ZERO,
+            ProbeVolume::new(Vec3::ZERO, Vec3::splat(2.0)).with_blend_distance(2.0),
+        ));
+        let corner = probes.add(ReflectionProbe::new(
+            Vec3::splat(2.0),
+            ProbeVolume::new(Vec3::splat(2.0), Vec3::ONE),
+        ));
+        // unbaked probes aren't selected
+        assert_eq!(probes.select(Vec3::ZERO), ProbeBlend::default());
+
+        let face = [0; 4];
+        let mut capture = |renderer: &mut Renderer<_>, _: &ReflectionProbe| {
+            Texture::cubemap(renderer, [&face[..]; 6], 1, TextureFormat::Rgba8)
+        };
+        assert_eq!(probes.bake(&mut renderer, &mut capture).unwrap(), 2);
+        assert_eq!(probes.bake(&mut renderer, &mut capture).unwrap(), 0);
+
+        // inside both, the smaller corner comes first and the weights are normalized
+        let blend = probes.select(Vec3::splat(1.5));
+        assert_eq!(blend.probes, [Some(corner), Some(room)]);
+        assert_eq!(blend.weights, [0.5, 0.5]);
+        // only inside the margin of the room
+        let blend = probes.select(Vec3::new(-3.0, 0.0, 0.0));
+        assert_eq!(blend.probes, [Some(room), None]);
+        assert_eq!(blend.weights, [0.5, 0.0]);
+
+        let clone = probes.material(&mut renderer, material, &blend).unwrap();
+        assert_ne!(clone, material);
+        assert_eq!(
+            probes.material(&mut renderer, material, &blend).unwrap(),
+            clone
+        );
+
+        // re-baking replaces the cubemap and drops the clone
+        let previous = probes.probe(room).unwrap().cubemap().unwrap();
+        probes.invalidate(room);
+        assert_eq!(probes.bake(&mut renderer, &mut capture).unwrap(), 1);
+        assert!(renderer.textures.get(previous).is_none());
+        assert!(renderer.materials.get(clone).is_none());
+    }
+}
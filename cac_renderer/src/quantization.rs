@@ -0,0 +1,175 @@
+//! Helpers to quantize vertex data into smaller integer formats.
+//!
+//! Quantized attributes are uploaded with the matching
+//! [VertexAttributeKind][crate::VertexAttributeKind] via
+//! [BufferAttributes::with_kinds][crate::BufferAttributes::with_kinds]. The GPU normalizes the
+//! integers into floats, so the shaders only need to undo the remaining encoding with the
+//! provided GLSL snippets.
+//!
+//! | Data     | Encoding              | Kind    | Size          |
+//! |----------|-----------------------|---------|---------------|
+//! | Position | snorm16 within bounds | `I16x4` | 12 -> 8 bytes |
+//! | Normal   | oct-encoded snorm16   | `I16x2` | 12 -> 4 bytes |
+//! | UV       | unorm16               | `U16x2` | 8 -> 4 bytes  |
+use crate::math::{vec2, vec3, Vec2, Vec3};
+
+/// GLSL function to decode a position that was quantized with [quantize_position].
+/// The bounds need to be the same ones used for the quantization, e.g. passed in as uniforms.
+pub const DECODE_POSITION_GLSL: &str = r#"
+vec3 decode_position(vec3 quantized, vec3 bounds_min, vec3 bounds_max) {
+    return bounds_min + (quantized * 0.5 + 0.5) * (bounds_max - bounds_min);
+}
+"#;
+
+/// GLSL function to decode a normal that was encoded with [oct_encode].
+pub const DECODE_NORMAL_GLSL: &str = r#"
+vec3 oct_decode(vec2 encoded) {
+    vec3 normal = vec3(encoded, 1.0 - abs(encoded.x) - abs(encoded.y));
+    if (normal.z < 0.0) {
+        vec2 signs = vec2(normal.x >= 0.0 ? 1.0 : -1.0, normal.y >= 0.0 ? 1.0 : -1.0);
+        normal.xy = (1.0 - abs(normal.yx)) * signs;
+    }
+    return normalize(normal);
+}
+"#;
+
+fn to_snorm16(value: f32) -> i16 {
+    (value.clamp(-1.0, 1.0) * f32::from(i16::MAX)).round() as i16
+}
+
+fn from_snorm16(value: i16) -> f32 {
+    (f32::from(value) / f32::from(i16::MAX)).max(-1.0)
+}
+
+fn to_unorm16(value: f32) -> u16 {
+    (value.clamp(0.0, 1.0) * f32::from(u16::MAX)).round() as u16
+}
+
+fn from_unorm16(value: u16) -> f32 {
+    f32::from(value) / f32::from(u16::MAX)
+}
+
+/// Quantizes the position into snorm16 relative to the bounds, usually the bounding box of the
+/// mesh. Positions outside of the bounds are clamped.
+/// The fourth component is padding to keep the attribute 4 byte aligned.
+pub fn quantize_position(position: Vec3, bounds_min: Vec3, bounds_max: Vec3) -> [i16; 4] {
+    let extent = (bounds_max - bounds_min).max(Vec3::splat(f32::EPSILON));
+    let relative = (position - bounds_min) / extent * 2.0 - Vec3::ONE;
+
+    [
+        to_snorm16(relative.x),
+        to_snorm16(relative.y),
+        to_snorm16(relative.z),
+        0,
+    ]
+}
+
+/// Reverses [quantize_position].
+pub fn dequantize_position(quantized: [i16; 4], bounds_min: Vec3, bounds_max: Vec3) -> Vec3 {
+    let relative = vec3(
+        from_snorm16(quantized[0]),
+        from_snorm16(quantized[1]),
+        from_snorm16(quantized[2]),
+    );
+
+    bounds_min + (relative * 0.5 + 0.5) * (bounds_max - bounds_min)
+}
+
+/// Encodes a unit vector with the octahedral mapping into two snorm16.
+pub fn oct_encode(normal: Vec3) -> [i16; 2] {
+    let normal = normal / (normal.x.abs() + normal.y.abs() + normal.z.abs());
+
+    let encoded = if normal.z >= 0.0 {
+        vec2(normal.x, normal.y)
+    } else {
+        let signs = vec2(
+            if normal.x >= 0.0 { 1.0 } else { -1.0 },
+            if normal.y >= 0.0 { 1.0 } else { -1.0 },
+        );
+        (Vec2::ONE - vec2(normal.y.abs(), normal.x.abs())) * signs
+    };
+
+    [to_snorm16(encoded.x), to_snorm16(encoded.y)]
+}
+
+/// Reverses [oct_encode]. The result is normalized.
+pub fn oct_decode(encoded: [i16; 2]) -> Vec3 {
+    let encoded = vec2(from_snorm16(encoded[0]), from_snorm16(encoded[1]));
+    let mut normal = vec3(
+        encoded.x,
+        encoded.y,
+        1.0 - encoded.x.abs() - encoded.y.abs(),
+    );
+
+    if normal.z < 0.0 {
+        let signs = vec2(
+            if normal.x >= 0.0 { 1.0 } else { -1.0 },
+            if normal.y >= 0.0 { 1.0 } else { -1.0 },
+        );
+        let xy = (Vec2::ONE - vec2(normal.y.abs(), normal.x.abs())) * signs;
+        normal.x = xy.x;
+        normal.y = xy.y;
+    }
+
+    normal.normalize()
+}
+
+/// Quantizes the UV into unorm16. Components outside of [0.0, 1.0] are clamped, so repeating
+/// UVs can't be quantized this way.
+pub fn quantize_uv(uv: Vec2) -> [u16; 2] {
+    [to_unorm16(uv.x), to_unorm16(uv.y)]
+}
+
+/// Reverses [quantize_uv].
+pub fn dequantize_uv(quantized: [u16; 2]) -> Vec2 {
+    vec2(from_unorm16(quantized[0]), from_unorm16(quantized[1]))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn position_roundtrip() {
+        let (min, max) = (vec3(-10.0, 0.0, 5.0), vec3(10.0, 2.0, 105.0));
+        let positions = [min, max, vec3(0.0, 1.0, 50.0), vec3(-3.3, 0.123, 99.9)];
+
+        for position in positions {
+            let decoded = dequantize_position(quantize_position(position, min, max), min, max);
+            assert!((decoded - position).abs().max_element() < 0.002);
+        }
+
+        // outside of the bounds gets clamped
+        let decoded = dequantize_position(quantize_position(max * 2.0, min, max), min, max);
+        assert!((decoded - max).abs().max_element() < 0.002);
+    }
+
+    #[test]
+    fn normal_roundtrip() {
+        let normals = [
+            Vec3::X,
+            Vec3::NEG_Y,
+            Vec3::Z,
+            Vec3::NEG_Z,
+            vec3(1.0, -1.0, -1.0).normalize(),
+            vec3(-0.2, 0.7, -0.3).normalize(),
+        ];
+
+        for normal in normals {
+            let decoded = oct_decode(oct_encode(normal));
+            assert!(
+                decoded.dot(normal) > 0.9999,
+                "{normal} decoded to {decoded}"
+            );
+        }
+    }
+
+    #[test]
+    fn uv_roundtrip() {
+        let uv = vec2(0.25, 0.999);
+        let decoded = dequantize_uv(quantize_uv(uv));
+        assert!((decoded - uv).abs().max_element() < 0.0001);
+
+        assert_eq!(quantize_uv(vec2(-1.0, 2.0)), [0, u16::MAX]);
+    }
+}
@@ -0,0 +1,130 @@
+use std::ops::Range;
+
+use crate::{
+    renderer::buffer::{as_bytes, CreateBuffer},
+    BufferUsage, RendererError,
+};
+
+use super::{HeadlessContext, RecordedCommand};
+
+/// Headless Buffer
+///
+/// Keeps the bytes that were written into it, so they can be [read][crate::Renderer::read_buffer]
+/// back. Nothing writes into buffers on the device side, e.g. compute programs.
+#[derive(Debug)]
+pub struct HeadlessBuffer {
+    pub(super) is_index: bool,
+    data: Vec<u8>,
+    usage: BufferUsage,
+}
+
+impl CreateBuffer for HeadlessBuffer {
+    type Context = HeadlessContext;
+
+    fn with_vertex<T>(
+        ctx: &mut HeadlessContext,
+        data: &[T],
+        usage: BufferUsage,
+    ) -> Result<Self, RendererError> {
+        let data = as_bytes(data).to_vec();
+        ctx.record(RecordedCommand::UploadVertices { size: data.len() });
+
+        Ok(Self {
+            is_index: false,
+            data,
+            usage,
+        })
+    }
+
+    fn with_index<T>(
+        ctx: &mut HeadlessContext,
+        data: &[T],
+        usage: BufferUsage,
+    ) -> Result<Self, RendererError> {
+        let data = as_bytes(data).to_vec();
+        ctx.record(RecordedCommand::UploadIndices { size: data.len() });
+
+        Ok(Self {
+            is_index: true,
+            data,
+            usage,
+        })
+    }
+
+    fn set_data<T>(
+        &mut self,
+        ctx: &mut HeadlessContext,
+        data: &[T],
+        usage: BufferUsage,
+    ) -> Result<(), RendererError> {
+        let size = std::mem::size_of_val(data);
+        ctx.record(match self.is_index {
+            true => RecordedCommand::UploadIndices { size },
+            false => RecordedCommand::UploadVertices { size },
+        });
+        self.data = as_bytes(data).to_vec();
+        self.usage = usage;
+
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    fn usage(&self) -> BufferUsage {
+        self.usage
+    }
+
+    fn update<T>(
+        &mut self,
+        ctx: &mut HeadlessContext,
+        offset: usize,
+        data: &[T],
+    ) -> Result<(), RendererError> {
+        let data = as_bytes(data);
+        ctx.record(RecordedCommand::UpdateBuffer {
+            offset,
+            size: data.len(),
+        });
+        self.data[offset..offset + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn orphan(&mut self, ctx: &mut HeadlessContext, size: usize) -> Result<(), RendererError> {
+        ctx.record(RecordedCommand::OrphanBuffer { size });
+        self.data = vec![0; size];
+        Ok(())
+    }
+
+    fn read(
+        &self,
+        ctx: &mut HeadlessContext,
+        range: Range<usize>,
+    ) -> Result<Vec<u8>, RendererError> {
+        ctx.record(RecordedCommand::ReadBuffer {
+            offset: range.start,
+            size: range.len(),
+        });
+        Ok(self.data[range].to_vec())
+    }
+
+    fn with_stream(ctx: &mut HeadlessContext, size: usize) -> Result<Self, RendererError> {
+        ctx.record(RecordedCommand::UploadVertices { size });
+
+        Ok(Self {
+            is_index: false,
+            data: vec![0; size],
+            usage: BufferUsage::StreamingWrite,
+        })
+    }
+
+    fn begin_stream_region(
+        &mut self,
+        ctx: &mut HeadlessContext,
+        region: usize,
+    ) -> Result<(), RendererError> {
+        ctx.record(RecordedCommand::BeginStreamRegion { region });
+        Ok(())
+    }
+}
@@ -1,49 +1,249 @@
 #![cfg(feature = "headless")]
 
-use crate::{Handle, Renderer};
+use std::collections::HashMap;
 
-mod mesh;
+use crate::{
+    generation_vec::GenerationVec,
+    renderer::{
+        resolve_properties, resolve_texture, shares_state, Capabilities, ComputeProgram, DebugView,
+        DrawBatch, DrawCommand, DrawList, EmulatedFeatures, FrameArena, FrameStats, GpuScope,
+        InstanceValue, Material, MemoryBarriers, Sampler, ShaderPreprocessor, ShaderProgram,
+        Texture, TimedRun, VertexLayout,
+    },
+    BackendKind, Buffer, ClearFlags, Color32, CreationReport, Handle, MaterialProperty, Mesh,
+    Renderer, RendererError,
+};
+
+mod buffer;
+use buffer::HeadlessBuffer;
 
 mod render_target;
 use render_target::RenderTarget;
 
+mod sampler;
+use sampler::HeadlessSampler;
+
+mod shader;
+use shader::{HeadlessShader, HeadlessShaderProgram};
+
+mod texture;
+use texture::HeadlessTexture;
+
+mod vertex_layout;
+use vertex_layout::HeadlessVertexLayout;
+
+use super::{Context, DebugLabel};
+
+/// Command issued by the headless backend.
+///
+/// Instead of talking to a graphics device, the headless backend records everything it would
+/// have sent to it, so tests can make assertions about the issued commands.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordedCommand {
+    /// A vertex buffer with `size` bytes was uploaded.
+    UploadVertices { size: usize },
+    /// An index buffer with `size` bytes was uploaded.
+    UploadIndices { size: usize },
+    /// `size` bytes of a buffer were replaced, starting at the byte `offset`.
+    UpdateBuffer { offset: usize, size: usize },
+    /// The storage of a buffer was replaced with `size` bytes of undefined content.
+    OrphanBuffer { size: usize },
+    /// `size` bytes of a buffer, starting at the byte `offset`, were read back.
+    ReadBuffer { offset: usize, size: usize },
+    /// The first write of the frame into the region of a [StreamBuffer][crate::StreamBuffer].
+    BeginStreamRegion { region: usize },
+    /// The screen target was cleared at the start of an update.
+    Clear { flags: ClearFlags, color: Color32 },
+    /// The material and its program were bound.
+    BindMaterial {
+        material: Handle<Material>,
+        program: Handle<ShaderProgram>,
+    },
+    /// A texture of the bound material was bound to the texture unit, with the sampler if it
+    /// has one.
+    BindTexture {
+        unit: u32,
+        texture: Handle<Texture>,
+        sampler: Option<Handle<Sampler>>,
+    },
+    /// A per-draw property override was uploaded to the bound program.
+    SetUniform { location: u32, values: Vec<f32> },
+    /// `size` bytes of the uniform data of the frame, starting at `offset`, were bound to the
+    /// uniform block at the binding.
+    BindUniforms {
+        binding: u32,
+        offset: usize,
+        size: usize,
+    },
+    /// The mesh was drawn at the depth, either with or without an index buffer.
+    Draw {
+        mesh: Mesh,
+        material: Handle<Material>,
+        depth: f32,
+        indexed: bool,
+    },
+    /// The commands of the indirect buffer were drawn with the layout, either with or without an
+    /// index buffer.
+    DrawIndirect {
+        layout: Handle<VertexLayout>,
+        material: Handle<Material>,
+        buffer: Handle<Buffer>,
+        count: u32,
+        indexed: bool,
+    },
+    /// The compute program was dispatched with the number of work groups.
+    Dispatch {
+        program: Handle<ComputeProgram>,
+        groups: [u32; 3],
+    },
+    /// A memory barrier for the writes of the previous dispatches was issued.
+    MemoryBarrier { barriers: MemoryBarriers },
+    /// A GPU scope with the name was opened.
+    BeginGpuScope { name: String },
+    /// The open GPU scope was closed.
+    EndGpuScope,
+    /// A debug group with the name was pushed.
+    PushDebugGroup { name: String },
+    /// The innermost debug group was popped.
+    PopDebugGroup,
+}
+
 /// Headless Backend
 ///
 /// No graphics calls are send to the graphics device.
-/// Instead, it logs them and creates dummy values for the exposed resources.
+/// Instead, it records them and creates dummy values for the exposed resources.
 /// This allows the renderer to run tests and miri without creating expensive graphics contexts or
 /// using sys calls.
-pub struct Headless {
+pub struct HeadlessContext {
     screen_target: RenderTarget,
+    draw_list: DrawList,
+    recorded_commands: Vec<RecordedCommand>,
+    open_scope: Option<String>,
+    /// Ended GPU scopes of the current frame. They take no time without a GPU.
+    scopes: Vec<GpuScope>,
+    /// Runs of the draws of the last update with the same material and layer, which take no time
+    /// either.
+    runs: Vec<TimedRun>,
+    capabilities: Capabilities,
+    /// Set between [suspend][crate::Backend::suspend] and [resume][crate::Backend::resume].
+    suspended: bool,
 }
 
-impl Headless {
+impl HeadlessContext {
     pub(crate) fn new() -> Self {
         Self {
             screen_target: RenderTarget::default(),
+            draw_list: DrawList::with_capacity(100),
+            recorded_commands: Vec::with_capacity(100),
+            open_scope: None,
+            scopes: Vec::new(),
+            runs: Vec::new(),
+            suspended: false,
+            // the minimums of OpenGL 4.5, so the limits hold on any driver the code runs on later
+            capabilities: Capabilities {
+                max_texture_size: 16384,
+                max_cubemap_size: 16384,
+                max_array_layers: 2048,
+                max_vertex_attribs: 16,
+                max_texture_units: 80,
+                max_samples: 4,
+                supports_compute: true,
+                supports_dsa: true,
+                supports_tessellation: true,
+                supports_texture_views: true,
+                supports_multi_draw_indirect: true,
+                uniform_buffer_alignment: 256,
+                emulated: EmulatedFeatures::default(),
+                extensions: Vec::new(),
+            },
         }
     }
+
+    pub(super) fn record(&mut self, command: RecordedCommand) {
+        log::trace!("{command:?}");
+        self.recorded_commands.push(command);
+    }
+}
+
+impl Context for HeadlessContext {
+    type Context = Self;
+    type Buffer = HeadlessBuffer;
+    type VertexLayout = HeadlessVertexLayout;
+    type Shader = HeadlessShader;
+    type ShaderProgram = HeadlessShaderProgram;
+    type ComputeProgram = HeadlessShaderProgram;
+    type Sampler = HeadlessSampler;
+    type Texture = HeadlessTexture;
 }
 
-impl Renderer {
+// there is no debugger to show the labels
+impl DebugLabel for HeadlessBuffer {}
+impl DebugLabel for HeadlessVertexLayout {}
+impl DebugLabel for HeadlessShaderProgram {}
+impl DebugLabel for HeadlessSampler {}
+impl DebugLabel for HeadlessTexture {}
+
+impl Renderer<HeadlessContext> {
     /// New Headless Renderer
     /// So far there is no reason for it to ever fail, but the Result return type is consistent
     /// with the other renderers and avoid the "following code can't be reached" warning
-    pub fn new_headless() -> Result<Self, String> {
-        Ok(Self {
-            backend: Box::new(Headless::new()),
-        })
+    pub fn new_headless() -> Result<Self, RendererError> {
+        let context = HeadlessContext::new();
+        let preprocessor = ShaderPreprocessor::with_emulation(&context.capabilities.emulated);
+        let mut renderer = Self {
+            context,
+            buffers: GenerationVec::with_capacity(10),
+            layouts: GenerationVec::with_capacity(5),
+            shaders: GenerationVec::with_capacity(10),
+            programs: GenerationVec::with_capacity(5),
+            compute_programs: GenerationVec::with_capacity(5),
+            samplers: GenerationVec::with_capacity(5),
+            textures: GenerationVec::with_capacity(10),
+            materials: GenerationVec::with_capacity(10),
+            stats: FrameStats::default(),
+            frame_arena: FrameArena::with_capacity(1000, 100),
+            uploads: Default::default(),
+            preprocessor,
+            placeholders: None,
+            layout_checks: HashMap::new(),
+            property_validation: Default::default(),
+            attribute_names: Vec::new(),
+            debug_view: DebugView::Off,
+            debug_materials: HashMap::new(),
+            layout_config: Default::default(),
+            residency: Default::default(),
+            retention: Default::default(),
+            creation_report: CreationReport::new(BackendKind::Headless),
+            #[cfg(feature = "hot-reload")]
+            watcher: Default::default(),
+        };
+        // the uploads of the placeholders are not part of the recording
+        renderer.create_placeholders();
+        renderer.clear_recorded_commands();
+
+        Ok(renderer)
+    }
+
+    /// All commands recorded since the creation of the renderer or the last call to
+    /// [clear_recorded_commands][Self::clear_recorded_commands], in the order they were issued.
+    pub fn recorded_commands(&self) -> &[RecordedCommand] {
+        &self.context.recorded_commands
+    }
+
+    /// Forgets all recorded commands, e.g. to only assert the commands of the next frame.
+    pub fn clear_recorded_commands(&mut self) {
+        self.context.recorded_commands.clear();
     }
 }
 
-impl super::RendererBackend for Headless {
-    /// Returns a String with the content "Headless".       
+impl super::Backend for Renderer<HeadlessContext> {
+    /// Returns a String with the content "Headless".
     ///
     /// It's not very useful in this case, but returns driver, versions and extensions in the real
     /// backends.
     ///
     /// ```
-    /// # use cac_renderer::{Renderer};
+    /// # use cac_renderer::{Backend, Renderer};
     /// # let renderer = Renderer::new_headless().unwrap();
     /// assert_eq!(renderer.context_description(), "Headless Renderer".to_string());
     /// ```
@@ -52,21 +252,1640 @@ impl super::RendererBackend for Headless {
     }
 
     fn screen_target(&mut self) -> &mut dyn crate::RenderTarget {
-        &mut self.screen_target
+        &mut self.context.screen_target
+    }
+
+    /// There is no window, so the screenshot is empty. Use
+    /// [read_pixels][crate::RenderTarget::read_pixels] of the screen target for the clear color.
+    fn screenshot(&mut self) -> crate::Image {
+        crate::Image::default()
+    }
+
+    fn capabilities(&self) -> &Capabilities {
+        &self.context.capabilities
+    }
+
+    /// Nothing gets decoded, so every format is supported.
+    fn supports_compressed_format(&self, _format: crate::CompressedFormat) -> bool {
+        true
     }
 
-    fn create_buffer(
+    fn draw_at_depth(
         &mut self,
-        _buffer: crate::BufferData,
-        _usage: crate::BufferUsage,
-    ) -> Result<Handle<crate::Buffer>, crate::RendererError> {
-        todo!()
+        mesh: Mesh,
+        material: Handle<Material>,
+        depth: f32,
+        instance_properties: &[MaterialProperty],
+    ) {
+        let Some((mesh, material)) = self.resolve_draw(mesh, material) else {
+            return;
+        };
+        let Some((material, instance_data)) =
+            self.draw_material(&mesh, material, instance_properties)
+        else {
+            return;
+        };
+        let sort_key = self.sort_key(&mesh, material, depth);
+        self.context.draw_list.push(DrawCommand::new(
+            sort_key,
+            mesh,
+            depth,
+            material,
+            instance_data,
+        ));
     }
 
-    fn create_vertex_layout(
+    fn draw_indirect(
         &mut self,
-        _buffer_attributes: &[crate::renderer::buffer::BufferAttributes],
-    ) -> Result<Handle<crate::renderer::buffer::VertexLayout>, crate::RendererError> {
-        todo!()
+        layout: Handle<VertexLayout>,
+        material: Handle<Material>,
+        indirect_buffer: Handle<Buffer>,
+        count: u32,
+    ) {
+        if let Some(command) = self.indirect_command(layout, material, indirect_buffer, count) {
+            self.context.draw_list.push(command);
+        }
+    }
+
+    fn draw_batches(&mut self, batches: &[DrawBatch]) {
+        let commands = self.batch_commands(batches);
+        self.context.draw_list.extend(commands);
+    }
+
+    fn dispatch(
+        &mut self,
+        program: Handle<ComputeProgram>,
+        x: u32,
+        y: u32,
+        z: u32,
+        properties: &[MaterialProperty],
+    ) -> Result<(), RendererError> {
+        let compute_program =
+            self.compute_programs
+                .get(program)
+                .ok_or(RendererError::ResourceNotFound {
+                    resource: format!("ComputeProgram: {program:?}"),
+                })?;
+
+        for value in resolve_properties(compute_program, properties) {
+            match value {
+                InstanceValue::Uniform(location, values) => {
+                    self.context.record(RecordedCommand::SetUniform {
+                        location,
+                        values: values.to_vec(),
+                    })
+                }
+                InstanceValue::Block(..) => {
+                    log::warn!("Uniform data of the frame can't be used by dispatches")
+                }
+            }
+        }
+
+        self.context.record(RecordedCommand::Dispatch {
+            program,
+            groups: [x, y, z],
+        });
+        Ok(())
+    }
+
+    fn memory_barrier(&mut self, barriers: MemoryBarriers) {
+        self.context
+            .record(RecordedCommand::MemoryBarrier { barriers });
+    }
+
+    fn begin_gpu_scope(&mut self, name: &str) {
+        if self.context.open_scope.is_some() {
+            self.end_gpu_scope();
+        }
+        self.context.open_scope = Some(name.to_string());
+        self.context.record(RecordedCommand::BeginGpuScope {
+            name: name.to_string(),
+        });
+    }
+
+    fn end_gpu_scope(&mut self) {
+        if let Some(name) = self.context.open_scope.take() {
+            self.context.scopes.push(GpuScope {
+                name,
+                nanoseconds: 0,
+            });
+            self.context.record(RecordedCommand::EndGpuScope);
+        }
+    }
+
+    fn push_debug_group(&mut self, name: &str) {
+        self.context.record(RecordedCommand::PushDebugGroup {
+            name: name.to_string(),
+        });
+    }
+
+    fn pop_debug_group(&mut self) {
+        self.context.record(RecordedCommand::PopDebugGroup);
+    }
+
+    // nothing is rasterized, so there is nothing to count
+    fn set_overdraw_analysis(&mut self, enabled: bool) -> Result<(), RendererError> {
+        if enabled {
+            return Err(RendererError::UnsupportedFeature {
+                feature: "Overdraw analysis".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn suspend(&mut self) {
+        if self.context.suspended {
+            return;
+        }
+        self.release_resources();
+        self.context.draw_list.clear();
+        self.frame_arena.reset();
+        self.context.suspended = true;
+    }
+
+    fn resume(
+        &mut self,
+        _window: &impl raw_window_handle::HasRawWindowHandle,
+    ) -> Result<(), RendererError> {
+        if self.context.suspended {
+            self.context.suspended = false;
+            self.restore_builtins();
+        }
+        Ok(())
+    }
+
+    fn is_suspended(&self) -> bool {
+        self.context.suspended
+    }
+
+    fn update(&mut self) {
+        if self.context.suspended {
+            self.context.draw_list.clear();
+            self.frame_arena.reset();
+            return;
+        }
+        self.process_uploads();
+
+        let placeholder_texture = self.placeholder_texture();
+        let context = &mut self.context;
+
+        crate::RenderTarget::clear(&mut context.screen_target);
+        let (flags, color) = (
+            context.screen_target.clear_flags,
+            context.screen_target.clear_color,
+        );
+        context.record(RecordedCommand::Clear { flags, color });
+
+        self.stats.clear();
+        let mut bound_material = Handle::<Material>::new();
+        let mut material_dirty = false;
+        let mut draw_list = std::mem::take(&mut context.draw_list);
+        draw_list.sort();
+        context.runs.clear();
+
+        for command in draw_list.iter() {
+            let indexed = match self.layouts.get(command.mesh.vertex_layout) {
+                Some(layout) => layout.has_indices,
+                None => {
+                    log::warn!("Vertex Layout not found");
+                    continue;
+                }
+            };
+
+            if command.material != bound_material
+                && !material_dirty
+                && shares_state(&self.materials, bound_material, command.material)
+            {
+                // clones of the bound material that weren't changed, so its state is still valid
+                bound_material = command.material;
+            } else if command.material != bound_material || material_dirty {
+                if let Some(material) = self.materials.get(command.material) {
+                    bound_material = command.material;
+                    material_dirty = false;
+                    self.stats.record_material_bind();
+                    context.record(RecordedCommand::BindMaterial {
+                        material: command.material,
+                        program: material.program,
+                    });
+                    for (unit, binding) in material.textures.iter().enumerate() {
+                        let Some(texture) =
+                            resolve_texture(&self.textures, placeholder_texture, binding)
+                        else {
+                            continue;
+                        };
+                        let sampler = binding.sampler.filter(|sampler| {
+                            let exists = self.samplers.get(*sampler).is_some();
+                            if !exists {
+                                log::warn!("Sampler {sampler:?} not found");
+                            }
+                            exists
+                        });
+                        self.stats.record_state_change();
+                        context.record(RecordedCommand::BindTexture {
+                            unit: unit as u32,
+                            texture,
+                            sampler,
+                        });
+                    }
+                }
+            }
+
+            if !command.instance_data.is_empty() {
+                for value in self.frame_arena.get(command.instance_data) {
+                    match value {
+                        InstanceValue::Uniform(location, values) => {
+                            context.record(RecordedCommand::SetUniform {
+                                location,
+                                values: values.to_vec(),
+                            });
+                            material_dirty = true;
+                        }
+                        InstanceValue::Block(binding, slice) => {
+                            context.record(RecordedCommand::BindUniforms {
+                                binding,
+                                offset: slice.offset(),
+                                size: slice.size(),
+                            })
+                        }
+                    }
+                }
+            }
+
+            let (material, layer) = (command.material, command.sort_key.layer());
+            if !context
+                .runs
+                .last()
+                .is_some_and(|run| run.material == material && run.layer == layer)
+            {
+                context.runs.push(TimedRun {
+                    material,
+                    layer,
+                    nanoseconds: 0,
+                });
+            }
+            self.stats.record_draw(command);
+            context.record(match command.indirect {
+                Some(buffer) => RecordedCommand::DrawIndirect {
+                    layout: command.mesh.vertex_layout,
+                    material: command.material,
+                    buffer,
+                    count: command.mesh.count,
+                    indexed,
+                },
+                None => RecordedCommand::Draw {
+                    mesh: command.mesh,
+                    material: command.material,
+                    depth: command.depth,
+                    indexed,
+                },
+            });
+        }
+
+        context.draw_list = draw_list;
+        context.draw_list.clear();
+        self.frame_arena.reset();
+        self.stats.attribute_gpu_time(&context.runs);
+
+        if !context.scopes.is_empty() {
+            self.stats.gpu_scopes = std::mem::take(&mut context.scopes);
+        }
+        self.collect_garbage();
+        self.enforce_memory_budget();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        renderer::{CreateVertexLayout, PropertyValidation, Uniform, UniformKind, VertexLayout},
+        AttributeSemantic, Backend, Buffer, BufferAttributes, BufferUsage, MultiMesh, Primitive,
+        PropertyId, PropertyValue, RenderQueue, SamplerDescription, Shader, Swizzle, SwizzleSource,
+        TextureFormat, WrapMode,
+    };
+
+    const VS_SOURCE: &str = "
+        layout(location = 0) in vec3 pos;
+        void main() { gl_Position = vec4(pos, 1.0); }";
+
+    const FS_SOURCE: &str = "
+        out vec4 result;
+        uniform vec4 color;
+        uniform mat4 bones[4];
+        void main() { result = color; }";
+
+    fn triangle(renderer: &mut Renderer<HeadlessContext>) -> Mesh {
+        let vertices = [0.0_f32; 9];
+        let indices = [0_u8, 1, 2];
+        let vertex_buffer =
+            Buffer::with_vertex(renderer, &vertices, BufferUsage::StaticRead).unwrap();
+        let index_buffer = Buffer::with_index(renderer, &indices, BufferUsage::StaticRead).unwrap();
+
+        let vertex_layout = VertexLayout::new(
+            renderer,
+            &[
+                BufferAttributes::with_semantics(vertex_buffer, 0, &[AttributeSemantic::Position]),
+                BufferAttributes::with_index(index_buffer, 0),
+            ],
+        )
+        .unwrap();
+
+        Mesh::new(vertex_layout, 3, Primitive::Triangles)
+    }
+
+    #[test]
+    fn reflect_uniforms() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let program = ShaderProgram::from_sources(&mut renderer, VS_SOURCE, FS_SOURCE).unwrap();
+        let program = renderer.programs.get(program).unwrap();
+
+        let uniforms = program.uniforms();
+        assert_eq!(uniforms.len(), 2);
+        assert_eq!(uniforms[0].name, "color");
+        assert_eq!(uniforms[1].name, "bones");
+        assert_eq!(uniforms[1].count, 4);
+        assert_eq!(uniforms[1].offset, 16);
+
+        assert_eq!(program.get_uniform_location("bones"), Some(1));
+        assert_eq!(program.get_uniform_location("unknown"), None);
+
+        // arrays that can't exist are ignored instead of overflowing the data size
+        let program = ShaderProgram::from_sources(
+            &mut renderer,
+            VS_SOURCE,
+            "uniform mat4 huge[18446744073709551615];\nuniform float empty[0];",
+        )
+        .unwrap();
+        assert!(renderer
+            .programs
+            .get(program)
+            .unwrap()
+            .uniforms()
+            .is_empty());
+    }
+
+    #[test]
+    fn reflect_attributes() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let program = ShaderProgram::from_sources(
+            &mut renderer,
+            "layout(location = 2) in vec2 uv;\nin vec3 pos;\nin mat4 model;\nin float id;",
+            "in vec2 uv;\nout vec4 result;\nvoid main() {}",
+        )
+        .unwrap();
+
+        let attributes = renderer.program_attributes(program).unwrap();
+        let names = attributes
+            .iter()
+            .map(|attribute| (attribute.name.as_str(), attribute.location))
+            .collect::<Vec<_>>();
+        assert_eq!(names, [("uv", 2), ("pos", 3), ("model", 4), ("id", 8)]);
+        assert_eq!(attributes[2].kind, UniformKind::Mat4);
+
+        let uniforms = renderer.program_uniforms(program).unwrap();
+        assert!(uniforms.is_empty());
+
+        renderer.programs.remove(program);
+        assert!(renderer.program_attributes(program).is_none());
+    }
+
+    #[test]
+    fn tessellation() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let vertex = Shader::with_vertex(&mut renderer, VS_SOURCE).unwrap();
+        let control = Shader::with_tess_control(
+            &mut renderer,
+            "layout(vertices = 4) out;\nuniform float detail;\nvoid main() {}",
+        )
+        .unwrap();
+        let evaluation = Shader::with_tess_evaluation(
+            &mut renderer,
+            "layout(quads) in;\nuniform float height_scale;\nvoid main() {}",
+        )
+        .unwrap();
+        let fragment = Shader::with_fragment(&mut renderer, FS_SOURCE).unwrap();
+
+        // the stages need to be in the right order
+        assert!(ShaderProgram::with_tessellation(
+            &mut renderer,
+            vertex,
+            evaluation,
+            control,
+            fragment
+        )
+        .is_err());
+
+        let program =
+            ShaderProgram::with_tessellation(&mut renderer, vertex, control, evaluation, fragment)
+                .unwrap();
+        let uniforms = renderer.programs.get(program).unwrap().uniforms();
+        assert_eq!(uniforms.len(), 4);
+        assert_eq!(uniforms[1].name, "height_scale");
+
+        let material = renderer.create_material(program, &[]).unwrap();
+        let mesh = Mesh {
+            primitive: Primitive::Patches(4),
+            ..triangle(&mut renderer)
+        };
+        renderer.clear_recorded_commands();
+        renderer.draw(mesh, material, &[]);
+        renderer.update();
+        assert!(renderer
+            .recorded_commands()
+            .contains(&RecordedCommand::Draw {
+                mesh,
+                material,
+                depth: 0.0,
+                indexed: true
+            }));
+    }
+
+    #[test]
+    fn record_uploads() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        triangle(&mut renderer);
+
+        assert_eq!(
+            renderer.recorded_commands(),
+            &[
+                RecordedCommand::UploadVertices { size: 36 },
+                RecordedCommand::UploadIndices { size: 3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn update_and_orphan_buffers() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let buffer =
+            Buffer::with_vertex(&mut renderer, &[0.0f32; 8], BufferUsage::StreamingWrite).unwrap();
+        renderer.clear_recorded_commands();
+
+        renderer.update_buffer(buffer, 8, &[1.0f32; 4]).unwrap();
+        assert!(matches!(
+            renderer.update_buffer(buffer, 20, &[1.0f32; 4]),
+            Err(RendererError::FailedToUpdateBuffer { .. })
+        ));
+
+        renderer.orphan_buffer(buffer, 64).unwrap();
+        renderer.update_buffer(buffer, 32, &[1.0f32; 8]).unwrap();
+        assert_eq!(
+            renderer.recorded_commands(),
+            &[
+                RecordedCommand::UpdateBuffer {
+                    offset: 8,
+                    size: 16
+                },
+                RecordedCommand::OrphanBuffer { size: 64 },
+                RecordedCommand::UpdateBuffer {
+                    offset: 32,
+                    size: 32
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn read_buffers() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let data = [1u32, 2, 3, 4];
+        let buffer = Buffer::with_vertex(&mut renderer, &data, BufferUsage::DynamicRead).unwrap();
+        renderer.update_buffer(buffer, 4, &[7u32]).unwrap();
+
+        let read = renderer.read_buffer(buffer, 4..12).unwrap();
+        assert_eq!(read, [7, 0, 0, 0, 3, 0, 0, 0]);
+        assert_eq!(
+            renderer.recorded_commands().last(),
+            Some(&RecordedCommand::ReadBuffer { offset: 4, size: 8 })
+        );
+        assert!(matches!(
+            renderer.read_buffer(buffer, 8..20),
+            Err(RendererError::FailedToReadBuffer { .. })
+        ));
+
+        let write_only =
+            Buffer::with_vertex(&mut renderer, &data, BufferUsage::StaticWrite).unwrap();
+        assert!(matches!(
+            renderer.read_buffer(write_only, 0..4),
+            Err(RendererError::FailedToReadBuffer { .. })
+        ));
+    }
+
+    #[test]
+    fn record_frame() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mesh = triangle(&mut renderer);
+        let program = ShaderProgram::from_sources(&mut renderer, VS_SOURCE, FS_SOURCE).unwrap();
+        let material = renderer.create_material(program, &[]).unwrap();
+
+        renderer.screen_target().set_clear_flags(ClearFlags::COLOR);
+        renderer.clear_recorded_commands();
+
+        renderer.draw(mesh, material, &[]);
+        renderer.draw(mesh, material, &[]);
+        renderer.update();
+
+        let draws = renderer
+            .recorded_commands()
+            .iter()
+            .filter(|command| matches!(command, RecordedCommand::Draw { .. }))
+            .count();
+        assert_eq!(draws, 2);
+
+        assert_eq!(
+            renderer.recorded_commands()[..2],
+            [
+                RecordedCommand::Clear {
+                    flags: ClearFlags::COLOR,
+                    color: Color32::BLACK
+                },
+                RecordedCommand::BindMaterial { material, program },
+            ]
+        );
+
+        // nothing is left over for the next frame
+        renderer.clear_recorded_commands();
+        renderer.update();
+        assert_eq!(renderer.recorded_commands().len(), 1);
+    }
+
+    #[test]
+    fn uniform_blocks() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mesh = triangle(&mut renderer);
+        let vertex_source = "
+            layout(location = 0) in vec3 pos;
+            layout(std140) uniform Camera { mat4 view_projection; };
+            layout(std140) uniform Object {
+                mat4 model;
+            };
+            void main() { gl_Position = view_projection * model * vec4(pos, 1.0); }";
+        let program = ShaderProgram::from_sources(&mut renderer, vertex_source, FS_SOURCE).unwrap();
+        assert_eq!(
+            renderer.programs.get(program).unwrap().uniform_blocks(),
+            [("Camera".to_string(), 0), ("Object".to_string(), 1)]
+        );
+        let material = renderer.create_material(program, &[]).unwrap();
+        renderer.clear_recorded_commands();
+
+        let first = renderer.alloc_uniforms(&[1.0f32; 16]);
+        let second = renderer.alloc_uniforms(&[2.0f32; 16]);
+        renderer.draw(mesh, material, &[MaterialProperty::new("Object", &first)]);
+        renderer.draw(
+            mesh,
+            material,
+            &[MaterialProperty {
+                property: PropertyId::Location(1),
+                value: PropertyValue::Uniforms(second),
+            }],
+        );
+        renderer.update();
+
+        let bound: Vec<_> = renderer
+            .recorded_commands()
+            .iter()
+            .filter(|command| matches!(command, RecordedCommand::BindUniforms { .. }))
+            .cloned()
+            .collect();
+        assert_eq!(
+            bound,
+            [
+                RecordedCommand::BindUniforms {
+                    binding: 1,
+                    offset: 0,
+                    size: 64
+                },
+                RecordedCommand::BindUniforms {
+                    binding: 1,
+                    offset: 256,
+                    size: 64
+                },
+            ]
+        );
+        assert!(!renderer
+            .recorded_commands()
+            .iter()
+            .any(|command| matches!(command, RecordedCommand::SetUniform { .. })));
+    }
+
+    #[test]
+    fn draw_placeholders() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mesh = triangle(&mut renderer);
+        let program = ShaderProgram::from_sources(&mut renderer, VS_SOURCE, FS_SOURCE).unwrap();
+        let material = renderer.create_material(program, &[]).unwrap();
+        let placeholder_material = renderer.placeholder_material().unwrap();
+        let placeholder_mesh = renderer.placeholder_mesh().unwrap();
+
+        let drawn = |renderer: &Renderer<HeadlessContext>| -> Vec<_> {
+            renderer
+                .recorded_commands()
+                .iter()
+                .filter_map(|command| match command {
+                    RecordedCommand::Draw { mesh, material, .. } => Some((*mesh, *material)),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // the material still exists, but its program is gone
+        renderer.programs.remove(program);
+        renderer.draw(mesh, material, &[]);
+        renderer.update();
+        assert_eq!(drawn(&renderer), [(mesh, placeholder_material)]);
+
+        // the layout of the mesh is gone as well
+        renderer.clear_recorded_commands();
+        renderer.layouts.remove(mesh.vertex_layout);
+        renderer.draw(mesh, material, &[]);
+        renderer.update();
+        assert_eq!(drawn(&renderer), [(placeholder_mesh, placeholder_material)]);
+    }
+
+    #[test]
+    fn draw_placeholder_texture() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mesh = triangle(&mut renderer);
+        let program = ShaderProgram::from_sources(
+            &mut renderer,
+            VS_SOURCE,
+            "uniform sampler2D albedo;\nvoid main() {}",
+        )
+        .unwrap();
+        let texture = Texture::new(&mut renderer, &[0; 4], 1, 1, TextureFormat::Rgba8).unwrap();
+        let material = renderer
+            .create_material(program, &[MaterialProperty::new("albedo", &texture)])
+            .unwrap();
+        let placeholder = renderer.placeholder_texture().unwrap();
+        assert_eq!(renderer.textures.get(placeholder).unwrap().width, 16);
+
+        renderer.textures.remove(texture);
+        renderer.clear_recorded_commands();
+        renderer.draw(mesh, material, &[]);
+        renderer.update();
+
+        assert!(renderer
+            .recorded_commands()
+            .contains(&RecordedCommand::BindTexture {
+                unit: 0,
+                texture: placeholder,
+                sampler: None
+            }));
+    }
+
+    #[test]
+    fn draw_submeshes() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mesh = triangle(&mut renderer);
+        let program = ShaderProgram::from_sources(&mut renderer, VS_SOURCE, FS_SOURCE).unwrap();
+        let first = renderer.create_material(program, &[]).unwrap();
+        let second = renderer.create_material(program, &[]).unwrap();
+
+        let second_range = mesh.with_index_offset(3);
+        let multi_mesh = MultiMesh::new()
+            .with_submesh(mesh, first)
+            .with_submesh(second_range, second);
+        renderer.clear_recorded_commands();
+        renderer.draw_mesh_with_materials(&multi_mesh, &[]);
+        renderer.update();
+
+        let drawn: Vec<_> = renderer
+            .recorded_commands()
+            .iter()
+            .filter_map(|command| match command {
+                RecordedCommand::Draw { mesh, material, .. } => Some((*mesh, *material)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(drawn.len(), 2);
+        assert!(drawn.contains(&(mesh, first)));
+        assert!(drawn.contains(&(second_range, second)));
+    }
+
+    #[test]
+    fn layout_mismatch() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mesh = triangle(&mut renderer);
+        let program = ShaderProgram::from_sources(
+            &mut renderer,
+            "layout(location = 0) in vec3 pos;\nlayout(location = 1) in vec2 uv;",
+            FS_SOURCE,
+        )
+        .unwrap();
+        let material = renderer.create_material(program, &[]).unwrap();
+
+        let error = VertexLayout::validate(&renderer, mesh.vertex_layout, material).unwrap_err();
+        assert!(
+            matches!(&error, RendererError::LayoutMismatch { missing } if missing == &["uv at location 1"])
+        );
+
+        // the draw is skipped instead of reading undefined values
+        renderer.draw(mesh, material, &[]);
+        renderer.update();
+        assert_eq!(renderer.frame_stats().draw_calls, 0);
+
+        let program = ShaderProgram::from_sources(&mut renderer, VS_SOURCE, FS_SOURCE).unwrap();
+        let material = renderer.create_material(program, &[]).unwrap();
+        assert!(VertexLayout::validate(&renderer, mesh.vertex_layout, material).is_ok());
+        renderer.draw(mesh, material, &[]);
+        renderer.update();
+        assert_eq!(renderer.frame_stats().draw_calls, 1);
+    }
+
+    #[test]
+    fn layout_kinds() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let buffer =
+            Buffer::with_vertex(&mut renderer, &[0.0_f32; 15], BufferUsage::StaticRead).unwrap();
+        let semantics = [AttributeSemantic::Position, AttributeSemantic::UV(0)];
+        let layout = VertexLayout::new(
+            &mut renderer,
+            &[BufferAttributes::with_semantics(buffer, 0, &semantics)],
+        )
+        .unwrap();
+
+        // the missing w of the position reads 1
+        let program = ShaderProgram::from_sources(
+            &mut renderer,
+            "layout(location = 0) in vec4 pos;\nlayout(location = 1) in vec2 uv;",
+            FS_SOURCE,
+        )
+        .unwrap();
+        assert!(VertexLayout::validate_program(&renderer, layout, program).is_ok());
+
+        let program = ShaderProgram::from_sources(
+            &mut renderer,
+            "layout(location = 0) in vec3 pos;\nlayout(location = 1) in vec3 normal;",
+            FS_SOURCE,
+        )
+        .unwrap();
+        let error = VertexLayout::validate_program(&renderer, layout, program).unwrap_err();
+        assert!(matches!(
+            error,
+            RendererError::LayoutMismatch { missing }
+                if missing == ["normal at location 1 with 3 components, not 2"]
+        ));
+    }
+
+    #[test]
+    fn location_overrides() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let buffer =
+            Buffer::with_vertex(&mut renderer, &[0.0_f32; 15], BufferUsage::StaticRead).unwrap();
+        let semantics = [AttributeSemantic::Position, AttributeSemantic::UV(7)];
+        let attributes = [BufferAttributes::with_semantics(buffer, 0, &semantics)];
+
+        // UV(7) has no default location
+        assert!(matches!(
+            VertexLayout::new(&mut renderer, &attributes),
+            Err(RendererError::AttributeHasNoLocation { .. })
+        ));
+
+        let locations = [(AttributeSemantic::UV(7), 5)];
+        let layout = VertexLayout::with_locations(&mut renderer, &attributes, &locations).unwrap();
+        assert_eq!(
+            renderer.layouts.get(layout).unwrap().attributes(),
+            [
+                (0, crate::VertexAttributeKind::Vec3),
+                (5, crate::VertexAttributeKind::Vec2)
+            ]
+        );
+
+        // the renderer's config applies to all following layouts
+        let mut config = crate::VertexLayoutConfig::default();
+        config.overrides.push((AttributeSemantic::UV(7), 6));
+        renderer.set_layout_config(config);
+        let layout = VertexLayout::new(&mut renderer, &attributes).unwrap();
+        assert_eq!(
+            renderer.layouts.get(layout).unwrap().attributes()[1],
+            (6, crate::VertexAttributeKind::Vec2)
+        );
+
+        // explicit locations of a layout beat the overrides of the renderer's config
+        let layout = VertexLayout::with_locations(&mut renderer, &attributes, &locations).unwrap();
+        assert_eq!(
+            renderer.layouts.get(layout).unwrap().attributes()[1],
+            (5, crate::VertexAttributeKind::Vec2)
+        );
+    }
+
+    #[test]
+    fn property_validation() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let program = ShaderProgram::from_sources(&mut renderer, VS_SOURCE, FS_SOURCE).unwrap();
+        let color = [1.0_f32; 4];
+        let material = renderer
+            .create_material(program, &[MaterialProperty::new("color", &color)])
+            .unwrap();
+
+        assert!(matches!(
+            renderer.create_material(program, &[MaterialProperty::new("tint", &color)]),
+            Err(RendererError::UniformNotFound { .. })
+        ));
+        let error = renderer
+            .update_material(material, &[MaterialProperty::new("color", &[0.0_f32; 3])])
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Property Name: color expected Vec4 (4 f32), got 3 f32"
+        );
+        // a partial array is fine, more than the array holds is not
+        let bones = [1.0_f32; 32];
+        renderer
+            .update_material(material, &[MaterialProperty::new("bones", &bones)])
+            .unwrap();
+        let bones = [1.0_f32; 80];
+        let bones = MaterialProperty::new("bones", &bones);
+        assert!(renderer.update_material(material, &[bones]).is_err());
+
+        // the failed updates didn't change anything
+        let data = &renderer.materials.get(material).unwrap().data;
+        assert_eq!(data[..16], 1.0_f32.to_le_bytes().repeat(4));
+        assert_eq!(data[16 + 32 * 4..20 + 32 * 4], [0; 4]);
+
+        // lenient updates cut off what doesn't fit
+        renderer.set_property_validation(PropertyValidation::Lenient);
+        let bones = [2.0_f32; 80];
+        let bones = MaterialProperty::new("bones", &bones);
+        renderer.update_material(material, &[bones]).unwrap();
+        let data = &renderer.materials.get(material).unwrap().data;
+        assert_eq!(data[16 + 63 * 4..16 + 64 * 4], 2.0_f32.to_le_bytes());
+        assert_eq!(data[16 + 64 * 4..16 + 65 * 4], [0; 4]);
+    }
+
+    #[test]
+    fn bind_attribute_names() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        renderer.bind_attribute_name(AttributeSemantic::Position, "a_position");
+        renderer.bind_attribute_name(AttributeSemantic::UV(0), "a_uv");
+        let program = ShaderProgram::from_sources(
+            &mut renderer,
+            "in vec2 a_uv;\nin vec3 a_position;\nlayout(location = 3) in vec4 a_color;",
+            FS_SOURCE,
+        )
+        .unwrap();
+
+        let attributes = renderer.program_attributes(program).unwrap();
+        let locations = attributes
+            .iter()
+            .map(|attribute| (attribute.name.as_str(), attribute.location))
+            .collect::<Vec<_>>();
+        assert_eq!(locations, [("a_position", 0), ("a_uv", 1), ("a_color", 3)]);
+
+        // the mesh only has positions, so it fits a program with just the position bound
+        let mesh = triangle(&mut renderer);
+        let program =
+            ShaderProgram::from_sources(&mut renderer, "in vec3 a_position;", FS_SOURCE).unwrap();
+        let material = renderer.create_material(program, &[]).unwrap();
+        assert!(VertexLayout::validate(&renderer, mesh.vertex_layout, material).is_ok());
+    }
+
+    /// Counts the allocations of the current thread while enabled, so tests running in parallel
+    /// don't interfere.
+    struct CountingAllocator;
+
+    thread_local! {
+        static COUNTING: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+        static ALLOCATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    unsafe impl std::alloc::GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            if COUNTING.with(|counting| counting.get()) {
+                ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+            }
+            unsafe { std::alloc::System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            unsafe { std::alloc::System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn count_allocations(f: impl FnOnce()) -> usize {
+        ALLOCATIONS.with(|allocations| allocations.set(0));
+        COUNTING.with(|counting| counting.set(true));
+        f();
+        COUNTING.with(|counting| counting.set(false));
+        ALLOCATIONS.with(|allocations| allocations.get())
+    }
+
+    #[test]
+    fn steady_state_allocations() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mesh = triangle(&mut renderer);
+        let program = ShaderProgram::from_sources(&mut renderer, VS_SOURCE, FS_SOURCE).unwrap();
+        let materials = [
+            renderer.create_material(program, &[]).unwrap(),
+            renderer.create_material(program, &[]).unwrap(),
+        ];
+        let color = [1.0_f32, 0.0, 0.0, 1.0];
+        let overrides = [MaterialProperty::new("color", &color)];
+
+        let frame = |renderer: &mut Renderer<HeadlessContext>, properties| {
+            for i in 0..500 {
+                renderer.draw_at_depth(mesh, materials[i % 2], i as f32, properties);
+            }
+        };
+
+        // the first frames grow the buffers to their steady state size
+        for _ in 0..2 {
+            frame(&mut renderer, &overrides);
+            renderer.update();
+            renderer.clear_recorded_commands();
+        }
+
+        // overrides are copied into the frame arena
+        assert_eq!(count_allocations(|| frame(&mut renderer, &overrides)), 0);
+        renderer.update();
+        renderer.clear_recorded_commands();
+
+        // the recorded SetUniform commands own their values, so the update is measured without
+        // overrides
+        let allocations = count_allocations(|| {
+            frame(&mut renderer, &[]);
+            renderer.update();
+        });
+        assert_eq!(allocations, 0);
+        assert_eq!(renderer.frame_stats().draw_calls, 500);
+    }
+
+    #[test]
+    fn draw_batches() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mesh = triangle(&mut renderer);
+        let program = ShaderProgram::from_sources(&mut renderer, VS_SOURCE, FS_SOURCE).unwrap();
+        let first = renderer.create_material(program, &[]).unwrap();
+        let second = renderer.create_material(program, &[]).unwrap();
+
+        // one batch per "thread", large enough to build the commands in parallel if enabled
+        let mut batches = [DrawBatch::default(), DrawBatch::default()];
+        for _ in 0..crate::renderer::PARALLEL_THRESHOLD {
+            batches[0].draw(mesh, second);
+        }
+        batches[1].draw(mesh, first);
+
+        renderer.clear_recorded_commands();
+        renderer.draw_batches(&batches);
+        renderer.update();
+
+        let binds: Vec<_> = renderer
+            .recorded_commands()
+            .iter()
+            .filter_map(|command| match command {
+                RecordedCommand::BindMaterial { material, .. } => Some(*material),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(binds, [first, second]);
+        assert_eq!(
+            renderer.frame_stats().draw_calls as usize,
+            crate::renderer::PARALLEL_THRESHOLD + 1
+        );
+    }
+
+    #[test]
+    fn texture_samplers() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mesh = triangle(&mut renderer);
+        let program = ShaderProgram::from_sources(
+            &mut renderer,
+            VS_SOURCE,
+            "uniform sampler2D albedo;\nuniform sampler2D normals;\nvoid main() {}",
+        )
+        .unwrap();
+
+        let face = [0_u8; 4];
+        let texture = Texture::cubemap(&mut renderer, [&face; 6], 1, TextureFormat::Rgba8).unwrap();
+        let pixelated = Sampler::new(&mut renderer, &SamplerDescription::nearest()).unwrap();
+        let clamped = SamplerDescription::default().with_wrap(WrapMode::ClampToEdge);
+        let clamped = Sampler::new(&mut renderer, &clamped).unwrap();
+        assert_eq!(
+            renderer.samplers.get(clamped).unwrap().description.wrap_v,
+            WrapMode::ClampToEdge
+        );
+
+        // the same texture, sampled differently
+        let first = renderer
+            .create_material(
+                program,
+                &[
+                    MaterialProperty::new("normals", &texture),
+                    MaterialProperty::new("albedo", &(texture, pixelated)),
+                ],
+            )
+            .unwrap();
+        let second = renderer
+            .create_material(
+                program,
+                &[MaterialProperty::new("albedo", &(texture, clamped))],
+            )
+            .unwrap();
+
+        // rebinding keeps the texture unit, the sampler uniform stores it
+        renderer
+            .update_material(first, &[MaterialProperty::new("normals", &texture)])
+            .unwrap();
+        let material = renderer.materials.get(first).unwrap();
+        let albedo = &renderer.programs.get(program).unwrap().uniforms()[0];
+        assert_eq!(material.textures.len(), 2);
+        assert_eq!(
+            material.data[albedo.offset..albedo.offset + 4],
+            1_i32.to_le_bytes()
+        );
+
+        renderer.clear_recorded_commands();
+        renderer.draw(mesh, first, &[]);
+        // textures can't be overridden per draw
+        renderer.draw(mesh, second, &[MaterialProperty::new("albedo", &texture)]);
+        renderer.update();
+
+        let textures: Vec<_> = renderer
+            .recorded_commands()
+            .iter()
+            .filter(|command| {
+                matches!(
+                    command,
+                    RecordedCommand::BindTexture { .. } | RecordedCommand::SetUniform { .. }
+                )
+            })
+            .cloned()
+            .collect();
+        assert_eq!(
+            textures,
+            [
+                RecordedCommand::BindTexture {
+                    unit: 0,
+                    texture,
+                    sampler: None
+                },
+                RecordedCommand::BindTexture {
+                    unit: 1,
+                    texture,
+                    sampler: Some(pixelated)
+                },
+                RecordedCommand::BindTexture {
+                    unit: 0,
+                    texture,
+                    sampler: Some(clamped)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn skybox() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mesh = triangle(&mut renderer);
+        let program = ShaderProgram::from_sources(
+            &mut renderer,
+            VS_SOURCE,
+            "uniform samplerCube skybox;\nvoid main() {}",
+        )
+        .unwrap();
+        let uniforms = renderer.programs.get(program).unwrap().uniforms();
+        assert_eq!(uniforms[0].kind, UniformKind::SamplerCube);
+
+        let face = [0_u8; 8 * 8 * 4];
+        let skybox =
+            Texture::cubemap(&mut renderer, [&face; 6], 8, TextureFormat::Srgb8Alpha8).unwrap();
+        assert_eq!(renderer.textures.get(skybox).unwrap().width, 8);
+        assert_eq!(
+            renderer.textures.get(skybox).unwrap().swizzle,
+            Swizzle::IDENTITY
+        );
+        let red = Swizzle::splat(SwizzleSource::Red);
+        Texture::set_swizzle(&mut renderer, skybox, red).unwrap();
+        assert_eq!(renderer.textures.get(skybox).unwrap().swizzle, red);
+        assert!(Texture::set_swizzle(&mut renderer, Handle::new(), red).is_err());
+        let material = renderer
+            .create_material(program, &[MaterialProperty::new("skybox", &skybox)])
+            .unwrap();
+
+        // cubemaps that don't exist (anymore) are skipped, the placeholder is a 2D texture
+        let missing = renderer
+            .create_material(
+                program,
+                &[MaterialProperty::new("skybox", &Handle::<Texture>::new())],
+            )
+            .unwrap();
+
+        renderer.clear_recorded_commands();
+        renderer.draw(mesh, material, &[]);
+        renderer.draw(mesh, missing, &[]);
+        renderer.update();
+
+        let textures: Vec<_> = renderer
+            .recorded_commands()
+            .iter()
+            .filter(|command| matches!(command, RecordedCommand::BindTexture { .. }))
+            .collect();
+        assert_eq!(
+            textures,
+            [&RecordedCommand::BindTexture {
+                unit: 0,
+                texture: skybox,
+                sampler: None
+            }]
+        );
+    }
+
+    #[test]
+    fn sort_by_material() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mesh = triangle(&mut renderer);
+        let program = ShaderProgram::from_sources(&mut renderer, VS_SOURCE, FS_SOURCE).unwrap();
+        let first = renderer.create_material(program, &[]).unwrap();
+        let second = renderer.create_material(program, &[]).unwrap();
+
+        renderer.clear_recorded_commands();
+        renderer.draw(mesh, second, &[]);
+        renderer.draw(mesh, first, &[]);
+        renderer.draw(mesh, second, &[]);
+        renderer.update();
+
+        let binds: Vec<_> = renderer
+            .recorded_commands()
+            .iter()
+            .filter_map(|command| match command {
+                RecordedCommand::BindMaterial { material, .. } => Some(*material),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(binds, [first, second]);
+    }
+
+    #[test]
+    fn clone_material() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mesh = triangle(&mut renderer);
+        let program = ShaderProgram::from_sources(&mut renderer, VS_SOURCE, FS_SOURCE).unwrap();
+        let red = [1.0_f32, 0.0, 0.0, 1.0];
+        let template = renderer
+            .create_material(program, &[MaterialProperty::new("color", &red)])
+            .unwrap();
+        let clone = renderer.clone_material(template, &[]).unwrap();
+        let green = [0.0_f32, 1.0, 0.0, 1.0];
+        let tinted = renderer
+            .clone_material(template, &[MaterialProperty::new("color", &green)])
+            .unwrap();
+
+        let data = |renderer: &Renderer<HeadlessContext>, material| {
+            renderer.materials.get(material).unwrap().data.clone()
+        };
+        assert!(std::sync::Arc::ptr_eq(
+            &data(&renderer, template),
+            &data(&renderer, clone)
+        ));
+        assert_eq!(data(&renderer, template)[..4], 1.0_f32.to_le_bytes());
+        assert_eq!(data(&renderer, tinted)[..4], 0.0_f32.to_le_bytes());
+
+        // the unchanged clone doesn't need its own bind
+        renderer.clear_recorded_commands();
+        renderer.draw(mesh, template, &[]);
+        renderer.draw(mesh, clone, &[]);
+        renderer.draw(mesh, tinted, &[]);
+        renderer.update();
+        let binds: Vec<_> = renderer
+            .recorded_commands()
+            .iter()
+            .filter_map(|command| match command {
+                RecordedCommand::BindMaterial { material, .. } => Some(*material),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(binds, [template, tinted]);
+
+        // changing the clone copies the data
+        renderer
+            .update_material(clone, &[MaterialProperty::new("color", &green)])
+            .unwrap();
+        assert!(!std::sync::Arc::ptr_eq(
+            &data(&renderer, template),
+            &data(&renderer, clone)
+        ));
+        assert_eq!(data(&renderer, template)[..4], 1.0_f32.to_le_bytes());
+    }
+
+    #[test]
+    fn debug_view() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mesh = triangle(&mut renderer);
+        let vs_source = r##"
+            #version 330 core
+            layout(location = 0) in vec3 pos;
+            uniform mat4 model;
+            void main() { gl_Position = model * vec4(pos, 1.0); }"##;
+        let program = ShaderProgram::from_sources(&mut renderer, vs_source, FS_SOURCE).unwrap();
+        let model: [f32; 16] = std::array::from_fn(|value| value as f32);
+        let material = renderer
+            .create_material(program, &[MaterialProperty::new("model", &model)])
+            .unwrap();
+
+        renderer.set_debug_view(DebugView::Overdraw).unwrap();
+        renderer.clear_recorded_commands();
+        renderer.draw(mesh, material, &[]);
+        renderer.update();
+
+        let debug_material = renderer.debug_materials[&DebugView::Overdraw];
+        let commands = renderer.recorded_commands();
+        assert!(commands.iter().any(|command| matches!(
+            command,
+            RecordedCommand::Draw { material, .. } if *material == debug_material
+        )));
+        // the debug material takes over the transform of the replaced material
+        assert!(commands.iter().any(|command| matches!(
+            command,
+            RecordedCommand::SetUniform { values, .. } if *values == model
+        )));
+
+        // the triangle has no normals to show
+        renderer.set_debug_view(DebugView::Normals).unwrap();
+        renderer.clear_recorded_commands();
+        renderer.draw(mesh, material, &[]);
+        renderer.update();
+        assert!(!renderer
+            .recorded_commands()
+            .iter()
+            .any(|command| matches!(command, RecordedCommand::Draw { .. })));
+
+        renderer.set_debug_view(DebugView::Off).unwrap();
+        renderer.clear_recorded_commands();
+        renderer.draw(mesh, material, &[]);
+        renderer.update();
+        assert!(renderer.recorded_commands().iter().any(|command| matches!(
+            command,
+            RecordedCommand::Draw { material: drawn, .. } if *drawn == material
+        )));
+    }
+
+    #[test]
+    fn frame_stats() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mesh = triangle(&mut renderer);
+        let program = ShaderProgram::from_sources(&mut renderer, VS_SOURCE, FS_SOURCE).unwrap();
+        let first = renderer.create_material(program, &[]).unwrap();
+        let second = renderer.create_material(program, &[]).unwrap();
+
+        renderer.draw(mesh, second, &[]);
+        renderer.draw(mesh, first, &[]);
+        renderer.draw(mesh, second, &[]);
+        renderer.update();
+
+        let stats = renderer.frame_stats();
+        assert_eq!(stats.draw_calls, 3);
+        assert_eq!(stats.material_binds, 2);
+        assert_eq!(stats.material(first).unwrap().draw_calls, 1);
+        assert_eq!(stats.material(second).unwrap().draw_calls, 2);
+        assert_eq!(stats.layer(0).unwrap().instances, 3);
+        assert_eq!(
+            stats.material(first).unwrap().gpu_time,
+            Some(std::time::Duration::ZERO)
+        );
+
+        // stats only cover the last frame
+        renderer.update();
+        assert_eq!(renderer.frame_stats(), &FrameStats::default());
+    }
+
+    #[test]
+    fn gpu_scopes() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mesh = triangle(&mut renderer);
+        let program = ShaderProgram::from_sources(&mut renderer, VS_SOURCE, FS_SOURCE).unwrap();
+        let material = renderer.create_material(program, &[]).unwrap();
+
+        renderer.begin_gpu_scope("shadow pass");
+        renderer.draw(mesh, material, &[]);
+        renderer.update();
+        // beginning a scope ends the open one
+        renderer.begin_gpu_scope("lighting");
+        renderer.begin_gpu_scope("post processing");
+        renderer.end_gpu_scope();
+        renderer.end_gpu_scope();
+        renderer.update();
+
+        let stats = renderer.frame_stats();
+        assert_eq!(stats.draw_calls, 0);
+        let names: Vec<_> = stats.gpu_scopes.iter().map(|scope| &scope.name).collect();
+        assert_eq!(names, ["shadow pass", "lighting", "post processing"]);
+        assert_eq!(stats.gpu_scope("lighting").unwrap().nanoseconds, 0);
+
+        // the last results stay until there are new ones
+        renderer.update();
+        assert_eq!(renderer.frame_stats().gpu_scopes.len(), 3);
+        assert_eq!(
+            renderer
+                .recorded_commands()
+                .iter()
+                .filter(|command| **command == RecordedCommand::EndGpuScope)
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn queue_order() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mesh = triangle(&mut renderer);
+        let program = ShaderProgram::from_sources(&mut renderer, VS_SOURCE, FS_SOURCE).unwrap();
+        let opaque = renderer.create_material(program, &[]).unwrap();
+        let transparent = renderer.create_material(program, &[]).unwrap();
+        let overlay = renderer.create_material(program, &[]).unwrap();
+        renderer.set_render_queue(transparent, RenderQueue::Transparent);
+        renderer.set_render_queue(overlay, RenderQueue::Overlay);
+
+        renderer.draw_at_depth(mesh, overlay, 1.0, &[]);
+        renderer.draw_at_depth(mesh, transparent, 1.0, &[]);
+        renderer.draw_at_depth(mesh, opaque, 5.0, &[]);
+        renderer.draw_at_depth(mesh, transparent, 5.0, &[]);
+        renderer.draw_at_depth(mesh, overlay, 0.0, &[]);
+        renderer.draw_at_depth(mesh, opaque, 1.0, &[]);
+        renderer.draw_at_depth(mesh, transparent, 3.0, &[]);
+        renderer.update();
+
+        let draws: Vec<_> = renderer
+            .recorded_commands()
+            .iter()
+            .filter_map(|command| match command {
+                RecordedCommand::Draw {
+                    material, depth, ..
+                } => Some((*material, *depth)),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            draws,
+            [
+                (opaque, 1.0),
+                (opaque, 5.0),
+                (transparent, 5.0),
+                (transparent, 3.0),
+                (transparent, 1.0),
+                (overlay, 1.0),
+                (overlay, 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn instance_overrides() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mesh = triangle(&mut renderer);
+        let program = ShaderProgram::from_sources(&mut renderer, VS_SOURCE, FS_SOURCE).unwrap();
+        let material = renderer.create_material(program, &[]).unwrap();
+        let tint = [1.0, 0.0, 0.0, 1.0];
+
+        renderer.clear_recorded_commands();
+        renderer.draw(mesh, material, &[MaterialProperty::new("color", &tint)]);
+        renderer.draw(mesh, material, &[MaterialProperty::new("unknown", &tint)]);
+        renderer.update();
+
+        let commands: Vec<_> = renderer
+            .recorded_commands()
+            .iter()
+            .filter(|command| !matches!(command, RecordedCommand::Draw { .. }))
+            .cloned()
+            .collect();
+
+        // the material gets bound again to undo the override of the first draw
+        assert_eq!(
+            commands[1..],
+            [
+                RecordedCommand::BindMaterial { material, program },
+                RecordedCommand::SetUniform {
+                    location: 0,
+                    values: tint.to_vec()
+                },
+                RecordedCommand::BindMaterial { material, program },
+            ]
+        );
+    }
+
+    #[test]
+    fn dispatch_compute() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let program = ComputeProgram::from_source(
+            &mut renderer,
+            "layout(local_size_x = 64) in;\nuniform float delta;\nvoid main() {}",
+        )
+        .unwrap();
+
+        // compute shaders can't be linked into graphics programs
+        let compute_shader = Shader::with_compute(&mut renderer, "void main() {}").unwrap();
+        assert!(ShaderProgram::new(&mut renderer, compute_shader, compute_shader).is_err());
+
+        renderer
+            .dispatch(program, 16, 1, 1, &[MaterialProperty::new("delta", &0.5)])
+            .unwrap();
+        renderer.memory_barrier(MemoryBarriers::VERTEX_BUFFER);
+
+        assert_eq!(
+            renderer.recorded_commands(),
+            &[
+                RecordedCommand::SetUniform {
+                    location: 0,
+                    values: vec![0.5]
+                },
+                RecordedCommand::Dispatch {
+                    program,
+                    groups: [16, 1, 1]
+                },
+                RecordedCommand::MemoryBarrier {
+                    barriers: MemoryBarriers::VERTEX_BUFFER
+                },
+            ]
+        );
+
+        renderer.compute_programs.remove(program);
+        assert!(renderer.dispatch(program, 1, 1, 1, &[]).is_err());
+    }
+
+    #[test]
+    fn read_pixels() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        renderer.screen_target().set_clear_color(Color32::RED);
+
+        let rect = crate::TextureRegion {
+            x: 0,
+            y: 0,
+            width: 2,
+            height: 1,
+        };
+        let pixels = renderer.screen_target().read_pixels(rect);
+        assert_eq!(pixels, [255, 0, 0, 255, 255, 0, 0, 255]);
+
+        let image = crate::Image {
+            width: 2,
+            height: 1,
+            pixels,
+        };
+        assert_eq!(image.pixel(1, 0), Some([255, 0, 0, 255]));
+        assert_eq!(image.pixel(2, 0), None);
+        assert_eq!(renderer.screenshot(), crate::Image::default());
+    }
+
+    #[test]
+    fn shadow_sampler() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mesh = triangle(&mut renderer);
+        let program = ShaderProgram::from_sources(
+            &mut renderer,
+            VS_SOURCE,
+            "uniform sampler2DShadow shadow_map;\nvoid main() {}",
+        )
+        .unwrap();
+        let uniforms = renderer.programs.get(program).unwrap().uniforms();
+        assert_eq!(uniforms[0].kind, UniformKind::Sampler2DShadow);
+        assert!(uniforms[0].kind.is_sampler());
+
+        let shadow_map = Texture::new(&mut renderer, &[0], 1, 1, TextureFormat::R8).unwrap();
+        let sampler = Sampler::new(&mut renderer, &SamplerDescription::shadow()).unwrap();
+        let material = renderer
+            .create_material(
+                program,
+                &[MaterialProperty::new("shadow_map", &(shadow_map, sampler))],
+            )
+            .unwrap();
+
+        renderer.clear_recorded_commands();
+        renderer.draw(mesh, material, &[]);
+        renderer.update();
+
+        assert!(renderer
+            .recorded_commands()
+            .contains(&RecordedCommand::BindTexture {
+                unit: 0,
+                texture: shadow_map,
+                sampler: Some(sampler),
+            }));
+    }
+
+    #[test]
+    fn debug_labels() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let texture = Texture::new(&mut renderer, &[0; 4], 1, 1, TextureFormat::Rgba8).unwrap();
+        let sampler = Sampler::new(&mut renderer, &SamplerDescription::default()).unwrap();
+        let program = ShaderProgram::from_sources(&mut renderer, VS_SOURCE, FS_SOURCE).unwrap();
+
+        assert!(Texture::set_label(&mut renderer, texture, "albedo").is_ok());
+        assert!(Sampler::set_label(&mut renderer, sampler, "linear").is_ok());
+        assert!(ShaderProgram::set_label(&mut renderer, program, "unlit").is_ok());
+
+        renderer.textures.remove(texture);
+        assert!(matches!(
+            Texture::set_label(&mut renderer, texture, "albedo"),
+            Err(RendererError::ResourceNotFound { .. })
+        ));
+
+        renderer.clear_recorded_commands();
+        renderer.push_debug_group("shadow pass");
+        renderer.pop_debug_group();
+        assert_eq!(
+            renderer.recorded_commands(),
+            [
+                RecordedCommand::PushDebugGroup {
+                    name: "shadow pass".to_string()
+                },
+                RecordedCommand::PopDebugGroup
+            ]
+        );
+    }
+
+    #[test]
+    fn stencil_sampler() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let program = ShaderProgram::from_sources(
+            &mut renderer,
+            VS_SOURCE,
+            "uniform usampler2D stencil;\nuniform isampler2D offsets;\nvoid main() {}",
+        )
+        .unwrap();
+        let uniforms = renderer.programs.get(program).unwrap().uniforms();
+        assert_eq!(uniforms[0].kind, UniformKind::USampler2D);
+        assert!(uniforms[0].kind.is_sampler());
+        assert_eq!(uniforms[1].kind, UniformKind::ISampler2D);
+        assert!(uniforms[1].kind.is_sampler());
+    }
+
+    #[test]
+    fn multisampled_sampler() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mesh = triangle(&mut renderer);
+        let program = ShaderProgram::from_sources(
+            &mut renderer,
+            VS_SOURCE,
+            "uniform sampler2DMS color;\nvoid main() {}",
+        )
+        .unwrap();
+        let uniforms = renderer.programs.get(program).unwrap().uniforms();
+        assert_eq!(uniforms[0].kind, UniformKind::Sampler2DMS);
+        assert!(uniforms[0].kind.is_sampler());
+
+        let color = Texture::multisampled(&mut renderer, 4, 4, 4, TextureFormat::Rgba8).unwrap();
+        let material = renderer
+            .create_material(program, &[MaterialProperty::new("color", &color)])
+            .unwrap();
+
+        renderer.clear_recorded_commands();
+        renderer.draw(mesh, material, &[]);
+        renderer.update();
+
+        assert!(renderer
+            .recorded_commands()
+            .contains(&RecordedCommand::BindTexture {
+                unit: 0,
+                texture: color,
+                sampler: None,
+            }));
+    }
+
+    #[test]
+    fn array_and_1d_samplers() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let program = ShaderProgram::from_sources(
+            &mut renderer,
+            VS_SOURCE,
+            "uniform sampler1D gradient;\nuniform samplerCubeArray probes;\n\
+             uniform samplerCubeArrayShadow shadows;\nvoid main() {}",
+        )
+        .unwrap();
+        let kinds: Vec<_> = renderer
+            .programs
+            .get(program)
+            .unwrap()
+            .uniforms()
+            .iter()
+            .map(|uniform| uniform.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                UniformKind::Sampler1D,
+                UniformKind::SamplerCubeArray,
+                UniformKind::SamplerCubeArrayShadow
+            ]
+        );
+        assert!(kinds.iter().all(UniformKind::is_sampler));
+    }
+
+    #[test]
+    fn capabilities() {
+        let renderer = Renderer::new_headless().unwrap();
+        let capabilities = renderer.capabilities();
+        assert_eq!(capabilities.max_texture_size, 16384);
+        assert!(capabilities.supports_compute && capabilities.extensions.is_empty());
+    }
+
+    #[test]
+    fn srgb_encode() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        assert!(!renderer.screen_target().srgb_encode());
+        renderer.screen_target().set_srgb_encode(true);
+        assert!(renderer.screen_target().srgb_encode());
     }
 }
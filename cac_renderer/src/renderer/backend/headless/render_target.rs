@@ -1,6 +1,7 @@
 pub(super) struct RenderTarget {
-    clear_color: crate::Color32,
-    clear_flags: crate::ClearFlags,
+    pub(super) clear_color: crate::Color32,
+    pub(super) clear_flags: crate::ClearFlags,
+    pub(super) srgb_encode: bool,
 }
 
 impl Default for RenderTarget {
@@ -8,6 +9,7 @@ impl Default for RenderTarget {
         Self {
             clear_color: crate::Color32::BLACK,
             clear_flags: crate::ClearFlags::NONE,
+            srgb_encode: false,
         }
     }
 }
@@ -24,4 +26,19 @@ impl crate::RenderTarget for RenderTarget {
     fn set_clear_flags(&mut self, flags: crate::renderer::render_target::ClearFlags) {
         self.clear_flags = flags
     }
+
+    fn set_srgb_encode(&mut self, encode: bool) {
+        self.srgb_encode = encode;
+    }
+
+    fn srgb_encode(&self) -> bool {
+        self.srgb_encode
+    }
+
+    /// Nothing is rasterized, so every pixel has the clear color.
+    fn read_pixels(&mut self, rect: crate::TextureRegion) -> Vec<u8> {
+        let (r, g, b, a) = self.clear_color.as_rgba();
+        let color = crate::Color8::from((r, g, b, a));
+        [color.r, color.g, color.b, color.a].repeat(rect.width as usize * rect.height as usize)
+    }
 }
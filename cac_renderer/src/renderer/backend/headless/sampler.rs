@@ -0,0 +1,22 @@
+use crate::{
+    renderer::sampler::{CreateSampler, SamplerDescription},
+    RendererError,
+};
+
+/// Headless Sampler
+///
+/// Only keeps the description, so tests can check what a real backend would have created.
+#[derive(Debug)]
+pub struct HeadlessSampler {
+    // nothing samples in the headless backend, it's only read by tests
+    #[allow(dead_code)]
+    pub(super) description: SamplerDescription,
+}
+
+impl CreateSampler for HeadlessSampler {
+    fn new(description: &SamplerDescription) -> Result<Self, RendererError> {
+        Ok(Self {
+            description: *description,
+        })
+    }
+}
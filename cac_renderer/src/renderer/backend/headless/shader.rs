@@ -0,0 +1,431 @@
+use std::collections::HashMap;
+
+use crate::{
+    renderer::{
+        compute::CreateComputeProgram,
+        shader::{CreateShader, CreateShaderProgram, Uniform},
+        AttributeDescription, ProgramBinary, UniformDescription, UniformKind,
+    },
+    RendererError,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) enum ShaderKind {
+    Vertex,
+    Fragment,
+    Compute,
+    TessControl,
+    TessEvaluation,
+}
+
+/// Headless Shader
+///
+/// Nothing gets compiled, the source is only kept around to reflect the uniforms when the program
+/// is "linked". Sources with unbalanced brackets fail like on a driver, so the handling of compile
+/// errors can be tested.
+#[derive(Debug)]
+pub struct HeadlessShader {
+    kind: ShaderKind,
+    source: String,
+}
+
+impl HeadlessShader {
+    fn new(kind: ShaderKind, source: &str) -> Result<Self, RendererError> {
+        check_brackets(source)?;
+        Ok(Self {
+            kind,
+            source: source.to_string(),
+        })
+    }
+}
+
+/// Checks that the brackets outside of line comments are balanced.
+fn check_brackets(source: &str) -> Result<(), RendererError> {
+    let mut open = Vec::new();
+    let code = source
+        .lines()
+        .flat_map(|line| line.split("//").next().unwrap_or_default().chars());
+    for character in code {
+        let expected = match character {
+            '(' | '[' | '{' => {
+                open.push(character);
+                continue;
+            }
+            ')' => '(',
+            ']' => '[',
+            '}' => '{',
+            _ => continue,
+        };
+        if open.pop() != Some(expected) {
+            return Err(RendererError::FailedToCompileShader {
+                error: format!("Unexpected {character}"),
+            });
+        }
+    }
+
+    match open.pop() {
+        Some(character) => Err(RendererError::FailedToCompileShader {
+            error: format!("Unclosed {character}"),
+        }),
+        None => Ok(()),
+    }
+}
+
+impl CreateShader for HeadlessShader {
+    fn with_vertex(source: &str) -> Result<Self, RendererError> {
+        Self::new(ShaderKind::Vertex, source)
+    }
+
+    fn with_fragment(source: &str) -> Result<Self, RendererError> {
+        Self::new(ShaderKind::Fragment, source)
+    }
+
+    fn with_compute(source: &str) -> Result<Self, RendererError> {
+        Self::new(ShaderKind::Compute, source)
+    }
+
+    fn with_tess_control(source: &str) -> Result<Self, RendererError> {
+        Self::new(ShaderKind::TessControl, source)
+    }
+
+    fn with_tess_evaluation(source: &str) -> Result<Self, RendererError> {
+        Self::new(ShaderKind::TessEvaluation, source)
+    }
+}
+
+/// Headless ShaderProgram
+///
+/// Uniforms are reflected by scanning the sources for plain `uniform <kind> <name>;`
+/// declarations. Locations are assigned in order of appearance.
+/// Vertex inputs are reflected from the `in <kind> <name>;` declarations of the vertex shader, at
+/// their `layout(location = N)`, their bound location or in order of appearance.
+/// The "binary" of the program are its sources, so loading it reflects them again. The stages
+/// aren't known anymore then, so the inputs of the other stages count as vertex inputs too.
+#[derive(Debug)]
+pub struct HeadlessShaderProgram {
+    source: String,
+    data_size: usize,
+    uniforms: Vec<UniformDescription>,
+    locations: HashMap<String, u32>,
+    attributes: Vec<AttributeDescription>,
+    uniform_blocks: Vec<(String, u32)>,
+}
+
+impl CreateShaderProgram for HeadlessShaderProgram {
+    type VertexShader = HeadlessShader;
+    type FragmentShader = HeadlessShader;
+    type TessellationShader = HeadlessShader;
+
+    fn new(
+        vertex_shader: &Self::VertexShader,
+        fragment_shader: &Self::FragmentShader,
+        attribute_locations: &[(String, u8)],
+    ) -> Result<Self, RendererError> {
+        check_kind(vertex_shader, ShaderKind::Vertex, "vertex_shader")?;
+        check_kind(fragment_shader, ShaderKind::Fragment, "fragment_shader")?;
+
+        Ok(Self::reflect(
+            &[vertex_shader, fragment_shader],
+            attribute_locations,
+        ))
+    }
+
+    fn with_tessellation(
+        vertex_shader: &Self::VertexShader,
+        tess_control_shader: &Self::TessellationShader,
+        tess_evaluation_shader: &Self::TessellationShader,
+        fragment_shader: &Self::FragmentShader,
+        attribute_locations: &[(String, u8)],
+    ) -> Result<Self, RendererError> {
+        check_kind(vertex_shader, ShaderKind::Vertex, "vertex_shader")?;
+        check_kind(
+            tess_control_shader,
+            ShaderKind::TessControl,
+            "tess_control_shader",
+        )?;
+        check_kind(
+            tess_evaluation_shader,
+            ShaderKind::TessEvaluation,
+            "tess_evaluation_shader",
+        )?;
+        check_kind(fragment_shader, ShaderKind::Fragment, "fragment_shader")?;
+
+        Ok(Self::reflect(
+            &[
+                vertex_shader,
+                tess_control_shader,
+                tess_evaluation_shader,
+                fragment_shader,
+            ],
+            attribute_locations,
+        ))
+    }
+
+    fn get_binary(&self) -> Result<ProgramBinary, RendererError> {
+        Ok(ProgramBinary {
+            format: 0,
+            data: self.source.as_bytes().to_vec(),
+        })
+    }
+
+    fn from_binary(binary: &ProgramBinary) -> Result<Self, RendererError> {
+        let source = std::str::from_utf8(&binary.data).map_err(|error| {
+            RendererError::FailedToLinkProgram {
+                error: format!("Invalid program binary: {error}"),
+            }
+        })?;
+
+        let shader = HeadlessShader {
+            kind: ShaderKind::Vertex,
+            source: source.to_string(),
+        };
+        Ok(Self::reflect(&[&shader], &[]))
+    }
+}
+
+fn check_kind(
+    shader: &HeadlessShader,
+    kind: ShaderKind,
+    argument: &str,
+) -> Result<(), RendererError> {
+    if shader.kind == kind {
+        Ok(())
+    } else {
+        Err(RendererError::FailedToLinkProgram {
+            error: format!("Argument {argument} is not a {kind:?}Shader"),
+        })
+    }
+}
+
+impl CreateComputeProgram for HeadlessShaderProgram {
+    type ComputeShader = HeadlessShader;
+
+    fn new(compute_shader: &Self::ComputeShader) -> Result<Self, RendererError> {
+        check_kind(compute_shader, ShaderKind::Compute, "compute_shader")?;
+
+        Ok(Self::reflect(&[compute_shader], &[]))
+    }
+}
+
+impl HeadlessShaderProgram {
+    fn reflect(shaders: &[&HeadlessShader], attribute_locations: &[(String, u8)]) -> Self {
+        let mut uniforms: Vec<UniformDescription> = Vec::new();
+        let mut uniform_blocks: Vec<(String, u32)> = Vec::new();
+        let mut data_size = 0;
+
+        for line in shaders.iter().flat_map(|shader| shader.source.lines()) {
+            if let Some(name) = parse_uniform_block(line) {
+                if !uniform_blocks.iter().any(|(block, _)| *block == name) {
+                    let binding = uniform_blocks.len() as u32;
+                    uniform_blocks.push((name, binding));
+                }
+                continue;
+            }
+            if let Some((name, kind, count)) = parse_uniform(line) {
+                // uniforms shared between the stages are the same uniform
+                if uniforms.iter().any(|uniform| uniform.name == name) {
+                    continue;
+                }
+
+                let size = uniform_size_from_kind(kind, count);
+                uniforms.push(UniformDescription {
+                    name,
+                    location: uniforms.len() as u32,
+                    kind,
+                    count: count as u32,
+                    size,
+                    offset: data_size,
+                });
+                data_size += size;
+            }
+        }
+
+        let locations = uniforms
+            .iter()
+            .map(|uniform| (uniform.name.clone(), uniform.location))
+            .collect();
+
+        let mut attributes: Vec<AttributeDescription> = Vec::new();
+        let vertex_sources = shaders
+            .iter()
+            .filter(|shader| shader.kind == ShaderKind::Vertex)
+            .flat_map(|shader| shader.source.lines());
+        for line in vertex_sources {
+            if let Some((location, name, kind, count)) = parse_attribute(line) {
+                let bound = attribute_locations
+                    .iter()
+                    .find_map(|(bound, location)| (*bound == name).then_some(u32::from(*location)));
+                let location = location.or(bound).unwrap_or_else(|| {
+                    attributes
+                        .iter()
+                        .map(|attribute| attribute.locations().end)
+                        .max()
+                        .unwrap_or_default()
+                });
+                attributes.push(AttributeDescription {
+                    name,
+                    location,
+                    kind,
+                    count: count as u32,
+                });
+            }
+        }
+        attributes.sort_by_key(|attribute| attribute.location);
+
+        let source = shaders
+            .iter()
+            .map(|shader| shader.source.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Self {
+            source,
+            data_size,
+            uniforms,
+            locations,
+            attributes,
+            uniform_blocks,
+        }
+    }
+}
+
+impl Uniform for HeadlessShaderProgram {
+    fn get_uniform_location(&self, name: &str) -> Option<u32> {
+        self.locations.get(name).copied()
+    }
+
+    fn data_size(&self) -> usize {
+        self.data_size
+    }
+
+    fn set_uniform_f32(&mut self, _location: u32, _value: &[f32]) {}
+
+    fn uniforms(&self) -> &Vec<UniformDescription> {
+        &self.uniforms
+    }
+
+    fn attributes(&self) -> &[AttributeDescription] {
+        &self.attributes
+    }
+
+    fn set_uniform_data(&mut self, _data: &[u8]) {}
+
+    fn uniform_blocks(&self) -> &[(String, u32)] {
+        &self.uniform_blocks
+    }
+}
+
+/// Returns name, kind and array count of a `uniform <kind> <name>;` declaration.
+fn parse_uniform(line: &str) -> Option<(String, UniformKind, usize)> {
+    parse_declaration(line.trim().strip_prefix("uniform ")?)
+}
+
+/// Returns the name of a `layout(std140) uniform <name> {` block declaration. The layout is
+/// optional, the brace can be on the next line.
+fn parse_uniform_block(line: &str) -> Option<String> {
+    let line = line.trim();
+    let line = match line.strip_prefix("layout") {
+        Some(layout) => layout.split_once(')')?.1.trim_start(),
+        None => line,
+    };
+
+    let block = line.strip_prefix("uniform ")?;
+    let block = block.split_once('{').map_or(block, |(name, _)| name).trim();
+    let is_name = |name: &str| {
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+    is_name(block).then(|| block.to_string())
+}
+
+/// Returns the explicit location, name, kind and array count of a
+/// `layout(location = N) in <kind> <name>;` declaration. The layout is optional.
+fn parse_attribute(line: &str) -> Option<(Option<u32>, String, UniformKind, usize)> {
+    let line = line.trim();
+    let (location, declaration) = match line.strip_prefix("layout") {
+        Some(layout) => {
+            let (qualifiers, declaration) =
+                layout.trim_start().strip_prefix('(')?.split_once(')')?;
+            let location = qualifiers.split(',').find_map(|qualifier| {
+                let (key, value) = qualifier.split_once('=')?;
+                (key.trim() == "location").then(|| value.trim().parse().ok())?
+            });
+            (location, declaration.trim_start())
+        }
+        None => (None, line),
+    };
+
+    let (name, kind, count) = parse_declaration(declaration.strip_prefix("in ")?)?;
+    if kind.is_sampler() {
+        return None;
+    }
+    Some((location, name, kind, count))
+}
+
+/// Returns name, kind and array count of a `<kind> <name>;` declaration.
+fn parse_declaration(declaration: &str) -> Option<(String, UniformKind, usize)> {
+    let mut tokens = declaration.split_whitespace();
+
+    let mut kind = tokens.next()?;
+    if matches!(kind, "lowp" | "mediump" | "highp") {
+        kind = tokens.next()?;
+    }
+
+    let kind = match kind {
+        "float" => UniformKind::F32,
+        "vec2" => UniformKind::Vec2,
+        "vec3" => UniformKind::Vec3,
+        "vec4" => UniformKind::Vec4,
+        "mat2" => UniformKind::Mat2,
+        "mat3" => UniformKind::Mat3,
+        "mat4" => UniformKind::Mat4,
+        "sampler2D" => UniformKind::Sampler2D,
+        "sampler1D" => UniformKind::Sampler1D,
+        "samplerCube" => UniformKind::SamplerCube,
+        "samplerCubeArray" => UniformKind::SamplerCubeArray,
+        "samplerCubeArrayShadow" => UniformKind::SamplerCubeArrayShadow,
+        "sampler2DShadow" => UniformKind::Sampler2DShadow,
+        "sampler2DMS" => UniformKind::Sampler2DMS,
+        "usampler2D" => UniformKind::USampler2D,
+        "isampler2D" => UniformKind::ISampler2D,
+        "uimage2D" => UniformKind::UImage2D,
+        _ => return None,
+    };
+
+    let declaration = tokens.next()?.trim_end_matches(';');
+    match declaration.split_once('[') {
+        Some((name, count)) => {
+            let count = count.trim_end_matches(']').parse().ok()?;
+            // no driver has room for larger arrays, and their sizes could overflow
+            if count == 0 || count > MAX_ARRAY_LEN {
+                return None;
+            }
+            Some((name.to_string(), kind, count))
+        }
+        None => Some((declaration.to_string(), kind, 1)),
+    }
+}
+
+const MAX_ARRAY_LEN: usize = 1 << 16;
+
+fn uniform_size_from_kind(kind: UniformKind, count: usize) -> usize {
+    let size = match kind {
+        UniformKind::F32
+        | UniformKind::Sampler2D
+        | UniformKind::Sampler1D
+        | UniformKind::SamplerCube
+        | UniformKind::SamplerCubeArray
+        | UniformKind::Sampler2DShadow
+        | UniformKind::SamplerCubeArrayShadow
+        | UniformKind::Sampler2DMS
+        | UniformKind::USampler2D
+        | UniformKind::ISampler2D
+        | UniformKind::UImage2D => 4,
+        UniformKind::Vec2 => 4 * 2,
+        UniformKind::Vec3 => 4 * 3,
+        UniformKind::Vec4 | UniformKind::Mat2 => 4 * 4,
+        UniformKind::Mat3 => 4 * 9,
+        UniformKind::Mat4 => 4 * 16,
+    };
+
+    size * count
+}
@@ -0,0 +1,191 @@
+use crate::{
+    renderer::texture::{
+        mip_levels, CreateTexture, DepthStencilMode, Swizzle, TextureKind, TextureRegion,
+        TextureViewDescription,
+    },
+    CompressedFormat, RendererError, TextureFormat,
+};
+
+/// Headless Texture
+///
+/// The texels are dropped, only the shape is kept.
+#[derive(Debug)]
+pub struct HeadlessTexture {
+    pub(super) width: u32,
+    pub(super) height: u32,
+    /// None for compressed textures.
+    pub(super) format: Option<TextureFormat>,
+    pub(super) levels: usize,
+    pub(super) kind: TextureKind,
+    /// 1 unless it's multisampled.
+    // it's only read by tests
+    #[allow(dead_code)]
+    pub(crate) samples: u32,
+    pub(super) swizzle: Swizzle,
+    pub(crate) depth_stencil_mode: DepthStencilMode,
+}
+
+impl CreateTexture for HeadlessTexture {
+    fn new(
+        _data: &[u8],
+        width: u32,
+        height: u32,
+        _row_pitch: usize,
+        format: TextureFormat,
+    ) -> Result<Self, RendererError> {
+        Ok(Self {
+            width,
+            height,
+            format: Some(format),
+            levels: 1,
+            kind: TextureKind::Texture2D,
+            samples: 1,
+            swizzle: Swizzle::IDENTITY,
+            depth_stencil_mode: DepthStencilMode::Depth,
+        })
+    }
+
+    fn mipmapped(
+        _data: &[u8],
+        width: u32,
+        height: u32,
+        _row_pitch: usize,
+        format: TextureFormat,
+    ) -> Result<Self, RendererError> {
+        Ok(Self {
+            levels: mip_levels(width, height) as usize,
+            ..Self::new(&[], width, height, 0, format)?
+        })
+    }
+
+    fn new_1d(_data: &[u8], width: u32, format: TextureFormat) -> Result<Self, RendererError> {
+        Ok(Self {
+            kind: TextureKind::Texture1D,
+            ..Self::new(&[], width, 1, 0, format)?
+        })
+    }
+
+    fn cubemap(
+        _faces: [&[u8]; 6],
+        size: u32,
+        format: TextureFormat,
+    ) -> Result<Self, RendererError> {
+        Ok(Self {
+            width: size,
+            height: size,
+            format: Some(format),
+            levels: 1,
+            kind: TextureKind::Cubemap,
+            samples: 1,
+            swizzle: Swizzle::IDENTITY,
+            depth_stencil_mode: DepthStencilMode::Depth,
+        })
+    }
+
+    fn cubemap_array(
+        cubemaps: &[[&[u8]; 6]],
+        size: u32,
+        format: TextureFormat,
+    ) -> Result<Self, RendererError> {
+        Ok(Self {
+            kind: TextureKind::CubemapArray {
+                cubemaps: cubemaps.len() as u32,
+            },
+            ..Self::new(&[], size, size, 0, format)?
+        })
+    }
+
+    fn compressed(
+        levels: &[&[u8]],
+        width: u32,
+        height: u32,
+        _format: CompressedFormat,
+    ) -> Result<Self, RendererError> {
+        Ok(Self {
+            width,
+            height,
+            format: None,
+            levels: levels.len(),
+            kind: TextureKind::Texture2D,
+            samples: 1,
+            swizzle: Swizzle::IDENTITY,
+            depth_stencil_mode: DepthStencilMode::Depth,
+        })
+    }
+
+    fn multisampled(
+        width: u32,
+        height: u32,
+        samples: u32,
+        format: TextureFormat,
+    ) -> Result<Self, RendererError> {
+        Ok(Self {
+            width,
+            height,
+            format: Some(format),
+            levels: 1,
+            kind: TextureKind::Multisampled,
+            samples,
+            swizzle: Swizzle::IDENTITY,
+            depth_stencil_mode: DepthStencilMode::Depth,
+        })
+    }
+
+    fn update(
+        &mut self,
+        _data: &[u8],
+        _region: TextureRegion,
+        _row_pitch: usize,
+    ) -> Result<(), RendererError> {
+        if self.kind != TextureKind::Texture2D {
+            return Err(RendererError::FailedToUpdateTexture {
+                error: format!("{:?} textures can't be updated", self.kind),
+            });
+        }
+        Ok(())
+    }
+
+    fn view(&self, description: &TextureViewDescription) -> Result<Self, RendererError> {
+        // single faces of cubemaps are 2D textures
+        let kind = match (self.kind, description.layer) {
+            (TextureKind::Cubemap | TextureKind::CubemapArray { .. }, Some(_)) => {
+                TextureKind::Texture2D
+            }
+            (kind, _) => kind,
+        };
+
+        Ok(Self {
+            width: (self.width >> description.first_level).max(1),
+            height: (self.height >> description.first_level).max(1),
+            format: Some(description.format),
+            levels: description.levels as usize,
+            kind,
+            ..*self
+        })
+    }
+
+    fn set_swizzle(&mut self, swizzle: Swizzle) {
+        self.swizzle = swizzle;
+    }
+
+    fn set_depth_stencil_mode(&mut self, mode: DepthStencilMode) -> Result<(), RendererError> {
+        self.depth_stencil_mode = mode;
+        Ok(())
+    }
+
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn format(&self) -> Option<TextureFormat> {
+        self.format
+    }
+
+    fn levels(&self) -> u32 {
+        self.levels as u32
+    }
+
+    fn kind(&self) -> TextureKind {
+        self.kind
+    }
+}
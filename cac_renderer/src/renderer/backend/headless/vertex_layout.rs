@@ -0,0 +1,52 @@
+use crate::{
+    renderer::{Context, CreateVertexLayout},
+    Renderer, RendererError, VertexAttribute, VertexAttributeKind, VertexLayoutConfig,
+};
+
+use super::buffer::HeadlessBuffer;
+
+/// Headless Vertex Layout
+///
+/// Only keeps track of whether an index buffer is attached and of the attributes.
+#[derive(Debug, Default)]
+pub struct HeadlessVertexLayout {
+    pub(super) has_indices: bool,
+    attributes: Vec<(u8, VertexAttributeKind)>,
+}
+
+impl CreateVertexLayout for HeadlessVertexLayout {
+    type Buffer = HeadlessBuffer;
+
+    fn new<C: Context>(_ctx: &mut Renderer<C>) -> Result<Self, RendererError> {
+        Ok(Self::default())
+    }
+
+    fn set_buffer_attributes(
+        &mut self,
+        buffer: &Self::Buffer,
+        attributes: &[VertexAttribute],
+        _offset: usize,
+        config: &VertexLayoutConfig,
+    ) -> Result<(), RendererError> {
+        if buffer.is_index {
+            self.has_indices = true;
+        }
+
+        for attr in attributes {
+            match config.location(attr.semantic) {
+                Some(location) => self.attributes.push((location, attr.kind)),
+                None => {
+                    return Err(RendererError::AttributeHasNoLocation {
+                        semantic: attr.semantic,
+                    })
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn attributes(&self) -> &[(u8, VertexAttributeKind)] {
+        &self.attributes
+    }
+}
@@ -1,10 +1,14 @@
-use crate::{Handle, MaterialProperty};
+use crate::{Handle, MaterialProperty, RendererError};
 
 use super::{
     buffer::CreateBuffer,
+    compute::CreateComputeProgram,
+    sampler::CreateSampler,
     shader::{CreateShader, CreateShaderProgram},
-    vertex_layout::CreateVertexLayout,
-    Material, Mesh, RenderTarget, Uniform,
+    texture::CreateTexture,
+    vertex_layout::{CreateVertexLayout, VertexLayout},
+    Buffer, Capabilities, CompressedFormat, ComputeProgram, DrawBatch, Image, Material,
+    MemoryBarriers, Mesh, MultiMesh, RenderTarget, Uniform,
 };
 
 pub mod headless;
@@ -12,11 +16,28 @@ pub mod opengl;
 
 pub trait Context {
     type Context;
-    type Buffer: CreateBuffer;
-    type VertexLayout: CreateVertexLayout<Buffer = Self::Buffer>;
-    type Shader: CreateShader;
-    type ShaderProgram: CreateShaderProgram<VertexShader = Self::Shader, FragmentShader = Self::Shader>
-        + Uniform;
+    type Buffer: CreateBuffer<Context = Self::Context> + DebugLabel + 'static;
+    type VertexLayout: CreateVertexLayout<Buffer = Self::Buffer> + DebugLabel + 'static;
+    type Shader: CreateShader + 'static;
+    type ShaderProgram: CreateShaderProgram<
+            VertexShader = Self::Shader,
+            FragmentShader = Self::Shader,
+            TessellationShader = Self::Shader,
+        > + Uniform
+        + DebugLabel
+        + 'static;
+    type ComputeProgram: CreateComputeProgram<ComputeShader = Self::Shader>
+        + Uniform
+        + DebugLabel
+        + 'static;
+    type Sampler: CreateSampler + DebugLabel + 'static;
+    type Texture: CreateTexture + DebugLabel + 'static;
+}
+
+/// Resource that can be named in graphics debuggers like RenderDoc and apitrace.
+pub trait DebugLabel {
+    /// Backends without debug tooling ignore the label.
+    fn set_label(&mut self, _label: &str) {}
 }
 
 /// Renderer Backend that is used by the [Renderer][crate::Renderer]
@@ -31,12 +52,139 @@ pub trait Backend {
 
     fn screen_target(&mut self) -> &mut dyn RenderTarget;
 
+    /// Reads the whole screen, e.g. to attach the frame to a bug report.
+    /// See [read_pixels][RenderTarget::read_pixels]. OpenGL renderers need the screen
+    /// readback on before the frame is drawn, see `Renderer::set_screen_readback`.
+    fn screenshot(&mut self) -> Image;
+
+    /// Limits and optional features of the context, e.g. to pick shader variants and texture
+    /// sizes.
+    fn capabilities(&self) -> &Capabilities;
+
+    /// Returns true if textures with the format can be created, e.g. to fall back to decoding
+    /// them into RGBA8 textures otherwise.
+    fn supports_compressed_format(&self, format: CompressedFormat) -> bool;
+
+    /// Draws the mesh with the material at depth 0. See [draw_at_depth][Self::draw_at_depth].
     fn draw(
         &mut self,
         mesh: Mesh,
         material: Handle<Material>,
         instance_properties: &[MaterialProperty],
+    ) {
+        self.draw_at_depth(mesh, material, 0.0, instance_properties)
+    }
+
+    /// Draws the mesh with the material.
+    /// The depth is the distance to the camera and used to sort the draws of the material's
+    /// [RenderQueue][crate::RenderQueue].
+    fn draw_at_depth(
+        &mut self,
+        mesh: Mesh,
+        material: Handle<Material>,
+        depth: f32,
+        instance_properties: &[MaterialProperty],
+    );
+
+    /// Draws every submesh of the mesh with its material, in the order of the submeshes. The
+    /// properties override the uniforms of all the materials, properties that a material doesn't
+    /// have are skipped like for any other draw.
+    fn draw_mesh_with_materials(
+        &mut self,
+        mesh: &MultiMesh,
+        instance_properties: &[MaterialProperty],
+    ) {
+        for submesh in &mesh.submeshes {
+            self.draw(submesh.mesh, submesh.material, instance_properties);
+        }
+    }
+
+    /// Draws count [IndirectCommand][crate::IndirectCommand]s of the indirect buffer as
+    /// triangles with the layout and material, in a single call, e.g. the meshes of a large
+    /// static scene. The draw is sorted like one with depth 0 and doesn't support per-draw
+    /// property overrides.
+    ///
+    /// Requires [multi-draw indirect][Capabilities::supports_multi_draw_indirect], the draw is
+    /// dropped with a warning otherwise.
+    fn draw_indirect(
+        &mut self,
+        layout: Handle<VertexLayout>,
+        material: Handle<Material>,
+        indirect_buffer: Handle<Buffer>,
+        count: u32,
     );
 
+    /// Draws everything recorded in the batches, like [draw_at_depth][Self::draw_at_depth] does for
+    /// a single draw. The batches are drawn in order, as if their draws were issued one after
+    /// another.
+    fn draw_batches(&mut self, batches: &[DrawBatch]);
+
+    /// Runs the compute program with x * y * z work groups.
+    /// The properties are set on the program before the dispatch.
+    ///
+    /// Unlike draws, dispatches are issued immediately, so they run before the draws of the
+    /// current frame. Use [memory_barrier][Self::memory_barrier] before reading their results.
+    fn dispatch(
+        &mut self,
+        program: Handle<ComputeProgram>,
+        x: u32,
+        y: u32,
+        z: u32,
+        properties: &[MaterialProperty],
+    ) -> Result<(), RendererError>;
+
+    /// Makes the writes of previous dispatches visible to the following reads of the memory.
+    fn memory_barrier(&mut self, barriers: MemoryBarriers);
+
+    /// Starts measuring the GPU time of the work issued until [end_gpu_scope][Self::end_gpu_scope],
+    /// e.g. the [update][Self::update] of a render target or a few dispatches.
+    /// The times end up in the [FrameStats][crate::FrameStats] a few frames later, without
+    /// waiting for the GPU.
+    ///
+    /// Scopes can't be nested, beginning a scope ends the open one.
+    fn begin_gpu_scope(&mut self, name: &str);
+
+    fn end_gpu_scope(&mut self);
+
+    /// Groups the following commands under the name in graphics debuggers, until the matching
+    /// [pop_debug_group][Self::pop_debug_group]. Groups can be nested.
+    ///
+    /// Every [update][Self::update] and GPU scope is a group of its own already.
+    fn push_debug_group(&mut self, name: &str);
+
+    fn pop_debug_group(&mut self);
+
+    /// Counts how often every pixel is drawn, by drawing the frame a second time into an R32UI
+    /// texture after every [update][Self::update]. The frame is replaced with a heatmap of the
+    /// counts, and [FrameStats::overdraw][crate::FrameStats::overdraw] reports them.
+    ///
+    /// The counts are read back every frame, which stalls the pipeline, so it's only meant for
+    /// profiling. Requires image load/store, OpenGL 4.2.
+    fn set_overdraw_analysis(&mut self, enabled: bool) -> Result<(), RendererError>;
+
+    /// Releases the graphics context, e.g. when an Android app loses its window.
+    ///
+    /// The contexts of the backends can't outlive their window, so all resources are released
+    /// with it. Shaders, programs, samplers and vertex layouts keep their sources and settings,
+    /// except programs created from binaries, and materials live on the CPU, so they are created again with the same handles on
+    /// [resume][Self::resume]. Buffers and textures need their data for that, see
+    /// [set_retain_data][crate::Renderer::set_retain_data], the handles of the others become
+    /// invalid, like the ones of layouts whose buffers weren't retained. Settings of the
+    /// renderer, like the [DebugView][crate::DebugView] and the
+    /// [layout config][crate::VertexLayoutConfig], are kept.
+    /// [update][Self::update] drops the draws until [resume][Self::resume], and no other
+    /// resources must be created in the meantime. The overdraw analysis is turned off.
+    fn suspend(&mut self);
+
+    /// Recreates the graphics context with the settings it was created with, for the new window
+    /// after a [suspend][Self::suspend]. The retained resources are restored, the others have to
+    /// be created again. The built-in ones, like the placeholders, are recreated right away.
+    fn resume(
+        &mut self,
+        window: &impl raw_window_handle::HasRawWindowHandle,
+    ) -> Result<(), RendererError>;
+
+    fn is_suspended(&self) -> bool;
+
     fn update(&mut self);
 }
@@ -0,0 +1,203 @@
+use crate::{renderer::Texture, Handle, Material, MaterialProperty, Renderer, RendererError};
+
+use super::{GpuReduction, OpenGLContext, ReductionOp, ReductionResult};
+
+/// Name of the `float` uniform the tonemapping material reads the exposure from, see
+/// [AutoExposure::apply].
+pub const EXPOSURE_PROPERTY: &str = "exposure";
+
+/// How an [AutoExposure] adapts.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AutoExposureSettings {
+    /// Luminance the average luminance of the frame is exposed to, middle gray by default.
+    pub key: f32,
+    /// Rate of the adaptation to brighter frames, in 1 / seconds.
+    pub speed_to_bright: f32,
+    /// Rate of the adaptation to darker frames, in 1 / seconds. Eyes adapt to the dark slower.
+    pub speed_to_dark: f32,
+    pub min_exposure: f32,
+    pub max_exposure: f32,
+    /// Bins of the luminance histogram.
+    pub bins: u32,
+    /// log2 luminance range of the histogram, luminance outside counts as the nearest end.
+    pub min_log2: f32,
+    pub max_log2: f32,
+}
+
+impl Default for AutoExposureSettings {
+    fn default() -> Self {
+        Self {
+            key: 0.18,
+            speed_to_bright: 3.0,
+            speed_to_dark: 1.0,
+            min_exposure: 1.0 / 64.0,
+            max_exposure: 64.0,
+            bins: 64,
+            min_log2: -10.0,
+            max_log2: 10.0,
+        }
+    }
+}
+
+impl AutoExposureSettings {
+    fn reduction_op(&self) -> ReductionOp {
+        ReductionOp::LuminanceHistogram {
+            bins: self.bins,
+            min_log2: self.min_log2,
+            max_log2: self.max_log2,
+        }
+    }
+
+    /// The exposure that maps the average luminance to the key.
+    fn target_exposure(&self, average_luminance: f32) -> f32 {
+        (self.key / average_luminance.max(f32::MIN_POSITIVE))
+            .clamp(self.min_exposure, self.max_exposure)
+    }
+
+    /// Moves the exposure towards the target, in stops, so brightening by one stop takes as long
+    /// as darkening by one.
+    fn adapt(&self, exposure: f32, target: f32, delta_seconds: f32) -> f32 {
+        let speed = match target < exposure {
+            true => self.speed_to_bright,
+            false => self.speed_to_dark,
+        };
+        let (current, target) = (exposure.log2(), target.log2());
+        let factor = 1.0 - (-delta_seconds.max(0.0) * speed).exp();
+        (current + (target - current) * factor).exp2()
+    }
+}
+
+/// Eye adaptation for HDR frames. Every [update][Self::update] reduces the frame to its luminance
+/// histogram with a [GpuReduction], and moves the exposure towards the one that maps the average
+/// luminance to the [key][AutoExposureSettings::key]. The histograms arrive a few frames late,
+/// which the gradual adaptation hides.
+///
+/// The tonemapping pass scales the HDR colors by the exposure before mapping them to the screen:
+///
+/// ```no_run
+/// # use cac_renderer::{AutoExposure, Handle, Material, OpenGLContext, Renderer, Texture};
+/// # fn frame(renderer: &mut Renderer<OpenGLContext>, exposure: &mut AutoExposure, hdr: Handle<Texture>, tonemap: Handle<Material>, delta: f32) {
+/// exposure.update(renderer, hdr, delta).unwrap();
+/// exposure.apply(renderer, tonemap).unwrap();
+/// # }
+/// ```
+///
+/// ```glsl
+/// uniform sampler2D hdr;
+/// uniform float exposure;
+/// // ...
+/// vec3 color = texture(hdr, uv).rgb * exposure;
+/// result = vec4(color / (color + 1.0), 1.0);
+/// ```
+#[derive(Debug)]
+pub struct AutoExposure {
+    reduction: GpuReduction,
+    settings: AutoExposureSettings,
+    exposure: f32,
+    /// Exposure of the latest histogram.
+    target: f32,
+}
+
+impl AutoExposure {
+    /// Starts at an exposure of 1.
+    pub fn new(
+        renderer: &mut Renderer<OpenGLContext>,
+        settings: AutoExposureSettings,
+    ) -> Result<Self, RendererError> {
+        let exposure = 1.0_f32.clamp(settings.min_exposure, settings.max_exposure);
+        Ok(Self {
+            reduction: GpuReduction::new(renderer, settings.reduction_op())?,
+            settings,
+            exposure,
+            target: exposure,
+        })
+    }
+
+    pub fn settings(&self) -> AutoExposureSettings {
+        self.settings
+    }
+
+    /// Changes the settings, the histogram reduction is created again if its bins or range
+    /// changed.
+    pub fn set_settings(
+        &mut self,
+        renderer: &mut Renderer<OpenGLContext>,
+        settings: AutoExposureSettings,
+    ) -> Result<(), RendererError> {
+        if settings.reduction_op() != self.settings.reduction_op() {
+            let reduction = GpuReduction::new(renderer, settings.reduction_op())?;
+            std::mem::replace(&mut self.reduction, reduction).remove(renderer);
+        }
+        self.settings = settings;
+        self.target = self
+            .target
+            .clamp(settings.min_exposure, settings.max_exposure);
+        Ok(())
+    }
+
+    /// The exposure of the frame.
+    pub fn exposure(&self) -> f32 {
+        self.exposure
+    }
+
+    /// Reduces the HDR frame and adapts the exposure over delta_seconds towards the latest
+    /// histogram that arrived. Returns the new exposure.
+    pub fn update(
+        &mut self,
+        renderer: &mut Renderer<OpenGLContext>,
+        hdr: Handle<Texture>,
+        delta_seconds: f32,
+    ) -> Result<f32, RendererError> {
+        self.reduction.reduce(renderer, hdr)?;
+        if let Some(ReductionResult::LuminanceHistogram(histogram)) =
+            self.reduction.poll(renderer)?
+        {
+            self.target = self.settings.target_exposure(histogram.average_luminance());
+        }
+        self.exposure = self
+            .settings
+            .adapt(self.exposure, self.target, delta_seconds);
+        Ok(self.exposure)
+    }
+
+    /// The exposure as override of [EXPOSURE_PROPERTY], for tonemapping draws.
+    pub fn property(&self) -> MaterialProperty<'_> {
+        MaterialProperty::new(EXPOSURE_PROPERTY, &self.exposure)
+    }
+
+    /// Sets the exposure on the tonemapping material.
+    pub fn apply(
+        &self,
+        renderer: &mut Renderer<OpenGLContext>,
+        tonemap: Handle<Material>,
+    ) -> Result<(), RendererError> {
+        renderer.update_material(tonemap, &[self.property()])
+    }
+
+    /// Removes the histogram reduction.
+    pub fn remove(self, renderer: &mut Renderer<OpenGLContext>) {
+        self.reduction.remove(renderer);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn adaptation() {
+        let settings = AutoExposureSettings::default();
+        // middle gray needs no exposure, a dark frame is clamped to the maximum
+        assert_eq!(settings.target_exposure(0.18), 1.0);
+        assert_eq!(settings.target_exposure(0.0), settings.max_exposure);
+
+        // no time, no change
+        assert_eq!(settings.adapt(1.0, 4.0, 0.0), 1.0);
+        // a second closes 1 - e^-1 of the two stops to the darker frame's exposure
+        let darker = settings.adapt(1.0, 4.0, 1.0);
+        assert!((darker.log2() - 2.0 * (1.0 - (-1.0_f32).exp())).abs() < 1e-5);
+        // adapting to bright frames is faster
+        let brighter = settings.adapt(4.0, 1.0, 1.0);
+        assert!(4.0_f32.log2() - brighter.log2() > darker.log2());
+    }
+}
@@ -1,27 +1,112 @@
-use gl::types::{GLenum, GLuint};
+use std::ops::Range;
+
+use gl::types::{GLbitfield, GLenum, GLsync, GLuint};
 
 use crate::{
     generation_vec::GenerationVec,
-    renderer::buffer::{BufferStorage, CreateBuffer},
+    renderer::{
+        buffer::{BufferStorage, CreateBuffer},
+        stream_buffer::STREAM_REGIONS,
+    },
     Buffer, BufferUsage, Handle, RendererError,
 };
 
-use super::OpenGLContext;
+use super::{DebugLabel, OpenGLContext};
+
+/// How long the write into a stream region waits for the draws of an earlier frame, in
+/// nanoseconds.
+const STREAM_WAIT_TIMEOUT: u64 = 1_000_000_000;
+
+const STREAM_MAP_FLAGS: GLbitfield =
+    gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+
+/// Persistent mapping of a stream buffer, with a fence per region for the draws that read it.
+#[derive(Debug)]
+struct StreamMapping {
+    ptr: *mut u8,
+    fences: [GLsync; STREAM_REGIONS],
+    region: usize,
+}
 
 #[derive(Debug)]
 pub struct GLBuffer {
     pub(super) kind: GLuint,
     id: GLuint,
+    size: usize,
+    usage: BufferUsage,
+    /// Set for stream buffers with immutable storage.
+    mapping: Option<StreamMapping>,
 }
 
 impl CreateBuffer for GLBuffer {
-    fn with_vertex<T>(data: &[T], usage: BufferUsage) -> Result<Self, RendererError> {
+    type Context = OpenGLContext;
+
+    fn with_vertex<T>(
+        _ctx: &mut OpenGLContext,
+        data: &[T],
+        usage: BufferUsage,
+    ) -> Result<Self, RendererError> {
         Self::with_vertex(data, usage)
     }
 
-    fn with_index<T>(data: &[T], usage: BufferUsage) -> Result<Self, RendererError> {
+    fn with_index<T>(
+        _ctx: &mut OpenGLContext,
+        data: &[T],
+        usage: BufferUsage,
+    ) -> Result<Self, RendererError> {
         Self::with_index(data, usage)
     }
+
+    fn set_data<T>(
+        &mut self,
+        _ctx: &mut OpenGLContext,
+        data: &[T],
+        usage: BufferUsage,
+    ) -> Result<(), RendererError> {
+        self.set_data(data, usage)
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn usage(&self) -> BufferUsage {
+        self.usage
+    }
+
+    fn update<T>(
+        &mut self,
+        _ctx: &mut OpenGLContext,
+        offset: usize,
+        data: &[T],
+    ) -> Result<(), RendererError> {
+        self.update(offset, data);
+        Ok(())
+    }
+
+    fn orphan(&mut self, _ctx: &mut OpenGLContext, size: usize) -> Result<(), RendererError> {
+        self.orphan(size)
+    }
+
+    fn read(
+        &self,
+        _ctx: &mut OpenGLContext,
+        range: Range<usize>,
+    ) -> Result<Vec<u8>, RendererError> {
+        self.read(range)
+    }
+
+    fn with_stream(_ctx: &mut OpenGLContext, size: usize) -> Result<Self, RendererError> {
+        Self::with_stream(size)
+    }
+
+    fn begin_stream_region(
+        &mut self,
+        _ctx: &mut OpenGLContext,
+        region: usize,
+    ) -> Result<(), RendererError> {
+        self.begin_stream_region(region)
+    }
 }
 
 impl BufferStorage<OpenGLContext> for GenerationVec<Buffer, GLBuffer> {
@@ -86,52 +171,234 @@ impl GLBuffer {
                 gl::GenBuffers(1, &mut vbo as *mut GLuint);
                 vbo
             },
+            size: 0,
+            usage,
+            mapping: None,
         };
 
         buffer.set_data(data, usage)?;
         Ok(buffer)
     }
 
+    /// Vertex buffer with immutable storage that stays mapped, if `glBufferStorage` is available
+    /// (GL 4.4), and a streamed buffer that is orphaned for every region otherwise.
+    pub(super) fn with_stream(size: usize) -> Result<Self, RendererError> {
+        if !gl::BufferStorage::is_loaded() {
+            let mut buffer = Self::with_vertex::<u8>(&[], BufferUsage::StreamingWrite)?;
+            buffer.orphan(size)?;
+            return Ok(buffer);
+        }
+
+        let gl_size = Self::gl_size(size)?;
+        let mut buffer = Self::with_vertex::<u8>(&[], BufferUsage::StreamingWrite)?;
+        let ptr = unsafe {
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, buffer.id);
+            // the storage of the buffer isn't specified yet, so it can still be made immutable
+            gl::BufferStorage(
+                gl::COPY_WRITE_BUFFER,
+                gl_size,
+                std::ptr::null(),
+                STREAM_MAP_FLAGS,
+            );
+            gl::MapBufferRange(gl::COPY_WRITE_BUFFER, 0, gl_size, STREAM_MAP_FLAGS)
+        };
+        if ptr.is_null() {
+            return Err(RendererError::FailedToUpdateBuffer {
+                error: format!("Failed to map the {size} bytes of the stream buffer"),
+            });
+        }
+
+        buffer.size = size;
+        buffer.mapping = Some(StreamMapping {
+            ptr: ptr as *mut u8,
+            fences: [std::ptr::null(); STREAM_REGIONS],
+            region: 0,
+        });
+        Ok(buffer)
+    }
+
+    /// Fences the draws of the current region, and waits for the fence of the next region.
+    /// Without a mapping, the buffer is orphaned instead, so the driver keeps the old storage for
+    /// the draws of the earlier frames.
+    pub(super) fn begin_stream_region(&mut self, region: usize) -> Result<(), RendererError> {
+        let Some(mapping) = &mut self.mapping else {
+            return self.orphan(self.size);
+        };
+
+        unsafe {
+            let current = &mut mapping.fences[mapping.region];
+            if !current.is_null() {
+                gl::DeleteSync(*current);
+            }
+            *current = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+
+            let fence = std::mem::replace(&mut mapping.fences[region], std::ptr::null());
+            if !fence.is_null() {
+                let result =
+                    gl::ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, STREAM_WAIT_TIMEOUT);
+                if result == gl::TIMEOUT_EXPIRED || result == gl::WAIT_FAILED {
+                    log::warn!("Stream buffer {} didn't get region {region} back", self.id);
+                }
+                gl::DeleteSync(fence);
+            }
+        }
+        mapping.region = region;
+        Ok(())
+    }
+
     pub(super) fn set_data<T>(
         &mut self,
         data: &[T],
         usage: BufferUsage,
     ) -> Result<(), RendererError> {
+        if self.mapping.is_some() {
+            return Err(Self::immutable());
+        }
         self.bind();
 
         let size = std::mem::size_of::<T>() * data.len();
-
-        let size = match size.try_into() {
-            Ok(val) => val,
-            Err(e) => {
-                return Err(RendererError::ConversionError {
-                    error: format!(
-                        "Failed to convert Buffer usize({size}) into isize{}: {e}",
-                        isize::MAX
-                    ),
-                })
-            }
-        };
+        let gl_size = Self::gl_size(size)?;
 
         unsafe {
             gl::BufferData(
                 self.kind,
-                size,
+                gl_size,
                 data.as_ptr() as *const std::ffi::c_void,
                 usage.into(),
             );
         }
+        self.size = size;
+        self.usage = usage;
 
         Ok(())
     }
 
+    fn gl_size(size: usize) -> Result<isize, RendererError> {
+        size.try_into().map_err(|e| RendererError::ConversionError {
+            error: format!(
+                "Failed to convert Buffer usize({size}) into isize{}: {e}",
+                isize::MAX
+            ),
+        })
+    }
+
+    fn immutable() -> RendererError {
+        RendererError::FailedToUpdateBuffer {
+            error: "The storage of mapped stream buffers can't be replaced".to_string(),
+        }
+    }
+
+    /// Binds the buffer to the copy target for updates, so the index buffer of the bound vertex
+    /// array isn't replaced.
+    fn bind_for_update(&self) {
+        unsafe { gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.id) }
+    }
+
+    /// Replaces the bytes starting at the offset, which has to be inside of the buffer with the
+    /// data.
+    pub(super) fn update<T>(&mut self, offset: usize, data: &[T]) {
+        if let Some(mapping) = &self.mapping {
+            // the mapping is coherent, the writes are visible to the following draws
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    data.as_ptr() as *const u8,
+                    mapping.ptr.add(offset),
+                    std::mem::size_of_val(data),
+                );
+            }
+            return;
+        }
+
+        self.bind_for_update();
+        unsafe {
+            gl::BufferSubData(
+                gl::COPY_WRITE_BUFFER,
+                offset as isize,
+                std::mem::size_of_val(data) as isize,
+                data.as_ptr() as *const std::ffi::c_void,
+            );
+        }
+    }
+
+    /// Re-specifies the storage with size bytes of undefined content, with the usage it has.
+    pub(super) fn orphan(&mut self, size: usize) -> Result<(), RendererError> {
+        if self.mapping.is_some() {
+            return Err(Self::immutable());
+        }
+        let gl_size = Self::gl_size(size)?;
+        self.bind_for_update();
+        unsafe {
+            gl::BufferData(
+                gl::COPY_WRITE_BUFFER,
+                gl_size,
+                std::ptr::null(),
+                self.usage.into(),
+            );
+        }
+        self.size = size;
+        Ok(())
+    }
+
+    /// Reads the bytes of the range, which has to be inside of the buffer, with
+    /// `glGetBufferSubData`, or by mapping the range where it isn't available (GLES).
+    pub(super) fn read(&self, range: Range<usize>) -> Result<Vec<u8>, RendererError> {
+        let mut data = vec![0u8; range.len()];
+        if data.is_empty() {
+            return Ok(data);
+        }
+
+        unsafe {
+            gl::BindBuffer(gl::COPY_READ_BUFFER, self.id);
+            if gl::GetBufferSubData::is_loaded() {
+                gl::GetBufferSubData(
+                    gl::COPY_READ_BUFFER,
+                    range.start as isize,
+                    data.len() as isize,
+                    data.as_mut_ptr() as *mut std::ffi::c_void,
+                );
+                return Ok(data);
+            }
+
+            let ptr = gl::MapBufferRange(
+                gl::COPY_READ_BUFFER,
+                range.start as isize,
+                data.len() as isize,
+                gl::MAP_READ_BIT,
+            );
+            if ptr.is_null() {
+                return Err(RendererError::FailedToReadBuffer {
+                    error: format!("Failed to map the range {range:?} of buffer {}", self.id),
+                });
+            }
+            std::ptr::copy_nonoverlapping(ptr as *const u8, data.as_mut_ptr(), data.len());
+            gl::UnmapBuffer(gl::COPY_READ_BUFFER);
+        }
+        Ok(data)
+    }
+
     pub(super) fn bind(&self) {
         unsafe { gl::BindBuffer(self.kind, self.id) }
     }
+
+    pub(super) fn id(&self) -> GLuint {
+        self.id
+    }
+}
+
+impl DebugLabel for GLBuffer {
+    fn set_label(&mut self, label: &str) {
+        super::object_label(gl::BUFFER, self.id, label);
+    }
 }
 
 impl Drop for GLBuffer {
     fn drop(&mut self) {
+        if let Some(mapping) = &self.mapping {
+            for fence in mapping.fences.iter().filter(|fence| !fence.is_null()) {
+                unsafe { gl::DeleteSync(*fence) };
+            }
+        }
+        // deleting the buffer unmaps it
         if self.id > 0 {
             unsafe {
                 gl::DeleteBuffers(1, &self.id);
@@ -0,0 +1,570 @@
+use std::{
+    ffi::c_void,
+    os::fd::{IntoRawFd, OwnedFd},
+};
+
+use gl::types::{GLenum, GLint, GLsizei, GLubyte, GLuint, GLuint64};
+
+use crate::{
+    renderer::{Backend, ExternalTarget, Texture},
+    Handle, Renderer, RendererError, TextureFormat,
+};
+
+use super::{texture::GLTexture, OpenGLContext};
+
+// EXT_memory_object and EXT_semaphore aren't part of the generated bindings
+const TEXTURE_TILING_EXT: GLenum = 0x9580;
+const DEDICATED_MEMORY_OBJECT_EXT: GLenum = 0x9581;
+const OPTIMAL_TILING_EXT: GLenum = 0x9584;
+const LINEAR_TILING_EXT: GLenum = 0x9585;
+const HANDLE_TYPE_OPAQUE_FD_EXT: GLenum = 0x9586;
+const DEVICE_UUID_EXT: GLenum = 0x9597;
+const LAYOUT_GENERAL_EXT: GLenum = 0x958D;
+const UUID_SIZE_EXT: usize = 16;
+
+const EXTENSIONS: [&str; 4] = [
+    "GL_EXT_memory_object",
+    "GL_EXT_memory_object_fd",
+    "GL_EXT_semaphore",
+    "GL_EXT_semaphore_fd",
+];
+
+/// Entry points of the external memory and semaphore extensions, loaded with the context.
+#[derive(Clone, Copy)]
+pub(super) struct ExternalMemoryFns {
+    create_memory_objects: unsafe extern "system" fn(GLsizei, *mut GLuint),
+    delete_memory_objects: unsafe extern "system" fn(GLsizei, *const GLuint),
+    memory_object_parameteriv: unsafe extern "system" fn(GLuint, GLenum, *const GLint),
+    import_memory_fd: unsafe extern "system" fn(GLuint, GLuint64, GLenum, GLint),
+    tex_storage_mem_2d:
+        unsafe extern "system" fn(GLenum, GLsizei, GLenum, GLsizei, GLsizei, GLuint, GLuint64),
+    gen_semaphores: unsafe extern "system" fn(GLsizei, *mut GLuint),
+    delete_semaphores: unsafe extern "system" fn(GLsizei, *const GLuint),
+    import_semaphore_fd: unsafe extern "system" fn(GLuint, GLenum, GLint),
+    /// Semaphore, buffers and textures with their layouts.
+    signal_semaphore: unsafe extern "system" fn(
+        GLuint,
+        GLuint,
+        *const GLuint,
+        GLuint,
+        *const GLuint,
+        *const GLenum,
+    ),
+    wait_semaphore: unsafe extern "system" fn(
+        GLuint,
+        GLuint,
+        *const GLuint,
+        GLuint,
+        *const GLuint,
+        *const GLenum,
+    ),
+    get_unsigned_bytei_v: unsafe extern "system" fn(GLenum, GLuint, *mut GLubyte),
+}
+
+impl ExternalMemoryFns {
+    /// The entry points, or None if the context lacks one of the extensions.
+    pub(super) fn load(
+        extensions: &[String],
+        get_proc_address: impl Fn(&str) -> *const c_void,
+    ) -> Option<Self> {
+        if !EXTENSIONS
+            .iter()
+            .all(|name| extensions.iter().any(|extension| extension == name))
+        {
+            return None;
+        }
+
+        macro_rules! load {
+            ($name:literal) => {{
+                let function = get_proc_address($name);
+                if function.is_null() {
+                    return None;
+                }
+                // SAFETY: the extensions define the signatures of the entry points
+                unsafe { std::mem::transmute_copy(&function) }
+            }};
+        }
+
+        Some(Self {
+            create_memory_objects: load!("glCreateMemoryObjectsEXT"),
+            delete_memory_objects: load!("glDeleteMemoryObjectsEXT"),
+            memory_object_parameteriv: load!("glMemoryObjectParameterivEXT"),
+            import_memory_fd: load!("glImportMemoryFdEXT"),
+            tex_storage_mem_2d: load!("glTexStorageMem2DEXT"),
+            gen_semaphores: load!("glGenSemaphoresEXT"),
+            delete_semaphores: load!("glDeleteSemaphoresEXT"),
+            import_semaphore_fd: load!("glImportSemaphoreFdEXT"),
+            signal_semaphore: load!("glSignalSemaphoreEXT"),
+            wait_semaphore: load!("glWaitSemaphoreEXT"),
+            get_unsigned_bytei_v: load!("glGetUnsignedBytei_vEXT"),
+        })
+    }
+
+    /// The entry points, or the missing extensions as error.
+    fn require(functions: Option<&Self>) -> Result<&Self, RendererError> {
+        functions.ok_or(RendererError::UnsupportedFeature {
+            feature: EXTENSIONS.join(", "),
+        })
+    }
+}
+
+/// Imported memory object, deleted when dropped.
+#[derive(Debug)]
+struct MemoryObject {
+    id: GLuint,
+    delete: unsafe extern "system" fn(GLsizei, *const GLuint),
+}
+
+impl Drop for MemoryObject {
+    fn drop(&mut self) {
+        unsafe { (self.delete)(1, &self.id) };
+    }
+}
+
+/// The imported ready and rendered semaphores, deleted when dropped.
+#[derive(Debug)]
+struct Semaphores {
+    ids: [GLuint; 2],
+    delete: unsafe extern "system" fn(GLsizei, *const GLuint),
+}
+
+impl Semaphores {
+    fn import(functions: &ExternalMemoryFns, semaphores: ExternalSemaphores) -> Self {
+        let mut ids = [0; 2];
+        unsafe {
+            (functions.gen_semaphores)(2, ids.as_mut_ptr());
+            for (id, fd) in ids.iter().zip([semaphores.ready, semaphores.rendered]) {
+                // GL owns the descriptor afterwards
+                (functions.import_semaphore_fd)(*id, HANDLE_TYPE_OPAQUE_FD_EXT, fd.into_raw_fd());
+            }
+        }
+
+        Self {
+            ids,
+            delete: functions.delete_semaphores,
+        }
+    }
+}
+
+impl Drop for Semaphores {
+    fn drop(&mut self) {
+        unsafe { (self.delete)(2, self.ids.as_ptr()) };
+    }
+}
+
+/// Imports the memory into a new memory object and a duplicate of its descriptor.
+/// Allocate binds the object to the storage of a texture and returns the GL error if that fails,
+/// in which case the object is deleted again.
+fn import_memory<T>(
+    functions: Option<&ExternalMemoryFns>,
+    memory: ExternalMemory,
+    allocate: impl FnOnce(&ExternalMemoryFns, GLuint) -> Result<T, GLenum>,
+) -> Result<(T, MemoryObject, OwnedFd), RendererError> {
+    let functions = ExternalMemoryFns::require(functions)?;
+    let fd = memory
+        .fd
+        .try_clone()
+        .map_err(|error| RendererError::FailedToCreateTexture {
+            error: error.to_string(),
+        })?;
+
+    let mut object = MemoryObject {
+        id: 0,
+        delete: functions.delete_memory_objects,
+    };
+    unsafe {
+        (functions.create_memory_objects)(1, &mut object.id);
+        let dedicated = GLint::from(memory.dedicated);
+        (functions.memory_object_parameteriv)(object.id, DEDICATED_MEMORY_OBJECT_EXT, &dedicated);
+        // GL owns the descriptor afterwards
+        (functions.import_memory_fd)(
+            object.id,
+            memory.size,
+            HANDLE_TYPE_OPAQUE_FD_EXT,
+            memory.fd.into_raw_fd(),
+        );
+    }
+
+    let texture =
+        allocate(functions, object.id).map_err(|error| RendererError::FailedToCreateTexture {
+            error: format!("Failed to import the memory: {error:#x}"),
+        })?;
+    Ok((texture, object, fd))
+}
+
+/// Image memory allocated and exported as an opaque file descriptor by another API, e.g. a
+/// Vulkan video encoder or compositor (VK_KHR_external_memory_fd).
+#[derive(Debug)]
+pub struct ExternalMemory {
+    pub fd: OwnedFd,
+    /// Size of the whole allocation in bytes.
+    pub size: u64,
+    /// Offset of the image in the allocation.
+    pub offset: u64,
+    /// The allocation is dedicated to the image, like VkMemoryDedicatedAllocateInfo.
+    pub dedicated: bool,
+    /// The image is laid out row by row instead of in the driver's optimal tiling.
+    pub linear_tiling: bool,
+}
+
+/// Semaphores shared with the owner of the memory of a [SharedTarget], exported as opaque file
+/// descriptors (VK_KHR_external_semaphore_fd).
+#[derive(Debug)]
+pub struct ExternalSemaphores {
+    /// Signaled by the owner when it's done with the image, rendering waits for it.
+    pub ready: OwnedFd,
+    /// Signaled when the image is rendered, the owner waits for it before reading it.
+    pub rendered: OwnedFd,
+}
+
+/// Texture target in [ExternalMemory], so the renderer draws into the memory of another API
+/// zero-copy, e.g. frames for a video encoder or the buffers of a compositor.
+///
+/// GL can only import memory, so the consumer allocates it with an exportable opaque file
+/// descriptor, on the device with the [device_uuid][Renderer::device_uuid] of the renderer.
+/// The target keeps a descriptor of the memory, which [export_fd][Self::export_fd] hands out to
+/// further consumers, e.g. over a Unix socket to another process.
+///
+/// ```no_run
+/// # use cac_renderer::{Backend, ExternalMemory, OpenGLContext, Renderer, SharedTarget, TextureFormat};
+/// # fn frame(renderer: &mut Renderer<OpenGLContext>, memory: ExternalMemory) {
+/// let target = SharedTarget::new(renderer, memory, 1920, 1080, TextureFormat::Rgba8).unwrap();
+///
+/// target.render(renderer, |renderer| {
+///     // the draws of the frame
+/// });
+/// // the frame is in the memory, e.g. hand it to the encoder
+/// let fd = target.export_fd().unwrap();
+/// # }
+/// ```
+///
+/// Without [semaphores][Self::set_semaphores] the owner has to wait until the rendering is
+/// finished, e.g. with glFinish. The image is in the general layout (VK_IMAGE_LAYOUT_GENERAL)
+/// whenever it changes hands. The imported objects don't survive a
+/// [suspend][Backend::suspend], remove the target before.
+///
+/// Dropping the target releases the imported memory and semaphores, but only
+/// [remove][Self::remove] also removes its texture and render target.
+#[derive(Debug)]
+pub struct SharedTarget {
+    pub texture: Handle<Texture>,
+    pub target: Handle<ExternalTarget>,
+    /// Only held to be deleted with the target.
+    _memory: MemoryObject,
+    /// Duplicate of the descriptor GL took ownership of.
+    fd: OwnedFd,
+    semaphores: Option<Semaphores>,
+}
+
+impl Renderer<OpenGLContext> {
+    /// Whether the context can import [ExternalMemory] and [ExternalSemaphores], which needs
+    /// EXT_memory_object_fd and EXT_semaphore_fd.
+    pub fn supports_external_memory(&self) -> bool {
+        self.context.external_memory.is_some()
+    }
+
+    /// UUID of the device the context renders on, to allocate [ExternalMemory] on the same
+    /// device, like VkPhysicalDeviceIDProperties::deviceUUID. None without external memory
+    /// support.
+    pub fn device_uuid(&self) -> Option<[u8; UUID_SIZE_EXT]> {
+        let functions = self.context.external_memory.as_ref()?;
+        let mut uuid = [0; UUID_SIZE_EXT];
+        unsafe { (functions.get_unsigned_bytei_v)(DEVICE_UUID_EXT, 0, uuid.as_mut_ptr()) };
+        Some(uuid)
+    }
+}
+
+impl SharedTarget {
+    /// Imports the memory and creates a texture of the size and format in it, which the target
+    /// renders into. The format has to match the image of the owner, e.g.
+    /// [Rgba8][TextureFormat::Rgba8] for VK_FORMAT_R8G8B8A8_UNORM.
+    pub fn new(
+        renderer: &mut Renderer<OpenGLContext>,
+        memory: ExternalMemory,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Result<Self, RendererError> {
+        let tiling = if memory.linear_tiling {
+            LINEAR_TILING_EXT
+        } else {
+            OPTIMAL_TILING_EXT
+        };
+        let offset = memory.offset;
+
+        super::clear_errors();
+        let functions = renderer.context.external_memory.as_ref();
+        let (texture, memory, fd) = import_memory(functions, memory, |functions, object| {
+            let texture = unsafe {
+                GLTexture::with_storage(width, height, format, |internal_format| {
+                    gl::TexParameteri(gl::TEXTURE_2D, TEXTURE_TILING_EXT, tiling as GLint);
+                    (functions.tex_storage_mem_2d)(
+                        gl::TEXTURE_2D,
+                        1,
+                        internal_format,
+                        width as GLsizei,
+                        height as GLsizei,
+                        object,
+                        offset,
+                    );
+                })
+            };
+            match unsafe { gl::GetError() } {
+                gl::NO_ERROR => Ok(texture),
+                error => Err(error),
+            }
+        })?;
+
+        let texture = renderer.textures.push(texture);
+        let target = match renderer.texture_target(texture) {
+            Ok(target) => target,
+            Err(error) => {
+                renderer.textures.remove(texture);
+                return Err(error);
+            }
+        };
+
+        Ok(Self {
+            texture,
+            target,
+            _memory: memory,
+            fd,
+            semaphores: None,
+        })
+    }
+
+    /// A new descriptor of the memory for another consumer, who owns it.
+    pub fn export_fd(&self) -> std::io::Result<OwnedFd> {
+        self.fd.try_clone()
+    }
+
+    /// Imports the semaphores, so [render][Self::render] waits until the owner is done with the
+    /// image and signals when it's rendered. Replaces the previous semaphores.
+    pub fn set_semaphores(
+        &mut self,
+        renderer: &mut Renderer<OpenGLContext>,
+        semaphores: ExternalSemaphores,
+    ) -> Result<(), RendererError> {
+        let functions = ExternalMemoryFns::require(renderer.context.external_memory.as_ref())?;
+        self.semaphores = Some(Semaphores::import(functions, semaphores));
+        Ok(())
+    }
+
+    /// Draws what draw records in an [update][Backend::update] into the target. The previous
+    /// target is active again afterwards.
+    pub fn render(
+        &self,
+        renderer: &mut Renderer<OpenGLContext>,
+        draw: impl FnOnce(&mut Renderer<OpenGLContext>),
+    ) {
+        let (texture, functions) = match (
+            renderer.textures.get(self.texture),
+            renderer.context.external_memory.as_ref(),
+        ) {
+            (Some(texture), Some(functions)) => (texture.id, functions),
+            _ => return,
+        };
+        if let Some(Semaphores {
+            ids: [ready, _], ..
+        }) = self.semaphores
+        {
+            unsafe {
+                (functions.wait_semaphore)(
+                    ready,
+                    0,
+                    std::ptr::null(),
+                    1,
+                    &texture,
+                    &LAYOUT_GENERAL_EXT,
+                )
+            };
+        }
+
+        let previous_target = renderer.context.active_target;
+        renderer.set_external_target(Some(self.target));
+        draw(renderer);
+        Backend::update(renderer);
+        renderer.set_external_target(previous_target);
+
+        if let (
+            Some(Semaphores {
+                ids: [_, rendered], ..
+            }),
+            Some(functions),
+        ) = (
+            self.semaphores.as_ref(),
+            renderer.context.external_memory.as_ref(),
+        ) {
+            unsafe {
+                (functions.signal_semaphore)(
+                    *rendered,
+                    0,
+                    std::ptr::null(),
+                    1,
+                    &texture,
+                    &LAYOUT_GENERAL_EXT,
+                );
+                // the owner only sees the signal once it's submitted
+                gl::Flush();
+            }
+        }
+    }
+
+    /// Removes the target and its texture, and releases the imported memory and semaphores.
+    pub fn remove(self, renderer: &mut Renderer<OpenGLContext>) {
+        renderer.remove_external_target(self.target);
+        renderer.textures.remove(self.texture);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        cell::RefCell,
+        fs::File,
+        os::fd::{FromRawFd, OwnedFd},
+    };
+
+    use super::*;
+
+    const MEMORY_OBJECT: GLuint = 7;
+    const SEMAPHORES: [GLuint; 2] = [3, 4];
+
+    // the deleted objects, tests run on their own threads
+    thread_local! {
+        static DELETED_MEMORY: RefCell<Vec<GLuint>> = const { RefCell::new(Vec::new()) };
+        static DELETED_SEMAPHORES: RefCell<Vec<GLuint>> = const { RefCell::new(Vec::new()) };
+    }
+
+    unsafe extern "system" fn create(_: GLsizei, objects: *mut GLuint) {
+        *objects = MEMORY_OBJECT;
+    }
+
+    unsafe extern "system" fn delete(count: GLsizei, objects: *const GLuint) {
+        let objects = std::slice::from_raw_parts(objects, count as usize);
+        DELETED_MEMORY.with(|deleted| deleted.borrow_mut().extend_from_slice(objects));
+    }
+
+    unsafe extern "system" fn parameteriv(_: GLuint, _: GLenum, _: *const GLint) {}
+
+    // takes ownership of the descriptor like GL does
+    unsafe extern "system" fn import_fd(_: GLuint, _: GLuint64, _: GLenum, fd: GLint) {
+        drop(OwnedFd::from_raw_fd(fd));
+    }
+
+    unsafe extern "system" fn tex_storage(
+        _: GLenum,
+        _: GLsizei,
+        _: GLenum,
+        _: GLsizei,
+        _: GLsizei,
+        _: GLuint,
+        _: GLuint64,
+    ) {
+    }
+
+    unsafe extern "system" fn gen_semaphores(count: GLsizei, semaphores: *mut GLuint) {
+        std::slice::from_raw_parts_mut(semaphores, count as usize).copy_from_slice(&SEMAPHORES);
+    }
+
+    unsafe extern "system" fn delete_semaphores(count: GLsizei, semaphores: *const GLuint) {
+        let semaphores = std::slice::from_raw_parts(semaphores, count as usize);
+        DELETED_SEMAPHORES.with(|deleted| deleted.borrow_mut().extend_from_slice(semaphores));
+    }
+
+    unsafe extern "system" fn import_semaphore_fd(_: GLuint, _: GLenum, fd: GLint) {
+        drop(OwnedFd::from_raw_fd(fd));
+    }
+
+    unsafe extern "system" fn semaphore(
+        _: GLuint,
+        _: GLuint,
+        _: *const GLuint,
+        _: GLuint,
+        _: *const GLuint,
+        _: *const GLenum,
+    ) {
+    }
+
+    unsafe extern "system" fn get_uuid(_: GLenum, _: GLuint, _: *mut GLubyte) {}
+
+    fn functions() -> ExternalMemoryFns {
+        ExternalMemoryFns {
+            create_memory_objects: create,
+            delete_memory_objects: delete,
+            memory_object_parameteriv: parameteriv,
+            import_memory_fd: import_fd,
+            tex_storage_mem_2d: tex_storage,
+            gen_semaphores,
+            delete_semaphores,
+            import_semaphore_fd,
+            signal_semaphore: semaphore,
+            wait_semaphore: semaphore,
+            get_unsigned_bytei_v: get_uuid,
+        }
+    }
+
+    fn fd() -> OwnedFd {
+        File::open("/dev/null").unwrap().into()
+    }
+
+    fn memory() -> ExternalMemory {
+        ExternalMemory {
+            fd: fd(),
+            size: 1024,
+            offset: 0,
+            dedicated: true,
+            linear_tiling: false,
+        }
+    }
+
+    #[test]
+    fn missing_extensions() {
+        let extensions = ["GL_EXT_memory_object".to_string()];
+        assert!(ExternalMemoryFns::load(&extensions, |_| unreachable!()).is_none());
+
+        let result = import_memory(None, memory(), |_, _| Ok(()));
+        assert!(matches!(
+            result,
+            Err(RendererError::UnsupportedFeature { feature }) if feature.contains("GL_EXT_memory_object_fd")
+        ));
+    }
+
+    #[test]
+    fn failed_import() {
+        let functions = functions();
+        let result = import_memory(Some(&functions), memory(), |_, object| {
+            assert_eq!(object, MEMORY_OBJECT);
+            Err::<(), _>(gl::INVALID_OPERATION)
+        });
+
+        assert!(matches!(
+            result,
+            Err(RendererError::FailedToCreateTexture { error }) if error.ends_with("0x502")
+        ));
+        assert_eq!(DELETED_MEMORY.take(), [MEMORY_OBJECT]);
+    }
+
+    #[test]
+    fn release_on_drop() {
+        let functions = functions();
+        let ((), memory, fd) = import_memory(Some(&functions), memory(), |_, _| Ok(())).unwrap();
+        let semaphores = ExternalSemaphores {
+            ready: self::fd(),
+            rendered: self::fd(),
+        };
+        let target = SharedTarget {
+            texture: Handle::new(),
+            target: Handle::new(),
+            _memory: memory,
+            fd,
+            semaphores: Some(Semaphores::import(&functions, semaphores)),
+        };
+        assert!(target.export_fd().is_ok());
+        assert!(DELETED_MEMORY.take().is_empty());
+
+        drop(target);
+        assert_eq!(DELETED_MEMORY.take(), [MEMORY_OBJECT]);
+        assert_eq!(DELETED_SEMAPHORES.take(), SEMAPHORES);
+    }
+}
@@ -0,0 +1,175 @@
+use gl::types::{GLenum, GLint, GLuint};
+
+use crate::{Buffer, Handle, Renderer, Texture};
+
+use super::OpenGLContext;
+
+/// Capabilities the backend relies on being in the state it left them in.
+const CAPABILITIES: [GLenum; 11] = [
+    gl::BLEND,
+    gl::DEPTH_TEST,
+    gl::CULL_FACE,
+    gl::SCISSOR_TEST,
+    gl::STENCIL_TEST,
+    gl::FRAMEBUFFER_SRGB,
+    gl::TEXTURE_CUBE_MAP_SEAMLESS,
+    gl::PRIMITIVE_RESTART,
+    gl::RASTERIZER_DISCARD,
+    gl::MULTISAMPLE,
+    gl::POLYGON_OFFSET_FILL,
+];
+
+const PIXEL_STORE: [GLenum; 4] = [
+    gl::UNPACK_ALIGNMENT,
+    gl::UNPACK_ROW_LENGTH,
+    gl::PACK_ALIGNMENT,
+    gl::PACK_ROW_LENGTH,
+];
+
+/// Snapshot of the GL state the backend expects between its calls.
+struct GlState {
+    capabilities: [bool; CAPABILITIES.len()],
+    pixel_store: [GLint; PIXEL_STORE.len()],
+    program: GLint,
+    vertex_array: GLint,
+    array_buffer: GLint,
+    uniform_buffer: GLint,
+    draw_framebuffer: GLint,
+    read_framebuffer: GLint,
+    active_texture: GLint,
+    viewport: [GLint; 4],
+    /// Source and destination factors of RGB and alpha.
+    blend_func: [GLint; 4],
+    /// Equations of RGB and alpha.
+    blend_equation: [GLint; 2],
+    color_mask: [u8; 4],
+    depth_mask: u8,
+    depth_func: GLint,
+    polygon_mode: [GLint; 2],
+}
+
+fn integer(name: GLenum) -> GLint {
+    let mut value = 0;
+    unsafe { gl::GetIntegerv(name, &mut value) };
+    value
+}
+
+impl GlState {
+    fn capture() -> Self {
+        let mut viewport = [0; 4];
+        let mut color_mask = [0; 4];
+        let mut depth_mask = 0;
+        // some core profiles only return a single value
+        let mut polygon_mode = [gl::FILL as GLint; 2];
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            gl::GetBooleanv(gl::COLOR_WRITEMASK, color_mask.as_mut_ptr());
+            gl::GetBooleanv(gl::DEPTH_WRITEMASK, &mut depth_mask);
+            gl::GetIntegerv(gl::POLYGON_MODE, polygon_mode.as_mut_ptr());
+        }
+
+        Self {
+            capabilities: CAPABILITIES.map(|capability| unsafe { gl::IsEnabled(capability) } != 0),
+            pixel_store: PIXEL_STORE.map(integer),
+            program: integer(gl::CURRENT_PROGRAM),
+            vertex_array: integer(gl::VERTEX_ARRAY_BINDING),
+            array_buffer: integer(gl::ARRAY_BUFFER_BINDING),
+            uniform_buffer: integer(gl::UNIFORM_BUFFER_BINDING),
+            draw_framebuffer: integer(gl::DRAW_FRAMEBUFFER_BINDING),
+            read_framebuffer: integer(gl::READ_FRAMEBUFFER_BINDING),
+            active_texture: integer(gl::ACTIVE_TEXTURE),
+            viewport,
+            blend_func: [
+                gl::BLEND_SRC_RGB,
+                gl::BLEND_DST_RGB,
+                gl::BLEND_SRC_ALPHA,
+                gl::BLEND_DST_ALPHA,
+            ]
+            .map(integer),
+            blend_equation: [gl::BLEND_EQUATION_RGB, gl::BLEND_EQUATION_ALPHA].map(integer),
+            color_mask,
+            depth_mask,
+            depth_func: integer(gl::DEPTH_FUNC),
+            polygon_mode,
+        }
+    }
+
+    fn restore(&self) {
+        unsafe {
+            for (capability, enabled) in CAPABILITIES.iter().zip(self.capabilities) {
+                if enabled {
+                    gl::Enable(*capability);
+                } else {
+                    gl::Disable(*capability);
+                }
+            }
+            for (name, value) in PIXEL_STORE.iter().zip(self.pixel_store) {
+                gl::PixelStorei(*name, value);
+            }
+
+            gl::UseProgram(self.program as GLuint);
+            gl::BindVertexArray(self.vertex_array as GLuint);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.array_buffer as GLuint);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.uniform_buffer as GLuint);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.draw_framebuffer as GLuint);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.read_framebuffer as GLuint);
+            gl::ActiveTexture(self.active_texture as GLenum);
+
+            let [x, y, width, height] = self.viewport;
+            gl::Viewport(x, y, width, height);
+            let [src_rgb, dst_rgb, src_alpha, dst_alpha] = self.blend_func.map(|f| f as GLenum);
+            gl::BlendFuncSeparate(src_rgb, dst_rgb, src_alpha, dst_alpha);
+            let [rgb, alpha] = self.blend_equation.map(|equation| equation as GLenum);
+            gl::BlendEquationSeparate(rgb, alpha);
+            let [r, g, b, a] = self.color_mask;
+            gl::ColorMask(r, g, b, a);
+            gl::DepthMask(self.depth_mask);
+            gl::DepthFunc(self.depth_func as GLenum);
+            gl::PolygonMode(gl::FRONT_AND_BACK, self.polygon_mode[0] as GLenum);
+        }
+    }
+}
+
+/// Access to the GL objects of the renderer for foreign GL code, see
+/// [with_foreign_gl][Renderer::with_foreign_gl].
+pub struct ForeignGl<'a> {
+    renderer: &'a Renderer<OpenGLContext>,
+}
+
+impl ForeignGl<'_> {
+    /// Name of the GL texture, e.g. for a video decoder to upload its frames into.
+    pub fn texture_id(&self, texture: Handle<Texture>) -> Option<GLuint> {
+        self.renderer
+            .textures
+            .get(texture)
+            .map(|texture| texture.id)
+    }
+
+    /// Name of the GL buffer.
+    pub fn buffer_id(&self, buffer: Handle<Buffer>) -> Option<GLuint> {
+        self.renderer.buffers.get(buffer).map(|buffer| buffer.id())
+    }
+}
+
+impl Renderer<OpenGLContext> {
+    /// Runs GL code of other libraries, like video players or UI toolkits, on the context of the
+    /// renderer. The GL state the renderer relies on is saved before and restored after the
+    /// closure, and the cached uniform values of the programs are forgotten, so the closure can
+    /// change any state. Returns None without running it while the renderer is
+    /// [suspended][crate::Backend::suspend].
+    ///
+    /// GL objects of the renderer that the closure deletes aren't restored.
+    pub fn with_foreign_gl<R>(&mut self, f: impl FnOnce(&ForeignGl) -> R) -> Option<R> {
+        self.context.context.as_ref()?.make_current();
+
+        let state = GlState::capture();
+        let result = f(&ForeignGl { renderer: self });
+        state.restore();
+
+        self.programs
+            .iter_mut()
+            .chain(self.compute_programs.iter_mut())
+            .for_each(|program| program.invalidate_uniforms());
+        Some(result)
+    }
+}
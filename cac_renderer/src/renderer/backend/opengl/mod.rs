@@ -1,13 +1,22 @@
 #![cfg(feature = "opengl")]
-use std::ffi::CStr;
+use std::{collections::HashMap, ffi::CStr};
 
 use gl::types::GLenum;
-use render_target::ScreenTarget;
+use render_target::{GLExternalTarget, ScreenTarget};
 
 use crate::{
     generation_vec::GenerationVec,
-    renderer::{vertex_layout::VertexLayout, Material, Uniform},
-    Handle, MaterialProperty, Mesh, Primitive, RenderTarget, Renderer, RendererError,
+    math::Vec3,
+    renderer::{
+        resolve_properties, resolve_texture, shares_state, vertex_layout::VertexLayout,
+        Capabilities, ComputeProgram, DebugView, DrawBatch, DrawCommand, DrawList,
+        EmulatedFeatures, ExternalTarget, FrameArena, FrameStats, Image, InstanceValue, Material,
+        MemoryBarriers, ShaderPreprocessor, ShaderProgram, Texture, TextureFormat, TextureRegion,
+        Uniform, UniformSlice,
+    },
+    scene::Camera,
+    BackendKind, Buffer, ClearFlags, CreationReport, Handle, MaterialProperty, Primitive,
+    RenderTarget, Renderer, RendererBuilder, RendererError,
 };
 
 mod mesh;
@@ -25,47 +34,359 @@ use shader::GLShader;
 mod shader_program;
 use shader_program::GLShaderProgram;
 
-use super::Context;
+mod sampler;
+use sampler::GLSampler;
+
+mod texture;
+use texture::GLTexture;
+
+mod timer;
+use timer::GpuTimer;
+
+mod overdraw;
+use overdraw::OverdrawPass;
+
+mod uniform_ring;
+use uniform_ring::UniformRing;
+
+mod foreign;
+pub use foreign::ForeignGl;
+
+mod reduction;
+pub use reduction::{
+    GpuReduction, LuminanceHistogram, ReductionOp, ReductionResult, MAX_HISTOGRAM_BINS,
+};
+
+mod auto_exposure;
+pub use auto_exposure::{AutoExposure, AutoExposureSettings, EXPOSURE_PROPERTY};
+
+mod motion_vectors;
+pub use motion_vectors::MotionVectorTarget;
+
+#[cfg(unix)]
+mod external_memory;
+#[cfg(unix)]
+pub use external_memory::{ExternalMemory, ExternalSemaphores, SharedTarget};
+
+use super::{Context, DebugLabel};
 
 pub struct OpenGLContext {
-    context: raw_gl_context::GlContext,
+    /// None while the renderer is [suspended][crate::Backend::suspend].
+    context: Option<raw_gl_context::GlContext>,
+    /// Version and settings the context was created with, to create it again on resume.
+    version: (u8, u8),
+    config: RendererBuilder,
     screen_target: ScreenTarget,
+    external_targets: GenerationVec<ExternalTarget, GLExternalTarget>,
+    /// The external target the next update renders into, or None for the screen.
+    active_target: Option<Handle<ExternalTarget>>,
+    /// Copy the frames before the swap, see [set_screen_readback][Renderer::set_screen_readback].
+    screen_readback: bool,
+    /// Viewport of the screen, restored when switching back from an external target.
+    /// It's the size of the window when the context was created.
+    screen_viewport: [i32; 4],
+    /// Compressed texture formats reported by the driver.
+    compressed_formats: Vec<GLenum>,
+    capabilities: Capabilities,
+    /// None if the driver can't import memory and semaphores of other APIs.
+    #[cfg(unix)]
+    external_memory: Option<external_memory::ExternalMemoryFns>,
+    timer: GpuTimer,
+    uniform_ring: UniformRing,
+    /// Set while the overdraw analysis is on.
+    overdraw: Option<OverdrawPass>,
 
-    draw_list: Vec<DrawCommand>,
-}
-
-struct DrawCommand {
-    mesh: Mesh,
-    material: Handle<Material>,
-    instance_data: Vec<(u32, Vec<f32>)>,
+    draw_list: DrawList,
 }
 
 impl Renderer<OpenGLContext> {
+    /// Renderer with the default settings of the [RendererBuilder].
     pub fn new(
         window: &impl raw_window_handle::HasRawWindowHandle,
         version: (u8, u8),
     ) -> Result<Self, RendererError> {
-        let context = OpenGLContext::new(window, version)?;
+        Self::with_config(window, version, &RendererBuilder::default())
+    }
 
-        Ok(Self {
+    pub(crate) fn with_config(
+        window: &impl raw_window_handle::HasRawWindowHandle,
+        version: (u8, u8),
+        config: &RendererBuilder,
+    ) -> Result<Self, RendererError> {
+        let context = OpenGLContext::with_config(window, version, config)?;
+        let preprocessor = ShaderPreprocessor::with_emulation(&context.capabilities.emulated);
+
+        let mut renderer = Self {
             context,
             buffers: GenerationVec::with_capacity(10),
             layouts: GenerationVec::with_capacity(5),
             shaders: GenerationVec::with_capacity(10),
             programs: GenerationVec::with_capacity(5),
+            compute_programs: GenerationVec::with_capacity(5),
+            samplers: GenerationVec::with_capacity(5),
+            textures: GenerationVec::with_capacity(10),
             materials: GenerationVec::with_capacity(10),
-        })
+            stats: FrameStats::default(),
+            frame_arena: FrameArena::with_capacity(1000, 100),
+            uploads: Default::default(),
+            preprocessor,
+            placeholders: None,
+            layout_checks: HashMap::new(),
+            property_validation: Default::default(),
+            attribute_names: Vec::new(),
+            debug_view: DebugView::Off,
+            debug_materials: HashMap::new(),
+            layout_config: Default::default(),
+            residency: Default::default(),
+            retention: Default::default(),
+            creation_report: CreationReport::new(BackendKind::OpenGL(version)),
+            #[cfg(feature = "hot-reload")]
+            watcher: Default::default(),
+        };
+        renderer
+            .frame_arena
+            .set_uniform_alignment(renderer.context.capabilities.uniform_buffer_alignment as usize);
+        renderer.create_placeholders();
+
+        Ok(renderer)
     }
 }
 
+impl Renderer<OpenGLContext> {
+    /// Wraps the GL texture as a render target, e.g. a swapchain image of an OpenXR session.
+    /// The texture stays owned by the caller and has to outlive the target.
+    pub fn wrap_external_texture(
+        &mut self,
+        texture: u32,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Result<Handle<ExternalTarget>, RendererError> {
+        let target = GLExternalTarget::new(texture, gl::TEXTURE_2D, 1, width, height, format)?;
+        Ok(self.context.external_targets.push(target))
+    }
+
+    /// Wraps one of the renderer's 2D textures as a render target, so the following updates draw
+    /// into it and later draws can sample it, e.g. for post-processing.
+    /// [Multisampled][Texture::multisampled] textures are rendered into with multisampling.
+    /// Remove the target before the texture.
+    pub fn texture_target(
+        &mut self,
+        texture: Handle<Texture>,
+    ) -> Result<Handle<ExternalTarget>, RendererError> {
+        let texture = self
+            .textures
+            .get(texture)
+            .ok_or(RendererError::ResourceNotFound {
+                resource: format!("Texture: {texture:?}"),
+            })?;
+        let format = match (texture.target, texture.format) {
+            (_, Some(TextureFormat::Depth24Stencil8)) => {
+                return Err(RendererError::FailedToCreateRenderTarget {
+                    error: "Depth-stencil textures can only be depth-stencil attachments"
+                        .to_string(),
+                })
+            }
+            (gl::TEXTURE_2D | gl::TEXTURE_2D_MULTISAMPLE, Some(format)) => format,
+            _ => {
+                return Err(RendererError::FailedToCreateRenderTarget {
+                    error: "Only uncompressed 2D textures can be render targets".to_string(),
+                })
+            }
+        };
+
+        let target = GLExternalTarget::new(
+            texture.id,
+            texture.target,
+            texture.samples,
+            texture.width,
+            texture.height,
+            format,
+        )?;
+        Ok(self.context.external_targets.push(target))
+    }
+
+    /// Attaches the [Depth24Stencil8][TextureFormat::Depth24Stencil8] texture as depth and stencil
+    /// buffer of the target, or detaches it with None. The texture needs the size and sample count
+    /// of the target, and can be sampled by later draws, see
+    /// [set_depth_stencil_mode][Texture::set_depth_stencil_mode].
+    /// Clear it with [ClearFlags::DEPTH][crate::ClearFlags::DEPTH] and
+    /// [ClearFlags::STENCIL][crate::ClearFlags::STENCIL].
+    pub fn set_depth_stencil_attachment(
+        &mut self,
+        target: Handle<ExternalTarget>,
+        texture: Option<Handle<Texture>>,
+    ) -> Result<(), RendererError> {
+        let external_target = self.context.external_targets.get_mut(target).ok_or(
+            RendererError::ResourceNotFound {
+                resource: format!("ExternalTarget: {target:?}"),
+            },
+        )?;
+
+        let texture = match texture {
+            Some(handle) => {
+                let texture = self
+                    .textures
+                    .get(handle)
+                    .ok_or(RendererError::ResourceNotFound {
+                        resource: format!("Texture: {handle:?}"),
+                    })?;
+                if texture.format != Some(TextureFormat::Depth24Stencil8) {
+                    return Err(RendererError::FailedToCreateRenderTarget {
+                        error: format!("{:?} texture has no depth and stencil", texture.format),
+                    });
+                }
+                Some(texture)
+            }
+            None => None,
+        };
+
+        external_target.attach_depth_stencil(texture)
+    }
+
+    /// Returns the external target to change its clear settings.
+    pub fn external_target(
+        &mut self,
+        target: Handle<ExternalTarget>,
+    ) -> Option<&mut dyn RenderTarget> {
+        self.context
+            .external_targets
+            .get_mut(target)
+            .map(|target| target as &mut dyn RenderTarget)
+    }
+
+    /// Keeps a copy of every frame drawn to the screen, taken before the buffers are swapped, so
+    /// [screenshots][super::Backend::screenshot] and reads of the
+    /// [screen target][super::Backend::screen_target] return the last frame. Neither the back
+    /// buffer after the swap nor the front buffer of obscured or composited windows are defined.
+    /// The copy costs a blit of the screen per frame, so it's off by default, and reads of the
+    /// screen return zeros.
+    pub fn set_screen_readback(&mut self, enabled: bool) {
+        self.context.screen_readback = enabled;
+        if self.context.context.is_some() {
+            let viewport = self.context.screen_viewport;
+            self.context.screen_target.set_readback(viewport, enabled);
+        }
+    }
+
+    /// Makes the following [updates][super::Backend::update] render into the external target,
+    /// or into the screen again with None.
+    /// Updates into external targets don't swap the buffers of the window.
+    pub fn set_external_target(&mut self, target: Option<Handle<ExternalTarget>>) {
+        if self.context.active_target.is_none() && target.is_some() {
+            unsafe { gl::GetIntegerv(gl::VIEWPORT, self.context.screen_viewport.as_mut_ptr()) };
+        }
+        self.context.active_target = target;
+    }
+
+    /// Removes the target. The wrapped texture isn't deleted.
+    pub fn remove_external_target(&mut self, target: Handle<ExternalTarget>) {
+        if self.context.active_target == Some(target) {
+            self.set_external_target(None);
+        }
+        self.context.external_targets.remove(target);
+    }
+
+    /// Renders the surroundings of the position into a new [Rgba16F][TextureFormat::Rgba16F]
+    /// cubemap with resolution * resolution faces, e.g. for a runtime reflection probe.
+    ///
+    /// draw_scene records the draws of each face with the [Camera] of the face, see
+    /// [Camera::cubemap_faces], from [CAPTURE_NEAR] to [CAPTURE_FAR], and the face is drawn with an
+    /// [update][super::Backend::update] into it. The six updates count as frames for the stats,
+    /// and the screen target is active again afterwards.
+    ///
+    /// ```no_run
+    /// # use cac_renderer::{math::*, scene::Scene, OpenGLContext, Renderer};
+    /// # fn probe(renderer: &mut Renderer<OpenGLContext>, scene: &mut Scene) {
+    /// let probe = renderer
+    ///     .capture_cubemap(vec3(0.0, 1.0, 0.0), 128, |renderer, camera| scene.draw(renderer, camera))
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn capture_cubemap(
+        &mut self,
+        position: Vec3,
+        resolution: u32,
+        mut draw_scene: impl FnMut(&mut Self, &Camera),
+    ) -> Result<Handle<Texture>, RendererError> {
+        let format = TextureFormat::Rgba16F;
+        let face = vec![0; resolution as usize * resolution as usize * format.texel_size()];
+        let cubemap = Texture::cubemap(self, [&face; 6].map(Vec::as_slice), resolution, format)?;
+        let depth = vec![0; resolution as usize * resolution as usize * 4];
+        let depth = Texture::new(
+            self,
+            &depth,
+            resolution,
+            resolution,
+            TextureFormat::Depth24Stencil8,
+        )?;
+
+        let previous_target = self.context.active_target;
+        let result = self.capture_faces(position, cubemap, depth, &mut draw_scene);
+        self.set_external_target(previous_target);
+        self.textures.remove(depth);
+        match result {
+            Ok(()) => Ok(cubemap),
+            Err(error) => {
+                self.textures.remove(cubemap);
+                Err(error)
+            }
+        }
+    }
+
+    fn capture_faces(
+        &mut self,
+        position: Vec3,
+        cubemap: Handle<Texture>,
+        depth: Handle<Texture>,
+        draw_scene: &mut impl FnMut(&mut Self, &Camera),
+    ) -> Result<(), RendererError> {
+        let cameras = Camera::cubemap_faces(position, CAPTURE_NEAR, CAPTURE_FAR);
+        for (index, camera) in cameras.iter().enumerate() {
+            let (Some(texture), Some(depth)) =
+                (self.textures.get(cubemap), self.textures.get(depth))
+            else {
+                return Err(RendererError::ResourceNotFound {
+                    resource: format!("Texture: {cubemap:?}"),
+                });
+            };
+            let mut target = GLExternalTarget::new(
+                texture.id,
+                gl::TEXTURE_CUBE_MAP_POSITIVE_X + index as GLenum,
+                1,
+                texture.width,
+                texture.height,
+                TextureFormat::Rgba16F,
+            )?;
+            target.attach_depth_stencil(Some(depth))?;
+            target.set_clear_flags(ClearFlags::COLOR | ClearFlags::DEPTH);
+            let target = self.context.external_targets.push(target);
+
+            self.set_external_target(Some(target));
+            draw_scene(self, camera);
+            super::Backend::update(self);
+            self.remove_external_target(target);
+        }
+        Ok(())
+    }
+}
+
+/// Near plane of the cameras of [capture_cubemap][Renderer::capture_cubemap].
+pub const CAPTURE_NEAR: f32 = 0.1;
+/// Far plane of the cameras of [capture_cubemap][Renderer::capture_cubemap].
+pub const CAPTURE_FAR: f32 = 1000.0;
+
 impl From<Primitive> for GLenum {
     fn from(primitive: Primitive) -> Self {
         match primitive {
             Primitive::Triangles => gl::TRIANGLES,
             Primitive::TriangleStrip => gl::TRIANGLE_STRIP,
+            Primitive::TriangleFan => gl::TRIANGLE_FAN,
             Primitive::Lines => gl::LINES,
             Primitive::LineStrip => gl::LINE_STRIP,
+            Primitive::LineLoop => gl::LINE_LOOP,
             Primitive::Points => gl::POINTS,
+            Primitive::Patches(_) => gl::PATCHES,
         }
     }
 }
@@ -74,6 +395,14 @@ impl OpenGLContext {
     pub fn new(
         window: &impl raw_window_handle::HasRawWindowHandle,
         version: (u8, u8),
+    ) -> Result<Self, RendererError> {
+        Self::with_config(window, version, &RendererBuilder::default())
+    }
+
+    fn with_config(
+        window: &impl raw_window_handle::HasRawWindowHandle,
+        version: (u8, u8),
+        config: &RendererBuilder,
     ) -> Result<Self, RendererError> {
         let context = raw_gl_context::GlContext::create(
             window,
@@ -81,6 +410,11 @@ impl OpenGLContext {
                 alpha_bits: 0,
                 version,
                 profile: raw_gl_context::Profile::Core,
+                depth_bits: config.depth_bits,
+                stencil_bits: config.stencil_bits,
+                samples: (config.samples > 1).then_some(config.samples),
+                srgb: config.srgb,
+                vsync: config.vsync,
                 ..Default::default()
             },
         );
@@ -105,7 +439,9 @@ impl OpenGLContext {
 
         //use debug callback for errors
         // it is supported on GL 4.3, so we need to check wether it or a fallback are loaded.
-        if gl::DebugMessageCallback::is_loaded() {
+        if !config.debug {
+            unsafe { gl::Disable(gl::DEBUG_OUTPUT) };
+        } else if gl::DebugMessageCallback::is_loaded() {
             unsafe {
                 gl::Enable(gl::DEBUG_OUTPUT);
                 gl::DebugMessageCallback(Some(debug_callback), std::ptr::null());
@@ -114,12 +450,104 @@ impl OpenGLContext {
             log::warn!("DebugMessageCallback is not loaded!")
         }
 
+        // filters cubemaps across the faces instead of clamping at their edges
+        unsafe { gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS) };
+        // texture rows of any size are tightly packed, e.g. RGB8 textures with odd widths
+        unsafe { gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1) };
+
+        let mut screen_viewport = [0; 4];
+        unsafe { gl::GetIntegerv(gl::VIEWPORT, screen_viewport.as_mut_ptr()) };
+
+        let compressed_formats = unsafe {
+            let mut count = 0;
+            gl::GetIntegerv(gl::NUM_COMPRESSED_TEXTURE_FORMATS, &mut count);
+            let mut formats = vec![0; count.max(0) as usize];
+            gl::GetIntegerv(gl::COMPRESSED_TEXTURE_FORMATS, formats.as_mut_ptr());
+            formats.into_iter().map(|format| format as GLenum).collect()
+        };
+
+        let mut screen_target = ScreenTarget::default();
+        screen_target.set_srgb_encode(config.srgb);
+
+        let capabilities = query_capabilities();
+        #[cfg(unix)]
+        let external_memory =
+            external_memory::ExternalMemoryFns::load(&capabilities.extensions, |name| {
+                context.get_proc_address(name)
+            });
+
         Ok(OpenGLContext {
-            context,
-            screen_target: ScreenTarget::default(),
-            draw_list: Vec::with_capacity(100),
+            context: Some(context),
+            version,
+            config: config.clone(),
+            screen_target,
+            external_targets: GenerationVec::with_capacity(2),
+            active_target: None,
+            screen_readback: false,
+            screen_viewport,
+            compressed_formats,
+            capabilities,
+            #[cfg(unix)]
+            external_memory,
+            timer: GpuTimer::default(),
+            uniform_ring: UniformRing::default(),
+            overdraw: None,
+            draw_list: DrawList::with_capacity(100),
+        })
+    }
+}
+
+/// Queries the limits of the current context. Features are detected by their entry points, like
+/// everywhere else in the backend.
+fn query_capabilities() -> Capabilities {
+    let integer = |name| {
+        let mut value = 0;
+        unsafe { gl::GetIntegerv(name, &mut value) };
+        value.max(0) as u32
+    };
+
+    let extensions = (0..integer(gl::NUM_EXTENSIONS))
+        .filter_map(|index| {
+            let name = unsafe { gl::GetStringi(gl::EXTENSIONS, index) };
+            (!name.is_null()).then(|| {
+                let name = unsafe { CStr::from_ptr(name as *const std::ffi::c_char) };
+                name.to_string_lossy().into_owned()
+            })
         })
+        .collect();
+
+    Capabilities {
+        max_texture_size: integer(gl::MAX_TEXTURE_SIZE),
+        max_cubemap_size: integer(gl::MAX_CUBE_MAP_TEXTURE_SIZE),
+        max_array_layers: integer(gl::MAX_ARRAY_TEXTURE_LAYERS),
+        max_vertex_attribs: integer(gl::MAX_VERTEX_ATTRIBS),
+        max_texture_units: integer(gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS),
+        max_samples: integer(gl::MAX_SAMPLES),
+        supports_compute: gl::DispatchCompute::is_loaded(),
+        supports_dsa: gl::CreateTextures::is_loaded(),
+        supports_tessellation: gl::PatchParameteri::is_loaded(),
+        supports_texture_views: gl::TextureView::is_loaded(),
+        supports_multi_draw_indirect: gl::MultiDrawElementsIndirect::is_loaded(),
+        uniform_buffer_alignment: integer(gl::UNIFORM_BUFFER_OFFSET_ALIGNMENT),
+        emulated: EmulatedFeatures {
+            srgb_framebuffer: !default_framebuffer_is_srgb(),
+        },
+        extensions,
+    }
+}
+
+/// Whether the color buffer of the default framebuffer, which has to be bound, stores sRGB.
+fn default_framebuffer_is_srgb() -> bool {
+    let mut encoding = 0;
+    unsafe {
+        gl::GetFramebufferAttachmentParameteriv(
+            gl::FRAMEBUFFER,
+            gl::BACK_LEFT,
+            gl::FRAMEBUFFER_ATTACHMENT_COLOR_ENCODING,
+            &mut encoding,
+        );
     }
+    encoding == gl::SRGB as i32
 }
 
 impl Context for OpenGLContext {
@@ -128,12 +556,17 @@ impl Context for OpenGLContext {
     type Context = Self;
     type Shader = GLShader;
     type ShaderProgram = GLShaderProgram;
+    type ComputeProgram = GLShaderProgram;
+    type Sampler = GLSampler;
+    type Texture = GLTexture;
 }
 
 impl crate::Renderer<OpenGLContext> {
     /// Creates a renderer using the OpenGL backend.
     /// By default, it will try to create a 3.3 or newer Core Context.
-    /// It will also set the debug callbacks in debug builds
+    /// It will also set the debug callbacks in debug builds.
+    /// See [RendererBuilder] for more settings and fallbacks.
+    #[deprecated(note = "use `new` or `DynRenderer::builder().build_opengl(window)`")]
     pub fn new_opengl(
         window: &impl raw_window_handle::HasRawWindowHandle,
         version: (u8, u8),
@@ -165,108 +598,534 @@ impl super::Backend for Renderer<OpenGLContext> {
         &mut self.context.screen_target
     }
 
-    fn draw(
+    fn screenshot(&mut self) -> Image {
+        let [x, y, width, height] = self
+            .context
+            .screen_viewport
+            .map(|value| value.max(0) as u32);
+        let pixels = self.context.screen_target.read_pixels(TextureRegion {
+            x,
+            y,
+            width,
+            height,
+        });
+
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    fn capabilities(&self) -> &Capabilities {
+        &self.context.capabilities
+    }
+
+    fn supports_compressed_format(&self, format: crate::CompressedFormat) -> bool {
+        self.context
+            .compressed_formats
+            .contains(&texture::compressed_format(format))
+    }
+
+    fn draw_at_depth(
         &mut self,
         mesh: crate::Mesh,
         material: Handle<Material>,
+        depth: f32,
         instance_properties: &[MaterialProperty],
     ) {
-        let mut instance_data = Vec::with_capacity(instance_properties.len());
-        for prop in instance_properties {
-            let loc = match prop.property {
-                crate::PropertyId::Name(name) => {
-                    log::warn!("Using uniform names for instance properties is super slow. Use locations instead!");
-                    if let Some(material) = self.materials.get(material) {
-                        if let Some(program) = self.programs.get(material.program) {
-                            program.get_uniform_location(name)
-                        } else {
-                            log::warn!("Program not found!");
-                            0
-                        }
-                    } else {
-                        log::warn!("Material not found!");
-                        0
-                    }
-                }
-                crate::PropertyId::Location(loc) => loc,
-            };
+        let Some((mesh, material)) = self.resolve_draw(mesh, material) else {
+            return;
+        };
+        let Some((material, instance_data)) =
+            self.draw_material(&mesh, material, instance_properties)
+        else {
+            return;
+        };
+        let sort_key = self.sort_key(&mesh, material, depth);
+        self.context.draw_list.push(DrawCommand::new(
+            sort_key,
+            mesh,
+            depth,
+            material,
+            instance_data,
+        ));
+    }
+
+    fn draw_indirect(
+        &mut self,
+        layout: Handle<VertexLayout>,
+        material: Handle<Material>,
+        indirect_buffer: Handle<Buffer>,
+        count: u32,
+    ) {
+        if !self.context.capabilities.supports_multi_draw_indirect {
+            log::warn!("Indirect draws need OpenGL 4.3, dropping the draw");
+            return;
+        }
+        if let Some(command) = self.indirect_command(layout, material, indirect_buffer, count) {
+            self.context.draw_list.push(command);
+        }
+    }
 
-            let mut data = Vec::with_capacity(12);
+    fn draw_batches(&mut self, batches: &[DrawBatch]) {
+        let commands = self.batch_commands(batches);
+        self.context.draw_list.extend(commands);
+    }
 
-            match prop.value {
-                crate::PropertyValue::F32(values) => {
-                    values.iter().enumerate().for_each(|(index, v)| {
-                        let bits = v.to_le_bytes();
-                        let index = index * 4;
-                        data.splice(index..(index + 4), bits);
-                    });
+    fn dispatch(
+        &mut self,
+        program: Handle<ComputeProgram>,
+        x: u32,
+        y: u32,
+        z: u32,
+        properties: &[MaterialProperty],
+    ) -> Result<(), RendererError> {
+        let program =
+            self.compute_programs
+                .get_mut(program)
+                .ok_or(RendererError::ResourceNotFound {
+                    resource: format!("ComputeProgram: {program:?}"),
+                })?;
+
+        program.bind();
+        let uniforms: Vec<_> = resolve_properties(program, properties).collect();
+        for value in uniforms {
+            match value {
+                InstanceValue::Uniform(location, values) => {
+                    program.set_uniform_f32(location, values)
+                }
+                InstanceValue::Block(..) => {
+                    log::warn!("Uniform data of the frame can't be used by dispatches")
                 }
             }
-            instance_data.push((loc, data));
         }
 
-        self.context.draw_list.push(DrawCommand {
-            mesh,
-            material,
-            instance_data: Vec::with_capacity(instance_properties.len()),
-        });
+        unsafe { gl::DispatchCompute(x, y, z) };
+        Ok(())
+    }
+
+    fn memory_barrier(&mut self, barriers: MemoryBarriers) {
+        let bits = [
+            (
+                MemoryBarriers::VERTEX_BUFFER,
+                gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT,
+            ),
+            (MemoryBarriers::INDEX_BUFFER, gl::ELEMENT_ARRAY_BARRIER_BIT),
+            (MemoryBarriers::UNIFORM_BUFFER, gl::UNIFORM_BARRIER_BIT),
+            (MemoryBarriers::TEXTURE_FETCH, gl::TEXTURE_FETCH_BARRIER_BIT),
+            (
+                MemoryBarriers::IMAGE_ACCESS,
+                gl::SHADER_IMAGE_ACCESS_BARRIER_BIT,
+            ),
+            (
+                MemoryBarriers::STORAGE_BUFFER,
+                gl::SHADER_STORAGE_BARRIER_BIT,
+            ),
+            (MemoryBarriers::BUFFER_UPDATE, gl::BUFFER_UPDATE_BARRIER_BIT),
+        ]
+        .into_iter()
+        .filter(|(barrier, _)| barriers & *barrier)
+        .fold(0, |bits, (_, bit)| bits | bit);
+
+        if bits != 0 && gl::MemoryBarrier::is_loaded() {
+            unsafe { gl::MemoryBarrier(bits) };
+        }
+    }
+
+    fn begin_gpu_scope(&mut self, name: &str) {
+        if let Some(open) = self.context.timer.open_scope() {
+            log::warn!("GPU scope {open} is still open while beginning {name}, ending it");
+            self.end_gpu_scope();
+        }
+        push_debug_group(name);
+        self.context.timer.begin(name);
+    }
+
+    fn end_gpu_scope(&mut self) {
+        if self.context.timer.end() {
+            pop_debug_group();
+        }
+    }
+
+    fn push_debug_group(&mut self, name: &str) {
+        push_debug_group(name);
+    }
+
+    fn pop_debug_group(&mut self) {
+        pop_debug_group();
+    }
+
+    fn set_overdraw_analysis(&mut self, enabled: bool) -> Result<(), RendererError> {
+        match (self.context.overdraw, enabled) {
+            (None, true) => self.context.overdraw = Some(self.create_overdraw_pass()?),
+            (Some(pass), false) => {
+                self.remove_overdraw_pass(pass);
+                self.context.overdraw = None;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn suspend(&mut self) {
+        let Some(context) = &self.context.context else {
+            return;
+        };
+        context.make_current();
+        unsafe { gl::Finish() };
+
+        // the GL objects are deleted while their context is still current
+        if let Some(pass) = self.context.overdraw.take() {
+            self.remove_overdraw_pass(pass);
+        }
+        self.release_resources();
+        self.context.screen_target.set_readback([0; 4], false);
+        self.context.external_targets.clear();
+        self.context.active_target = None;
+        self.context.timer = GpuTimer::default();
+        self.context.uniform_ring = UniformRing::default();
+        self.context.draw_list.clear();
+        self.frame_arena.reset();
+
+        if let Some(context) = self.context.context.take() {
+            context.make_not_current();
+        }
+    }
+
+    fn resume(
+        &mut self,
+        window: &impl raw_window_handle::HasRawWindowHandle,
+    ) -> Result<(), RendererError> {
+        if self.context.context.is_some() {
+            return Ok(());
+        }
+
+        let mut context =
+            OpenGLContext::with_config(window, self.context.version, &self.context.config)?;
+        // keeps the generations, so the handles of the released targets stay invalid
+        context.external_targets = std::mem::take(&mut self.context.external_targets);
+        context.screen_readback = self.context.screen_readback;
+        let viewport = context.screen_viewport;
+        context
+            .screen_target
+            .set_readback(viewport, context.screen_readback);
+        self.context = context;
+        self.restore_builtins();
+        Ok(())
+    }
+
+    fn is_suspended(&self) -> bool {
+        self.context.context.is_none()
     }
 
     fn update(&mut self) {
-        self.context.screen_target.clear();
+        if self.context.context.is_none() {
+            self.context.draw_list.clear();
+            self.frame_arena.reset();
+            return;
+        }
+        self.process_uploads();
+
+        let context = &mut self.context;
+        let external_target = context
+            .active_target
+            .and_then(|target| context.external_targets.get_mut(target));
+        let to_screen = external_target.is_none();
+        push_debug_group(if to_screen {
+            "update screen"
+        } else {
+            "update external target"
+        });
+        match external_target {
+            Some(target) => {
+                target.bind();
+                target.clear();
+            }
+            None => {
+                let [x, y, width, height] = context.screen_viewport;
+                context.screen_target.bind();
+                unsafe {
+                    if width > 0 && height > 0 {
+                        gl::Viewport(x, y, width, height);
+                    }
+                }
+                context.screen_target.clear();
+            }
+        }
+
+        self.context.draw_list.sort();
+        self.stats.clear();
 
         let mut has_indices = false;
         let mut bound_vao = Handle::<VertexLayout>::new();
         let mut bound_material = Handle::<Material>::new();
+        let mut bound_program = Handle::<ShaderProgram>::new();
+        // overrides of the previous draw need to be undone by binding the material again
+        let mut material_dirty = false;
+        // the patch size of the last frame is unknown, so it's always set for the first patch draw
+        let mut patch_vertices = 0;
+        // the uniform data of all draws is uploaded at once, the draws bind their ranges of it
+        let uniform_data = self.frame_arena.uniform_data();
+        let uniform_buffer = if uniform_data.is_empty() {
+            0
+        } else {
+            self.context.uniform_ring.upload(uniform_data)
+        };
+        let mut bound_blocks: Vec<Option<UniformSlice>> = Vec::new();
+        let mut timed_run = None;
+        let placeholder_texture = self.placeholder_texture();
+
+        // every draw adds its color, so the brightness counts the draws of a pixel
+        let overdraw = self.debug_view == DebugView::Overdraw;
+        if overdraw {
+            unsafe {
+                gl::Enable(gl::BLEND);
+                gl::BlendFunc(gl::ONE, gl::ONE);
+            }
+        }
 
-        for command in &self.context.draw_list {
+        for command in self.context.draw_list.iter() {
             if command.mesh.vertex_layout != bound_vao {
                 if let Some(vertex_array) = self.layouts.get_mut(command.mesh.vertex_layout) {
                     vertex_array.bind();
                     has_indices = vertex_array.has_indices;
                     bound_vao = command.mesh.vertex_layout;
+                    self.stats.record_state_change();
                 } else {
                     log::warn!("Vertex Layout not found");
                     continue;
                 }
             }
 
-            if command.material != bound_material {
+            if command.material != bound_material
+                && !material_dirty
+                && shares_state(&self.materials, bound_material, command.material)
+            {
+                // clones of the bound material that weren't changed, so its state is still valid
+                bound_material = command.material;
+            } else if command.material != bound_material || material_dirty {
                 if let Some(material) = self.materials.get(command.material) {
                     bound_material = command.material;
+                    material_dirty = false;
+                    self.stats.record_material_bind();
                     if let Some(program) = self.programs.get_mut(material.program) {
+                        // materials of the same program don't need to bind it again
+                        if material.program != bound_program {
+                            program.bind();
+                            bound_program = material.program;
+                        }
                         program.set_uniform_data(&material.data);
+                    }
+                    for (unit, binding) in material.textures.iter().enumerate() {
+                        let Some(texture) =
+                            resolve_texture(&self.textures, placeholder_texture, binding)
+                                .and_then(|texture| self.textures.get(texture))
+                        else {
+                            continue;
+                        };
+                        let sampler = binding
+                            .sampler
+                            .and_then(|sampler| self.samplers.get(sampler))
+                            .map_or(0, |sampler| sampler.id);
+                        unsafe {
+                            gl::ActiveTexture(gl::TEXTURE0 + unit as u32);
+                            gl::BindTexture(texture.target, texture.id);
+                            gl::BindSampler(unit as u32, sampler);
+                        }
+                        self.stats.record_state_change();
+                    }
+                }
+            }
 
-                        command.instance_data.iter().for_each(|(location, val)| {
-                            program.set_uniform_f32(*location, val);
-                        })
+            if !command.instance_data.is_empty() {
+                if let Some(material) = self.materials.get(command.material) {
+                    if let Some(program) = self.programs.get_mut(material.program) {
+                        for value in self.frame_arena.get(command.instance_data) {
+                            match value {
+                                InstanceValue::Uniform(location, values) => {
+                                    program.set_uniform_f32(location, values);
+                                    material_dirty = true;
+                                }
+                                InstanceValue::Block(binding, slice) => {
+                                    let binding = binding as usize;
+                                    if bound_blocks.len() <= binding {
+                                        bound_blocks.resize(binding + 1, None);
+                                    }
+                                    if bound_blocks[binding] == Some(slice) {
+                                        continue;
+                                    }
+                                    unsafe {
+                                        gl::BindBufferRange(
+                                            gl::UNIFORM_BUFFER,
+                                            binding as u32,
+                                            uniform_buffer,
+                                            slice.offset() as isize,
+                                            slice.size() as isize,
+                                        );
+                                    }
+                                    bound_blocks[binding] = Some(slice);
+                                    self.stats.record_state_change();
+                                }
+                            }
+                        }
                     }
                 }
             }
 
-            if has_indices {
-                let start_index = command.mesh.start_index as i32;
-                unsafe {
-                    gl::DrawElements(
-                        command.mesh.primitive.into(),
-                        command.mesh.count as i32,
-                        gl::UNSIGNED_BYTE,
-                        start_index as *const i32 as *const std::ffi::c_void,
-                    );
+            if let Primitive::Patches(vertices) = command.mesh.primitive {
+                if vertices != patch_vertices {
+                    unsafe { gl::PatchParameteri(gl::PATCH_VERTICES, vertices as i32) };
+                    patch_vertices = vertices;
+                    self.stats.record_state_change();
                 }
+            }
+
+            let run = (command.material, command.sort_key.layer());
+            if timed_run != Some(run) {
+                self.context.timer.begin_run(run.0, run.1);
+                timed_run = Some(run);
+            }
+            self.stats.record_draw(command);
+            match command.indirect {
+                Some(buffer) => match self.buffers.get(buffer) {
+                    Some(buffer) => draw_indirect(&command.mesh, buffer.id(), has_indices),
+                    None => log::warn!("Indirect buffer {buffer:?} not found"),
+                },
+                None => draw_mesh(&command.mesh, has_indices),
+            }
+        }
+        self.context.timer.end_runs();
+        if overdraw {
+            unsafe { gl::Disable(gl::BLEND) };
+        }
+        if let Some(mut pass) = self.context.overdraw {
+            self.draw_overdraw(&mut pass);
+            self.context.overdraw = Some(pass);
+        }
+
+        pop_debug_group();
+        if to_screen {
+            self.context.screen_target.copy_back_buffer();
+            if let Some(context) = &self.context.context {
+                context.swap_buffers();
+            }
+            if let Some(scopes) = self.context.timer.end_frame() {
+                self.stats.gpu_scopes = scopes;
+            }
+        }
+        self.stats.attribute_gpu_time(self.context.timer.runs());
+        self.context.draw_list.clear();
+        self.frame_arena.reset();
+        self.collect_garbage();
+        self.enforce_memory_budget();
+    }
+}
+
+/// Type of the indices in index buffers.
+const INDEX_TYPE: GLenum = gl::UNSIGNED_BYTE;
+const INDEX_SIZE: usize = std::mem::size_of::<u8>();
+
+/// Draws the mesh with the bound vertex array, which has an index buffer if has_indices is set.
+fn draw_mesh(mesh: &crate::Mesh, has_indices: bool) {
+    if has_indices {
+        // the offset into the bound index buffer is passed as pointer, in bytes
+        let offset = (mesh.index_offset * INDEX_SIZE) as *const std::ffi::c_void;
+        unsafe {
+            if mesh.base_vertex != 0 && gl::DrawElementsBaseVertex::is_loaded() {
+                gl::DrawElementsBaseVertex(
+                    mesh.primitive.into(),
+                    mesh.count as i32,
+                    INDEX_TYPE,
+                    offset,
+                    mesh.base_vertex,
+                );
             } else {
-                unsafe {
-                    gl::DrawArrays(
-                        command.mesh.primitive.into(),
-                        command.mesh.start_index as i32,
-                        command.mesh.count as i32,
-                    );
+                if mesh.base_vertex != 0 {
+                    log::warn!("Base vertex of {mesh:?} ignored, glDrawElementsBaseVertex is not available");
                 }
+                gl::DrawElements(mesh.primitive.into(), mesh.count as i32, INDEX_TYPE, offset);
             }
         }
+    } else {
+        unsafe {
+            gl::DrawArrays(
+                mesh.primitive.into(),
+                mesh.vertex_offset as i32,
+                mesh.count as i32,
+            );
+        }
+    }
+}
 
-        self.context.context.swap_buffers();
-        self.context.draw_list.clear();
+/// Draws mesh.count [IndirectCommand][crate::IndirectCommand]s of the buffer, starting at the
+/// command mesh.index_offset, with the bound vertex array.
+fn draw_indirect(mesh: &crate::Mesh, buffer: gl::types::GLuint, has_indices: bool) {
+    let offset = (mesh.index_offset * crate::IndirectCommand::STRIDE) as *const std::ffi::c_void;
+    let stride = crate::IndirectCommand::STRIDE as i32;
+    unsafe {
+        gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, buffer);
+        if has_indices {
+            gl::MultiDrawElementsIndirect(
+                mesh.primitive.into(),
+                INDEX_TYPE,
+                offset,
+                mesh.count as i32,
+                stride,
+            );
+        } else {
+            // the first three fields match the arrays command, and base_vertex is 0 in place of
+            // its base instance
+            gl::MultiDrawArraysIndirect(mesh.primitive.into(), offset, mesh.count as i32, stride);
+        }
+    }
+}
+
+/// Labels and group names are cut off at the smallest maximum length GL guarantees.
+const MAX_LABEL_LENGTH: usize = 255;
+
+/// Names the object in graphics debuggers. Requires a context with KHR_debug, core since 4.3.
+pub(super) fn object_label(identifier: GLenum, name: gl::types::GLuint, label: &str) {
+    if gl::ObjectLabel::is_loaded() {
+        let label = &label.as_bytes()[..label.len().min(MAX_LABEL_LENGTH)];
+        unsafe {
+            gl::ObjectLabel(
+                identifier,
+                name,
+                label.len() as i32,
+                label.as_ptr() as *const i8,
+            )
+        };
+    }
+}
+
+/// Clears the errors of previous calls, so only the ones of the next calls are checked. A lost
+/// context keeps reporting errors, so it gives up eventually.
+pub(super) fn clear_errors() {
+    for _ in 0..16 {
+        if unsafe { gl::GetError() } == gl::NO_ERROR {
+            break;
+        }
+    }
+}
+
+fn push_debug_group(name: &str) {
+    if gl::PushDebugGroup::is_loaded() {
+        let name = &name.as_bytes()[..name.len().min(MAX_LABEL_LENGTH)];
+        unsafe {
+            gl::PushDebugGroup(
+                gl::DEBUG_SOURCE_APPLICATION,
+                0,
+                name.len() as i32,
+                name.as_ptr() as *const i8,
+            )
+        };
+    }
+}
+
+fn pop_debug_group() {
+    if gl::PopDebugGroup::is_loaded() {
+        unsafe { gl::PopDebugGroup() };
     }
 }
 
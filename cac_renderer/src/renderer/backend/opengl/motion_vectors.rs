@@ -0,0 +1,94 @@
+use crate::{
+    renderer::{Backend, ExternalTarget, Texture},
+    scene::{Camera, Scene},
+    ClearFlags, Color32, Handle, Material, Renderer, RendererError, TextureFormat,
+};
+
+use super::OpenGLContext;
+
+/// Target of the motion vectors of a [Scene], with a [Rgba16F][TextureFormat::Rgba16F] texture
+/// of the velocities and its own depth, so only the closest surface of every pixel writes its
+/// velocity. Pixels without geometry have no motion.
+///
+/// ```no_run
+/// # use cac_renderer::{scene::*, Backend, MotionVectorTarget, OpenGLContext, Renderer};
+/// # fn frame(renderer: &mut Renderer<OpenGLContext>, scene: &mut Scene, camera: &Camera) {
+/// let program = motion_vector_program(renderer).unwrap();
+/// let material = renderer.create_material(program, &[]).unwrap();
+/// let motion = MotionVectorTarget::new(renderer, 1280, 720).unwrap();
+///
+/// motion.draw(renderer, scene, camera, material);
+/// scene.draw(renderer, camera);
+/// renderer.update();
+/// scene.end_frame(camera);
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct MotionVectorTarget {
+    /// The velocities in the red and green channels, sampled by motion blur or TAA.
+    pub texture: Handle<Texture>,
+    pub depth: Handle<Texture>,
+    pub target: Handle<ExternalTarget>,
+}
+
+impl MotionVectorTarget {
+    pub fn new(
+        renderer: &mut Renderer<OpenGLContext>,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, RendererError> {
+        let texels = width as usize * height as usize;
+        let format = TextureFormat::Rgba16F;
+        let texture = Texture::new(
+            renderer,
+            &vec![0; texels * format.texel_size()],
+            width,
+            height,
+            format,
+        )?;
+        let depth = Texture::new(
+            renderer,
+            &vec![0; texels * 4],
+            width,
+            height,
+            TextureFormat::Depth24Stencil8,
+        )?;
+
+        let target = renderer.texture_target(texture)?;
+        renderer.set_depth_stencil_attachment(target, Some(depth))?;
+        if let Some(target) = renderer.external_target(target) {
+            target.set_clear_color(Color32::from_rgba(0.0, 0.0, 0.0, 0.0));
+            target.set_clear_flags(ClearFlags::COLOR | ClearFlags::DEPTH);
+        }
+
+        Ok(Self {
+            texture,
+            depth,
+            target,
+        })
+    }
+
+    /// Draws the [motion vectors][Scene::draw_motion_vectors] of the scene with the material in
+    /// an [update][Backend::update] into the target. The previous target is active again
+    /// afterwards.
+    pub fn draw(
+        &self,
+        renderer: &mut Renderer<OpenGLContext>,
+        scene: &mut Scene,
+        camera: &Camera,
+        material: Handle<Material>,
+    ) {
+        let previous_target = renderer.context.active_target;
+        renderer.set_external_target(Some(self.target));
+        scene.draw_motion_vectors(renderer, camera, material);
+        Backend::update(renderer);
+        renderer.set_external_target(previous_target);
+    }
+
+    /// Removes the target and its textures.
+    pub fn remove(self, renderer: &mut Renderer<OpenGLContext>) {
+        renderer.remove_external_target(self.target);
+        renderer.textures.remove(self.texture);
+        renderer.textures.remove(self.depth);
+    }
+}
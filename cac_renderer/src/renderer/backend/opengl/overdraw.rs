@@ -0,0 +1,212 @@
+use gl::types::GLuint;
+
+use crate::{
+    renderer::{
+        DebugView, OverdrawStats, Primitive, ShaderProgram, Texture, TextureRegion, Uniform,
+    },
+    Handle, Material, Renderer, RendererError, TextureFormat,
+};
+
+use super::OpenGLContext;
+
+/// Counts the fragments of every pixel, image atomics need GLSL 4.20.
+const COUNT_FS_SOURCE: &str = r##"
+    #version 420 core
+    layout(r32ui, binding = 0) uniform uimage2D counts;
+    in vec4 value;
+    void main() { imageAtomicAdd(counts, ivec2(gl_FragCoord.xy), 1u); }"##;
+
+/// Fullscreen triangle without any vertex data.
+const HEATMAP_VS_SOURCE: &str = r##"
+    #version 330 core
+    const vec2 POSITIONS[3] = vec2[3](vec2(-1.0, -1.0), vec2(3.0, -1.0), vec2(-1.0, 3.0));
+    void main() { gl_Position = vec4(POSITIONS[gl_VertexID], 0.0, 1.0); }"##;
+
+const HEATMAP_FS_SOURCE: &str = r##"
+    #version 330 core
+    uniform usampler2D counts;
+    out vec4 result;
+    const vec3 HEAT[5] = vec3[5](
+        vec3(0.0, 0.0, 0.0),
+        vec3(0.0, 0.0, 1.0),
+        vec3(0.0, 1.0, 0.0),
+        vec3(1.0, 1.0, 0.0),
+        vec3(1.0, 0.0, 0.0)
+    );
+    void main() {
+        uint count = texelFetch(counts, ivec2(gl_FragCoord.xy), 0).r;
+        result = vec4(count < 5u ? HEAT[count] : vec3(1.0), 1.0);
+    }"##;
+
+/// Resources of the overdraw analysis, see
+/// [set_overdraw_analysis][crate::Backend::set_overdraw_analysis].
+///
+/// The heatmap is black where nothing was drawn, then blue, green, yellow and red for 1 to 4
+/// draws, and white for more.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct OverdrawPass {
+    /// Draws the positions like the [DebugView] materials and counts the fragments.
+    count_material: Handle<Material>,
+    heatmap_program: Handle<ShaderProgram>,
+    /// R32UI counts, created at the size of the viewport in the first update.
+    counts: Option<(Handle<Texture>, u32, u32)>,
+    /// Empty vertex array for the fullscreen triangle, core profiles can't draw without one.
+    vao: GLuint,
+}
+
+impl Renderer<OpenGLContext> {
+    pub(super) fn create_overdraw_pass(&mut self) -> Result<OverdrawPass, RendererError> {
+        if !gl::BindImageTexture::is_loaded() {
+            return Err(RendererError::UnsupportedFeature {
+                feature: "Image load/store (OpenGL 4.2)".to_string(),
+            });
+        }
+
+        let count_material = self.create_debug_material(DebugView::Overdraw, COUNT_FS_SOURCE)?;
+        let heatmap_program =
+            ShaderProgram::from_sources(self, HEATMAP_VS_SOURCE, HEATMAP_FS_SOURCE)?;
+
+        let mut vao = 0;
+        unsafe { gl::GenVertexArrays(1, &mut vao) };
+
+        Ok(OverdrawPass {
+            count_material,
+            heatmap_program,
+            counts: None,
+            vao,
+        })
+    }
+
+    pub(super) fn remove_overdraw_pass(&mut self, pass: OverdrawPass) {
+        if let Some(material) = self.materials.get(pass.count_material) {
+            self.programs.remove(material.program);
+        }
+        self.materials.remove(pass.count_material);
+        self.programs.remove(pass.heatmap_program);
+        if let Some((texture, ..)) = pass.counts {
+            self.textures.remove(texture);
+        }
+        unsafe { gl::DeleteVertexArrays(1, &pass.vao) };
+    }
+
+    /// Draws the recorded draws of the frame again to count the fragments of every pixel, then
+    /// replaces the frame with the heatmap and reads the counts back into the stats.
+    ///
+    /// Patches are skipped, the counting program has no tessellation stages.
+    pub(super) fn draw_overdraw(&mut self, pass: &mut OverdrawPass) {
+        let mut viewport = [0; 4];
+        unsafe { gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr()) };
+        // the counts are indexed by window coordinates
+        let width = (viewport[0] + viewport[2]).max(1) as u32;
+        let height = (viewport[1] + viewport[3]).max(1) as u32;
+
+        let zeros = vec![0; width as usize * height as usize * 4];
+        let counts = match pass.counts {
+            Some((texture, w, h)) if (w, h) == (width, height) => {
+                let region = TextureRegion {
+                    x: 0,
+                    y: 0,
+                    width,
+                    height,
+                };
+                Texture::update(self, texture, region, &zeros, None).map(|()| texture)
+            }
+            previous => {
+                if let Some((texture, ..)) = previous {
+                    self.textures.remove(texture);
+                }
+                Texture::new(self, &zeros, width, height, TextureFormat::R32UI)
+            }
+        };
+        let counts = match counts {
+            Ok(counts) => counts,
+            Err(error) => {
+                log::warn!("Failed to create the overdraw counts: {error}");
+                pass.counts = None;
+                return;
+            }
+        };
+        pass.counts = Some((counts, width, height));
+        let Some(count_program) = self
+            .materials
+            .get(pass.count_material)
+            .map(|material| material.program)
+        else {
+            return;
+        };
+
+        let commands: Vec<_> = self.context.draw_list.iter().cloned().collect();
+        let mut draws = Vec::with_capacity(commands.len());
+        for command in commands {
+            if !matches!(command.mesh.primitive, Primitive::Patches(_))
+                && self.layout_fits(command.mesh.vertex_layout, pass.count_material)
+            {
+                let transforms = self.command_transforms(&command, count_program);
+                let indirect = command
+                    .indirect
+                    .and_then(|buffer| self.buffers.get(buffer))
+                    .map(|buffer| buffer.id());
+                draws.push((command.mesh, indirect, transforms));
+            }
+        }
+
+        let Some(texture_id) = self.textures.get(counts).map(|texture| texture.id) else {
+            return;
+        };
+        unsafe {
+            gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+            gl::BindImageTexture(0, texture_id, 0, gl::FALSE, 0, gl::READ_WRITE, gl::R32UI);
+        }
+        self.use_material(pass.count_material);
+        if let Some(program) = self.programs.get_mut(count_program) {
+            program.bind();
+            for (mesh, indirect, transforms) in &draws {
+                for (location, values) in transforms {
+                    program.set_uniform_f32(*location, values);
+                }
+                if let Some(vertex_array) = self.layouts.get_mut(mesh.vertex_layout) {
+                    vertex_array.bind();
+                    match indirect {
+                        Some(buffer) => {
+                            super::draw_indirect(mesh, *buffer, vertex_array.has_indices)
+                        }
+                        None => super::draw_mesh(mesh, vertex_array.has_indices),
+                    }
+                }
+            }
+        }
+
+        // the heatmap reads the counts as texture
+        unsafe {
+            gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+            gl::MemoryBarrier(gl::TEXTURE_FETCH_BARRIER_BIT | gl::TEXTURE_UPDATE_BARRIER_BIT);
+        }
+        if let Some(program) = self.programs.get_mut(pass.heatmap_program) {
+            program.bind();
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0);
+                gl::BindTexture(gl::TEXTURE_2D, texture_id);
+                gl::BindSampler(0, 0);
+                gl::BindVertexArray(pass.vao);
+                gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            }
+        }
+
+        let mut counts = vec![0_u32; width as usize * height as usize];
+        unsafe {
+            gl::GetTexImage(
+                gl::TEXTURE_2D,
+                0,
+                gl::RED_INTEGER,
+                gl::UNSIGNED_INT,
+                counts.as_mut_ptr() as *mut std::ffi::c_void,
+            );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        let [x, y, ..] = viewport.map(|value| value.max(0) as usize);
+        let rows = counts.chunks_exact(width as usize).skip(y);
+        self.stats.overdraw = Some(OverdrawStats::from_counts(
+            rows.flat_map(|row| row[x..].iter().copied()),
+        ));
+    }
+}
@@ -0,0 +1,374 @@
+use gl::types::GLsync;
+
+use crate::{
+    math::vec2,
+    renderer::{Backend, ComputeProgram, MemoryBarriers, Texture},
+    Buffer, BufferUsage, Handle, MaterialProperty, Renderer, RendererError,
+};
+
+use super::OpenGLContext;
+
+/// Most bins of a [LuminanceHistogram][ReductionOp::LuminanceHistogram], one per invocation of
+/// a work group.
+pub const MAX_HISTOGRAM_BINS: u32 = 256;
+/// Reductions in flight before [reduce][GpuReduction::reduce] skips textures.
+const SLOTS: usize = 3;
+const GROUP_SIZE: u32 = 16;
+
+/// Smallest and largest value, as bits of non-negative floats, which order like the floats.
+const MIN_MAX_CS_SOURCE: &str = r##"
+    #version 430 core
+    layout(local_size_x = 16, local_size_y = 16) in;
+    uniform sampler2D source;
+    layout(std430, binding = 0) buffer Result { uint values[]; };
+    shared uint group_min;
+    shared uint group_max;
+    void main() {
+        if (gl_LocalInvocationIndex == 0) {
+            group_min = 0xffffffffu;
+            group_max = 0u;
+        }
+        barrier();
+        ivec2 texel = ivec2(gl_GlobalInvocationID.xy);
+        if (all(lessThan(texel, textureSize(source, 0)))) {
+            uint value = floatBitsToUint(max(texelFetch(source, texel, 0).r, 0.0));
+            atomicMin(group_min, value);
+            atomicMax(group_max, value);
+        }
+        barrier();
+        if (gl_LocalInvocationIndex == 0) {
+            atomicMin(values[0], group_min);
+            atomicMax(values[1], group_max);
+        }
+    }"##;
+
+fn histogram_source(bins: u32) -> String {
+    format!(
+        r##"
+    #version 430 core
+    layout(local_size_x = 16, local_size_y = 16) in;
+    uniform sampler2D source;
+    uniform vec2 log_range;
+    layout(std430, binding = 0) buffer Result {{ uint values[]; }};
+    shared uint bins[{bins}];
+    void main() {{
+        uint index = gl_LocalInvocationIndex;
+        if (index < {bins}u) {{
+            bins[index] = 0u;
+        }}
+        barrier();
+        ivec2 texel = ivec2(gl_GlobalInvocationID.xy);
+        if (all(lessThan(texel, textureSize(source, 0)))) {{
+            vec3 color = texelFetch(source, texel, 0).rgb;
+            float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+            float t = (log2(max(luminance, 1e-6)) - log_range.x) / (log_range.y - log_range.x);
+            atomicAdd(bins[min(uint(clamp(t, 0.0, 1.0) * {bins}.0), {bins}u - 1u)], 1u);
+        }}
+        barrier();
+        if (index < {bins}u) {{
+            atomicAdd(values[index], bins[index]);
+        }}
+    }}"##
+    )
+}
+
+/// What a [GpuReduction] computes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ReductionOp {
+    /// Smallest and largest value of the red channel, e.g. of a depth texture to fit shadow
+    /// cascades. Negative values count as 0.
+    MinMax,
+    /// Histogram of the luminance of the colors over log2 luminance, e.g. for auto-exposure.
+    /// Luminance outside of the range counts to the first or last of the bins.
+    LuminanceHistogram {
+        bins: u32,
+        min_log2: f32,
+        max_log2: f32,
+    },
+}
+
+/// The result of a [GpuReduction].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReductionResult {
+    MinMax { min: f32, max: f32 },
+    LuminanceHistogram(LuminanceHistogram),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LuminanceHistogram {
+    /// Texels per bin, the bins split the log2 range evenly.
+    pub bins: Vec<u32>,
+    pub min_log2: f32,
+    pub max_log2: f32,
+}
+
+impl LuminanceHistogram {
+    /// Geometric mean of the luminance, from the centers of the bins, or 0 for empty
+    /// histograms. Auto-exposure usually scales the frame by a key value divided by it.
+    pub fn average_luminance(&self) -> f32 {
+        let count: u64 = self.bins.iter().map(|count| *count as u64).sum();
+        if count == 0 {
+            return 0.0;
+        }
+        let width = (self.max_log2 - self.min_log2) / self.bins.len() as f32;
+        let sum: f64 = self
+            .bins
+            .iter()
+            .enumerate()
+            .map(|(bin, count)| {
+                let center = self.min_log2 + (bin as f32 + 0.5) * width;
+                center as f64 * *count as f64
+            })
+            .sum();
+        (sum / count as f64).exp2() as f32
+    }
+}
+
+/// Readback buffer of a reduction and the fence of the dispatch that fills it.
+#[derive(Debug)]
+struct Slot {
+    buffer: Handle<Buffer>,
+    fence: Option<GLsync>,
+}
+
+/// Reduces textures to a few values with a compute program, like the depth range or a
+/// luminance histogram, and reads them back without stalling.
+///
+/// Each [reduce][Self::reduce] writes into one of a few small readback buffers and fences it.
+/// [poll][Self::poll] checks the fences and returns the newest result whose dispatch is done,
+/// usually from a frame or two before, so a result isn't available right after the first
+/// reduction:
+///
+/// ```no_run
+/// # use cac_renderer::{GpuReduction, OpenGLContext, ReductionOp, ReductionResult, Renderer};
+/// # fn frame(renderer: &mut Renderer<OpenGLContext>, reduction: &mut GpuReduction, frame: cac_renderer::Handle<cac_renderer::Texture>) {
+/// reduction.reduce(renderer, frame).unwrap();
+/// if let Some(ReductionResult::LuminanceHistogram(histogram)) = reduction.poll(renderer).unwrap() {
+///     let exposure = 0.18 / histogram.average_luminance().max(1e-4);
+/// }
+/// # }
+/// ```
+///
+/// Requires compute programs (OpenGL 4.3). Multisampled textures and texture arrays aren't
+/// supported.
+#[derive(Debug)]
+pub struct GpuReduction {
+    op: ReductionOp,
+    program: Handle<ComputeProgram>,
+    slots: Vec<Slot>,
+    /// The slot of the next reduction.
+    next: usize,
+}
+
+impl GpuReduction {
+    pub fn new(
+        renderer: &mut Renderer<OpenGLContext>,
+        op: ReductionOp,
+    ) -> Result<Self, RendererError> {
+        let (source, values) = match op {
+            ReductionOp::MinMax => (MIN_MAX_CS_SOURCE.to_string(), 2),
+            ReductionOp::LuminanceHistogram { bins, .. } => {
+                if bins == 0 || bins > MAX_HISTOGRAM_BINS {
+                    return Err(RendererError::ConversionError {
+                        error: format!(
+                            "Histograms need 1 to {MAX_HISTOGRAM_BINS} bins, not {bins}"
+                        ),
+                    });
+                }
+                (histogram_source(bins), bins as usize)
+            }
+        };
+        let program = ComputeProgram::from_source(renderer, &source)?;
+
+        let mut slots = Vec::with_capacity(SLOTS);
+        for _ in 0..SLOTS {
+            match Buffer::with_vertex(renderer, &vec![0_u32; values], BufferUsage::DynamicRead) {
+                Ok(buffer) => slots.push(Slot {
+                    buffer,
+                    fence: None,
+                }),
+                Err(error) => {
+                    slots
+                        .iter()
+                        .for_each(|slot| renderer.buffers.remove(slot.buffer));
+                    renderer.compute_programs.remove(program);
+                    return Err(error);
+                }
+            }
+        }
+
+        Ok(Self {
+            op,
+            program,
+            slots,
+            next: 0,
+        })
+    }
+
+    pub fn op(&self) -> ReductionOp {
+        self.op
+    }
+
+    /// Dispatches the reduction of the first mip level of the 2D texture. Returns false without
+    /// dispatching if all readback buffers are still in flight, e.g. when the results aren't
+    /// [polled][Self::poll].
+    pub fn reduce(
+        &mut self,
+        renderer: &mut Renderer<OpenGLContext>,
+        texture: Handle<Texture>,
+    ) -> Result<bool, RendererError> {
+        let slot = &mut self.slots[self.next];
+        if slot.fence.is_some() {
+            return Ok(false);
+        }
+
+        let (width, height, texture_id, target) = renderer
+            .textures
+            .get(texture)
+            .map(|texture| (texture.width, texture.height, texture.id, texture.target))
+            .ok_or(RendererError::ResourceNotFound {
+                resource: format!("Texture: {texture:?}"),
+            })?;
+        let initial = match self.op {
+            ReductionOp::MinMax => vec![u32::MAX, 0],
+            ReductionOp::LuminanceHistogram { bins, .. } => vec![0; bins as usize],
+        };
+        renderer.update_buffer(slot.buffer, 0, &initial)?;
+        let buffer_id = renderer
+            .buffers
+            .get(slot.buffer)
+            .map(|buffer| buffer.id())
+            .ok_or(RendererError::ResourceNotFound {
+                resource: format!("Buffer: {:?}", slot.buffer),
+            })?;
+
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(target, texture_id);
+            gl::BindSampler(0, 0);
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, 0, buffer_id);
+        }
+        let log_range = match self.op {
+            ReductionOp::LuminanceHistogram {
+                min_log2, max_log2, ..
+            } => Some(vec2(min_log2, max_log2)),
+            ReductionOp::MinMax => None,
+        };
+        let properties: Vec<_> = log_range
+            .iter()
+            .map(|log_range| MaterialProperty::new("log_range", log_range))
+            .collect();
+        renderer.dispatch(
+            self.program,
+            width.div_ceil(GROUP_SIZE),
+            height.div_ceil(GROUP_SIZE),
+            1,
+            &properties,
+        )?;
+        renderer.memory_barrier(MemoryBarriers::BUFFER_UPDATE);
+
+        slot.fence = Some(unsafe { gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) });
+        self.next = (self.next + 1) % SLOTS;
+        Ok(true)
+    }
+
+    /// The newest result whose reduction is done, without waiting for the ones in flight. None
+    /// if none finished since the last poll.
+    pub fn poll(
+        &mut self,
+        renderer: &mut Renderer<OpenGLContext>,
+    ) -> Result<Option<ReductionResult>, RendererError> {
+        let mut latest = None;
+        // oldest first, from the slot after the last reduction
+        for offset in 0..SLOTS {
+            let slot = &mut self.slots[(self.next + offset) % SLOTS];
+            let Some(fence) = slot.fence else {
+                continue;
+            };
+            let status = unsafe { gl::ClientWaitSync(fence, 0, 0) };
+            if status != gl::ALREADY_SIGNALED && status != gl::CONDITION_SATISFIED {
+                break;
+            }
+            unsafe { gl::DeleteSync(fence) };
+            slot.fence = None;
+
+            let bytes = renderer.read_buffer(slot.buffer, 0..self.values() * 4)?;
+            latest = Some(decode_result(self.op, &bytes));
+        }
+        Ok(latest)
+    }
+
+    /// Removes the program and buffers of the reduction.
+    pub fn remove(self, renderer: &mut Renderer<OpenGLContext>) {
+        for slot in self.slots {
+            if let Some(fence) = slot.fence {
+                unsafe { gl::DeleteSync(fence) };
+            }
+            renderer.buffers.remove(slot.buffer);
+        }
+        renderer.compute_programs.remove(self.program);
+    }
+
+    fn values(&self) -> usize {
+        match self.op {
+            ReductionOp::MinMax => 2,
+            ReductionOp::LuminanceHistogram { bins, .. } => bins as usize,
+        }
+    }
+}
+
+fn decode_result(op: ReductionOp, bytes: &[u8]) -> ReductionResult {
+    let values: Vec<u32> = bytes
+        .chunks_exact(4)
+        .map(|value| u32::from_ne_bytes(value.try_into().unwrap()))
+        .collect();
+    match op {
+        ReductionOp::MinMax => ReductionResult::MinMax {
+            min: f32::from_bits(values[0]),
+            max: f32::from_bits(values[1]),
+        },
+        ReductionOp::LuminanceHistogram {
+            min_log2, max_log2, ..
+        } => ReductionResult::LuminanceHistogram(LuminanceHistogram {
+            bins: values,
+            min_log2,
+            max_log2,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_results() {
+        let bytes: Vec<u8> = [0.25_f32.to_bits(), 0.75_f32.to_bits()]
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+        assert_eq!(
+            decode_result(ReductionOp::MinMax, &bytes),
+            ReductionResult::MinMax {
+                min: 0.25,
+                max: 0.75
+            }
+        );
+
+        // half of the texels at 2^-3, half at 2^3
+        let histogram = LuminanceHistogram {
+            bins: vec![4, 0, 0, 4],
+            min_log2: -4.0,
+            max_log2: 4.0,
+        };
+        assert_eq!(histogram.average_luminance(), 1.0);
+        assert_eq!(
+            LuminanceHistogram {
+                bins: vec![0; 4],
+                ..histogram
+            }
+            .average_luminance(),
+            0.0
+        );
+    }
+}
@@ -1,16 +1,33 @@
-use gl::types::GLbitfield;
+use gl::types::{GLbitfield, GLenum, GLuint};
 
-use crate::ClearFlags;
+use crate::{ClearFlags, Color32, RendererError, TextureFormat, TextureRegion};
+
+use super::{texture::pixel_layout, GLTexture};
 
 #[derive(Debug, Copy, Clone)]
 pub struct ScreenTarget {
     clear_flags: GLbitfield,
+    srgb_encode: bool,
+    /// Copy of the last frame while the screen readback is on, see
+    /// [set_screen_readback][crate::Renderer::set_screen_readback].
+    readback: Option<FrameCopy>,
+}
+
+/// Framebuffer with an RGBA8 renderbuffer the back buffer is copied into before the swap.
+#[derive(Debug, Copy, Clone)]
+struct FrameCopy {
+    framebuffer: GLuint,
+    renderbuffer: GLuint,
+    width: i32,
+    height: i32,
 }
 
 impl Default for ScreenTarget {
     fn default() -> Self {
         Self {
             clear_flags: gl::COLOR_BUFFER_BIT,
+            srgb_encode: false,
+            readback: None,
         }
     }
 }
@@ -18,13 +35,13 @@ impl Default for ScreenTarget {
 impl From<ClearFlags> for GLbitfield {
     fn from(flag: ClearFlags) -> Self {
         let mut clear_flags = 0;
-        if flag == ClearFlags::COLOR {
+        if flag & ClearFlags::COLOR {
             clear_flags |= gl::COLOR_BUFFER_BIT;
         }
-        if flag == ClearFlags::DEPTH {
+        if flag & ClearFlags::DEPTH {
             clear_flags |= gl::DEPTH_BUFFER_BIT;
         }
-        if flag == ClearFlags::STENCIL {
+        if flag & ClearFlags::STENCIL {
             clear_flags |= gl::STENCIL_BUFFER_BIT;
         }
 
@@ -49,5 +66,389 @@ impl crate::RenderTarget for ScreenTarget {
     fn set_clear_flags(&mut self, flags: ClearFlags) {
         self.clear_flags = flags.into();
     }
+
+    fn set_srgb_encode(&mut self, encode: bool) {
+        self.srgb_encode = encode;
+    }
+
+    fn srgb_encode(&self) -> bool {
+        self.srgb_encode
+    }
+
+    fn read_pixels(&mut self, rect: TextureRegion) -> Vec<u8> {
+        // the back buffer is undefined after the swap, and the front buffer of obscured or
+        // composited windows too, so only the copy of the frame is defined
+        match self.readback {
+            Some(copy) => unsafe {
+                read_pixels(
+                    copy.framebuffer,
+                    gl::COLOR_ATTACHMENT0,
+                    TextureFormat::Rgba8,
+                    rect,
+                )
+            },
+            None => {
+                log::warn!("The screen can only be read with the screen readback on");
+                vec![0; rect.width as usize * rect.height as usize * 4]
+            }
+        }
+    }
+}
+impl ScreenTarget {
+    /// Binds the default framebuffer for the following draws.
+    pub(super) fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            set_framebuffer_srgb(self.srgb_encode);
+        }
+    }
+
+    /// Creates the copy of the frames of the size of the viewport, or deletes it.
+    pub(super) fn set_readback(&mut self, viewport: [i32; 4], enabled: bool) {
+        if let Some(copy) = self.readback.take() {
+            unsafe {
+                gl::DeleteFramebuffers(1, &copy.framebuffer);
+                gl::DeleteRenderbuffers(1, &copy.renderbuffer);
+            }
+        }
+        if !enabled {
+            return;
+        }
+
+        let [_, _, width, height] = viewport;
+        let (mut framebuffer, mut renderbuffer) = (0, 0);
+        unsafe {
+            let mut previous = 0;
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut previous);
+            gl::GenRenderbuffers(1, &mut renderbuffer);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, renderbuffer);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::RGBA8, width, height);
+            gl::GenFramebuffers(1, &mut framebuffer);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, framebuffer);
+            gl::FramebufferRenderbuffer(
+                gl::DRAW_FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::RENDERBUFFER,
+                renderbuffer,
+            );
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, previous as GLuint);
+        }
+
+        self.readback = Some(FrameCopy {
+            framebuffer,
+            renderbuffer,
+            width,
+            height,
+        });
+    }
+
+    /// Copies the back buffer into the copy of the frame, if the readback is on. Has to be
+    /// called before the swap, which leaves the back buffer undefined.
+    pub(super) fn copy_back_buffer(&self) {
+        let Some(copy) = self.readback else {
+            return;
+        };
+
+        unsafe {
+            let mut previous = [0; 2];
+            gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut previous[0]);
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut previous[1]);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+            gl::ReadBuffer(gl::BACK);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, copy.framebuffer);
+            // copies the stored bytes, without decoding sRGB
+            set_framebuffer_srgb(false);
+            let (width, height) = (copy.width, copy.height);
+            gl::BlitFramebuffer(
+                0,
+                0,
+                width,
+                height,
+                0,
+                0,
+                width,
+                height,
+                gl::COLOR_BUFFER_BIT,
+                gl::NEAREST,
+            );
+            set_framebuffer_srgb(self.srgb_encode);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, previous[0] as GLuint);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, previous[1] as GLuint);
+        }
+    }
+}
+
+unsafe fn set_framebuffer_srgb(encode: bool) {
+    if encode {
+        gl::Enable(gl::FRAMEBUFFER_SRGB);
+    } else {
+        gl::Disable(gl::FRAMEBUFFER_SRGB);
+    }
+}
+
+/// Reads RGBA8 pixels from the color buffer of the framebuffer, or the 32 bit texels of
+/// integer formats.
+unsafe fn read_pixels(
+    framebuffer: GLuint,
+    buffer: GLenum,
+    format: TextureFormat,
+    rect: TextureRegion,
+) -> Vec<u8> {
+    let (pixel_format, kind) = match format {
+        TextureFormat::R32UI | TextureFormat::R32I => {
+            let (_, pixel_format, kind) = pixel_layout(format);
+            (pixel_format, kind)
+        }
+        _ => (gl::RGBA, gl::UNSIGNED_BYTE),
+    };
+    let mut pixels = vec![0; rect.width as usize * rect.height as usize * 4];
+    let mut previous = 0;
+    gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut previous);
+
+    gl::BindFramebuffer(gl::READ_FRAMEBUFFER, framebuffer);
+    gl::ReadBuffer(buffer);
+    // rows of 4 byte pixels are always aligned to the default pack alignment of 4
+    gl::ReadPixels(
+        rect.x as i32,
+        rect.y as i32,
+        rect.width as i32,
+        rect.height as i32,
+        pixel_format,
+        kind,
+        pixels.as_mut_ptr() as *mut std::ffi::c_void,
+    );
+    gl::BindFramebuffer(gl::READ_FRAMEBUFFER, previous as GLuint);
+
+    pixels
+}
+
+/// Framebuffer with an external texture as its color attachment.
+/// The texture is owned by the caller and not deleted with the target.
+#[derive(Debug)]
+pub struct GLExternalTarget {
+    framebuffer: GLuint,
+    width: i32,
+    height: i32,
+    format: TextureFormat,
+    /// Multisampled framebuffers are resolved before reading them.
+    samples: u32,
+    srgb_encode: bool,
+    clear_flags: GLbitfield,
+    clear_color: Color32,
+}
+
+impl GLExternalTarget {
+    /// The texture is bound to texture_target, either TEXTURE_2D or TEXTURE_2D_MULTISAMPLE.
+    pub(super) fn new(
+        texture: GLuint,
+        texture_target: GLenum,
+        samples: u32,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Result<Self, RendererError> {
+        let (width, height) = match (i32::try_from(width), i32::try_from(height)) {
+            (Ok(width), Ok(height)) if width > 0 && height > 0 => (width, height),
+            _ => {
+                return Err(RendererError::FailedToCreateRenderTarget {
+                    error: format!("Invalid size {width}x{height}"),
+                })
+            }
+        };
+
+        let mut framebuffer = 0;
+        let status = unsafe {
+            gl::GenFramebuffers(1, &mut framebuffer);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                texture_target,
+                texture,
+                0,
+            );
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            status
+        };
+
+        // created first, so the framebuffer is deleted on errors
+        let target = Self {
+            framebuffer,
+            width,
+            height,
+            format,
+            samples,
+            srgb_encode: format == TextureFormat::Srgb8Alpha8,
+            clear_flags: gl::COLOR_BUFFER_BIT,
+            clear_color: Color32::default(),
+        };
+
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(RendererError::FailedToCreateRenderTarget {
+                error: format!("Framebuffer of texture {texture} is incomplete: {status:#x}"),
+            });
+        }
+
+        Ok(target)
+    }
+
+    /// Attaches the depth-stencil texture, or detaches the current one with None.
+    pub(super) fn attach_depth_stencil(
+        &mut self,
+        texture: Option<&GLTexture>,
+    ) -> Result<(), RendererError> {
+        if let Some(texture) = texture {
+            let size = (texture.width as i32, texture.height as i32);
+            if size != (self.width, self.height) || texture.samples != self.samples {
+                return Err(RendererError::FailedToCreateRenderTarget {
+                    error: format!(
+                        "Depth-stencil texture is {}x{} with {} samples, the target {}x{} with {}",
+                        texture.width,
+                        texture.height,
+                        texture.samples,
+                        self.width,
+                        self.height,
+                        self.samples
+                    ),
+                });
+            }
+        }
+
+        let (target, id) =
+            texture.map_or((gl::TEXTURE_2D, 0), |texture| (texture.target, texture.id));
+        let status = unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::DEPTH_STENCIL_ATTACHMENT, target, id, 0);
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            status
+        };
+
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(RendererError::FailedToCreateRenderTarget {
+                error: format!(
+                    "Framebuffer with depth-stencil texture {id} is incomplete: {status:#x}"
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// Binds the framebuffer and its viewport for the following draws.
+    pub(super) fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            gl::Viewport(0, 0, self.width, self.height);
+            set_framebuffer_srgb(self.srgb_encode);
+        }
+    }
+}
+
+impl crate::RenderTarget for GLExternalTarget {
+    fn set_clear_color(&mut self, color: Color32) {
+        self.clear_color = color;
+    }
+
+    fn clear(&mut self) {
+        // the clear color is converted to floats, integer targets are cleared to 0 instead
+        if self.format.is_integer() && self.clear_flags & gl::COLOR_BUFFER_BIT != 0 {
+            unsafe {
+                gl::ClearBufferuiv(gl::COLOR, 0, [0; 4].as_ptr());
+                let rest = self.clear_flags & !gl::COLOR_BUFFER_BIT;
+                if rest != 0 {
+                    gl::Clear(rest);
+                }
+            }
+            return;
+        }
+
+        // the clear color is global state, shared with the screen target
+        let mut previous = [0.0; 4];
+        let (r, g, b, a) = self.clear_color.as_rgba();
+        unsafe {
+            gl::GetFloatv(gl::COLOR_CLEAR_VALUE, previous.as_mut_ptr());
+            gl::ClearColor(r, g, b, a);
+            gl::Clear(self.clear_flags);
+            gl::ClearColor(previous[0], previous[1], previous[2], previous[3]);
+        }
+    }
+
+    fn set_clear_flags(&mut self, flags: ClearFlags) {
+        self.clear_flags = flags.into();
+    }
+
+    fn set_srgb_encode(&mut self, encode: bool) {
+        self.srgb_encode = encode;
+    }
+
+    fn srgb_encode(&self) -> bool {
+        self.srgb_encode
+    }
+
+    fn read_pixels(&mut self, rect: TextureRegion) -> Vec<u8> {
+        if self.samples <= 1 {
+            return unsafe {
+                read_pixels(self.framebuffer, gl::COLOR_ATTACHMENT0, self.format, rect)
+            };
+        }
+
+        // multisampled framebuffers can't be read, they are resolved into a temporary one first
+        let (width, height) = (rect.width as i32, rect.height as i32);
+        let (x, y) = (rect.x as i32, rect.y as i32);
+        let (mut framebuffer, mut renderbuffer) = (0, 0);
+        unsafe {
+            let mut previous = [0; 2];
+            gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut previous[0]);
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut previous[1]);
+
+            gl::GenRenderbuffers(1, &mut renderbuffer);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, renderbuffer);
+            let internal_format = if self.format.is_integer() {
+                pixel_layout(self.format).0
+            } else {
+                gl::RGBA8
+            };
+            gl::RenderbufferStorage(gl::RENDERBUFFER, internal_format, width, height);
+            gl::GenFramebuffers(1, &mut framebuffer);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, framebuffer);
+            gl::FramebufferRenderbuffer(
+                gl::DRAW_FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::RENDERBUFFER,
+                renderbuffer,
+            );
+
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.framebuffer);
+            gl::BlitFramebuffer(
+                x,
+                y,
+                x + width,
+                y + height,
+                0,
+                0,
+                width,
+                height,
+                gl::COLOR_BUFFER_BIT,
+                gl::NEAREST,
+            );
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, previous[0] as GLuint);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, previous[1] as GLuint);
+
+            let resolved = TextureRegion { x: 0, y: 0, ..rect };
+            let pixels = read_pixels(framebuffer, gl::COLOR_ATTACHMENT0, self.format, resolved);
+            gl::DeleteFramebuffers(1, &framebuffer);
+            gl::DeleteRenderbuffers(1, &renderbuffer);
+            pixels
+        }
+    }
+}
+
+impl Drop for GLExternalTarget {
+    fn drop(&mut self) {
+        if self.framebuffer > 0 {
+            unsafe { gl::DeleteFramebuffers(1, &self.framebuffer) }
+        }
+    }
 }
-impl ScreenTarget {}
@@ -0,0 +1,92 @@
+use gl::types::{GLenum, GLuint};
+
+use crate::{
+    renderer::sampler::{CompareFunction, CreateSampler, Filter, SamplerDescription, WrapMode},
+    RendererError,
+};
+
+use super::DebugLabel;
+
+/// Sampler object, core since OpenGL 3.3.
+#[derive(Debug)]
+pub struct GLSampler {
+    pub(super) id: GLuint,
+}
+
+impl CreateSampler for GLSampler {
+    fn new(description: &SamplerDescription) -> Result<Self, RendererError> {
+        let mut id = 0;
+        unsafe { gl::GenSamplers(1, &mut id) };
+
+        let min_filter = match (description.min_filter, description.mipmap_filter) {
+            (Filter::Nearest, None) => gl::NEAREST,
+            (Filter::Linear, None) => gl::LINEAR,
+            (Filter::Nearest, Some(Filter::Nearest)) => gl::NEAREST_MIPMAP_NEAREST,
+            (Filter::Linear, Some(Filter::Nearest)) => gl::LINEAR_MIPMAP_NEAREST,
+            (Filter::Nearest, Some(Filter::Linear)) => gl::NEAREST_MIPMAP_LINEAR,
+            (Filter::Linear, Some(Filter::Linear)) => gl::LINEAR_MIPMAP_LINEAR,
+        };
+        let mag_filter = match description.mag_filter {
+            Filter::Nearest => gl::NEAREST,
+            Filter::Linear => gl::LINEAR,
+        };
+
+        let parameters = [
+            (gl::TEXTURE_MIN_FILTER, min_filter),
+            (gl::TEXTURE_MAG_FILTER, mag_filter),
+            (gl::TEXTURE_WRAP_S, wrap_mode(description.wrap_u)),
+            (gl::TEXTURE_WRAP_T, wrap_mode(description.wrap_v)),
+            (gl::TEXTURE_WRAP_R, wrap_mode(description.wrap_w)),
+        ];
+        for (name, value) in parameters {
+            unsafe { gl::SamplerParameteri(id, name, value as i32) };
+        }
+
+        if let Some(function) = description.compare {
+            let function = match function {
+                CompareFunction::Never => gl::NEVER,
+                CompareFunction::Less => gl::LESS,
+                CompareFunction::LessOrEqual => gl::LEQUAL,
+                CompareFunction::Equal => gl::EQUAL,
+                CompareFunction::NotEqual => gl::NOTEQUAL,
+                CompareFunction::GreaterOrEqual => gl::GEQUAL,
+                CompareFunction::Greater => gl::GREATER,
+                CompareFunction::Always => gl::ALWAYS,
+            };
+            unsafe {
+                let mode = gl::COMPARE_REF_TO_TEXTURE as i32;
+                gl::SamplerParameteri(id, gl::TEXTURE_COMPARE_MODE, mode);
+                gl::SamplerParameteri(id, gl::TEXTURE_COMPARE_FUNC, function as i32);
+            }
+        }
+
+        let (r, g, b, a) = description.border_color.as_f32();
+        let border_color = [r, g, b, a];
+        unsafe { gl::SamplerParameterfv(id, gl::TEXTURE_BORDER_COLOR, border_color.as_ptr()) };
+
+        Ok(Self { id })
+    }
+}
+
+impl DebugLabel for GLSampler {
+    fn set_label(&mut self, label: &str) {
+        super::object_label(gl::SAMPLER, self.id, label);
+    }
+}
+
+impl Drop for GLSampler {
+    fn drop(&mut self) {
+        if self.id > 0 {
+            unsafe { gl::DeleteSamplers(1, &self.id) };
+        }
+    }
+}
+
+fn wrap_mode(wrap: WrapMode) -> GLenum {
+    match wrap {
+        WrapMode::Repeat => gl::REPEAT,
+        WrapMode::MirroredRepeat => gl::MIRRORED_REPEAT,
+        WrapMode::ClampToEdge => gl::CLAMP_TO_EDGE,
+        WrapMode::ClampToBorder => gl::CLAMP_TO_BORDER,
+    }
+}
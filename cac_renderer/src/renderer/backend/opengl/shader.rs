@@ -17,6 +17,27 @@ impl CreateShader for GLShader {
     fn with_fragment(source: &str) -> Result<Self, RendererError> {
         Self::with_kind(gl::FRAGMENT_SHADER, source)
     }
+
+    fn with_compute(source: &str) -> Result<Self, RendererError> {
+        // compute shaders are core since 4.3
+        if !gl::DispatchCompute::is_loaded() {
+            return Err(RendererError::UnsupportedFeature {
+                feature: "Compute shaders (OpenGL 4.3)".to_string(),
+            });
+        }
+
+        Self::with_kind(gl::COMPUTE_SHADER, source)
+    }
+
+    fn with_tess_control(source: &str) -> Result<Self, RendererError> {
+        Self::check_tessellation()?;
+        Self::with_kind(gl::TESS_CONTROL_SHADER, source)
+    }
+
+    fn with_tess_evaluation(source: &str) -> Result<Self, RendererError> {
+        Self::check_tessellation()?;
+        Self::with_kind(gl::TESS_EVALUATION_SHADER, source)
+    }
 }
 
 impl Drop for GLShader {
@@ -28,6 +49,17 @@ impl Drop for GLShader {
 }
 
 impl GLShader {
+    // tessellation shaders are core since 4.0
+    fn check_tessellation() -> Result<(), RendererError> {
+        if gl::PatchParameteri::is_loaded() {
+            Ok(())
+        } else {
+            Err(RendererError::UnsupportedFeature {
+                feature: "Tessellation shaders (OpenGL 4.0)".to_string(),
+            })
+        }
+    }
+
     fn with_kind(kind: GLenum, source: &str) -> Result<Self, RendererError> {
         let gl_shader = Self {
             id: unsafe { gl::CreateShader(kind) },
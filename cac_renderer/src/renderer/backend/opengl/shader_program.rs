@@ -1,28 +1,121 @@
-use std::mem::size_of;
+use std::{collections::HashMap, mem::size_of};
 
-use gl::types::{GLchar, GLuint};
+use gl::types::GLuint;
 
 use crate::{
     generation_vec::GenerationVec,
     renderer::{
-        shader::{CreateShaderProgram, ProgramStorage, Uniform},
-        ShaderProgram, UniformDescription, UniformKind,
+        compute::CreateComputeProgram,
+        shader::{reflected_uniform_name, CreateShaderProgram, ProgramStorage, Uniform},
+        AttributeDescription, ProgramBinary, ShaderProgram, UniformDescription, UniformKind,
     },
     Handle, RendererError,
 };
 
-use super::GLShader;
+use super::{DebugLabel, GLShader};
 
 impl CreateShaderProgram for GLShaderProgram {
     type VertexShader = GLShader;
 
     type FragmentShader = GLShader;
 
+    type TessellationShader = GLShader;
+
     fn new(
         vertex_shader: &Self::VertexShader,
         fragment_shader: &Self::FragmentShader,
+        attribute_locations: &[(String, u8)],
+    ) -> Result<Self, RendererError> {
+        Self::check_kinds(vertex_shader, fragment_shader)?;
+        Self::link(&[vertex_shader, fragment_shader], attribute_locations)
+    }
+
+    fn with_tessellation(
+        vertex_shader: &Self::VertexShader,
+        tess_control_shader: &Self::TessellationShader,
+        tess_evaluation_shader: &Self::TessellationShader,
+        fragment_shader: &Self::FragmentShader,
+        attribute_locations: &[(String, u8)],
     ) -> Result<Self, RendererError> {
-        Self::new(vertex_shader, fragment_shader)
+        Self::check_kinds(vertex_shader, fragment_shader)?;
+        if tess_control_shader.kind != gl::TESS_CONTROL_SHADER {
+            return Err(RendererError::FailedToLinkProgram {
+                error: "Argument tess_control_shader is not a TessControlShader".to_string(),
+            });
+        }
+        if tess_evaluation_shader.kind != gl::TESS_EVALUATION_SHADER {
+            return Err(RendererError::FailedToLinkProgram {
+                error: "Argument tess_evaluation_shader is not a TessEvaluationShader".to_string(),
+            });
+        }
+
+        Self::link(
+            &[
+                vertex_shader,
+                tess_control_shader,
+                tess_evaluation_shader,
+                fragment_shader,
+            ],
+            attribute_locations,
+        )
+    }
+
+    fn get_binary(&self) -> Result<ProgramBinary, RendererError> {
+        Self::check_binary_support()?;
+
+        let mut length = 0;
+        unsafe { gl::GetProgramiv(self.id, gl::PROGRAM_BINARY_LENGTH, &mut length) };
+        // drivers without any binary formats report a length of 0
+        if length <= 0 {
+            return Err(RendererError::UnsupportedFeature {
+                feature: "Program binaries (no binary formats)".to_string(),
+            });
+        }
+
+        let mut format = 0;
+        let mut data = vec![0_u8; length as usize];
+        unsafe {
+            gl::GetProgramBinary(
+                self.id,
+                length,
+                &mut length,
+                &mut format,
+                data.as_mut_ptr() as *mut std::ffi::c_void,
+            );
+        }
+        data.truncate(length as usize);
+
+        Ok(ProgramBinary { format, data })
+    }
+
+    fn from_binary(binary: &ProgramBinary) -> Result<Self, RendererError> {
+        Self::check_binary_support()?;
+
+        let id = unsafe { gl::CreateProgram() };
+        unsafe {
+            gl::ProgramBinary(
+                id,
+                binary.format,
+                binary.data.as_ptr() as *const std::ffi::c_void,
+                binary.data.len() as i32,
+            );
+        }
+
+        Self::from_linked(id)
+    }
+}
+
+impl CreateComputeProgram for GLShaderProgram {
+    type ComputeShader = GLShader;
+
+    fn new(compute_shader: &Self::ComputeShader) -> Result<Self, RendererError> {
+        if compute_shader.kind != gl::COMPUTE_SHADER {
+            return Err(RendererError::FailedToLinkProgram {
+                error: "Argument compute_shader is not a ComputeShader".to_string(),
+            });
+        }
+
+        Self::link(&[compute_shader], &[])
     }
 }
 
@@ -37,7 +130,7 @@ impl ProgramStorage for GenerationVec<ShaderProgram, GLShaderProgram> {
         vertex_shader: &Self::VertexShader,
         fragment_shader: &Self::FragmentShader,
     ) -> Result<crate::Handle<ShaderProgram>, RendererError> {
-        let program = Self::ShaderProgram::new(vertex_shader, fragment_shader)?;
+        let program = GLShaderProgram::new(vertex_shader, fragment_shader)?;
         Ok(self.push(program))
     }
 
@@ -54,11 +147,55 @@ pub struct GLShaderProgram {
     id: GLuint,
     data_size: usize,
     uniforms: Vec<UniformDescription>,
+    locations: HashMap<String, u32>,
+    attributes: Vec<AttributeDescription>,
+    uniform_blocks: Vec<(String, u32)>,
+    shadow: UniformShadow,
+}
+
+/// Copy of the last uploaded uniform values of a program, so materials that share a program
+/// only upload the uniforms whose values differ. Uniforms keep their values while the program
+/// isn't used, so the copy stays valid across frames.
+#[derive(Debug, Default)]
+struct UniformShadow {
+    data: Vec<u8>,
+    /// Whether the uniform at the index was uploaded with the bytes in data. Uniforms start
+    /// unknown, since GLSL initializers give them values the copy doesn't know about.
+    known: Vec<bool>,
+}
+
+impl UniformShadow {
+    fn new(uniforms: &[UniformDescription], data_size: usize) -> Self {
+        Self {
+            data: vec![0; data_size],
+            known: vec![false; uniforms.len()],
+        }
+    }
+
+    /// Returns true if the bytes of the uniform at the index differ from the uploaded ones, and
+    /// remembers them as uploaded.
+    fn update(&mut self, index: usize, offset: usize, bytes: &[u8]) -> bool {
+        let Some(shadow) = self.data.get_mut(offset..offset + bytes.len()) else {
+            return true;
+        };
+        if self.known[index] && shadow == bytes {
+            return false;
+        }
+
+        shadow.copy_from_slice(bytes);
+        self.known[index] = true;
+        true
+    }
+
+    /// Forgets the value of the uniform, after it was uploaded without the copy.
+    fn invalidate(&mut self, index: usize) {
+        self.known[index] = false;
+    }
 }
 
 impl Uniform for GLShaderProgram {
-    fn get_uniform_location(&self, name: &str) -> u32 {
-        unsafe { gl::GetUniformLocation(self.id, name.as_ptr() as *const GLchar) as u32 }
+    fn get_uniform_location(&self, name: &str) -> Option<u32> {
+        self.locations.get(name).copied()
     }
 
     fn data_size(&self) -> usize {
@@ -69,54 +206,122 @@ impl Uniform for GLShaderProgram {
         &self.uniforms
     }
 
+    fn attributes(&self) -> &[AttributeDescription] {
+        &self.attributes
+    }
+
     fn set_uniform_f32(&mut self, location: u32, value: &[f32]) {
-        if let Some(uniform) = self
+        if let Some((index, uniform)) = self
             .uniforms
             .iter()
-            .find(|uniform| uniform.location == location)
+            .enumerate()
+            .find(|(_, uniform)| uniform.location == location)
         {
-            let location = location as i32;
-            let count = uniform.count as i32;
-            let value = value.as_ptr();
-            unsafe {
-                match uniform.kind {
-                    UniformKind::F32 => gl::Uniform1fv(location, count, value),
-                    UniformKind::Mat4 => gl::UniformMatrix4fv(location, count, gl::FALSE, value),
-                    UniformKind::Mat3 => gl::UniformMatrix3fv(location, count, gl::FALSE, value),
-                    UniformKind::Mat2 => gl::UniformMatrix2fv(location, count, gl::FALSE, value),
-                    UniformKind::Vec4 => gl::Uniform4fv(location, count, value),
-                    UniformKind::Vec3 => gl::Uniform3fv(location, count, value),
-                    UniformKind::Vec2 => gl::Uniform2fv(location, count, value),
-                    UniformKind::Sampler2D => todo!(),
-                }
+            if uniform.kind.is_sampler() {
+                log::warn!("Sampler {} can't be set to f32 values", uniform.name);
+                return;
             }
+
+            // never read past the end of the values, even if they don't fill the whole uniform
+            let count =
+                (value.len() / components_from_kind(uniform.kind)).min(uniform.count as usize);
+            if count == 0 {
+                log::warn!("Not enough values for uniform {}", uniform.name);
+                return;
+            }
+
+            self.shadow.invalidate(index);
+            upload(self.id, uniform, count as i32, value.as_ptr());
         }
     }
 
+    /// Without `glProgramUniform` (GL 4.1), the program is bound to upload the data.
+    fn uniform_blocks(&self) -> &[(String, u32)] {
+        &self.uniform_blocks
+    }
+
     fn set_uniform_data(&mut self, data: &[u8]) {
-        self.bind();
-        for uniform in &self.uniforms {
-            let (location, count, value) = (
-                uniform.location as i32,
-                uniform.count as i32,
-                &data[uniform.offset] as *const u8 as *const f32,
-            );
-            unsafe {
-                match uniform.kind {
-                    UniformKind::F32 => gl::Uniform1fv(location, count, value),
-                    UniformKind::Mat4 => gl::UniformMatrix4fv(location, count, gl::FALSE, value),
-                    UniformKind::Mat3 => gl::UniformMatrix3fv(location, count, gl::FALSE, value),
-                    UniformKind::Mat2 => gl::UniformMatrix2fv(location, count, gl::FALSE, value),
-                    UniformKind::Vec4 => gl::Uniform4fv(location, count, value),
-                    UniformKind::Vec3 => gl::Uniform3fv(location, count, value),
-                    UniformKind::Vec2 => gl::Uniform2fv(location, count, value),
-                    UniformKind::Sampler2D => todo!(),
+        if !gl::ProgramUniform1fv::is_loaded() {
+            self.bind();
+        }
+        for (index, uniform) in self.uniforms.iter().enumerate() {
+            // materials often only differ in a few of their values
+            let bytes = &data[uniform.offset..uniform.offset + uniform.size];
+            if !self.shadow.update(index, uniform.offset, bytes) {
+                continue;
+            }
+
+            let value = &data[uniform.offset] as *const u8 as *const f32;
+            upload(self.id, uniform, uniform.count as i32, value);
+        }
+    }
+}
+
+/// Uploads the values of the uniform to the program. Uses `glProgramUniform` where it's available
+/// (GL 4.1 or separate shader objects), so the program doesn't need to be bound. Otherwise it
+/// needs to be the bound one.
+fn upload(program: GLuint, uniform: &UniformDescription, count: i32, value: *const f32) {
+    let location = uniform.location as i32;
+    unsafe {
+        if gl::ProgramUniform1fv::is_loaded() {
+            match uniform.kind {
+                UniformKind::F32 => gl::ProgramUniform1fv(program, location, count, value),
+                UniformKind::Mat4 => {
+                    gl::ProgramUniformMatrix4fv(program, location, count, gl::FALSE, value)
+                }
+                UniformKind::Mat3 => {
+                    gl::ProgramUniformMatrix3fv(program, location, count, gl::FALSE, value)
+                }
+                UniformKind::Mat2 => {
+                    gl::ProgramUniformMatrix2fv(program, location, count, gl::FALSE, value)
+                }
+                UniformKind::Vec4 => gl::ProgramUniform4fv(program, location, count, value),
+                UniformKind::Vec3 => gl::ProgramUniform3fv(program, location, count, value),
+                UniformKind::Vec2 => gl::ProgramUniform2fv(program, location, count, value),
+                // the material stores the texture unit
+                UniformKind::Sampler2D
+                | UniformKind::Sampler1D
+                | UniformKind::SamplerCube
+                | UniformKind::SamplerCubeArray
+                | UniformKind::Sampler2DShadow
+                | UniformKind::SamplerCubeArrayShadow
+                | UniformKind::Sampler2DMS
+                | UniformKind::USampler2D
+                | UniformKind::ISampler2D
+                | UniformKind::UImage2D => {
+                    gl::ProgramUniform1iv(program, location, count, value as *const i32)
                 }
             }
+        } else {
+            match uniform.kind {
+                UniformKind::F32 => gl::Uniform1fv(location, count, value),
+                UniformKind::Mat4 => gl::UniformMatrix4fv(location, count, gl::FALSE, value),
+                UniformKind::Mat3 => gl::UniformMatrix3fv(location, count, gl::FALSE, value),
+                UniformKind::Mat2 => gl::UniformMatrix2fv(location, count, gl::FALSE, value),
+                UniformKind::Vec4 => gl::Uniform4fv(location, count, value),
+                UniformKind::Vec3 => gl::Uniform3fv(location, count, value),
+                UniformKind::Vec2 => gl::Uniform2fv(location, count, value),
+                UniformKind::Sampler2D
+                | UniformKind::Sampler1D
+                | UniformKind::SamplerCube
+                | UniformKind::SamplerCubeArray
+                | UniformKind::Sampler2DShadow
+                | UniformKind::SamplerCubeArrayShadow
+                | UniformKind::Sampler2DMS
+                | UniformKind::USampler2D
+                | UniformKind::ISampler2D
+                | UniformKind::UImage2D => gl::Uniform1iv(location, count, value as *const i32),
+            }
         }
     }
 }
 
+impl DebugLabel for GLShaderProgram {
+    fn set_label(&mut self, label: &str) {
+        super::object_label(gl::PROGRAM, self.id, label);
+    }
+}
+
 impl Drop for GLShaderProgram {
     fn drop(&mut self) {
         if self.id > 0 {
@@ -130,6 +335,14 @@ impl GLShaderProgram {
         vertex_shader: &GLShader,
         fragment_shader: &GLShader,
     ) -> Result<Self, RendererError> {
+        Self::check_kinds(vertex_shader, fragment_shader)?;
+        Self::link(&[vertex_shader, fragment_shader], &[])
+    }
+
+    fn check_kinds(
+        vertex_shader: &GLShader,
+        fragment_shader: &GLShader,
+    ) -> Result<(), RendererError> {
         if vertex_shader.kind != gl::VERTEX_SHADER {
             return Err(RendererError::FailedToLinkProgram {
                 error: "Argument vertex_shader is not a VertexShader".to_string(),
@@ -141,16 +354,56 @@ impl GLShaderProgram {
             });
         }
 
+        Ok(())
+    }
+
+    fn link(
+        shaders: &[&GLShader],
+        attribute_locations: &[(String, u8)],
+    ) -> Result<Self, RendererError> {
         let id = unsafe { gl::CreateProgram() };
 
-        let mut link_status = 0;
+        // bindings of names the shaders don't have are ignored
+        for (name, location) in attribute_locations {
+            match std::ffi::CString::new(name.as_str()) {
+                Ok(name) => unsafe { gl::BindAttribLocation(id, *location as u32, name.as_ptr()) },
+                Err(_) => log::warn!("Attribute name {name:?} contains a nul byte"),
+            }
+        }
+
         unsafe {
-            gl::AttachShader(id, vertex_shader.id);
-            gl::AttachShader(id, fragment_shader.id);
+            // some drivers only keep the binary around if they are told so before linking
+            if gl::ProgramParameteri::is_loaded() {
+                gl::ProgramParameteri(id, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as i32);
+            }
+            for shader in shaders {
+                gl::AttachShader(id, shader.id);
+            }
             gl::LinkProgram(id);
-            gl::DetachShader(id, vertex_shader.id);
-            gl::DetachShader(id, fragment_shader.id);
+            for shader in shaders {
+                gl::DetachShader(id, shader.id);
+            }
+        }
+
+        Self::from_linked(id)
+    }
 
+    // program binaries are core since 4.1
+    fn check_binary_support() -> Result<(), RendererError> {
+        if gl::ProgramBinary::is_loaded() && gl::GetProgramBinary::is_loaded() {
+            Ok(())
+        } else {
+            Err(RendererError::UnsupportedFeature {
+                feature: "Program binaries (OpenGL 4.1)".to_string(),
+            })
+        }
+    }
+
+    /// Checks the link status of the program, which was either linked from shaders or loaded from
+    /// a binary, and reflects its uniforms.
+    fn from_linked(id: GLuint) -> Result<Self, RendererError> {
+        let mut link_status = 0;
+        unsafe {
             gl::GetProgramiv(id, gl::LINK_STATUS, &mut link_status);
         }
 
@@ -158,10 +411,18 @@ impl GLShaderProgram {
         //link_status == 0 means there is a link error
         if link_status != 0 {
             unsafe { gl::UseProgram(id) };
+            let locations = uniforms
+                .iter()
+                .map(|uniform| (uniform.name.clone(), uniform.location))
+                .collect();
             let program = Self {
                 id,
                 data_size,
+                shadow: UniformShadow::new(&uniforms, data_size),
                 uniforms,
+                locations,
+                attributes: Self::get_attributes(id),
+                uniform_blocks: Self::bind_uniform_blocks(id),
             };
             Ok(program)
         } else {
@@ -188,12 +449,18 @@ impl GLShaderProgram {
         }
     }
 
-    fn bind(&mut self) {
+    pub(super) fn bind(&mut self) {
         unsafe {
             gl::UseProgram(self.id);
         }
     }
 
+    /// Forgets the uploaded uniform values, after code outside of the renderer might have
+    /// changed them.
+    pub(super) fn invalidate_uniforms(&mut self) {
+        self.shadow.known.fill(false);
+    }
+
     fn get_uniforms(id: GLuint) -> (Vec<UniformDescription>, usize) {
         let mut uniform_count = 0;
         unsafe {
@@ -248,17 +515,20 @@ impl GLShaderProgram {
                 gl::FLOAT_MAT3 => UniformKind::Mat3,
                 gl::FLOAT_MAT4 => UniformKind::Mat4,
                 gl::SAMPLER_2D => UniformKind::Sampler2D,
+                gl::SAMPLER_1D => UniformKind::Sampler1D,
+                gl::SAMPLER_CUBE => UniformKind::SamplerCube,
+                gl::SAMPLER_CUBE_MAP_ARRAY => UniformKind::SamplerCubeArray,
+                gl::SAMPLER_CUBE_MAP_ARRAY_SHADOW => UniformKind::SamplerCubeArrayShadow,
+                gl::SAMPLER_2D_SHADOW => UniformKind::Sampler2DShadow,
+                gl::SAMPLER_2D_MULTISAMPLE => UniformKind::Sampler2DMS,
+                gl::UNSIGNED_INT_SAMPLER_2D => UniformKind::USampler2D,
+                gl::INT_SAMPLER_2D => UniformKind::ISampler2D,
+                gl::UNSIGNED_INT_IMAGE_2D => UniformKind::UImage2D,
                 _ => todo!(),
             };
 
-            //it's an array, so get rid of the [0] at the end
-            //TODO: potentially driver dependent whether [0] is appended, so I need to look
-            //into that
-            if uniform_len > 1 {
-                name_length -= 3;
-            }
-
-            let name = String::from_utf8_lossy(&uniform_name[0..name_length as usize]).to_string();
+            let name_length = (name_length.max(0) as usize).min(BUFFER_SIZE);
+            let name = reflected_uniform_name(&uniform_name[..name_length]);
 
             //let texture_slot = match kind {
             //Kind::Sampler2D { len } => {
@@ -284,12 +554,133 @@ impl GLShaderProgram {
 
         (uniforms, data_size)
     }
+
+    /// Binds each active uniform block to the binding point of its index and returns their names
+    /// and bindings.
+    fn bind_uniform_blocks(id: GLuint) -> Vec<(String, u32)> {
+        let mut block_count = 0;
+        unsafe {
+            gl::GetProgramiv(id, gl::ACTIVE_UNIFORM_BLOCKS, &mut block_count);
+        }
+
+        const BUFFER_SIZE: usize = 256;
+        (0..block_count.max(0) as u32)
+            .map(|index| {
+                let mut name_length = 0;
+                let mut name = [0u8; BUFFER_SIZE];
+                unsafe {
+                    gl::GetActiveUniformBlockName(
+                        id,
+                        index,
+                        BUFFER_SIZE as i32,
+                        &mut name_length,
+                        name.as_mut_ptr() as *mut i8,
+                    );
+                    gl::UniformBlockBinding(id, index, index);
+                }
+
+                let name_length = (name_length.max(0) as usize).min(BUFFER_SIZE);
+                let name = String::from_utf8_lossy(&name[..name_length]).into_owned();
+                (name, index)
+            })
+            .collect()
+    }
+
+    fn get_attributes(id: GLuint) -> Vec<AttributeDescription> {
+        let mut attribute_count = 0;
+        unsafe {
+            gl::GetProgramiv(id, gl::ACTIVE_ATTRIBUTES, &mut attribute_count);
+        }
+
+        const BUFFER_SIZE: usize = 256;
+        let mut attributes = Vec::with_capacity(attribute_count.max(0) as usize);
+        for index in 0..attribute_count.max(0) {
+            let mut count = 0;
+            let mut name_length = 0;
+            let mut kind = 0;
+            let mut name = [0u8; BUFFER_SIZE];
+
+            unsafe {
+                gl::GetActiveAttrib(
+                    id,
+                    index as u32,
+                    BUFFER_SIZE as i32,
+                    &mut name_length,
+                    &mut count,
+                    &mut kind,
+                    name.as_mut_ptr() as *mut i8,
+                )
+            }
+
+            //built-in inputs like gl_VertexID have no location
+            let location = unsafe { gl::GetAttribLocation(id, name.as_ptr() as *const i8) };
+            if location < 0 {
+                continue;
+            }
+
+            let name_length = (name_length.max(0) as usize).min(BUFFER_SIZE);
+            let name = reflected_uniform_name(&name[..name_length]);
+            let kind = match kind {
+                gl::FLOAT => UniformKind::F32,
+                gl::FLOAT_VEC2 => UniformKind::Vec2,
+                gl::FLOAT_VEC3 => UniformKind::Vec3,
+                gl::FLOAT_VEC4 => UniformKind::Vec4,
+                gl::FLOAT_MAT2 => UniformKind::Mat2,
+                gl::FLOAT_MAT3 => UniformKind::Mat3,
+                gl::FLOAT_MAT4 => UniformKind::Mat4,
+                _ => {
+                    log::warn!("Attribute {name} has an unsupported type {kind:#x}");
+                    continue;
+                }
+            };
+
+            attributes.push(AttributeDescription {
+                name,
+                location: location as u32,
+                kind,
+                count: count as u32,
+            });
+        }
+
+        attributes.sort_by_key(|attribute| attribute.location);
+        attributes
+    }
+}
+
+fn components_from_kind(kind: UniformKind) -> usize {
+    match kind {
+        UniformKind::F32
+        | UniformKind::Sampler2D
+        | UniformKind::Sampler1D
+        | UniformKind::SamplerCube
+        | UniformKind::SamplerCubeArray
+        | UniformKind::Sampler2DShadow
+        | UniformKind::SamplerCubeArrayShadow
+        | UniformKind::Sampler2DMS
+        | UniformKind::USampler2D
+        | UniformKind::ISampler2D
+        | UniformKind::UImage2D => 1,
+        UniformKind::Vec2 => 2,
+        UniformKind::Vec3 => 3,
+        UniformKind::Vec4 | UniformKind::Mat2 => 4,
+        UniformKind::Mat3 => 9,
+        UniformKind::Mat4 => 16,
+    }
 }
 
 fn uniform_size_from_kind(kind: UniformKind, count: usize) -> usize {
     let size = match kind {
         UniformKind::F32 => size_of::<gl::types::GLfloat>(),
-        UniformKind::Sampler2D => todo!(),
+        UniformKind::Sampler2D
+        | UniformKind::Sampler1D
+        | UniformKind::SamplerCube
+        | UniformKind::SamplerCubeArray
+        | UniformKind::Sampler2DShadow
+        | UniformKind::SamplerCubeArrayShadow
+        | UniformKind::Sampler2DMS
+        | UniformKind::USampler2D
+        | UniformKind::ISampler2D
+        | UniformKind::UImage2D => size_of::<gl::types::GLint>(),
         UniformKind::Mat2 => size_of::<gl::types::GLfloat>() * 4,
         UniformKind::Mat3 => size_of::<gl::types::GLfloat>() * 12,
         UniformKind::Mat4 => size_of::<gl::types::GLfloat>() * 16,
@@ -300,3 +691,33 @@ fn uniform_size_from_kind(kind: UniformKind, count: usize) -> usize {
 
     size * count
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn uniform_shadow() {
+        let uniforms = [UniformDescription {
+            name: "tint".to_string(),
+            location: 0,
+            kind: UniformKind::Vec2,
+            count: 1,
+            size: 8,
+            offset: 0,
+        }];
+        let mut shadow = UniformShadow::new(&uniforms, 8);
+        let value = [1; 8];
+
+        // unknown values are always uploaded, even if they match the copy
+        assert!(shadow.update(0, 0, &[0; 8]));
+        assert!(shadow.update(0, 0, &value));
+        assert!(!shadow.update(0, 0, &value));
+        assert!(shadow.update(0, 0, &[2; 8]));
+
+        shadow.invalidate(0);
+        assert!(shadow.update(0, 0, &[2; 8]));
+        // values past the copy can't be compared
+        assert!(shadow.update(0, 4, &value));
+    }
+}
@@ -0,0 +1,687 @@
+use gl::types::{GLenum, GLuint};
+
+use crate::{
+    renderer::texture::{
+        mip_levels, CreateTexture, DepthStencilMode, Swizzle, SwizzleSource, TextureKind,
+        TextureRegion, TextureViewDescription,
+    },
+    CompressedFormat, RendererError, TextureFormat,
+};
+
+use super::DebugLabel;
+
+// S3TC isn't core and not part of the generated bindings
+const COMPRESSED_RGBA_S3TC_DXT1_EXT: GLenum = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT3_EXT: GLenum = 0x83F2;
+const COMPRESSED_RGBA_S3TC_DXT5_EXT: GLenum = 0x83F3;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT: GLenum = 0x8C4D;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT: GLenum = 0x8C4E;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT: GLenum = 0x8C4F;
+
+#[derive(Debug)]
+pub struct GLTexture {
+    pub(super) id: GLuint,
+    /// The target it's bound to, e.g. TEXTURE_CUBE_MAP.
+    pub(super) target: GLenum,
+    pub(super) width: u32,
+    pub(super) height: u32,
+    /// None for compressed textures.
+    pub(super) format: Option<TextureFormat>,
+    /// 1 unless it's multisampled.
+    pub(super) samples: u32,
+    levels: u32,
+    /// Faces of all cubemaps of cubemap arrays, 1 for other textures.
+    layers: u32,
+    /// Allocated with TexStorage2D, which texture views need. Otherwise it's allocated level by
+    /// level, limited to the levels by TEXTURE_MAX_LEVEL.
+    immutable: bool,
+}
+
+impl CreateTexture for GLTexture {
+    fn new(
+        data: &[u8],
+        width: u32,
+        height: u32,
+        row_pitch: usize,
+        format: TextureFormat,
+    ) -> Result<Self, RendererError> {
+        let mut texture = Self::with_target(gl::TEXTURE_2D, width, height, Some(format));
+        unsafe {
+            texture.allocate(pixel_layout(format).0, 1);
+            texture.image(gl::TEXTURE_2D, format, data, row_pitch);
+            // there are no mip levels
+            texture.set_parameters(&[
+                (gl::TEXTURE_MIN_FILTER, filter(format)),
+                (gl::TEXTURE_MAG_FILTER, filter(format)),
+            ]);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        Ok(texture)
+    }
+
+    fn mipmapped(
+        data: &[u8],
+        width: u32,
+        height: u32,
+        row_pitch: usize,
+        format: TextureFormat,
+    ) -> Result<Self, RendererError> {
+        let mut texture = Self::with_target(gl::TEXTURE_2D, width, height, Some(format));
+        unsafe {
+            texture.allocate(pixel_layout(format).0, mip_levels(width, height));
+            texture.image(gl::TEXTURE_2D, format, data, row_pitch);
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+            texture.set_parameters(&[
+                (gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR),
+                (gl::TEXTURE_MAG_FILTER, gl::LINEAR),
+            ]);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        Ok(texture)
+    }
+
+    fn new_1d(data: &[u8], width: u32, format: TextureFormat) -> Result<Self, RendererError> {
+        let mut texture = Self::with_target(gl::TEXTURE_1D, width, 1, Some(format));
+        let (internal_format, pixel_format, kind) = pixel_layout(format);
+        let pixels = data.as_ptr() as *const std::ffi::c_void;
+        unsafe {
+            texture.allocate(internal_format, 1);
+            if texture.immutable {
+                gl::TexSubImage1D(
+                    gl::TEXTURE_1D,
+                    0,
+                    0,
+                    width as i32,
+                    pixel_format,
+                    kind,
+                    pixels,
+                );
+            } else {
+                gl::TexImage1D(
+                    gl::TEXTURE_1D,
+                    0,
+                    internal_format as i32,
+                    width as i32,
+                    0,
+                    pixel_format,
+                    kind,
+                    pixels,
+                );
+            }
+
+            // gradients are usually sampled over their whole range, without repeating
+            texture.set_parameters(&[
+                (gl::TEXTURE_MIN_FILTER, filter(format)),
+                (gl::TEXTURE_MAG_FILTER, filter(format)),
+                (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE),
+            ]);
+            gl::BindTexture(gl::TEXTURE_1D, 0);
+        }
+
+        Ok(texture)
+    }
+
+    fn cubemap(faces: [&[u8]; 6], size: u32, format: TextureFormat) -> Result<Self, RendererError> {
+        let mut texture = Self::with_target(gl::TEXTURE_CUBE_MAP, size, size, Some(format));
+        unsafe {
+            texture.allocate(pixel_layout(format).0, 1);
+            // the faces are in the same order as the targets
+            for (index, face) in faces.iter().enumerate() {
+                let target = gl::TEXTURE_CUBE_MAP_POSITIVE_X + index as u32;
+                texture.image(target, format, face, size as usize * format.texel_size());
+            }
+
+            // there are no mip levels, and repeating would bleed the opposite edge into the seams
+            texture.set_parameters(&[
+                (gl::TEXTURE_MIN_FILTER, filter(format)),
+                (gl::TEXTURE_MAG_FILTER, filter(format)),
+                (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE),
+                (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE),
+                (gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE),
+            ]);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+        }
+
+        Ok(texture)
+    }
+
+    fn cubemap_array(
+        cubemaps: &[[&[u8]; 6]],
+        size: u32,
+        format: TextureFormat,
+    ) -> Result<Self, RendererError> {
+        let target = gl::TEXTURE_CUBE_MAP_ARRAY;
+        let mut texture = Self::with_target(target, size, size, Some(format));
+        texture.layers = cubemaps.len() as u32 * 6;
+        let (internal_format, pixel_format, kind) = pixel_layout(format);
+        unsafe {
+            texture.allocate(internal_format, 1);
+            if !texture.immutable {
+                gl::TexImage3D(
+                    target,
+                    0,
+                    internal_format as i32,
+                    size as i32,
+                    size as i32,
+                    texture.layers as i32,
+                    0,
+                    pixel_format,
+                    kind,
+                    std::ptr::null(),
+                );
+            }
+
+            // the layers are the faces of the cubemaps, in order
+            let faces = cubemaps.iter().flatten();
+            for (layer, face) in faces.enumerate() {
+                gl::TexSubImage3D(
+                    target,
+                    0,
+                    0,
+                    0,
+                    layer as i32,
+                    size as i32,
+                    size as i32,
+                    1,
+                    pixel_format,
+                    kind,
+                    face.as_ptr() as *const std::ffi::c_void,
+                );
+            }
+
+            texture.set_parameters(&[
+                (gl::TEXTURE_MIN_FILTER, filter(format)),
+                (gl::TEXTURE_MAG_FILTER, filter(format)),
+                (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE),
+                (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE),
+                (gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE),
+            ]);
+            gl::BindTexture(target, 0);
+        }
+
+        Ok(texture)
+    }
+
+    fn compressed(
+        levels: &[&[u8]],
+        width: u32,
+        height: u32,
+        format: CompressedFormat,
+    ) -> Result<Self, RendererError> {
+        // unsupported formats are only reported by the debug callback, check them with
+        // supports_compressed_format beforehand
+        let mut texture = Self::with_target(gl::TEXTURE_2D, width, height, None);
+        let internal_format = compressed_format(format);
+        unsafe {
+            texture.allocate(internal_format, levels.len() as u32);
+            for (level, data) in levels.iter().enumerate() {
+                let (width, height) = ((width >> level).max(1), (height >> level).max(1));
+                let pixels = data.as_ptr() as *const std::ffi::c_void;
+                if texture.immutable {
+                    gl::CompressedTexSubImage2D(
+                        gl::TEXTURE_2D,
+                        level as i32,
+                        0,
+                        0,
+                        width as i32,
+                        height as i32,
+                        internal_format,
+                        data.len() as i32,
+                        pixels,
+                    );
+                } else {
+                    gl::CompressedTexImage2D(
+                        gl::TEXTURE_2D,
+                        level as i32,
+                        internal_format,
+                        width as i32,
+                        height as i32,
+                        0,
+                        data.len() as i32,
+                        pixels,
+                    );
+                }
+            }
+
+            let min_filter = if levels.len() > 1 {
+                gl::LINEAR_MIPMAP_LINEAR
+            } else {
+                gl::LINEAR
+            };
+            texture.set_parameters(&[
+                (gl::TEXTURE_MIN_FILTER, min_filter),
+                (gl::TEXTURE_MAG_FILTER, gl::LINEAR),
+            ]);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        Ok(texture)
+    }
+
+    fn multisampled(
+        width: u32,
+        height: u32,
+        samples: u32,
+        format: TextureFormat,
+    ) -> Result<Self, RendererError> {
+        let mut max_samples = 0;
+        unsafe { gl::GetIntegerv(gl::MAX_COLOR_TEXTURE_SAMPLES, &mut max_samples) };
+        if samples > max_samples.max(1) as u32 {
+            return Err(RendererError::FailedToCreateTexture {
+                error: format!("{samples} samples, but the driver supports at most {max_samples}"),
+            });
+        }
+
+        let mut texture =
+            Self::with_target(gl::TEXTURE_2D_MULTISAMPLE, width, height, Some(format));
+        texture.samples = samples;
+        let (internal_format, _, _) = pixel_layout(format);
+        unsafe {
+            // fixed sample locations, so resolves and TAA see the same pattern in every pixel
+            if gl::TexStorage2DMultisample::is_loaded() {
+                gl::TexStorage2DMultisample(
+                    gl::TEXTURE_2D_MULTISAMPLE,
+                    samples as i32,
+                    internal_format,
+                    width as i32,
+                    height as i32,
+                    gl::TRUE,
+                );
+                texture.immutable = true;
+            } else {
+                gl::TexImage2DMultisample(
+                    gl::TEXTURE_2D_MULTISAMPLE,
+                    samples as i32,
+                    internal_format,
+                    width as i32,
+                    height as i32,
+                    gl::TRUE,
+                );
+            }
+            gl::BindTexture(gl::TEXTURE_2D_MULTISAMPLE, 0);
+        }
+
+        Ok(texture)
+    }
+
+    fn update(
+        &mut self,
+        data: &[u8],
+        region: TextureRegion,
+        row_pitch: usize,
+    ) -> Result<(), RendererError> {
+        let format = match (self.target, self.format) {
+            (gl::TEXTURE_2D, Some(format)) => format,
+            _ => {
+                return Err(RendererError::FailedToUpdateTexture {
+                    error: "Only uncompressed 2D textures can be updated".to_string(),
+                })
+            }
+        };
+
+        let (_, pixel_format, kind) = pixel_layout(format);
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            with_row_pitch(format, row_pitch, || {
+                gl::TexSubImage2D(
+                    gl::TEXTURE_2D,
+                    0,
+                    region.x as i32,
+                    region.y as i32,
+                    region.width as i32,
+                    region.height as i32,
+                    pixel_format,
+                    kind,
+                    data.as_ptr() as *const std::ffi::c_void,
+                )
+            });
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        Ok(())
+    }
+
+    fn set_swizzle(&mut self, swizzle: Swizzle) {
+        let source = |source| match source {
+            SwizzleSource::Red => gl::RED,
+            SwizzleSource::Green => gl::GREEN,
+            SwizzleSource::Blue => gl::BLUE,
+            SwizzleSource::Alpha => gl::ALPHA,
+            SwizzleSource::Zero => gl::ZERO,
+            SwizzleSource::One => gl::ONE,
+        } as i32;
+        let mask = [
+            source(swizzle.r),
+            source(swizzle.g),
+            source(swizzle.b),
+            source(swizzle.a),
+        ];
+
+        unsafe {
+            gl::BindTexture(self.target, self.id);
+            gl::TexParameteriv(self.target, gl::TEXTURE_SWIZZLE_RGBA, mask.as_ptr());
+            gl::BindTexture(self.target, 0);
+        }
+    }
+
+    fn set_depth_stencil_mode(&mut self, mode: DepthStencilMode) -> Result<(), RendererError> {
+        // integer textures are incomplete with linear filtering
+        let (mode, filter) = match mode {
+            DepthStencilMode::Depth => (gl::DEPTH_COMPONENT, gl::LINEAR),
+            DepthStencilMode::Stencil => (gl::STENCIL_INDEX, gl::NEAREST),
+        };
+
+        let error = unsafe {
+            gl::BindTexture(self.target, self.id);
+            super::clear_errors();
+            gl::TexParameteri(self.target, gl::DEPTH_STENCIL_TEXTURE_MODE, mode as i32);
+            let error = gl::GetError();
+            if error == gl::NO_ERROR {
+                self.set_parameters(&[
+                    (gl::TEXTURE_MIN_FILTER, filter),
+                    (gl::TEXTURE_MAG_FILTER, filter),
+                ]);
+            }
+            gl::BindTexture(self.target, 0);
+            error
+        };
+
+        if error != gl::NO_ERROR {
+            return Err(RendererError::UnsupportedFeature {
+                feature: "Stencil texturing (OpenGL 4.3)".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn view(&self, description: &TextureViewDescription) -> Result<Self, RendererError> {
+        if !self.immutable || !gl::TextureView::is_loaded() {
+            return Err(RendererError::UnsupportedFeature {
+                feature: "Texture views (OpenGL 4.3)".to_string(),
+            });
+        }
+
+        // the layer is validated to be inside the texture
+        let (target, first_layer, layers) = match (self.target, description.layer) {
+            (gl::TEXTURE_CUBE_MAP, None) => (gl::TEXTURE_CUBE_MAP, 0, 6),
+            (gl::TEXTURE_CUBE_MAP | gl::TEXTURE_CUBE_MAP_ARRAY, Some(face)) => {
+                (gl::TEXTURE_2D, face, 1)
+            }
+            (target, _) => (target, 0, self.layers),
+        };
+
+        let (internal_format, _, _) = pixel_layout(description.format);
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::TextureView(
+                id,
+                target,
+                self.id,
+                internal_format,
+                description.first_level,
+                description.levels,
+                first_layer,
+                layers,
+            );
+        }
+
+        let view = Self {
+            id,
+            target,
+            width: (self.width >> description.first_level).max(1),
+            height: (self.height >> description.first_level).max(1),
+            format: Some(description.format),
+            samples: self.samples,
+            levels: description.levels,
+            layers: if target == gl::TEXTURE_CUBE_MAP_ARRAY {
+                layers
+            } else {
+                1
+            },
+            immutable: true,
+        };
+        // multisampled textures have no sampler state
+        if target == gl::TEXTURE_2D_MULTISAMPLE {
+            return Ok(view);
+        }
+
+        let min_filter = match filter(description.format) {
+            gl::LINEAR if view.levels > 1 => gl::LINEAR_MIPMAP_LINEAR,
+            filter => filter,
+        };
+        unsafe {
+            gl::BindTexture(target, view.id);
+            view.set_parameters(&[
+                (gl::TEXTURE_MIN_FILTER, min_filter),
+                (gl::TEXTURE_MAG_FILTER, filter(description.format)),
+            ]);
+            gl::BindTexture(target, 0);
+        }
+        Ok(view)
+    }
+
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn format(&self) -> Option<TextureFormat> {
+        self.format
+    }
+
+    fn levels(&self) -> u32 {
+        self.levels
+    }
+
+    fn kind(&self) -> TextureKind {
+        match self.target {
+            gl::TEXTURE_1D => TextureKind::Texture1D,
+            gl::TEXTURE_CUBE_MAP => TextureKind::Cubemap,
+            gl::TEXTURE_CUBE_MAP_ARRAY => TextureKind::CubemapArray {
+                cubemaps: self.layers / 6,
+            },
+            gl::TEXTURE_2D_MULTISAMPLE => TextureKind::Multisampled,
+            _ => TextureKind::Texture2D,
+        }
+    }
+}
+
+/// Filter of the format, integer textures are incomplete with linear filtering.
+fn filter(format: TextureFormat) -> GLenum {
+    if format.is_integer() {
+        gl::NEAREST
+    } else {
+        gl::LINEAR
+    }
+}
+
+/// Internal format, pixel format and component type of the texture format.
+pub(super) fn pixel_layout(format: TextureFormat) -> (GLenum, GLenum, GLenum) {
+    match format {
+        TextureFormat::R8 => (gl::R8, gl::RED, gl::UNSIGNED_BYTE),
+        TextureFormat::Rg8 => (gl::RG8, gl::RG, gl::UNSIGNED_BYTE),
+        TextureFormat::Rgb8 => (gl::RGB8, gl::RGB, gl::UNSIGNED_BYTE),
+        TextureFormat::Rgba8 => (gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE),
+        TextureFormat::Srgb8Alpha8 => (gl::SRGB8_ALPHA8, gl::RGBA, gl::UNSIGNED_BYTE),
+        TextureFormat::Rgba16F => (gl::RGBA16F, gl::RGBA, gl::HALF_FLOAT),
+        TextureFormat::R16F => (gl::R16F, gl::RED, gl::HALF_FLOAT),
+        TextureFormat::R32F => (gl::R32F, gl::RED, gl::FLOAT),
+        TextureFormat::R32UI => (gl::R32UI, gl::RED_INTEGER, gl::UNSIGNED_INT),
+        TextureFormat::R32I => (gl::R32I, gl::RED_INTEGER, gl::INT),
+        TextureFormat::Depth24Stencil8 => (
+            gl::DEPTH24_STENCIL8,
+            gl::DEPTH_STENCIL,
+            gl::UNSIGNED_INT_24_8,
+        ),
+    }
+}
+
+/// Runs the upload with rows that start row_pitch bytes apart.
+///
+/// The unpack alignment is 1 for the whole context, so rows of any size are tightly packed by
+/// default. Padded rows are described by the row length in texels, which is reset afterwards.
+unsafe fn with_row_pitch(format: TextureFormat, row_pitch: usize, upload: impl FnOnce()) {
+    gl::PixelStorei(
+        gl::UNPACK_ROW_LENGTH,
+        (row_pitch / format.texel_size()) as i32,
+    );
+    upload();
+    gl::PixelStorei(gl::UNPACK_ROW_LENGTH, 0);
+}
+
+pub(super) fn compressed_format(format: CompressedFormat) -> GLenum {
+    match format {
+        CompressedFormat::Bc1 => COMPRESSED_RGBA_S3TC_DXT1_EXT,
+        CompressedFormat::Bc1Srgb => COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT,
+        CompressedFormat::Bc2 => COMPRESSED_RGBA_S3TC_DXT3_EXT,
+        CompressedFormat::Bc2Srgb => COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT,
+        CompressedFormat::Bc3 => COMPRESSED_RGBA_S3TC_DXT5_EXT,
+        CompressedFormat::Bc3Srgb => COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT,
+        CompressedFormat::Bc4 => gl::COMPRESSED_RED_RGTC1,
+        CompressedFormat::Bc5 => gl::COMPRESSED_RG_RGTC2,
+        CompressedFormat::Bc6hUfloat => gl::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT,
+        CompressedFormat::Bc7 => gl::COMPRESSED_RGBA_BPTC_UNORM,
+        CompressedFormat::Bc7Srgb => gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM,
+        CompressedFormat::Etc2Rgb8 => gl::COMPRESSED_RGB8_ETC2,
+        CompressedFormat::Etc2Srgb8 => gl::COMPRESSED_SRGB8_ETC2,
+        CompressedFormat::Etc2Rgba8 => gl::COMPRESSED_RGBA8_ETC2_EAC,
+        CompressedFormat::Etc2Srgb8Alpha8 => gl::COMPRESSED_SRGB8_ALPHA8_ETC2_EAC,
+    }
+}
+
+impl GLTexture {
+    /// Creates the texture and leaves it bound.
+    fn with_target(target: GLenum, width: u32, height: u32, format: Option<TextureFormat>) -> Self {
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(target, id);
+        }
+
+        Self {
+            id,
+            target,
+            width,
+            height,
+            format,
+            samples: 1,
+            levels: 1,
+            layers: 1,
+            immutable: false,
+        }
+    }
+
+    /// 2D texture with a single level, in immutable storage that allocate creates for the bound
+    /// texture from the internal format, e.g. in [imported memory][super::SharedTarget].
+    pub(super) fn with_storage(
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        allocate: impl FnOnce(GLenum),
+    ) -> Self {
+        let mut texture = Self::with_target(gl::TEXTURE_2D, width, height, Some(format));
+        texture.immutable = true;
+        allocate(pixel_layout(format).0);
+        unsafe {
+            texture.set_parameters(&[
+                (gl::TEXTURE_MIN_FILTER, filter(format)),
+                (gl::TEXTURE_MAG_FILTER, filter(format)),
+            ]);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        texture
+    }
+
+    /// Allocates immutable storage for the levels of the bound texture, if the context has
+    /// TexStorage (core since 4.2), so the drivers can lay out the whole mip chain up front and
+    /// it can be viewed.
+    ///
+    /// Otherwise the images are allocated when they are uploaded, and the levels are limited to
+    /// the uploaded ones, so the texture can't be incomplete because of missing levels.
+    unsafe fn allocate(&mut self, internal_format: GLenum, levels: u32) {
+        self.levels = levels;
+        if gl::TexStorage2D::is_loaded() {
+            let (levels, width, height) = (levels as i32, self.width as i32, self.height as i32);
+            match self.target {
+                gl::TEXTURE_1D => gl::TexStorage1D(self.target, levels, internal_format, width),
+                gl::TEXTURE_CUBE_MAP_ARRAY => gl::TexStorage3D(
+                    self.target,
+                    levels,
+                    internal_format,
+                    width,
+                    height,
+                    self.layers as i32,
+                ),
+                _ => gl::TexStorage2D(self.target, levels, internal_format, width, height),
+            }
+            self.immutable = true;
+        } else {
+            self.set_parameters(&[
+                (gl::TEXTURE_BASE_LEVEL, 0),
+                (gl::TEXTURE_MAX_LEVEL, levels - 1),
+            ]);
+        }
+    }
+
+    /// Uploads the base level of the image target of the bound texture, into its storage if it's
+    /// allocated already.
+    unsafe fn image(
+        &self,
+        image_target: GLenum,
+        format: TextureFormat,
+        data: &[u8],
+        row_pitch: usize,
+    ) {
+        let (internal_format, pixel_format, kind) = pixel_layout(format);
+        with_row_pitch(format, row_pitch, || {
+            if self.immutable {
+                gl::TexSubImage2D(
+                    image_target,
+                    0,
+                    0,
+                    0,
+                    self.width as i32,
+                    self.height as i32,
+                    pixel_format,
+                    kind,
+                    data.as_ptr() as *const std::ffi::c_void,
+                )
+            } else {
+                gl::TexImage2D(
+                    image_target,
+                    0,
+                    internal_format as i32,
+                    self.width as i32,
+                    self.height as i32,
+                    0,
+                    pixel_format,
+                    kind,
+                    data.as_ptr() as *const std::ffi::c_void,
+                )
+            }
+        });
+    }
+
+    unsafe fn set_parameters(&self, parameters: &[(GLenum, GLenum)]) {
+        for (name, value) in parameters {
+            gl::TexParameteri(self.target, *name, *value as i32);
+        }
+    }
+}
+
+impl DebugLabel for GLTexture {
+    fn set_label(&mut self, label: &str) {
+        super::object_label(gl::TEXTURE, self.id, label);
+    }
+}
+
+impl Drop for GLTexture {
+    fn drop(&mut self) {
+        if self.id > 0 {
+            unsafe { gl::DeleteTextures(1, &self.id) };
+        }
+    }
+}
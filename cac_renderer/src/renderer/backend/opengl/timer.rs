@@ -0,0 +1,189 @@
+use std::collections::VecDeque;
+
+use gl::types::GLuint;
+
+use crate::{
+    renderer::{GpuScope, Material, TimedRun},
+    Handle,
+};
+
+/// Frames whose queries may still be in flight. Once there are more, the oldest frame waits for
+/// its results.
+const MAX_FRAMES_IN_FLIGHT: usize = 4;
+
+/// Material and layer of a run of draws.
+type RunKey = (Handle<Material>, u8);
+
+/// Queries of a frame.
+#[derive(Debug, Default)]
+struct TimerFrame {
+    /// Ended scopes.
+    scopes: Vec<(String, GLuint)>,
+    /// Timestamps at the start of the runs of draws with the same material and layer. The end of
+    /// the runs of an update is a timestamp without a run.
+    runs: Vec<(Option<RunKey>, GLuint)>,
+}
+
+impl TimerFrame {
+    fn is_empty(&self) -> bool {
+        self.scopes.is_empty() && self.runs.is_empty()
+    }
+
+    fn queries(self) -> impl Iterator<Item = GLuint> {
+        let scopes = self.scopes.into_iter().map(|(_, query)| query);
+        scopes.chain(self.runs.into_iter().map(|(_, query)| query))
+    }
+}
+
+/// Measures GPU scopes with GL_TIME_ELAPSED queries, and the runs of draws with the same material
+/// on the same layer with GL_TIMESTAMP queries, both core since OpenGL 3.3. Time elapsed queries
+/// can't nest, but the runs are inside layers and maybe inside a scope.
+///
+/// The results are only read once the GPU finished the frame, which is usually a few frames
+/// later, so reading them doesn't stall the pipeline.
+#[derive(Debug, Default)]
+pub(super) struct GpuTimer {
+    /// Query objects whose results were read already.
+    free: Vec<GLuint>,
+    open: Option<(String, GLuint)>,
+    frame: TimerFrame,
+    in_flight: VecDeque<TimerFrame>,
+    /// The runs of the latest frame whose results are available.
+    runs: Vec<TimedRun>,
+}
+
+impl GpuTimer {
+    pub(super) fn open_scope(&self) -> Option<&str> {
+        self.open.as_ref().map(|(name, _)| name.as_str())
+    }
+
+    fn query(&mut self) -> GLuint {
+        self.free.pop().unwrap_or_else(|| {
+            let mut query = 0;
+            unsafe { gl::GenQueries(1, &mut query) };
+            query
+        })
+    }
+
+    /// The open scope has to be ended first, time elapsed queries can't overlap.
+    pub(super) fn begin(&mut self, name: &str) {
+        let query = self.query();
+        unsafe { gl::BeginQuery(gl::TIME_ELAPSED, query) };
+        self.open = Some((name.to_string(), query));
+    }
+
+    /// Returns false if there was no open scope.
+    pub(super) fn end(&mut self) -> bool {
+        match self.open.take() {
+            Some(scope) => {
+                unsafe { gl::EndQuery(gl::TIME_ELAPSED) };
+                self.frame.scopes.push(scope);
+                true
+            }
+            None => {
+                log::warn!("No GPU scope to end");
+                false
+            }
+        }
+    }
+
+    /// Starts a run of draws with the material on the layer, which lasts until the next run or
+    /// the [end of the runs][Self::end_runs].
+    pub(super) fn begin_run(&mut self, material: Handle<Material>, layer: u8) {
+        let query = self.query();
+        unsafe { gl::QueryCounter(query, gl::TIMESTAMP) };
+        self.frame.runs.push((Some((material, layer)), query));
+    }
+
+    /// Ends the last run of the update.
+    pub(super) fn end_runs(&mut self) {
+        if let Some((Some(_), _)) = self.frame.runs.last() {
+            let query = self.query();
+            unsafe { gl::QueryCounter(query, gl::TIMESTAMP) };
+            self.frame.runs.push((None, query));
+        }
+    }
+
+    /// The runs of the latest frame whose results are available.
+    pub(super) fn runs(&self) -> &[TimedRun] {
+        &self.runs
+    }
+
+    /// Ends the frame, returning the scopes of the newest frame with scopes whose results are
+    /// available.
+    pub(super) fn end_frame(&mut self) -> Option<Vec<GpuScope>> {
+        if !self.frame.is_empty() {
+            self.in_flight.push_back(std::mem::take(&mut self.frame));
+        }
+
+        let mut latest = None;
+        while let Some(frame) = self.in_flight.front() {
+            let must_wait = self.in_flight.len() > MAX_FRAMES_IN_FLIGHT;
+            // queries finish in order, so the last scope and the last run finish the frame
+            let last_scope = frame.scopes.last().map(|(_, query)| *query);
+            let last_run = frame.runs.last().map(|(_, query)| *query);
+            let available = last_scope.into_iter().chain(last_run).all(|query| {
+                let mut available = 0;
+                unsafe { gl::GetQueryObjectiv(query, gl::QUERY_RESULT_AVAILABLE, &mut available) };
+                available != 0
+            });
+            if !available && !must_wait {
+                break;
+            }
+
+            let frame = self.in_flight.pop_front().unwrap_or_default();
+            let result = |query: GLuint| {
+                let mut result = 0;
+                unsafe { gl::GetQueryObjectui64v(query, gl::QUERY_RESULT, &mut result) };
+                result
+            };
+
+            if !frame.runs.is_empty() {
+                let timestamps: Vec<u64> =
+                    frame.runs.iter().map(|(_, query)| result(*query)).collect();
+                self.runs = frame
+                    .runs
+                    .iter()
+                    .zip(timestamps.windows(2))
+                    .filter_map(|((run, _), pair)| {
+                        run.map(|(material, layer)| TimedRun {
+                            material,
+                            layer,
+                            nanoseconds: pair[1].saturating_sub(pair[0]),
+                        })
+                    })
+                    .collect();
+            }
+            if !frame.scopes.is_empty() {
+                let scopes = frame
+                    .scopes
+                    .iter()
+                    .map(|(name, query)| GpuScope {
+                        name: name.clone(),
+                        nanoseconds: result(*query),
+                    })
+                    .collect();
+                latest = Some(scopes);
+            }
+            self.free.extend(frame.queries());
+        }
+
+        latest
+    }
+}
+
+impl Drop for GpuTimer {
+    fn drop(&mut self) {
+        let queries: Vec<GLuint> = self
+            .free
+            .drain(..)
+            .chain(self.open.take().map(|(_, query)| query))
+            .chain(std::mem::take(&mut self.frame).queries())
+            .chain(self.in_flight.drain(..).flat_map(TimerFrame::queries))
+            .collect();
+
+        if !queries.is_empty() {
+            unsafe { gl::DeleteQueries(queries.len() as i32, queries.as_ptr()) };
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use gl::types::GLuint;
+
+/// Frames whose uniform data can be in flight at once.
+const FRAMES: usize = 3;
+
+/// Uniform buffers the [uniform data][crate::Renderer::alloc_uniforms] of the frames is uploaded
+/// to, one per frame in flight. Every frame writes into the buffer the oldest frame used, so the
+/// upload doesn't wait for the draws of the last frame.
+#[derive(Debug, Default)]
+pub(super) struct UniformRing {
+    buffers: [GLuint; FRAMES],
+    /// Allocated size of each buffer.
+    sizes: [usize; FRAMES],
+    current: usize,
+}
+
+impl UniformRing {
+    /// Uploads the data of the frame into the next buffer and returns it.
+    pub(super) fn upload(&mut self, data: &[u8]) -> GLuint {
+        self.current = (self.current + 1) % FRAMES;
+        let buffer = &mut self.buffers[self.current];
+        if *buffer == 0 {
+            unsafe { gl::GenBuffers(1, buffer) };
+        }
+
+        let size = data.len() as isize;
+        let ptr = data.as_ptr() as *const std::ffi::c_void;
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, *buffer);
+            if data.len() > self.sizes[self.current] {
+                gl::BufferData(gl::UNIFORM_BUFFER, size, ptr, gl::STREAM_DRAW);
+                self.sizes[self.current] = data.len();
+            } else {
+                gl::BufferSubData(gl::UNIFORM_BUFFER, 0, size, ptr);
+            }
+        }
+        *buffer
+    }
+}
+
+impl Drop for UniformRing {
+    fn drop(&mut self) {
+        for buffer in self.buffers.iter().filter(|buffer| **buffer > 0) {
+            unsafe { gl::DeleteBuffers(1, buffer) };
+        }
+    }
+}
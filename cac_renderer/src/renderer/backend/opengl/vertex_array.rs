@@ -2,10 +2,10 @@ use gl::types::{GLenum, GLint, GLuint};
 
 use crate::{
     renderer::{Context, CreateVertexLayout},
-    Renderer, RendererError, VertexAttribute,
+    Renderer, RendererError, VertexAttribute, VertexAttributeKind, VertexLayoutConfig,
 };
 
-use super::buffer::GLBuffer;
+use super::{buffer::GLBuffer, DebugLabel};
 
 impl CreateVertexLayout for Vao {
     type Buffer = GLBuffer;
@@ -21,20 +21,33 @@ impl CreateVertexLayout for Vao {
         buffer: &Self::Buffer,
         attributes: &[VertexAttribute],
         offset: usize,
+        config: &VertexLayoutConfig,
     ) -> Result<(), RendererError> {
-        self.set_buffer_attributes(buffer, attributes, offset)?;
+        self.set_buffer_attributes(buffer, attributes, offset, config)?;
         Ok(())
     }
+
+    fn attributes(&self) -> &[(u8, VertexAttributeKind)] {
+        &self.attributes
+    }
 }
 
 impl VertexAttribute {
     fn as_gl_enum(&self) -> GLenum {
         use crate::VertexAttributeKind::*;
-        match self.semantic.kind() {
+        match self.kind {
             F32 => gl::FLOAT,
             Vec2 => gl::FLOAT,
             Vec3 => gl::FLOAT,
             Vec4 => gl::FLOAT,
+            I16x2 => gl::SHORT,
+            I16x4 => gl::SHORT,
+            U16x2 => gl::UNSIGNED_SHORT,
+            U16x4 => gl::UNSIGNED_SHORT,
+            U8x4 => gl::UNSIGNED_BYTE,
+            F16x2 => gl::HALF_FLOAT,
+            F16x4 => gl::HALF_FLOAT,
+            Int2_10_10_10 => gl::INT_2_10_10_10_REV,
         }
     }
 }
@@ -45,6 +58,7 @@ static mut MAX_ATTRIBUTES: Option<GLint> = None;
 pub struct Vao {
     id: GLuint,
     pub(super) has_indices: bool,
+    attributes: Vec<(u8, VertexAttributeKind)>,
 }
 
 impl Vao {
@@ -56,6 +70,7 @@ impl Vao {
                 vao
             },
             has_indices: false,
+            attributes: Vec::new(),
         }
     }
 
@@ -64,6 +79,7 @@ impl Vao {
         buffer: &GLBuffer,
         attributes: &[VertexAttribute],
         offset: usize,
+        config: &VertexLayoutConfig,
     ) -> Result<(), RendererError> {
         let mut max_attributes = 0;
         unsafe {
@@ -83,7 +99,7 @@ impl Vao {
         buffer.bind();
 
         for attr in attributes.iter() {
-            if let Some(location) = attr.semantic.location() {
+            if let Some(location) = config.location(attr.semantic) {
                 if location as i32 >= max_attributes {
                     return Err(RendererError::AttributeLocationOverMax {
                         location,
@@ -91,17 +107,30 @@ impl Vao {
                         max: max_attributes as u8,
                     });
                 }
+                self.attributes.push((location, attr.kind));
                 let offset = offset + attr.offset;
+                let pointer = offset as *const usize as *const std::ffi::c_void;
                 unsafe {
                     gl::EnableVertexAttribArray(location.into());
-                    gl::VertexAttribPointer(
-                        location.into(),
-                        attr.semantic.kind().components().into(),
-                        attr.as_gl_enum(),
-                        if attr.normalized { gl::TRUE } else { gl::FALSE },
-                        attr.stride as GLint,
-                        offset as *const usize as *const std::ffi::c_void,
-                    )
+                    // integers that aren't normalized are read as integers, not converted
+                    if attr.kind.is_integer() && !attr.normalized {
+                        gl::VertexAttribIPointer(
+                            location.into(),
+                            attr.kind.components().into(),
+                            attr.as_gl_enum(),
+                            attr.stride as GLint,
+                            pointer,
+                        )
+                    } else {
+                        gl::VertexAttribPointer(
+                            location.into(),
+                            attr.kind.components().into(),
+                            attr.as_gl_enum(),
+                            if attr.normalized { gl::TRUE } else { gl::FALSE },
+                            attr.stride as GLint,
+                            pointer,
+                        )
+                    }
                 }
             } else {
                 return Err(RendererError::AttributeHasNoLocation {
@@ -120,6 +149,12 @@ impl Vao {
     }
 }
 
+impl DebugLabel for Vao {
+    fn set_label(&mut self, label: &str) {
+        super::object_label(gl::VERTEX_ARRAY, self.id, label);
+    }
+}
+
 impl Drop for Vao {
     fn drop(&mut self) {
         if self.id > 0 {
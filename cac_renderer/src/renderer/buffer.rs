@@ -1,7 +1,15 @@
-use crate::{AttributeSemantic, Handle, Renderer, RendererError, VertexAttribute};
+use std::ops::Range;
+
+use crate::{
+    AttributeSemantic, Handle, Renderer, RendererError, VertexAttribute, VertexAttributeKind,
+};
 
 use super::Context;
 
+pub(crate) fn as_bytes<T>(data: &[T]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data)) }
+}
+
 pub trait BufferStorage<C: Context> {
     fn new_vertex<T>(
         &mut self,
@@ -19,8 +27,58 @@ pub trait BufferStorage<C: Context> {
 }
 
 pub trait CreateBuffer: Sized {
-    fn with_vertex<T>(data: &[T], usage: BufferUsage) -> Result<Self, RendererError>;
-    fn with_index<T>(data: &[T], usage: BufferUsage) -> Result<Self, RendererError>;
+    type Context;
+
+    fn with_vertex<T>(
+        ctx: &mut Self::Context,
+        data: &[T],
+        usage: BufferUsage,
+    ) -> Result<Self, RendererError>;
+    fn with_index<T>(
+        ctx: &mut Self::Context,
+        data: &[T],
+        usage: BufferUsage,
+    ) -> Result<Self, RendererError>;
+
+    /// Replaces the content of the buffer, keeping its kind.
+    fn set_data<T>(
+        &mut self,
+        ctx: &mut Self::Context,
+        data: &[T],
+        usage: BufferUsage,
+    ) -> Result<(), RendererError>;
+
+    /// Size of the buffer in bytes.
+    fn size(&self) -> usize;
+
+    fn usage(&self) -> BufferUsage;
+
+    /// Replaces the bytes of the buffer starting at the offset, which the caller checked to be
+    /// inside of it.
+    fn update<T>(
+        &mut self,
+        ctx: &mut Self::Context,
+        offset: usize,
+        data: &[T],
+    ) -> Result<(), RendererError>;
+
+    /// Replaces the storage of the buffer with size bytes of undefined content.
+    fn orphan(&mut self, ctx: &mut Self::Context, size: usize) -> Result<(), RendererError>;
+
+    /// Reads the bytes of the range, which the caller checked to be inside of the buffer.
+    fn read(&self, ctx: &mut Self::Context, range: Range<usize>) -> Result<Vec<u8>, RendererError>;
+
+    /// Vertex buffer of size bytes for a [StreamBuffer][super::StreamBuffer], which is written
+    /// with [update][Self::update].
+    fn with_stream(ctx: &mut Self::Context, size: usize) -> Result<Self, RendererError>;
+
+    /// Called before the first write of a frame into the region of a stream buffer. Waits until
+    /// the draws that read the region in an earlier frame are done.
+    fn begin_stream_region(
+        &mut self,
+        ctx: &mut Self::Context,
+        region: usize,
+    ) -> Result<(), RendererError>;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -32,8 +90,10 @@ impl Buffer {
         data: &[T],
         usage: BufferUsage,
     ) -> Result<Handle<Buffer>, RendererError> {
-        let buffer = C::Buffer::with_vertex(data, usage)?;
-        Ok(ctx.buffers.push(buffer))
+        let buffer = C::Buffer::with_vertex(&mut ctx.context, data, usage)?;
+        let handle = ctx.buffers.push(buffer);
+        ctx.retention.retain_buffer(handle, data, usage, false);
+        Ok(handle)
     }
 
     pub fn with_index<T, C: Context>(
@@ -41,8 +101,110 @@ impl Buffer {
         data: &[T],
         usage: BufferUsage,
     ) -> Result<Handle<Buffer>, RendererError> {
-        let buffer = C::Buffer::with_index(data, usage)?;
-        Ok(ctx.buffers.push(buffer))
+        let buffer = C::Buffer::with_index(&mut ctx.context, data, usage)?;
+        let handle = ctx.buffers.push(buffer);
+        ctx.retention.retain_buffer(handle, data, usage, true);
+        Ok(handle)
+    }
+
+    /// Names the buffer in graphics debuggers, see [DebugLabel][super::DebugLabel].
+    pub fn set_label<C: Context>(
+        ctx: &mut Renderer<C>,
+        buffer: Handle<Self>,
+        label: &str,
+    ) -> Result<(), RendererError> {
+        super::set_debug_label(&mut ctx.buffers, buffer, label, "Buffer")
+    }
+}
+
+impl<C: Context> Renderer<C> {
+    /// Replaces the bytes of the buffer starting at the byte offset with the data, without
+    /// reallocating it. The data has to fit in the buffer.
+    ///
+    /// Draws are only issued by [update][super::Backend::update], so all draws of the frame see
+    /// the content the buffer has at that point.
+    pub fn update_buffer<T>(
+        &mut self,
+        buffer: Handle<Buffer>,
+        offset: usize,
+        data: &[T],
+    ) -> Result<(), RendererError> {
+        let stored = self
+            .buffers
+            .get_mut(buffer)
+            .ok_or(RendererError::ResourceNotFound {
+                resource: format!("Buffer: {buffer:?}"),
+            })?;
+
+        let size = std::mem::size_of_val(data);
+        let buffer_size = stored.size();
+        if offset.checked_add(size).is_none_or(|end| end > buffer_size) {
+            return Err(RendererError::FailedToUpdateBuffer {
+                error: format!(
+                    "{size} bytes at offset {offset} don't fit in the {buffer_size} bytes of \
+                     {buffer:?}"
+                ),
+            });
+        }
+
+        stored.update(&mut self.context, offset, data)?;
+        self.retention.update_buffer(buffer, offset, data);
+        Ok(())
+    }
+
+    /// Replaces the storage of the buffer with new_size bytes of undefined content, to fill it
+    /// again with [update_buffer][Self::update_buffer]. The graphics device keeps the old storage
+    /// until the draws that use it are done, so streamed buffers like sprite batches can be
+    /// refilled every frame without waiting for them.
+    pub fn orphan_buffer(
+        &mut self,
+        buffer: Handle<Buffer>,
+        new_size: usize,
+    ) -> Result<(), RendererError> {
+        self.buffers
+            .get_mut(buffer)
+            .ok_or(RendererError::ResourceNotFound {
+                resource: format!("Buffer: {buffer:?}"),
+            })?
+            .orphan(&mut self.context, new_size)?;
+        self.retention.orphan_buffer(buffer, new_size);
+        Ok(())
+    }
+
+    /// Reads the bytes of the range back from the buffer, e.g. the results of a
+    /// [compute program][super::ComputeProgram] or transform feedback. Only buffers with a
+    /// [readable][BufferUsage::is_readable] usage can be read.
+    ///
+    /// The read waits until the graphics device finished the commands that write the buffer, so
+    /// reading right after the [update][super::Backend::update] that issued them stalls.
+    pub fn read_buffer(
+        &mut self,
+        buffer: Handle<Buffer>,
+        range: Range<usize>,
+    ) -> Result<Vec<u8>, RendererError> {
+        let stored = self
+            .buffers
+            .get(buffer)
+            .ok_or(RendererError::ResourceNotFound {
+                resource: format!("Buffer: {buffer:?}"),
+            })?;
+
+        let usage = stored.usage();
+        if !usage.is_readable() {
+            return Err(RendererError::FailedToReadBuffer {
+                error: format!("{buffer:?} has the usage {usage:?}, which doesn't permit reads"),
+            });
+        }
+        let buffer_size = stored.size();
+        if range.start > range.end || range.end > buffer_size {
+            return Err(RendererError::FailedToReadBuffer {
+                error: format!(
+                    "The range {range:?} isn't inside of the {buffer_size} bytes of {buffer:?}"
+                ),
+            });
+        }
+
+        stored.read(&mut self.context, range)
     }
 }
 
@@ -64,6 +226,22 @@ pub enum BufferUsage {
     StreamingCopy,
 }
 
+impl BufferUsage {
+    /// Whether the content of buffers with the usage can be read back, see
+    /// [read_buffer][Renderer::read_buffer].
+    pub fn is_readable(&self) -> bool {
+        matches!(
+            self,
+            BufferUsage::StaticRead
+                | BufferUsage::StaticReadWrite
+                | BufferUsage::DynamicRead
+                | BufferUsage::DynamicReadWrite
+                | BufferUsage::StreamingRead
+                | BufferUsage::StreamingReadWrite
+        )
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum BufferData<'a> {
     VertexF32(&'a [f32]),
@@ -72,6 +250,7 @@ pub enum BufferData<'a> {
     IndexU32(&'a [u32]),
 }
 
+#[derive(Clone)]
 pub struct BufferAttributes {
     pub buffer: Handle<Buffer>,
     pub attributes: Vec<VertexAttribute>,
@@ -103,6 +282,7 @@ impl BufferAttributes {
                 let attr = VertexAttribute {
                     stride,
                     semantic: *semantic,
+                    kind: semantic.kind(),
                     normalized: semantic.normalized(),
                     offset,
                 };
@@ -117,4 +297,41 @@ impl BufferAttributes {
             offset: buffer_offset,
         }
     }
+
+    /// Like [with_semantics][Self::with_semantics], but with an explicit kind per semantic
+    /// instead of its default one, e.g. to use
+    /// [quantized][crate::quantization] attributes.
+    ///
+    /// Integer kinds are [normalized][VertexAttributeKind::normalized], except for
+    /// [Joints][AttributeSemantic::Joints], which are read as integers.
+    pub fn with_kinds(
+        buffer: Handle<Buffer>,
+        buffer_offset: usize,
+        semantics: &[(AttributeSemantic, VertexAttributeKind)],
+    ) -> Self {
+        let stride = semantics.iter().map(|(_, kind)| kind.size()).sum();
+
+        let mut offset = 0;
+        let attributes = semantics
+            .iter()
+            .map(|(semantic, kind)| -> VertexAttribute {
+                let attr = VertexAttribute {
+                    stride,
+                    semantic: *semantic,
+                    kind: *kind,
+                    normalized: kind.normalized()
+                        && !matches!(semantic, AttributeSemantic::Joints(_)),
+                    offset,
+                };
+                offset += kind.size();
+                attr
+            })
+            .collect();
+
+        BufferAttributes {
+            buffer,
+            attributes,
+            offset: buffer_offset,
+        }
+    }
 }
@@ -0,0 +1,501 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use super::DynRenderer;
+#[cfg(all(feature = "headless", not(feature = "opengl")))]
+use super::HeadlessContext;
+#[cfg(feature = "opengl")]
+use super::OpenGLContext;
+#[cfg(any(feature = "opengl", feature = "headless"))]
+use super::Renderer;
+use crate::RendererError;
+
+/// Backend the [RendererBuilder] tries to create a renderer with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BackendKind {
+    /// OpenGL Core context with the version.
+    OpenGL((u8, u8)),
+    /// Renderer without a graphics device, e.g. for CI machines without GPU.
+    Headless,
+}
+
+/// The backends that were tried to create a renderer, see
+/// [creation_report][crate::Renderer::creation_report].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CreationReport {
+    /// The backend the renderer was created with.
+    pub backend: BackendKind,
+    /// Backends that were tried before it, with the error they failed with.
+    pub failures: Vec<(BackendKind, String)>,
+}
+
+impl CreationReport {
+    pub(crate) fn new(backend: BackendKind) -> Self {
+        Self {
+            backend,
+            failures: Vec::new(),
+        }
+    }
+
+    /// Returns true if the renderer wasn't created with the preferred backend.
+    pub fn fell_back(&self) -> bool {
+        !self.failures.is_empty()
+    }
+}
+
+impl std::fmt::Display for CreationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Created with {:?}", self.backend)?;
+        for (backend, error) in &self.failures {
+            write!(f, "\n  {backend:?} failed: {error}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Configures the graphics context before creating a [Renderer][crate::Renderer].
+///
+/// By default, the backends are tried in this order, skipping those whose feature is disabled:
+///
+/// | Backend       | Feature    | Falls back when                                    |
+/// |---------------|------------|----------------------------------------------------|
+/// | OpenGL 4.5    | `opengl`   | the driver has no 4.5 core context                 |
+/// | OpenGL 3.3    | `opengl`   | the driver has no 3.3 core context, e.g. no GPU    |
+/// | Headless      | `headless` | never, it doesn't need a graphics device           |
+///
+/// [build_opengl][Self::build_opengl] only tries the OpenGL versions. The
+/// [creation_report][crate::Renderer::creation_report] of the renderer lists why the preferred
+/// backends failed, and if no backend works, so does the
+/// [NoAvailableBackend][RendererError::NoAvailableBackend] error.
+/// ```no_run
+/// # use cac_renderer::*;
+/// # fn build(window: &impl raw_window_handle::HasRawWindowHandle) -> Result<(), RendererError> {
+/// let renderer = Renderer::builder()
+///     .backend_preference([BackendKind::OpenGL((4, 5)), BackendKind::Headless])
+///     .gl_version((4, 3))
+///     .vsync(true)
+///     .msaa(4)
+///     .debug(true)
+///     .build(window)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RendererBuilder {
+    /// Backends in the order they are tried.
+    pub backends: Vec<BackendKind>,
+    pub depth_bits: u8,
+    pub stencil_bits: u8,
+    /// Samples of the screen's multisampling, 0 or 1 disables it.
+    pub samples: u8,
+    /// Converts the linear output of the shaders to sRGB when rendering to the screen.
+    pub srgb: bool,
+    /// Waits for the vertical blank when swapping, to limit the frame rate to the refresh rate.
+    pub vsync: bool,
+    /// Logs the messages of the driver, if the context supports debug output (GL 4.3).
+    pub debug: bool,
+}
+
+impl Default for RendererBuilder {
+    fn default() -> Self {
+        Self {
+            backends: vec![
+                BackendKind::OpenGL((4, 5)),
+                BackendKind::OpenGL((3, 3)),
+                BackendKind::Headless,
+            ],
+            depth_bits: 24,
+            stencil_bits: 8,
+            samples: 0,
+            srgb: false,
+            vsync: false,
+            debug: cfg!(debug_assertions),
+        }
+    }
+}
+
+impl RendererBuilder {
+    /// Prefers OpenGL 4.5 and falls back to 3.3 and then headless, with debug output in debug
+    /// builds.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[deprecated(note = "use `backend_preference`")]
+    pub fn with_backends(self, backends: &[BackendKind]) -> Self {
+        self.backend_preference(backends.iter().copied())
+    }
+
+    /// The backends in the order they are tried, see [backends][Self::backends].
+    pub fn backend_preference(mut self, backends: impl IntoIterator<Item = BackendKind>) -> Self {
+        self.backends = backends.into_iter().collect();
+        self
+    }
+
+    /// Only tries this OpenGL version, in place of the first OpenGL backend of the preference.
+    pub fn gl_version(mut self, version: (u8, u8)) -> Self {
+        let position = self
+            .backends
+            .iter()
+            .position(|backend| matches!(backend, BackendKind::OpenGL(_)))
+            .unwrap_or(0);
+        self.backends
+            .retain(|backend| !matches!(backend, BackendKind::OpenGL(_)));
+        self.backends.insert(position, BackendKind::OpenGL(version));
+        self
+    }
+
+    /// See [vsync][Self::vsync].
+    pub fn vsync(mut self, vsync: bool) -> Self {
+        self.vsync = vsync;
+        self
+    }
+
+    /// Samples of the screen's multisampling, see [samples][Self::samples].
+    pub fn msaa(mut self, samples: u8) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    /// See [debug][Self::debug].
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    pub fn depth_bits(mut self, bits: u8) -> Self {
+        self.depth_bits = bits;
+        self
+    }
+
+    pub fn stencil_bits(mut self, bits: u8) -> Self {
+        self.stencil_bits = bits;
+        self
+    }
+
+    /// See [srgb][Self::srgb].
+    pub fn srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    #[deprecated(note = "use `depth_bits`")]
+    pub fn with_depth_bits(self, bits: u8) -> Self {
+        self.depth_bits(bits)
+    }
+
+    #[deprecated(note = "use `stencil_bits`")]
+    pub fn with_stencil_bits(self, bits: u8) -> Self {
+        self.stencil_bits(bits)
+    }
+
+    #[deprecated(note = "use `msaa`")]
+    pub fn with_samples(self, samples: u8) -> Self {
+        self.msaa(samples)
+    }
+
+    #[deprecated(note = "use `srgb`")]
+    pub fn with_srgb(self, srgb: bool) -> Self {
+        self.srgb(srgb)
+    }
+
+    #[deprecated(note = "use `vsync`")]
+    pub fn with_vsync(self, vsync: bool) -> Self {
+        self.vsync(vsync)
+    }
+
+    #[deprecated(note = "use `debug`")]
+    pub fn with_debug(self, debug: bool) -> Self {
+        self.debug(debug)
+    }
+
+    /// Creates the renderer with the first of the [backends][Self::backends] that works.
+    /// Backends whose features are disabled are skipped.
+    pub fn build(
+        &self,
+        window: &impl raw_window_handle::HasRawWindowHandle,
+    ) -> Result<DynRenderer, RendererError> {
+        let mut pending = self.build_pending();
+        loop {
+            if let Poll::Ready(result) = pending.poll(window) {
+                return result;
+            }
+        }
+    }
+
+    /// Same as [build][Self::build], but tries only one backend per
+    /// [poll][PendingRenderer::poll], e.g. once per iteration of the event loop.
+    pub fn build_pending(&self) -> PendingRenderer {
+        PendingRenderer {
+            builder: self.clone(),
+            next: 0,
+            failures: Vec::new(),
+        }
+    }
+
+    /// Same as [build][Self::build], but yields to the executor after every backend that failed.
+    pub fn build_async<'a, W: raw_window_handle::HasRawWindowHandle>(
+        &self,
+        window: &'a W,
+    ) -> RendererFuture<'a, W> {
+        RendererFuture {
+            pending: self.build_pending(),
+            window,
+        }
+    }
+
+    fn create(
+        &self,
+        backend: BackendKind,
+        #[allow(unused_variables)] window: &impl raw_window_handle::HasRawWindowHandle,
+    ) -> Result<DynRenderer, RendererError> {
+        match backend {
+            #[cfg(feature = "opengl")]
+            BackendKind::OpenGL(version) => {
+                Renderer::with_config(window, version, self).map(DynRenderer::OpenGL)
+            }
+            #[cfg(feature = "headless")]
+            BackendKind::Headless => Renderer::new_headless().map(DynRenderer::Headless),
+            #[allow(unreachable_patterns)]
+            _ => Err(RendererError::UnsupportedFeature {
+                feature: format!("{backend:?} isn't enabled by the features"),
+            }),
+        }
+    }
+
+    /// Creates an OpenGL renderer with the first OpenGL version of the
+    /// [backends][Self::backends] that works.
+    #[cfg(feature = "opengl")]
+    pub fn build_opengl(
+        &self,
+        window: &impl raw_window_handle::HasRawWindowHandle,
+    ) -> Result<Renderer<OpenGLContext>, RendererError> {
+        let versions = self.backends.iter().filter_map(|backend| match backend {
+            BackendKind::OpenGL(version) => Some(*version),
+            _ => None,
+        });
+
+        let mut failures = Vec::new();
+        for version in versions {
+            match Renderer::with_config(window, version, self) {
+                Ok(mut renderer) => {
+                    renderer.creation_report.failures = failures;
+                    return Ok(renderer);
+                }
+                Err(error) => {
+                    log::warn!("Couldn't create OpenGL {version:?}: {error}");
+                    failures.push((BackendKind::OpenGL(version), error.to_string()));
+                }
+            }
+        }
+
+        Err(RendererError::NoAvailableBackend { failures })
+    }
+}
+
+/// Renderer that is created over several calls, for platforms where the event loop must not be
+/// blocked by trying all backends at once. See [build_pending][RendererBuilder::build_pending].
+/// ```no_run
+/// # use cac_renderer::*;
+/// # fn run(window: &impl raw_window_handle::HasRawWindowHandle) {
+/// let mut pending = RendererBuilder::new().build_pending();
+/// let mut renderer = None;
+/// // in the event loop
+/// if renderer.is_none() {
+///     if let std::task::Poll::Ready(result) = pending.poll(window) {
+///         renderer = Some(result);
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct PendingRenderer {
+    builder: RendererBuilder,
+    /// Index of the next backend to try.
+    next: usize,
+    failures: Vec<(BackendKind, String)>,
+}
+
+impl PendingRenderer {
+    /// Tries the next backend. Ready with the renderer once one of them works, or with the
+    /// [NoAvailableBackend][RendererError::NoAvailableBackend] error once all of them failed.
+    pub fn poll(
+        &mut self,
+        window: &impl raw_window_handle::HasRawWindowHandle,
+    ) -> Poll<Result<DynRenderer, RendererError>> {
+        if let Some(backend) = self.builder.backends.get(self.next).copied() {
+            self.next += 1;
+            match self.builder.create(backend, window) {
+                #[allow(unused_mut)]
+                Ok(mut renderer) => {
+                    let failures = std::mem::take(&mut self.failures);
+                    if !failures.is_empty() {
+                        log::info!("Fell back to {backend:?}");
+                    }
+                    crate::with_renderer!(&mut renderer, |ctx| {
+                        ctx.creation_report.failures = failures
+                    });
+                    return Poll::Ready(Ok(renderer));
+                }
+                Err(error) => {
+                    log::warn!("Couldn't create {backend:?}: {error}");
+                    self.failures.push((backend, error.to_string()));
+                }
+            }
+        }
+
+        if self.next < self.builder.backends.len() {
+            Poll::Pending
+        } else {
+            Poll::Ready(Err(RendererError::NoAvailableBackend {
+                failures: std::mem::take(&mut self.failures),
+            }))
+        }
+    }
+}
+
+/// [PendingRenderer] as future, see [build_async][RendererBuilder::build_async].
+#[derive(Debug)]
+pub struct RendererFuture<'a, W> {
+    pending: PendingRenderer,
+    window: &'a W,
+}
+
+impl<W: raw_window_handle::HasRawWindowHandle> Future for RendererFuture<'_, W> {
+    type Output = Result<DynRenderer, RendererError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let poll = this.pending.poll(this.window);
+        if poll.is_pending() {
+            // the next backend can be tried right away
+            cx.waker().wake_by_ref();
+        }
+        poll
+    }
+}
+
+/// The untyped entry point, same as [DynRenderer::builder]. It's only defined for one backend,
+/// so `Renderer::builder()` doesn't need the type of the context, which the builder picks at
+/// runtime.
+#[cfg(feature = "opengl")]
+impl Renderer<OpenGLContext> {
+    pub fn builder() -> RendererBuilder {
+        RendererBuilder::new()
+    }
+}
+
+#[cfg(all(feature = "headless", not(feature = "opengl")))]
+impl Renderer<HeadlessContext> {
+    pub fn builder() -> RendererBuilder {
+        RendererBuilder::new()
+    }
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod test {
+    use crate::renderer::test_window::NoWindow;
+
+    use super::*;
+
+    #[test]
+    fn backend_order() {
+        let builder = RendererBuilder::new().backend_preference([BackendKind::Headless]);
+        assert!(matches!(
+            builder.build(&NoWindow),
+            Ok(DynRenderer::Headless(_))
+        ));
+
+        let renderer = builder.build(&NoWindow).unwrap();
+        assert_eq!(
+            renderer.creation_report(),
+            &CreationReport::new(BackendKind::Headless)
+        );
+
+        let builder = builder.backend_preference([]);
+        assert!(matches!(
+            builder.build(&NoWindow),
+            Err(RendererError::NoAvailableBackend { failures }) if failures.is_empty()
+        ));
+    }
+
+    #[test]
+    fn options() {
+        let builder = RendererBuilder::new()
+            .depth_bits(32)
+            .stencil_bits(0)
+            .msaa(4)
+            .srgb(true)
+            .vsync(true)
+            .debug(false);
+
+        assert_eq!(builder.backends, RendererBuilder::default().backends);
+        assert_eq!(
+            (builder.depth_bits, builder.stencil_bits, builder.samples),
+            (32, 0, 4)
+        );
+        assert!(builder.srgb && builder.vsync && !builder.debug);
+
+        assert_eq!(Renderer::builder(), DynRenderer::builder());
+        let builder = Renderer::builder()
+            .backend_preference([
+                BackendKind::Headless,
+                BackendKind::OpenGL((4, 5)),
+                BackendKind::OpenGL((3, 3)),
+            ])
+            .gl_version((4, 1))
+            .vsync(false)
+            .msaa(8)
+            .debug(true);
+        assert_eq!(
+            builder.backends,
+            [BackendKind::Headless, BackendKind::OpenGL((4, 1))]
+        );
+        assert!(builder.samples == 8 && !builder.vsync && builder.debug);
+    }
+
+    #[test]
+    fn creation_report() {
+        let mut report = CreationReport::new(BackendKind::OpenGL((3, 3)));
+        assert!(!report.fell_back());
+
+        report
+            .failures
+            .push((BackendKind::OpenGL((4, 5)), "no 4.5 context".to_string()));
+        assert!(report.fell_back());
+        assert_eq!(
+            report.to_string(),
+            "Created with OpenGL((3, 3))\n  OpenGL((4, 5)) failed: no 4.5 context"
+        );
+    }
+
+    #[test]
+    fn pending() {
+        let builder = RendererBuilder::new().backend_preference([BackendKind::Headless]);
+        let mut pending = builder.build_pending();
+        assert!(matches!(
+            pending.poll(&NoWindow),
+            Poll::Ready(Ok(DynRenderer::Headless(_)))
+        ));
+
+        let mut pending = builder.backend_preference([]).build_pending();
+        assert!(matches!(
+            pending.poll(&NoWindow),
+            Poll::Ready(Err(RendererError::NoAvailableBackend { .. }))
+        ));
+    }
+
+    #[test]
+    fn future() {
+        let builder = RendererBuilder::new().backend_preference([BackendKind::Headless]);
+        let mut future = std::pin::pin!(builder.build_async(&NoWindow));
+        let mut cx = Context::from_waker(std::task::Waker::noop());
+        assert!(matches!(
+            future.as_mut().poll(&mut cx),
+            Poll::Ready(Ok(DynRenderer::Headless(_)))
+        ));
+    }
+}
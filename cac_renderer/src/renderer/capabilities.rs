@@ -0,0 +1,72 @@
+/// Limits and optional features of the graphics context, see
+/// [capabilities][super::Backend::capabilities]. They are queried once when the context is
+/// created.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Largest width and height of 2D textures.
+    pub max_texture_size: u32,
+    /// Largest size of the faces of cubemaps.
+    pub max_cubemap_size: u32,
+    /// Largest number of layers of array textures, the faces of cubemap arrays included.
+    pub max_array_layers: u32,
+    pub max_vertex_attribs: u32,
+    /// Textures that can be bound at once, across all shader stages.
+    pub max_texture_units: u32,
+    /// Most samples of multisampled render targets and textures.
+    pub max_samples: u32,
+    /// Compute programs and [dispatch][super::Backend::dispatch]es, OpenGL 4.3.
+    pub supports_compute: bool,
+    /// Direct state access, OpenGL 4.5.
+    pub supports_dsa: bool,
+    /// Tessellation shaders and patches, OpenGL 4.0.
+    pub supports_tessellation: bool,
+    /// [Texture views][super::Texture::view], OpenGL 4.3.
+    pub supports_texture_views: bool,
+    /// [Indirect draws][super::Backend::draw_indirect], OpenGL 4.3.
+    pub supports_multi_draw_indirect: bool,
+    /// Alignment of the offsets of the [uniform data][crate::Renderer::alloc_uniforms] that is
+    /// bound to uniform blocks.
+    pub uniform_buffer_alignment: u32,
+    /// Features the context lacks, which the shaders emulate.
+    pub emulated: EmulatedFeatures,
+    /// Names of the extensions of the driver, e.g. `GL_ARB_bindless_texture`.
+    pub extensions: Vec<String>,
+}
+
+/// Features the renderer emulates on contexts that don't have them, see
+/// [Capabilities::emulated].
+///
+/// Every shader can `#include "cac_emulation.glsl"` and use its helpers, which only do work on
+/// the contexts that lack the feature, so the same shaders run everywhere. The include checks the
+/// `CAC_EMULATE_*` defines the [preprocessor][super::ShaderPreprocessor] sets for the emulated
+/// features.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EmulatedFeatures {
+    /// The default framebuffer has linear colors, so
+    /// [srgb encoding][super::RenderTarget::set_srgb_encode] of the screen target has no effect.
+    /// `vec4 cac_encode_output(vec4 color)` encodes the colors of shaders that draw into the
+    /// screen into sRGB instead. Sets `CAC_EMULATE_SRGB_FRAMEBUFFER`.
+    pub srgb_framebuffer: bool,
+}
+
+impl Capabilities {
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.iter().any(|extension| extension == name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn has_extension() {
+        let capabilities = Capabilities {
+            extensions: vec!["GL_ARB_bindless_texture".to_string()],
+            ..Default::default()
+        };
+
+        assert!(capabilities.has_extension("GL_ARB_bindless_texture"));
+        assert!(!capabilities.has_extension("GL_ARB_bindless"));
+    }
+}
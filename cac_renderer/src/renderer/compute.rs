@@ -0,0 +1,135 @@
+use std::fmt::Display;
+
+use crate::{Handle, Renderer, RendererError};
+
+use super::{Context, Shader};
+
+/// Memory that needs to be synchronized after a compute dispatch wrote to it, before it is read
+/// by later dispatches or draws. See [memory_barrier][super::Backend::memory_barrier].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MemoryBarriers(u16);
+
+impl MemoryBarriers {
+    pub const NONE: Self = Self(0x00);
+    /// Vertex buffers read by draws.
+    pub const VERTEX_BUFFER: Self = Self(0x01);
+    /// Index buffers read by draws.
+    pub const INDEX_BUFFER: Self = Self(0x02);
+    pub const UNIFORM_BUFFER: Self = Self(0x04);
+    /// Textures sampled by shaders.
+    pub const TEXTURE_FETCH: Self = Self(0x08);
+    /// Images loaded from or stored to by shaders.
+    pub const IMAGE_ACCESS: Self = Self(0x10);
+    pub const STORAGE_BUFFER: Self = Self(0x20);
+    /// Buffers read or written by the CPU, e.g. when updating their data.
+    pub const BUFFER_UPDATE: Self = Self(0x40);
+    pub const ALL: Self = Self(0x7f);
+}
+
+impl std::ops::BitAnd for MemoryBarriers {
+    type Output = bool;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        MemoryBarriers(self.0 & rhs.0).0 > 0
+    }
+}
+
+impl std::ops::BitOr for MemoryBarriers {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        MemoryBarriers(self.0 | rhs.0)
+    }
+}
+
+impl Display for MemoryBarriers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = [
+            (Self::VERTEX_BUFFER, "VERTEX_BUFFER"),
+            (Self::INDEX_BUFFER, "INDEX_BUFFER"),
+            (Self::UNIFORM_BUFFER, "UNIFORM_BUFFER"),
+            (Self::TEXTURE_FETCH, "TEXTURE_FETCH"),
+            (Self::IMAGE_ACCESS, "IMAGE_ACCESS"),
+            (Self::STORAGE_BUFFER, "STORAGE_BUFFER"),
+            (Self::BUFFER_UPDATE, "BUFFER_UPDATE"),
+        ];
+
+        write!(f, "MemoryBarriers {{ ")?;
+        for (barrier, name) in names {
+            if *self & barrier {
+                write!(f, "{name} ")?;
+            }
+        }
+        write!(f, "}}")
+    }
+}
+
+/// A program that only consists of a compute shader. It runs outside of the graphics pipeline
+/// with [dispatch][super::Backend::dispatch].
+///
+/// Requires OpenGL 4.3 or newer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ComputeProgram {}
+
+pub trait CreateComputeProgram: Sized {
+    type ComputeShader;
+
+    fn new(compute_shader: &Self::ComputeShader) -> Result<Self, RendererError>;
+}
+
+impl ComputeProgram {
+    pub fn new<C: Context>(
+        ctx: &mut Renderer<C>,
+        compute: Handle<Shader>,
+    ) -> Result<Handle<Self>, RendererError> {
+        let compute_shader = ctx
+            .shaders
+            .get(compute)
+            .ok_or(RendererError::ResourceNotFound {
+                resource: "compute shader".to_string(),
+            })?;
+
+        let program = C::ComputeProgram::new(compute_shader)?;
+
+        let handle = ctx.compute_programs.push(program);
+        ctx.retention.retain_compute_program(handle, compute);
+        Ok(handle)
+    }
+
+    pub fn from_source<C: Context>(
+        ctx: &mut Renderer<C>,
+        compute_shader: &str,
+    ) -> Result<Handle<Self>, RendererError> {
+        let compute_shader = Shader::with_compute(ctx, compute_shader)?;
+        let program = Self::new(ctx, compute_shader);
+        ctx.shaders.remove(compute_shader);
+
+        program
+    }
+
+    /// Names the compute program in graphics debuggers, see [DebugLabel][super::DebugLabel].
+    pub fn set_label<C: Context>(
+        ctx: &mut Renderer<C>,
+        program: Handle<Self>,
+        label: &str,
+    ) -> Result<(), RendererError> {
+        super::set_debug_label(&mut ctx.compute_programs, program, label, "ComputeProgram")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn barrier_flags() {
+        let barriers = MemoryBarriers::VERTEX_BUFFER | MemoryBarriers::STORAGE_BUFFER;
+        assert!(barriers & MemoryBarriers::STORAGE_BUFFER);
+        assert!(!(barriers & MemoryBarriers::INDEX_BUFFER));
+        assert!(MemoryBarriers::ALL & MemoryBarriers::BUFFER_UPDATE);
+        assert_eq!(
+            barriers.to_string(),
+            "MemoryBarriers { VERTEX_BUFFER STORAGE_BUFFER }"
+        );
+    }
+}
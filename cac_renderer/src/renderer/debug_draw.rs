@@ -0,0 +1,264 @@
+use crate::{
+    math::{Mat4, Vec3},
+    Color32, Handle, Renderer, RendererError,
+};
+
+use super::{
+    AttributeSemantic, Backend, BufferAttributes, Context, Material, MaterialProperty, Mesh,
+    Primitive, RenderQueue, ShaderProgram, StreamBuffer, VertexAttributeKind, VertexLayout,
+    VertexLayoutConfig,
+};
+
+const DEBUG_DRAW_FS_SOURCE: &str = r##"
+    #version 330 core
+    in vec4 frag_color;
+    out vec4 result;
+    void main() { result = frag_color; }"##;
+
+/// Line segments of each of the three circles of a [sphere][DebugDraw::sphere].
+pub const SPHERE_SEGMENTS: usize = 16;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(C)]
+struct LineVertex {
+    position: Vec3,
+    color: [f32; 4],
+}
+
+const VERTEX_SIZE: usize = std::mem::size_of::<LineVertex>();
+
+/// Immediate-mode lines for debugging physics and gameplay, e.g. colliders, rays or paths.
+///
+/// The shapes are added every frame, and [update][Self::update] draws all of them with a single
+/// draw call from its [StreamBuffer] and clears them:
+///
+/// ```no_run
+/// # use cac_renderer::{math::*, Backend, Color32, DebugDraw, Renderer};
+/// # fn frame<C: cac_renderer::Context>(renderer: &mut Renderer<C>, debug: &mut DebugDraw, view_projection: Mat4)
+/// # where Renderer<C>: Backend {
+/// debug.line(Vec3::ZERO, Vec3::Y, Color32::GREEN);
+/// debug.aabb(vec3(-1.0, 0.0, -1.0), vec3(1.0, 2.0, 1.0), Color32::WHITE);
+/// debug.sphere(vec3(0.0, 1.0, 0.0), 0.5, Color32::RED);
+/// debug.set_view_projection(view_projection);
+/// debug.update(renderer).unwrap();
+/// renderer.update();
+/// # }
+/// ```
+///
+/// The lines are unlit and drawn in the [Overlay][RenderQueue::Overlay] queue.
+#[derive(Debug)]
+pub struct DebugDraw {
+    capacity: usize,
+    vertices: StreamBuffer,
+    vertex_layout: Handle<VertexLayout>,
+    material: Handle<Material>,
+    view_projection: Mat4,
+    /// Two vertices per line since the last update.
+    lines: Vec<LineVertex>,
+}
+
+impl DebugDraw {
+    /// Debug draw of up to capacity lines per frame.
+    pub fn new<C: Context>(ctx: &mut Renderer<C>, capacity: usize) -> Result<Self, RendererError> {
+        let capacity = capacity.max(1);
+        let vertex_source = vertex_source(ctx.layout_config())?;
+        let program = ShaderProgram::from_sources(ctx, &vertex_source, DEBUG_DRAW_FS_SOURCE)?;
+        let material = ctx.create_material(program, &[])?;
+        ctx.set_render_queue(material, RenderQueue::Overlay);
+
+        let vertices = StreamBuffer::new(ctx, capacity * 2 * VERTEX_SIZE)?;
+        let vertex_layout = VertexLayout::new(
+            ctx,
+            &[BufferAttributes::with_kinds(
+                vertices.buffer(),
+                0,
+                &[
+                    (AttributeSemantic::Position, VertexAttributeKind::Vec3),
+                    (AttributeSemantic::Color(0), VertexAttributeKind::Vec4),
+                ],
+            )],
+        )?;
+
+        Ok(Self {
+            capacity,
+            vertices,
+            vertex_layout,
+            material,
+            view_projection: Mat4::IDENTITY,
+            lines: Vec::new(),
+        })
+    }
+
+    /// Lines that can be drawn per frame.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of lines added since the last update.
+    pub fn len(&self) -> usize {
+        self.lines.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Transforms the world space positions of the shapes into clip space. The identity by
+    /// default.
+    pub fn set_view_projection(&mut self, view_projection: Mat4) {
+        self.view_projection = view_projection;
+    }
+
+    pub fn view_projection(&self) -> Mat4 {
+        self.view_projection
+    }
+
+    pub fn line(&mut self, a: Vec3, b: Vec3, color: Color32) {
+        let (red, green, blue, alpha) = color.as_rgba();
+        let color = [red, green, blue, alpha];
+        self.lines.extend([
+            LineVertex { position: a, color },
+            LineVertex { position: b, color },
+        ]);
+    }
+
+    /// The twelve edges of the axis-aligned box between the corners.
+    pub fn aabb(&mut self, min: Vec3, max: Vec3, color: Color32) {
+        let corner = |index: usize| {
+            Vec3::new(
+                if index & 1 == 0 { min.x } else { max.x },
+                if index & 2 == 0 { min.y } else { max.y },
+                if index & 4 == 0 { min.z } else { max.z },
+            )
+        };
+        // every corner connects to the ones that differ in a single axis
+        for index in 0..8 {
+            for axis in [1, 2, 4] {
+                if index & axis == 0 {
+                    self.line(corner(index), corner(index | axis), color);
+                }
+            }
+        }
+    }
+
+    /// Circles around the center in the XY, XZ and YZ planes, with [SPHERE_SEGMENTS] lines each.
+    pub fn sphere(&mut self, center: Vec3, radius: f32, color: Color32) {
+        let point = |segment: usize| {
+            let angle = segment as f32 / SPHERE_SEGMENTS as f32 * std::f32::consts::TAU;
+            (angle.cos() * radius, angle.sin() * radius)
+        };
+        for segment in 0..SPHERE_SEGMENTS {
+            let ((x0, y0), (x1, y1)) = (point(segment), point(segment + 1));
+            for (a, b) in [
+                (Vec3::new(x0, y0, 0.0), Vec3::new(x1, y1, 0.0)),
+                (Vec3::new(x0, 0.0, y0), Vec3::new(x1, 0.0, y1)),
+                (Vec3::new(0.0, x0, y0), Vec3::new(0.0, x1, y1)),
+            ] {
+                self.line(center + a, center + b, color);
+            }
+        }
+    }
+
+    /// Draws the lines added since the last update with one draw call, and clears them.
+    ///
+    /// Can be called once per frame. Fails if more than [capacity][Self::capacity] lines were
+    /// added, the lines are dropped either way.
+    pub fn update<C: Context>(&mut self, renderer: &mut Renderer<C>) -> Result<(), RendererError>
+    where
+        Renderer<C>: Backend,
+    {
+        if self.lines.is_empty() {
+            return Ok(());
+        }
+
+        let slice = self.vertices.push(renderer, &self.lines);
+        self.lines.clear();
+        let slice = slice?;
+        let mesh = Mesh::new(
+            self.vertex_layout,
+            slice.count(VERTEX_SIZE),
+            Primitive::Lines,
+        )
+        .with_vertex_offset(slice.first_element(VERTEX_SIZE));
+        renderer.draw(
+            mesh,
+            self.material,
+            &[MaterialProperty::new(
+                "view_projection",
+                &self.view_projection,
+            )],
+        );
+        Ok(())
+    }
+}
+
+fn vertex_source(config: &VertexLayoutConfig) -> Result<String, RendererError> {
+    let location = |semantic: AttributeSemantic| {
+        config
+            .location(semantic)
+            .ok_or(RendererError::AttributeHasNoLocation { semantic })
+    };
+    let position = location(AttributeSemantic::Position)?;
+    let color = location(AttributeSemantic::Color(0))?;
+
+    Ok(format!(
+        r##"
+    #version 330 core
+    layout(location = {position}) in vec3 pos;
+    layout(location = {color}) in vec4 color;
+    uniform mat4 view_projection;
+    out vec4 frag_color;
+    void main() {{
+        frag_color = color;
+        gl_Position = view_projection * vec4(pos, 1.0);
+    }}"##
+    ))
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod test {
+    use super::*;
+    use crate::RecordedCommand;
+
+    #[test]
+    fn draw_shapes() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mut debug = DebugDraw::new(&mut renderer, 64).unwrap();
+
+        debug.line(Vec3::ZERO, Vec3::X, Color32::WHITE);
+        debug.aabb(Vec3::ZERO, Vec3::ONE, Color32::WHITE);
+        assert_eq!(debug.len(), 13);
+        // every edge of the unit box has a length of one
+        for edge in debug.lines[2..].chunks(2) {
+            assert_eq!(edge[0].position.distance(edge[1].position), 1.0);
+        }
+        debug.sphere(Vec3::ONE, 2.0, Color32::RED);
+        assert_eq!(debug.len(), 13 + 3 * SPHERE_SEGMENTS);
+        assert!(debug.lines[26..]
+            .iter()
+            .all(|vertex| (vertex.position.distance(Vec3::ONE) - 2.0).abs() < 1e-5));
+
+        renderer.clear_recorded_commands();
+        debug.update(&mut renderer).unwrap();
+        assert!(debug.is_empty());
+        renderer.update();
+        let meshes: Vec<_> = renderer
+            .recorded_commands()
+            .iter()
+            .filter_map(|command| match command {
+                RecordedCommand::Draw { mesh, .. } => Some(*mesh),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].count as usize, 2 * (13 + 3 * SPHERE_SEGMENTS));
+        assert_eq!(meshes[0].primitive, Primitive::Lines);
+
+        // more lines than the capacity
+        for _ in 0..65 {
+            debug.line(Vec3::ZERO, Vec3::X, Color32::WHITE);
+        }
+        assert!(debug.update(&mut renderer).is_err());
+        assert!(debug.is_empty());
+    }
+}
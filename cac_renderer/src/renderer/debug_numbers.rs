@@ -0,0 +1,213 @@
+use crate::{math::Vec2, Color32, Handle, Renderer, RendererError};
+
+use super::{
+    AttributeSemantic, Backend, Buffer, BufferAttributes, BufferUsage, Context, Material,
+    MaterialProperty, Mesh, Primitive, RenderQueue, ShaderProgram, VertexLayout,
+};
+
+/// Most glyphs one number can have, longer numbers are cut off.
+pub const MAX_DEBUG_GLYPHS: usize = 16;
+
+/// 3x5 bitmaps of the digits, '.', '-' and a blank, one bit per pixel, row by row from the
+/// top left pixel in the highest bit.
+#[rustfmt::skip]
+const FONT: [u16; 13] = [
+    0b111_101_101_101_111, 0b010_110_010_010_111, 0b111_001_111_100_111, 0b111_001_111_001_111,
+    0b101_101_111_001_001, 0b111_100_111_001_111, 0b111_100_111_101_111, 0b111_001_001_001_001,
+    0b111_101_111_101_111, 0b111_101_111_001_111, 0b000_000_000_000_010, 0b000_000_111_000_000,
+    0,
+];
+pub(crate) const DOT: f32 = 10.0;
+const MINUS: f32 = 11.0;
+const BLANK: f32 = 12.0;
+
+const NUMBERS_VS_SOURCE: &str = r##"
+    #version 330 core
+    layout(location = 0) in vec3 pos;
+    uniform vec4 rect;
+    out vec2 uv;
+    void main() {
+        uv = pos.xy;
+        gl_Position = vec4(rect.x + pos.x * rect.z, rect.y - (1.0 - pos.y) * rect.w, 0.0, 1.0);
+    }"##;
+
+#[rustfmt::skip]
+const QUAD_VERTICES: [f32; 12] = [
+    0.0, 0.0, 0.0,
+    1.0, 0.0, 0.0,
+    1.0, 1.0, 0.0,
+    0.0, 1.0, 0.0,
+];
+
+/// Draws numbers with a bitmap font that is baked into the fragment program, for frame stats and
+/// other counters in test apps that don't have any text rendering, see
+/// [draw][DebugNumbers::draw].
+///
+/// ```no_run
+/// # use cac_renderer::{math::*, Color32, DebugNumbers, Renderer};
+/// # fn frame<C: cac_renderer::Context>(renderer: &mut Renderer<C>, numbers: &DebugNumbers)
+/// # where Renderer<C>: cac_renderer::Backend {
+/// let draw_calls = renderer.frame_stats().draw_calls;
+/// numbers.draw(renderer, draw_calls as f64, 0, vec2(-1.0, 1.0), vec2(0.04, 0.08), Color32::WHITE);
+/// # }
+/// ```
+///
+/// The glyphs are drawn in the [Overlay][RenderQueue::Overlay] queue, but replaced like any other
+/// material while a [DebugView][super::DebugView] is set.
+#[derive(Debug, Clone, Copy)]
+pub struct DebugNumbers {
+    mesh: Mesh,
+    material: Handle<Material>,
+}
+
+impl DebugNumbers {
+    pub fn new<C: Context>(ctx: &mut Renderer<C>) -> Result<Self, RendererError> {
+        let program = ShaderProgram::from_sources(ctx, NUMBERS_VS_SOURCE, &fragment_source())?;
+        let material = ctx.create_material(program, &[])?;
+        ctx.set_render_queue(material, RenderQueue::Overlay);
+
+        let vertex_buffer = Buffer::with_vertex(ctx, &QUAD_VERTICES, BufferUsage::StaticRead)?;
+        let vertex_layout = VertexLayout::new(
+            ctx,
+            &[BufferAttributes::with_semantics(
+                vertex_buffer,
+                0,
+                &[AttributeSemantic::Position],
+            )],
+        )?;
+
+        Ok(Self {
+            mesh: Mesh::new(vertex_layout, 4, Primitive::TriangleFan),
+            material,
+        })
+    }
+
+    /// Draws the value with the number of decimals, with the top left corner of the first glyph
+    /// at the position. Position and glyph size are in clip space, the size includes a pixel of
+    /// spacing to the right and below the 3x5 pixels of the glyph.
+    pub fn draw(
+        &self,
+        renderer: &mut impl Backend,
+        value: f64,
+        decimals: usize,
+        position: Vec2,
+        glyph_size: Vec2,
+        color: Color32,
+    ) {
+        let (glyphs, count) = glyphs(value, decimals);
+        let rect = [
+            position.x,
+            position.y,
+            glyph_size.x * count as f32,
+            glyph_size.y,
+        ];
+        let count = count as f32;
+        let properties = [
+            MaterialProperty::new("rect", &rect),
+            MaterialProperty::new("glyphs", &glyphs),
+            MaterialProperty::new("glyph_count", &count),
+            MaterialProperty::new("color", &color),
+        ];
+        renderer.draw(self.mesh, self.material, &properties);
+    }
+}
+
+/// Whether the pixel of the glyph in the column and row, counted from the top left, is set.
+pub(crate) fn font_pixel(glyph: usize, column: u32, row: u32) -> bool {
+    column < 3
+        && row < 5
+        && FONT
+            .get(glyph)
+            .is_some_and(|bits| bits >> (14 - row * 3 - column) & 1 == 1)
+}
+
+/// Indices into the font of the formatted value, and how many there are. Values that aren't
+/// finite are drawn as dashes.
+pub(crate) fn glyphs(value: f64, decimals: usize) -> ([f32; MAX_DEBUG_GLYPHS], usize) {
+    let text = match value.is_finite() {
+        true => format!("{value:.decimals$}"),
+        false => "---".to_string(),
+    };
+
+    let mut glyphs = [BLANK; MAX_DEBUG_GLYPHS];
+    let mut count = 0;
+    for (glyph, char) in glyphs.iter_mut().zip(text.chars()) {
+        *glyph = match char {
+            '.' => DOT,
+            '-' => MINUS,
+            char => char.to_digit(10).map_or(BLANK, |digit| digit as f32),
+        };
+        count += 1;
+    }
+    (glyphs, count)
+}
+
+fn fragment_source() -> String {
+    let font = FONT.map(|bits| bits.to_string()).join(", ");
+    format!(
+        r##"
+    #version 330 core
+    in vec2 uv;
+    out vec4 result;
+    uniform float glyphs[{MAX_DEBUG_GLYPHS}];
+    uniform float glyph_count;
+    uniform vec4 color;
+    const int FONT[{}] = int[{}]({font});
+    void main() {{
+        float x = uv.x * glyph_count;
+        int glyph = int(glyphs[clamp(int(x), 0, {})]);
+        // 3x5 pixels with a column and a row of spacing
+        int column = int(fract(x) * 4.0);
+        int row = int((1.0 - uv.y) * 6.0);
+        if (column > 2 || row > 4 || ((FONT[glyph] >> (14 - row * 3 - column)) & 1) == 0) {{
+            discard;
+        }}
+        result = color;
+    }}"##,
+        FONT.len(),
+        FONT.len(),
+        MAX_DEBUG_GLYPHS - 1,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn format_glyphs() {
+        let (negative, count) = glyphs(-12.5, 1);
+        assert_eq!(count, 5);
+        assert_eq!(negative[..6], [MINUS, 1.0, 2.0, DOT, 5.0, BLANK]);
+
+        assert_eq!(glyphs(f64::NAN, 0).0[..3], [MINUS; 3]);
+        // cut off after the last glyph that fits
+        assert_eq!(glyphs(1e20, 0).1, MAX_DEBUG_GLYPHS);
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn draw_numbers() {
+        use crate::RecordedCommand;
+
+        let mut renderer = Renderer::new_headless().unwrap();
+        let numbers = DebugNumbers::new(&mut renderer).unwrap();
+        renderer.clear_recorded_commands();
+        numbers.draw(
+            &mut renderer,
+            42.0,
+            0,
+            Vec2::new(-1.0, 1.0),
+            Vec2::new(0.1, 0.1),
+            Color32::WHITE,
+        );
+        renderer.update();
+
+        let draws = renderer
+            .recorded_commands()
+            .iter()
+            .filter(|command| matches!(command, RecordedCommand::Draw { .. }))
+            .count();
+        assert_eq!(draws, 1);
+    }
+}
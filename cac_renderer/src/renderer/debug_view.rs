@@ -0,0 +1,305 @@
+use crate::{Handle, Renderer, RendererError};
+
+use super::{
+    frame_arena::{InstanceData, InstanceValue},
+    texture::CreateTexture,
+    AttributeSemantic, Context, Material, MaterialProperty, Mesh, PropertyId, ShaderProgram,
+    Uniform, UniformDescription, UniformKind, VertexLayoutConfig,
+};
+
+/// Built-in materials that replace the materials of all draws, to diagnose asset and shading
+/// problems, see [set_debug_view][Renderer::set_debug_view].
+///
+/// The debug programs transform the positions with `uniform mat4 model;` and
+/// `uniform mat4 view_projection;`. They take the values of the uniforms with these names from
+/// the replaced material and the per-draw overrides, and use the identity otherwise.
+/// Meshes without the attribute of the view are skipped.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum DebugView {
+    /// Draws with the materials of the draws.
+    #[default]
+    Off,
+    /// World space [Normals(0)][AttributeSemantic::Normals], mapped into colors.
+    Normals,
+    /// World space [Tangent][AttributeSemantic::Tangent]s, mapped into colors.
+    Tangents,
+    /// Checker pattern of the [UV(0)][AttributeSemantic::UV] coordinates, to spot stretching and
+    /// seams. The red and green tint are the coordinates.
+    UvChecker,
+    /// The [Color(0)][AttributeSemantic::Color] attribute.
+    VertexColor,
+    /// Every draw adds the same dim color, so areas that are drawn more often get brighter.
+    Overdraw,
+    /// Tints the mip level the first texture of the material is sampled at. Level 0 is blue, 1
+    /// green, 2 yellow and everything smaller red.
+    MipLevel,
+}
+
+const DEBUG_FS_SOURCE: &str = r##"
+    #version 330 core
+    in vec4 value;
+    out vec4 result;
+    void main() { result = value; }"##;
+
+const UV_CHECKER_FS_SOURCE: &str = r##"
+    #version 330 core
+    in vec4 value;
+    out vec4 result;
+    void main() {
+        vec2 cell = floor(value.xy * 8.0);
+        float checker = mod(cell.x + cell.y, 2.0);
+        vec3 tint = vec3(0.5 + 0.5 * fract(value.xy), 1.0);
+        result = vec4(mix(0.2, 0.8, checker) * tint, 1.0);
+    }"##;
+
+const MIP_LEVEL_FS_SOURCE: &str = r##"
+    #version 330 core
+    in vec4 value;
+    out vec4 result;
+    uniform vec2 texture_size;
+    const vec3 LEVELS[4] = vec3[4](
+        vec3(0.0, 0.0, 1.0),
+        vec3(0.0, 1.0, 0.0),
+        vec3(1.0, 1.0, 0.0),
+        vec3(1.0, 0.0, 0.0)
+    );
+    void main() {
+        vec2 dx = dFdx(value.xy * texture_size);
+        vec2 dy = dFdy(value.xy * texture_size);
+        float lod = 0.5 * log2(max(dot(dx, dx), dot(dy, dy)));
+        result = vec4(LEVELS[clamp(int(lod), 0, 3)], 1.0);
+    }"##;
+
+#[rustfmt::skip]
+const IDENTITY: [f32; 16] = [
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 1.0, 0.0,
+    0.0, 0.0, 0.0, 1.0,
+];
+
+/// Uniforms of the replaced materials that the debug programs take over.
+const TRANSFORMS: [&str; 2] = ["model", "view_projection"];
+
+impl DebugView {
+    /// Vertex shader that reads the attribute of the view, if any, into `value`.
+    fn vertex_source(&self, config: &VertexLayoutConfig) -> Result<String, RendererError> {
+        let input = |semantic: AttributeSemantic, declaration: &str| {
+            config
+                .location(semantic)
+                .map(|location| format!("layout(location = {location}) in {declaration};"))
+                .ok_or(RendererError::AttributeHasNoLocation { semantic })
+        };
+
+        let (input, value) = match self {
+            DebugView::Off => unreachable!("there is no debug material without a debug view"),
+            DebugView::Normals => (
+                input(AttributeSemantic::Normals(0), "vec3 normal")?,
+                "vec4(normalize(mat3(model) * normal) * 0.5 + 0.5, 1.0)",
+            ),
+            DebugView::Tangents => (
+                input(AttributeSemantic::Tangent, "vec4 tangent")?,
+                "vec4(normalize(mat3(model) * tangent.xyz) * 0.5 + 0.5, 1.0)",
+            ),
+            DebugView::UvChecker | DebugView::MipLevel => (
+                input(AttributeSemantic::UV(0), "vec2 uv")?,
+                "vec4(uv, 0.0, 0.0)",
+            ),
+            DebugView::VertexColor => (input(AttributeSemantic::Color(0), "vec4 color")?, "color"),
+            DebugView::Overdraw => (String::new(), "vec4(0.1, 0.05, 0.02, 1.0)"),
+        };
+        let position = config.location(AttributeSemantic::Position).ok_or(
+            RendererError::AttributeHasNoLocation {
+                semantic: AttributeSemantic::Position,
+            },
+        )?;
+
+        Ok(format!(
+            r##"
+    #version 330 core
+    layout(location = {position}) in vec3 pos;
+    {input}
+    uniform mat4 model;
+    uniform mat4 view_projection;
+    out vec4 value;
+    void main() {{
+        value = {value};
+        gl_Position = view_projection * model * vec4(pos, 1.0);
+    }}"##
+        ))
+    }
+
+    fn fragment_source(&self) -> &'static str {
+        match self {
+            DebugView::UvChecker => UV_CHECKER_FS_SOURCE,
+            DebugView::MipLevel => MIP_LEVEL_FS_SOURCE,
+            _ => DEBUG_FS_SOURCE,
+        }
+    }
+}
+
+impl<C: Context> Renderer<C> {
+    /// Replaces the materials of all following draws with the material of the view, until it's
+    /// set to [DebugView::Off]. The material of a view is created the first time it's set.
+    pub fn set_debug_view(&mut self, view: DebugView) -> Result<(), RendererError> {
+        if view != DebugView::Off && !self.debug_materials.contains_key(&view) {
+            let material = self.create_debug_material(view, view.fragment_source())?;
+            self.debug_materials.insert(view, material);
+        }
+
+        self.debug_view = view;
+        Ok(())
+    }
+
+    pub fn debug_view(&self) -> DebugView {
+        self.debug_view
+    }
+
+    /// Material with the vertex shader of the view, which starts with identity transforms.
+    pub(crate) fn create_debug_material(
+        &mut self,
+        view: DebugView,
+        fragment_source: &str,
+    ) -> Result<Handle<Material>, RendererError> {
+        let vertex_source = view.vertex_source(&self.layout_config)?;
+        let program = ShaderProgram::from_sources(self, &vertex_source, fragment_source)?;
+        let transforms = TRANSFORMS.map(|name| MaterialProperty::new(name, &IDENTITY));
+        self.create_material(program, &transforms)
+    }
+
+    /// The material the draw is drawn with and its per-draw overrides. With a [DebugView], the
+    /// material of the view replaces the material, and takes over its transforms. Returns None if
+    /// the layout of the mesh doesn't fit the debug program.
+    pub(crate) fn draw_material(
+        &mut self,
+        mesh: &Mesh,
+        material: Handle<Material>,
+        properties: &[MaterialProperty],
+    ) -> Option<(Handle<Material>, InstanceData)> {
+        let Some(debug_material) = self.debug_materials.get(&self.debug_view).copied() else {
+            return Some((material, self.instance_data(material, properties)));
+        };
+        if !self.layout_fits(mesh.vertex_layout, debug_material) {
+            return None;
+        }
+
+        let uniforms = self.debug_uniforms(material, debug_material, properties);
+        let instance_data = self.frame_arena.alloc(
+            uniforms
+                .iter()
+                .map(|(location, values)| InstanceValue::Uniform(*location, values)),
+        );
+        Some((debug_material, instance_data))
+    }
+
+    /// Values of the debug program's uniforms that come from the replaced material and the
+    /// overrides of the draw.
+    fn debug_uniforms(
+        &self,
+        material: Handle<Material>,
+        debug_material: Handle<Material>,
+        properties: &[MaterialProperty],
+    ) -> Vec<(u32, Vec<f32>)> {
+        let (Some(material), Some(debug_program)) = (
+            self.materials.get(material),
+            self.materials
+                .get(debug_material)
+                .and_then(|debug_material| self.programs.get(debug_material.program)),
+        ) else {
+            return Vec::new();
+        };
+        let program = self.programs.get(material.program);
+
+        let mut uniforms = Vec::new();
+        for name in TRANSFORMS {
+            let Some(location) = debug_program.get_uniform_location(name) else {
+                continue;
+            };
+            let overridden = properties.iter().find_map(|prop| match prop.property {
+                PropertyId::Name(property) if property == name => prop.value.as_f32(),
+                _ => None,
+            });
+            let stored = program
+                .and_then(|program| transform_uniform(program.uniforms(), name))
+                .and_then(|uniform| stored_values(material, uniform));
+
+            match (overridden, stored) {
+                (Some(values), _) => uniforms.push((location, values.to_vec())),
+                (None, Some(values)) => uniforms.push((location, values)),
+                // the debug material has the identity
+                (None, None) => {}
+            }
+        }
+
+        if let Some(location) = debug_program.get_uniform_location("texture_size") {
+            let size = material
+                .textures
+                .first()
+                .and_then(|binding| self.textures.get(binding.texture))
+                .map_or((1, 1), |texture| texture.size());
+            uniforms.push((location, vec![size.0 as f32, size.1 as f32]));
+        }
+
+        uniforms
+    }
+
+    /// The transforms of the recorded draw, for a program that draws it again, like
+    /// [debug_uniforms][Self::debug_uniforms] does for the draws with a debug view.
+    #[cfg(feature = "opengl")]
+    pub(crate) fn command_transforms(
+        &self,
+        command: &super::DrawCommand,
+        program: Handle<ShaderProgram>,
+    ) -> Vec<(u32, Vec<f32>)> {
+        let (Some(material), Some(program)) = (
+            self.materials.get(command.material),
+            self.programs.get(program),
+        ) else {
+            return Vec::new();
+        };
+        let Some(drawn_program) = self.programs.get(material.program) else {
+            return Vec::new();
+        };
+
+        TRANSFORMS
+            .iter()
+            .filter_map(|name| {
+                let location = program.get_uniform_location(name)?;
+                let uniform = transform_uniform(drawn_program.uniforms(), name)?;
+                let values = self
+                    .frame_arena
+                    .get(command.instance_data)
+                    .find_map(|value| match value {
+                        InstanceValue::Uniform(location, values)
+                            if location == uniform.location =>
+                        {
+                            Some(values.to_vec())
+                        }
+                        _ => None,
+                    })
+                    .or_else(|| stored_values(material, uniform))?;
+                Some((location, values))
+            })
+            .collect()
+    }
+}
+
+fn transform_uniform<'a>(
+    uniforms: &'a [UniformDescription],
+    name: &str,
+) -> Option<&'a UniformDescription> {
+    uniforms
+        .iter()
+        .find(|uniform| uniform.name == name && uniform.kind == UniformKind::Mat4)
+}
+
+/// The values of the Mat4 uniform in the data of the material.
+fn stored_values(material: &Material, uniform: &UniformDescription) -> Option<Vec<f32>> {
+    let bytes = material.data.get(uniform.offset..uniform.offset + 64)?;
+    Some(
+        bytes
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect(),
+    )
+}
@@ -0,0 +1,348 @@
+use crate::Handle;
+
+use super::{frame_arena::InstanceData, Buffer, Material, Mesh, RenderQueue};
+
+/// Key to sort the draw commands by, so that commands sharing the same state end up next to each
+/// other and the backend can skip redundant binds.
+///
+/// From the most to the least significant bits:
+/// | layer: 8 | program: 12 | material: 16 | layout: 12 | depth: 16 |
+///
+/// Draws that need to be drawn back-to-front use
+/// | layer: 8 | inverted depth: 16 | program: 12 | material: 16 | layout: 12 |
+/// instead.
+///
+/// The layer groups draws that belong together, like the render target or
+/// [queue][super::RenderQueue] they are drawn in.
+///
+/// Program, material and layout are identified by their sort indices (see
+/// [Renderer::compact][crate::Renderer::compact]), so resources that are close in memory end up
+/// next to each other. Indices that don't fit into their bits are truncated, which only costs some
+/// sorting quality, but never correctness.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SortKey(u64);
+
+impl SortKey {
+    const LAYER_BITS: u32 = 8;
+    const PROGRAM_BITS: u32 = 12;
+    const MATERIAL_BITS: u32 = 16;
+    const LAYOUT_BITS: u32 = 12;
+    const DEPTH_BITS: u32 = 16;
+
+    /// Key for draws that are sorted by state first and front-to-back second.
+    pub fn new(layer: u8, program: usize, material: usize, layout: usize, depth: u16) -> Self {
+        let mut key = u64::from(layer);
+        key = Self::append(key, program as u64, Self::PROGRAM_BITS);
+        key = Self::append(key, material as u64, Self::MATERIAL_BITS);
+        key = Self::append(key, layout as u64, Self::LAYOUT_BITS);
+        key = Self::append(key, u64::from(depth), Self::DEPTH_BITS);
+
+        Self(key)
+    }
+
+    /// Key for draws that are sorted back-to-front first and by state second.
+    pub fn back_to_front(
+        layer: u8,
+        depth: u16,
+        program: usize,
+        material: usize,
+        layout: usize,
+    ) -> Self {
+        let mut key = u64::from(layer);
+        key = Self::append(key, u64::from(u16::MAX - depth), Self::DEPTH_BITS);
+        key = Self::append(key, program as u64, Self::PROGRAM_BITS);
+        key = Self::append(key, material as u64, Self::MATERIAL_BITS);
+        key = Self::append(key, layout as u64, Self::LAYOUT_BITS);
+
+        Self(key)
+    }
+
+    /// Key for draws that keep their submission order within the layer.
+    pub fn in_order(layer: u8) -> Self {
+        Self(u64::from(layer) << (u64::BITS - Self::LAYER_BITS))
+    }
+
+    /// Quantizes the distance to the camera into the 16 bits of the key.
+    ///
+    /// The upper bits of a positive f32 keep the order of the floats, so this works for any range,
+    /// with more precision closer to the camera. Negative depths are treated as 0.
+    pub fn quantize_depth(depth: f32) -> u16 {
+        (depth.max(0.0).to_bits() >> 16) as u16
+    }
+
+    fn append(key: u64, value: u64, bits: u32) -> u64 {
+        (key << bits) | (value & ((1 << bits) - 1))
+    }
+
+    /// The layer the draw belongs to.
+    pub fn layer(&self) -> u8 {
+        (self.0 >> (u64::BITS - Self::LAYER_BITS)) as u8
+    }
+}
+
+// all parts need to fit into the 64 bits.
+const _: () = assert!(
+    SortKey::LAYER_BITS
+        + SortKey::PROGRAM_BITS
+        + SortKey::MATERIAL_BITS
+        + SortKey::LAYOUT_BITS
+        + SortKey::DEPTH_BITS
+        == u64::BITS
+);
+
+/// A single recorded draw call.
+/// Instance data holds the per-draw property overrides, stored in the frame arena.
+#[derive(Debug, Clone)]
+pub struct DrawCommand {
+    pub(crate) sort_key: SortKey,
+    pub(crate) mesh: Mesh,
+    // the sort key only holds the quantized depth, the exact one orders draws with equal keys
+    pub(crate) depth: f32,
+    pub(crate) material: Handle<Material>,
+    pub(crate) instance_data: InstanceData,
+    /// Buffer of the [IndirectCommand][super::IndirectCommand]s of indirect draws.
+    pub(crate) indirect: Option<Handle<Buffer>>,
+    // submission order, so the sort can be unstable (which doesn't allocate) and still keep the
+    // order of equal keys
+    sequence: u32,
+}
+
+impl DrawCommand {
+    pub(crate) fn new(
+        sort_key: SortKey,
+        mesh: Mesh,
+        depth: f32,
+        material: Handle<Material>,
+        instance_data: InstanceData,
+    ) -> Self {
+        Self {
+            sort_key,
+            mesh,
+            depth,
+            material,
+            instance_data,
+            indirect: None,
+            sequence: 0,
+        }
+    }
+
+    pub(crate) fn with_indirect(mut self, buffer: Handle<Buffer>) -> Self {
+        self.indirect = Some(buffer);
+        self
+    }
+
+    /// Orders draws whose keys are equal, because their depths fall into the same quantization
+    /// step: front-to-back in the opaque queue and back-to-front in the transparent one.
+    /// The overlay queue keeps the submission order.
+    fn depth_order(&self) -> u32 {
+        // the bits of a positive f32 keep the order of the floats
+        let depth = self.depth.max(0.0).to_bits();
+        match self.sort_key.layer() {
+            layer if layer == RenderQueue::Opaque as u8 => depth,
+            layer if layer == RenderQueue::Transparent as u8 => u32::MAX - depth,
+            _ => 0,
+        }
+    }
+}
+
+/// Draw lists with at least this many commands are sorted on multiple threads, if the `parallel`
+/// feature is enabled. Below it, the overhead of the threads outweighs the gain.
+pub const PARALLEL_THRESHOLD: usize = 4096;
+
+/// Draws recorded independently of the renderer, e.g. one batch per worker thread, and submitted
+/// together with [draw_batches][super::Backend::draw_batches].
+///
+/// Batches don't support per-draw property overrides, since they need the material's program.
+#[derive(Debug, Default, Clone)]
+pub struct DrawBatch {
+    pub(crate) draws: Vec<(Mesh, Handle<Material>, f32)>,
+}
+
+impl DrawBatch {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            draws: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Records a draw of the mesh with the material at depth 0.
+    pub fn draw(&mut self, mesh: Mesh, material: Handle<Material>) {
+        self.draw_at_depth(mesh, material, 0.0);
+    }
+
+    /// Records a draw of the mesh with the material at the distance to the camera.
+    pub fn draw_at_depth(&mut self, mesh: Mesh, material: Handle<Material>, depth: f32) {
+        self.draws.push((mesh, material, depth));
+    }
+
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    /// Removes all draws, but keeps the allocated memory for the next frame.
+    pub fn clear(&mut self) {
+        self.draws.clear();
+    }
+}
+
+/// The draw calls of the current frame.
+/// Backends fill it in `draw()` and consume it in `update()`.
+#[derive(Debug, Default)]
+pub struct DrawList {
+    commands: Vec<DrawCommand>,
+}
+
+impl DrawList {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            commands: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(&mut self, mut command: DrawCommand) {
+        command.sequence = self.commands.len() as u32;
+        self.commands.push(command);
+    }
+
+    /// Appends the commands in their order, e.g. the ones of [DrawBatch]es.
+    pub fn extend(&mut self, commands: impl IntoIterator<Item = DrawCommand>) {
+        for command in commands {
+            self.push(command);
+        }
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, DrawCommand> {
+        self.commands.iter()
+    }
+
+    /// Sorts the commands by their [SortKey], and commands with the same key by their exact depth,
+    /// in the direction of their [queue][RenderQueue].
+    /// The sort is stable, so commands that are still equal keep their submission order.
+    ///
+    /// With the `parallel` feature, lists of at least [PARALLEL_THRESHOLD] commands are sorted on
+    /// the rayon thread pool.
+    pub fn sort(&mut self) {
+        #[cfg(feature = "parallel")]
+        if self.commands.len() >= PARALLEL_THRESHOLD {
+            use rayon::slice::ParallelSliceMut;
+
+            self.commands.par_sort_unstable_by_key(Self::order);
+            return;
+        }
+
+        self.commands.sort_unstable_by_key(Self::order);
+    }
+
+    fn order(command: &DrawCommand) -> (SortKey, u32, u32) {
+        (command.sort_key, command.depth_order(), command.sequence)
+    }
+
+    /// Removes all commands, but keeps the allocated memory for the next frame.
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn key_order() {
+        let key =
+            |program, material, layout, depth| SortKey::new(0, program, material, layout, depth);
+
+        // program changes are the most expensive ones
+        assert!(key(1, 5, 5, 5) < key(2, 0, 0, 0));
+        assert!(key(1, 1, 5, 5) < key(1, 2, 0, 0));
+        assert!(key(1, 1, 1, 5) < key(1, 1, 2, 0));
+        assert!(key(1, 1, 1, 1) < key(1, 1, 1, 2));
+
+        // the layer trumps everything else
+        let layer_key = SortKey::new(1, 0, 0, 0, 0);
+        assert!(key(4095, 65535, 4095, 65535) < layer_key);
+        assert_eq!(layer_key.layer(), 1);
+    }
+
+    #[test]
+    fn back_to_front() {
+        let key = |depth| SortKey::back_to_front(1, depth, 4, 4, 4);
+
+        assert!(key(10) < key(5));
+        assert!(key(10) < SortKey::back_to_front(1, 5, 0, 0, 0));
+        assert!(SortKey::in_order(0) < key(u16::MAX));
+        assert!(key(0) < SortKey::in_order(2));
+    }
+
+    #[test]
+    fn quantize_depth() {
+        let depths = [0.0, 0.001, 0.5, 1.0, 10.0, 1000.0, f32::MAX];
+
+        for pair in depths.windows(2) {
+            assert!(SortKey::quantize_depth(pair[0]) < SortKey::quantize_depth(pair[1]));
+        }
+        assert_eq!(SortKey::quantize_depth(-5.0), 0);
+    }
+
+    #[test]
+    fn stable_sort() {
+        let mesh = Mesh::new(Handle::new(), 3, super::super::Primitive::Triangles);
+
+        // large enough to take the parallel path, if enabled
+        let mut draw_list = DrawList::with_capacity(PARALLEL_THRESHOLD * 2);
+        draw_list.extend((0..PARALLEL_THRESHOLD * 2).map(|i| {
+            let key = SortKey::new(0, i % 3, 0, 0, 0);
+            DrawCommand::new(key, mesh, i as f32, Handle::new(), InstanceData::default())
+        }));
+        draw_list.sort();
+
+        assert_eq!(draw_list.commands.len(), PARALLEL_THRESHOLD * 2);
+        for pair in draw_list.commands.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            assert!(a.sort_key < b.sort_key || (a.sort_key == b.sort_key && a.depth < b.depth));
+        }
+    }
+
+    #[test]
+    fn exact_depth_order() {
+        let mesh = Mesh::new(Handle::new(), 3, super::super::Primitive::Triangles);
+        // all depths fall into the same quantization step
+        let depths = [1.0004, 1.0, 1.0002];
+        assert!(depths
+            .iter()
+            .all(|&depth| SortKey::quantize_depth(depth) == SortKey::quantize_depth(1.0)));
+
+        let sorted = |key: fn(u16) -> SortKey| {
+            let mut draw_list = DrawList::default();
+            draw_list.extend(depths.iter().map(|&depth| {
+                let key = key(SortKey::quantize_depth(depth));
+                DrawCommand::new(key, mesh, depth, Handle::new(), InstanceData::default())
+            }));
+            draw_list.sort();
+            draw_list
+                .iter()
+                .map(|command| command.depth)
+                .collect::<Vec<_>>()
+        };
+
+        let opaque = |depth| SortKey::new(RenderQueue::Opaque as u8, 1, 1, 1, depth);
+        assert_eq!(sorted(opaque), [1.0, 1.0002, 1.0004]);
+
+        let transparent =
+            |depth| SortKey::back_to_front(RenderQueue::Transparent as u8, depth, 1, 1, 1);
+        assert_eq!(sorted(transparent), [1.0004, 1.0002, 1.0]);
+
+        let overlay = |_| SortKey::in_order(RenderQueue::Overlay as u8);
+        assert_eq!(sorted(overlay), depths);
+    }
+
+    #[test]
+    fn truncate_index() {
+        let key = SortKey::new(0, 0, 0, 1 << 12, 0);
+        assert_eq!(key, SortKey::default());
+    }
+}
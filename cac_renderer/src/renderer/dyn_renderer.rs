@@ -0,0 +1,341 @@
+#[cfg(feature = "headless")]
+use super::HeadlessContext;
+#[cfg(feature = "opengl")]
+use super::OpenGLContext;
+#[cfg(any(feature = "opengl", feature = "headless"))]
+use super::Renderer;
+use super::{
+    AttributeDescription, AttributeSemantic, Backend, Buffer, Capabilities, CompressedFormat,
+    ComputeProgram, CreationReport, DebugView, DrawBatch, FrameStats, Image, Material,
+    MaterialProperty, MemoryBarriers, Mesh, PropertyValidation, RenderQueue, RenderTarget,
+    RendererBuilder, RendererFuture, Resource, ResourceId, ResourceKind, ShaderProgram,
+    UniformDescription, VertexLayout,
+};
+use crate::{Handle, RcHandle, RendererError};
+
+/// [Renderer] whose backend is picked at runtime, for applications that can't commit to one at
+/// compile time, e.g. to fall back to the headless backend on CI machines without a GPU.
+///
+/// It implements [Backend] and forwards the renderer methods that don't depend on the backend.
+/// Resources are created with the generic functions, on the renderer of the variant, which
+/// [with_renderer!][crate::with_renderer] matches. The handles don't depend on the backend, so
+/// they can be stored without it.
+/// ```no_run
+/// # use cac_renderer::*;
+/// # fn create(window: &impl raw_window_handle::HasRawWindowHandle) -> Result<(), RendererError> {
+/// let mut renderer = DynRenderer::new(window)?;
+/// let texture = with_renderer!(&mut renderer, |ctx| {
+///     Texture::new(ctx, &[255; 4], 1, 1, TextureFormat::Rgba8)
+/// })?;
+/// renderer.update();
+/// # Ok(())
+/// # }
+/// ```
+/// The generic [Renderer] stays the zero-cost path, without matching on the backend.
+// it's unpacked right after it's built, so the size difference doesn't matter
+#[allow(clippy::large_enum_variant)]
+pub enum DynRenderer {
+    #[cfg(feature = "opengl")]
+    OpenGL(Renderer<OpenGLContext>),
+    #[cfg(feature = "headless")]
+    Headless(Renderer<HeadlessContext>),
+}
+
+/// Runs the body with `ctx` bound to the [Renderer] of the [DynRenderer] variant.
+/// The body is compiled for every enabled backend.
+#[cfg(all(feature = "opengl", feature = "headless"))]
+#[macro_export]
+macro_rules! with_renderer {
+    ($renderer:expr, |$ctx:ident| $body:expr) => {
+        match $renderer {
+            $crate::DynRenderer::OpenGL($ctx) => $body,
+            $crate::DynRenderer::Headless($ctx) => $body,
+        }
+    };
+}
+
+/// Runs the body with `ctx` bound to the [Renderer] of the [DynRenderer] variant.
+/// The body is compiled for every enabled backend.
+#[cfg(all(feature = "opengl", not(feature = "headless")))]
+#[macro_export]
+macro_rules! with_renderer {
+    ($renderer:expr, |$ctx:ident| $body:expr) => {
+        match $renderer {
+            $crate::DynRenderer::OpenGL($ctx) => $body,
+        }
+    };
+}
+
+/// Runs the body with `ctx` bound to the [Renderer] of the [DynRenderer] variant.
+/// The body is compiled for every enabled backend.
+#[cfg(all(not(feature = "opengl"), feature = "headless"))]
+#[macro_export]
+macro_rules! with_renderer {
+    ($renderer:expr, |$ctx:ident| $body:expr) => {
+        match $renderer {
+            $crate::DynRenderer::Headless($ctx) => $body,
+        }
+    };
+}
+
+/// Without backends, there's no [DynRenderer] to run the body with.
+#[cfg(not(any(feature = "opengl", feature = "headless")))]
+#[macro_export]
+macro_rules! with_renderer {
+    ($renderer:expr, |$ctx:ident| $body:expr) => {
+        match *$renderer {}
+    };
+}
+
+impl DynRenderer {
+    /// Creates the renderer with the first backend that works, in the order of
+    /// [RendererBuilder::new].
+    pub fn new(window: &impl raw_window_handle::HasRawWindowHandle) -> Result<Self, RendererError> {
+        RendererBuilder::new().build(window)
+    }
+
+    /// Configures the backends and the graphics context before creating the renderer.
+    /// Use [build_opengl][RendererBuilder::build_opengl] for a [Renderer] of a fixed backend.
+    pub fn builder() -> RendererBuilder {
+        RendererBuilder::new()
+    }
+
+    /// Same as [new][Self::new], but yields to the executor between the backends, for event
+    /// loops that must not block. See [RendererBuilder::build_pending] to poll it manually.
+    pub fn new_async<W: raw_window_handle::HasRawWindowHandle>(
+        window: &W,
+    ) -> RendererFuture<'_, W> {
+        RendererBuilder::new().build_async(window)
+    }
+
+    pub fn create_material(
+        &mut self,
+        program: Handle<ShaderProgram>,
+        properties: &[MaterialProperty],
+    ) -> Result<Handle<Material>, RendererError> {
+        with_renderer!(self, |ctx| ctx.create_material(program, properties))
+    }
+
+    /// See [Renderer::clone_material].
+    pub fn clone_material(
+        &mut self,
+        template: Handle<Material>,
+        overrides: &[MaterialProperty],
+    ) -> Result<Handle<Material>, RendererError> {
+        with_renderer!(self, |ctx| ctx.clone_material(template, overrides))
+    }
+
+    pub fn update_material(
+        &mut self,
+        handle: Handle<Material>,
+        properties: &[MaterialProperty],
+    ) -> Result<(), RendererError> {
+        with_renderer!(self, |ctx| ctx.update_material(handle, properties))
+    }
+
+    /// See [Renderer::bind_attribute_name].
+    pub fn bind_attribute_name(&mut self, semantic: AttributeSemantic, name: &str) {
+        with_renderer!(self, |ctx| ctx.bind_attribute_name(semantic, name))
+    }
+
+    pub fn set_property_validation(&mut self, validation: PropertyValidation) {
+        with_renderer!(self, |ctx| ctx.set_property_validation(validation))
+    }
+
+    pub fn get_raw(&self, id: ResourceId) -> Option<&dyn std::any::Any> {
+        with_renderer!(self, |ctx| ctx.get_raw(id))
+    }
+
+    pub fn contains(&self, id: ResourceId) -> bool {
+        with_renderer!(self, |ctx| ctx.contains(id))
+    }
+
+    pub fn resource_ids(&self, kind: ResourceKind) -> Vec<ResourceId> {
+        with_renderer!(self, |ctx| ctx.resource_ids(kind))
+    }
+
+    pub fn share<T: Resource>(&mut self, handle: Handle<T>) -> Option<RcHandle<T>> {
+        with_renderer!(self, |ctx| ctx.share(handle))
+    }
+
+    pub fn collect_garbage(&mut self) -> usize {
+        with_renderer!(self, |ctx| ctx.collect_garbage())
+    }
+
+    pub fn release(&mut self, id: ResourceId) {
+        with_renderer!(self, |ctx| ctx.release(id))
+    }
+
+    pub fn set_debug_view(&mut self, view: DebugView) -> Result<(), RendererError> {
+        with_renderer!(self, |ctx| ctx.set_debug_view(view))
+    }
+
+    pub fn debug_view(&self) -> DebugView {
+        with_renderer!(self, |ctx| ctx.debug_view())
+    }
+
+    pub fn set_render_queue(&mut self, handle: Handle<Material>, queue: RenderQueue) {
+        with_renderer!(self, |ctx| ctx.set_render_queue(handle, queue))
+    }
+
+    /// See [Renderer::program_uniforms].
+    pub fn program_uniforms(&self, handle: Handle<ShaderProgram>) -> Option<&[UniformDescription]> {
+        with_renderer!(self, |ctx| ctx.program_uniforms(handle))
+    }
+
+    /// See [Renderer::program_attributes].
+    pub fn program_attributes(
+        &self,
+        handle: Handle<ShaderProgram>,
+    ) -> Option<&[AttributeDescription]> {
+        with_renderer!(self, |ctx| ctx.program_attributes(handle))
+    }
+
+    /// See [Renderer::frame_stats].
+    pub fn frame_stats(&self) -> &FrameStats {
+        with_renderer!(self, |ctx| ctx.frame_stats())
+    }
+
+    pub fn compact(&mut self) {
+        with_renderer!(self, |ctx| ctx.compact())
+    }
+
+    /// See [Renderer::creation_report].
+    pub fn creation_report(&self) -> &CreationReport {
+        with_renderer!(self, |ctx| ctx.creation_report())
+    }
+}
+
+impl Backend for DynRenderer {
+    fn context_description(&self) -> String {
+        with_renderer!(self, |ctx| ctx.context_description())
+    }
+
+    fn screen_target(&mut self) -> &mut dyn RenderTarget {
+        with_renderer!(self, |ctx| ctx.screen_target())
+    }
+
+    fn screenshot(&mut self) -> Image {
+        with_renderer!(self, |ctx| ctx.screenshot())
+    }
+
+    fn capabilities(&self) -> &Capabilities {
+        with_renderer!(self, |ctx| ctx.capabilities())
+    }
+
+    fn supports_compressed_format(&self, format: CompressedFormat) -> bool {
+        with_renderer!(self, |ctx| ctx.supports_compressed_format(format))
+    }
+
+    fn draw_at_depth(
+        &mut self,
+        mesh: Mesh,
+        material: Handle<Material>,
+        depth: f32,
+        instance_properties: &[MaterialProperty],
+    ) {
+        with_renderer!(self, |ctx| ctx.draw_at_depth(
+            mesh,
+            material,
+            depth,
+            instance_properties
+        ))
+    }
+
+    fn draw_indirect(
+        &mut self,
+        layout: Handle<VertexLayout>,
+        material: Handle<Material>,
+        indirect_buffer: Handle<Buffer>,
+        count: u32,
+    ) {
+        with_renderer!(self, |ctx| ctx.draw_indirect(
+            layout,
+            material,
+            indirect_buffer,
+            count
+        ))
+    }
+
+    fn draw_batches(&mut self, batches: &[DrawBatch]) {
+        with_renderer!(self, |ctx| ctx.draw_batches(batches))
+    }
+
+    fn dispatch(
+        &mut self,
+        program: Handle<ComputeProgram>,
+        x: u32,
+        y: u32,
+        z: u32,
+        properties: &[MaterialProperty],
+    ) -> Result<(), RendererError> {
+        with_renderer!(self, |ctx| ctx.dispatch(program, x, y, z, properties))
+    }
+
+    fn memory_barrier(&mut self, barriers: MemoryBarriers) {
+        with_renderer!(self, |ctx| ctx.memory_barrier(barriers))
+    }
+
+    fn begin_gpu_scope(&mut self, name: &str) {
+        with_renderer!(self, |ctx| ctx.begin_gpu_scope(name))
+    }
+
+    fn end_gpu_scope(&mut self) {
+        with_renderer!(self, |ctx| ctx.end_gpu_scope())
+    }
+
+    fn push_debug_group(&mut self, name: &str) {
+        with_renderer!(self, |ctx| ctx.push_debug_group(name))
+    }
+
+    fn pop_debug_group(&mut self) {
+        with_renderer!(self, |ctx| ctx.pop_debug_group())
+    }
+
+    fn set_overdraw_analysis(&mut self, enabled: bool) -> Result<(), RendererError> {
+        with_renderer!(self, |ctx| ctx.set_overdraw_analysis(enabled))
+    }
+
+    fn suspend(&mut self) {
+        with_renderer!(self, |ctx| ctx.suspend())
+    }
+
+    fn resume(
+        &mut self,
+        window: &impl raw_window_handle::HasRawWindowHandle,
+    ) -> Result<(), RendererError> {
+        with_renderer!(self, |ctx| ctx.resume(window))
+    }
+
+    fn is_suspended(&self) -> bool {
+        with_renderer!(self, |ctx| ctx.is_suspended())
+    }
+
+    fn update(&mut self) {
+        with_renderer!(self, |ctx| ctx.update())
+    }
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod test {
+    use super::*;
+    use crate::{Texture, TextureFormat};
+
+    #[test]
+    fn forwards_to_backend() {
+        let mut renderer = DynRenderer::Headless(Renderer::new_headless().unwrap());
+        assert!(renderer.context_description().contains("Headless"));
+
+        let texture = with_renderer!(&mut renderer, |ctx| {
+            Texture::new(ctx, &[0; 4], 1, 1, TextureFormat::Rgba8)
+        })
+        .unwrap();
+        let exists = with_renderer!(&renderer, |ctx| ctx.textures.get(texture).is_some());
+        assert!(exists);
+
+        renderer.begin_gpu_scope("frame");
+        renderer.update();
+        renderer.end_gpu_scope();
+        assert_eq!(renderer.frame_stats().draw_calls, 0);
+    }
+}
@@ -0,0 +1,298 @@
+//! Distance and height fog, so distant geometry fades into the sky.
+//!
+//! The fog parameters are part of the `Frame` uniform block, together with the
+//! [camera][crate::camera::CameraUniforms], see [FrameUniforms]. Every renderer can include
+//! [FOG_INCLUDE] into its shaders, which declares the block and the `cac_fog_factor` and
+//! `cac_apply_fog` functions, so forward shaders fog their own colors:
+//!
+//! ```glsl
+//! #include "cac_fog.glsl"
+//! in vec3 world_position;
+//! // ...
+//! result = vec4(cac_apply_fog(color.rgb, world_position), color.a);
+//! ```
+//!
+//! ```no_run
+//! # use cac_renderer::{camera::Camera, fog::*, Backend, Handle, Material, MaterialProperty, Mesh, Renderer};
+//! # fn frame<C: cac_renderer::Context>(renderer: &mut Renderer<C>, camera: &Camera, mesh: Mesh, material: Handle<Material>)
+//! # where Renderer<C>: Backend {
+//! let fog = Fog {
+//!     density: 0.05,
+//!     height_falloff: 0.2,
+//!     ..Fog::default()
+//! };
+//! let frame = renderer.alloc_uniforms(&FrameUniforms::new(camera, &fog));
+//! renderer.draw(mesh, material, &[MaterialProperty::new(FRAME_BLOCK, &frame)]);
+//! # }
+//! ```
+//!
+//! Scenes whose shaders don't fog their colors apply it afterwards with a [FogPass], from the
+//! depth of the frame.
+use crate::{
+    camera::{Camera, CameraUniforms},
+    math::{Mat4, Vec3, Vec4},
+    Color32, Handle, Renderer, RendererError,
+};
+
+use super::{fullscreen::FullscreenPass, Backend, Context, MaterialProperty, Texture};
+
+/// Name of the include with the `Frame` block and the fog functions, registered in the
+/// [ShaderPreprocessor][crate::ShaderPreprocessor] of every renderer.
+pub const FOG_INCLUDE: &str = "cac_fog.glsl";
+/// Name of the `std140` uniform block with the [FrameUniforms].
+pub const FRAME_BLOCK: &str = "Frame";
+
+pub(crate) const FOG_SOURCE: &str = r##"
+layout(std140) uniform Frame {
+    mat4 view;
+    mat4 projection;
+    mat4 view_projection;
+    vec4 camera_position;
+    // rgb and the max opacity in a
+    vec4 fog_color;
+    // density, height falloff, base height and start distance
+    vec4 fog_params;
+};
+
+float cac_fog_factor(vec3 world_position) {
+    vec3 ray = world_position - camera_position.xyz;
+    float distance = max(length(ray) - fog_params.w, 0.0);
+    float density = fog_params.x * exp(-fog_params.y * (camera_position.y - fog_params.z));
+    // the density integrated along the ray, through the exponential falloff with the height
+    float falloff = fog_params.y * ray.y;
+    if (abs(falloff) > 0.0001) {
+        density *= (1.0 - exp(-falloff)) / falloff;
+    }
+    return min(1.0 - exp(-density * distance), fog_color.a);
+}
+
+vec3 cac_apply_fog(vec3 color, vec3 world_position) {
+    return mix(color, fog_color.rgb, cac_fog_factor(world_position));
+}
+"##;
+
+const FOG_PASS_FS_SOURCE: &str = r##"
+    #version 330 core
+    #include "cac_fog.glsl"
+    in vec2 frag_uv;
+    out vec4 result;
+    uniform sampler2D source;
+    uniform sampler2D depth;
+    uniform mat4 inverse_view_projection;
+    void main() {
+        vec3 ndc = vec3(frag_uv, texture(depth, frag_uv).r) * 2.0 - 1.0;
+        vec4 world = inverse_view_projection * vec4(ndc, 1.0);
+        vec4 color = texture(source, frag_uv);
+        result = vec4(cac_apply_fog(color.rgb, world.xyz / world.w), color.a);
+    }"##;
+
+/// Exponential fog that gets thinner with the height, see the [module documentation][self].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fog {
+    pub color: Color32,
+    /// Fog per world unit at the [base height][Self::base_height]. 0 turns the fog off.
+    pub density: f32,
+    /// How fast the density falls off above the base height, and grows below it. 0 is the same
+    /// density at every height.
+    pub height_falloff: f32,
+    pub base_height: f32,
+    /// Distance to the camera without fog.
+    pub start: f32,
+    /// Most the fog covers, below 1 keeps the far geometry visible.
+    pub max_opacity: f32,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Self {
+            color: Color32::from_rgb(0.5, 0.6, 0.7),
+            density: 0.02,
+            height_falloff: 0.0,
+            base_height: 0.0,
+            start: 0.0,
+            max_opacity: 1.0,
+        }
+    }
+}
+
+impl Fog {
+    /// The fog members of the `Frame` block.
+    pub fn uniforms(&self) -> FogUniforms {
+        let (r, g, b) = self.color.as_rgb();
+        FogUniforms {
+            color: Vec4::new(r, g, b, self.max_opacity),
+            params: Vec4::new(
+                self.density,
+                self.height_falloff,
+                self.base_height,
+                self.start,
+            ),
+        }
+    }
+
+    /// How much of the color at the position is fog when seen from the camera position, from 0
+    /// to the max opacity. The same as `cac_fog_factor` of the include, e.g. to skip objects that
+    /// are hidden in the fog.
+    pub fn factor(&self, camera_position: Vec3, position: Vec3) -> f32 {
+        let ray = position - camera_position;
+        let distance = (ray.length() - self.start).max(0.0);
+        let mut density =
+            self.density * (-self.height_falloff * (camera_position.y - self.base_height)).exp();
+        let falloff = self.height_falloff * ray.y;
+        if falloff.abs() > 0.0001 {
+            density *= (1.0 - (-falloff).exp()) / falloff;
+        }
+        (1.0 - (-density * distance).exp()).min(self.max_opacity)
+    }
+}
+
+/// The fog members of the `Frame` block, see [Fog::uniforms].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct FogUniforms {
+    /// The color in rgb and the max opacity in w.
+    pub color: Vec4,
+    /// The density, height falloff, base height and start.
+    pub params: Vec4,
+}
+
+/// The `Frame` uniform block of the [FOG_INCLUDE], shared by the draws of a frame, laid out like
+/// the `std140` block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct FrameUniforms {
+    pub camera: CameraUniforms,
+    pub fog: FogUniforms,
+}
+
+impl FrameUniforms {
+    pub fn new(camera: &Camera, fog: &Fog) -> Self {
+        Self {
+            camera: camera.uniforms(),
+            fog: fog.uniforms(),
+        }
+    }
+}
+
+/// Fullscreen pass that fogs a rendered frame by the depth of its pixels, for scenes whose
+/// shaders don't apply the fog themselves.
+///
+/// ```no_run
+/// # use cac_renderer::{camera::Camera, fog::*, Backend, Handle, Renderer, Texture};
+/// # fn frame<C: cac_renderer::Context>(renderer: &mut Renderer<C>, color: Handle<Texture>, depth: Handle<Texture>, camera: &Camera)
+/// # where Renderer<C>: Backend {
+/// let pass = FogPass::new(renderer, color, depth).unwrap();
+/// pass.draw(renderer, camera, &Fog::default());
+/// renderer.update();
+/// # }
+/// ```
+///
+/// The pass is drawn in the [Overlay][crate::RenderQueue::Overlay] queue, on top of whatever else is
+/// drawn into the active target.
+#[derive(Debug)]
+pub struct FogPass {
+    pass: FullscreenPass,
+}
+
+impl FogPass {
+    /// Fogs the source colors with the depth of the frame, e.g. the
+    /// [Depth24Stencil8][crate::TextureFormat::Depth24Stencil8] attachment it was drawn with.
+    pub fn new<C: Context>(
+        ctx: &mut Renderer<C>,
+        source: Handle<Texture>,
+        depth: Handle<Texture>,
+    ) -> Result<Self, RendererError> {
+        let pass = FullscreenPass::new(
+            ctx,
+            FOG_PASS_FS_SOURCE,
+            &[
+                MaterialProperty::new("source", &source),
+                MaterialProperty::new("depth", &depth),
+            ],
+        )?;
+
+        Ok(Self { pass })
+    }
+
+    /// Draws the fogged frame into the active target, seen from the camera the frame was drawn
+    /// with.
+    pub fn draw<C: Context>(&self, ctx: &mut Renderer<C>, camera: &Camera, fog: &Fog)
+    where
+        Renderer<C>: Backend,
+    {
+        let frame = ctx.alloc_uniforms(&FrameUniforms::new(camera, fog));
+        let inverse_view_projection: Mat4 = camera.view_projection().inverse();
+        self.pass.draw(
+            ctx,
+            &[
+                MaterialProperty::new(FRAME_BLOCK, &frame),
+                MaterialProperty::new("inverse_view_projection", &inverse_view_projection),
+            ],
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::vec3;
+
+    #[test]
+    fn fog_factor() {
+        let fog = Fog {
+            density: 0.1,
+            start: 5.0,
+            max_opacity: 0.9,
+            ..Fog::default()
+        };
+        let eye = Vec3::ZERO;
+        assert_eq!(fog.factor(eye, vec3(0.0, 0.0, -5.0)), 0.0);
+        let factor = fog.factor(eye, vec3(0.0, 0.0, -15.0));
+        assert!((factor - (1.0 - (-1.0_f32).exp())).abs() < 1e-6);
+        // clamped to the max opacity
+        assert_eq!(fog.factor(eye, vec3(0.0, 0.0, -1000.0)), 0.9);
+
+        // thinner above the base height, the same for rays that climb and descend
+        let height = Fog {
+            height_falloff: 0.5,
+            ..fog
+        };
+        let low = height.factor(eye, vec3(0.0, 0.0, -15.0));
+        let high = height.factor(vec3(0.0, 10.0, 0.0), vec3(0.0, 10.0, -15.0));
+        assert!((low - factor).abs() < 1e-6);
+        assert!(high < low);
+        let up = height.factor(eye, vec3(0.0, 10.0, -15.0));
+        let down = height.factor(vec3(0.0, 10.0, -15.0), eye);
+        assert!((up - down).abs() < 1e-5);
+    }
+
+    #[test]
+    fn frame_block_layout() {
+        // three mat4 and three vec4
+        assert_eq!(std::mem::size_of::<FrameUniforms>(), 3 * 64 + 3 * 16);
+        let fog = Fog::default().uniforms();
+        assert_eq!(fog.params, Vec4::new(0.02, 0.0, 0.0, 0.0));
+        assert_eq!(fog.color.w, 1.0);
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn draw_fog_pass() {
+        use crate::{RecordedCommand, TextureFormat};
+
+        let mut renderer = Renderer::new_headless().unwrap();
+        let [source, depth] = [TextureFormat::Rgba8, TextureFormat::Depth24Stencil8]
+            .map(|format| Texture::new(&mut renderer, &[0; 4], 1, 1, format).unwrap());
+        // compiles with the fog include of the preprocessor
+        let pass = FogPass::new(&mut renderer, source, depth).unwrap();
+
+        renderer.clear_recorded_commands();
+        let camera = Camera::perspective(1.0, 1.0, 0.1, 100.0);
+        pass.draw(&mut renderer, &camera, &Fog::default());
+        renderer.update();
+        assert!(renderer
+            .recorded_commands()
+            .iter()
+            .any(|command| matches!(command, RecordedCommand::Draw { material, .. } if *material == pass.pass.material)));
+    }
+}
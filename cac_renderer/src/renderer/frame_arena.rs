@@ -0,0 +1,211 @@
+/// Bump allocator for the transient data of the draw commands of a frame, like the per-draw
+/// property overrides.
+///
+/// Everything is appended to a few flat buffers and referenced by ranges, so recording a draw
+/// doesn't allocate once the buffers grew to the size of a typical frame. Ranges are stored as
+/// u32, which keeps them inline in the draw commands at 8 bytes. The arena is reset at
+/// the end of every [update][super::Backend::update], keeping the allocated memory.
+#[derive(Debug, Default)]
+pub(crate) struct FrameArena {
+    values: Vec<f32>,
+    uniforms: Vec<ArenaUniform>,
+    /// Data of the uniform blocks, uploaded to a uniform buffer once per frame.
+    uniform_data: Vec<u8>,
+    uniform_alignment: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ArenaUniform {
+    Values { location: u32, start: u32, len: u32 },
+    Block { binding: u32, slice: UniformSlice },
+}
+
+/// Range of the uniform data of the frame, allocated with
+/// [alloc_uniforms][super::Renderer::alloc_uniforms] and bound to a uniform block of a draw with
+/// [PropertyValue::Uniforms][super::PropertyValue::Uniforms].
+///
+/// Slices are only valid until the end of the [update][super::Backend::update] that draws them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UniformSlice {
+    offset: u32,
+    size: u32,
+}
+
+impl UniformSlice {
+    /// Byte offset of the slice in the uniform data of the frame.
+    pub fn offset(&self) -> usize {
+        self.offset as usize
+    }
+
+    pub fn size(&self) -> usize {
+        self.size as usize
+    }
+}
+
+/// A per-draw value, resolved for the program of the draw.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum InstanceValue<'a> {
+    /// Values of the uniform at the location.
+    Uniform(u32, &'a [f32]),
+    /// Uniform data bound to the uniform block at the binding.
+    Block(u32, UniformSlice),
+}
+
+/// The property overrides of a single draw, stored in the [FrameArena] of the frame.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct InstanceData {
+    start: u32,
+    len: u32,
+}
+
+impl InstanceData {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl FrameArena {
+    pub(crate) fn with_capacity(values: usize, uniforms: usize) -> Self {
+        Self {
+            values: Vec::with_capacity(values),
+            uniforms: Vec::with_capacity(uniforms),
+            uniform_data: Vec::new(),
+            uniform_alignment: 256,
+        }
+    }
+
+    /// Sets the alignment of the offsets of the uniform slices, which the graphics device
+    /// requires for binding them.
+    #[cfg(feature = "opengl")]
+    pub(crate) fn set_uniform_alignment(&mut self, alignment: usize) {
+        self.uniform_alignment = alignment;
+    }
+
+    /// Appends the per-draw values to the arena and returns the range they occupy.
+    pub(crate) fn alloc<'a>(
+        &mut self,
+        uniforms: impl IntoIterator<Item = InstanceValue<'a>>,
+    ) -> InstanceData {
+        let start = self.uniforms.len();
+        for uniform in uniforms {
+            match uniform {
+                InstanceValue::Uniform(location, values) => {
+                    self.uniforms.push(ArenaUniform::Values {
+                        location,
+                        start: self.values.len() as u32,
+                        len: values.len() as u32,
+                    });
+                    self.values.extend_from_slice(values);
+                }
+                InstanceValue::Block(binding, slice) => {
+                    self.uniforms.push(ArenaUniform::Block { binding, slice })
+                }
+            }
+        }
+
+        InstanceData {
+            start: start as u32,
+            len: (self.uniforms.len() - start) as u32,
+        }
+    }
+
+    /// The values of the instance data.
+    pub(crate) fn get(&self, data: InstanceData) -> impl Iterator<Item = InstanceValue<'_>> {
+        let range = |start: u32, len: u32| start as usize..(start + len) as usize;
+        self.uniforms[range(data.start, data.len)]
+            .iter()
+            .map(move |uniform| match *uniform {
+                ArenaUniform::Values {
+                    location,
+                    start,
+                    len,
+                } => InstanceValue::Uniform(location, &self.values[range(start, len)]),
+                ArenaUniform::Block { binding, slice } => InstanceValue::Block(binding, slice),
+            })
+    }
+
+    /// Appends the bytes to the uniform data, at the next aligned offset.
+    pub(crate) fn alloc_uniform_data(&mut self, bytes: &[u8]) -> UniformSlice {
+        let offset = self
+            .uniform_data
+            .len()
+            .next_multiple_of(self.uniform_alignment.max(1));
+        self.uniform_data.resize(offset, 0);
+        self.uniform_data.extend_from_slice(bytes);
+
+        UniformSlice {
+            offset: offset as u32,
+            size: bytes.len() as u32,
+        }
+    }
+
+    /// The uniform data of the frame, which the slices point into.
+    #[cfg(feature = "opengl")]
+    pub(crate) fn uniform_data(&self) -> &[u8] {
+        &self.uniform_data
+    }
+
+    /// Frees everything at once. Ranges handed out before are invalid afterwards.
+    pub(crate) fn reset(&mut self) {
+        self.values.clear();
+        self.uniforms.clear();
+        self.uniform_data.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn alloc_and_reset() {
+        let mut arena = FrameArena::with_capacity(8, 2);
+        let empty = arena.alloc([]);
+        let first = arena.alloc([
+            InstanceValue::Uniform(0, &[1.0]),
+            InstanceValue::Uniform(3, &[2.0, 3.0]),
+        ]);
+        let second = arena.alloc([InstanceValue::Uniform(1, &[4.0])]);
+
+        assert!(empty.is_empty());
+        assert_eq!(arena.get(empty).count(), 0);
+        assert_eq!(
+            arena.get(first).collect::<Vec<_>>(),
+            [
+                InstanceValue::Uniform(0, &[1.0]),
+                InstanceValue::Uniform(3, &[2.0, 3.0])
+            ]
+        );
+        assert_eq!(
+            arena.get(second).collect::<Vec<_>>(),
+            [InstanceValue::Uniform(1, &[4.0])]
+        );
+
+        arena.reset();
+        assert!(arena.alloc([]).is_empty());
+        assert_eq!(arena.values.capacity(), 8);
+
+        assert_eq!(std::mem::size_of::<InstanceData>(), 8);
+    }
+
+    #[cfg(feature = "opengl")]
+    #[test]
+    fn uniform_data() {
+        let mut arena = FrameArena::with_capacity(0, 1);
+        arena.set_uniform_alignment(16);
+        let first = arena.alloc_uniform_data(&[1; 20]);
+        let second = arena.alloc_uniform_data(&[2; 4]);
+        assert_eq!((first.offset(), first.size()), (0, 20));
+        assert_eq!((second.offset(), second.size()), (32, 4));
+        assert_eq!(arena.uniform_data().len(), 36);
+
+        let data = arena.alloc([InstanceValue::Block(2, second)]);
+        assert_eq!(
+            arena.get(data).collect::<Vec<_>>(),
+            [InstanceValue::Block(2, second)]
+        );
+
+        arena.reset();
+        assert!(arena.uniform_data().is_empty());
+    }
+}
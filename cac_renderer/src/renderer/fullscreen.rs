@@ -0,0 +1,72 @@
+use crate::{Handle, Renderer, RendererError};
+
+use super::{
+    AttributeSemantic, Backend, Buffer, BufferAttributes, BufferUsage, Context, Material,
+    MaterialProperty, Mesh, Primitive, RenderQueue, ShaderProgram, VertexLayout,
+};
+
+/// Vertex shader of the fullscreen passes, which passes the texture coordinates of the screen to
+/// the fragment shader as `frag_uv`.
+const FULLSCREEN_VS_SOURCE: &str = r##"
+    #version 330 core
+    layout(location = 0) in vec3 pos;
+    out vec2 frag_uv;
+    void main() {
+        frag_uv = pos.xy * 0.5 + 0.5;
+        gl_Position = vec4(pos, 1.0);
+    }"##;
+
+/// A single triangle that covers the whole screen.
+#[rustfmt::skip]
+const FULLSCREEN_VERTICES: [f32; 9] = [
+    -1.0, -1.0, 0.0,
+     3.0, -1.0, 0.0,
+    -1.0,  3.0, 0.0,
+];
+
+/// A triangle that covers the whole screen, drawn with a material of its own in the
+/// [Overlay][RenderQueue::Overlay] queue, on top of whatever else is drawn into the active
+/// target. The post processing passes, like the [MotionBlur][super::MotionBlur], are built on it.
+#[derive(Debug)]
+pub(crate) struct FullscreenPass {
+    pub(crate) mesh: Mesh,
+    pub(crate) material: Handle<Material>,
+}
+
+impl FullscreenPass {
+    /// Creates the pass with the fragment shader, which gets the texture coordinates as
+    /// `in vec2 frag_uv`, and the material properties, e.g. the textures it samples.
+    pub(crate) fn new<C: Context>(
+        ctx: &mut Renderer<C>,
+        fragment_source: &str,
+        properties: &[MaterialProperty],
+    ) -> Result<Self, RendererError> {
+        let vertex_buffer =
+            Buffer::with_vertex(ctx, &FULLSCREEN_VERTICES, BufferUsage::StaticRead)?;
+        let vertex_layout = VertexLayout::new(
+            ctx,
+            &[BufferAttributes::with_semantics(
+                vertex_buffer,
+                0,
+                &[AttributeSemantic::Position],
+            )],
+        )?;
+
+        let program = ShaderProgram::from_sources(ctx, FULLSCREEN_VS_SOURCE, fragment_source)?;
+        let material = ctx.create_material(program, properties)?;
+        ctx.set_render_queue(material, RenderQueue::Overlay);
+
+        Ok(Self {
+            mesh: Mesh::new(vertex_layout, 3, Primitive::Triangles),
+            material,
+        })
+    }
+
+    /// Draws the pass into the active target, with the per-draw properties.
+    pub(crate) fn draw<C: Context>(&self, ctx: &mut Renderer<C>, properties: &[MaterialProperty])
+    where
+        Renderer<C>: Backend,
+    {
+        ctx.draw(self.mesh, self.material, properties);
+    }
+}
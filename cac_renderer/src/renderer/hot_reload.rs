@@ -0,0 +1,585 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{Handle, Renderer, RendererError};
+
+use super::{
+    retention::RetainedShader,
+    shader::{CreateShaderProgram, ShaderStage},
+    Buffer, BufferDescription, Context, Shader, ShaderPreprocessor, ShaderProgram, Texture,
+    TextureData, Uniform,
+};
+
+impl ShaderStage {
+    fn from_path(path: &Path) -> Result<Self, RendererError> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("vert" | "vs") => Ok(Self::Vertex),
+            Some("frag" | "fs") => Ok(Self::Fragment),
+            _ => Err(RendererError::FailedToCompileShader {
+                error: format!("Unknown shader stage of {}", path.display()),
+            }),
+        }
+    }
+
+    /// Returns the shader with its preprocessed source, to keep it for
+    /// [resume][super::Backend::resume].
+    fn compile<C: Context>(
+        &self,
+        preprocessor: &ShaderPreprocessor,
+        source: &str,
+    ) -> Result<(C::Shader, RetainedShader), RendererError> {
+        let source = preprocessor.process(source)?;
+        let shader = self.create::<C>(&source)?;
+        Ok((shader, RetainedShader::new(*self, source)))
+    }
+}
+
+fn read_error(path: &Path, error: std::io::Error) -> RendererError {
+    RendererError::FailedToReadFile {
+        path: path.display().to_string(),
+        error: error.to_string(),
+    }
+}
+
+fn read_source(path: &Path) -> Result<String, RendererError> {
+    fs::read_to_string(path).map_err(|error| read_error(path, error))
+}
+
+/// Turns the content of a file into the data of a [Buffer], e.g. the vertices of a mesh format.
+type BufferDecoder = Box<dyn Fn(&[u8]) -> Result<BufferDescription, RendererError>>;
+
+fn decode_file(path: &Path, decode: &BufferDecoder) -> Result<BufferDescription, RendererError> {
+    let bytes = fs::read(path).map_err(|error| read_error(path, error))?;
+    decode(&bytes)
+}
+
+/// Turns the content of a file into the texels of a [Texture], e.g. the pixels of an image format.
+type TextureDecoder = Box<dyn Fn(&[u8]) -> Result<TextureData, RendererError>>;
+
+fn decode_texture(path: &Path, decode: &TextureDecoder) -> Result<TextureData, RendererError> {
+    let bytes = fs::read(path).map_err(|error| read_error(path, error))?;
+    decode(&bytes)
+}
+
+#[derive(Debug)]
+struct WatchedFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl WatchedFile {
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            modified: Self::modified(path),
+        }
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    /// Returns true if the file was modified since the last call.
+    /// Files that were deleted don't count as changed, so the last working version is kept.
+    fn changed(&mut self) -> bool {
+        let modified = Self::modified(&self.path);
+        if modified.is_some() && modified != self.modified {
+            self.modified = modified;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Debug)]
+struct WatchedShader {
+    handle: Handle<Shader>,
+    stage: ShaderStage,
+    file: WatchedFile,
+}
+
+#[derive(Debug)]
+struct WatchedProgram {
+    handle: Handle<ShaderProgram>,
+    vertex: WatchedFile,
+    fragment: WatchedFile,
+}
+
+struct WatchedBuffer {
+    handle: Handle<Buffer>,
+    decode: BufferDecoder,
+    file: WatchedFile,
+}
+
+struct WatchedTexture {
+    handle: Handle<Texture>,
+    decode: TextureDecoder,
+    file: WatchedFile,
+}
+
+/// Source files of the resources that were loaded from files.
+#[derive(Default)]
+pub(crate) struct Watcher {
+    shaders: Vec<WatchedShader>,
+    programs: Vec<WatchedProgram>,
+    buffers: Vec<WatchedBuffer>,
+    textures: Vec<WatchedTexture>,
+}
+
+impl Shader {
+    /// Loads and compiles the shader from the file. The stage depends on the extension:
+    /// `.vert`/`.vs` for vertex and `.frag`/`.fs` for fragment shaders.
+    ///
+    /// The file is watched by [poll_shader_changes][Renderer::poll_shader_changes].
+    pub fn from_file<C: Context>(
+        ctx: &mut Renderer<C>,
+        path: impl AsRef<Path>,
+    ) -> Result<Handle<Self>, RendererError> {
+        let path = path.as_ref();
+        let stage = ShaderStage::from_path(path)?;
+        let (shader, retained) = stage.compile::<C>(&ctx.preprocessor, &read_source(path)?)?;
+
+        let handle = ctx.shaders.push(shader);
+        ctx.retention.retain_shader(handle, retained);
+        ctx.watcher.shaders.push(WatchedShader {
+            handle,
+            stage,
+            file: WatchedFile::new(path),
+        });
+
+        Ok(handle)
+    }
+}
+
+impl ShaderProgram {
+    /// Loads, compiles and links the program from the source files.
+    ///
+    /// The files are watched by [poll_shader_changes][Renderer::poll_shader_changes].
+    pub fn from_files<C: Context>(
+        ctx: &mut Renderer<C>,
+        vertex_path: impl AsRef<Path>,
+        fragment_path: impl AsRef<Path>,
+    ) -> Result<Handle<Self>, RendererError> {
+        let (vertex_path, fragment_path) = (vertex_path.as_ref(), fragment_path.as_ref());
+        let handle = Self::from_sources(
+            ctx,
+            &read_source(vertex_path)?,
+            &read_source(fragment_path)?,
+        )?;
+
+        ctx.watcher.programs.push(WatchedProgram {
+            handle,
+            vertex: WatchedFile::new(vertex_path),
+            fragment: WatchedFile::new(fragment_path),
+        });
+
+        Ok(handle)
+    }
+}
+
+impl Buffer {
+    /// Loads the buffer from the file, e.g. the vertices or indices of a mesh. The decoder turns
+    /// the content of the file into the buffer data.
+    ///
+    /// The file is watched by [poll_buffer_changes][Renderer::poll_buffer_changes].
+    pub fn from_file<C: Context>(
+        ctx: &mut Renderer<C>,
+        path: impl AsRef<Path>,
+        decode: impl Fn(&[u8]) -> Result<BufferDescription, RendererError> + 'static,
+    ) -> Result<Handle<Self>, RendererError> {
+        let path = path.as_ref();
+        let decode: BufferDecoder = Box::new(decode);
+        let buffer = decode_file(path, &decode)?.create::<C>(&mut ctx.context)?;
+
+        let handle = ctx.buffers.push(buffer);
+        ctx.watcher.buffers.push(WatchedBuffer {
+            handle,
+            decode,
+            file: WatchedFile::new(path),
+        });
+
+        Ok(handle)
+    }
+}
+
+impl Texture {
+    /// Loads the texture from the file, e.g. an image. The decoder turns the content of the file
+    /// into the texels.
+    ///
+    /// The file is watched by [poll_texture_changes][Renderer::poll_texture_changes].
+    pub fn from_file_with<C: Context>(
+        ctx: &mut Renderer<C>,
+        path: impl AsRef<Path>,
+        decode: impl Fn(&[u8]) -> Result<TextureData, RendererError> + 'static,
+    ) -> Result<Handle<Self>, RendererError> {
+        let path = path.as_ref();
+        let decode: TextureDecoder = Box::new(decode);
+        let data = decode_texture(path, &decode)?;
+        let handle = ctx.textures.push(data.create::<C>()?);
+        ctx.retain_texture_data(handle, &data);
+
+        ctx.watcher.textures.push(WatchedTexture {
+            handle,
+            decode,
+            file: WatchedFile::new(path),
+        });
+
+        Ok(handle)
+    }
+}
+
+impl<C: Context> Renderer<C> {
+    /// Recreates the textures whose files changed since the last call in place, and returns the
+    /// textures that were reloaded. Materials keep sampling them with the same handle, even if
+    /// the size or format changed. Render targets and views of the previous texture don't see
+    /// the new one.
+    /// If the file fails to decode, the error is logged and the previous version is kept.
+    pub fn poll_texture_changes(&mut self) -> Vec<Handle<Texture>> {
+        let mut watcher = std::mem::take(&mut self.watcher);
+        let mut reloaded = Vec::new();
+
+        for texture in &mut watcher.textures {
+            if !texture.file.changed() {
+                continue;
+            }
+
+            let decoded = decode_texture(&texture.file.path, &texture.decode)
+                .and_then(|data| Ok((data.create::<C>()?, data)));
+            match (decoded, self.textures.update(texture.handle)) {
+                (Ok((created, data)), Some(previous)) => {
+                    *previous = created;
+                    self.retain_texture_data(texture.handle, &data);
+                    reloaded.push(texture.handle);
+                }
+                (Ok(_), None) => {}
+                (Err(error), _) => {
+                    log::error!("Failed to reload {}: {error}", texture.file.path.display())
+                }
+            }
+        }
+
+        watcher
+            .textures
+            .retain(|texture| self.textures.get(texture.handle).is_some());
+        self.watcher = watcher;
+
+        reloaded
+    }
+
+    fn retain_texture_data(&mut self, texture: Handle<Texture>, data: &TextureData) {
+        let row_pitch = data.width as usize * data.format.texel_size();
+        self.retention.retain_texture(
+            texture,
+            &data.data,
+            (data.width, data.height),
+            row_pitch,
+            data.format,
+            data.mipmapped,
+        );
+    }
+
+    /// Re-uploads the buffers whose files changed since the last call into the existing
+    /// buffers, and returns the buffers that were reloaded. Vertex layouts, meshes and draws
+    /// referring to them stay valid, but a [Mesh][super::Mesh] needs to be updated by hand if
+    /// the number of vertices or indices changed.
+    /// If the file fails to decode, the error is logged and the previous version is kept.
+    pub fn poll_buffer_changes(&mut self) -> Vec<Handle<Buffer>> {
+        let mut watcher = std::mem::take(&mut self.watcher);
+        let mut reloaded = Vec::new();
+
+        for buffer in &mut watcher.buffers {
+            if !buffer.file.changed() {
+                continue;
+            }
+
+            let target = match self.buffers.update(buffer.handle) {
+                Some(target) => target,
+                None => continue,
+            };
+
+            // the data is replaced in place, so vertex layouts keep pointing to the buffer
+            match decode_file(&buffer.file.path, &buffer.decode)
+                .and_then(|description| description.write::<C>(&mut self.context, target))
+            {
+                Ok(()) => reloaded.push(buffer.handle),
+                Err(error) => {
+                    log::error!("Failed to reload {}: {error}", buffer.file.path.display())
+                }
+            }
+        }
+
+        watcher
+            .buffers
+            .retain(|buffer| self.buffers.get(buffer.handle).is_some());
+        self.watcher = watcher;
+
+        reloaded
+    }
+
+    /// Recompiles the shaders and relinks the programs whose source files changed since the last
+    /// call, and returns the programs that were reloaded.
+    ///
+    /// Existing handles stay valid and materials keep their property values, as long as the
+    /// uniform keeps its name and kind. If the new version fails to compile, the error is logged
+    /// and the previous version is kept.
+    pub fn poll_shader_changes(&mut self) -> Vec<Handle<ShaderProgram>> {
+        let mut watcher = std::mem::take(&mut self.watcher);
+
+        for shader in &mut watcher.shaders {
+            if !shader.file.changed() {
+                continue;
+            }
+
+            let compiled = read_source(&shader.file.path)
+                .and_then(|source| shader.stage.compile::<C>(&self.preprocessor, &source));
+            match (compiled, self.shaders.update(shader.handle)) {
+                (Ok((compiled, retained)), Some(previous)) => {
+                    *previous = compiled;
+                    self.retention.retain_shader(shader.handle, retained);
+                }
+                (Ok(_), None) => {}
+                (Err(error), _) => {
+                    log::error!("Failed to reload {}: {error}", shader.file.path.display())
+                }
+            }
+        }
+
+        let mut reloaded = Vec::new();
+        for program in &mut watcher.programs {
+            // check both, so both timestamps are up to date
+            let vertex_changed = program.vertex.changed();
+            let fragment_changed = program.fragment.changed();
+            if !vertex_changed && !fragment_changed {
+                continue;
+            }
+
+            match self.reload_program(program) {
+                Ok(true) => reloaded.push(program.handle),
+                Ok(false) => {}
+                Err(error) => log::error!(
+                    "Failed to reload {} and {}: {error}",
+                    program.vertex.path.display(),
+                    program.fragment.path.display()
+                ),
+            }
+        }
+
+        // programs that were removed don't need to be watched anymore
+        watcher
+            .programs
+            .retain(|program| self.programs.get(program.handle).is_some());
+        watcher
+            .shaders
+            .retain(|shader| self.shaders.get(shader.handle).is_some());
+        self.watcher = watcher;
+
+        reloaded
+    }
+
+    /// Replaces the program in place and remaps the data of its materials.
+    /// Returns false if the program doesn't exist anymore.
+    fn reload_program(&mut self, watched: &WatchedProgram) -> Result<bool, RendererError> {
+        let (vertex_shader, vertex) = ShaderStage::Vertex
+            .compile::<C>(&self.preprocessor, &read_source(&watched.vertex.path)?)?;
+        let (fragment_shader, fragment) = ShaderStage::Fragment
+            .compile::<C>(&self.preprocessor, &read_source(&watched.fragment.path)?)?;
+        let attribute_locations = self.attribute_locations();
+        let linked = C::ShaderProgram::new(&vertex_shader, &fragment_shader, &attribute_locations)?;
+
+        let program = match self.programs.update(watched.handle) {
+            Some(program) => program,
+            None => return Ok(false),
+        };
+
+        let previous = std::mem::replace(program, linked);
+        let (uniforms, data_size) = (program.uniforms().clone(), program.data_size());
+        self.retention
+            .retain_relinked(watched.handle, vertex, fragment, attribute_locations);
+
+        self.materials
+            .iter_mut()
+            .filter(|material| material.program == watched.handle)
+            .for_each(|material| material.remap(previous.uniforms(), &uniforms, data_size));
+
+        Ok(true)
+    }
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod test {
+    use super::*;
+    use crate::{math::Vec4, renderer::texture::CreateTexture, MaterialProperty};
+
+    const VERTEX: &str = "uniform mat4 transform;\nvoid main() {}";
+    const FRAGMENT: &str = "uniform vec4 color;\nvoid main() {}";
+
+    fn write(path: &Path, source: &str) {
+        fs::write(path, source).unwrap();
+        // the timestamp resolution of some file systems is too coarse to notice the change
+        let modified = WatchedFile::modified(path).unwrap() + std::time::Duration::from_secs(1);
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    #[test]
+    fn reload_program() {
+        let dir = std::env::temp_dir().join(format!("cac_hot_reload_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (vertex_path, fragment_path) = (dir.join("test.vert"), dir.join("test.frag"));
+        write(&vertex_path, VERTEX);
+        write(&fragment_path, FRAGMENT);
+
+        let mut renderer = Renderer::new_headless().unwrap();
+        let program =
+            ShaderProgram::from_files(&mut renderer, &vertex_path, &fragment_path).unwrap();
+        let color = Vec4::new(1.0, 0.5, 0.25, 1.0);
+        let material = renderer
+            .create_material(program, &[MaterialProperty::new("color", &color)])
+            .unwrap();
+
+        assert!(renderer.poll_shader_changes().is_empty());
+
+        // broken sources keep the previous version
+        write(
+            &fragment_path,
+            "uniform float intensity;\nuniform vec4 color;\nvoid main() {",
+        );
+        assert!(renderer.poll_shader_changes().is_empty());
+        let uniforms = renderer.programs.get(program).unwrap().uniforms();
+        assert!(uniforms.iter().all(|uniform| uniform.name != "intensity"));
+        let offset = uniforms.iter().find(|u| u.name == "color").unwrap().offset;
+        assert_eq!(offset, 64);
+        assert!(renderer
+            .update_material(material, &[MaterialProperty::new("color", &color)])
+            .is_ok());
+
+        // a new uniform in front of the color moves its offset
+        write(
+            &fragment_path,
+            "uniform float intensity;\nuniform vec4 color;\nvoid main() {}",
+        );
+        assert_eq!(renderer.poll_shader_changes(), vec![program]);
+
+        let uniforms = renderer.programs.get(program).unwrap().uniforms();
+        let offset = uniforms.iter().find(|u| u.name == "color").unwrap().offset;
+        assert_eq!(offset, 4 + 64);
+
+        let data = &renderer.materials.get(material).unwrap().data;
+        let remapped: Vec<f32> = data[offset..offset + 16]
+            .chunks(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        assert_eq!(remapped, color.to_array());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reload_buffer() {
+        let dir =
+            std::env::temp_dir().join(format!("cac_hot_reload_buffer_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("positions.txt");
+        write(&path, "0.0 1.0 2.0");
+
+        // whitespace separated floats
+        let decode = |bytes: &[u8]| {
+            let values: Result<Vec<f32>, _> = String::from_utf8_lossy(bytes)
+                .split_whitespace()
+                .map(str::parse)
+                .collect();
+            values
+                .map(|values| BufferDescription::vertex(&values, crate::BufferUsage::StaticRead))
+                .map_err(|error| RendererError::ConversionError {
+                    error: error.to_string(),
+                })
+        };
+
+        let mut renderer = Renderer::new_headless().unwrap();
+        let buffer = Buffer::from_file(&mut renderer, &path, decode).unwrap();
+        renderer.clear_recorded_commands();
+
+        write(&path, "0.0 1.0 two");
+        assert!(renderer.poll_buffer_changes().is_empty());
+
+        write(&path, "0.0 1.0 2.0 3.0");
+        assert_eq!(renderer.poll_buffer_changes(), vec![buffer]);
+        assert_eq!(
+            renderer.recorded_commands(),
+            &[crate::RecordedCommand::UploadVertices { size: 16 }]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reload_texture() {
+        let dir =
+            std::env::temp_dir().join(format!("cac_hot_reload_texture_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("row.rgba");
+        write(&path, "abcd");
+
+        // a single row of Rgba8 texels
+        let decode = |bytes: &[u8]| {
+            if bytes.len() % 4 != 0 {
+                return Err(RendererError::ConversionError {
+                    error: format!("{} bytes aren't Rgba8 texels", bytes.len()),
+                });
+            }
+            Ok(TextureData {
+                data: bytes.to_vec(),
+                width: bytes.len() as u32 / 4,
+                height: 1,
+                format: crate::TextureFormat::Rgba8,
+                mipmapped: false,
+            })
+        };
+
+        let mut renderer = Renderer::new_headless().unwrap();
+        let texture = Texture::from_file_with(&mut renderer, &path, decode).unwrap();
+        let size = |renderer: &Renderer<crate::HeadlessContext>| {
+            renderer.textures.get(texture).unwrap().size()
+        };
+        assert_eq!(size(&renderer), (1, 1));
+        assert!(renderer.poll_texture_changes().is_empty());
+
+        write(&path, "abcde");
+        assert!(renderer.poll_texture_changes().is_empty());
+        assert_eq!(size(&renderer), (1, 1));
+
+        // the handle stays the same, even though the size changed
+        write(&path, "abcdefgh");
+        assert_eq!(renderer.poll_texture_changes(), vec![texture]);
+        assert_eq!(size(&renderer), (2, 1));
+
+        renderer.textures.remove(texture);
+        write(&path, "abcd");
+        assert!(renderer.poll_texture_changes().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stage_from_extension() {
+        assert_eq!(
+            ShaderStage::from_path(Path::new("lit.vs")).unwrap(),
+            ShaderStage::Vertex
+        );
+        assert_eq!(
+            ShaderStage::from_path(Path::new("lit.frag")).unwrap(),
+            ShaderStage::Fragment
+        );
+        assert!(ShaderStage::from_path(Path::new("lit.glsl")).is_err());
+    }
+}
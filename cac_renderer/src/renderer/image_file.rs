@@ -0,0 +1,254 @@
+use std::path::Path;
+
+use image::DynamicImage;
+
+use crate::{pack::PackFile, Handle, Renderer, RendererError};
+
+use super::{Context, Texture, TextureFormat};
+
+impl Texture {
+    /// Decodes a PNG, JPEG, TGA or HDR file into a mipmapped texture, see
+    /// [from_bytes][Self::from_bytes].
+    ///
+    /// With the `hot-reload` feature, the file is watched by
+    /// [poll_texture_changes][Renderer::poll_texture_changes].
+    pub fn from_file<C: Context>(
+        ctx: &mut Renderer<C>,
+        path: impl AsRef<Path>,
+    ) -> Result<Handle<Self>, RendererError> {
+        Self::decoded_file(ctx, path.as_ref(), true)
+    }
+
+    /// Decodes an image in one of the formats of [from_file][Self::from_file] into a mipmapped
+    /// texture. The format is detected from the data.
+    ///
+    /// Images with 8 or 16 bits per channel are colors, and become
+    /// [Srgb8Alpha8][TextureFormat::Srgb8Alpha8] textures that are sampled as linear values.
+    /// Floating point images like HDR files are linear already, and become
+    /// [Rgba16F][TextureFormat::Rgba16F] textures. Non-color data like normal or roughness maps
+    /// is loaded with [from_bytes_linear][Self::from_bytes_linear] instead.
+    pub fn from_bytes<C: Context>(
+        ctx: &mut Renderer<C>,
+        bytes: &[u8],
+    ) -> Result<Handle<Self>, RendererError> {
+        Self::decoded(ctx, bytes, true, "image")
+    }
+
+    /// Like [from_file][Self::from_file], for images that aren't sRGB encoded.
+    pub fn from_file_linear<C: Context>(
+        ctx: &mut Renderer<C>,
+        path: impl AsRef<Path>,
+    ) -> Result<Handle<Self>, RendererError> {
+        Self::decoded_file(ctx, path.as_ref(), false)
+    }
+
+    /// Like [from_bytes][Self::from_bytes], but 8 and 16 bit images become
+    /// [Rgba8][TextureFormat::Rgba8] textures, which are sampled as they are stored.
+    pub fn from_bytes_linear<C: Context>(
+        ctx: &mut Renderer<C>,
+        bytes: &[u8],
+    ) -> Result<Handle<Self>, RendererError> {
+        Self::decoded(ctx, bytes, false, "image")
+    }
+
+    /// Decodes the image stored in the pack like [from_bytes][Self::from_bytes].
+    pub fn from_pack<C: Context>(
+        ctx: &mut Renderer<C>,
+        pack: &PackFile,
+        name: &str,
+    ) -> Result<Handle<Self>, RendererError> {
+        Self::decoded(ctx, pack.entry(name)?, true, name)
+    }
+
+    /// Like [from_pack][Self::from_pack], for images that aren't sRGB encoded.
+    pub fn from_pack_linear<C: Context>(
+        ctx: &mut Renderer<C>,
+        pack: &PackFile,
+        name: &str,
+    ) -> Result<Handle<Self>, RendererError> {
+        Self::decoded(ctx, pack.entry(name)?, false, name)
+    }
+
+    fn decoded<C: Context>(
+        ctx: &mut Renderer<C>,
+        bytes: &[u8],
+        srgb: bool,
+        path: &str,
+    ) -> Result<Handle<Self>, RendererError> {
+        let (texels, width, height, format) =
+            decode(bytes, srgb).map_err(|error| RendererError::FailedToReadFile {
+                path: path.to_string(),
+                error,
+            })?;
+        Texture::mipmapped(ctx, &texels, width, height, format)
+    }
+
+    #[cfg(not(feature = "hot-reload"))]
+    fn decoded_file<C: Context>(
+        ctx: &mut Renderer<C>,
+        path: &Path,
+        srgb: bool,
+    ) -> Result<Handle<Self>, RendererError> {
+        let path = path.display().to_string();
+        match std::fs::read(&path) {
+            Ok(bytes) => Self::decoded(ctx, &bytes, srgb, &path),
+            Err(error) => Err(RendererError::FailedToReadFile {
+                path,
+                error: error.to_string(),
+            }),
+        }
+    }
+
+    #[cfg(feature = "hot-reload")]
+    fn decoded_file<C: Context>(
+        ctx: &mut Renderer<C>,
+        path: &Path,
+        srgb: bool,
+    ) -> Result<Handle<Self>, RendererError> {
+        let display = path.display().to_string();
+        Self::from_file_with(ctx, path, move |bytes| {
+            let (data, width, height, format) =
+                decode(bytes, srgb).map_err(|error| RendererError::FailedToReadFile {
+                    path: display.clone(),
+                    error,
+                })?;
+            Ok(super::TextureData {
+                data,
+                width,
+                height,
+                format,
+                mipmapped: true,
+            })
+        })
+    }
+}
+
+/// The texels of the image, starting with the bottom row, its size and the format they're in.
+fn decode(bytes: &[u8], srgb: bool) -> Result<(Vec<u8>, u32, u32, TextureFormat), String> {
+    let image = image::load_from_memory(bytes).map_err(|error| error.to_string())?;
+    let (width, height) = (image.width(), image.height());
+
+    let (texels, format) = match image {
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => {
+            let texels = image
+                .into_rgba32f()
+                .into_raw()
+                .into_iter()
+                .flat_map(|value| f32_to_f16(value).to_ne_bytes())
+                .collect();
+            (texels, TextureFormat::Rgba16F)
+        }
+        image => {
+            let format = match srgb {
+                true => TextureFormat::Srgb8Alpha8,
+                false => TextureFormat::Rgba8,
+            };
+            (image.into_rgba8().into_raw(), format)
+        }
+    };
+
+    let row_pitch = width as usize * format.texel_size();
+    Ok((
+        Texture::flip_y(&texels, row_pitch, height),
+        width,
+        height,
+        format,
+    ))
+}
+
+/// Half float bits of the value, rounded to the nearest one. Values beyond the range of half
+/// floats become infinite.
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        // infinity stays infinite, NaN keeps a mantissa bit
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        // subnormal, or too small for half floats
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let half = mantissa >> shift;
+        let round = (mantissa >> (shift - 1)) & 1;
+        return sign | (half + round) as u16;
+    }
+
+    let half = ((exponent as u32) << 10) | (mantissa >> 13);
+    // rounding can carry into the exponent, which gives the next power of two or infinity
+    let round = (mantissa >> 12) & 1;
+    sign | (half + round) as u16
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn half_floats() {
+        assert_eq!(f32_to_f16(0.0), 0);
+        assert_eq!(f32_to_f16(1.0), 0x3c00);
+        assert_eq!(f32_to_f16(-2.0), 0xc000);
+        assert_eq!(f32_to_f16(65504.0), 0x7bff);
+        assert_eq!(f32_to_f16(1e6), 0x7c00);
+        assert_eq!(f32_to_f16(f32::NAN) & 0x7e00, 0x7e00);
+        // smallest subnormal
+        assert_eq!(f32_to_f16(5.960_464_5e-8), 1);
+    }
+
+    /// 1x2 PNG with a red top row and a blue bottom row.
+    fn png() -> Vec<u8> {
+        let image = image::RgbImage::from_raw(1, 2, vec![255, 0, 0, 0, 0, 255]).unwrap();
+        let mut png = Vec::new();
+        image
+            .write_to(
+                &mut std::io::Cursor::new(&mut png),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn decode_png() {
+        let png = png();
+        let (texels, width, height, format) = decode(&png, true).unwrap();
+        assert_eq!((width, height, format), (1, 2, TextureFormat::Srgb8Alpha8));
+        assert_eq!(texels, [0, 0, 255, 255, 255, 0, 0, 255]);
+        assert_eq!(decode(&png, false).unwrap().3, TextureFormat::Rgba8);
+        assert!(decode(&[1, 2, 3], true).is_err());
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn from_pack() {
+        let mut writer = crate::pack::PackWriter::default();
+        writer.add("textures/stripes.png", &png());
+        writer.add("textures/broken.png", &[1, 2, 3]);
+        let pack = PackFile::from_bytes(writer.finish().unwrap()).unwrap();
+
+        let mut renderer = Renderer::new_headless().unwrap();
+        assert!(Texture::from_pack(&mut renderer, &pack, "textures/stripes.png").is_ok());
+        assert!(Texture::from_pack_linear(&mut renderer, &pack, "textures/stripes.png").is_ok());
+        assert!(matches!(
+            Texture::from_pack(&mut renderer, &pack, "textures/broken.png"),
+            Err(RendererError::FailedToReadFile { path, .. }) if path == "textures/broken.png"
+        ));
+        assert!(matches!(
+            Texture::from_pack(&mut renderer, &pack, "textures/missing.png"),
+            Err(RendererError::ResourceNotFound { .. })
+        ));
+    }
+}
@@ -0,0 +1,173 @@
+use crate::{Handle, Renderer, RendererError};
+
+use super::{
+    buffer::CreateBuffer, Buffer, BufferUsage, Context, DrawCommand, Material, Mesh, Primitive,
+    VertexLayout,
+};
+
+/// Parameters of one draw of an indirect buffer, in the layout of `glMultiDrawElementsIndirect`,
+/// see [draw_indirect][super::Backend::draw_indirect].
+///
+/// Layouts without an index buffer read the same struct, with `first_index` as the first vertex.
+/// `base_vertex` has to be 0 for them, see [from_mesh][Self::from_mesh].
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IndirectCommand {
+    pub count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub base_instance: u32,
+}
+
+impl IndirectCommand {
+    /// Bytes between the commands of an indirect buffer.
+    pub const STRIDE: usize = std::mem::size_of::<Self>();
+
+    /// Draws the range of the mesh once, with its index offset and base vertex if the layout
+    /// has an index buffer, and with its vertex offset otherwise.
+    pub fn from_mesh(mesh: &Mesh, indexed: bool) -> Self {
+        let (first_index, base_vertex) = match indexed {
+            true => (mesh.index_offset, mesh.base_vertex),
+            false => (mesh.vertex_offset, 0),
+        };
+        Self {
+            count: mesh.count,
+            instance_count: 1,
+            first_index: first_index as u32,
+            base_vertex,
+            base_instance: 0,
+        }
+    }
+
+    /// One command per mesh, e.g. for all the meshes of a static scene that share the buffers
+    /// of a vertex layout.
+    pub fn from_meshes(meshes: &[Mesh], indexed: bool) -> Vec<Self> {
+        meshes
+            .iter()
+            .map(|mesh| Self::from_mesh(mesh, indexed))
+            .collect()
+    }
+}
+
+impl Buffer {
+    /// Buffer with the commands of [draw_indirect][super::Backend::draw_indirect]s.
+    pub fn with_indirect<C: Context>(
+        ctx: &mut Renderer<C>,
+        commands: &[IndirectCommand],
+        usage: BufferUsage,
+    ) -> Result<Handle<Buffer>, RendererError> {
+        Buffer::with_vertex(ctx, commands, usage)
+    }
+}
+
+impl<C: Context> Renderer<C> {
+    /// The draw command of an indirect draw, which draws count [IndirectCommand]s of the buffer
+    /// as triangles. The mesh of the command has the layout, the number of draws as count and
+    /// the first one as index offset.
+    pub(crate) fn indirect_command(
+        &mut self,
+        layout: Handle<VertexLayout>,
+        material: Handle<Material>,
+        buffer: Handle<Buffer>,
+        count: u32,
+    ) -> Option<DrawCommand> {
+        if self.layouts.get(layout).is_none() {
+            log::warn!("Vertex Layout {layout:?} of the indirect draw not found");
+            return None;
+        }
+        let Some(stored) = self.buffers.get(buffer) else {
+            log::warn!("Indirect buffer {buffer:?} not found");
+            return None;
+        };
+        if count as usize * IndirectCommand::STRIDE > stored.size() {
+            log::warn!(
+                "{buffer:?} has only {} of the {count} indirect commands",
+                stored.size() / IndirectCommand::STRIDE
+            );
+            return None;
+        }
+
+        let mesh = Mesh::new(layout, count, Primitive::Triangles);
+        let (mesh, material) = self.resolve_draw(mesh, material)?;
+        let (material, instance_data) = self.draw_material(&mesh, material, &[])?;
+        let sort_key = self.sort_key(&mesh, material, 0.0);
+        Some(DrawCommand::new(sort_key, mesh, 0.0, material, instance_data).with_indirect(buffer))
+    }
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod test {
+    use super::*;
+    use crate::{AttributeSemantic, Backend, BufferAttributes, RecordedCommand, ShaderProgram};
+
+    const VS_SOURCE: &str = r#"
+        #version 330 core
+        layout(location = 0) in vec3 pos;
+        void main() { gl_Position = vec4(pos, 1.0); }"#;
+
+    const FS_SOURCE: &str = r#"
+        #version 330 core
+        out vec4 color;
+        void main() { color = vec4(1.0); }"#;
+
+    #[test]
+    fn draw_indirect() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let vertices =
+            Buffer::with_vertex(&mut renderer, &[0.0f32; 9 * 4], BufferUsage::StaticRead).unwrap();
+        let layout = VertexLayout::new(
+            &mut renderer,
+            &[BufferAttributes::with_semantics(
+                vertices,
+                0,
+                &[AttributeSemantic::Position],
+            )],
+        )
+        .unwrap();
+        let program = ShaderProgram::from_sources(&mut renderer, VS_SOURCE, FS_SOURCE).unwrap();
+        let material = renderer.create_material(program, &[]).unwrap();
+
+        let meshes = [0, 3, 9].map(|offset| {
+            Mesh::new(layout, 3, Primitive::Triangles)
+                .with_vertex_offset(offset)
+                .with_index_offset(offset + 1)
+                .with_base_vertex(-1)
+        });
+        let commands = IndirectCommand::from_meshes(&meshes, false);
+        assert_eq!((commands[2].first_index, commands[2].base_vertex), (9, 0));
+        let indexed = IndirectCommand::from_mesh(&meshes[2], true);
+        assert_eq!((indexed.first_index, indexed.base_vertex), (10, -1));
+        assert_eq!(IndirectCommand::STRIDE, 20);
+        let indirect =
+            Buffer::with_indirect(&mut renderer, &commands, BufferUsage::StaticWrite).unwrap();
+
+        renderer.clear_recorded_commands();
+        renderer.draw_indirect(layout, material, indirect, 3);
+        // more draws than the buffer has
+        renderer.draw_indirect(layout, material, indirect, 4);
+        renderer.update();
+
+        let draws: Vec<_> = renderer
+            .recorded_commands()
+            .iter()
+            .filter(|command| {
+                matches!(
+                    command,
+                    RecordedCommand::Draw { .. } | RecordedCommand::DrawIndirect { .. }
+                )
+            })
+            .collect();
+        assert_eq!(
+            draws,
+            [&RecordedCommand::DrawIndirect {
+                layout,
+                material,
+                buffer: indirect,
+                count: 3,
+                indexed: false,
+            }]
+        );
+        assert_eq!(renderer.frame_stats().draw_calls, 1);
+    }
+}
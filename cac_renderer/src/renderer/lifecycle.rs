@@ -0,0 +1,164 @@
+use crate::Renderer;
+
+use super::{Context, DebugView};
+
+impl<C: Context> Renderer<C> {
+    /// Releases every resource of the backend, for [suspend][super::Backend::suspend]. The slots
+    /// keep their generations, so the handles of the released resources stay invalid instead of
+    /// referring to the resources created after the [resume][super::Backend::resume]. The copies
+    /// and descriptions of the retained resources are kept to [restore][Self::restore_retained]
+    /// them, and the materials only live on the CPU, so they are kept as they are.
+    pub(crate) fn release_resources(&mut self) {
+        self.prune_retained();
+        // the placeholders aren't retained and get created again, with a new material
+        if let Some(material) = self.placeholder_material() {
+            self.materials.remove(material);
+        }
+        self.layouts.clear();
+        self.buffers.clear();
+        self.programs.clear();
+        self.compute_programs.clear();
+        self.shaders.clear();
+        self.samplers.clear();
+        self.textures.clear();
+
+        self.residency.clear();
+        self.placeholders = None;
+        self.layout_checks.clear();
+    }
+
+    /// Restores the retained resources after a [resume][super::Backend::resume], and recreates
+    /// the built-in ones, like the placeholders and the material of the debug view if its program
+    /// couldn't be restored.
+    pub(crate) fn restore_builtins(&mut self) {
+        // before the placeholders, so they don't take the slots of the retained resources
+        self.restore_retained();
+        self.create_placeholders();
+        let (programs, materials) = (&self.programs, &mut self.materials);
+        self.debug_materials.retain(|_, material| {
+            let restored = materials
+                .get(*material)
+                .is_some_and(|material| programs.get(material.program).is_some());
+            if !restored {
+                materials.remove(*material);
+            }
+            restored
+        });
+        if self.debug_view != DebugView::Off {
+            if let Err(error) = self.set_debug_view(self.debug_view) {
+                log::warn!(
+                    "Failed to restore the debug view {:?}: {error}",
+                    self.debug_view
+                );
+                self.debug_view = DebugView::Off;
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod test {
+    use crate::{
+        renderer::test_window::NoWindow, AttributeSemantic, Backend, Buffer, BufferAttributes,
+        BufferUsage, MaterialProperty, Mesh, Primitive, RecordedCommand, Sampler,
+        SamplerDescription, ShaderProgram, Texture, TextureFormat, VertexLayout,
+    };
+
+    use super::*;
+
+    #[test]
+    fn suspend_and_resume() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        renderer.set_debug_view(DebugView::VertexColor).unwrap();
+        let texture = Texture::new(&mut renderer, &[0; 4], 1, 1, TextureFormat::Rgba8).unwrap();
+        let mesh = renderer.placeholder_mesh().unwrap();
+        let material = renderer.placeholder_material().unwrap();
+
+        renderer.suspend();
+        assert!(renderer.is_suspended());
+        assert!(renderer.textures.get(texture).is_none());
+        assert!(renderer.placeholder_material().is_none());
+
+        // draws are dropped while suspended
+        renderer.clear_recorded_commands();
+        renderer.draw(mesh, material, &[]);
+        renderer.update();
+        assert!(renderer.recorded_commands().is_empty());
+
+        renderer.resume(&NoWindow).unwrap();
+        assert!(!renderer.is_suspended());
+        assert_eq!(renderer.debug_view(), DebugView::VertexColor);
+        assert!(renderer
+            .debug_materials
+            .contains_key(&DebugView::VertexColor));
+        assert!(renderer.placeholder_material().is_some());
+
+        // the handles of the released resources don't refer to the new ones
+        let new_texture = Texture::new(&mut renderer, &[0; 4], 1, 1, TextureFormat::Rgba8).unwrap();
+        assert_ne!(new_texture, texture);
+        assert!(renderer.textures.get(texture).is_none());
+    }
+
+    #[test]
+    fn draw_material_after_resume() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        renderer.set_retain_data(true);
+        let program = ShaderProgram::from_sources(
+            &mut renderer,
+            "layout(location = 0) in vec3 pos;\nvoid main() {}",
+            "uniform vec4 color;\nuniform sampler2D albedo;\nvoid main() {}",
+        )
+        .unwrap();
+        let texture = Texture::new(&mut renderer, &[0; 4], 1, 1, TextureFormat::Rgba8).unwrap();
+        let sampler = Sampler::new(&mut renderer, &SamplerDescription::nearest()).unwrap();
+        let material = renderer
+            .create_material(
+                program,
+                &[
+                    MaterialProperty::new("color", &[1.0_f32, 0.0, 0.0, 1.0]),
+                    MaterialProperty::new("albedo", &(texture, sampler)),
+                ],
+            )
+            .unwrap();
+        let buffer =
+            Buffer::with_vertex(&mut renderer, &[0.0_f32; 9], BufferUsage::StaticRead).unwrap();
+        let layout = VertexLayout::new(
+            &mut renderer,
+            &[BufferAttributes::with_semantics(
+                buffer,
+                0,
+                &[AttributeSemantic::Position],
+            )],
+        )
+        .unwrap();
+        let mesh = Mesh::new(layout, 3, Primitive::Triangles);
+
+        renderer.suspend();
+        assert!(renderer.programs.get(program).is_none());
+        renderer.resume(&NoWindow).unwrap();
+
+        // restored with the same handles
+        assert!(renderer.programs.get(program).is_some());
+        assert!(renderer.samplers.get(sampler).is_some());
+        assert!(renderer.layouts.get(layout).is_some());
+        assert_eq!(
+            renderer.materials.get(material).unwrap().data[..16],
+            [1.0_f32, 0.0, 0.0, 1.0].map(f32::to_le_bytes).concat()
+        );
+
+        renderer.clear_recorded_commands();
+        renderer.draw(mesh, material, &[]);
+        renderer.update();
+        let commands = renderer.recorded_commands();
+        assert!(commands.contains(&RecordedCommand::BindTexture {
+            unit: 0,
+            texture,
+            sampler: Some(sampler)
+        }));
+        assert!(commands.iter().any(|command| matches!(
+            command,
+            RecordedCommand::Draw { mesh: drawn, material: drawn_material, .. }
+                if *drawn == mesh && *drawn_material == material
+        )));
+    }
+}
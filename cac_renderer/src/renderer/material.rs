@@ -1,13 +1,60 @@
-use std::fmt::Display;
-
-use crate::{math, Handle, Renderer, RendererError};
+use std::{fmt::Display, sync::Arc};
+
+use crate::{math, Color32, Color8, Handle, Renderer, RendererError};
+
+use super::{
+    Context, Sampler, ShaderProgram, Texture, Uniform, UniformDescription, UniformKind,
+    UniformSlice,
+};
+
+/// The queue a material is drawn in.
+/// Queues are drawn one after another, each with its own sort order.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum RenderQueue {
+    /// Sorted by state first and front-to-back second, to reduce state changes and overdraw.
+    #[default]
+    Opaque,
+    /// Sorted back-to-front, so blending works as expected.
+    Transparent,
+    /// Drawn in submission order on top of everything else, e.g. for UI.
+    Overlay,
+}
 
-use super::{Context, ShaderProgram, Uniform, UniformDescription};
+/// How [create_material][Renderer::create_material] and
+/// [update_material][Renderer::update_material] treat properties that don't fit the uniforms of
+/// the program.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum PropertyValidation {
+    /// Fails with [UniformNotFound][RendererError::UniformNotFound] or
+    /// [PropertyMismatch][RendererError::PropertyMismatch] without changing the material.
+    #[default]
+    Strict,
+    /// Skips unknown properties and values of the wrong kind with a warning, and cuts off values
+    /// that don't fit the uniform.
+    Lenient,
+}
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Material {
     pub program: Handle<ShaderProgram>,
-    pub(crate) data: Vec<u8>,
+    pub queue: RenderQueue,
+    /// Shared with the [clones][Renderer::clone_material] of the material until one of them
+    /// changes it.
+    pub(crate) data: Arc<Vec<u8>>,
+    /// Bound to the texture unit of their index. The sampler uniforms in the data hold the unit.
+    pub(crate) textures: Vec<TextureBinding>,
+}
+
+/// A texture of a [Material] and the sampler to sample it with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct TextureBinding {
+    pub(crate) location: u32,
+    /// Kind of the sampler uniform, missing textures of 2D samplers are replaced with the
+    /// [placeholder texture][Renderer::placeholder_texture].
+    pub(crate) kind: UniformKind,
+    pub(crate) texture: Handle<Texture>,
+    /// None samples the texture with its own filtering.
+    pub(crate) sampler: Option<Handle<Sampler>>,
 }
 
 impl Material {
@@ -19,9 +66,14 @@ impl Material {
         if let Some(program) = ctx.programs.get(shader_program) {
             let mut material = Material {
                 program: shader_program,
-                data: Vec::with_capacity(program.data_size()),
+                queue: RenderQueue::default(),
+                data: Arc::new(Vec::with_capacity(program.data_size())),
+                textures: Vec::new(),
             };
 
+            if ctx.property_validation == PropertyValidation::Strict {
+                validate_properties(program.uniforms(), properties)?;
+            }
             material.update(program.uniforms(), properties);
 
             Ok(ctx.materials.push(material))
@@ -34,6 +86,46 @@ impl Material {
 }
 
 impl Material {
+    /// Moves the property values to the offsets of the new uniforms, e.g. after the program was
+    /// reloaded. Values of uniforms that were removed or changed their kind are dropped.
+    #[cfg(feature = "hot-reload")]
+    pub(super) fn remap(
+        &mut self,
+        old: &[UniformDescription],
+        new: &[UniformDescription],
+        data_size: usize,
+    ) {
+        let mut data = vec![0; data_size * 4];
+
+        for uniform in new {
+            if let Some(previous) = old
+                .iter()
+                .find(|previous| previous.name == uniform.name && previous.kind == uniform.kind)
+            {
+                let size = previous.size.min(uniform.size);
+                if let Some(value) = self.data.get(previous.offset..previous.offset + size) {
+                    data[uniform.offset..uniform.offset + size].copy_from_slice(value);
+                }
+            }
+        }
+
+        self.data = Arc::new(data);
+
+        // the units are reassigned, since textures of removed uniforms are dropped
+        for binding in std::mem::take(&mut self.textures) {
+            let name = old
+                .iter()
+                .find(|previous| previous.location == binding.location)
+                .map(|previous| &previous.name);
+            if let Some(uniform) = new
+                .iter()
+                .find(|uniform| Some(&uniform.name) == name && uniform.kind.is_sampler())
+            {
+                self.set_texture(uniform, binding.texture, binding.sampler);
+            }
+        }
+    }
+
     pub(super) fn update(
         &mut self,
         uniforms: &[UniformDescription],
@@ -47,19 +139,155 @@ impl Material {
                 }
             } {
                 match prop.value {
-                    PropertyValue::F32(value) => {
-                        value.iter().enumerate().for_each(|(index, v)| {
+                    PropertyValue::F32(_) | PropertyValue::Converted(_)
+                        if uniform.kind.is_sampler() =>
+                    {
+                        log::warn!("Property {} is a sampler", prop.property)
+                    }
+                    PropertyValue::F32(_) | PropertyValue::Converted(_) => {
+                        let value = prop.value.as_f32().unwrap_or_default();
+                        // values past the end would overwrite the next uniform
+                        let len = value.len().min(uniform.size / 4);
+                        if len < value.len() {
+                            log::warn!("Too many values for property {}", prop.property)
+                        }
+                        value[..len].iter().enumerate().for_each(|(index, v)| {
                             let bits = v.to_le_bytes();
                             let index = uniform.offset + index * 4;
-                            self.data.splice(index..(index + 4), bits);
+                            Arc::make_mut(&mut self.data).splice(index..(index + 4), bits);
                         });
                     }
+                    PropertyValue::Texture(texture, sampler) => {
+                        if uniform.kind.is_sampler() {
+                            self.set_texture(uniform, texture, sampler);
+                        } else {
+                            log::warn!("Property {} is not a sampler", prop.property)
+                        }
+                    }
+                    PropertyValue::Uniforms(_) => {
+                        log::warn!("Uniform data {} can only be set per draw", prop.property)
+                    }
                 };
             } else {
                 log::warn!("Property {} not found in ShaderProgram", prop.property)
             }
         }
     }
+
+    /// Whether drawing with the material needs the same state as drawing with the other one,
+    /// which is cheap to check for clones that weren't changed.
+    pub(crate) fn same_state(&self, other: &Material) -> bool {
+        self.program == other.program
+            && Arc::ptr_eq(&self.data, &other.data)
+            && self.textures == other.textures
+    }
+
+    /// Binds the texture to the sampler uniform, keeping the texture unit if it already had one.
+    fn set_texture(
+        &mut self,
+        uniform: &UniformDescription,
+        texture: Handle<Texture>,
+        sampler: Option<Handle<Sampler>>,
+    ) {
+        let binding = TextureBinding {
+            location: uniform.location,
+            kind: uniform.kind,
+            texture,
+            sampler,
+        };
+        let unit = match self
+            .textures
+            .iter()
+            .position(|bound| bound.location == uniform.location)
+        {
+            Some(unit) => {
+                self.textures[unit] = binding;
+                unit
+            }
+            None => {
+                self.textures.push(binding);
+                self.textures.len() - 1
+            }
+        };
+
+        let bits = (unit as i32).to_le_bytes();
+        Arc::make_mut(&mut self.data).splice(uniform.offset..(uniform.offset + 4), bits);
+    }
+}
+
+/// Checks that every property has a uniform and that its value fits it, see
+/// [PropertyValidation::Strict].
+pub(super) fn validate_properties(
+    uniforms: &[UniformDescription],
+    properties: &[MaterialProperty],
+) -> Result<(), RendererError> {
+    for prop in properties {
+        let uniform = match prop.property {
+            PropertyId::Name(name) => uniforms.iter().find(|uniform| uniform.name == name),
+            PropertyId::Location(loc) => uniforms.iter().find(|uniform| uniform.location == loc),
+        }
+        .ok_or(RendererError::UniformNotFound {
+            property: prop.property.to_string(),
+        })?;
+
+        let mismatch = |got: String| RendererError::PropertyMismatch {
+            property: prop.property.to_string(),
+            expected: describe_uniform(uniform),
+            got,
+        };
+        match prop.value.as_f32() {
+            Some(value) => {
+                if uniform.kind.is_sampler() {
+                    return Err(mismatch(format!("{} f32", value.len())));
+                }
+                // arrays can be set partially, e.g. a palette with fewer bones than the maximum
+                let components = uniform_components(uniform.kind);
+                let max = components * uniform.count as usize;
+                if value.is_empty() || value.len() % components != 0 || value.len() > max {
+                    return Err(mismatch(format!("{} f32", value.len())));
+                }
+            }
+            None if matches!(prop.value, PropertyValue::Uniforms(_)) => {
+                return Err(mismatch("uniform data".to_string()));
+            }
+            None => {
+                if !uniform.kind.is_sampler() {
+                    return Err(mismatch("a texture".to_string()));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn uniform_components(kind: UniformKind) -> usize {
+    match kind {
+        UniformKind::Vec2 => 2,
+        UniformKind::Vec3 => 3,
+        UniformKind::Vec4 | UniformKind::Mat2 => 4,
+        UniformKind::Mat3 => 9,
+        UniformKind::Mat4 => 16,
+        _ => 1,
+    }
+}
+
+fn describe_uniform(uniform: &UniformDescription) -> String {
+    if uniform.kind.is_sampler() {
+        format!("a texture for {:?}", uniform.kind)
+    } else if uniform.count > 1 {
+        format!(
+            "up to {} {:?} ({} f32 each)",
+            uniform.count,
+            uniform.kind,
+            uniform_components(uniform.kind)
+        )
+    } else {
+        format!(
+            "{:?} ({} f32)",
+            uniform.kind,
+            uniform_components(uniform.kind)
+        )
+    }
 }
 
 pub enum PropertyId<'a> {
@@ -81,6 +309,26 @@ impl<'a> From<u32> for PropertyId<'a> {
 #[derive(Debug, PartialEq)]
 pub enum PropertyValue<'a> {
     F32(&'a [f32]),
+    /// Values that had to be converted, e.g. from [Color8]s.
+    Converted(Vec<f32>),
+    /// Texture of a sampler uniform, optionally sampled with the [Sampler] instead of the
+    /// texture's own filtering. Only materials can have textures, not per-draw overrides.
+    Texture(Handle<Texture>, Option<Handle<Sampler>>),
+    /// Uniform data of the frame bound to a uniform block, see
+    /// [Renderer::alloc_uniforms][super::Renderer::alloc_uniforms]. The property is the name or
+    /// binding of the block. Only per-draw overrides can have them, not materials.
+    Uniforms(UniformSlice),
+}
+
+impl PropertyValue<'_> {
+    /// The values of [F32][Self::F32] and [Converted][Self::Converted], None for textures.
+    pub fn as_f32(&self) -> Option<&[f32]> {
+        match self {
+            PropertyValue::F32(values) => Some(values),
+            PropertyValue::Converted(values) => Some(values),
+            PropertyValue::Texture(..) | PropertyValue::Uniforms(_) => None,
+        }
+    }
 }
 
 pub struct MaterialProperty<'a> {
@@ -188,6 +436,65 @@ impl<const N: usize> AsPropertyValue for [math::Mat4; N] {
     }
 }
 
+/// Linear RGBA, like it's stored.
+impl AsPropertyValue for Color32 {
+    fn as_property_value(&self) -> PropertyValue {
+        let data = unsafe { std::slice::from_raw_parts(self as *const Color32 as *const f32, 4) };
+        PropertyValue::F32(data)
+    }
+}
+impl<const N: usize> AsPropertyValue for [Color32; N] {
+    fn as_property_value(&self) -> PropertyValue {
+        let data = unsafe { std::slice::from_raw_parts(self.as_ptr() as *const f32, 4 * N) };
+        PropertyValue::F32(data)
+    }
+}
+/// The channels are sRGB encoded, like the texels of
+/// [Srgb8Alpha8][super::TextureFormat::Srgb8Alpha8], and converted to linear RGBA. Alpha is
+/// linear already.
+impl AsPropertyValue for Color8 {
+    fn as_property_value(&self) -> PropertyValue {
+        PropertyValue::Converted(color8_to_linear(self).to_vec())
+    }
+}
+impl<const N: usize> AsPropertyValue for [Color8; N] {
+    fn as_property_value(&self) -> PropertyValue {
+        PropertyValue::Converted(self.iter().flat_map(color8_to_linear).collect())
+    }
+}
+
+fn color8_to_linear(color: &Color8) -> [f32; 4] {
+    let (r, g, b, a) = color.as_f32();
+    let (r, g, b) = Color32::from_srgb(r, g, b).as_rgb();
+    [r, g, b, a]
+}
+
+impl AsPropertyValue for UniformSlice {
+    fn as_property_value(&self) -> PropertyValue {
+        PropertyValue::Uniforms(*self)
+    }
+}
+
+impl AsPropertyValue for Handle<Texture> {
+    fn as_property_value(&self) -> PropertyValue {
+        PropertyValue::Texture(*self, None)
+    }
+}
+impl AsPropertyValue for (Handle<Texture>, Handle<Sampler>) {
+    fn as_property_value(&self) -> PropertyValue {
+        PropertyValue::Texture(self.0, Some(self.1))
+    }
+}
+
+/// E.g. the bone matrix palette of an [animated skeleton][crate::animation::Skeleton::palette].
+impl AsPropertyValue for Vec<math::Mat4> {
+    fn as_property_value(&self) -> PropertyValue {
+        let data =
+            unsafe { std::slice::from_raw_parts(self.as_ptr() as *const f32, 16 * self.len()) };
+        PropertyValue::F32(data)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -248,6 +555,28 @@ mod test {
             prop
         );
     }
+    #[test]
+    fn color_prop_value() {
+        let color = Color32::from_rgba(0.5, 0.25, 1.0, 0.75);
+        assert_eq!(
+            PropertyValue::F32(&[0.5, 0.25, 1.0, 0.75]),
+            color.as_property_value()
+        );
+        let colors = [color, Color32::WHITE];
+        assert_eq!(
+            PropertyValue::F32(&[0.5, 0.25, 1.0, 0.75, 1.0, 1.0, 1.0, 1.0]),
+            colors.as_property_value()
+        );
+
+        let color = Color8::new_rgba(255, 0, 188, 51);
+        let prop = color.as_property_value();
+        let values = prop.as_f32().unwrap();
+        assert_eq!(values[..2], [1.0, 0.0]);
+        assert!((values[2] - 0.5).abs() < 0.01);
+        assert_eq!(values[3], 0.2);
+        assert_eq!([color; 3].as_property_value().as_f32().unwrap().len(), 12);
+    }
+
     #[test]
     fn mat2_prop_value() {
         let value = math::mat2(math::vec2(10.0, 22.1234), math::vec2(11.0, 12.0));
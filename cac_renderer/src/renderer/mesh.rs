@@ -1,18 +1,120 @@
-use super::{vertex_layout::VertexLayout, Handle};
+use super::{vertex_layout::VertexLayout, Handle, Material};
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Primitive {
     Triangles,
     TriangleStrip,
+    TriangleFan,
     Lines,
     LineStrip,
+    /// Line strip that connects the last vertex with the first one.
+    LineLoop,
     Points,
+    /// Patches with the number of control points each, for programs with tessellation stages.
+    Patches(u32),
 }
 
-#[derive(Debug, Copy, Clone)]
+/// Range of the buffers of a vertex layout that is drawn as primitives.
+///
+/// Several meshes can share the buffers of one layout. Layouts with an index buffer draw count
+/// indices from the index offset, and add the base vertex to every index. Layouts without one
+/// draw count vertices from the vertex offset.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Mesh {
     pub vertex_layout: Handle<VertexLayout>,
-    pub start_index: usize,
+    /// First index in the index buffer, in indices.
+    pub index_offset: usize,
+    /// Added to the indices before they select a vertex.
+    pub base_vertex: i32,
+    /// First vertex of layouts without an index buffer.
+    pub vertex_offset: usize,
     pub count: u32,
     pub primitive: Primitive,
 }
+
+impl Mesh {
+    /// Mesh of the first count indices, or vertices, of the layout.
+    pub fn new(vertex_layout: Handle<VertexLayout>, count: u32, primitive: Primitive) -> Self {
+        Self {
+            vertex_layout,
+            index_offset: 0,
+            base_vertex: 0,
+            vertex_offset: 0,
+            count,
+            primitive,
+        }
+    }
+
+    pub fn with_index_offset(mut self, index_offset: usize) -> Self {
+        self.index_offset = index_offset;
+        self
+    }
+
+    pub fn with_base_vertex(mut self, base_vertex: i32) -> Self {
+        self.base_vertex = base_vertex;
+        self
+    }
+
+    pub fn with_vertex_offset(mut self, vertex_offset: usize) -> Self {
+        self.vertex_offset = vertex_offset;
+        self
+    }
+}
+
+/// A range of a [MultiMesh] with the material it's drawn with.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Submesh {
+    pub mesh: Mesh,
+    pub material: Handle<Material>,
+}
+
+/// Mesh made of several ranges with their own materials, like the primitives of a glTF mesh,
+/// drawn together with [draw_mesh_with_materials][super::Backend::draw_mesh_with_materials].
+///
+/// The ranges usually share the buffers of one vertex layout, but can have layouts of their own.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MultiMesh {
+    pub submeshes: Vec<Submesh>,
+}
+
+impl MultiMesh {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_submesh(mut self, mesh: Mesh, material: Handle<Material>) -> Self {
+        self.push(mesh, material);
+        self
+    }
+
+    pub fn push(&mut self, mesh: Mesh, material: Handle<Material>) {
+        self.submeshes.push(Submesh { mesh, material });
+    }
+}
+
+/// Indices that draw a list of quads, four vertices each, as [Primitive::Triangles], for
+/// backends without quads. The quads are split along the diagonal from their first to their
+/// third vertex and keep their winding. Leftover vertices that don't make a quad are skipped.
+///
+/// The indices are `u8`, the type the backends draw index buffers with, so only the first 64
+/// quads (256 vertices) can be indexed, the vertices past them are skipped too.
+pub fn quad_indices(vertex_count: usize) -> Vec<u8> {
+    let quads = vertex_count.min(usize::from(u8::MAX) + 1) / 4;
+    (0..quads as u8)
+        .flat_map(|quad| [0, 1, 2, 0, 2, 3].map(|corner| quad * 4 + corner))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn quads_to_triangles() {
+        assert_eq!(quad_indices(9), [0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7]);
+        assert!(quad_indices(3).is_empty());
+        let indices = quad_indices(1000);
+        assert_eq!(indices.len(), 64 * 6);
+        assert_eq!(indices.last(), Some(&255));
+    }
+}
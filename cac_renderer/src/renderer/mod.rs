@@ -1,40 +1,153 @@
 mod backend;
 
-pub use backend::{Backend, Context};
+#[cfg(feature = "headless")]
+pub use backend::headless::{HeadlessContext, RecordedCommand};
+#[cfg(feature = "opengl")]
+pub use backend::opengl::{
+    AutoExposure, AutoExposureSettings, ForeignGl, GpuReduction, LuminanceHistogram,
+    MotionVectorTarget, OpenGLContext, ReductionOp, ReductionResult, CAPTURE_FAR, CAPTURE_NEAR,
+    EXPOSURE_PROPERTY, MAX_HISTOGRAM_BINS,
+};
+#[cfg(all(feature = "opengl", unix))]
+pub use backend::opengl::{ExternalMemory, ExternalSemaphores, SharedTarget};
+pub use backend::{Backend, Context, DebugLabel};
+
+mod capabilities;
+pub use capabilities::{Capabilities, EmulatedFeatures};
+
+mod builder;
+pub use builder::{BackendKind, CreationReport, PendingRenderer, RendererBuilder, RendererFuture};
+
+mod dyn_renderer;
+pub use dyn_renderer::DynRenderer;
 
 mod mesh;
-pub use mesh::{Mesh, Primitive};
+pub use mesh::{quad_indices, Mesh, MultiMesh, Primitive, Submesh};
 
 mod render_target;
-pub use render_target::{ClearFlags, RenderTarget};
+pub use render_target::{ClearFlags, ExternalTarget, Image, RenderTarget, TextureFormat};
 
 mod shader;
-pub use shader::{ProgramStorage, Shader, ShaderProgram, Uniform, UniformDescription, UniformKind};
+#[cfg(fuzzing)]
+pub use shader::reflected_uniform_name;
+pub use shader::{
+    AttributeDescription, ProgramBinary, ProgramStorage, Shader, ShaderProgram, Uniform,
+    UniformDescription, UniformKind,
+};
 
 mod buffer;
 pub use buffer::{Buffer, BufferAttributes, BufferData, BufferStorage, BufferUsage, CreateBuffer};
 
+mod indirect;
+pub use indirect::IndirectCommand;
+
+mod stream_buffer;
+pub use stream_buffer::{BufferSlice, StreamBuffer};
+
 mod vertex_layout;
 pub use vertex_layout::{
     AttributeSemantic, CreateVertexLayout, VertexAttribute, VertexAttributeKind, VertexLayout,
+    VertexLayoutConfig,
 };
 
+mod compute;
+pub use compute::{ComputeProgram, MemoryBarriers};
+
 mod material;
-pub use material::{Material, MaterialProperty, PropertyId, PropertyValue};
+pub use material::{
+    Material, MaterialProperty, PropertyId, PropertyValidation, PropertyValue, RenderQueue,
+};
 
 mod draw_list;
-pub use draw_list::DrawList;
+pub use draw_list::{DrawBatch, DrawCommand, DrawList, SortKey, PARALLEL_THRESHOLD};
+
+mod frame_arena;
+pub use frame_arena::UniformSlice;
+use frame_arena::{FrameArena, InstanceData, InstanceValue};
+
+mod stats;
+pub(crate) use stats::TimedRun;
+pub use stats::{FrameStats, GpuScope, LayerStats, MaterialStats, OverdrawStats};
+
+#[cfg(feature = "hot-reload")]
+mod hot_reload;
+
+mod upload;
+pub use upload::{BufferDescription, GpuAsset, PendingUpload, UploadBudget, UploadSender};
+
+mod preprocessor;
+pub use preprocessor::ShaderPreprocessor;
+
+mod variants;
+pub use variants::ShaderVariants;
+
+mod placeholder;
+pub(crate) use placeholder::resolve_texture;
+
+mod debug_view;
+pub use debug_view::DebugView;
+
+mod debug_draw;
+pub use debug_draw::{DebugDraw, SPHERE_SEGMENTS};
+
+mod debug_numbers;
+pub use debug_numbers::{DebugNumbers, MAX_DEBUG_GLYPHS};
+
+pub mod fog;
+mod fullscreen;
+
+mod motion_blur;
+pub use motion_blur::{MotionBlur, MAX_MOTION_BLUR_SAMPLES};
+
+mod plot;
+pub use plot::{Plot, PlotArea, PlotStyle};
+
+mod sprite_batch;
+pub use sprite_batch::{SpriteBatch, SpriteRegion};
+
+mod lifecycle;
+
+mod residency;
+pub use residency::{TextureData, TextureSource};
+
+mod retention;
+
+#[cfg(all(test, feature = "headless"))]
+mod test_window;
+
+mod resource;
+pub use resource::{Resource, ResourceId, ResourceKind};
+
+mod shared;
+pub use shared::Shared;
+
+mod program_cache;
+pub use program_cache::ProgramCache;
 
 mod texture;
-pub use texture::Texture;
+pub use texture::{
+    CompressedFormat, DepthStencilMode, Swizzle, SwizzleSource, Texture, TextureKind,
+    TextureRegion, TextureViewDescription,
+};
+
+#[cfg(feature = "image")]
+mod image_file;
+
+mod test_texture;
+pub use test_texture::TestPattern;
+
+mod sampler;
+pub use sampler::{CompareFunction, CreateSampler, Filter, Sampler, SamplerDescription, WrapMode};
+
+use std::{collections::HashMap, sync::Arc};
 
 use crate::{generation_vec::GenerationVec, Handle, RendererError};
 
 /// Renderer abstraction
 ///
-/// Provides a high level API for the graphics backends. The backend can be chosen dynamically,
-/// depending on the supported features, version and graphics device capabilities. See
-/// [StaticRenderer] if you want to use a compile-time dependent one to avoid the boxing.
+/// Provides a high level API for the graphics backends. The backend is a type parameter, so it's
+/// chosen at compile time without any dispatch. See [DynRenderer] to choose it at runtime
+/// instead, depending on the supported features, version and graphics device capabilities.
 ///
 /// [Shader]s, [Mesh]es, [Texture]s can be created on the graphics device, while the renderer returns handles to them.
 /// The user is responsible to releasing them with the handles. It is recommended to
@@ -47,7 +160,30 @@ pub struct Renderer<T: Context> {
     pub layouts: GenerationVec<VertexLayout, T::VertexLayout>,
     pub shaders: GenerationVec<Shader, T::Shader>,
     pub programs: GenerationVec<ShaderProgram, T::ShaderProgram>,
-    materials: GenerationVec<Material, Material>,
+    pub compute_programs: GenerationVec<ComputeProgram, T::ComputeProgram>,
+    pub samplers: GenerationVec<Sampler, T::Sampler>,
+    pub textures: GenerationVec<Texture, T::Texture>,
+    pub(crate) materials: GenerationVec<Material, Material>,
+    stats: FrameStats,
+    frame_arena: FrameArena,
+    uploads: upload::UploadQueue<T>,
+    preprocessor: ShaderPreprocessor,
+    placeholders: Option<placeholder::Placeholders>,
+    /// Results of [VertexLayout::validate] for the layouts and programs drawn so far.
+    layout_checks: HashMap<(Handle<VertexLayout>, Handle<ShaderProgram>), bool>,
+    property_validation: PropertyValidation,
+    /// Names of the vertex inputs of the semantics, see [bind_attribute_name][Self::bind_attribute_name].
+    attribute_names: Vec<(AttributeSemantic, String)>,
+    debug_view: DebugView,
+    /// Materials of the debug views that were set so far.
+    debug_materials: HashMap<DebugView, Handle<Material>>,
+    /// Locations of the semantics in the layouts and debug programs.
+    layout_config: VertexLayoutConfig,
+    creation_report: CreationReport,
+    residency: residency::Residency,
+    retention: retention::Retention,
+    #[cfg(feature = "hot-reload")]
+    watcher: hot_reload::Watcher,
 }
 
 impl<T: Context> Renderer<T> {
@@ -59,9 +195,14 @@ impl<T: Context> Renderer<T> {
         if let Some(shader_program) = self.programs.get(program) {
             let mut material = Material {
                 program,
-                data: vec![0; shader_program.data_size() * 4],
+                queue: RenderQueue::default(),
+                data: Arc::new(vec![0; shader_program.data_size() * 4]),
+                textures: Vec::new(),
             };
 
+            if self.property_validation == PropertyValidation::Strict {
+                material::validate_properties(shader_program.uniforms(), properties)?;
+            }
             material.update(shader_program.uniforms(), properties);
 
             Ok(self.materials.push(material))
@@ -72,6 +213,76 @@ impl<T: Context> Renderer<T> {
         }
     }
 
+    /// Creates a material with the program, properties and queue of the template, with the
+    /// overrides applied on top.
+    ///
+    /// The clone shares the data of the template until either of them is changed, so clones that
+    /// only differ in a few values are cheap to create, and draws of unchanged clones don't
+    /// rebind anything.
+    pub fn clone_material(
+        &mut self,
+        template: Handle<Material>,
+        overrides: &[MaterialProperty],
+    ) -> Result<Handle<Material>, RendererError> {
+        let mut material = self
+            .materials
+            .get(template)
+            .ok_or(RendererError::ResourceNotFound {
+                resource: format!("Material: {template:?}"),
+            })?
+            .clone();
+
+        if !overrides.is_empty() {
+            let program =
+                self.programs
+                    .get(material.program)
+                    .ok_or(RendererError::ResourceNotFound {
+                        resource: format!("Shaderprogram: {:?}", material.program),
+                    })?;
+            if self.property_validation == PropertyValidation::Strict {
+                material::validate_properties(program.uniforms(), overrides)?;
+            }
+            material.update(program.uniforms(), overrides);
+        }
+
+        Ok(self.materials.push(material))
+    }
+
+    /// The preprocessor that is applied to all shader sources before they are compiled.
+    pub fn shader_preprocessor(&mut self) -> &mut ShaderPreprocessor {
+        &mut self.preprocessor
+    }
+
+    /// Statistics of the last [update][Backend::update].
+    pub fn frame_stats(&self) -> &FrameStats {
+        &self.stats
+    }
+
+    /// The backend the renderer was created with, and the errors of the backends the
+    /// [RendererBuilder] tried before.
+    pub fn creation_report(&self) -> &CreationReport {
+        &self.creation_report
+    }
+
+    /// Uniforms of the program, e.g. to generate the UI of its materials in an editor.
+    pub fn program_uniforms(&self, handle: Handle<ShaderProgram>) -> Option<&[UniformDescription]> {
+        self.programs
+            .get(handle)
+            .map(|program| program.uniforms().as_slice())
+    }
+
+    /// Vertex inputs of the program, e.g. to check which [VertexLayout]s fit it.
+    pub fn program_attributes(
+        &self,
+        handle: Handle<ShaderProgram>,
+    ) -> Option<&[AttributeDescription]> {
+        self.programs
+            .get(handle)
+            .map(|program| program.attributes())
+    }
+
+    /// Uploads the uniform data of the material to its program. The program isn't necessarily
+    /// bound afterwards.
     pub fn use_material(&mut self, handle: Handle<Material>) {
         if let Some(material) = self.materials.get(handle) {
             if let Some(program) = self.programs.get_mut(material.program) {
@@ -80,13 +291,331 @@ impl<T: Context> Renderer<T> {
         }
     }
 
-    pub fn update_material(&mut self, handle: Handle<Material>, properties: &[MaterialProperty]) {
+    /// Resolves per-draw property overrides into (location, values) pairs of the material's
+    /// program and stores them in the frame arena. Properties the program doesn't have are skipped
+    /// with a warning.
+    pub(crate) fn instance_data(
+        &mut self,
+        material: Handle<Material>,
+        properties: &[MaterialProperty],
+    ) -> InstanceData {
+        if properties.is_empty() {
+            return InstanceData::default();
+        }
+
+        let program = match self.materials.get(material) {
+            Some(material) => match self.programs.get(material.program) {
+                Some(program) => program,
+                None => {
+                    log::warn!("Program not found!");
+                    return InstanceData::default();
+                }
+            },
+            None => {
+                log::warn!("Material not found!");
+                return InstanceData::default();
+            }
+        };
+
+        self.frame_arena
+            .alloc(resolve_properties(program, properties))
+    }
+
+    /// Copies the value into the uniform data of the frame, for a uniform block of a draw
+    /// recorded in the same frame, with [PropertyValue::Uniforms]. The data of all draws is
+    /// uploaded to a uniform buffer at once, so per-draw data like model matrices doesn't need a
+    /// uniform upload per draw.
+    ///
+    /// The value has to match the `std140` layout of the block, e.g. a `#[repr(C)]` struct with
+    /// vec3s padded to 16 bytes.
+    pub fn alloc_uniforms<U: Copy>(&mut self, value: &U) -> UniformSlice {
+        let bytes = unsafe {
+            std::slice::from_raw_parts(value as *const U as *const u8, std::mem::size_of::<U>())
+        };
+        self.frame_arena.alloc_uniform_data(bytes)
+    }
+
+    /// Changes the [RenderQueue] the material is drawn in.
+    pub fn set_render_queue(&mut self, handle: Handle<Material>, queue: RenderQueue) {
         if let Some(material) = self.materials.get_mut(handle) {
-            if let Some(shader_program) = self.programs.get(material.program) {
-                material.update(shader_program.uniforms(), properties);
+            material.queue = queue;
+        }
+    }
+
+    /// Builds the [SortKey] for a draw of the mesh with the material.
+    ///
+    /// The draw also counts as a use of its resources for [compact][Self::compact].
+    pub(crate) fn sort_key(
+        &mut self,
+        mesh: &Mesh,
+        material: Handle<Material>,
+        depth: f32,
+    ) -> SortKey {
+        self.record_uses(mesh, material);
+        build_sort_key(
+            &self.materials,
+            &self.programs,
+            &self.layouts,
+            mesh,
+            material,
+            depth,
+        )
+    }
+
+    fn record_uses(&mut self, mesh: &Mesh, material: Handle<Material>) {
+        if let Some(program) = self
+            .materials
+            .get(material)
+            .map(|material| material.program)
+        {
+            self.programs.record_use(program);
+        }
+        self.materials.record_use(material);
+        self.layouts.record_use(mesh.vertex_layout);
+        self.use_textures(material);
+    }
+
+    /// Turns the draws of the batches into commands, in the order of the batches.
+    /// Missing resources are replaced like in [draw_at_depth][Backend::draw_at_depth].
+    ///
+    /// With the `parallel` feature, the sort keys of large batches are built on the rayon thread
+    /// pool.
+    pub(crate) fn batch_commands(&mut self, batches: &[DrawBatch]) -> Vec<DrawCommand>
+    where
+        T::ShaderProgram: Sync,
+        T::VertexLayout: Sync,
+    {
+        let mut draws = Vec::with_capacity(batches.iter().map(DrawBatch::len).sum());
+        for (mesh, material, depth) in batches.iter().flat_map(|batch| &batch.draws) {
+            let Some((mesh, material)) = self.resolve_draw(*mesh, *material) else {
+                continue;
+            };
+            if let Some((material, instance_data)) = self.draw_material(&mesh, material, &[]) {
+                self.record_uses(&mesh, material);
+                draws.push((mesh, material, *depth, instance_data));
             }
         }
+
+        let (materials, programs, layouts) = (&self.materials, &self.programs, &self.layouts);
+        let command = |(mesh, material, depth, instance_data): &(
+            Mesh,
+            Handle<Material>,
+            f32,
+            InstanceData,
+        )| {
+            let sort_key = build_sort_key(materials, programs, layouts, mesh, *material, *depth);
+            DrawCommand::new(sort_key, *mesh, *depth, *material, *instance_data)
+        };
+
+        #[cfg(feature = "parallel")]
+        if draws.len() >= PARALLEL_THRESHOLD {
+            use rayon::prelude::*;
+
+            return draws.par_iter().map(command).collect();
+        }
+
+        draws.iter().map(command).collect()
+    }
+
+    /// Reorders the stored resources by how often they were drawn since the last compaction, so
+    /// the most used ones are next to each other in memory and first in the sorted draw list.
+    ///
+    /// Handles stay valid. Call it every now and then, e.g. after loading a level, not every frame.
+    pub fn compact(&mut self) {
+        self.buffers.compact();
+        self.layouts.compact();
+        self.shaders.compact();
+        self.programs.compact();
+        self.compute_programs.compact();
+        self.samplers.compact();
+        self.textures.compact();
+        self.materials.compact();
+    }
+
+    /// Sets the properties of the material. With [PropertyValidation::Strict], nothing is changed
+    /// if one of them doesn't fit.
+    pub fn update_material(
+        &mut self,
+        handle: Handle<Material>,
+        properties: &[MaterialProperty],
+    ) -> Result<(), RendererError> {
+        let material = self
+            .materials
+            .get_mut(handle)
+            .ok_or(RendererError::ResourceNotFound {
+                resource: format!("Material: {handle:?}"),
+            })?;
+        let shader_program =
+            self.programs
+                .get(material.program)
+                .ok_or(RendererError::ResourceNotFound {
+                    resource: format!("Shaderprogram: {:?}", material.program),
+                })?;
+
+        if self.property_validation == PropertyValidation::Strict {
+            material::validate_properties(shader_program.uniforms(), properties)?;
+        }
+        material.update(shader_program.uniforms(), properties);
+        Ok(())
+    }
+
+    /// Binds vertex inputs with the name to the location of the semantic, e.g.
+    /// `Position -> "a_position"`, so shaders without `layout(location = N)` qualifiers work with
+    /// the [layout config][Self::layout_config].
+    ///
+    /// Applies to programs linked afterwards. Semantics without a location are skipped.
+    pub fn bind_attribute_name(&mut self, semantic: AttributeSemantic, name: &str) {
+        match self
+            .attribute_names
+            .iter_mut()
+            .find(|(bound, _)| *bound == semantic)
+        {
+            Some((_, bound_name)) => *bound_name = name.to_string(),
+            None => self.attribute_names.push((semantic, name.to_string())),
+        }
     }
+
+    /// Locations of the [bound attribute names][Self::bind_attribute_name].
+    pub(crate) fn attribute_locations(&self) -> Vec<(String, u8)> {
+        self.attribute_names
+            .iter()
+            .filter_map(
+                |(semantic, name)| match self.layout_config.location(*semantic) {
+                    Some(location) => Some((name.clone(), location)),
+                    None => {
+                        log::warn!("{semantic} has no location to bind {name} to");
+                        None
+                    }
+                },
+            )
+            .collect()
+    }
+
+    /// Locations of the semantics in the [VertexLayout]s created afterwards. Layouts that were
+    /// already created keep their locations.
+    pub fn set_layout_config(&mut self, config: VertexLayoutConfig) {
+        self.layout_config = config;
+    }
+
+    pub fn layout_config(&self) -> &VertexLayoutConfig {
+        &self.layout_config
+    }
+
+    /// How properties that don't fit their uniforms are treated, strict by default.
+    pub fn set_property_validation(&mut self, validation: PropertyValidation) {
+        self.property_validation = validation;
+    }
+}
+
+/// Builds the [SortKey] for a draw of the mesh with the material.
+/// The layer is the [RenderQueue] of the material. The target isn't part of the key, since every
+/// [update][Backend::update] draws its whole list into the one active target, the screen or an
+/// external or texture target.
+fn build_sort_key<P, L>(
+    materials: &GenerationVec<Material, Material>,
+    programs: &GenerationVec<ShaderProgram, P>,
+    layouts: &GenerationVec<VertexLayout, L>,
+    mesh: &Mesh,
+    material: Handle<Material>,
+    depth: f32,
+) -> SortKey {
+    let (program, queue) = materials
+        .get(material)
+        .map(|material| (material.program, material.queue))
+        .unwrap_or_default();
+
+    let layer = queue as u8;
+    let depth = SortKey::quantize_depth(depth);
+    let program = programs.sort_index(program).unwrap_or_default();
+    let layout = layouts.sort_index(mesh.vertex_layout).unwrap_or_default();
+    let material = materials.sort_index(material).unwrap_or_default();
+
+    match queue {
+        RenderQueue::Opaque => SortKey::new(layer, program, material, layout, depth),
+        RenderQueue::Transparent => SortKey::back_to_front(layer, depth, program, material, layout),
+        RenderQueue::Overlay => SortKey::in_order(layer),
+    }
+}
+
+/// Whether the materials can be drawn with the same bound state, see [Material::same_state].
+pub(crate) fn shares_state(
+    materials: &GenerationVec<Material, Material>,
+    bound: Handle<Material>,
+    material: Handle<Material>,
+) -> bool {
+    match (materials.get(bound), materials.get(material)) {
+        (Some(bound), Some(material)) => bound.same_state(material),
+        _ => false,
+    }
+}
+
+/// Sets the label of the resource, see [DebugLabel].
+fn set_debug_label<K: std::fmt::Debug, V: DebugLabel>(
+    resources: &mut GenerationVec<K, V>,
+    handle: Handle<K>,
+    label: &str,
+    resource: &str,
+) -> Result<(), RendererError> {
+    resources
+        .get_mut(handle)
+        .ok_or(RendererError::ResourceNotFound {
+            resource: format!("{resource}: {handle:?}"),
+        })?
+        .set_label(label);
+    Ok(())
+}
+
+/// Resolves the properties into the uniforms and uniform blocks of the program. Properties the
+/// program doesn't have are skipped with a warning.
+pub(crate) fn resolve_properties<'a, 'p, U: Uniform>(
+    program: &'p U,
+    properties: &'a [MaterialProperty],
+) -> impl Iterator<Item = InstanceValue<'a>> + use<'a, 'p, U> {
+    properties.iter().filter_map(|prop| {
+        if let PropertyValue::Uniforms(slice) = prop.value {
+            let blocks = program.uniform_blocks();
+            let binding = match prop.property {
+                PropertyId::Name(name) => blocks
+                    .iter()
+                    .find_map(|(block, binding)| (block == name).then_some(*binding)),
+                PropertyId::Location(binding) => blocks
+                    .iter()
+                    .any(|(_, bound)| *bound == binding)
+                    .then_some(binding),
+            };
+            if binding.is_none() {
+                log::warn!("Uniform block {} not found in ShaderProgram", prop.property);
+            }
+            return binding.map(|binding| InstanceValue::Block(binding, slice));
+        }
+
+        let location = match prop.property {
+            PropertyId::Name(name) => program.get_uniform_location(name),
+            PropertyId::Location(location) => program
+                .uniforms()
+                .iter()
+                .any(|uniform| uniform.location == location)
+                .then_some(location),
+        };
+
+        let location = match location {
+            Some(location) => location,
+            None => {
+                log::warn!("Property {} not found in ShaderProgram", prop.property);
+                return None;
+            }
+        };
+
+        match &prop.value {
+            PropertyValue::F32(values) => Some(InstanceValue::Uniform(location, values)),
+            PropertyValue::Converted(values) => Some(InstanceValue::Uniform(location, values)),
+            PropertyValue::Texture(..) => {
+                log::warn!("Texture property {} can't be set per draw", prop.property);
+                None
+            }
+            PropertyValue::Uniforms(_) => None,
+        }
+    })
 }
 
 //pub trait GraphicsStorage {
@@ -0,0 +1,127 @@
+use crate::{Handle, Renderer, RendererError};
+
+use super::{fullscreen::FullscreenPass, Backend, Context, MaterialProperty, Texture};
+
+/// Most samples a [MotionBlur] takes per pixel.
+pub const MAX_MOTION_BLUR_SAMPLES: u32 = 32;
+
+const MOTION_BLUR_FS_SOURCE: &str = r##"
+    #version 330 core
+    in vec2 frag_uv;
+    out vec4 result;
+    uniform sampler2D source;
+    uniform sampler2D velocity;
+    uniform float samples;
+    uniform float shutter_scale;
+    void main() {
+        vec2 motion = texture(velocity, frag_uv).rg * shutter_scale;
+        int count = int(samples);
+        vec4 sum = vec4(0.0);
+        // the loop needs a constant bound, MAX_MOTION_BLUR_SAMPLES
+        for (int i = 0; i < 32; i++) {
+            if (i >= count) break;
+            float t = count > 1 ? float(i) / float(count - 1) - 0.5 : 0.0;
+            sum += texture(source, frag_uv - motion * t);
+        }
+        result = sum / float(count);
+    }"##;
+
+/// Fullscreen pass that blurs the colors of a frame along the velocities of its
+/// [motion vectors][crate::scene::Scene::draw_motion_vectors].
+///
+/// Every pixel averages [samples][Self::samples] colors on a line through it, along the distance
+/// the pixel moved since the previous frame, scaled by the [shutter_scale][Self::shutter_scale].
+///
+/// ```no_run
+/// # use cac_renderer::{Backend, Handle, MotionBlur, Renderer, Texture};
+/// # fn frame<C: cac_renderer::Context>(renderer: &mut Renderer<C>, frame: Handle<Texture>, velocity: Handle<Texture>)
+/// # where Renderer<C>: Backend {
+/// let mut blur = MotionBlur::new(renderer, frame, velocity).unwrap();
+/// blur.samples = 12;
+/// // the shutter is open for half of the frame
+/// blur.shutter_scale = 0.5;
+/// blur.draw(renderer);
+/// renderer.update();
+/// # }
+/// ```
+///
+/// The pass is drawn in the [Overlay][crate::RenderQueue::Overlay] queue, on top of whatever else is
+/// drawn into the active target.
+#[derive(Debug)]
+pub struct MotionBlur {
+    pass: FullscreenPass,
+    /// Colors per pixel, up to [MAX_MOTION_BLUR_SAMPLES]. 8 by default, 1 turns the blur off.
+    pub samples: u32,
+    /// Fraction of the frame the shutter is open for, which scales the length of the blur. 1 by
+    /// default, which blurs along the whole motion. Scaling it by the target frame time over the
+    /// current one keeps the blur the same at any frame rate.
+    pub shutter_scale: f32,
+}
+
+impl MotionBlur {
+    /// Blurs the source colors with the velocities in the red and green channels of the velocity
+    /// texture, e.g. the texture of a [MotionVectorTarget][crate::MotionVectorTarget].
+    pub fn new<C: Context>(
+        ctx: &mut Renderer<C>,
+        source: Handle<Texture>,
+        velocity: Handle<Texture>,
+    ) -> Result<Self, RendererError> {
+        let pass = FullscreenPass::new(
+            ctx,
+            MOTION_BLUR_FS_SOURCE,
+            &[
+                MaterialProperty::new("source", &source),
+                MaterialProperty::new("velocity", &velocity),
+            ],
+        )?;
+
+        Ok(Self {
+            pass,
+            samples: 8,
+            shutter_scale: 1.0,
+        })
+    }
+
+    /// Draws the blurred frame into the active target.
+    pub fn draw<C: Context>(&self, ctx: &mut Renderer<C>)
+    where
+        Renderer<C>: Backend,
+    {
+        let samples = self.samples.clamp(1, MAX_MOTION_BLUR_SAMPLES) as f32;
+        self.pass.draw(
+            ctx,
+            &[
+                MaterialProperty::new("samples", &samples),
+                MaterialProperty::new("shutter_scale", &self.shutter_scale),
+            ],
+        );
+    }
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod test {
+    use super::*;
+    use crate::{RecordedCommand, TextureFormat};
+
+    #[test]
+    fn draw_blur() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let [source, velocity] = [TextureFormat::Rgba8, TextureFormat::Rgba16F].map(|format| {
+            Texture::new(&mut renderer, &vec![0; format.texel_size()], 1, 1, format).unwrap()
+        });
+        let blur = MotionBlur::new(&mut renderer, source, velocity).unwrap();
+
+        renderer.clear_recorded_commands();
+        blur.draw(&mut renderer);
+        renderer.update();
+        let draws: Vec<_> = renderer
+            .recorded_commands()
+            .iter()
+            .filter_map(|command| match command {
+                RecordedCommand::Draw { mesh, material, .. } => Some((mesh.count, *material)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(draws, [(3, blur.pass.material)]);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::{generation_vec::GenerationVec, Handle, Renderer, RendererError};
+
+use super::{
+    material::TextureBinding, AttributeSemantic, Buffer, BufferAttributes, BufferUsage, Context,
+    Material, Mesh, Primitive, ShaderProgram, Texture, TextureFormat, UniformKind, VertexLayout,
+};
+
+const ERROR_VS_SOURCE: &str = r##"
+    #version 330 core
+    layout(location = 0) in vec3 pos;
+    void main() { gl_Position = vec4(pos, 1.0); }"##;
+
+const ERROR_FS_SOURCE: &str = r##"
+    #version 330 core
+    out vec4 result;
+    void main() { result = vec4(1.0, 0.0, 1.0, 1.0); }"##;
+
+#[rustfmt::skip]
+const QUAD_VERTICES: [f32; 12] = [
+    -0.5, -0.5, 0.0,
+    -0.5,  0.5, 0.0,
+     0.5,  0.5, 0.0,
+     0.5, -0.5, 0.0,
+];
+const QUAD_INDICES: [u8; 6] = [0, 1, 2, 2, 3, 0];
+
+/// Width and height of the checker texture, in texels.
+const CHECKER_SIZE: u32 = 16;
+/// Width and height of one cell of the checker texture, in texels.
+const CHECKER_CELL: u32 = 4;
+
+/// Magenta and black cells, which stand out in any scene.
+fn checker_texels() -> Vec<u8> {
+    (0..CHECKER_SIZE * CHECKER_SIZE)
+        .flat_map(|texel| {
+            let (x, y) = (texel % CHECKER_SIZE, texel / CHECKER_SIZE);
+            if (x / CHECKER_CELL + y / CHECKER_CELL) % 2 == 0 {
+                [255, 0, 255, 255]
+            } else {
+                [0, 0, 0, 255]
+            }
+        })
+        .collect()
+}
+
+/// Built-in resources that are drawn instead of missing ones, so a single missing resource
+/// doesn't break the whole frame.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Placeholders {
+    /// Unit quad in the XY plane.
+    mesh: Mesh,
+    /// Material with a program that draws everything in magenta.
+    material: Handle<Material>,
+    /// Magenta checker, bound instead of missing 2D textures.
+    texture: Handle<Texture>,
+}
+
+impl<C: Context> Renderer<C> {
+    /// Creates the placeholders. Called by the backends after the renderer was created.
+    /// If it fails, draws with missing resources are skipped instead.
+    pub(crate) fn create_placeholders(&mut self) {
+        // they are created again on resume, so their data and descriptions aren't retained
+        let retention = std::mem::take(&mut self.retention);
+        let placeholders = self.try_create_placeholders();
+        self.retention = retention;
+
+        match placeholders {
+            Ok(placeholders) => self.placeholders = Some(placeholders),
+            Err(error) => log::warn!("Failed to create placeholders: {error}"),
+        }
+    }
+
+    fn try_create_placeholders(&mut self) -> Result<Placeholders, RendererError> {
+        let program = ShaderProgram::from_sources(self, ERROR_VS_SOURCE, ERROR_FS_SOURCE)?;
+        let material = self.create_material(program, &[])?;
+        let texture = Texture::new(
+            self,
+            &checker_texels(),
+            CHECKER_SIZE,
+            CHECKER_SIZE,
+            TextureFormat::Rgba8,
+        )?;
+
+        let vertex_buffer = Buffer::with_vertex(self, &QUAD_VERTICES, BufferUsage::StaticRead)?;
+        let index_buffer = Buffer::with_index(self, &QUAD_INDICES, BufferUsage::StaticRead)?;
+        let vertex_layout = VertexLayout::new(
+            self,
+            &[
+                BufferAttributes::with_semantics(vertex_buffer, 0, &[AttributeSemantic::Position]),
+                BufferAttributes::with_index(index_buffer, 0),
+            ],
+        )?;
+
+        Ok(Placeholders {
+            mesh: Mesh::new(
+                vertex_layout,
+                QUAD_INDICES.len() as u32,
+                Primitive::Triangles,
+            ),
+            material,
+            texture,
+        })
+    }
+
+    /// Unit quad in the XY plane, drawn instead of meshes with a missing vertex layout.
+    pub fn placeholder_mesh(&self) -> Option<Mesh> {
+        self.placeholders.map(|placeholders| placeholders.mesh)
+    }
+
+    /// Magenta material, drawn instead of missing materials or materials with a missing program.
+    pub fn placeholder_material(&self) -> Option<Handle<Material>> {
+        self.placeholders.map(|placeholders| placeholders.material)
+    }
+
+    /// Magenta checker texture, bound instead of missing textures of 2D samplers.
+    pub fn placeholder_texture(&self) -> Option<Handle<Texture>> {
+        self.placeholders.map(|placeholders| placeholders.texture)
+    }
+
+    /// Replaces missing resources of the draw with the placeholders, with a warning.
+    /// Returns None if there are no placeholders to replace them with, or if the vertex layout
+    /// doesn't fit the program, see [VertexLayout::validate].
+    pub(crate) fn resolve_draw(
+        &mut self,
+        mut mesh: Mesh,
+        mut material: Handle<Material>,
+    ) -> Option<(Mesh, Handle<Material>)> {
+        if self.layouts.get(mesh.vertex_layout).is_none() {
+            log::warn!("Vertex Layout {:?} not found", mesh.vertex_layout);
+            mesh = self.placeholder_mesh()?;
+        }
+
+        let has_program = self
+            .materials
+            .get(material)
+            .map(|material| self.programs.get(material.program).is_some());
+        match has_program {
+            Some(true) => {}
+            Some(false) => {
+                log::warn!("Program of Material {material:?} not found");
+                material = self.placeholder_material()?;
+            }
+            None => {
+                log::warn!("Material {material:?} not found");
+                material = self.placeholder_material()?;
+            }
+        }
+
+        self.layout_fits(mesh.vertex_layout, material)
+            .then_some((mesh, material))
+    }
+
+    /// Validates the layout once per program, later draws reuse the result.
+    pub(crate) fn layout_fits(
+        &mut self,
+        layout: Handle<VertexLayout>,
+        material: Handle<Material>,
+    ) -> bool {
+        let Some(program) = self
+            .materials
+            .get(material)
+            .map(|material| material.program)
+        else {
+            return false;
+        };
+        if let Some(fits) = self.layout_checks.get(&(layout, program)) {
+            return *fits;
+        }
+
+        let fits = match VertexLayout::validate(self, layout, material) {
+            Ok(()) => true,
+            Err(error) => {
+                log::warn!("Skipping draws of {layout:?} with {program:?}: {error}");
+                false
+            }
+        };
+        self.layout_checks.insert((layout, program), fits);
+        fits
+    }
+}
+
+/// The texture to bind for the binding of a material, the placeholder texture if the texture is
+/// missing, with a warning. Returns None if there is no texture to bind, e.g. for missing
+/// cubemaps, which the placeholder can't replace.
+///
+/// Takes the textures instead of the renderer, so the backends can call it while they hold their
+/// context.
+pub(crate) fn resolve_texture<T>(
+    textures: &GenerationVec<Texture, T>,
+    placeholder: Option<Handle<Texture>>,
+    binding: &TextureBinding,
+) -> Option<Handle<Texture>> {
+    if textures.get(binding.texture).is_some() {
+        return Some(binding.texture);
+    }
+
+    log::warn!("Texture {:?} not found", binding.texture);
+    placeholder.filter(|_| binding.kind == UniformKind::Sampler2D)
+}
@@ -0,0 +1,266 @@
+use std::collections::VecDeque;
+
+use crate::{
+    math::{Vec2, Vec3},
+    Color32, Handle, Renderer, RendererError,
+};
+
+use super::{
+    AttributeSemantic, Backend, BufferAttributes, Context, Material, MaterialProperty, Mesh,
+    Primitive, RenderQueue, ShaderProgram, StreamBuffer, VertexLayout,
+};
+
+const PLOT_VS_SOURCE: &str = r##"
+    #version 330 core
+    layout(location = 0) in vec3 pos;
+    void main() { gl_Position = vec4(pos, 1.0); }"##;
+
+const PLOT_FS_SOURCE: &str = r##"
+    #version 330 core
+    out vec4 result;
+    uniform vec4 color;
+    void main() { result = color; }"##;
+
+const VERTEX_SIZE: usize = std::mem::size_of::<Vec3>();
+
+/// How a [Plot] draws its samples.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum PlotStyle {
+    /// Line through the samples.
+    #[default]
+    Line,
+    /// The area between the samples and the bottom of the plot.
+    Area,
+}
+
+/// Where a [Plot] is drawn, in clip space, and the sample values at its bottom and top.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PlotArea {
+    /// Bottom left corner.
+    pub position: Vec2,
+    pub size: Vec2,
+    /// Values at the bottom and the top, samples outside are clamped. None fits the range to
+    /// the smallest and largest sample.
+    pub range: Option<(f32, f32)>,
+}
+
+/// History of a value, like frame times or custom counters, drawn as graph on top of the frame.
+///
+/// The plot keeps the last samples up to its capacity and writes the vertices into its own
+/// [StreamBuffer] every time it's [drawn][Self::draw], the oldest sample on the left:
+///
+/// ```no_run
+/// # use cac_renderer::{math::*, Color32, Plot, PlotArea, PlotStyle, Renderer};
+/// # fn frame<C: cac_renderer::Context>(renderer: &mut Renderer<C>, plot: &mut Plot)
+/// # where Renderer<C>: cac_renderer::Backend {
+/// plot.push(renderer.frame_stats().draw_calls as f32);
+/// let area = PlotArea {
+///     position: vec2(-1.0, -1.0),
+///     size: vec2(0.5, 0.2),
+///     range: None,
+/// };
+/// plot.draw(renderer, &area, PlotStyle::Area, Color32::GREEN).unwrap();
+/// # }
+/// ```
+///
+/// Like [DebugNumbers][super::DebugNumbers], the plots are drawn in the
+/// [Overlay][RenderQueue::Overlay] queue.
+#[derive(Debug)]
+pub struct Plot {
+    samples: VecDeque<f32>,
+    capacity: usize,
+    vertices: StreamBuffer,
+    vertex_layout: Handle<VertexLayout>,
+    material: Handle<Material>,
+    /// Vertices of the last draw, kept to not allocate every frame.
+    scratch: Vec<Vec3>,
+}
+
+impl Plot {
+    /// Plot of the last capacity samples.
+    pub fn new<C: Context>(ctx: &mut Renderer<C>, capacity: usize) -> Result<Self, RendererError> {
+        let capacity = capacity.max(2);
+        let program = ShaderProgram::from_sources(ctx, PLOT_VS_SOURCE, PLOT_FS_SOURCE)?;
+        let material = ctx.create_material(program, &[])?;
+        ctx.set_render_queue(material, RenderQueue::Overlay);
+
+        // an area has two vertices per sample
+        let vertices = StreamBuffer::new(ctx, capacity * 2 * VERTEX_SIZE)?;
+        let vertex_layout = VertexLayout::new(
+            ctx,
+            &[BufferAttributes::with_semantics(
+                vertices.buffer(),
+                0,
+                &[AttributeSemantic::Position],
+            )],
+        )?;
+
+        Ok(Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            vertices,
+            vertex_layout,
+            material,
+            scratch: Vec::new(),
+        })
+    }
+
+    /// Adds a sample, and drops the oldest one if the plot is full.
+    pub fn push(&mut self, sample: f32) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn samples(&self) -> impl Iterator<Item = f32> + '_ {
+        self.samples.iter().copied()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Draws the samples into the area. Plots with less than two samples aren't drawn. Can be
+    /// called once per frame, the vertices of further draws don't fit into the stream buffer.
+    pub fn draw<C: Context>(
+        &mut self,
+        renderer: &mut Renderer<C>,
+        area: &PlotArea,
+        style: PlotStyle,
+        color: Color32,
+    ) -> Result<(), RendererError>
+    where
+        Renderer<C>: Backend,
+    {
+        if self.samples.len() < 2 {
+            return Ok(());
+        }
+
+        plot_vertices(&self.samples, self.capacity, area, style, &mut self.scratch);
+        let slice = self.vertices.push(renderer, &self.scratch)?;
+        let primitive = match style {
+            PlotStyle::Line => Primitive::LineStrip,
+            PlotStyle::Area => Primitive::TriangleStrip,
+        };
+        let mesh = Mesh::new(self.vertex_layout, slice.count(VERTEX_SIZE), primitive)
+            .with_vertex_offset(slice.first_element(VERTEX_SIZE));
+
+        renderer.draw(
+            mesh,
+            self.material,
+            &[MaterialProperty::new("color", &color)],
+        );
+        Ok(())
+    }
+}
+
+/// Clip space positions of the samples, spread over the full width as if the plot was full, so
+/// new samples appear on the right. Areas alternate between the bottom and the sample.
+fn plot_vertices(
+    samples: &VecDeque<f32>,
+    capacity: usize,
+    area: &PlotArea,
+    style: PlotStyle,
+    vertices: &mut Vec<Vec3>,
+) {
+    let (min, max) = area.range.unwrap_or_else(|| {
+        samples
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), sample| {
+                (min.min(*sample), max.max(*sample))
+            })
+    });
+    let height = max - min;
+    let step = area.size.x / (capacity - 1) as f32;
+    let first = capacity - samples.len();
+
+    vertices.clear();
+    for (index, sample) in samples.iter().enumerate() {
+        let x = area.position.x + (first + index) as f32 * step;
+        let relative = match height > 0.0 {
+            true => ((sample - min) / height).clamp(0.0, 1.0),
+            false => 0.5,
+        };
+        let y = area.position.y + relative * area.size.y;
+        if style == PlotStyle::Area {
+            vertices.push(Vec3::new(x, area.position.y, 0.0));
+        }
+        vertices.push(Vec3::new(x, y, 0.0));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_positions() {
+        let samples = VecDeque::from([1.0, 3.0, 2.0]);
+        let area = PlotArea {
+            position: Vec2::new(-1.0, -1.0),
+            size: Vec2::new(1.0, 1.0),
+            range: None,
+        };
+        let mut vertices = Vec::new();
+        plot_vertices(&samples, 5, &area, PlotStyle::Line, &mut vertices);
+        // the two missing samples leave the left half empty
+        assert_eq!(
+            vertices,
+            [
+                Vec3::new(-0.5, -1.0, 0.0),
+                Vec3::new(-0.25, 0.0, 0.0),
+                Vec3::new(0.0, -0.5, 0.0),
+            ]
+        );
+
+        let area = PlotArea {
+            range: Some((0.0, 2.0)),
+            ..area
+        };
+        plot_vertices(&samples, 3, &area, PlotStyle::Area, &mut vertices);
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(vertices[0], Vec3::new(-1.0, -1.0, 0.0));
+        // clamped to the top
+        assert_eq!(vertices[3], Vec3::new(-0.5, 0.0, 0.0));
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn draw_history() {
+        use crate::RecordedCommand;
+
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mut plot = Plot::new(&mut renderer, 4).unwrap();
+        let area = PlotArea {
+            position: Vec2::ZERO,
+            size: Vec2::ONE,
+            range: None,
+        };
+        for sample in 0..6 {
+            plot.push(sample as f32);
+        }
+        assert_eq!(plot.samples().collect::<Vec<_>>(), [2.0, 3.0, 4.0, 5.0]);
+
+        renderer.clear_recorded_commands();
+        plot.draw(&mut renderer, &area, PlotStyle::Area, Color32::WHITE)
+            .unwrap();
+        renderer.update();
+
+        let meshes: Vec<_> = renderer
+            .recorded_commands()
+            .iter()
+            .filter_map(|command| match command {
+                RecordedCommand::Draw { mesh, .. } => Some(*mesh),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].count, 8);
+        assert_eq!(meshes[0].primitive, Primitive::TriangleStrip);
+    }
+}
@@ -0,0 +1,208 @@
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{EmulatedFeatures, RendererError};
+
+/// Name of the include with the helpers of the [EmulatedFeatures].
+const EMULATION_INCLUDE: &str = "cac_emulation.glsl";
+
+const EMULATION_SOURCE: &str = r##"
+vec4 cac_encode_output(vec4 color) {
+#ifdef CAC_EMULATE_SRGB_FRAMEBUFFER
+    vec3 low = color.rgb * 12.92;
+    vec3 high = 1.055 * pow(color.rgb, vec3(1.0 / 2.4)) - 0.055;
+    return vec4(mix(high, low, vec3(lessThanEqual(color.rgb, vec3(0.0031308)))), color.a);
+#else
+    return color;
+#endif
+}"##;
+
+/// Resolves `#include "name"` directives and injects `#define`s into shader sources before they
+/// are compiled by [Shader::with_vertex][super::Shader::with_vertex] and
+/// [Shader::with_fragment][super::Shader::with_fragment].
+///
+/// Includes are looked up in the sources registered with [add_include][Self::add_include] and
+/// every include is only pasted once per shader, like with `#pragma once`.
+/// ```
+/// # use cac_renderer::ShaderPreprocessor;
+/// let mut preprocessor = ShaderPreprocessor::default();
+/// preprocessor.add_include("common.glsl", "float luminance(vec3 c) { return dot(c, vec3(0.2126, 0.7152, 0.0722)); }");
+/// preprocessor.set_define("MAX_LIGHTS", "4");
+///
+/// let source = preprocessor.process("#version 330\n#include \"common.glsl\"\nvoid main() {}").unwrap();
+/// assert!(source.starts_with("#version 330\n#define MAX_LIGHTS 4\n"));
+/// assert!(source.contains("float luminance"));
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct ShaderPreprocessor {
+    includes: HashMap<String, String>,
+    defines: Vec<(String, String)>,
+}
+
+impl ShaderPreprocessor {
+    /// Preprocessor with the emulation include, the defines of the emulated features and the
+    /// [fog include][crate::fog::FOG_INCLUDE], which every renderer starts with.
+    pub(crate) fn with_emulation(emulated: &EmulatedFeatures) -> Self {
+        let mut preprocessor = Self::default();
+        preprocessor.add_include(EMULATION_INCLUDE, EMULATION_SOURCE);
+        preprocessor.add_include(crate::fog::FOG_INCLUDE, crate::fog::FOG_SOURCE);
+        if emulated.srgb_framebuffer {
+            preprocessor.set_define("CAC_EMULATE_SRGB_FRAMEBUFFER", "1");
+        }
+        preprocessor
+    }
+
+    /// Registers the source under the name, so it can be included with `#include "name"`.
+    pub fn add_include(&mut self, name: &str, source: &str) {
+        self.includes.insert(name.to_string(), source.to_string());
+    }
+
+    /// Sets the define for all shaders compiled afterwards, replacing an existing value.
+    pub fn set_define(&mut self, name: &str, value: &str) {
+        match self.defines.iter_mut().find(|(define, _)| define == name) {
+            Some((_, previous)) => *previous = value.to_string(),
+            None => self.defines.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn remove_define(&mut self, name: &str) {
+        self.defines.retain(|(define, _)| define != name);
+    }
+
+    /// Returns the source with the defines injected and the includes resolved.
+    pub fn process(&self, source: &str) -> Result<String, RendererError> {
+        // nothing to do, which is the case for most shaders
+        if self.defines.is_empty() && !source.contains("#include") {
+            return Ok(source.to_string());
+        }
+
+        let mut output = String::with_capacity(source.len());
+        let mut lines = source.lines();
+
+        // #version has to stay the first directive, so the defines go after it
+        if source
+            .lines()
+            .any(|line| line.trim_start().starts_with("#version"))
+        {
+            for line in lines.by_ref() {
+                output.push_str(line);
+                output.push('\n');
+                if line.trim_start().starts_with("#version") {
+                    break;
+                }
+            }
+        }
+
+        for (name, value) in &self.defines {
+            let _ = writeln!(output, "#define {name} {value}");
+        }
+
+        let mut included = Vec::new();
+        for line in lines {
+            self.process_line(line, &mut output, &mut included)?;
+        }
+
+        Ok(output)
+    }
+
+    fn process_line<'a>(
+        &'a self,
+        line: &str,
+        output: &mut String,
+        included: &mut Vec<&'a str>,
+    ) -> Result<(), RendererError> {
+        let name = match line.trim_start().strip_prefix("#include") {
+            Some(name) => name.trim().trim_matches('"'),
+            None => {
+                output.push_str(line);
+                output.push('\n');
+                return Ok(());
+            }
+        };
+
+        let (name, source) = self.includes.get_key_value(name).ok_or_else(|| {
+            RendererError::FailedToCompileShader {
+                error: format!("Include \"{name}\" not found"),
+            }
+        })?;
+
+        // this also stops includes that include themselves
+        if included.contains(&name.as_str()) {
+            return Ok(());
+        }
+        included.push(name);
+
+        for line in source.lines() {
+            self.process_line(line, output, included)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn defines_after_version() {
+        let mut preprocessor = ShaderPreprocessor::default();
+        preprocessor.set_define("SHADOWS", "0");
+        preprocessor.set_define("SHADOWS", "1");
+
+        let source = preprocessor
+            .process("// comment\n#version 330 core\nvoid main() {}")
+            .unwrap();
+        assert_eq!(
+            source,
+            "// comment\n#version 330 core\n#define SHADOWS 1\nvoid main() {}\n"
+        );
+
+        preprocessor.remove_define("SHADOWS");
+        assert_eq!(
+            preprocessor.process("void main() {}").unwrap(),
+            "void main() {}"
+        );
+    }
+
+    #[test]
+    fn nested_includes() {
+        let mut preprocessor = ShaderPreprocessor::default();
+        preprocessor.add_include("constants.glsl", "const float PI = 3.14159;");
+        preprocessor.add_include(
+            "lighting.glsl",
+            "#include \"constants.glsl\"\n#include \"lighting.glsl\"\nfloat light() { return PI; }",
+        );
+
+        let source = preprocessor
+            .process("#include \"lighting.glsl\"\n  #include \"constants.glsl\"\nvoid main() {}")
+            .unwrap();
+        assert_eq!(
+            source,
+            "const float PI = 3.14159;\nfloat light() { return PI; }\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn emulation() {
+        let source = "#version 330 core\n#include \"cac_emulation.glsl\"\nvoid main() {}";
+
+        let native = ShaderPreprocessor::with_emulation(&EmulatedFeatures::default());
+        let processed = native.process(source).unwrap();
+        assert!(processed.contains("vec4 cac_encode_output(vec4 color)"));
+        assert!(!processed.contains("#define CAC_EMULATE_SRGB_FRAMEBUFFER"));
+
+        let emulated = ShaderPreprocessor::with_emulation(&EmulatedFeatures {
+            srgb_framebuffer: true,
+        });
+        let processed = emulated.process(source).unwrap();
+        assert!(
+            processed.starts_with("#version 330 core\n#define CAC_EMULATE_SRGB_FRAMEBUFFER 1\n")
+        );
+    }
+
+    #[test]
+    fn missing_include() {
+        let preprocessor = ShaderPreprocessor::default();
+        assert!(preprocessor.process("#include \"missing.glsl\"").is_err());
+    }
+}
@@ -0,0 +1,209 @@
+use std::path::{Path, PathBuf};
+
+use crate::{Handle, Renderer, RendererError};
+
+use super::{Context, ProgramBinary, ShaderProgram};
+
+const MAGIC: &[u8; 8] = b"CACPROG\0";
+/// Changes whenever the key or the file layout changes, so old files are never read.
+const CACHE_VERSION: u32 = 1;
+
+/// Caches linked programs as binaries on disk, so they don't need to be compiled and linked on
+/// every launch.
+///
+/// Entries are keyed by a hash of the preprocessed sources and the driver string, usually the
+/// [context_description][super::Backend::context_description]. A driver update changes the
+/// string and thereby invalidates all entries. Entries that fail to load anyway are replaced.
+///
+/// Each file is `CACPROG\0`, the u32 cache version, the u32 binary format and the binary, all
+/// little endian.
+#[derive(Debug, Clone)]
+pub struct ProgramCache {
+    directory: PathBuf,
+    driver: String,
+}
+
+impl ProgramCache {
+    /// The directory is created when the first entry is stored.
+    pub fn new(directory: impl Into<PathBuf>, driver: &str) -> Self {
+        Self {
+            directory: directory.into(),
+            driver: driver.to_string(),
+        }
+    }
+
+    /// Loads the program from the cache or, if it isn't cached yet, compiles and links it like
+    /// [ShaderProgram::from_sources] and stores its binary.
+    ///
+    /// Failing to read or write the cache only logs a warning, since the program can always be
+    /// built from the sources.
+    pub fn from_sources<C: Context>(
+        &self,
+        ctx: &mut Renderer<C>,
+        vertex_shader: &str,
+        fragment_shader: &str,
+    ) -> Result<Handle<ShaderProgram>, RendererError> {
+        // the defines of the preprocessor are part of what gets compiled, so they are hashed too
+        let vertex = ctx.preprocessor.process(vertex_shader)?;
+        let fragment = ctx.preprocessor.process(fragment_shader)?;
+        let path = self.path(&[&vertex, &fragment]);
+
+        if let Some(binary) = read_binary(&path) {
+            match ShaderProgram::from_binary(ctx, &binary) {
+                Ok(program) => return Ok(program),
+                Err(error) => log::warn!("Cached program {path:?} is invalid: {error}"),
+            }
+        }
+
+        let program = ShaderProgram::from_sources(ctx, vertex_shader, fragment_shader)?;
+        match ShaderProgram::get_binary(ctx, program) {
+            Ok(binary) => {
+                if let Err(error) = self.write_binary(&path, &binary) {
+                    log::warn!("Failed to cache program {path:?}: {error}");
+                }
+            }
+            Err(error) => log::warn!("Program can't be cached: {error}"),
+        }
+
+        Ok(program)
+    }
+
+    /// Removes all cached programs.
+    pub fn clear(&self) -> std::io::Result<()> {
+        if !self.directory.exists() {
+            return Ok(());
+        }
+
+        for entry in std::fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "bin") {
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&self, sources: &[&str]) -> PathBuf {
+        let mut hash = Fnv1a::default();
+        hash.write(&CACHE_VERSION.to_le_bytes());
+        hash.write(self.driver.as_bytes());
+        for source in sources {
+            // separates the sources, so moving code between them changes the key
+            hash.write(&[0]);
+            hash.write(source.as_bytes());
+        }
+
+        self.directory.join(format!("{:016x}.bin", hash.0))
+    }
+
+    fn write_binary(&self, path: &Path, binary: &ProgramBinary) -> std::io::Result<()> {
+        let mut bytes = Vec::with_capacity(16 + binary.data.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&binary.format.to_le_bytes());
+        bytes.extend_from_slice(&binary.data);
+
+        std::fs::create_dir_all(&self.directory)?;
+        std::fs::write(path, bytes)
+    }
+}
+
+fn read_binary(path: &Path) -> Option<ProgramBinary> {
+    let bytes = std::fs::read(path).ok()?;
+    if bytes.len() < 16 || &bytes[..8] != MAGIC {
+        log::warn!("Cached program {path:?} is corrupted");
+        return None;
+    }
+
+    let version = u32::from_le_bytes(bytes[8..12].try_into().ok()?);
+    if version != CACHE_VERSION {
+        return None;
+    }
+
+    Some(ProgramBinary {
+        format: u32::from_le_bytes(bytes[12..16].try_into().ok()?),
+        data: bytes[16..].to_vec(),
+    })
+}
+
+/// 64 bit FNV-1a. Unlike the std hashers, it's guaranteed to stay the same between Rust versions,
+/// which matters for keys that are stored on disk.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod test {
+    use super::*;
+    use crate::renderer::Uniform;
+
+    const VERTEX: &str = "#version 330\nuniform mat4 model;\nvoid main() {}";
+    const FRAGMENT: &str = "#version 330\nuniform vec4 color;\nvoid main() {}";
+
+    fn cached_files(directory: &Path) -> usize {
+        std::fs::read_dir(directory).unwrap().count()
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let program = ShaderProgram::from_sources(&mut renderer, VERTEX, FRAGMENT).unwrap();
+
+        let binary = ShaderProgram::get_binary(&renderer, program).unwrap();
+        let loaded = ShaderProgram::from_binary(&mut renderer, &binary).unwrap();
+
+        let uniforms = |program| renderer.programs.get(program).unwrap().uniforms().clone();
+        assert_eq!(uniforms(loaded), uniforms(program));
+    }
+
+    #[test]
+    fn cache_programs() {
+        let directory =
+            std::env::temp_dir().join(format!("cac_program_cache_{}", std::process::id()));
+        let mut renderer = Renderer::new_headless().unwrap();
+        let cache = ProgramCache::new(&directory, "Headless Renderer");
+
+        let compiled = cache.from_sources(&mut renderer, VERTEX, FRAGMENT).unwrap();
+        assert_eq!(cached_files(&directory), 1);
+
+        let loaded = cache.from_sources(&mut renderer, VERTEX, FRAGMENT).unwrap();
+        assert_eq!(cached_files(&directory), 1);
+        assert_eq!(
+            renderer.programs.get(loaded).unwrap().uniforms(),
+            renderer.programs.get(compiled).unwrap().uniforms()
+        );
+
+        // other drivers and defines get their own entries
+        ProgramCache::new(&directory, "Other Driver")
+            .from_sources(&mut renderer, VERTEX, FRAGMENT)
+            .unwrap();
+        renderer.shader_preprocessor().set_define("FOG", "1");
+        cache.from_sources(&mut renderer, VERTEX, FRAGMENT).unwrap();
+        assert_eq!(cached_files(&directory), 3);
+
+        // corrupted entries are replaced
+        renderer.shader_preprocessor().remove_define("FOG");
+        let process = |source| renderer.preprocessor.process(source).unwrap();
+        let path = cache.path(&[&process(VERTEX), &process(FRAGMENT)]);
+        std::fs::write(&path, b"garbage").unwrap();
+        cache.from_sources(&mut renderer, VERTEX, FRAGMENT).unwrap();
+        assert!(std::fs::read(&path).unwrap().starts_with(MAGIC));
+
+        cache.clear().unwrap();
+        assert_eq!(cached_files(&directory), 0);
+        std::fs::remove_dir(&directory).unwrap();
+    }
+}
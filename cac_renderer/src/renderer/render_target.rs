@@ -2,6 +2,8 @@ use std::fmt::Display;
 
 use crate::color::Color32;
 
+use super::TextureRegion;
+
 /// The buffers of a render target that should be cleared at the start of every frame
 /// ```
 /// # use cac_renderer::*;
@@ -60,6 +62,9 @@ impl Display for ClearFlags {
 /// Can be either a screen target, a texture or a graphics specific render target.
 /// Setting clear flags will make the target clear at the start of every [renderer
 /// update][Renderer::update].
+///
+/// With OpenGL on Unix, a [SharedTarget][crate::SharedTarget] renders into memory imported from
+/// another API, so video encoders or compositors read the frames zero-copy.
 pub trait RenderTarget {
     /// The color used to clear the target
     fn set_clear_color(&mut self, color: Color32);
@@ -71,12 +76,141 @@ pub trait RenderTarget {
 
     /// The bits to clear. Multiple flags can be used with a bitwiseor |.
     fn set_clear_flags(&mut self, flags: ClearFlags);
+
+    /// Sets whether the linear output of the shaders is encoded to sRGB when it's written to the
+    /// target, for every update that renders into it. E.g. off for intermediate targets that
+    /// are read as linear data, and on for the final output.
+    ///
+    /// Only sRGB targets encode, like [TextureFormat::Srgb8Alpha8] textures or the screen of a
+    /// renderer [built with sRGB][super::RendererBuilder::srgb], which start with it on.
+    fn set_srgb_encode(&mut self, encode: bool);
+    fn srgb_encode(&self) -> bool;
+
+    /// Reads the RGBA8 pixels of the rectangle from what was last rendered into the target, row
+    /// by row, starting at the bottom. Pixels outside of the target are undefined.
+    ///
+    /// Targets with an [integer format][TextureFormat::is_integer] return their texels as
+    /// native-endian 32 bit integers instead, e.g. the object ids of a picking pass.
+    fn read_pixels(&mut self, rect: TextureRegion) -> Vec<u8>;
+}
+
+/// RGBA8 pixels read back from a render target, row by row, starting at the bottom.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Reverses the order of the rows, e.g. for image encoders that expect the top row first.
+    pub fn flip_y(&mut self) {
+        let row_pitch = self.width as usize * 4;
+        self.pixels = crate::Texture::flip_y(&self.pixels, row_pitch, self.height);
+    }
+
+    /// The pixel at x, y, counted from the bottom left.
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        let start = (y as usize * self.width as usize + x as usize) * 4;
+        self.pixels.get(start..start + 4)?.try_into().ok()
+    }
+}
+
+/// Color format of a texture.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TextureFormat {
+    /// Single channel, e.g. for glyph atlases.
+    R8,
+    /// Two channels, e.g. for flow fields or the XY of normal maps.
+    Rg8,
+    Rgb8,
+    Rgba8,
+    /// Rgba8 with sRGB encoded color channels. Rendering into it converts the linear output of
+    /// the shaders.
+    Srgb8Alpha8,
+    Rgba16F,
+    /// Single half float channel, read as f32 from `[u16]` data.
+    R16F,
+    /// Single f32 channel, e.g. for height maps or scalar fields.
+    R32F,
+    /// Single u32 channel, sampled with a `usampler2D` uniform, e.g. for the object ids of
+    /// picking. Integer textures can't be filtered, so use a nearest sampler or none.
+    R32UI,
+    /// Single i32 channel, sampled with an `isampler2D` uniform. Can't be filtered, like
+    /// [R32UI][Self::R32UI].
+    R32I,
+    /// Packed u32 texels with 24 bits of depth above 8 bits of stencil, e.g. for the depth-stencil
+    /// attachment of a texture target. Samples the depth, unless
+    /// [switched to the stencil][super::Texture::set_depth_stencil_mode].
+    Depth24Stencil8,
 }
 
+impl TextureFormat {
+    /// Size of a single texel in bytes.
+    pub fn texel_size(&self) -> usize {
+        match self {
+            TextureFormat::R8 => 1,
+            TextureFormat::Rg8 | TextureFormat::R16F => 2,
+            TextureFormat::Rgb8 => 3,
+            TextureFormat::Rgba8
+            | TextureFormat::Srgb8Alpha8
+            | TextureFormat::R32F
+            | TextureFormat::R32UI
+            | TextureFormat::R32I
+            | TextureFormat::Depth24Stencil8 => 4,
+            TextureFormat::Rgba16F => 8,
+        }
+    }
+
+    /// Integer formats are read as integers by shaders and can't be filtered.
+    pub fn is_integer(&self) -> bool {
+        matches!(self, TextureFormat::R32UI | TextureFormat::R32I)
+    }
+}
+
+/// Render target that wraps a texture owned by someone else, e.g. a swapchain image of an
+/// OpenXR session.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ExternalTarget {}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn texel_sizes() {
+        let formats = [
+            (TextureFormat::R8, 1),
+            (TextureFormat::Rg8, 2),
+            (TextureFormat::R16F, 2),
+            (TextureFormat::R32F, 4),
+            (TextureFormat::R32UI, 4),
+            (TextureFormat::R32I, 4),
+        ];
+        for (format, size) in formats {
+            assert_eq!(format.texel_size(), size, "{format:?}");
+        }
+
+        assert!(TextureFormat::R32UI.is_integer() && TextureFormat::R32I.is_integer());
+        assert!(!TextureFormat::R32F.is_integer());
+    }
+
+    #[test]
+    fn image_flip_y() {
+        let mut image = Image {
+            width: 1,
+            height: 2,
+            pixels: vec![1, 1, 1, 1, 2, 2, 2, 2],
+        };
+        image.flip_y();
+        assert_eq!(image.pixel(0, 0), Some([2; 4]));
+        assert_eq!(image.pixel(0, 1), Some([1; 4]));
+    }
+
     #[test]
     fn or() {
         let flags = ClearFlags::COLOR | ClearFlags::DEPTH;
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+
+use crate::{Handle, Renderer, RendererError};
+
+use super::{
+    texture::{check_size, CreateTexture},
+    Context, Material, Texture, TextureFormat,
+};
+
+/// Texels of a [streamed][Texture::streamed] texture, as [TextureSource]s return them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextureData {
+    /// width * height texels of the format, row by row, starting at the bottom.
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    /// Generates the mip chain, like [Texture::mipmapped].
+    pub mipmapped: bool,
+}
+
+impl TextureData {
+    /// Bytes the texture takes on the graphics device, including its mip chain.
+    pub fn gpu_size(&self) -> usize {
+        let size = self.width as usize * self.height as usize * self.format.texel_size();
+        if self.mipmapped {
+            size * 4 / 3
+        } else {
+            size
+        }
+    }
+
+    /// Validates the texels and creates the texture of the backend from them, without adding it
+    /// to a renderer.
+    pub(crate) fn create<C: Context>(&self) -> Result<C::Texture, RendererError> {
+        let row_pitch = self.width as usize * self.format.texel_size();
+        check_size(&self.data, self.width, self.height, row_pitch, self.format)
+            .map_err(|error| RendererError::FailedToCreateTexture { error })?;
+        if !self.mipmapped {
+            return C::Texture::new(&self.data, self.width, self.height, row_pitch, self.format);
+        }
+        if self.format == TextureFormat::Depth24Stencil8 || self.format.is_integer() {
+            return Err(RendererError::FailedToCreateTexture {
+                error: format!(
+                    "Mip levels can't be generated for {:?} textures",
+                    self.format
+                ),
+            });
+        }
+        C::Texture::mipmapped(&self.data, self.width, self.height, row_pitch, self.format)
+    }
+}
+
+/// Loads the texels of a [streamed][Texture::streamed] texture, every time it's loaded again
+/// after it was evicted, e.g. by decoding its file.
+pub trait TextureSource: Send + 'static {
+    fn load(&self) -> Result<TextureData, RendererError>;
+}
+
+impl<F> TextureSource for F
+where
+    F: Fn() -> Result<TextureData, RendererError> + Send + 'static,
+{
+    fn load(&self) -> Result<TextureData, RendererError> {
+        self()
+    }
+}
+
+struct StreamedTexture {
+    source: Box<dyn TextureSource>,
+    /// Size of the texture while it's resident, 0 while it's evicted.
+    bytes: usize,
+    last_used: u64,
+}
+
+/// The [streamed][Texture::streamed] textures and the memory budget they are evicted by.
+#[derive(Default)]
+pub(crate) struct Residency {
+    budget: Option<usize>,
+    frame: u64,
+    textures: HashMap<Handle<Texture>, StreamedTexture>,
+    reloaded: u32,
+}
+
+impl Residency {
+    pub(crate) fn clear(&mut self) {
+        self.textures.clear();
+    }
+
+    /// Number of updates so far.
+    pub(crate) fn frame(&self) -> u64 {
+        self.frame
+    }
+}
+
+impl Texture {
+    /// Texture whose texels are loaded from the source, and can be evicted when the
+    /// [memory budget][Renderer::set_memory_budget] is exceeded. Evicted textures are loaded again
+    /// by the next draw of a material with them, and the handle stays the same.
+    ///
+    /// Evicted textures are replaced by a single black texel, so only materials see the reloaded
+    /// texture. Updates and views of the texture are lost when it's evicted.
+    pub fn streamed<C: Context>(
+        ctx: &mut Renderer<C>,
+        source: impl TextureSource,
+    ) -> Result<Handle<Self>, RendererError> {
+        let data = source.load()?;
+        let texture = if data.mipmapped {
+            Self::mipmapped(ctx, &data.data, data.width, data.height, data.format)?
+        } else {
+            Self::new(ctx, &data.data, data.width, data.height, data.format)?
+        };
+
+        let frame = ctx.residency.frame;
+        ctx.residency.textures.insert(
+            texture,
+            StreamedTexture {
+                source: Box::new(source),
+                bytes: data.gpu_size(),
+                last_used: frame,
+            },
+        );
+        Ok(texture)
+    }
+}
+
+impl<C: Context> Renderer<C> {
+    /// Limits the memory of the [streamed][Texture::streamed] textures. After every
+    /// [update][super::Backend::update], the textures that were used least recently are evicted
+    /// until they fit in the budget again. Textures used in the last frame are kept, even if
+    /// they exceed it. None disables the limit, which is the default.
+    ///
+    /// Other resources don't count against the budget.
+    pub fn set_memory_budget(&mut self, bytes: Option<usize>) {
+        self.residency.budget = bytes;
+    }
+
+    pub fn memory_budget(&self) -> Option<usize> {
+        self.residency.budget
+    }
+
+    /// Returns true if the texture is a [streamed][Texture::streamed] texture that is evicted
+    /// right now.
+    pub fn is_evicted(&self, texture: Handle<Texture>) -> bool {
+        self.residency
+            .textures
+            .get(&texture)
+            .is_some_and(|streamed| streamed.bytes == 0)
+    }
+
+    /// Marks the streamed textures of the material as used, and loads the evicted ones again.
+    pub(crate) fn use_textures(&mut self, material: Handle<Material>) {
+        if self.residency.textures.is_empty() {
+            return;
+        }
+        let Some(material) = self.materials.get(material) else {
+            return;
+        };
+
+        let frame = self.residency.frame;
+        for binding in &material.textures {
+            let Some(streamed) = self.residency.textures.get_mut(&binding.texture) else {
+                continue;
+            };
+            streamed.last_used = frame;
+            if streamed.bytes > 0 {
+                continue;
+            }
+
+            let reloaded = streamed
+                .source
+                .load()
+                .and_then(|data| Ok((data.create::<C>()?, data.gpu_size())));
+            match (reloaded, self.textures.get_mut(binding.texture)) {
+                (Ok((texture, bytes)), Some(evicted)) => {
+                    *evicted = texture;
+                    streamed.bytes = bytes;
+                    self.residency.reloaded += 1;
+                }
+                (Err(error), _) => log::warn!("Failed to reload {:?}: {error}", binding.texture),
+                (_, None) => {}
+            }
+        }
+    }
+
+    /// Evicts the least recently used streamed textures until they fit in the budget, and
+    /// reports the evictions of the frame in the stats. Called at the end of every update.
+    pub(crate) fn enforce_memory_budget(&mut self) {
+        let textures = &self.textures;
+        self.residency
+            .textures
+            .retain(|texture, _| textures.get(*texture).is_some());
+
+        let mut resident: usize = self.residency.textures.values().map(|t| t.bytes).sum();
+        let mut evicted = Vec::new();
+        if let Some(budget) = self.residency.budget {
+            let frame = self.residency.frame;
+            let mut candidates: Vec<_> = self
+                .residency
+                .textures
+                .iter()
+                .filter(|(_, streamed)| streamed.bytes > 0 && streamed.last_used < frame)
+                .map(|(texture, streamed)| (streamed.last_used, *texture))
+                .collect();
+            candidates.sort_unstable();
+
+            for (_, texture) in candidates {
+                if resident <= budget {
+                    break;
+                }
+                match C::Texture::new(&[0; 4], 1, 1, 4, TextureFormat::Rgba8) {
+                    Ok(empty) => {
+                        if let Some(value) = self.textures.get_mut(texture) {
+                            *value = empty;
+                        }
+                        if let Some(streamed) = self.residency.textures.get_mut(&texture) {
+                            resident -= streamed.bytes;
+                            streamed.bytes = 0;
+                        }
+                        evicted.push(texture);
+                    }
+                    Err(error) => {
+                        log::warn!("Failed to evict {texture:?}: {error}");
+                        break;
+                    }
+                }
+            }
+        }
+
+        if !evicted.is_empty() {
+            log::info!(
+                "Evicted {} textures to fit the memory budget",
+                evicted.len()
+            );
+        }
+        self.stats.evicted_textures = evicted;
+        self.stats.reloaded_textures = std::mem::take(&mut self.residency.reloaded);
+        self.stats.streamed_bytes = resident;
+        self.residency.frame += 1;
+    }
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod test {
+    use super::*;
+    use crate::{Backend, MaterialProperty, ShaderProgram};
+
+    const VS_SOURCE: &str = "
+        layout(location = 0) in vec3 pos;
+        void main() { gl_Position = vec4(pos, 1.0); }";
+
+    const FS_SOURCE: &str = "
+        uniform sampler2D albedo;
+        out vec4 color;
+        void main() { color = texture(albedo, vec2(0.0)); }";
+
+    fn source() -> impl TextureSource {
+        || {
+            Ok(TextureData {
+                data: vec![255; 2 * 2 * 4],
+                width: 2,
+                height: 2,
+                format: TextureFormat::Rgba8,
+                mipmapped: false,
+            })
+        }
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let program = ShaderProgram::from_sources(&mut renderer, VS_SOURCE, FS_SOURCE).unwrap();
+        let old = Texture::streamed(&mut renderer, source()).unwrap();
+        let recent = Texture::streamed(&mut renderer, source()).unwrap();
+        let material = renderer
+            .create_material(program, &[MaterialProperty::new("albedo", &old)])
+            .unwrap();
+        let mesh = renderer.placeholder_mesh().unwrap();
+
+        // both fit without a budget
+        renderer.update();
+        assert_eq!(renderer.frame_stats().streamed_bytes, 32);
+
+        renderer.set_memory_budget(Some(16));
+        renderer
+            .update_material(material, &[MaterialProperty::new("albedo", &recent)])
+            .unwrap();
+        renderer.draw(mesh, material, &[]);
+        renderer.update();
+        assert!(renderer.is_evicted(old));
+        assert!(!renderer.is_evicted(recent));
+        assert_eq!(renderer.frame_stats().evicted_textures, [old]);
+        assert_eq!(renderer.frame_stats().streamed_bytes, 16);
+
+        // drawing it loads it again with the same handle
+        renderer
+            .update_material(material, &[MaterialProperty::new("albedo", &old)])
+            .unwrap();
+        renderer.draw(mesh, material, &[]);
+        assert!(!renderer.is_evicted(old));
+        renderer.update();
+        assert_eq!(renderer.frame_stats().reloaded_textures, 1);
+        assert_eq!(renderer.frame_stats().evicted_textures, [recent]);
+    }
+}
@@ -0,0 +1,256 @@
+use std::any::Any;
+
+use crate::{Handle, RcHandle, Renderer};
+
+use super::{
+    Buffer, ComputeProgram, Context, Material, Sampler, Shader, ShaderProgram, Texture,
+    VertexLayout,
+};
+
+/// The kinds of resources the [Renderer] stores.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ResourceKind {
+    Buffer,
+    VertexLayout,
+    Shader,
+    ShaderProgram,
+    ComputeProgram,
+    Sampler,
+    Texture,
+    Material,
+}
+
+impl ResourceKind {
+    pub const ALL: [ResourceKind; 8] = [
+        ResourceKind::Buffer,
+        ResourceKind::VertexLayout,
+        ResourceKind::Shader,
+        ResourceKind::ShaderProgram,
+        ResourceKind::ComputeProgram,
+        ResourceKind::Sampler,
+        ResourceKind::Texture,
+        ResourceKind::Material,
+    ];
+}
+
+/// Types that [Handle]s of the [Renderer] refer to.
+pub trait Resource: sealed::Sealed {
+    const KIND: ResourceKind;
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+macro_rules! resource {
+    ($($resource:ident),*) => {
+        $(
+            impl sealed::Sealed for $resource {}
+            impl Resource for $resource {
+                const KIND: ResourceKind = ResourceKind::$resource;
+            }
+        )*
+    };
+}
+
+resource!(
+    Buffer,
+    VertexLayout,
+    Shader,
+    ShaderProgram,
+    ComputeProgram,
+    Sampler,
+    Texture,
+    Material
+);
+
+/// A [Handle] of any resource kind, e.g. for inspectors, reference counters and serializers that
+/// treat all resources the same. Handles convert into it with `into()`, and back with
+/// [downcast][Self::downcast].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ResourceId {
+    kind: ResourceKind,
+    /// [Handle::to_bits] of the handle.
+    bits: u64,
+}
+
+impl ResourceId {
+    pub fn kind(&self) -> ResourceKind {
+        self.kind
+    }
+
+    /// The handle, or None if the id refers to another kind of resource.
+    pub fn downcast<T: Resource>(self) -> Option<Handle<T>> {
+        (self.kind == T::KIND).then(|| Handle::from_bits(self.bits))
+    }
+}
+
+impl<T: Resource> From<Handle<T>> for ResourceId {
+    fn from(handle: Handle<T>) -> Self {
+        Self {
+            kind: T::KIND,
+            bits: handle.to_bits(),
+        }
+    }
+}
+
+impl<C: Context> Renderer<C> {
+    /// The resource the id refers to, or None if it was removed. Materials can be downcast to
+    /// [Material], the other resources to the types of the backend, e.g. the textures of a
+    /// headless renderer to `HeadlessTexture`.
+    pub fn get_raw(&self, id: ResourceId) -> Option<&dyn Any> {
+        fn get<K: Resource, V: Any>(
+            values: &crate::generation_vec::GenerationVec<K, V>,
+            id: ResourceId,
+        ) -> Option<&dyn Any> {
+            values.get(id.downcast()?).map(|value| value as &dyn Any)
+        }
+
+        match id.kind {
+            ResourceKind::Buffer => get(&self.buffers, id),
+            ResourceKind::VertexLayout => get(&self.layouts, id),
+            ResourceKind::Shader => get(&self.shaders, id),
+            ResourceKind::ShaderProgram => get(&self.programs, id),
+            ResourceKind::ComputeProgram => get(&self.compute_programs, id),
+            ResourceKind::Sampler => get(&self.samplers, id),
+            ResourceKind::Texture => get(&self.textures, id),
+            ResourceKind::Material => get(&self.materials, id),
+        }
+    }
+
+    /// Returns true if the resource the id refers to wasn't removed.
+    pub fn contains(&self, id: ResourceId) -> bool {
+        self.get_raw(id).is_some()
+    }
+
+    /// Ids of all resources of the kind, in the order they are stored in.
+    pub fn resource_ids(&self, kind: ResourceKind) -> Vec<ResourceId> {
+        fn ids<K: Resource, V>(
+            values: &crate::generation_vec::GenerationVec<K, V>,
+        ) -> Vec<ResourceId> {
+            values.handles().map(ResourceId::from).collect()
+        }
+
+        match kind {
+            ResourceKind::Buffer => ids(&self.buffers),
+            ResourceKind::VertexLayout => ids(&self.layouts),
+            ResourceKind::Shader => ids(&self.shaders),
+            ResourceKind::ShaderProgram => ids(&self.programs),
+            ResourceKind::ComputeProgram => ids(&self.compute_programs),
+            ResourceKind::Sampler => ids(&self.samplers),
+            ResourceKind::Texture => ids(&self.textures),
+            ResourceKind::Material => ids(&self.materials),
+        }
+    }
+}
+
+impl<C: Context> Renderer<C> {
+    /// Reference counted handle of the resource, which is released by the next
+    /// [collect_garbage][Self::collect_garbage] after its last clone was dropped.
+    /// None if the handle is invalid.
+    ///
+    /// Resources that never had an [RcHandle] are only released manually.
+    pub fn share<T: Resource>(&mut self, handle: Handle<T>) -> Option<RcHandle<T>> {
+        let id = ResourceId::from(handle);
+        let count = match id.kind {
+            ResourceKind::Buffer => self.buffers.ref_count(id.downcast()?),
+            ResourceKind::VertexLayout => self.layouts.ref_count(id.downcast()?),
+            ResourceKind::Shader => self.shaders.ref_count(id.downcast()?),
+            ResourceKind::ShaderProgram => self.programs.ref_count(id.downcast()?),
+            ResourceKind::ComputeProgram => self.compute_programs.ref_count(id.downcast()?),
+            ResourceKind::Sampler => self.samplers.ref_count(id.downcast()?),
+            ResourceKind::Texture => self.textures.ref_count(id.downcast()?),
+            ResourceKind::Material => self.materials.ref_count(id.downcast()?),
+        }?;
+
+        Some(RcHandle::new(handle, count))
+    }
+
+    /// Releases the resources whose [RcHandle]s were all dropped and returns how many.
+    /// Called by the backends at the end of every [update][super::Backend::update].
+    pub fn collect_garbage(&mut self) -> usize {
+        let released = self.buffers.collect_garbage()
+            + self.layouts.collect_garbage()
+            + self.shaders.collect_garbage()
+            + self.programs.collect_garbage()
+            + self.compute_programs.collect_garbage()
+            + self.samplers.collect_garbage()
+            + self.textures.collect_garbage()
+            + self.materials.collect_garbage();
+        if released > 0 {
+            self.prune_retained();
+        }
+        released
+    }
+
+    /// Removes the resource the id refers to, if it still exists.
+    pub fn release(&mut self, id: ResourceId) {
+        match id.kind {
+            ResourceKind::Buffer => id.downcast().map(|handle| self.buffers.remove(handle)),
+            ResourceKind::VertexLayout => id.downcast().map(|handle| self.layouts.remove(handle)),
+            ResourceKind::Shader => id.downcast().map(|handle| self.shaders.remove(handle)),
+            ResourceKind::ShaderProgram => id.downcast().map(|handle| self.programs.remove(handle)),
+            ResourceKind::ComputeProgram => id
+                .downcast()
+                .map(|handle| self.compute_programs.remove(handle)),
+            ResourceKind::Sampler => id.downcast().map(|handle| self.samplers.remove(handle)),
+            ResourceKind::Texture => id.downcast().map(|handle| self.textures.remove(handle)),
+            ResourceKind::Material => id.downcast().map(|handle| self.materials.remove(handle)),
+        };
+        self.prune_retained();
+    }
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod test {
+    use super::*;
+    use crate::{Backend, HeadlessContext, TextureFormat};
+
+    #[test]
+    fn resource_ids() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let texture = Texture::new(&mut renderer, &[0; 4], 1, 1, TextureFormat::Rgba8).unwrap();
+
+        let id = ResourceId::from(texture);
+        assert_eq!(id.kind(), ResourceKind::Texture);
+        assert_eq!(id.downcast::<Texture>(), Some(texture));
+        assert_eq!(id.downcast::<Buffer>(), None);
+        assert!(renderer.resource_ids(ResourceKind::Texture).contains(&id));
+
+        let raw = renderer.get_raw(id).unwrap();
+        assert!(raw
+            .downcast_ref::<<HeadlessContext as Context>::Texture>()
+            .is_some());
+
+        // the id of another kind with the same bits doesn't refer to it
+        let sampler = ResourceId {
+            kind: ResourceKind::Sampler,
+            ..id
+        };
+        assert!(!renderer.contains(sampler));
+
+        renderer.release(id);
+        assert!(!renderer.contains(id));
+        assert!(!renderer.resource_ids(ResourceKind::Texture).contains(&id));
+    }
+
+    #[test]
+    fn rc_handles() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let texture = Texture::new(&mut renderer, &[0; 4], 1, 1, TextureFormat::Rgba8).unwrap();
+        let rc = renderer.share(texture).unwrap();
+        let clone = rc.clone();
+        assert_eq!(Handle::from(&clone), texture);
+
+        drop(rc);
+        renderer.update();
+        assert!(renderer.textures.get(texture).is_some());
+
+        // released with the next update, not right away
+        drop(clone);
+        assert!(renderer.textures.get(texture).is_some());
+        renderer.update();
+        assert!(renderer.textures.get(texture).is_none());
+        assert!(renderer.share(texture).is_none());
+    }
+}
@@ -0,0 +1,556 @@
+use std::collections::HashMap;
+
+use crate::{generation_vec::GenerationVec, lz4, Handle, Renderer};
+
+use super::{
+    buffer::{as_bytes, CreateBuffer},
+    compute::CreateComputeProgram,
+    sampler::CreateSampler,
+    shader::{CreateShaderProgram, ShaderStage},
+    texture::CreateTexture,
+    Buffer, BufferAttributes, BufferUsage, ComputeProgram, Context, RendererError, Sampler,
+    SamplerDescription, Shader, ShaderProgram, Texture, TextureFormat, TextureRegion, VertexLayout,
+    VertexLayoutConfig,
+};
+
+struct RetainedBuffer {
+    /// LZ4 compressed content of the buffer.
+    data: Vec<u8>,
+    size: usize,
+    usage: BufferUsage,
+    is_index: bool,
+}
+
+struct RetainedTexture {
+    /// LZ4 compressed texels, with the rows right after each other.
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+    mipmapped: bool,
+}
+
+impl RetainedTexture {
+    fn size(&self) -> usize {
+        self.width as usize * self.height as usize * self.format.texel_size()
+    }
+}
+
+/// Preprocessed source of a [Shader].
+#[derive(Clone, Debug)]
+pub(crate) struct RetainedShader {
+    stage: ShaderStage,
+    source: String,
+}
+
+impl RetainedShader {
+    pub(crate) fn new(stage: ShaderStage, source: String) -> Self {
+        Self { stage, source }
+    }
+
+    fn create<C: Context>(&self) -> Result<C::Shader, RendererError> {
+        self.stage.create::<C>(&self.source)
+    }
+}
+
+/// Sources of the stages of a [ShaderProgram], which outlive the shaders it was linked from.
+struct RetainedProgram {
+    vertex: RetainedShader,
+    tessellation: Option<(RetainedShader, RetainedShader)>,
+    fragment: RetainedShader,
+    attribute_locations: Vec<(String, u8)>,
+}
+
+impl RetainedProgram {
+    fn create<C: Context>(&self) -> Result<C::ShaderProgram, RendererError> {
+        let (vertex, fragment) = (self.vertex.create::<C>()?, self.fragment.create::<C>()?);
+        match &self.tessellation {
+            Some((control, evaluation)) => C::ShaderProgram::with_tessellation(
+                &vertex,
+                &control.create::<C>()?,
+                &evaluation.create::<C>()?,
+                &fragment,
+                &self.attribute_locations,
+            ),
+            None => C::ShaderProgram::new(&vertex, &fragment, &self.attribute_locations),
+        }
+    }
+}
+
+struct RetainedLayout {
+    buffer_attributes: Vec<BufferAttributes>,
+    config: VertexLayoutConfig,
+}
+
+/// Compressed CPU copies of the buffers and textures, to create them again after the context
+/// was lost, see [Renderer::set_retain_data].
+///
+/// The descriptions of the shaders, programs, samplers and layouts are small, so they are always
+/// kept, and [resume][super::Backend::resume] creates them again with the same handles.
+#[derive(Default)]
+pub(crate) struct Retention {
+    enabled: bool,
+    buffers: HashMap<Handle<Buffer>, RetainedBuffer>,
+    textures: HashMap<Handle<Texture>, RetainedTexture>,
+    shaders: HashMap<Handle<Shader>, RetainedShader>,
+    programs: HashMap<Handle<ShaderProgram>, RetainedProgram>,
+    compute_programs: HashMap<Handle<ComputeProgram>, RetainedShader>,
+    samplers: HashMap<Handle<Sampler>, SamplerDescription>,
+    layouts: HashMap<Handle<VertexLayout>, RetainedLayout>,
+}
+
+impl Retention {
+    pub(crate) fn retain_buffer<T>(
+        &mut self,
+        buffer: Handle<Buffer>,
+        data: &[T],
+        usage: BufferUsage,
+        is_index: bool,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        let retained = RetainedBuffer {
+            data: lz4::compress(as_bytes(data)),
+            size: std::mem::size_of_val(data),
+            usage,
+            is_index,
+        };
+        self.buffers.insert(buffer, retained);
+    }
+
+    /// Writes an [update][Renderer::update_buffer] of the buffer into its copy.
+    pub(crate) fn update_buffer<T>(&mut self, buffer: Handle<Buffer>, offset: usize, data: &[T]) {
+        let Some(retained) = self.buffers.get_mut(&buffer) else {
+            return;
+        };
+        match lz4::decompress(&retained.data, retained.size) {
+            Ok(mut content) => {
+                let data = as_bytes(data);
+                content[offset..offset + data.len()].copy_from_slice(data);
+                retained.data = lz4::compress(&content);
+            }
+            Err(error) => {
+                log::warn!("Dropping the corrupted copy of {buffer:?}: {error}");
+                self.buffers.remove(&buffer);
+            }
+        }
+    }
+
+    /// The content of [orphaned][Renderer::orphan_buffer] buffers is undefined, their copies are
+    /// zeroed.
+    pub(crate) fn orphan_buffer(&mut self, buffer: Handle<Buffer>, size: usize) {
+        if let Some(retained) = self.buffers.get_mut(&buffer) {
+            retained.data = lz4::compress(&vec![0; size]);
+            retained.size = size;
+        }
+    }
+
+    /// Keeps a copy of the data of a 2D texture, whose rows start row_pitch bytes apart.
+    pub(crate) fn retain_texture(
+        &mut self,
+        texture: Handle<Texture>,
+        data: &[u8],
+        (width, height): (u32, u32),
+        row_pitch: usize,
+        format: TextureFormat,
+        mipmapped: bool,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        let row_size = width as usize * format.texel_size();
+        let texels: Vec<u8> = data
+            .chunks(row_pitch)
+            .take(height as usize)
+            .flat_map(|row| &row[..row_size])
+            .copied()
+            .collect();
+        let retained = RetainedTexture {
+            data: lz4::compress(&texels),
+            width,
+            height,
+            format,
+            mipmapped,
+        };
+        self.textures.insert(texture, retained);
+    }
+
+    /// Writes the region of a texture [update][Texture::update] into its copy.
+    pub(crate) fn update_texture(
+        &mut self,
+        texture: Handle<Texture>,
+        region: TextureRegion,
+        data: &[u8],
+        row_pitch: usize,
+    ) {
+        let Some(retained) = self.textures.get_mut(&texture) else {
+            return;
+        };
+        let mut texels = match lz4::decompress(&retained.data, retained.size()) {
+            Ok(texels) => texels,
+            Err(error) => {
+                log::warn!("Dropping the corrupted copy of {texture:?}: {error}");
+                self.textures.remove(&texture);
+                return;
+            }
+        };
+
+        let texel_size = retained.format.texel_size();
+        let row_size = region.width as usize * texel_size;
+        for (row, source) in data
+            .chunks(row_pitch)
+            .take(region.height as usize)
+            .enumerate()
+        {
+            let start = ((region.y as usize + row) * retained.width as usize + region.x as usize)
+                * texel_size;
+            texels[start..start + row_size].copy_from_slice(&source[..row_size]);
+        }
+        retained.data = lz4::compress(&texels);
+    }
+
+    pub(crate) fn retain_shader(&mut self, shader: Handle<Shader>, retained: RetainedShader) {
+        self.shaders.insert(shader, retained);
+    }
+
+    /// The source of the shader, to keep it with the programs it's linked into.
+    pub(crate) fn shader(&self, shader: Handle<Shader>) -> Option<RetainedShader> {
+        self.shaders.get(&shader).cloned()
+    }
+
+    /// Keeps the sources of the shaders the program was linked from. Programs whose shaders have
+    /// no source, like the ones created from binaries, aren't retained.
+    pub(crate) fn retain_linked(
+        &mut self,
+        program: Handle<ShaderProgram>,
+        vertex: Handle<Shader>,
+        tessellation: Option<(Handle<Shader>, Handle<Shader>)>,
+        fragment: Handle<Shader>,
+        attribute_locations: Vec<(String, u8)>,
+    ) {
+        let tessellation = match tessellation {
+            Some((control, evaluation)) => match (self.shader(control), self.shader(evaluation)) {
+                (Some(control), Some(evaluation)) => Some((control, evaluation)),
+                _ => return,
+            },
+            None => None,
+        };
+        let (Some(vertex), Some(fragment)) = (self.shader(vertex), self.shader(fragment)) else {
+            return;
+        };
+
+        let retained = RetainedProgram {
+            vertex,
+            tessellation,
+            fragment,
+            attribute_locations,
+        };
+        self.programs.insert(program, retained);
+    }
+
+    /// Replaces the sources of a program that was linked again from new sources.
+    #[cfg(feature = "hot-reload")]
+    pub(crate) fn retain_relinked(
+        &mut self,
+        program: Handle<ShaderProgram>,
+        vertex: RetainedShader,
+        fragment: RetainedShader,
+        attribute_locations: Vec<(String, u8)>,
+    ) {
+        let retained = RetainedProgram {
+            vertex,
+            tessellation: None,
+            fragment,
+            attribute_locations,
+        };
+        self.programs.insert(program, retained);
+    }
+
+    pub(crate) fn retain_compute_program(
+        &mut self,
+        program: Handle<ComputeProgram>,
+        compute: Handle<Shader>,
+    ) {
+        if let Some(compute) = self.shader(compute) {
+            self.compute_programs.insert(program, compute);
+        }
+    }
+
+    pub(crate) fn retain_sampler(
+        &mut self,
+        sampler: Handle<Sampler>,
+        description: SamplerDescription,
+    ) {
+        self.samplers.insert(sampler, description);
+    }
+
+    pub(crate) fn retain_layout(
+        &mut self,
+        layout: Handle<VertexLayout>,
+        buffer_attributes: &[BufferAttributes],
+        config: &VertexLayoutConfig,
+    ) {
+        let retained = RetainedLayout {
+            buffer_attributes: buffer_attributes.to_vec(),
+            config: config.clone(),
+        };
+        self.layouts.insert(layout, retained);
+    }
+
+    /// Drops the copies of the data, the descriptions are kept.
+    pub(crate) fn clear(&mut self) {
+        self.buffers.clear();
+        self.textures.clear();
+    }
+}
+
+impl<C: Context> Renderer<C> {
+    /// Keeps LZ4 compressed copies of the data of the buffers and 2D textures that are created
+    /// from now on, so they are created again with the same handles after the context was lost,
+    /// e.g. on [resume][super::Backend::resume], without going back to the assets.
+    /// Disabling it drops the copies. Disabled by default.
+    ///
+    /// [Updates][Texture::update] of retained textures are written into their copies as well.
+    /// Cubemaps, texture arrays and compressed textures aren't retained.
+    pub fn set_retain_data(&mut self, enabled: bool) {
+        self.retention.enabled = enabled;
+        if !enabled {
+            self.retention.clear();
+        }
+    }
+
+    pub fn retains_data(&self) -> bool {
+        self.retention.enabled
+    }
+
+    /// Bytes of the compressed copies of the retained resources.
+    pub fn retained_bytes(&self) -> usize {
+        self.retention
+            .buffers
+            .values()
+            .map(|buffer| buffer.data.len())
+            .chain(
+                self.retention
+                    .textures
+                    .values()
+                    .map(|texture| texture.data.len()),
+            )
+            .sum()
+    }
+
+    /// Drops the copies and descriptions of the resources that were released.
+    pub(crate) fn prune_retained(&mut self) {
+        let retention = &mut self.retention;
+        let buffers = &self.buffers;
+        let textures = &self.textures;
+        let shaders = &self.shaders;
+        let programs = &self.programs;
+        let compute_programs = &self.compute_programs;
+        let samplers = &self.samplers;
+        let layouts = &self.layouts;
+        retention
+            .buffers
+            .retain(|handle, _| buffers.get(*handle).is_some());
+        retention
+            .textures
+            .retain(|handle, _| textures.get(*handle).is_some());
+        retention
+            .shaders
+            .retain(|handle, _| shaders.get(*handle).is_some());
+        retention
+            .programs
+            .retain(|handle, _| programs.get(*handle).is_some());
+        retention
+            .compute_programs
+            .retain(|handle, _| compute_programs.get(*handle).is_some());
+        retention
+            .samplers
+            .retain(|handle, _| samplers.get(*handle).is_some());
+        retention
+            .layouts
+            .retain(|handle, _| layouts.get(*handle).is_some());
+    }
+
+    /// Creates the retained resources again, in the slots of their handles.
+    pub(crate) fn restore_retained(&mut self) {
+        let mut failed_buffers = Vec::new();
+        for (handle, retained) in &self.retention.buffers {
+            let buffer = lz4::decompress(&retained.data, retained.size).and_then(|data| {
+                let buffer = if retained.is_index {
+                    C::Buffer::with_index(&mut self.context, &data, retained.usage)
+                } else {
+                    C::Buffer::with_vertex(&mut self.context, &data, retained.usage)
+                };
+                buffer.map_err(|error| error.to_string())
+            });
+            match buffer.map(|buffer| self.buffers.restore(*handle, buffer)) {
+                Ok(true) => {}
+                Ok(false) => failed_buffers.push(*handle),
+                Err(error) => {
+                    log::warn!("Failed to restore {handle:?}: {error}");
+                    failed_buffers.push(*handle);
+                }
+            }
+        }
+
+        let mut failed_textures = Vec::new();
+        for (handle, retained) in &self.retention.textures {
+            let texture = lz4::decompress(&retained.data, retained.size()).and_then(|data| {
+                let row_pitch = retained.width as usize * retained.format.texel_size();
+                let texture = if retained.mipmapped {
+                    C::Texture::mipmapped(
+                        &data,
+                        retained.width,
+                        retained.height,
+                        row_pitch,
+                        retained.format,
+                    )
+                } else {
+                    C::Texture::new(
+                        &data,
+                        retained.width,
+                        retained.height,
+                        row_pitch,
+                        retained.format,
+                    )
+                };
+                texture.map_err(|error| error.to_string())
+            });
+            match texture.map(|texture| self.textures.restore(*handle, texture)) {
+                Ok(true) => {}
+                Ok(false) => failed_textures.push(*handle),
+                Err(error) => {
+                    log::warn!("Failed to restore {handle:?}: {error}");
+                    failed_textures.push(*handle);
+                }
+            }
+        }
+
+        for handle in failed_buffers {
+            self.retention.buffers.remove(&handle);
+        }
+        for handle in failed_textures {
+            self.retention.textures.remove(&handle);
+        }
+
+        self.restore_descriptions();
+    }
+
+    /// Creates the shaders, programs and samplers again from their descriptions, and then the
+    /// layouts, whose buffers have to be restored first.
+    fn restore_descriptions(&mut self) {
+        let retention = &mut self.retention;
+        restore(&mut retention.shaders, &mut self.shaders, |retained| {
+            retained.create::<C>()
+        });
+        restore(&mut retention.programs, &mut self.programs, |retained| {
+            retained.create::<C>()
+        });
+        restore(
+            &mut retention.compute_programs,
+            &mut self.compute_programs,
+            |retained| C::ComputeProgram::new(&retained.create::<C>()?),
+        );
+        restore(&mut retention.samplers, &mut self.samplers, |description| {
+            C::Sampler::new(description)
+        });
+
+        let mut layouts = std::mem::take(&mut self.retention.layouts);
+        layouts.retain(|handle, retained| {
+            let missing = retained
+                .buffer_attributes
+                .iter()
+                .find(|attributes| self.buffers.get(attributes.buffer).is_none());
+            let layout = match missing {
+                Some(attributes) => Err(RendererError::ResourceNotFound {
+                    resource: format!("Buffer: {:?}", attributes.buffer),
+                }),
+                None => VertexLayout::create(self, &retained.buffer_attributes, &retained.config),
+            };
+            match layout.map(|layout| self.layouts.restore(*handle, layout)) {
+                Ok(restored) => restored,
+                Err(error) => {
+                    log::warn!("Failed to restore {handle:?}: {error}");
+                    false
+                }
+            }
+        });
+        self.retention.layouts = layouts;
+    }
+}
+
+/// Creates the resources again in the slots of their handles, and drops the descriptions of the
+/// ones that failed.
+fn restore<K, R, V>(
+    retained: &mut HashMap<Handle<K>, R>,
+    resources: &mut GenerationVec<K, V>,
+    create: impl Fn(&R) -> Result<V, RendererError>,
+) {
+    retained.retain(|handle, retained| {
+        match create(retained).map(|resource| resources.restore(*handle, resource)) {
+            Ok(restored) => restored,
+            Err(error) => {
+                log::warn!("Failed to restore {handle:?}: {error}");
+                false
+            }
+        }
+    });
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod test {
+    use crate::{
+        renderer::test_window::NoWindow, Backend, Buffer, BufferUsage, Texture, TextureFormat,
+        TextureRegion,
+    };
+
+    use super::*;
+
+    #[test]
+    fn restores_retained_resources() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let forgotten = Texture::new(&mut renderer, &[0; 4], 1, 1, TextureFormat::Rgba8).unwrap();
+
+        renderer.set_retain_data(true);
+        let texels = [255; 4 * 4 * 4];
+        let texture = Texture::new(&mut renderer, &texels, 4, 4, TextureFormat::Rgba8).unwrap();
+        let buffer =
+            Buffer::with_vertex(&mut renderer, &[0.5f32; 64], BufferUsage::StaticWrite).unwrap();
+        let released =
+            Buffer::with_index(&mut renderer, &[0u16; 6], BufferUsage::StaticWrite).unwrap();
+        renderer.release(released.into());
+        assert!(renderer.retained_bytes() > 0);
+        assert!(renderer.retained_bytes() < texels.len() + 64 * 4);
+
+        let region = TextureRegion {
+            x: 1,
+            y: 1,
+            width: 2,
+            height: 2,
+        };
+        Texture::update(&mut renderer, texture, region, &[0; 2 * 2 * 4], None).unwrap();
+
+        renderer.suspend();
+        assert!(renderer.textures.get(texture).is_none());
+        renderer.resume(&NoWindow).unwrap();
+
+        assert!(renderer.textures.get(texture).is_some());
+        assert!(renderer.buffers.get(buffer).is_some());
+        assert!(renderer.buffers.get(released).is_none());
+        assert!(renderer.textures.get(forgotten).is_none());
+        assert_eq!(renderer.textures.get(texture).unwrap().size(), (4, 4));
+
+        let retained = &renderer.retention.textures[&texture];
+        let data = lz4::decompress(&retained.data, retained.size()).unwrap();
+        assert_eq!(data[(4 + 1) * 4..(4 + 1) * 4 + 4], [0; 4]);
+        assert_eq!(data[..4], [255; 4]);
+
+        // new resources don't take the slots of the restored ones
+        let new_texture = Texture::new(&mut renderer, &[0; 4], 1, 1, TextureFormat::Rgba8).unwrap();
+        assert_ne!(new_texture, texture);
+        assert_eq!(renderer.textures.get(texture).unwrap().size(), (4, 4));
+    }
+}
@@ -0,0 +1,177 @@
+use crate::{Color8, Handle, Renderer, RendererError};
+
+use super::Context;
+
+/// How texels are combined when a texture is sampled between them.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Filter {
+    Nearest,
+    #[default]
+    Linear,
+}
+
+/// What happens with texture coordinates outside of [0, 1].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum WrapMode {
+    #[default]
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    /// Coordinates outside of the texture sample the
+    /// [border color][SamplerDescription::border_color], e.g. for shadow maps outside of the
+    /// light's frustum.
+    ClampToBorder,
+}
+
+/// How a compare sampler tests the reference value against the sampled depth. The result is 1
+/// where the test passes and 0 where it fails.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CompareFunction {
+    Never,
+    Less,
+    #[default]
+    LessOrEqual,
+    Equal,
+    NotEqual,
+    GreaterOrEqual,
+    Greater,
+    Always,
+}
+
+/// Filtering and wrapping of a [Sampler].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SamplerDescription {
+    /// Filter when the texture is drawn smaller than its size.
+    pub min_filter: Filter,
+    /// Filter when the texture is drawn larger than its size.
+    pub mag_filter: Filter,
+    /// Filter between the mip levels, or None to only sample the base level.
+    pub mipmap_filter: Option<Filter>,
+    pub wrap_u: WrapMode,
+    pub wrap_v: WrapMode,
+    pub wrap_w: WrapMode,
+    /// Sampled outside of the texture with [WrapMode::ClampToBorder].
+    pub border_color: Color8,
+    /// Compares the reference value of a `sampler2DShadow` lookup with the sampled depth instead
+    /// of returning it. With linear filtering, the results of the texels are blended, which
+    /// gives percentage-closer filtering of shadows.
+    pub compare: Option<CompareFunction>,
+}
+
+impl Default for SamplerDescription {
+    /// Trilinear filtering and repeating coordinates.
+    fn default() -> Self {
+        Self {
+            min_filter: Filter::Linear,
+            mag_filter: Filter::Linear,
+            mipmap_filter: Some(Filter::Linear),
+            wrap_u: WrapMode::Repeat,
+            wrap_v: WrapMode::Repeat,
+            wrap_w: WrapMode::Repeat,
+            border_color: Color8::new_rgba(0, 0, 0, 0),
+            compare: None,
+        }
+    }
+}
+
+impl SamplerDescription {
+    /// Unfiltered texels, e.g. for pixel art or lookup tables.
+    pub fn nearest() -> Self {
+        Self {
+            min_filter: Filter::Nearest,
+            mag_filter: Filter::Nearest,
+            mipmap_filter: None,
+            ..Default::default()
+        }
+    }
+
+    /// Filtered depth comparisons for shadow maps. Outside of the map, everything is lit.
+    pub fn shadow() -> Self {
+        Self {
+            mipmap_filter: None,
+            compare: Some(CompareFunction::LessOrEqual),
+            ..Default::default()
+        }
+        .with_border(Color8::new_rgba(255, 255, 255, 255))
+    }
+
+    /// Clamps all coordinates to the border color.
+    pub fn with_border(self, color: Color8) -> Self {
+        Self {
+            border_color: color,
+            ..self.with_wrap(WrapMode::ClampToBorder)
+        }
+    }
+
+    /// Same wrap mode for all coordinates.
+    pub fn with_wrap(self, wrap: WrapMode) -> Self {
+        Self {
+            wrap_u: wrap,
+            wrap_v: wrap,
+            wrap_w: wrap,
+            ..self
+        }
+    }
+}
+
+/// How a texture is filtered and wrapped when it's sampled.
+///
+/// Samplers are separate from the textures, so the same texture can be sampled differently by
+/// different materials. Set both with a texture property, see
+/// [PropertyValue::Texture][super::PropertyValue::Texture]. Textures without a sampler use the
+/// filtering of the texture itself.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Sampler {}
+
+pub trait CreateSampler: Sized {
+    fn new(description: &SamplerDescription) -> Result<Self, RendererError>;
+}
+
+impl Sampler {
+    pub fn new<C: Context>(
+        ctx: &mut Renderer<C>,
+        description: &SamplerDescription,
+    ) -> Result<Handle<Self>, RendererError> {
+        let sampler = C::Sampler::new(description)?;
+        let handle = ctx.samplers.push(sampler);
+        ctx.retention.retain_sampler(handle, *description);
+        Ok(handle)
+    }
+
+    /// Names the sampler in graphics debuggers, see [DebugLabel][super::DebugLabel].
+    pub fn set_label<C: Context>(
+        ctx: &mut Renderer<C>,
+        sampler: Handle<Self>,
+        label: &str,
+    ) -> Result<(), RendererError> {
+        super::set_debug_label(&mut ctx.samplers, sampler, label, "Sampler")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn border() {
+        let white = Color8::new_rgba(255, 255, 255, 255);
+        let description = SamplerDescription::nearest().with_border(white);
+
+        assert_eq!(description.border_color, white);
+        assert_eq!(description.wrap_u, WrapMode::ClampToBorder);
+        assert_eq!(description.wrap_w, WrapMode::ClampToBorder);
+        assert_eq!(description.mipmap_filter, None);
+        assert_eq!(SamplerDescription::default().border_color.a, 0);
+    }
+
+    #[test]
+    fn shadow() {
+        let description = SamplerDescription::shadow();
+
+        assert_eq!(description.compare, Some(CompareFunction::LessOrEqual));
+        assert_eq!(description.min_filter, Filter::Linear);
+        assert_eq!(description.wrap_v, WrapMode::ClampToBorder);
+        assert_eq!(description.border_color.r, 255);
+        assert_eq!(SamplerDescription::default().compare, None);
+    }
+}
@@ -1,20 +1,47 @@
 use crate::{Handle, Renderer, RendererError};
 
-use super::Context;
+use super::{retention::RetainedShader, Context};
 
 pub trait Uniform {
-    fn get_uniform_location(&self, name: &str) -> u32;
+    /// Location of the uniform with the name, or None if the program has no such active uniform.
+    /// The lookup uses a map that is built once when the program is linked.
+    fn get_uniform_location(&self, name: &str) -> Option<u32>;
     fn data_size(&self) -> usize;
 
     fn set_uniform_f32(&mut self, location: u32, value: &[f32]);
     fn uniforms(&self) -> &Vec<UniformDescription>;
+    /// Active vertex inputs of the program, reflected when it's linked.
+    fn attributes(&self) -> &[AttributeDescription];
     fn set_uniform_data(&mut self, data: &[u8]);
+    /// Names and binding points of the active uniform blocks of the program. The blocks are
+    /// bound to their index when the program is linked.
+    fn uniform_blocks(&self) -> &[(String, u32)];
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum UniformKind {
     F32,
     Sampler2D,
+    Sampler1D,
+    SamplerCube,
+    SamplerCubeArray,
+    /// Depth texture sampled with a compare sampler, see
+    /// [SamplerDescription::compare][super::SamplerDescription::compare].
+    Sampler2DShadow,
+    /// Depth cubemap array sampled with a compare sampler, e.g. for point light shadows.
+    SamplerCubeArrayShadow,
+    /// Multisampled texture, read per sample with `texelFetch`, see
+    /// [Texture::multisampled][super::Texture::multisampled].
+    Sampler2DMS,
+    /// Unsigned integer texture, e.g. the stencil of a depth-stencil texture, see
+    /// [Texture::set_depth_stencil_mode][super::Texture::set_depth_stencil_mode].
+    USampler2D,
+    /// Signed integer texture, e.g. [TextureFormat::R32I][super::TextureFormat::R32I].
+    ISampler2D,
+    /// Unsigned integer image, read and written with `imageLoad`, `imageStore` and the image
+    /// atomics, e.g. the counters of the [overdraw analysis][super::Backend::set_overdraw_analysis].
+    /// Holds the image unit instead of a texture unit.
+    UImage2D,
     Mat4,
     Mat3,
     Mat2,
@@ -23,6 +50,24 @@ pub enum UniformKind {
     Vec2,
 }
 
+impl UniformKind {
+    /// Samplers hold the texture unit of a texture, see [Material][super::Material].
+    pub fn is_sampler(&self) -> bool {
+        matches!(
+            self,
+            UniformKind::Sampler2D
+                | UniformKind::Sampler1D
+                | UniformKind::SamplerCube
+                | UniformKind::SamplerCubeArray
+                | UniformKind::Sampler2DShadow
+                | UniformKind::SamplerCubeArrayShadow
+                | UniformKind::Sampler2DMS
+                | UniformKind::USampler2D
+                | UniformKind::ISampler2D
+        )
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct UniformDescription {
     pub name: String,
@@ -33,12 +78,88 @@ pub struct UniformDescription {
     pub offset: usize,
 }
 
+/// Vertex input of a linked [ShaderProgram], see
+/// [program_attributes][crate::Renderer::program_attributes].
+///
+/// The kind is the type in the shader, which isn't necessarily the
+/// [VertexAttributeKind][super::VertexAttributeKind] of the buffer that feeds it, e.g. normalized
+/// i16 are read as floats.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AttributeDescription {
+    pub name: String,
+    pub location: u32,
+    pub kind: UniformKind,
+    pub count: u32,
+}
+
+impl AttributeDescription {
+    /// Locations the input takes up, matrices take one per column.
+    pub fn locations(&self) -> std::ops::Range<u32> {
+        let columns = match self.kind {
+            UniformKind::Mat2 => 2,
+            UniformKind::Mat3 => 3,
+            UniformKind::Mat4 => 4,
+            _ => 1,
+        };
+        self.location..self.location + columns * self.count
+    }
+}
+
+/// Name of a uniform as reported by the driver, without the `[0]` suffix of arrays.
+///
+/// Whether the suffix is appended depends on the driver, so it's only removed if it's there.
+/// Invalid UTF-8 is replaced instead of failing.
+#[cfg_attr(not(any(feature = "opengl", fuzzing)), allow(dead_code))]
+pub fn reflected_uniform_name(name: &[u8]) -> String {
+    let name = String::from_utf8_lossy(name);
+    match name.strip_suffix("[0]") {
+        Some(array) => array.to_string(),
+        None => name.into_owned(),
+    }
+}
+
+/// Binary of a linked [ShaderProgram] in a driver specific format, see
+/// [get_binary][ShaderProgram::get_binary].
+///
+/// It is only valid for the driver and version that created it, so loading it elsewhere fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProgramBinary {
+    pub format: u32,
+    pub data: Vec<u8>,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Shader {}
 
+/// Stage of a [Shader], which decides how its source is compiled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ShaderStage {
+    Vertex,
+    Fragment,
+    Compute,
+    TessControl,
+    TessEvaluation,
+}
+
+impl ShaderStage {
+    /// Compiles the source, which went through the [preprocessor][super::ShaderPreprocessor] already.
+    pub(crate) fn create<C: Context>(&self, source: &str) -> Result<C::Shader, RendererError> {
+        match self {
+            Self::Vertex => C::Shader::with_vertex(source),
+            Self::Fragment => C::Shader::with_fragment(source),
+            Self::Compute => C::Shader::with_compute(source),
+            Self::TessControl => C::Shader::with_tess_control(source),
+            Self::TessEvaluation => C::Shader::with_tess_evaluation(source),
+        }
+    }
+}
+
 pub trait CreateShader: Sized {
     fn with_vertex(source: &str) -> Result<Self, RendererError>;
     fn with_fragment(source: &str) -> Result<Self, RendererError>;
+    fn with_compute(source: &str) -> Result<Self, RendererError>;
+    fn with_tess_control(source: &str) -> Result<Self, RendererError>;
+    fn with_tess_evaluation(source: &str) -> Result<Self, RendererError>;
 }
 
 impl Shader {
@@ -46,15 +167,54 @@ impl Shader {
         ctx: &mut Renderer<C>,
         source: &str,
     ) -> Result<Handle<Self>, RendererError> {
-        let shader = C::Shader::with_vertex(source)?;
-        Ok(ctx.shaders.push(shader))
+        Self::with_stage(ctx, ShaderStage::Vertex, source)
     }
     pub fn with_fragment<C: Context>(
         ctx: &mut Renderer<C>,
         source: &str,
     ) -> Result<Handle<Self>, RendererError> {
-        let shader = C::Shader::with_fragment(source)?;
-        Ok(ctx.shaders.push(shader))
+        Self::with_stage(ctx, ShaderStage::Fragment, source)
+    }
+    pub fn with_compute<C: Context>(
+        ctx: &mut Renderer<C>,
+        source: &str,
+    ) -> Result<Handle<Self>, RendererError> {
+        Self::with_stage(ctx, ShaderStage::Compute, source)
+    }
+
+    /// Tessellation control shader, that decides how often each patch gets subdivided.
+    ///
+    /// Requires OpenGL 4.0 or newer.
+    pub fn with_tess_control<C: Context>(
+        ctx: &mut Renderer<C>,
+        source: &str,
+    ) -> Result<Handle<Self>, RendererError> {
+        Self::with_stage(ctx, ShaderStage::TessControl, source)
+    }
+
+    /// Tessellation evaluation shader, that positions the vertices generated by the subdivision.
+    ///
+    /// Requires OpenGL 4.0 or newer.
+    pub fn with_tess_evaluation<C: Context>(
+        ctx: &mut Renderer<C>,
+        source: &str,
+    ) -> Result<Handle<Self>, RendererError> {
+        Self::with_stage(ctx, ShaderStage::TessEvaluation, source)
+    }
+
+    /// Keeps the preprocessed source, so the shader can be compiled again on
+    /// [resume][super::Backend::resume].
+    fn with_stage<C: Context>(
+        ctx: &mut Renderer<C>,
+        stage: ShaderStage,
+        source: &str,
+    ) -> Result<Handle<Self>, RendererError> {
+        let source = ctx.preprocessor.process(source)?;
+        let shader = stage.create::<C>(&source)?;
+        let handle = ctx.shaders.push(shader);
+        ctx.retention
+            .retain_shader(handle, RetainedShader::new(stage, source));
+        Ok(handle)
     }
 }
 
@@ -67,21 +227,86 @@ impl ShaderProgram {
         vertex_shader: Handle<Shader>,
         fragment_shader: Handle<Shader>,
     ) -> Result<Handle<Self>, RendererError> {
-        let vertex_shader =
-            ctx.shaders
-                .get(vertex_shader)
-                .ok_or(RendererError::ResourceNotFound {
-                    resource: "vertex shader".to_string(),
-                })?;
-        let fragment_shader =
+        Self::with_stages(ctx, vertex_shader, None, fragment_shader)
+    }
+
+    /// Program with tessellation stages between the vertex and the fragment shader.
+    /// Meshes drawn with it need to use [Primitive::Patches][super::Primitive::Patches].
+    pub fn with_tessellation<C: Context>(
+        ctx: &mut Renderer<C>,
+        vertex_shader: Handle<Shader>,
+        tess_control_shader: Handle<Shader>,
+        tess_evaluation_shader: Handle<Shader>,
+        fragment_shader: Handle<Shader>,
+    ) -> Result<Handle<Self>, RendererError> {
+        Self::with_stages(
+            ctx,
+            vertex_shader,
+            Some((tess_control_shader, tess_evaluation_shader)),
+            fragment_shader,
+        )
+    }
+
+    fn with_stages<C: Context>(
+        ctx: &mut Renderer<C>,
+        vertex: Handle<Shader>,
+        tessellation: Option<(Handle<Shader>, Handle<Shader>)>,
+        fragment: Handle<Shader>,
+    ) -> Result<Handle<Self>, RendererError> {
+        let get_shader = |shader, resource: &str| {
             ctx.shaders
-                .get(fragment_shader)
+                .get(shader)
                 .ok_or(RendererError::ResourceNotFound {
-                    resource: "fragment shader".to_string(),
-                })?;
+                    resource: resource.to_string(),
+                })
+        };
+
+        let vertex_shader = get_shader(vertex, "vertex shader")?;
+        let fragment_shader = get_shader(fragment, "fragment shader")?;
 
-        let program = C::ShaderProgram::new(vertex_shader, fragment_shader)?;
+        let attribute_locations = ctx.attribute_locations();
+        let program = match tessellation {
+            Some((tess_control_shader, tess_evaluation_shader)) => {
+                C::ShaderProgram::with_tessellation(
+                    vertex_shader,
+                    get_shader(tess_control_shader, "tessellation control shader")?,
+                    get_shader(tess_evaluation_shader, "tessellation evaluation shader")?,
+                    fragment_shader,
+                    &attribute_locations,
+                )?
+            }
+            None => C::ShaderProgram::new(vertex_shader, fragment_shader, &attribute_locations)?,
+        };
 
+        let handle = ctx.programs.push(program);
+        ctx.retention
+            .retain_linked(handle, vertex, tessellation, fragment, attribute_locations);
+        Ok(handle)
+    }
+
+    /// Returns the linked program in the driver's binary format, e.g. to store it in a
+    /// [ProgramCache][super::ProgramCache].
+    ///
+    /// Requires OpenGL 4.1 or newer.
+    pub fn get_binary<C: Context>(
+        ctx: &Renderer<C>,
+        program: Handle<Self>,
+    ) -> Result<ProgramBinary, RendererError> {
+        ctx.programs
+            .get(program)
+            .ok_or(RendererError::ResourceNotFound {
+                resource: format!("Shaderprogram: {program:?}"),
+            })?
+            .get_binary()
+    }
+
+    /// Creates the program from a binary returned by [get_binary][Self::get_binary], without
+    /// compiling and linking the sources again.
+    pub fn from_binary<C: Context>(
+        ctx: &mut Renderer<C>,
+        binary: &ProgramBinary,
+    ) -> Result<Handle<Self>, RendererError> {
+        let program = C::ShaderProgram::from_binary(binary)?;
         Ok(ctx.programs.push(program))
     }
 
@@ -100,16 +325,41 @@ impl ShaderProgram {
 
         Ok(program)
     }
+
+    /// Names the program in graphics debuggers, see [DebugLabel][super::DebugLabel].
+    pub fn set_label<C: Context>(
+        ctx: &mut Renderer<C>,
+        program: Handle<Self>,
+        label: &str,
+    ) -> Result<(), RendererError> {
+        super::set_debug_label(&mut ctx.programs, program, label, "Shaderprogram")
+    }
 }
 
 pub trait CreateShaderProgram: Sized {
     type VertexShader;
     type FragmentShader;
+    type TessellationShader;
 
+    /// The attribute locations bind vertex inputs by name, see
+    /// [bind_attribute_name][crate::Renderer::bind_attribute_name]. Explicit
+    /// `layout(location = N)` qualifiers take precedence.
     fn new(
         vertex_shader: &Self::VertexShader,
         fragment_shader: &Self::FragmentShader,
+        attribute_locations: &[(String, u8)],
     ) -> Result<Self, RendererError>;
+
+    fn with_tessellation(
+        vertex_shader: &Self::VertexShader,
+        tess_control_shader: &Self::TessellationShader,
+        tess_evaluation_shader: &Self::TessellationShader,
+        fragment_shader: &Self::FragmentShader,
+        attribute_locations: &[(String, u8)],
+    ) -> Result<Self, RendererError>;
+
+    fn get_binary(&self) -> Result<ProgramBinary, RendererError>;
+    fn from_binary(binary: &ProgramBinary) -> Result<Self, RendererError>;
 }
 
 pub trait ProgramStorage {
@@ -127,3 +377,20 @@ pub trait ProgramStorage {
 
     fn get_mut(&mut self, handle: Handle<ShaderProgram>) -> Option<&mut Self::ShaderProgram>;
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn array_names() {
+        assert_eq!(reflected_uniform_name(b"bones[0]"), "bones");
+        assert_eq!(reflected_uniform_name(b"bones"), "bones");
+        assert_eq!(
+            reflected_uniform_name(b"lights[0].color"),
+            "lights[0].color"
+        );
+        assert_eq!(reflected_uniform_name(b"[0]"), "");
+        assert_eq!(reflected_uniform_name(b"\xff[0]"), "\u{fffd}");
+    }
+}
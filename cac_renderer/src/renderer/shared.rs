@@ -0,0 +1,34 @@
+use crate::RcHandle;
+
+/// Reference counted [Handle][crate::Handle] that releases its resource once the last clone is
+/// dropped, for owners that prefer that over releasing their resources themselves. See
+/// [share][crate::Renderer::share].
+///
+/// The resource is released at the end of the next [update][super::Backend::update], so draws
+/// of the current frame still find it. Plain handles to it are invalid afterwards.
+pub type Shared<T> = RcHandle<T>;
+
+#[cfg(all(test, feature = "headless"))]
+mod test {
+    use super::*;
+    use crate::{Backend, Handle, Renderer, Texture, TextureFormat};
+
+    #[test]
+    fn release_last_clone() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let texture = Texture::new(&mut renderer, &[0; 4], 1, 1, TextureFormat::Rgba8).unwrap();
+        let shared: Shared<Texture> = renderer.share(texture).unwrap();
+        let clone = shared.clone();
+        assert_eq!(Handle::from(&clone), texture);
+
+        drop(shared);
+        renderer.update();
+        assert!(renderer.textures.get(texture).is_some());
+
+        // released with the next update, not right away
+        drop(clone);
+        assert!(renderer.textures.get(texture).is_some());
+        renderer.update();
+        assert!(renderer.textures.get(texture).is_none());
+    }
+}
@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+
+use crate::{
+    math::{Mat4, Vec2},
+    Color32, Handle, Renderer, RendererError,
+};
+
+use super::{
+    AttributeSemantic, Backend, BufferAttributes, Context, Material, MaterialProperty, Mesh,
+    Primitive, RenderQueue, ShaderProgram, StreamBuffer, Texture, TextureRegion,
+    VertexAttributeKind, VertexLayout, VertexLayoutConfig,
+};
+
+const SPRITE_FS_SOURCE: &str = r##"
+    #version 330 core
+    in vec2 frag_uv;
+    in vec4 frag_color;
+    out vec4 result;
+    uniform sampler2D sprite;
+    void main() {
+        result = texture(sprite, frag_uv) * frag_color;
+        if (result.a < 0.5) discard;
+    }"##;
+
+/// Two triangles per sprite, there is no index buffer for more than a few quads.
+const SPRITE_VERTICES: usize = 6;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(C)]
+struct SpriteVertex {
+    position: Vec2,
+    uv: Vec2,
+    color: [f32; 4],
+}
+
+const VERTEX_SIZE: usize = std::mem::size_of::<SpriteVertex>();
+
+/// The part of a texture a sprite shows.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SpriteRegion {
+    pub texture: Handle<Texture>,
+    /// Bottom left texture coordinate.
+    pub uv_min: Vec2,
+    /// Top right texture coordinate.
+    pub uv_max: Vec2,
+    /// Size of the sprite before it's scaled, usually in texels.
+    pub size: Vec2,
+}
+
+impl SpriteRegion {
+    /// The whole texture of width * height texels.
+    pub fn new(texture: Handle<Texture>, width: u32, height: u32) -> Self {
+        Self {
+            texture,
+            uv_min: Vec2::ZERO,
+            uv_max: Vec2::ONE,
+            size: Vec2::new(width as f32, height as f32),
+        }
+    }
+
+    /// A region of the texels of a texture of width * height texels, like a frame of a sprite
+    /// sheet or an image in an atlas.
+    pub fn from_texels(
+        texture: Handle<Texture>,
+        width: u32,
+        height: u32,
+        region: TextureRegion,
+    ) -> Self {
+        let texture_size = Vec2::new(width as f32, height as f32);
+        let min = Vec2::new(region.x as f32, region.y as f32);
+        let size = Vec2::new(region.width as f32, region.height as f32);
+        Self {
+            texture,
+            uv_min: min / texture_size,
+            uv_max: (min + size) / texture_size,
+            size,
+        }
+    }
+}
+
+/// Draws many textured quads with few draw calls, for 2D games and UI.
+///
+/// Every [draw][Self::draw] adds a sprite to the batch, and [update][Self::update] writes all of
+/// them into the [StreamBuffer] of the batch, with one draw call per texture:
+///
+/// ```no_run
+/// # use cac_renderer::{math::*, Backend, Color32, Renderer, SpriteBatch, SpriteRegion};
+/// # fn frame<C: cac_renderer::Context>(renderer: &mut Renderer<C>, batch: &mut SpriteBatch, player: SpriteRegion)
+/// # where Renderer<C>: Backend {
+/// batch.set_view_projection(Mat4::orthographic_rh(0.0, 800.0, 0.0, 600.0, -1.0, 1.0));
+/// batch.draw(&player, vec2(400.0, 300.0), 0.0, Vec2::ONE, Color32::WHITE);
+/// batch.update(renderer).unwrap();
+/// renderer.update();
+/// # }
+/// ```
+///
+/// The sprites are sorted by texture, so sprites of the same texture are drawn in the order they
+/// were added, but sprites of different textures don't overlap in a defined order. Sprites that
+/// have to be drawn on top of others of another texture go into a later batch, or an atlas.
+///
+/// There is no blending, texels with an alpha below 0.5 are discarded. The sprites are drawn in
+/// the [Overlay][RenderQueue::Overlay] queue.
+#[derive(Debug)]
+pub struct SpriteBatch {
+    capacity: usize,
+    vertices: StreamBuffer,
+    vertex_layout: Handle<VertexLayout>,
+    program: Handle<ShaderProgram>,
+    /// Material of each texture that was drawn so far.
+    materials: HashMap<Handle<Texture>, Handle<Material>>,
+    view_projection: Mat4,
+    /// Corners of the sprites since the last update.
+    sprites: Vec<(Handle<Texture>, [SpriteVertex; 4])>,
+    /// Vertices of the last update, kept to not allocate every frame.
+    scratch: Vec<SpriteVertex>,
+}
+
+impl SpriteBatch {
+    /// Batch of up to capacity sprites per frame.
+    pub fn new<C: Context>(ctx: &mut Renderer<C>, capacity: usize) -> Result<Self, RendererError> {
+        let capacity = capacity.max(1);
+        let vertex_source = vertex_source(ctx.layout_config())?;
+        let program = ShaderProgram::from_sources(ctx, &vertex_source, SPRITE_FS_SOURCE)?;
+
+        let vertices = StreamBuffer::new(ctx, capacity * SPRITE_VERTICES * VERTEX_SIZE)?;
+        let vertex_layout = VertexLayout::new(
+            ctx,
+            &[BufferAttributes::with_kinds(
+                vertices.buffer(),
+                0,
+                &[
+                    (AttributeSemantic::Position, VertexAttributeKind::Vec2),
+                    (AttributeSemantic::UV(0), VertexAttributeKind::Vec2),
+                    (AttributeSemantic::Color(0), VertexAttributeKind::Vec4),
+                ],
+            )],
+        )?;
+
+        Ok(Self {
+            capacity,
+            vertices,
+            vertex_layout,
+            program,
+            materials: HashMap::new(),
+            view_projection: Mat4::IDENTITY,
+            sprites: Vec::with_capacity(capacity),
+            scratch: Vec::new(),
+        })
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of sprites drawn since the last update.
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    /// Transforms the sprite positions into clip space, e.g. an orthographic projection of the
+    /// screen in pixels. The identity by default, which takes the positions as clip space.
+    pub fn set_view_projection(&mut self, view_projection: Mat4) {
+        self.view_projection = view_projection;
+    }
+
+    pub fn view_projection(&self) -> Mat4 {
+        self.view_projection
+    }
+
+    /// Adds a sprite centered at the position, rotated counterclockwise by rotation in radians
+    /// and with the size of the region scaled by scale. The texture is multiplied with the color.
+    pub fn draw(
+        &mut self,
+        region: &SpriteRegion,
+        position: Vec2,
+        rotation: f32,
+        scale: Vec2,
+        color: Color32,
+    ) {
+        let half = region.size * scale * 0.5;
+        let rotation = Vec2::from_angle(rotation);
+        let (r, g, b, a) = color.as_rgba();
+        let corner = |offset: Vec2, uv: Vec2| SpriteVertex {
+            position: position + rotation.rotate(offset * half),
+            uv,
+            color: [r, g, b, a],
+        };
+
+        let (min, max) = (region.uv_min, region.uv_max);
+        self.sprites.push((
+            region.texture,
+            [
+                corner(Vec2::new(-1.0, -1.0), min),
+                corner(Vec2::new(1.0, -1.0), Vec2::new(max.x, min.y)),
+                corner(Vec2::new(1.0, 1.0), max),
+                corner(Vec2::new(-1.0, 1.0), Vec2::new(min.x, max.y)),
+            ],
+        ));
+    }
+
+    /// Writes the sprites into the stream buffer, draws them with one draw call per texture and
+    /// clears the batch. Returns the number of draw calls.
+    ///
+    /// Can be called once per frame. Fails if more than [capacity][Self::capacity] sprites were
+    /// drawn, the sprites are dropped either way.
+    pub fn update<C: Context>(&mut self, renderer: &mut Renderer<C>) -> Result<usize, RendererError>
+    where
+        Renderer<C>: Backend,
+    {
+        if self.sprites.is_empty() {
+            return Ok(0);
+        }
+
+        let batches = sort_into_batches(&mut self.sprites, &mut self.scratch);
+        self.sprites.clear();
+        let slice = self.vertices.push(renderer, &self.scratch)?;
+        let first_vertex = slice.first_element(VERTEX_SIZE);
+
+        for (texture, start, count) in &batches {
+            let material = match self.materials.get(texture) {
+                Some(material) => *material,
+                None => {
+                    let material = renderer.create_material(
+                        self.program,
+                        &[MaterialProperty::new("sprite", texture)],
+                    )?;
+                    renderer.set_render_queue(material, RenderQueue::Overlay);
+                    self.materials.insert(*texture, material);
+                    material
+                }
+            };
+
+            let mesh = Mesh::new(
+                self.vertex_layout,
+                (count * SPRITE_VERTICES) as u32,
+                Primitive::Triangles,
+            )
+            .with_vertex_offset(first_vertex + start * SPRITE_VERTICES);
+            renderer.draw(
+                mesh,
+                material,
+                &[MaterialProperty::new(
+                    "view_projection",
+                    &self.view_projection,
+                )],
+            );
+        }
+        Ok(batches.len())
+    }
+}
+
+fn vertex_source(config: &VertexLayoutConfig) -> Result<String, RendererError> {
+    let location = |semantic: AttributeSemantic| {
+        config
+            .location(semantic)
+            .ok_or(RendererError::AttributeHasNoLocation { semantic })
+    };
+    let position = location(AttributeSemantic::Position)?;
+    let uv = location(AttributeSemantic::UV(0))?;
+    let color = location(AttributeSemantic::Color(0))?;
+
+    Ok(format!(
+        r##"
+    #version 330 core
+    layout(location = {position}) in vec2 pos;
+    layout(location = {uv}) in vec2 uv;
+    layout(location = {color}) in vec4 color;
+    uniform mat4 view_projection;
+    out vec2 frag_uv;
+    out vec4 frag_color;
+    void main() {{
+        frag_uv = uv;
+        frag_color = color;
+        gl_Position = view_projection * vec4(pos, 0.0, 1.0);
+    }}"##
+    ))
+}
+
+/// Sorts the sprites by texture, keeping the order of sprites of the same texture, and writes
+/// their triangles into vertices. Returns the texture, first sprite and sprite count of each
+/// batch.
+fn sort_into_batches(
+    sprites: &mut [(Handle<Texture>, [SpriteVertex; 4])],
+    vertices: &mut Vec<SpriteVertex>,
+) -> Vec<(Handle<Texture>, usize, usize)> {
+    sprites.sort_by_key(|(texture, _)| *texture);
+
+    vertices.clear();
+    let mut batches: Vec<(Handle<Texture>, usize, usize)> = Vec::new();
+    for (index, (texture, corners)) in sprites.iter().enumerate() {
+        match batches.last_mut() {
+            Some((batch_texture, _, count)) if batch_texture == texture => *count += 1,
+            _ => batches.push((*texture, index, 1)),
+        }
+        vertices.extend([0, 1, 2, 0, 2, 3].map(|corner| corners[corner]));
+    }
+    batches
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sprite(texture: Handle<Texture>, x: f32) -> (Handle<Texture>, [SpriteVertex; 4]) {
+        let vertex = SpriteVertex {
+            position: Vec2::new(x, 0.0),
+            uv: Vec2::ZERO,
+            color: [1.0; 4],
+        };
+        (texture, [vertex; 4])
+    }
+
+    #[test]
+    fn batch_by_texture() {
+        let (a, b) = (Handle::from_parts(0, 0), Handle::from_parts(1, 0));
+        let mut sprites = vec![
+            sprite(b, 0.0),
+            sprite(a, 1.0),
+            sprite(b, 2.0),
+            sprite(a, 3.0),
+        ];
+        let mut vertices = Vec::new();
+
+        let batches = sort_into_batches(&mut sprites, &mut vertices);
+        assert_eq!(batches, [(a, 0, 2), (b, 2, 2)]);
+        assert_eq!(vertices.len(), 4 * SPRITE_VERTICES);
+        // same texture keeps the draw order
+        let order: Vec<_> = vertices
+            .chunks(SPRITE_VERTICES)
+            .map(|quad| quad[0].position.x)
+            .collect();
+        assert_eq!(order, [1.0, 3.0, 0.0, 2.0]);
+    }
+
+    #[test]
+    fn regions() {
+        let texture = Handle::new();
+        let region = SpriteRegion::from_texels(
+            texture,
+            64,
+            32,
+            TextureRegion {
+                x: 16,
+                y: 8,
+                width: 16,
+                height: 8,
+            },
+        );
+        assert_eq!(region.uv_min, Vec2::new(0.25, 0.25));
+        assert_eq!(region.uv_max, Vec2::new(0.5, 0.5));
+        assert_eq!(region.size, Vec2::new(16.0, 8.0));
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn draw_sprites() {
+        use crate::{RecordedCommand, TextureFormat};
+
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mut batch = SpriteBatch::new(&mut renderer, 8).unwrap();
+        let textures: Vec<_> = (0..2)
+            .map(|_| Texture::new(&mut renderer, &[255; 4], 1, 1, TextureFormat::Rgba8).unwrap())
+            .collect();
+
+        for index in 0..5 {
+            let region = SpriteRegion::new(textures[index % 2], 1, 1);
+            let position = Vec2::new(index as f32, 0.0);
+            batch.draw(&region, position, 0.5, Vec2::splat(2.0), Color32::WHITE);
+        }
+        assert_eq!(batch.len(), 5);
+
+        renderer.clear_recorded_commands();
+        assert_eq!(batch.update(&mut renderer).unwrap(), 2);
+        assert!(batch.is_empty());
+        renderer.update();
+
+        let counts: Vec<_> = renderer
+            .recorded_commands()
+            .iter()
+            .filter_map(|command| match command {
+                RecordedCommand::Draw { mesh, .. } => Some(mesh.count),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(counts.iter().sum::<u32>(), 5 * SPRITE_VERTICES as u32);
+        assert_eq!(counts.len(), 2);
+
+        // more sprites than fit into the stream buffer
+        for _ in 0..9 {
+            batch.draw(
+                &SpriteRegion::new(textures[0], 1, 1),
+                Vec2::ZERO,
+                0.0,
+                Vec2::ONE,
+                Color32::WHITE,
+            );
+        }
+        assert!(batch.update(&mut renderer).is_err());
+        assert!(batch.is_empty());
+    }
+}
@@ -0,0 +1,359 @@
+use std::time::Duration;
+
+use crate::Handle;
+
+use super::{DrawCommand, Material, Texture};
+
+/// Draw statistics of a single material.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaterialStats {
+    pub material: Handle<Material>,
+    pub draw_calls: u32,
+    pub instances: u32,
+    /// GPU time of the draws with the material, see [FrameStats::materials].
+    pub gpu_time: Option<Duration>,
+}
+
+/// Draw statistics of a single layer. See [SortKey][super::SortKey] for what a layer is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayerStats {
+    pub layer: u8,
+    pub draw_calls: u32,
+    pub instances: u32,
+    /// GPU time of the draws on the layer, see [FrameStats::layers].
+    pub gpu_time: Option<Duration>,
+}
+
+/// GPU time of a run of consecutive draws with the same material on the same layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TimedRun {
+    pub material: Handle<Material>,
+    pub layer: u8,
+    pub nanoseconds: u64,
+}
+
+/// GPU time of a scope between [begin_gpu_scope][super::Backend::begin_gpu_scope] and
+/// [end_gpu_scope][super::Backend::end_gpu_scope].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpuScope {
+    pub name: String,
+    pub nanoseconds: u64,
+}
+
+impl GpuScope {
+    pub fn milliseconds(&self) -> f64 {
+        self.nanoseconds as f64 / 1_000_000.0
+    }
+}
+
+/// How often the pixels of the target were drawn, see
+/// [set_overdraw_analysis][super::Backend::set_overdraw_analysis].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OverdrawStats {
+    /// Pixels of the viewport.
+    pub pixels: u32,
+    /// Pixels that were drawn at least once.
+    pub covered_pixels: u32,
+    /// Fragments of all draws, the sum of the counts of all pixels.
+    pub fragments: u64,
+    /// Count of the pixel that was drawn most often.
+    pub max: u32,
+}
+
+impl OverdrawStats {
+    /// Sums up the draw counts of the pixels.
+    #[cfg(feature = "opengl")]
+    pub(crate) fn from_counts(counts: impl IntoIterator<Item = u32>) -> Self {
+        counts
+            .into_iter()
+            .fold(OverdrawStats::default(), |mut stats, count| {
+                stats.pixels += 1;
+                stats.covered_pixels += (count > 0) as u32;
+                stats.fragments += count as u64;
+                stats.max = stats.max.max(count);
+                stats
+            })
+    }
+
+    /// Average number of times the covered pixels were drawn, 1 means there was no overdraw at
+    /// all.
+    pub fn average(&self) -> f32 {
+        if self.covered_pixels == 0 {
+            return 0.0;
+        }
+        self.fragments as f32 / self.covered_pixels as f32
+    }
+}
+
+/// Statistics of the last frame, gathered by the backend during
+/// [update][super::Backend::update].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Number of issued draw calls.
+    pub draw_calls: u32,
+    /// Number of drawn instances over all draw calls.
+    pub instances: u32,
+    /// How often a material had to be bound.
+    pub material_binds: u32,
+    /// Changes of the pipeline state between draws, like binding vertex layouts, materials and
+    /// textures.
+    pub state_changes: u32,
+    /// Breakdown per material, in the order they were first drawn.
+    ///
+    /// Like the [GPU scopes][Self::gpu_scopes], the GPU times are the ones of the latest frame
+    /// whose results are available, and None for materials that weren't drawn in it.
+    pub materials: Vec<MaterialStats>,
+    /// Breakdown per layer, in the order they were first drawn. The GPU times are measured like
+    /// the ones of the [materials][Self::materials].
+    pub layers: Vec<LayerStats>,
+    /// GPU times of the scopes, in the order they were begun.
+    /// The results of the GPU arrive a few frames later, so these are the latest available
+    /// frame's scopes and not reset with the other counters.
+    pub gpu_scopes: Vec<GpuScope>,
+    /// Overdraw of the frame, if the analysis is on.
+    pub overdraw: Option<OverdrawStats>,
+    /// [Streamed][Texture::streamed] textures that were evicted to fit the
+    /// [memory budget][crate::Renderer::set_memory_budget].
+    pub evicted_textures: Vec<Handle<Texture>>,
+    /// Evicted textures that were loaded again for the draws of the frame.
+    pub reloaded_textures: u32,
+    /// Bytes of the resident streamed textures.
+    pub streamed_bytes: usize,
+}
+
+impl FrameStats {
+    /// Returns the statistics of the material, or None if it wasn't drawn last frame.
+    pub fn material(&self, material: Handle<Material>) -> Option<&MaterialStats> {
+        self.materials
+            .iter()
+            .find(|stats| stats.material == material)
+    }
+
+    /// Returns the statistics of the layer, or None if nothing was drawn on it last frame.
+    pub fn layer(&self, layer: u8) -> Option<&LayerStats> {
+        self.layers.iter().find(|stats| stats.layer == layer)
+    }
+
+    /// Returns the GPU time of the scope, or None if there is no result for it yet.
+    pub fn gpu_scope(&self, name: &str) -> Option<&GpuScope> {
+        self.gpu_scopes.iter().find(|scope| scope.name == name)
+    }
+
+    /// Resets all counters, but keeps the allocated memory for the next frame.
+    pub(crate) fn clear(&mut self) {
+        self.draw_calls = 0;
+        self.instances = 0;
+        self.material_binds = 0;
+        self.state_changes = 0;
+        self.materials.clear();
+        self.layers.clear();
+        self.overdraw = None;
+    }
+
+    pub(crate) fn record_material_bind(&mut self) {
+        self.material_binds += 1;
+        self.state_changes += 1;
+    }
+
+    pub(crate) fn record_state_change(&mut self) {
+        self.state_changes += 1;
+    }
+
+    pub(crate) fn record_draw(&mut self, command: &DrawCommand) {
+        //every command is a single instance until instancing is supported
+        let instances = 1;
+
+        self.draw_calls += 1;
+        self.instances += instances;
+
+        // the draw list is sorted, so the same material is most likely the last one
+        match self
+            .materials
+            .iter_mut()
+            .rev()
+            .find(|stats| stats.material == command.material)
+        {
+            Some(stats) => {
+                stats.draw_calls += 1;
+                stats.instances += instances;
+            }
+            None => self.materials.push(MaterialStats {
+                material: command.material,
+                draw_calls: 1,
+                instances,
+                gpu_time: None,
+            }),
+        }
+
+        let layer = command.sort_key.layer();
+        match self
+            .layers
+            .iter_mut()
+            .rev()
+            .find(|stats| stats.layer == layer)
+        {
+            Some(stats) => {
+                stats.draw_calls += 1;
+                stats.instances += instances;
+            }
+            None => self.layers.push(LayerStats {
+                layer,
+                draw_calls: 1,
+                instances,
+                gpu_time: None,
+            }),
+        }
+    }
+
+    /// Sums up the GPU times of the runs per material and per layer. Materials and layers
+    /// without a run have no GPU time.
+    pub(crate) fn attribute_gpu_time(&mut self, runs: &[TimedRun]) {
+        let sum = |matches: &dyn Fn(&TimedRun) -> bool| {
+            runs.iter()
+                .filter(|run| matches(run))
+                .map(|run| run.nanoseconds)
+                .reduce(|sum, nanoseconds| sum + nanoseconds)
+                .map(Duration::from_nanos)
+        };
+
+        for stats in &mut self.materials {
+            stats.gpu_time = sum(&|run| run.material == stats.material);
+        }
+        for stats in &mut self.layers {
+            stats.gpu_time = sum(&|run| run.layer == stats.layer);
+        }
+    }
+}
+
+impl std::fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} draw calls, {} instances, {} material binds, {} state changes",
+            self.draw_calls, self.instances, self.material_binds, self.state_changes
+        )?;
+        if !self.evicted_textures.is_empty() || self.reloaded_textures > 0 {
+            writeln!(
+                f,
+                "  streaming: {} evicted, {} reloaded, {} bytes resident",
+                self.evicted_textures.len(),
+                self.reloaded_textures,
+                self.streamed_bytes
+            )?;
+        }
+        if let Some(overdraw) = &self.overdraw {
+            writeln!(
+                f,
+                "  overdraw: {:.2} average, {} max",
+                overdraw.average(),
+                overdraw.max
+            )?;
+        }
+        for scope in &self.gpu_scopes {
+            writeln!(f, "  {}: {:.3} ms", scope.name, scope.milliseconds())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let stats = FrameStats {
+            draw_calls: 3,
+            instances: 3,
+            material_binds: 2,
+            state_changes: 4,
+            gpu_scopes: vec![GpuScope {
+                name: "shadow pass".to_string(),
+                nanoseconds: 1_250_000,
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            stats.to_string(),
+            "3 draw calls, 3 instances, 2 material binds, 4 state changes\n  shadow pass: 1.250 ms\n"
+        );
+        assert_eq!(stats.gpu_scope("shadow pass").unwrap().milliseconds(), 1.25);
+        assert_eq!(stats.gpu_scope("lighting"), None);
+    }
+
+    #[test]
+    fn gpu_time() {
+        let [first, second, third] = [0, 1, 2].map(|index| Handle::from_parts(index, 0));
+        let mut stats = FrameStats {
+            materials: [first, second, third]
+                .map(|material| MaterialStats {
+                    material,
+                    draw_calls: 1,
+                    instances: 1,
+                    gpu_time: None,
+                })
+                .to_vec(),
+            layers: [0, 1]
+                .map(|layer| LayerStats {
+                    layer,
+                    draw_calls: 1,
+                    instances: 1,
+                    gpu_time: None,
+                })
+                .to_vec(),
+            ..Default::default()
+        };
+
+        let run = |material, layer, nanoseconds| TimedRun {
+            material,
+            layer,
+            nanoseconds,
+        };
+        stats.attribute_gpu_time(&[run(first, 0, 100), run(second, 0, 50), run(first, 1, 20)]);
+
+        // the runs of a material on different layers add up
+        let gpu_time = |stats: &FrameStats, material| stats.material(material).unwrap().gpu_time;
+        assert_eq!(gpu_time(&stats, first), Some(Duration::from_nanos(120)));
+        assert_eq!(gpu_time(&stats, second), Some(Duration::from_nanos(50)));
+        // the third material wasn't drawn in the measured frame
+        assert_eq!(gpu_time(&stats, third), None);
+        assert_eq!(
+            stats.layer(0).unwrap().gpu_time,
+            Some(Duration::from_nanos(150))
+        );
+        assert_eq!(
+            stats.layer(1).unwrap().gpu_time,
+            Some(Duration::from_nanos(20))
+        );
+
+        stats.attribute_gpu_time(&[]);
+        assert_eq!(gpu_time(&stats, first), None);
+    }
+
+    #[cfg(feature = "opengl")]
+    #[test]
+    fn overdraw() {
+        let overdraw = OverdrawStats::from_counts([0, 1, 3, 2]);
+        assert_eq!(
+            overdraw,
+            OverdrawStats {
+                pixels: 4,
+                covered_pixels: 3,
+                fragments: 6,
+                max: 3,
+            }
+        );
+        assert_eq!(overdraw.average(), 2.0);
+        assert_eq!(OverdrawStats::from_counts([0; 4]).average(), 0.0);
+
+        let stats = FrameStats {
+            overdraw: Some(overdraw),
+            ..Default::default()
+        };
+        assert_eq!(
+            stats.to_string(),
+            "0 draw calls, 0 instances, 0 material binds, 0 state changes\n  overdraw: 2.00 average, 3 max\n"
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use crate::{Handle, Renderer, RendererError};
+
+use super::{buffer::CreateBuffer, Buffer, Context};
+
+/// Regions of a [StreamBuffer], one per frame in flight.
+pub(crate) const STREAM_REGIONS: usize = 3;
+
+/// The bytes of a [StreamBuffer] that a [push][StreamBuffer::push] wrote to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BufferSlice {
+    pub buffer: Handle<Buffer>,
+    /// Offset of the first byte in the buffer.
+    pub offset: usize,
+    /// Size in bytes.
+    pub size: usize,
+}
+
+impl BufferSlice {
+    /// Index of the first element in the buffer, for the
+    /// [vertex_offset][super::Mesh::vertex_offset] of a mesh whose vertices have the stride, or
+    /// the [base_vertex][super::Mesh::base_vertex] of an indexed one.
+    pub fn first_element(&self, stride: usize) -> usize {
+        self.offset / stride
+    }
+
+    /// Number of elements of the stride in the slice.
+    pub fn count(&self, stride: usize) -> u32 {
+        (self.size / stride) as u32
+    }
+}
+
+/// Vertex buffer for data that is written every frame, like UI, particles or debug lines.
+///
+/// The buffer is split into one region per frame in flight, and every frame writes into the
+/// region the oldest frame used, so the writes don't wait for the draws of the last frames. The
+/// OpenGL backend maps the buffer persistently and fences the regions if `glBufferStorage` is
+/// available, and orphans the buffer at the start of every region otherwise.
+///
+/// The [buffer][Self::buffer] is created once and used in [BufferAttributes][super::BufferAttributes]
+/// like any other buffer, the draws select their vertices by the
+/// [vertex_offset][super::Mesh::vertex_offset] of the mesh, see [BufferSlice::first_element].
+///
+/// The content isn't [retained][Renderer::set_retain_data], the buffer has to be created again
+/// after the context was lost.
+#[derive(Debug)]
+pub struct StreamBuffer {
+    buffer: Handle<Buffer>,
+    region_size: usize,
+    region: usize,
+    /// Bytes written into the region in the current frame.
+    cursor: usize,
+    /// Frame the region was started in.
+    frame: Option<u64>,
+}
+
+impl StreamBuffer {
+    /// Stream buffer whose frames can push up to region_size bytes each.
+    pub fn new<C: Context>(
+        ctx: &mut Renderer<C>,
+        region_size: usize,
+    ) -> Result<Self, RendererError> {
+        let size =
+            region_size
+                .checked_mul(STREAM_REGIONS)
+                .ok_or(RendererError::ConversionError {
+                    error: format!("Stream buffer regions of {region_size} bytes are too large"),
+                })?;
+        let buffer = C::Buffer::with_stream(&mut ctx.context, size)?;
+
+        Ok(Self {
+            buffer: ctx.buffers.push(buffer),
+            region_size,
+            region: 0,
+            cursor: 0,
+            frame: None,
+        })
+    }
+
+    pub fn buffer(&self) -> Handle<Buffer> {
+        self.buffer
+    }
+
+    /// Bytes each frame can push.
+    pub fn region_size(&self) -> usize {
+        self.region_size
+    }
+
+    /// Writes the data into the region of the frame and returns where it is. The slice is only
+    /// valid for the draws of the current frame.
+    ///
+    /// The offset is a multiple of the size of T, so pushing whole vertices keeps the
+    /// [first_element][BufferSlice::first_element] exact. Fails if the data doesn't fit in what
+    /// is left of the region.
+    pub fn push<T: Copy, C: Context>(
+        &mut self,
+        ctx: &mut Renderer<C>,
+        data: &[T],
+    ) -> Result<BufferSlice, RendererError> {
+        let stored = ctx
+            .buffers
+            .get_mut(self.buffer)
+            .ok_or(RendererError::ResourceNotFound {
+                resource: format!("Buffer: {:?}", self.buffer),
+            })?;
+
+        let frame = ctx.residency.frame();
+        if self.frame != Some(frame) {
+            if self.frame.is_some() {
+                self.region = (self.region + 1) % STREAM_REGIONS;
+            }
+            stored.begin_stream_region(&mut ctx.context, self.region)?;
+            self.frame = Some(frame);
+            self.cursor = 0;
+        }
+
+        let region_start = self.region * self.region_size;
+        let offset = (region_start + self.cursor).next_multiple_of(std::mem::size_of::<T>().max(1));
+        let size = std::mem::size_of_val(data);
+        if offset + size > region_start + self.region_size {
+            return Err(RendererError::FailedToUpdateBuffer {
+                error: format!(
+                    "{size} bytes don't fit in the {} bytes left in the stream region of {:?}",
+                    (region_start + self.region_size).saturating_sub(offset),
+                    self.buffer
+                ),
+            });
+        }
+
+        stored.update(&mut ctx.context, offset, data)?;
+        self.cursor = offset + size - region_start;
+        Ok(BufferSlice {
+            buffer: self.buffer,
+            offset,
+            size,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod test {
+    use super::*;
+    use crate::{Backend, RecordedCommand};
+
+    #[test]
+    fn push_into_regions() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let mut stream = StreamBuffer::new(&mut renderer, 64).unwrap();
+        assert_eq!(renderer.buffers.get(stream.buffer()).unwrap().size(), 192);
+        renderer.clear_recorded_commands();
+
+        let first = stream.push(&mut renderer, &[[0.0f32; 3]; 2]).unwrap();
+        let second = stream.push(&mut renderer, &[0u8; 3]).unwrap();
+        let third = stream.push(&mut renderer, &[0.0f32; 2]).unwrap();
+        assert_eq!((first.offset, first.size), (0, 24));
+        assert_eq!(first.count(12), 2);
+        assert_eq!(second.offset, 24);
+        // aligned to the size of f32
+        assert_eq!(third.offset, 28);
+        assert!(stream.push(&mut renderer, &[0u8; 30]).is_err());
+
+        renderer.update();
+        let next = stream.push(&mut renderer, &[[0.0f32; 3]; 2]).unwrap();
+        // the second region starts at 64, the next multiple of the vertex size
+        assert_eq!(next.offset, 72);
+        assert_eq!(next.first_element(12), 6);
+
+        let commands: Vec<_> = renderer
+            .recorded_commands()
+            .iter()
+            .filter(|command| {
+                matches!(
+                    command,
+                    RecordedCommand::BeginStreamRegion { .. }
+                        | RecordedCommand::UpdateBuffer { .. }
+                )
+            })
+            .cloned()
+            .collect();
+        assert_eq!(
+            commands,
+            [
+                RecordedCommand::BeginStreamRegion { region: 0 },
+                RecordedCommand::UpdateBuffer {
+                    offset: 0,
+                    size: 24
+                },
+                RecordedCommand::UpdateBuffer {
+                    offset: 24,
+                    size: 3
+                },
+                RecordedCommand::UpdateBuffer {
+                    offset: 28,
+                    size: 8
+                },
+                RecordedCommand::BeginStreamRegion { region: 1 },
+                RecordedCommand::UpdateBuffer {
+                    offset: 72,
+                    size: 24
+                },
+            ]
+        );
+
+        for _ in 0..2 {
+            renderer.update();
+            stream.push(&mut renderer, &[0u8; 4]).unwrap();
+        }
+        // wraps around to the first region
+        assert_eq!(stream.push(&mut renderer, &[0u8; 4]).unwrap().offset, 4);
+    }
+}
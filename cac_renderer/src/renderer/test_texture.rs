@@ -0,0 +1,167 @@
+use crate::{Handle, Renderer, RendererError};
+
+use super::{
+    debug_numbers::{font_pixel, glyphs, DOT},
+    Context, Texture, TextureFormat,
+};
+
+/// Procedural [Rgba8][TextureFormat::Rgba8] patterns for examples, golden-image tests and spotting
+/// UV problems, see [Texture::from_pattern].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TestPattern {
+    /// Black and white cells, cells x cells of them.
+    Checker { cells: u32 },
+    /// U in red and V in green, from 0 on the left and bottom to 1 on the right and top.
+    Gradient,
+    /// Gray white noise, the same for the same seed.
+    Noise { seed: u32 },
+    /// The gradient with white lines between cells x cells cells, which are labeled with their
+    /// column and row, e.g. `2.0` for the third cell of the bottom row.
+    UvGrid { cells: u32 },
+}
+
+impl TestPattern {
+    /// The texels of the pattern, row by row, starting at the bottom like [Texture::new].
+    pub fn generate(&self, width: u32, height: u32) -> Vec<u8> {
+        let mut texels = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in 0..height {
+            for x in 0..width {
+                texels.extend_from_slice(&self.texel(x, y, width, height));
+            }
+        }
+        texels
+    }
+
+    fn texel(&self, x: u32, y: u32, width: u32, height: u32) -> [u8; 4] {
+        let gradient = || {
+            let u = (x as u64 * 255 / width.saturating_sub(1).max(1) as u64) as u8;
+            let v = (y as u64 * 255 / height.saturating_sub(1).max(1) as u64) as u8;
+            [u, v, 0, 255]
+        };
+
+        match *self {
+            TestPattern::Checker { cells } => {
+                let (column, row) = cell(x, y, width, height, cells);
+                let value = if (column + row) % 2 == 0 { 0 } else { 255 };
+                [value, value, value, 255]
+            }
+            TestPattern::Gradient => gradient(),
+            TestPattern::Noise { seed } => {
+                let value = hash(x ^ hash(y ^ hash(seed))) as u8;
+                [value, value, value, 255]
+            }
+            TestPattern::UvGrid { cells } => {
+                let cells = cells.max(1);
+                let (column, row) = cell(x, y, width, height, cells);
+                let cell_width = (width / cells).max(1);
+                let cell_height = (height / cells).max(1);
+                let (local_x, local_y) = (x % cell_width, y % cell_height);
+                if local_x == 0 || local_y == 0 {
+                    return [255; 4];
+                }
+                // counted from the top of the cell, like the font, below a row of margin
+                let from_top = cell_height - 1 - local_y;
+                if from_top > 0 && label_pixel(column, row, local_x - 1, from_top - 1, cell_height)
+                {
+                    return [255; 4];
+                }
+                gradient()
+            }
+        }
+    }
+}
+
+/// Column and row of the cell the texel is in, with cells x cells cells.
+fn cell(x: u32, y: u32, width: u32, height: u32, cells: u32) -> (u32, u32) {
+    let cells = cells.max(1) as u64;
+    (
+        (x as u64 * cells / width.max(1) as u64) as u32,
+        (y as u64 * cells / height.max(1) as u64) as u32,
+    )
+}
+
+/// Whether the pixel at the offset from the top left corner of the cell is part of its label.
+/// Labels are scaled up with the cell, with a pixel of margin.
+fn label_pixel(column: u32, row: u32, x: u32, y: u32, cell_height: u32) -> bool {
+    let scale = (cell_height / 24).max(1);
+    let (x, y) = (x / scale, y / scale);
+    let (glyph, glyph_column) = ((x / 4) as usize, x % 4);
+
+    let (column, column_count) = glyphs(column as f64, 0);
+    let (row, row_count) = glyphs(row as f64, 0);
+    column[..column_count]
+        .iter()
+        .chain(std::iter::once(&DOT))
+        .chain(&row[..row_count])
+        .nth(glyph)
+        .is_some_and(|glyph| font_pixel(*glyph as usize, glyph_column, y))
+}
+
+/// Integer hash, see <https://nullprogram.com/blog/2018/07/31/>.
+fn hash(mut value: u32) -> u32 {
+    value ^= value >> 16;
+    value = value.wrapping_mul(0x7feb352d);
+    value ^= value >> 15;
+    value = value.wrapping_mul(0x846ca68b);
+    value ^ value >> 16
+}
+
+impl Texture {
+    /// [Rgba8][TextureFormat::Rgba8] texture of the pattern, generated on the CPU.
+    pub fn from_pattern<C: Context>(
+        ctx: &mut Renderer<C>,
+        pattern: TestPattern,
+        width: u32,
+        height: u32,
+    ) -> Result<Handle<Self>, RendererError> {
+        let texels = pattern.generate(width, height);
+        Texture::new(ctx, &texels, width, height, TextureFormat::Rgba8)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn texel(texels: &[u8], width: u32, x: u32, y: u32) -> [u8; 4] {
+        let index = (y * width + x) as usize * 4;
+        texels[index..index + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn patterns() {
+        let checker = TestPattern::Checker { cells: 2 }.generate(4, 4);
+        assert_eq!(checker.len(), 64);
+        assert_eq!(texel(&checker, 4, 0, 0), [0, 0, 0, 255]);
+        assert_eq!(texel(&checker, 4, 2, 0), [255; 4]);
+        assert_eq!(texel(&checker, 4, 3, 3), [0, 0, 0, 255]);
+
+        let gradient = TestPattern::Gradient.generate(2, 2);
+        assert_eq!(texel(&gradient, 2, 1, 0), [255, 0, 0, 255]);
+        assert_eq!(texel(&gradient, 2, 0, 1), [0, 255, 0, 255]);
+
+        let noise = TestPattern::Noise { seed: 1 };
+        assert_eq!(noise.generate(8, 8), noise.generate(8, 8));
+        assert_ne!(
+            noise.generate(8, 8),
+            TestPattern::Noise { seed: 2 }.generate(8, 8)
+        );
+    }
+
+    #[test]
+    fn uv_grid_labels() {
+        let grid = TestPattern::UvGrid { cells: 2 }.generate(32, 32);
+        // lines at the left and bottom edge of the cells
+        assert_eq!(texel(&grid, 32, 0, 5), [255; 4]);
+        assert_eq!(texel(&grid, 32, 5, 16), [255; 4]);
+
+        // the label of the top right cell is "1.1", its first row starts right of the line and
+        // below the top row of the cell
+        let label_row = |x: u32| texel(&grid, 32, 16 + 1 + x, 30) == [255; 4];
+        let first_row: Vec<_> = (0..11).map(label_row).collect();
+        assert_eq!(
+            first_row,
+            [false, true, false, false, false, false, false, false, false, true, false]
+        );
+    }
+}
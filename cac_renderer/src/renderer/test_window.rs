@@ -0,0 +1,10 @@
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle, XlibHandle};
+
+/// Window handle for the tests of backends that don't need a window, like the headless one.
+pub(crate) struct NoWindow;
+
+unsafe impl HasRawWindowHandle for NoWindow {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        RawWindowHandle::Xlib(XlibHandle::empty())
+    }
+}
@@ -0,0 +1,941 @@
+use crate::{Handle, Renderer, RendererError};
+
+use super::{Context, TextureFormat};
+
+/// Image on the graphics device, sampled by shaders through sampler uniforms.
+/// Materials get it with a texture property, see
+/// [PropertyValue::Texture][super::PropertyValue::Texture].
+///
+/// The first row of the texel data is the bottom of the texture, at the texture coordinate
+/// v = 0. Most image decoders return the top row first instead, which shows the image upside
+/// down. Upload their images with [new_top_down][Self::new_top_down], or flip them with
+/// [flip_y][Self::flip_y] beforehand.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Texture {}
+
+pub trait CreateTexture: Sized {
+    /// The data is already validated to contain width * height texels of the format, with the
+    /// rows row_pitch bytes apart.
+    fn new(
+        data: &[u8],
+        width: u32,
+        height: u32,
+        row_pitch: usize,
+        format: TextureFormat,
+    ) -> Result<Self, RendererError>;
+    /// Like new, with the whole mip chain generated from the data. The format is already
+    /// validated to be filterable, so neither a depth nor an integer format.
+    fn mipmapped(
+        data: &[u8],
+        width: u32,
+        height: u32,
+        row_pitch: usize,
+        format: TextureFormat,
+    ) -> Result<Self, RendererError>;
+    /// The data is already validated to contain width texels of the format.
+    fn new_1d(data: &[u8], width: u32, format: TextureFormat) -> Result<Self, RendererError>;
+    /// The faces are already validated to contain size * size texels of the format.
+    fn cubemap(faces: [&[u8]; 6], size: u32, format: TextureFormat) -> Result<Self, RendererError>;
+    /// There's at least one cubemap, and the faces are already validated to contain size * size
+    /// texels of the format.
+    fn cubemap_array(
+        cubemaps: &[[&[u8]; 6]],
+        size: u32,
+        format: TextureFormat,
+    ) -> Result<Self, RendererError>;
+    /// The levels are already validated to be the mip chain of a width * height texture, starting
+    /// with the largest one.
+    fn compressed(
+        levels: &[&[u8]],
+        width: u32,
+        height: u32,
+        format: CompressedFormat,
+    ) -> Result<Self, RendererError>;
+    /// The size is already validated to not be empty and samples to be at least 1.
+    fn multisampled(
+        width: u32,
+        height: u32,
+        samples: u32,
+        format: TextureFormat,
+    ) -> Result<Self, RendererError>;
+    /// The region is already validated to be inside the texture, and the data to contain its
+    /// texels with the rows row_pitch bytes apart. Only 2D textures can be updated.
+    fn update(
+        &mut self,
+        data: &[u8],
+        region: TextureRegion,
+        row_pitch: usize,
+    ) -> Result<(), RendererError>;
+    /// The description is already validated to have a format with the texel size of the
+    /// texture's format, and levels and a layer inside of the texture.
+    fn view(&self, description: &TextureViewDescription) -> Result<Self, RendererError>;
+    fn set_swizzle(&mut self, swizzle: Swizzle);
+    /// The texture is already validated to be [TextureFormat::Depth24Stencil8].
+    fn set_depth_stencil_mode(&mut self, mode: DepthStencilMode) -> Result<(), RendererError>;
+    fn size(&self) -> (u32, u32);
+    /// None for compressed textures.
+    fn format(&self) -> Option<TextureFormat>;
+    fn levels(&self) -> u32;
+    fn kind(&self) -> TextureKind;
+}
+
+/// Shape of a texture, which decides the sampler uniform that reads it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TextureKind {
+    /// Row of texels, sampled with a `sampler1D` uniform, e.g. for gradients and transfer
+    /// functions.
+    Texture1D,
+    /// Sampled with a `sampler2D` uniform, or `sampler2DShadow` and `usampler2D` for
+    /// depth-stencil textures.
+    Texture2D,
+    /// Sampled with a `samplerCube` uniform.
+    Cubemap,
+    /// Sampled with a `samplerCubeArray` uniform, or `samplerCubeArrayShadow` for depth, e.g.
+    /// for the shadows of many point lights.
+    CubemapArray { cubemaps: u32 },
+    /// Sampled with a `sampler2DMS` uniform.
+    Multisampled,
+}
+
+impl TextureKind {
+    /// Layers a [view][Texture::view] can select, the faces of all cubemaps for cubemaps.
+    pub fn layers(&self) -> u32 {
+        match self {
+            Self::Texture1D | Self::Texture2D | Self::Multisampled => 1,
+            Self::Cubemap => 6,
+            Self::CubemapArray { cubemaps } => cubemaps * 6,
+        }
+    }
+}
+
+/// Part of a texture that a [view][Texture::view] covers, and the format it's read as.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TextureViewDescription {
+    /// Needs the texel size of the viewed texture's format, e.g. [TextureFormat::Srgb8Alpha8] to
+    /// view a [TextureFormat::Rgba8] texture. Depth-stencil textures can't be reinterpreted.
+    pub format: TextureFormat,
+    pub first_level: u32,
+    pub levels: u32,
+    /// The face of a cubemap, to view it as 2D texture. The faces of a cubemap array are in order
+    /// of the cubemaps, so face 2 of the second cubemap is layer 8. None views all layers.
+    pub layer: Option<u32>,
+}
+
+impl TextureViewDescription {
+    /// View of the base level and all layers, read as the format.
+    pub fn new(format: TextureFormat) -> Self {
+        Self {
+            format,
+            first_level: 0,
+            levels: 1,
+            layer: None,
+        }
+    }
+
+    pub fn with_levels(self, first_level: u32, levels: u32) -> Self {
+        Self {
+            first_level,
+            levels,
+            ..self
+        }
+    }
+
+    pub fn with_layer(self, layer: u32) -> Self {
+        Self {
+            layer: Some(layer),
+            ..self
+        }
+    }
+}
+
+/// Which component of a [TextureFormat::Depth24Stencil8] texture is sampled.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DepthStencilMode {
+    /// Normalized depth, with a `sampler2D` or `sampler2DShadow` uniform.
+    #[default]
+    Depth,
+    /// The stencil value as unsigned integer, with a `usampler2D` uniform. Integer textures
+    /// can't be filtered, so use a nearest sampler or none.
+    Stencil,
+}
+
+/// Where a channel of a sampled texel comes from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SwizzleSource {
+    Red,
+    Green,
+    Blue,
+    Alpha,
+    Zero,
+    One,
+}
+
+/// Reorders the channels of a texture when it's sampled, e.g. to read a single channel glyph
+/// atlas as `.rrrr` without changing the shader.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Swizzle {
+    pub r: SwizzleSource,
+    pub g: SwizzleSource,
+    pub b: SwizzleSource,
+    pub a: SwizzleSource,
+}
+
+impl Swizzle {
+    /// Every channel is sampled from itself.
+    pub const IDENTITY: Self = Self::new(
+        SwizzleSource::Red,
+        SwizzleSource::Green,
+        SwizzleSource::Blue,
+        SwizzleSource::Alpha,
+    );
+
+    pub const fn new(
+        r: SwizzleSource,
+        g: SwizzleSource,
+        b: SwizzleSource,
+        a: SwizzleSource,
+    ) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Every channel is sampled from the source, e.g. `.rrrr` with [SwizzleSource::Red].
+    pub const fn splat(source: SwizzleSource) -> Self {
+        Self::new(source, source, source, source)
+    }
+}
+
+impl Default for Swizzle {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// Rectangle of texels, with the origin at the bottom left of the texture.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TextureRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Block-compressed texel formats. All of them compress blocks of 4x4 texels.
+///
+/// Not every driver supports every format, see
+/// [supports_compressed_format][super::Backend::supports_compressed_format].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CompressedFormat {
+    /// Also known as DXT1.
+    Bc1,
+    Bc1Srgb,
+    /// Also known as DXT3.
+    Bc2,
+    Bc2Srgb,
+    /// Also known as DXT5.
+    Bc3,
+    Bc3Srgb,
+    /// Single channel, e.g. for masks.
+    Bc4,
+    /// Two channels, e.g. for normal maps.
+    Bc5,
+    /// Unsigned HDR colors.
+    Bc6hUfloat,
+    Bc7,
+    Bc7Srgb,
+    Etc2Rgb8,
+    Etc2Srgb8,
+    Etc2Rgba8,
+    Etc2Srgb8Alpha8,
+}
+
+impl CompressedFormat {
+    pub const ALL: [CompressedFormat; 15] = [
+        Self::Bc1,
+        Self::Bc1Srgb,
+        Self::Bc2,
+        Self::Bc2Srgb,
+        Self::Bc3,
+        Self::Bc3Srgb,
+        Self::Bc4,
+        Self::Bc5,
+        Self::Bc6hUfloat,
+        Self::Bc7,
+        Self::Bc7Srgb,
+        Self::Etc2Rgb8,
+        Self::Etc2Srgb8,
+        Self::Etc2Rgba8,
+        Self::Etc2Srgb8Alpha8,
+    ];
+
+    /// Size of a 4x4 block in bytes.
+    pub fn block_size(&self) -> usize {
+        match self {
+            Self::Bc1 | Self::Bc1Srgb | Self::Bc4 | Self::Etc2Rgb8 | Self::Etc2Srgb8 => 8,
+            _ => 16,
+        }
+    }
+
+    /// Size in bytes of a width * height image. Partial blocks at the edges take a whole block.
+    pub fn image_size(&self, width: u32, height: u32) -> usize {
+        let blocks = |texels: u32| (texels as usize).div_ceil(4).max(1);
+        blocks(width) * blocks(height) * self.block_size()
+    }
+}
+
+impl Texture {
+    /// 2D texture, sampled with a `sampler2D` uniform.
+    ///
+    /// The data has width * height texels of the format, row by row, starting at the bottom.
+    pub fn new<C: Context>(
+        ctx: &mut Renderer<C>,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Result<Handle<Self>, RendererError> {
+        let row_pitch = width as usize * format.texel_size();
+        Self::with_row_pitch(ctx, data, width, height, row_pitch, format)
+    }
+
+    /// Like [new][Self::new], but the data starts with the top row, like the images of most
+    /// decoders. The rows are flipped while uploading, so v = 0 is still the bottom.
+    pub fn new_top_down<C: Context>(
+        ctx: &mut Renderer<C>,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Result<Handle<Self>, RendererError> {
+        let row_pitch = width as usize * format.texel_size();
+        check_size(data, width, height, row_pitch, format)
+            .map_err(|error| RendererError::FailedToCreateTexture { error })?;
+
+        let flipped = Self::flip_y(data, row_pitch, height);
+        let texture = C::Texture::new(&flipped, width, height, row_pitch, format)?;
+        let handle = ctx.textures.push(texture);
+        ctx.retention
+            .retain_texture(handle, &flipped, (width, height), row_pitch, format, false);
+        Ok(handle)
+    }
+
+    /// Reverses the order of the height rows of the data, between top-down and bottom-up
+    /// images, e.g. for [update][Self::update]s with top-down data. The rows are row_pitch bytes
+    /// apart, bytes past the last row are dropped.
+    pub fn flip_y(data: &[u8], row_pitch: usize, height: u32) -> Vec<u8> {
+        if row_pitch == 0 {
+            return Vec::new();
+        }
+
+        let rows = data.chunks(row_pitch).take(height as usize);
+        let mut flipped = Vec::with_capacity(data.len());
+        for row in rows.rev() {
+            flipped.extend_from_slice(row);
+        }
+        flipped
+    }
+
+    /// Like [new][Self::new], but the rows start row_pitch bytes apart, e.g. for images with
+    /// padded rows. The last row doesn't need the padding.
+    pub fn with_row_pitch<C: Context>(
+        ctx: &mut Renderer<C>,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        row_pitch: usize,
+        format: TextureFormat,
+    ) -> Result<Handle<Self>, RendererError> {
+        check_size(data, width, height, row_pitch, format)
+            .map_err(|error| RendererError::FailedToCreateTexture { error })?;
+
+        let texture = C::Texture::new(data, width, height, row_pitch, format)?;
+        let handle = ctx.textures.push(texture);
+        ctx.retention
+            .retain_texture(handle, data, (width, height), row_pitch, format, false);
+        Ok(handle)
+    }
+
+    /// Like [new][Self::new], with the complete mip chain down to 1x1 generated from the data,
+    /// to sample it minified without aliasing.
+    pub fn mipmapped<C: Context>(
+        ctx: &mut Renderer<C>,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Result<Handle<Self>, RendererError> {
+        let row_pitch = width as usize * format.texel_size();
+        check_size(data, width, height, row_pitch, format)
+            .map_err(|error| RendererError::FailedToCreateTexture { error })?;
+        if format == TextureFormat::Depth24Stencil8 || format.is_integer() {
+            return Err(RendererError::FailedToCreateTexture {
+                error: format!("Mip levels can't be generated for {format:?} textures"),
+            });
+        }
+
+        let texture = C::Texture::mipmapped(data, width, height, row_pitch, format)?;
+        let handle = ctx.textures.push(texture);
+        ctx.retention
+            .retain_texture(handle, data, (width, height), row_pitch, format, true);
+        Ok(handle)
+    }
+
+    /// Cubemap for skyboxes and environment reflections, sampled with a `samplerCube` uniform.
+    ///
+    /// The faces are in the order +X, -X, +Y, -Y, +Z, -Z, each with size * size texels of the
+    /// format, row by row.
+    pub fn cubemap<C: Context>(
+        ctx: &mut Renderer<C>,
+        faces: [&[u8]; 6],
+        size: u32,
+        format: TextureFormat,
+    ) -> Result<Handle<Self>, RendererError> {
+        let row_pitch = size as usize * format.texel_size();
+        for (index, face) in faces.iter().enumerate() {
+            check_size(face, size, size, row_pitch, format).map_err(|error| {
+                RendererError::FailedToCreateTexture {
+                    error: format!("Cubemap face {index}: {error}"),
+                }
+            })?;
+        }
+
+        let texture = C::Texture::cubemap(faces, size, format)?;
+        Ok(ctx.textures.push(texture))
+    }
+
+    /// Array of cubemaps with the same size and format, sampled with a `samplerCubeArray`
+    /// uniform and the index of the cubemap as fourth coordinate. E.g. the shadow maps of all
+    /// point lights in a single texture unit.
+    ///
+    /// Each cubemap has its faces in the order of [cubemap][Self::cubemap]. Requires OpenGL 4.0
+    /// or newer.
+    pub fn cubemap_array<C: Context>(
+        ctx: &mut Renderer<C>,
+        cubemaps: &[[&[u8]; 6]],
+        size: u32,
+        format: TextureFormat,
+    ) -> Result<Handle<Self>, RendererError> {
+        if cubemaps.is_empty() {
+            return Err(RendererError::FailedToCreateTexture {
+                error: "Cubemap array needs at least one cubemap".to_string(),
+            });
+        }
+
+        let row_pitch = size as usize * format.texel_size();
+        for (cubemap, faces) in cubemaps.iter().enumerate() {
+            for (index, face) in faces.iter().enumerate() {
+                check_size(face, size, size, row_pitch, format).map_err(|error| {
+                    RendererError::FailedToCreateTexture {
+                        error: format!("Cubemap {cubemap} face {index}: {error}"),
+                    }
+                })?;
+            }
+        }
+
+        let texture = C::Texture::cubemap_array(cubemaps, size, format)?;
+        Ok(ctx.textures.push(texture))
+    }
+
+    /// 1D texture, sampled with a `sampler1D` uniform, e.g. a color gradient that maps values of
+    /// a simulation to colors.
+    pub fn new_1d<C: Context>(
+        ctx: &mut Renderer<C>,
+        data: &[u8],
+        width: u32,
+        format: TextureFormat,
+    ) -> Result<Handle<Self>, RendererError> {
+        let row_pitch = width as usize * format.texel_size();
+        check_size(data, width, 1, row_pitch, format)
+            .map_err(|error| RendererError::FailedToCreateTexture { error })?;
+
+        let texture = C::Texture::new_1d(data, width, format)?;
+        Ok(ctx.textures.push(texture))
+    }
+
+    /// 2D texture with block-compressed texels, sampled with a `sampler2D` uniform.
+    ///
+    /// The levels are its mip chain, starting with the width * height level and halving the size
+    /// down to at most 1x1. Loaders for KTX2 and DDS files are in the
+    /// `compressed` module behind the `compressed-textures` feature.
+    pub fn compressed<C: Context>(
+        ctx: &mut Renderer<C>,
+        levels: &[&[u8]],
+        width: u32,
+        height: u32,
+        format: CompressedFormat,
+    ) -> Result<Handle<Self>, RendererError> {
+        if width == 0 || height == 0 || levels.is_empty() {
+            return Err(RendererError::FailedToCreateTexture {
+                error: "Compressed texture can't be empty".to_string(),
+            });
+        }
+
+        let max_levels = mip_levels(width, height) as usize;
+        if levels.len() > max_levels {
+            return Err(RendererError::FailedToCreateTexture {
+                error: format!(
+                    "{width}x{height} texture has at most {max_levels} levels, got {}",
+                    levels.len()
+                ),
+            });
+        }
+
+        for (level, data) in levels.iter().enumerate() {
+            let (width, height) = ((width >> level).max(1), (height >> level).max(1));
+            let size = format.image_size(width, height);
+            if data.len() != size {
+                return Err(RendererError::FailedToCreateTexture {
+                    error: format!(
+                        "Level {level} has {} bytes, but {width}x{height} {format:?} needs {size}",
+                        data.len()
+                    ),
+                });
+            }
+        }
+
+        let texture = C::Texture::compressed(levels, width, height, format)?;
+        Ok(ctx.textures.push(texture))
+    }
+
+    /// Multisampled 2D texture with undefined texels, to render into it with
+    /// [texture_target][Renderer::texture_target] and read the individual samples with a
+    /// `sampler2DMS` uniform and `texelFetch`, e.g. for custom resolves or temporal
+    /// anti-aliasing.
+    ///
+    /// Multisampled textures have no mip levels and aren't filtered, so samplers have no effect
+    /// on them.
+    pub fn multisampled<C: Context>(
+        ctx: &mut Renderer<C>,
+        width: u32,
+        height: u32,
+        samples: u32,
+        format: TextureFormat,
+    ) -> Result<Handle<Self>, RendererError> {
+        if width == 0 || height == 0 || samples == 0 {
+            return Err(RendererError::FailedToCreateTexture {
+                error: format!(
+                    "Invalid multisampled texture {width}x{height} with {samples} samples"
+                ),
+            });
+        }
+
+        let texture = C::Texture::multisampled(width, height, samples, format)?;
+        Ok(ctx.textures.push(texture))
+    }
+
+    /// New texture that shares the texels of the texture, but reads them in another format or
+    /// only covers some of its levels or layers. E.g. a [TextureFormat::Rgba8] render target can
+    /// be sampled as [TextureFormat::Srgb8Alpha8] in a later pass, without a copy.
+    ///
+    /// Writes to either texture are visible in both, and removing one keeps the other intact.
+    /// Requires OpenGL 4.3 or newer.
+    pub fn view<C: Context>(
+        ctx: &mut Renderer<C>,
+        texture: Handle<Self>,
+        description: &TextureViewDescription,
+    ) -> Result<Handle<Self>, RendererError> {
+        let view_error = |error: String| RendererError::FailedToCreateTexture { error };
+
+        let source = ctx
+            .textures
+            .get(texture)
+            .ok_or(RendererError::ResourceNotFound {
+                resource: format!("Texture: {texture:?}"),
+            })?;
+        let format = source
+            .format()
+            .ok_or_else(|| view_error("Compressed textures can't be viewed".to_string()))?;
+
+        let depth = |format| format == TextureFormat::Depth24Stencil8;
+        let view_format = description.format;
+        if format.texel_size() != view_format.texel_size() || depth(format) != depth(view_format) {
+            return Err(view_error(format!(
+                "{format:?} texture can't be viewed as {view_format:?}"
+            )));
+        }
+
+        let levels = description.first_level.checked_add(description.levels);
+        if description.levels == 0 || levels.is_none_or(|levels| levels > source.levels()) {
+            return Err(view_error(format!(
+                "Texture with {} levels has no levels {}..+{}",
+                source.levels(),
+                description.first_level,
+                description.levels
+            )));
+        }
+
+        let layers = source.kind().layers();
+        if description.layer.is_some_and(|layer| layer >= layers) {
+            return Err(view_error(format!(
+                "Texture with {layers} layers has no layer {:?}",
+                description.layer
+            )));
+        }
+
+        let view = source.view(description)?;
+        Ok(ctx.textures.push(view))
+    }
+
+    /// Names the texture in graphics debuggers, see [DebugLabel][super::DebugLabel].
+    pub fn set_label<C: Context>(
+        ctx: &mut Renderer<C>,
+        texture: Handle<Self>,
+        label: &str,
+    ) -> Result<(), RendererError> {
+        super::set_debug_label(&mut ctx.textures, texture, label, "Texture")
+    }
+
+    /// Sets whether the depth or the stencil of a [TextureFormat::Depth24Stencil8] texture is
+    /// sampled, e.g. to read the stencil of a deferred renderer for decals or outlines.
+    ///
+    /// Requires OpenGL 4.3 or newer.
+    pub fn set_depth_stencil_mode<C: Context>(
+        ctx: &mut Renderer<C>,
+        texture: Handle<Self>,
+        mode: DepthStencilMode,
+    ) -> Result<(), RendererError> {
+        let texture = ctx
+            .textures
+            .get_mut(texture)
+            .ok_or(RendererError::ResourceNotFound {
+                resource: format!("Texture: {texture:?}"),
+            })?;
+        if texture.format() != Some(TextureFormat::Depth24Stencil8) {
+            return Err(RendererError::FailedToUpdateTexture {
+                error: format!("{:?} texture has no depth and stencil", texture.format()),
+            });
+        }
+
+        texture.set_depth_stencil_mode(mode)
+    }
+
+    /// Sets how the channels of the texture are reordered when it's sampled.
+    /// Textures start with [Swizzle::IDENTITY].
+    pub fn set_swizzle<C: Context>(
+        ctx: &mut Renderer<C>,
+        texture: Handle<Self>,
+        swizzle: Swizzle,
+    ) -> Result<(), RendererError> {
+        ctx.textures
+            .get_mut(texture)
+            .ok_or(RendererError::ResourceNotFound {
+                resource: format!("Texture: {texture:?}"),
+            })?
+            .set_swizzle(swizzle);
+        Ok(())
+    }
+
+    /// Replaces the texels of the region of a 2D texture, e.g. to add glyphs to an atlas.
+    ///
+    /// The data has the texels of the region in the format of the texture, row by row. The rows
+    /// start row_pitch bytes apart, or right after each other with None.
+    pub fn update<C: Context>(
+        ctx: &mut Renderer<C>,
+        texture: Handle<Self>,
+        region: TextureRegion,
+        data: &[u8],
+        row_pitch: Option<usize>,
+    ) -> Result<(), RendererError> {
+        let update_error = |error: String| RendererError::FailedToUpdateTexture { error };
+
+        let handle = texture;
+        let texture = ctx
+            .textures
+            .get_mut(texture)
+            .ok_or(RendererError::ResourceNotFound {
+                resource: format!("Texture: {texture:?}"),
+            })?;
+        let format = texture
+            .format()
+            .ok_or_else(|| update_error("Compressed textures can't be updated".to_string()))?;
+
+        let (width, height) = texture.size();
+        let inside =
+            |start: u32, size: u32, max: u32| start.checked_add(size).is_some_and(|end| end <= max);
+        if !inside(region.x, region.width, width) || !inside(region.y, region.height, height) {
+            return Err(update_error(format!(
+                "{region:?} is outside of the {width}x{height} texture"
+            )));
+        }
+
+        let row_pitch = row_pitch.unwrap_or(region.width as usize * format.texel_size());
+        check_size(data, region.width, region.height, row_pitch, format).map_err(update_error)?;
+        texture.update(data, region, row_pitch)?;
+        ctx.retention
+            .update_texture(handle, region, data, row_pitch);
+        Ok(())
+    }
+}
+
+/// Levels of the complete mip chain of a width * height texture, down to 1x1.
+pub(crate) fn mip_levels(width: u32, height: u32) -> u32 {
+    32 - width.max(height).leading_zeros()
+}
+
+/// Checks that the data has width * height texels, with the rows row_pitch bytes apart.
+pub(super) fn check_size(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    row_pitch: usize,
+    format: TextureFormat,
+) -> Result<(), String> {
+    if width == 0 || height == 0 {
+        return Err("Texture can't be empty".to_string());
+    }
+
+    let row_size = width as usize * format.texel_size();
+    if row_pitch < row_size || row_pitch % format.texel_size() != 0 {
+        return Err(format!(
+            "Row pitch {row_pitch} has to be a multiple of the {format:?} texel size and fit \
+             {width} texels"
+        ));
+    }
+
+    // the last row doesn't need to be padded
+    let size = row_pitch * (height as usize - 1) + row_size;
+    if data.len() < size || data.len() > row_pitch * height as usize {
+        return Err(format!(
+            "{} bytes don't match {width}x{height} {format:?} with a row pitch of {row_pitch}",
+            data.len()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cubemap_faces() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let face = [255_u8; 2 * 2 * 4];
+        let faces = [face.as_slice(); 6];
+
+        assert!(Texture::cubemap(&mut renderer, faces, 2, TextureFormat::Rgba8).is_ok());
+        assert!(Texture::cubemap(&mut renderer, faces, 0, TextureFormat::Rgba8).is_err());
+        assert!(Texture::cubemap(&mut renderer, faces, 2, TextureFormat::Rgba16F).is_err());
+
+        let mut faces = faces;
+        faces[3] = &face[..4];
+        assert!(Texture::cubemap(&mut renderer, faces, 2, TextureFormat::Rgba8).is_err());
+
+        assert!(Texture::new(&mut renderer, &face, 1, 4, TextureFormat::Rgba8).is_ok());
+        assert!(Texture::new(&mut renderer, &face, 2, 1, TextureFormat::Rgba8).is_err());
+    }
+
+    #[test]
+    fn flip_y() {
+        let rows = [1, 1, 2, 2, 3, 3];
+        assert_eq!(Texture::flip_y(&rows, 2, 3), [3, 3, 2, 2, 1, 1]);
+        // the last row isn't padded
+        assert_eq!(Texture::flip_y(&[1, 0, 2], 2, 2), [2, 1, 0]);
+        assert!(Texture::flip_y(&rows, 0, 3).is_empty());
+
+        let mut renderer = Renderer::new_headless().unwrap();
+        let format = TextureFormat::R8;
+        assert!(Texture::new_top_down(&mut renderer, &rows, 2, 3, format).is_ok());
+        assert!(Texture::new_top_down(&mut renderer, &rows, 3, 3, format).is_err());
+    }
+
+    #[test]
+    fn multisampled() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let format = TextureFormat::Rgba16F;
+
+        let texture = Texture::multisampled(&mut renderer, 64, 32, 4, format).unwrap();
+        let texture = renderer.textures.get(texture).unwrap();
+        assert_eq!(texture.size(), (64, 32));
+        assert_eq!(texture.samples, 4);
+        assert!(Texture::multisampled(&mut renderer, 64, 32, 0, format).is_err());
+        assert!(Texture::multisampled(&mut renderer, 0, 32, 4, format).is_err());
+    }
+
+    #[test]
+    fn depth_stencil_mode() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let depth_stencil =
+            Texture::new(&mut renderer, &[0; 4], 1, 1, TextureFormat::Depth24Stencil8).unwrap();
+        let color = Texture::new(&mut renderer, &[0; 4], 1, 1, TextureFormat::Rgba8).unwrap();
+
+        let stencil = DepthStencilMode::Stencil;
+        assert!(Texture::set_depth_stencil_mode(&mut renderer, depth_stencil, stencil).is_ok());
+        assert_eq!(
+            renderer
+                .textures
+                .get(depth_stencil)
+                .unwrap()
+                .depth_stencil_mode,
+            stencil
+        );
+        assert!(Texture::set_depth_stencil_mode(&mut renderer, color, stencil).is_err());
+    }
+
+    #[test]
+    fn mipmapped() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let format = TextureFormat::Rgba8;
+        let texture = Texture::mipmapped(&mut renderer, &[0; 8 * 3 * 4], 8, 3, format).unwrap();
+        assert_eq!(renderer.textures.get(texture).unwrap().levels(), 4);
+
+        // the levels can be viewed on their own
+        let level = TextureViewDescription::new(format).with_levels(3, 1);
+        let view = Texture::view(&mut renderer, texture, &level).unwrap();
+        assert_eq!(renderer.textures.get(view).unwrap().size(), (1, 1));
+
+        assert!(Texture::mipmapped(&mut renderer, &[0; 4], 2, 2, format).is_err());
+        let depth = TextureFormat::Depth24Stencil8;
+        assert!(Texture::mipmapped(&mut renderer, &[0; 16], 2, 2, depth).is_err());
+        let ids = TextureFormat::R32UI;
+        assert!(Texture::mipmapped(&mut renderer, &[0; 16], 2, 2, ids).is_err());
+    }
+
+    #[test]
+    fn kinds() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let format = TextureFormat::Rgba8;
+        let gradient = Texture::new_1d(&mut renderer, &[0; 16 * 4], 16, format).unwrap();
+        let gradient = renderer.textures.get(gradient).unwrap();
+        assert_eq!(
+            (gradient.kind(), gradient.size()),
+            (TextureKind::Texture1D, (16, 1))
+        );
+        assert!(Texture::new_1d(&mut renderer, &[0; 15 * 4], 16, format).is_err());
+
+        let face = [0_u8; 2 * 2 * 4];
+        let cubemaps = [[&face[..]; 6]; 3];
+        let shadows = Texture::cubemap_array(&mut renderer, &cubemaps, 2, format).unwrap();
+        let kind = renderer.textures.get(shadows).unwrap().kind();
+        assert_eq!(kind, TextureKind::CubemapArray { cubemaps: 3 });
+        assert_eq!(kind.layers(), 18);
+        assert!(Texture::cubemap_array(&mut renderer, &[], 2, format).is_err());
+        let mut invalid = cubemaps;
+        invalid[2][5] = &face[..4];
+        assert!(Texture::cubemap_array(&mut renderer, &invalid, 2, format).is_err());
+
+        // single faces of the array are 2D textures
+        let face = TextureViewDescription::new(format).with_layer(17);
+        let view = Texture::view(&mut renderer, shadows, &face).unwrap();
+        let view = renderer.textures.get(view).unwrap();
+        assert_eq!(view.kind(), TextureKind::Texture2D);
+        let whole = TextureViewDescription::new(format);
+        let view = Texture::view(&mut renderer, shadows, &whole).unwrap();
+        assert_eq!(renderer.textures.get(view).unwrap().kind(), kind);
+        assert!(Texture::view(&mut renderer, shadows, &face.with_layer(18)).is_err());
+    }
+
+    #[test]
+    fn views() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let texture = Texture::new(&mut renderer, &[0; 16], 2, 2, TextureFormat::Rgba8).unwrap();
+        let face = [0_u8; 4];
+        let cubemap = Texture::cubemap(&mut renderer, [&face; 6], 1, TextureFormat::Rgba8).unwrap();
+        let view =
+            |renderer: &mut _, texture, description| Texture::view(renderer, texture, &description);
+
+        let srgb = TextureViewDescription::new(TextureFormat::Srgb8Alpha8);
+        let view_handle = view(&mut renderer, texture, srgb).unwrap();
+        let srgb_view = renderer.textures.get(view_handle).unwrap();
+        assert_eq!(srgb_view.format(), Some(TextureFormat::Srgb8Alpha8));
+        assert_eq!(srgb_view.size(), (2, 2));
+        // views of views
+        assert!(view(
+            &mut renderer,
+            view_handle,
+            TextureViewDescription::new(TextureFormat::Rgba8)
+        )
+        .is_ok());
+
+        let face_view = view(&mut renderer, cubemap, srgb.with_layer(5)).unwrap();
+        assert_eq!(
+            renderer.textures.get(face_view).unwrap().kind(),
+            TextureKind::Texture2D
+        );
+        assert!(view(&mut renderer, cubemap, srgb.with_layer(6)).is_err());
+        assert!(view(&mut renderer, texture, srgb.with_layer(1)).is_err());
+
+        assert!(view(&mut renderer, texture, srgb.with_levels(0, 2)).is_err());
+        assert!(view(&mut renderer, texture, srgb.with_levels(0, 0)).is_err());
+        assert!(view(&mut renderer, texture, srgb.with_levels(u32::MAX, 1)).is_err());
+        // same texel size, e.g. to read packed colors as ids
+        let ids = TextureViewDescription::new(TextureFormat::R32UI);
+        assert!(view(&mut renderer, texture, ids).is_ok());
+
+        let formats = [TextureFormat::Rgba16F, TextureFormat::Depth24Stencil8];
+        for format in formats {
+            let description = TextureViewDescription::new(format);
+            assert!(view(&mut renderer, texture, description).is_err());
+        }
+    }
+
+    #[test]
+    fn row_pitch() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let format = TextureFormat::Rgb8;
+
+        // 3x2 with the rows padded to 12 bytes, the last one doesn't need the padding
+        let data = [0_u8; 12 + 9];
+        assert!(Texture::new(&mut renderer, &data[..18], 3, 2, format).is_ok());
+        assert!(Texture::with_row_pitch(&mut renderer, &data, 3, 2, 12, format).is_ok());
+        assert!(Texture::with_row_pitch(&mut renderer, &[0; 24], 3, 2, 12, format).is_ok());
+        assert!(Texture::with_row_pitch(&mut renderer, &[0; 25], 3, 2, 12, format).is_err());
+        assert!(Texture::with_row_pitch(&mut renderer, &data, 3, 2, 6, format).is_err());
+        assert!(Texture::with_row_pitch(&mut renderer, &data, 3, 2, 10, format).is_err());
+    }
+
+    #[test]
+    fn update_region() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let atlas = Texture::new(&mut renderer, &[0; 16 * 16], 16, 16, TextureFormat::R8).unwrap();
+        let glyph = [255_u8; 4 * 4 + 3];
+        let region = |x, y, width, height| TextureRegion {
+            x,
+            y,
+            width,
+            height,
+        };
+
+        let update = |renderer: &mut _, region, data: &[u8], row_pitch| {
+            Texture::update(renderer, atlas, region, data, row_pitch)
+        };
+        assert!(update(&mut renderer, region(4, 4, 3, 5), &glyph[..15], None).is_ok());
+        assert!(update(&mut renderer, region(4, 4, 3, 5), &glyph, Some(4)).is_ok());
+        assert!(update(&mut renderer, region(13, 0, 3, 5), &glyph[..15], None).is_ok());
+        assert!(update(&mut renderer, region(14, 0, 3, 5), &glyph[..15], None).is_err());
+        assert!(update(&mut renderer, region(u32::MAX, 0, 3, 5), &glyph[..15], None).is_err());
+        assert!(update(&mut renderer, region(4, 4, 3, 5), &glyph, None).is_err());
+
+        let face = [0_u8; 4];
+        let cubemap = Texture::cubemap(&mut renderer, [&face; 6], 1, TextureFormat::Rgba8).unwrap();
+        let compressed =
+            Texture::compressed(&mut renderer, &[&[0; 8]], 4, 4, CompressedFormat::Bc1).unwrap();
+        let multisampled =
+            Texture::multisampled(&mut renderer, 1, 1, 4, TextureFormat::Rgba8).unwrap();
+        for texture in [cubemap, compressed, multisampled] {
+            let region = region(0, 0, 1, 1);
+            assert!(Texture::update(&mut renderer, texture, region, &face, None).is_err());
+        }
+    }
+
+    #[test]
+    fn compressed_levels() {
+        assert_eq!(CompressedFormat::Bc1.image_size(8, 8), 32);
+        assert_eq!(CompressedFormat::Bc7.image_size(5, 1), 32);
+        assert_eq!(CompressedFormat::Bc3.image_size(1, 1), 16);
+
+        let mut renderer = Renderer::new_headless().unwrap();
+        let (level_0, level_1, level_2) = ([0; 32], [0; 8], [0; 8]);
+        let format = CompressedFormat::Bc1;
+
+        let levels = [&level_0[..], &level_1, &level_2];
+        assert!(Texture::compressed(&mut renderer, &levels, 8, 8, format).is_ok());
+        assert!(Texture::compressed(&mut renderer, &levels[..1], 8, 8, format).is_ok());
+        assert!(Texture::compressed(&mut renderer, &[], 8, 8, format).is_err());
+        assert!(Texture::compressed(&mut renderer, &levels, 8, 8, CompressedFormat::Bc7).is_err());
+
+        // 4x4 only has the levels 4x4, 2x2 and 1x1
+        let levels = [&level_1[..], &level_1, &level_1, &level_1];
+        assert!(Texture::compressed(&mut renderer, &levels[..3], 4, 4, format).is_ok());
+        assert!(Texture::compressed(&mut renderer, &levels, 4, 4, format).is_err());
+    }
+}
@@ -0,0 +1,286 @@
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    time::{Duration, Instant},
+};
+
+use crate::{Handle, Renderer, RendererError};
+
+use super::{Buffer, BufferUsage, Context, CreateBuffer};
+
+/// CPU-side description of a graphics resource that can be uploaded by the renderer.
+///
+/// Descriptions are created on any thread and handed to the renderer through an
+/// [UploadSender]. The renderer uploads them during [update][super::Backend::update], within the
+/// [UploadBudget], and sends the handle back through the returned [PendingUpload].
+pub trait GpuAsset: Send + 'static {
+    /// Resource type of the returned handle, e.g. [Buffer].
+    type Resource: Send + 'static;
+
+    /// Number of bytes transferred to the graphics device, counted against the budget.
+    fn size(&self) -> usize;
+
+    /// Creates the resource on the graphics device.
+    fn upload<C: Context>(
+        self,
+        renderer: &mut Renderer<C>,
+    ) -> Result<Handle<Self::Resource>, RendererError>;
+}
+
+/// Limits how much the renderer uploads per frame, so streaming assets doesn't cause hitches.
+///
+/// The upload that crosses a limit is still finished, so every frame uploads at least one asset,
+/// no matter how big it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UploadBudget {
+    pub bytes: usize,
+    pub time: Duration,
+}
+
+impl Default for UploadBudget {
+    fn default() -> Self {
+        Self {
+            bytes: 4 * 1024 * 1024,
+            time: Duration::from_millis(2),
+        }
+    }
+}
+
+type UploadFn<C> = Box<dyn FnOnce(&mut Renderer<C>) + Send>;
+
+struct UploadJob<C: Context> {
+    size: usize,
+    upload: UploadFn<C>,
+}
+
+/// Sends [GpuAsset]s to the renderer. Can be cloned and moved to other threads.
+pub struct UploadSender<C: Context> {
+    sender: Sender<UploadJob<C>>,
+}
+
+impl<C: Context> Clone for UploadSender<C> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<C: Context> UploadSender<C> {
+    /// Queues the asset for upload. If the renderer was dropped in the meantime, the asset is
+    /// dropped as well and the [PendingUpload] never finishes.
+    pub fn send<A: GpuAsset>(&self, asset: A) -> PendingUpload<A::Resource> {
+        let (result_sender, receiver) = mpsc::channel();
+
+        let job = UploadJob {
+            size: asset.size(),
+            upload: Box::new(move |renderer: &mut Renderer<C>| {
+                // the receiving side might not be interested anymore
+                let _ = result_sender.send(asset.upload(renderer));
+            }),
+        };
+
+        if self.sender.send(job).is_err() {
+            log::warn!("Renderer was dropped, asset won't be uploaded");
+        }
+
+        PendingUpload { receiver }
+    }
+}
+
+/// Handle of a queued upload.
+pub struct PendingUpload<R> {
+    receiver: Receiver<Result<Handle<R>, RendererError>>,
+}
+
+impl<R> PendingUpload<R> {
+    /// Returns the result once the renderer uploaded the asset, and None before that.
+    /// The result is only returned once.
+    pub fn try_take(&self) -> Option<Result<Handle<R>, RendererError>> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+/// Receiving end of the [UploadSender]s, owned by the [Renderer].
+pub(crate) struct UploadQueue<C: Context> {
+    sender: Sender<UploadJob<C>>,
+    receiver: Receiver<UploadJob<C>>,
+    pub(crate) budget: UploadBudget,
+}
+
+impl<C: Context> Default for UploadQueue<C> {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            sender,
+            receiver,
+            budget: UploadBudget::default(),
+        }
+    }
+}
+
+impl<C: Context> Renderer<C> {
+    /// Returns a sender to queue [GpuAsset]s from any thread.
+    pub fn upload_sender(&self) -> UploadSender<C> {
+        UploadSender {
+            sender: self.uploads.sender.clone(),
+        }
+    }
+
+    pub fn set_upload_budget(&mut self, budget: UploadBudget) {
+        self.uploads.budget = budget;
+    }
+
+    /// Uploads queued assets until the [UploadBudget] is used up and returns how many were
+    /// uploaded. Called by the backends during [update][super::Backend::update].
+    pub fn process_uploads(&mut self) -> usize {
+        let start = Instant::now();
+        let budget = self.uploads.budget;
+        let mut bytes = 0;
+        let mut uploaded = 0;
+
+        while bytes < budget.bytes && start.elapsed() < budget.time {
+            let job = match self.uploads.receiver.try_recv() {
+                Ok(job) => job,
+                Err(_) => break,
+            };
+
+            bytes += job.size;
+            (job.upload)(self);
+            uploaded += 1;
+        }
+
+        uploaded
+    }
+}
+
+/// Vertex or index data to upload into a new [Buffer].
+#[derive(Debug, Clone)]
+pub struct BufferDescription {
+    data: Vec<u8>,
+    usage: BufferUsage,
+    is_index: bool,
+}
+
+impl BufferDescription {
+    pub fn vertex<T: Copy>(data: &[T], usage: BufferUsage) -> Self {
+        Self {
+            data: to_bytes(data),
+            usage,
+            is_index: false,
+        }
+    }
+
+    pub fn index<T: Copy>(data: &[T], usage: BufferUsage) -> Self {
+        Self {
+            data: to_bytes(data),
+            usage,
+            is_index: true,
+        }
+    }
+}
+
+fn to_bytes<T: Copy>(data: &[T]) -> Vec<u8> {
+    let size = std::mem::size_of_val(data);
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, size) }.to_vec()
+}
+
+impl BufferDescription {
+    /// Replaces the content of an existing buffer with the data.
+    #[cfg(feature = "hot-reload")]
+    pub(crate) fn write<C: Context>(
+        &self,
+        ctx: &mut C::Context,
+        buffer: &mut C::Buffer,
+    ) -> Result<(), RendererError> {
+        buffer.set_data(ctx, &self.data, self.usage)
+    }
+
+    /// Creates the buffer on the graphics device, without storing it in the renderer.
+    pub(crate) fn create<C: Context>(
+        &self,
+        ctx: &mut C::Context,
+    ) -> Result<C::Buffer, RendererError> {
+        if self.is_index {
+            C::Buffer::with_index(ctx, &self.data, self.usage)
+        } else {
+            C::Buffer::with_vertex(ctx, &self.data, self.usage)
+        }
+    }
+}
+
+impl GpuAsset for BufferDescription {
+    type Resource = Buffer;
+
+    fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    fn upload<C: Context>(
+        self,
+        renderer: &mut Renderer<C>,
+    ) -> Result<Handle<Buffer>, RendererError> {
+        let buffer = self.create::<C>(&mut renderer.context)?;
+        let handle = renderer.buffers.push(buffer);
+        renderer
+            .retention
+            .retain_buffer(handle, &self.data, self.usage, self.is_index);
+        Ok(handle)
+    }
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod test {
+    use super::*;
+    use crate::{Backend, RecordedCommand};
+
+    #[test]
+    fn upload_from_thread() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        let sender = renderer.upload_sender();
+
+        let pending = std::thread::spawn(move || {
+            sender.send(BufferDescription::vertex(
+                &[0.0f32, 1.0, 2.0],
+                BufferUsage::StaticRead,
+            ))
+        })
+        .join()
+        .unwrap();
+        assert!(pending.try_take().is_none());
+
+        renderer.update();
+        let handle = pending.try_take().unwrap().unwrap();
+        assert!(renderer.buffers.get(handle).is_some());
+        assert!(renderer
+            .recorded_commands()
+            .contains(&RecordedCommand::UploadVertices { size: 12 }));
+    }
+
+    #[test]
+    fn byte_budget() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        renderer.set_upload_budget(UploadBudget {
+            bytes: 10,
+            time: Duration::from_secs(1),
+        });
+
+        let sender = renderer.upload_sender();
+        let first = sender.send(BufferDescription::index(
+            &[0u32; 4],
+            BufferUsage::StaticRead,
+        ));
+        let second = sender.send(BufferDescription::index(
+            &[0u32; 4],
+            BufferUsage::StaticRead,
+        ));
+
+        // the first upload already exceeds the budget
+        assert_eq!(renderer.process_uploads(), 1);
+        assert!(first.try_take().is_some());
+        assert!(second.try_take().is_none());
+
+        assert_eq!(renderer.process_uploads(), 1);
+        assert!(second.try_take().is_some());
+        assert_eq!(renderer.process_uploads(), 0);
+    }
+}
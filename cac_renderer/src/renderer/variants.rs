@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use crate::{Handle, Renderer, RendererError};
+
+use super::{Context, ShaderProgram};
+
+/// Permutations of the same shader sources with different feature defines, like `SKINNED`, `FOG`
+/// or `INSTANCED`.
+///
+/// Each feature is a bit of the variant key, in the order they were passed to
+/// [new][Self::new]. A variant is compiled and linked the first time it is requested and cached
+/// afterwards. Enabled features are defined as `1`, disabled ones aren't defined at all, so the
+/// sources can use `#ifdef`.
+#[derive(Debug, Clone)]
+pub struct ShaderVariants {
+    vertex_source: String,
+    fragment_source: String,
+    features: Vec<String>,
+    programs: HashMap<u64, Handle<ShaderProgram>>,
+}
+
+impl ShaderVariants {
+    /// At most 64 features are supported, the rest is ignored.
+    pub fn new(vertex_source: &str, fragment_source: &str, features: &[&str]) -> Self {
+        if features.len() > 64 {
+            log::warn!("Only 64 shader features are supported, ignoring the rest");
+        }
+
+        Self {
+            vertex_source: vertex_source.to_string(),
+            fragment_source: fragment_source.to_string(),
+            features: features
+                .iter()
+                .take(64)
+                .map(|feature| feature.to_string())
+                .collect(),
+            programs: HashMap::new(),
+        }
+    }
+
+    /// Returns the key of the variant with the features enabled. Unknown features are skipped
+    /// with a warning.
+    pub fn key(&self, features: &[&str]) -> u64 {
+        features.iter().fold(0, |key, feature| {
+            match self.features.iter().position(|known| known == feature) {
+                Some(bit) => key | 1 << bit,
+                None => {
+                    log::warn!("Unknown shader feature {feature}");
+                    key
+                }
+            }
+        })
+    }
+
+    /// Returns the program of the variant, compiling it if it wasn't requested before.
+    /// Bits without a feature are ignored.
+    pub fn variant<C: Context>(
+        &mut self,
+        ctx: &mut Renderer<C>,
+        key: u64,
+    ) -> Result<Handle<ShaderProgram>, RendererError> {
+        let key = key & self.valid_bits();
+        if let Some(program) = self.programs.get(&key) {
+            return Ok(*program);
+        }
+
+        // the features are only defined for this variant, so the global defines are restored
+        let previous = ctx.preprocessor.clone();
+        for (bit, feature) in self.features.iter().enumerate() {
+            if key & 1 << bit != 0 {
+                ctx.preprocessor.set_define(feature, "1");
+            }
+        }
+        let program = ShaderProgram::from_sources(ctx, &self.vertex_source, &self.fragment_source);
+        ctx.preprocessor = previous;
+
+        let program = program?;
+        self.programs.insert(key, program);
+        Ok(program)
+    }
+
+    /// Like [variant][Self::variant], but with the names of the enabled features.
+    pub fn variant_with<C: Context>(
+        &mut self,
+        ctx: &mut Renderer<C>,
+        features: &[&str],
+    ) -> Result<Handle<ShaderProgram>, RendererError> {
+        let key = self.key(features);
+        self.variant(ctx, key)
+    }
+
+    /// Number of variants that were compiled so far.
+    pub fn compiled(&self) -> usize {
+        self.programs.len()
+    }
+
+    /// Removes all compiled programs from the renderer. Handles to them become invalid.
+    pub fn clear<C: Context>(&mut self, ctx: &mut Renderer<C>) {
+        for (_, program) in self.programs.drain() {
+            ctx.programs.remove(program);
+        }
+    }
+
+    fn valid_bits(&self) -> u64 {
+        match self.features.len() {
+            64 => u64::MAX,
+            count => (1 << count) - 1,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod test {
+    use super::*;
+
+    const VERTEX: &str =
+        "#version 330\n#ifdef SKINNED\nuniform mat4 bones[4];\n#endif\nvoid main() {}";
+    const FRAGMENT: &str = "#version 330\nuniform vec4 color;\nvoid main() {}";
+
+    #[test]
+    fn lazy_variants() {
+        let mut renderer = Renderer::new_headless().unwrap();
+        renderer.shader_preprocessor().set_define("QUALITY", "2");
+
+        let mut variants = ShaderVariants::new(VERTEX, FRAGMENT, &["SKINNED", "FOG"]);
+        assert_eq!(variants.key(&["FOG", "SKINNED"]), 0b11);
+        assert_eq!(variants.key(&["FOG", "UNKNOWN"]), 0b10);
+
+        let skinned = variants.variant_with(&mut renderer, &["SKINNED"]).unwrap();
+        let plain = variants.variant(&mut renderer, 0).unwrap();
+        assert_ne!(skinned, plain);
+        assert_eq!(variants.compiled(), 2);
+
+        // cached, and unknown bits are ignored
+        assert_eq!(variants.variant(&mut renderer, 0b101).unwrap(), skinned);
+        assert_eq!(variants.compiled(), 2);
+
+        // the feature defines don't leak into other shaders
+        let preprocessed = renderer
+            .shader_preprocessor()
+            .process("void main() {}")
+            .unwrap();
+        assert_eq!(preprocessed, "#define QUALITY 2\nvoid main() {}\n");
+
+        variants.clear(&mut renderer);
+        assert!(renderer.programs.get(plain).is_none());
+    }
+}
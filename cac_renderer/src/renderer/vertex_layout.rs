@@ -1,24 +1,155 @@
-use std::{cell::RefCell, fmt::Display};
+use std::fmt::Display;
 
 use crate::{BufferAttributes, Handle, Renderer, RendererError};
 
-use super::Context;
+use super::{Context, Material, ShaderProgram, Uniform, UniformKind};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct VertexLayout {}
 
 impl VertexLayout {
+    /// Creates the layout with the locations of the renderer's
+    /// [layout config][Renderer::layout_config].
     pub fn new<C: Context>(
         ctx: &mut Renderer<C>,
         buffer_attributes: &[BufferAttributes],
     ) -> Result<Handle<Self>, RendererError> {
+        let config = ctx.layout_config.clone();
+        Self::with_config(ctx, buffer_attributes, &config)
+    }
+
+    /// Creates the layout with the locations of the semantics in `locations`, e.g. to match the
+    /// inputs of a shader that doesn't follow the defaults. Semantics that aren't in it fall back
+    /// to the renderer's [layout config][Renderer::layout_config].
+    pub fn with_locations<C: Context>(
+        ctx: &mut Renderer<C>,
+        buffer_attributes: &[BufferAttributes],
+        locations: &[(AttributeSemantic, u8)],
+    ) -> Result<Handle<Self>, RendererError> {
+        let mut config = ctx.layout_config.clone();
+        // the first override of a semantic wins, so the explicit locations go before the renderer's
+        config.overrides.splice(0..0, locations.iter().copied());
+        Self::with_config(ctx, buffer_attributes, &config)
+    }
+
+    /// Creates the layout with the locations of the config instead of the renderer's.
+    pub fn with_config<C: Context>(
+        ctx: &mut Renderer<C>,
+        buffer_attributes: &[BufferAttributes],
+        config: &VertexLayoutConfig,
+    ) -> Result<Handle<Self>, RendererError> {
+        let vao = Self::create(ctx, buffer_attributes, config)?;
+        let handle = ctx.layouts.push(vao);
+        ctx.retention
+            .retain_layout(handle, buffer_attributes, config);
+        Ok(handle)
+    }
+
+    /// Creates the layout on the backend, without a handle.
+    pub(crate) fn create<C: Context>(
+        ctx: &mut Renderer<C>,
+        buffer_attributes: &[BufferAttributes],
+        config: &VertexLayoutConfig,
+    ) -> Result<C::VertexLayout, RendererError> {
         let mut vao = C::VertexLayout::new(ctx)?;
         for buffer_attr in buffer_attributes {
             if let Some(buffer) = ctx.buffers.get(buffer_attr.buffer) {
-                vao.set_buffer_attributes(buffer, &buffer_attr.attributes, buffer_attr.offset)?;
+                vao.set_buffer_attributes(
+                    buffer,
+                    &buffer_attr.attributes,
+                    buffer_attr.offset,
+                    config,
+                )?;
             }
         }
-        Ok(ctx.layouts.push(vao))
+        Ok(vao)
+    }
+
+    /// Checks that the layout has an attribute for every vertex input of the program of the
+    /// material, see [validate_program][Self::validate_program].
+    ///
+    /// Draws check it the first time the layout is drawn with the program, and skip the draw with
+    /// a warning if it fails.
+    pub fn validate<C: Context>(
+        ctx: &Renderer<C>,
+        layout: Handle<Self>,
+        material: Handle<Material>,
+    ) -> Result<(), RendererError> {
+        let program = ctx
+            .materials
+            .get(material)
+            .ok_or(RendererError::ResourceNotFound {
+                resource: format!("Material: {material:?}"),
+            })?
+            .program;
+        Self::validate_program(ctx, layout, program)
+    }
+
+    /// Checks that the layout has an attribute with enough components at the location of every
+    /// vertex input of the program, e.g. when the layout and the program are paired at load time.
+    /// Inputs without an attribute read undefined values, missing components read 0.
+    ///
+    /// Only a missing fourth component is fine, it reads 1, e.g. for `vec4` positions from `Vec3`
+    /// attributes.
+    pub fn validate_program<C: Context>(
+        ctx: &Renderer<C>,
+        layout: Handle<Self>,
+        program: Handle<ShaderProgram>,
+    ) -> Result<(), RendererError> {
+        let vao = ctx
+            .layouts
+            .get(layout)
+            .ok_or(RendererError::ResourceNotFound {
+                resource: format!("VertexLayout: {layout:?}"),
+            })?;
+        let program = ctx
+            .programs
+            .get(program)
+            .ok_or(RendererError::ResourceNotFound {
+                resource: format!("Shaderprogram: {program:?}"),
+            })?;
+
+        let attributes = vao.attributes();
+        let missing: Vec<_> = program
+            .attributes()
+            .iter()
+            .filter_map(|input| {
+                let needed = input_components(input.kind);
+                input.locations().find_map(|location| {
+                    match attributes
+                        .iter()
+                        .find(|(attribute, _)| u32::from(*attribute) == location)
+                    {
+                        None => Some(format!("{} at location {location}", input.name)),
+                        Some((_, kind)) => {
+                            let components = kind.components();
+                            let fits = components >= needed || (needed == 4 && components == 3);
+                            (!fits).then(|| {
+                                format!(
+                                    "{} at location {location} with {needed} components, not {components}",
+                                    input.name
+                                )
+                            })
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(RendererError::LayoutMismatch { missing })
+        }
+    }
+
+    /// Names the vertex layout in graphics debuggers, see [DebugLabel][super::DebugLabel].
+    pub fn set_label<C: Context>(
+        ctx: &mut Renderer<C>,
+        layout: Handle<Self>,
+        label: &str,
+    ) -> Result<(), RendererError> {
+        super::set_debug_label(&mut ctx.layouts, layout, label, "VertexLayout")
     }
 }
 
@@ -27,12 +158,27 @@ pub trait CreateVertexLayout: Sized {
 
     fn new<C: Context>(ctx: &mut Renderer<C>) -> Result<Self, RendererError>;
 
+    /// Locations of the semantics are looked up with [VertexLayoutConfig::location].
     fn set_buffer_attributes(
         &mut self,
         buffer: &Self::Buffer,
         attributes: &[VertexAttribute],
         offset: usize,
+        config: &VertexLayoutConfig,
     ) -> Result<(), RendererError>;
+
+    /// Locations and kinds of the attributes that were set so far.
+    fn attributes(&self) -> &[(u8, VertexAttributeKind)];
+}
+
+/// Components a vertex input reads per location, matrices read one column per location.
+fn input_components(kind: UniformKind) -> u8 {
+    match kind {
+        UniformKind::Vec2 | UniformKind::Mat2 => 2,
+        UniformKind::Vec3 | UniformKind::Mat3 => 3,
+        UniformKind::Vec4 | UniformKind::Mat4 => 4,
+        _ => 1,
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -64,8 +210,7 @@ impl Display for AttributeSemantic {
     }
 }
 
-std::thread_local! {
-static DEFAULT_LOCATIONS: RefCell<[Option<AttributeSemantic>; 16]> = RefCell::new([
+const DEFAULT_LOCATIONS: [Option<AttributeSemantic>; 16] = [
     Some(AttributeSemantic::Position),
     Some(AttributeSemantic::UV(0)),
     Some(AttributeSemantic::UV(1)),
@@ -82,7 +227,44 @@ static DEFAULT_LOCATIONS: RefCell<[Option<AttributeSemantic>; 16]> = RefCell::ne
     Some(AttributeSemantic::Normals(2)),
     Some(AttributeSemantic::Weights(3)),
     Some(AttributeSemantic::Joints(0)),
-]);
+];
+
+/// Locations of the [AttributeSemantic]s in the [VertexLayout]s of a renderer, see
+/// [set_layout_config][Renderer::set_layout_config].
+///
+/// The default puts Position at 0, UV(0..4) at 1-4, Color(0..5) at 5-9, Tangent at 10,
+/// Normals(0..3) at 11-13, Weights(3) at 14 and Joints(0) at 15.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VertexLayoutConfig {
+    /// Semantic at each location, the index is the location.
+    pub locations: [Option<AttributeSemantic>; 16],
+    /// Locations that take precedence over [locations][Self::locations], e.g. for semantics
+    /// outside of the first 16 locations.
+    pub overrides: Vec<(AttributeSemantic, u8)>,
+}
+
+impl Default for VertexLayoutConfig {
+    fn default() -> Self {
+        Self {
+            locations: DEFAULT_LOCATIONS,
+            overrides: Vec::new(),
+        }
+    }
+}
+
+impl VertexLayoutConfig {
+    /// Location of the semantic, or None if the config has none for it.
+    pub fn location(&self, semantic: AttributeSemantic) -> Option<u8> {
+        self.overrides
+            .iter()
+            .find_map(|(overridden, location)| (*overridden == semantic).then_some(*location))
+            .or_else(|| {
+                self.locations
+                    .iter()
+                    .position(|located| *located == Some(semantic))
+                    .map(|location| location as u8)
+            })
+    }
 }
 
 impl AttributeSemantic {
@@ -109,20 +291,13 @@ impl AttributeSemantic {
         false
     }
 
+    /// Location of the semantic in the default [VertexLayoutConfig]. Renderers can use other
+    /// locations, see [Renderer::layout_config].
     pub fn location(&self) -> Option<u8> {
-        DEFAULT_LOCATIONS.with(|f| {
-            f.borrow()
-                .iter()
-                .enumerate()
-                .find_map(|(index, attr)| match *attr {
-                    Some(attr) if attr == *self => Some(index as u8),
-                    _ => None,
-                })
-        })
-    }
-
-    pub fn set_default_locations(locations: [Option<AttributeSemantic>; 16]) {
-        DEFAULT_LOCATIONS.with(|f| *f.borrow_mut() = locations)
+        DEFAULT_LOCATIONS
+            .iter()
+            .position(|semantic| *semantic == Some(*self))
+            .map(|location| location as u8)
     }
 }
 
@@ -132,6 +307,23 @@ pub enum VertexAttributeKind {
     Vec2,
     Vec3,
     Vec4,
+    /// Two normalized i16, e.g. for oct-encoded normals.
+    I16x2,
+    /// Four normalized i16, e.g. for quantized positions. The fourth is padding.
+    I16x4,
+    /// Two normalized u16, e.g. for quantized UVs.
+    U16x2,
+    /// Four normalized u16.
+    U16x4,
+    /// Four normalized u8, e.g. for colors, or four integers for bone indices.
+    U8x4,
+    /// Two half floats.
+    F16x2,
+    /// Four half floats, e.g. for tangents.
+    F16x4,
+    /// Three signed 10 bit components and a 2 bit one packed into 4 bytes, the first in the
+    /// lowest bits, e.g. for normals and tangents with the handedness in the last component.
+    Int2_10_10_10,
 }
 
 impl Display for VertexAttributeKind {
@@ -141,6 +333,14 @@ impl Display for VertexAttributeKind {
             VertexAttributeKind::Vec2 => write!(f, "Vec2"),
             VertexAttributeKind::Vec3 => write!(f, "Vec3"),
             VertexAttributeKind::Vec4 => write!(f, "Vec4"),
+            VertexAttributeKind::I16x2 => write!(f, "I16x2"),
+            VertexAttributeKind::I16x4 => write!(f, "I16x4"),
+            VertexAttributeKind::U16x2 => write!(f, "U16x2"),
+            VertexAttributeKind::U16x4 => write!(f, "U16x4"),
+            VertexAttributeKind::U8x4 => write!(f, "U8x4"),
+            VertexAttributeKind::F16x2 => write!(f, "F16x2"),
+            VertexAttributeKind::F16x4 => write!(f, "F16x4"),
+            VertexAttributeKind::Int2_10_10_10 => write!(f, "Int2_10_10_10"),
         }
     }
 }
@@ -152,10 +352,58 @@ impl VertexAttributeKind {
             VertexAttributeKind::Vec2 => 2,
             VertexAttributeKind::Vec3 => 3,
             VertexAttributeKind::Vec4 => 4,
+            VertexAttributeKind::I16x2 => 2,
+            VertexAttributeKind::I16x4 => 4,
+            VertexAttributeKind::U16x2 => 2,
+            VertexAttributeKind::U16x4 => 4,
+            VertexAttributeKind::U8x4 => 4,
+            VertexAttributeKind::F16x2 => 2,
+            VertexAttributeKind::F16x4 => 4,
+            VertexAttributeKind::Int2_10_10_10 => 4,
         }
     }
+
+    /// Size of a single component in bytes. The components of
+    /// [Int2_10_10_10][Self::Int2_10_10_10] share the 4 bytes of the packed value.
+    pub fn component_size(&self) -> usize {
+        match self {
+            VertexAttributeKind::F32
+            | VertexAttributeKind::Vec2
+            | VertexAttributeKind::Vec3
+            | VertexAttributeKind::Vec4 => std::mem::size_of::<f32>(),
+            VertexAttributeKind::I16x2 | VertexAttributeKind::I16x4 => std::mem::size_of::<i16>(),
+            VertexAttributeKind::U16x2 | VertexAttributeKind::U16x4 => std::mem::size_of::<u16>(),
+            VertexAttributeKind::U8x4 => std::mem::size_of::<u8>(),
+            VertexAttributeKind::F16x2 | VertexAttributeKind::F16x4 => 2,
+            VertexAttributeKind::Int2_10_10_10 => std::mem::size_of::<u32>(),
+        }
+    }
+
     pub fn size(&self) -> usize {
-        usize::from(self.components()) * std::mem::size_of::<f32>()
+        match self {
+            VertexAttributeKind::Int2_10_10_10 => self.component_size(),
+            _ => usize::from(self.components()) * self.component_size(),
+        }
+    }
+
+    /// Whether the integer components are mapped into the [-1.0, 1.0] (signed) or [0.0, 1.0]
+    /// (unsigned) range when the shader reads them. This is the default of the
+    /// [VertexAttribute]s of the kind.
+    pub fn normalized(&self) -> bool {
+        self.is_integer() || *self == VertexAttributeKind::Int2_10_10_10
+    }
+
+    /// Returns true for the kinds of unpacked integer components. Unless the attribute is
+    /// normalized, shaders read them as integers, e.g. `uvec4 joints`.
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            VertexAttributeKind::I16x2
+                | VertexAttributeKind::I16x4
+                | VertexAttributeKind::U16x2
+                | VertexAttributeKind::U16x4
+                | VertexAttributeKind::U8x4
+        )
     }
 }
 
@@ -163,6 +411,7 @@ impl VertexAttributeKind {
 pub struct VertexAttribute {
     pub stride: usize,
     pub semantic: AttributeSemantic,
+    pub kind: VertexAttributeKind,
     pub normalized: bool,
     pub offset: usize,
 }
@@ -171,18 +420,61 @@ pub struct VertexAttribute {
 mod test {
     use super::*;
 
+    #[test]
+    fn kind_size() {
+        assert_eq!(VertexAttributeKind::Vec3.size(), 12);
+        assert_eq!(VertexAttributeKind::I16x4.size(), 8);
+        assert_eq!(VertexAttributeKind::I16x2.size(), 4);
+        assert_eq!(VertexAttributeKind::U16x2.size(), 4);
+        assert_eq!(VertexAttributeKind::U16x4.size(), 8);
+        assert_eq!(VertexAttributeKind::U8x4.size(), 4);
+        assert_eq!(VertexAttributeKind::F16x4.size(), 8);
+        assert_eq!(VertexAttributeKind::Int2_10_10_10.size(), 4);
+        assert_eq!(VertexAttributeKind::Int2_10_10_10.components(), 4);
+    }
+
+    #[test]
+    fn integer_attributes() {
+        let attributes = BufferAttributes::with_kinds(
+            Handle::new(),
+            0,
+            &[
+                (
+                    AttributeSemantic::Normals(0),
+                    VertexAttributeKind::Int2_10_10_10,
+                ),
+                (AttributeSemantic::Color(0), VertexAttributeKind::U8x4),
+                (AttributeSemantic::Joints(0), VertexAttributeKind::U8x4),
+                (AttributeSemantic::UV(0), VertexAttributeKind::F16x2),
+            ],
+        );
+
+        let normalized: Vec<_> = attributes
+            .attributes
+            .iter()
+            .map(|attribute| attribute.normalized)
+            .collect();
+        // joints are indices
+        assert_eq!(normalized, [true, true, false, false]);
+        assert_eq!(attributes.attributes[3].offset, 12);
+        assert_eq!(attributes.attributes[0].stride, 16);
+    }
+
     #[test]
     fn default_location() {
         assert_eq!(AttributeSemantic::Position.location(), Some(0));
         assert_eq!(AttributeSemantic::UV(0).location(), Some(1));
         assert_eq!(AttributeSemantic::UV(10).location(), None);
 
-        let mut new_locations = DEFAULT_LOCATIONS.with(|f| *f.borrow());
-        new_locations[1] = Some(AttributeSemantic::UV(10));
-        AttributeSemantic::set_default_locations(new_locations);
+        let mut config = VertexLayoutConfig::default();
+        config.locations[1] = Some(AttributeSemantic::UV(10));
+        config.overrides.push((AttributeSemantic::Joints(1), 20));
 
-        assert_eq!(AttributeSemantic::Position.location(), Some(0));
-        assert_eq!(AttributeSemantic::UV(0).location(), None);
-        assert_eq!(AttributeSemantic::UV(10).location(), Some(1));
+        assert_eq!(config.location(AttributeSemantic::Position), Some(0));
+        assert_eq!(config.location(AttributeSemantic::UV(0)), None);
+        assert_eq!(config.location(AttributeSemantic::UV(10)), Some(1));
+        assert_eq!(config.location(AttributeSemantic::Joints(1)), Some(20));
+        // the defaults of other renderers stay the same
+        assert_eq!(AttributeSemantic::UV(0).location(), Some(1));
     }
 }
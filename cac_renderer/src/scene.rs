@@ -0,0 +1,609 @@
+//! Retained scene on top of the immediate-mode draws, for small tools and samples.
+//!
+//! A [Scene] keeps [Node]s with a transform, mesh and material. Every frame,
+//! [draw][Scene::draw] culls the nodes against the view frustum of the [Camera] and draws the
+//! visible ones front to back, which leaves the usual per-frame work of the immediate-mode API
+//! to the scene:
+//!
+//! ```no_run
+//! # use cac_renderer::{math::*, scene::*, Backend, Handle, Mesh, Renderer};
+//! # fn frame<C: cac_renderer::Context>(renderer: &mut Renderer<C>, mesh: Mesh, material: Handle<cac_renderer::Material>)
+//! # where Renderer<C>: Backend {
+//! let mut scene = Scene::default();
+//! let node = scene.add(Node::new(mesh, material).with_bounds(BoundingSphere::new(Vec3::ZERO, 1.0)));
+//! scene.node_mut(node).unwrap().transform = Mat4::from_translation(vec3(0.0, 0.0, -5.0));
+//!
+//! let camera = Camera {
+//!     view: Mat4::IDENTITY,
+//!     projection: Mat4::perspective_rh_gl(1.0, 16.0 / 9.0, 0.1, 100.0),
+//! };
+//! scene.draw(renderer, &camera);
+//! renderer.update();
+//! # }
+//! ```
+//!
+//! The programs of the materials receive the transforms as per-draw overrides of
+//! `uniform mat4 model;` and `uniform mat4 view_projection;`, the same uniforms the
+//! [DebugView][crate::DebugView]s use.
+//!
+//! ## Lightmaps
+//!
+//! Baked lighting is stored in one atlas for all nodes, which is set on the materials as
+//! [LIGHTMAP_TEXTURE]. The meshes have a second set of UVs for it, [LIGHTMAP_UV], that cover
+//! [0, 1] per mesh, and each node maps them into its rect of the atlas with a
+//! [lightmap][Node::lightmap] scale and offset, passed as override of [LIGHTMAP_SCALE_OFFSET].
+//! [pack_lightmaps] places the rects of the nodes in the atlas:
+//!
+//! ```glsl
+//! layout(location = 2) in vec2 lightmap_uv;
+//! uniform vec4 lightmap_scale_offset;
+//! out vec2 atlas_uv;
+//! void main() {
+//!     atlas_uv = lightmap_uv * lightmap_scale_offset.xy + lightmap_scale_offset.zw;
+//!     // ...
+//! }
+//! ```
+//!
+//! ## Motion vectors
+//!
+//! The scene keeps the transforms of the nodes and the camera of the previous frame, which
+//! [end_frame][Scene::end_frame] stores. [draw_motion_vectors][Scene::draw_motion_vectors] draws
+//! the nodes with a material of the [motion_vector_program], which writes how far every pixel
+//! moved since the previous frame into the red and green channels, in texture coordinates. Motion
+//! blur and temporal anti-aliasing read them to find where the pixel was in the previous frame,
+//! at `uv - velocity`. The camera of the motion vectors has no jitter.
+//!
+//! Custom motion vector programs receive the previous transforms as overrides of
+//! [PREVIOUS_MODEL] and [PREVIOUS_VIEW_PROJECTION].
+pub use crate::camera::Camera;
+use crate::{
+    generation_vec::GenerationVec,
+    math::{vec4, Mat4, Vec3, Vec4},
+    transform::{Transform, TransformGraph},
+    AttributeSemantic, Backend, Context, Handle, Material, MaterialProperty, Mesh, Renderer,
+    RendererError, ShaderProgram,
+};
+
+/// The vertex attribute of the lightmap UVs, the second UV channel.
+pub const LIGHTMAP_UV: AttributeSemantic = AttributeSemantic::UV(1);
+/// Name of the `sampler2D` uniform of the lightmap atlas, a property of the materials.
+pub const LIGHTMAP_TEXTURE: &str = "lightmap";
+/// Name of the `vec4` uniform with the [lightmap][Node::lightmap] scale and offset of a node.
+pub const LIGHTMAP_SCALE_OFFSET: &str = "lightmap_scale_offset";
+/// Name of the `mat4` uniform with the transform of a node in the previous frame.
+pub const PREVIOUS_MODEL: &str = "previous_model";
+/// Name of the `mat4` uniform with the view projection of the camera in the previous frame.
+pub const PREVIOUS_VIEW_PROJECTION: &str = "previous_view_projection";
+
+const MOTION_VECTORS_FS_SOURCE: &str = r##"
+    #version 330 core
+    in vec4 current;
+    in vec4 previous;
+    out vec4 result;
+    void main() {
+        vec2 velocity = (current.xy / current.w - previous.xy / previous.w) * 0.5;
+        result = vec4(velocity, 0.0, 1.0);
+    }"##;
+
+/// Sphere around the vertices of a mesh, in its local space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn new(center: Vec3, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    /// Sphere around the average of the positions that contains all of them.
+    pub fn from_positions(positions: &[Vec3]) -> Self {
+        let center = match positions.len() {
+            0 => Vec3::ZERO,
+            len => positions.iter().sum::<Vec3>() / len as f32,
+        };
+        let radius = positions
+            .iter()
+            .map(|position| position.distance(center))
+            .fold(0.0, f32::max);
+        Self { center, radius }
+    }
+
+    /// The sphere around the transformed sphere. Non-uniform scales grow it by the largest axis.
+    fn transformed(&self, transform: &Mat4) -> Self {
+        let scale = transform
+            .x_axis
+            .truncate()
+            .length()
+            .max(transform.y_axis.truncate().length())
+            .max(transform.z_axis.truncate().length());
+        Self {
+            center: transform.transform_point3(self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+/// A mesh drawn with a material at a transform, see [Scene::add].
+#[derive(Debug, Clone, Copy)]
+pub struct Node {
+    /// Transforms the mesh into world space.
+    pub transform: Mat4,
+    pub mesh: Mesh,
+    pub material: Handle<Material>,
+    /// Nodes without bounds are never culled.
+    pub bounds: Option<BoundingSphere>,
+    /// Hidden nodes stay in the scene, but aren't drawn.
+    pub visible: bool,
+    /// Transform of a [TransformGraph] whose world matrix replaces the transform in
+    /// [apply_transforms][Scene::apply_transforms].
+    pub graph_transform: Option<Handle<Transform>>,
+    /// Scale in xy and offset in zw of the lightmap UVs into the rect of the node in the atlas,
+    /// see [LightmapRect::scale_offset]. Nodes without one aren't lightmapped.
+    pub lightmap: Option<Vec4>,
+    /// Transform of the previous frame, see [end_frame][Scene::end_frame]. Nodes without one,
+    /// like new ones, didn't move.
+    pub previous_transform: Option<Mat4>,
+}
+
+impl Node {
+    /// Visible node at the origin, without bounds.
+    pub fn new(mesh: Mesh, material: Handle<Material>) -> Self {
+        Self {
+            transform: Mat4::IDENTITY,
+            mesh,
+            material,
+            bounds: None,
+            visible: true,
+            graph_transform: None,
+            lightmap: None,
+            previous_transform: None,
+        }
+    }
+
+    pub fn with_transform(mut self, transform: Mat4) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn with_bounds(mut self, bounds: BoundingSphere) -> Self {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    pub fn with_graph_transform(mut self, transform: Handle<Transform>) -> Self {
+        self.graph_transform = Some(transform);
+        self
+    }
+
+    pub fn with_lightmap(mut self, scale_offset: Vec4) -> Self {
+        self.lightmap = Some(scale_offset);
+        self
+    }
+}
+
+/// Rect of a node in a lightmap atlas, in texels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LightmapRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl LightmapRect {
+    /// Scale and offset that map [0, 1] into the rect of an atlas with the size, for
+    /// [Node::lightmap].
+    pub fn scale_offset(&self, atlas_width: u32, atlas_height: u32) -> Vec4 {
+        let (atlas_width, atlas_height) = (atlas_width as f32, atlas_height as f32);
+        vec4(
+            self.width as f32 / atlas_width,
+            self.height as f32 / atlas_height,
+            self.x as f32 / atlas_width,
+            self.y as f32 / atlas_height,
+        )
+    }
+}
+
+/// Places lightmaps of the sizes in an atlas, in rows from the bottom, with the padding between
+/// them so bilinear filtering doesn't bleed into the neighbours. The rects are in the order of
+/// the sizes, None if they don't fit.
+pub fn pack_lightmaps(
+    sizes: &[(u32, u32)],
+    atlas_width: u32,
+    atlas_height: u32,
+    padding: u32,
+) -> Option<Vec<LightmapRect>> {
+    // the tallest first, so the rows waste less space
+    let mut order: Vec<_> = (0..sizes.len()).collect();
+    order.sort_by_key(|index| std::cmp::Reverse(sizes[*index].1));
+
+    let mut rects = vec![
+        LightmapRect {
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 0,
+        };
+        sizes.len()
+    ];
+    let (mut x, mut y, mut row_height) = (padding, padding, 0);
+    for index in order {
+        let (width, height) = sizes[index];
+        if x + width + padding > atlas_width {
+            x = padding;
+            y += row_height + padding;
+            row_height = 0;
+        }
+        if x + width + padding > atlas_width || y + height + padding > atlas_height {
+            return None;
+        }
+        rects[index] = LightmapRect {
+            x,
+            y,
+            width,
+            height,
+        };
+        x += width + padding;
+        row_height = row_height.max(height);
+    }
+    Some(rects)
+}
+
+/// Planes of the view frustum, pointing inwards, with the distance in w.
+fn frustum_planes(view_projection: &Mat4) -> [Vec4; 6] {
+    let [x, y, z, w] = [0, 1, 2, 3].map(|row| view_projection.row(row));
+    [w + x, w - x, w + y, w - y, w + z, w - z]
+        .map(|plane| plane / plane.truncate().length().max(f32::EPSILON))
+}
+
+/// Retained nodes that are culled, sorted and drawn every frame, see the
+/// [module documentation][self].
+#[derive(Default)]
+pub struct Scene {
+    nodes: GenerationVec<Node, Node>,
+    /// The nodes that passed the last cull, with their distance to the camera.
+    visible: Vec<(Handle<Node>, f32)>,
+    /// View projection of the camera of the previous frame.
+    previous_view_projection: Option<Mat4>,
+}
+
+impl Scene {
+    pub fn add(&mut self, node: Node) -> Handle<Node> {
+        self.nodes.push(node)
+    }
+
+    pub fn remove(&mut self, node: Handle<Node>) {
+        self.nodes.remove(node);
+    }
+
+    pub fn node(&self, node: Handle<Node>) -> Option<&Node> {
+        self.nodes.get(node)
+    }
+
+    pub fn node_mut(&mut self, node: Handle<Node>) -> Option<&mut Node> {
+        self.nodes.get_mut(node)
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = (Handle<Node>, &Node)> {
+        self.nodes.handles().zip(self.nodes.iter())
+    }
+
+    /// Removes all nodes.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.visible.clear();
+        self.previous_view_projection = None;
+    }
+
+    /// [Updates][TransformGraph::update] the graph and copies the world matrices into the
+    /// transforms of the nodes that have a [graph transform][Node::graph_transform]. Nodes whose
+    /// graph transform was removed keep their last transform.
+    pub fn apply_transforms(&mut self, graph: &mut TransformGraph) {
+        graph.update();
+        for node in self.nodes.iter_mut() {
+            if let Some(world) = node
+                .graph_transform
+                .and_then(|transform| graph.world(transform))
+            {
+                node.transform = world;
+            }
+        }
+    }
+
+    /// The visible nodes whose bounds intersect the view frustum of the camera, sorted front to
+    /// back by the distance of their origin to the camera.
+    pub fn cull(&mut self, camera: &Camera) -> &[(Handle<Node>, f32)] {
+        let planes = frustum_planes(&camera.view_projection());
+        let position = camera.position();
+
+        self.visible.clear();
+        for (handle, node) in self.nodes.handles().zip(self.nodes.iter()) {
+            if !node.visible {
+                continue;
+            }
+
+            let (center, inside) = match node.bounds {
+                Some(bounds) => {
+                    let bounds = bounds.transformed(&node.transform);
+                    let inside = planes.iter().all(|plane| {
+                        plane.truncate().dot(bounds.center) + plane.w >= -bounds.radius
+                    });
+                    (bounds.center, inside)
+                }
+                None => (node.transform.w_axis.truncate(), true),
+            };
+            if inside {
+                self.visible.push((handle, center.distance(position)));
+            }
+        }
+
+        self.visible
+            .sort_unstable_by(|(_, a), (_, b)| a.total_cmp(b));
+        &self.visible
+    }
+
+    /// [Culls][Self::cull] the nodes and draws the visible ones, at their distance to the camera
+    /// as depth, with their transform and the one of the camera as overrides of the `model` and
+    /// `view_projection` uniforms. Lightmapped nodes also override [LIGHTMAP_SCALE_OFFSET].
+    pub fn draw(&mut self, renderer: &mut impl Backend, camera: &Camera) {
+        let view_projection = camera.view_projection();
+        self.cull(camera);
+
+        for (handle, depth) in &self.visible {
+            let Some(node) = self.nodes.get(*handle) else {
+                continue;
+            };
+            let properties = [
+                MaterialProperty::new("model", &node.transform),
+                MaterialProperty::new("view_projection", &view_projection),
+                MaterialProperty::new(
+                    LIGHTMAP_SCALE_OFFSET,
+                    node.lightmap.as_ref().unwrap_or(&Vec4::ZERO),
+                ),
+            ];
+            // the lightmap override only for lightmapped nodes
+            let count = 2 + node.lightmap.is_some() as usize;
+            renderer.draw_at_depth(node.mesh, node.material, *depth, &properties[..count]);
+        }
+    }
+
+    /// Stores the transforms of the nodes and the view projection of the camera as the ones of
+    /// the previous frame, for the motion vectors of the next one. Called once per frame, after
+    /// its draws.
+    pub fn end_frame(&mut self, camera: &Camera) {
+        for node in self.nodes.iter_mut() {
+            node.previous_transform = Some(node.transform);
+        }
+        self.previous_view_projection = Some(camera.view_projection());
+    }
+
+    /// View projection of the camera of the previous frame, if there was one since the scene was
+    /// created or cleared.
+    pub fn previous_view_projection(&self) -> Option<Mat4> {
+        self.previous_view_projection
+    }
+
+    /// [Culls][Self::cull] the nodes and draws the visible ones with the motion vector material,
+    /// like [draw][Self::draw] does with their own materials. The material also receives the
+    /// transforms of the previous frame as overrides of [PREVIOUS_MODEL] and
+    /// [PREVIOUS_VIEW_PROJECTION], the current ones in the first frame.
+    pub fn draw_motion_vectors(
+        &mut self,
+        renderer: &mut impl Backend,
+        camera: &Camera,
+        material: Handle<Material>,
+    ) {
+        let view_projection = camera.view_projection();
+        let previous_view_projection = self.previous_view_projection.unwrap_or(view_projection);
+        self.cull(camera);
+
+        for (handle, depth) in &self.visible {
+            let Some(node) = self.nodes.get(*handle) else {
+                continue;
+            };
+            let properties = [
+                MaterialProperty::new("model", &node.transform),
+                MaterialProperty::new("view_projection", &view_projection),
+                MaterialProperty::new(
+                    PREVIOUS_MODEL,
+                    node.previous_transform.as_ref().unwrap_or(&node.transform),
+                ),
+                MaterialProperty::new(PREVIOUS_VIEW_PROJECTION, &previous_view_projection),
+            ];
+            renderer.draw_at_depth(node.mesh, material, *depth, &properties);
+        }
+    }
+}
+
+/// Program that writes the motion vectors of [draw_motion_vectors][Scene::draw_motion_vectors]
+/// into the red and green channels of the target, best a float one like
+/// [Rgba16F][crate::TextureFormat::Rgba16F]. The positions are read from the location of the
+/// [Position][AttributeSemantic::Position] attribute.
+pub fn motion_vector_program<C: Context>(
+    ctx: &mut Renderer<C>,
+) -> Result<Handle<ShaderProgram>, RendererError> {
+    let position = ctx
+        .layout_config()
+        .location(AttributeSemantic::Position)
+        .ok_or(RendererError::AttributeHasNoLocation {
+            semantic: AttributeSemantic::Position,
+        })?;
+    let vertex_source = format!(
+        r##"
+    #version 330 core
+    layout(location = {position}) in vec3 pos;
+    uniform mat4 model;
+    uniform mat4 view_projection;
+    uniform mat4 {PREVIOUS_MODEL};
+    uniform mat4 {PREVIOUS_VIEW_PROJECTION};
+    out vec4 current;
+    out vec4 previous;
+    void main() {{
+        current = view_projection * model * vec4(pos, 1.0);
+        previous = {PREVIOUS_VIEW_PROJECTION} * {PREVIOUS_MODEL} * vec4(pos, 1.0);
+        gl_Position = current;
+    }}"##
+    );
+    ShaderProgram::from_sources(ctx, &vertex_source, MOTION_VECTORS_FS_SOURCE)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{math::vec3, Primitive};
+
+    fn node(z: f32) -> Node {
+        let mesh = Mesh::new(Handle::new(), 3, Primitive::Triangles);
+        Node::new(mesh, Handle::new())
+            .with_transform(Mat4::from_translation(vec3(0.0, 0.0, z)))
+            .with_bounds(BoundingSphere::new(Vec3::ZERO, 1.0))
+    }
+
+    #[test]
+    fn cull_and_sort() {
+        let mut scene = Scene::default();
+        let far = scene.add(node(-50.0));
+        let near = scene.add(node(-5.0));
+        let behind = scene.add(node(10.0));
+        // the center is in front of the near plane, but the bounds reach past it
+        let touching = scene.add(node(-0.5));
+        let hidden = scene.add(node(-10.0));
+        scene.node_mut(hidden).unwrap().visible = false;
+        let mut unbounded = node(10.0);
+        unbounded.bounds = None;
+        let unbounded = scene.add(unbounded);
+
+        let camera = Camera {
+            view: Mat4::IDENTITY,
+            projection: Mat4::perspective_rh_gl(1.0, 1.0, 1.0, 100.0),
+        };
+        let visible: Vec<_> = scene.cull(&camera).iter().map(|(node, _)| *node).collect();
+        assert_eq!(visible, [touching, near, unbounded, far]);
+        assert!(!visible.contains(&behind));
+
+        // a scale grows the bounds into the frustum
+        scene.node_mut(behind).unwrap().transform *= Mat4::from_scale(Vec3::splat(20.0));
+        assert_eq!(scene.cull(&camera).len(), 5);
+    }
+
+    #[test]
+    fn graph_transforms() {
+        let mut graph = TransformGraph::default();
+        let parent = graph.add(Mat4::from_translation(vec3(0.0, 0.0, -5.0)), None);
+        let child = graph.add(Mat4::from_translation(Vec3::X), Some(parent));
+
+        let mut scene = Scene::default();
+        let attached = scene.add(node(0.0).with_graph_transform(child));
+        let detached = scene.add(node(1.0));
+        scene.apply_transforms(&mut graph);
+
+        let transform = |node| scene.node(node).unwrap().transform.w_axis.truncate();
+        assert_eq!(transform(attached), vec3(1.0, 0.0, -5.0));
+        assert_eq!(transform(detached), vec3(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn lightmap_atlas() {
+        let rects = pack_lightmaps(&[(30, 10), (30, 20), (20, 20)], 64, 64, 1).unwrap();
+        // the two tallest share the first row, the flat one starts the next
+        assert_eq!(
+            rects,
+            [
+                LightmapRect {
+                    x: 1,
+                    y: 22,
+                    width: 30,
+                    height: 10
+                },
+                LightmapRect {
+                    x: 1,
+                    y: 1,
+                    width: 30,
+                    height: 20
+                },
+                LightmapRect {
+                    x: 32,
+                    y: 1,
+                    width: 20,
+                    height: 20
+                },
+            ]
+        );
+        assert_eq!(
+            rects[2].scale_offset(64, 64),
+            vec4(0.3125, 0.3125, 0.5, 1.0 / 64.0)
+        );
+        assert_eq!(pack_lightmaps(&[(64, 8)], 64, 64, 1), None);
+    }
+
+    #[test]
+    fn bounds_from_positions() {
+        let bounds = BoundingSphere::from_positions(&[Vec3::X, -Vec3::X, Vec3::Y, -Vec3::Y]);
+        assert_eq!(bounds, BoundingSphere::new(Vec3::ZERO, 1.0));
+        assert_eq!(
+            BoundingSphere::from_positions(&[]),
+            BoundingSphere::new(Vec3::ZERO, 0.0)
+        );
+    }
+
+    #[test]
+    fn previous_frame() {
+        let mut scene = Scene::default();
+        let moving = scene.add(node(-5.0));
+        assert_eq!(scene.node(moving).unwrap().previous_transform, None);
+        assert_eq!(scene.previous_view_projection(), None);
+
+        let camera = Camera {
+            view: Mat4::IDENTITY,
+            projection: Mat4::perspective_rh_gl(1.0, 1.0, 1.0, 100.0),
+        };
+        scene.end_frame(&camera);
+        let before = scene.node(moving).unwrap().transform;
+        scene.node_mut(moving).unwrap().transform = Mat4::from_translation(vec3(1.0, 0.0, -5.0));
+        // added after the frame ended, so it has no motion yet
+        let new = scene.add(node(-5.0));
+
+        assert_eq!(scene.node(moving).unwrap().previous_transform, Some(before));
+        assert_eq!(scene.node(new).unwrap().previous_transform, None);
+        assert_eq!(
+            scene.previous_view_projection(),
+            Some(camera.view_projection())
+        );
+
+        scene.clear();
+        assert_eq!(scene.previous_view_projection(), None);
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn draw_motion_vectors() {
+        use crate::RecordedCommand;
+
+        let mut renderer = Renderer::new_headless().unwrap();
+        let program = motion_vector_program(&mut renderer).unwrap();
+        let material = renderer.create_material(program, &[]).unwrap();
+
+        let mut scene = Scene::default();
+        scene.add(node(-5.0));
+        scene.add(node(-10.0));
+        let camera = Camera {
+            view: Mat4::IDENTITY,
+            projection: Mat4::perspective_rh_gl(1.0, 1.0, 1.0, 100.0),
+        };
+        renderer.clear_recorded_commands();
+        scene.draw_motion_vectors(&mut renderer, &camera, material);
+        renderer.update();
+
+        let materials: Vec<_> = renderer
+            .recorded_commands()
+            .iter()
+            .filter_map(|command| match command {
+                RecordedCommand::Draw { material, .. } => Some(*material),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(materials, [material, material]);
+    }
+}
@@ -0,0 +1,299 @@
+//! Golden-image regression tests: render a scene offscreen and compare it against a reference
+//! PNG.
+//! ```no_run
+//! # use cac_renderer::{testing::*, *};
+//! # fn scene(renderer: &mut Renderer<OpenGLContext>) {}
+//! # fn test(renderer: &mut Renderer<OpenGLContext>) {
+//! let image = render_offscreen(renderer, 64, 64, scene).unwrap();
+//! GoldenImages::new("tests/golden")
+//!     .with_tolerance(Tolerance::new(2, 10))
+//!     .check("scene", &image)
+//!     .unwrap();
+//! # }
+//! ```
+//! Running the tests with `CAC_UPDATE_GOLDEN=1` (re)writes the reference images instead of
+//! comparing against them. On a mismatch, the rendered image and a diff image are written next to
+//! the reference, as `<name>.actual.png` and `<name>.diff.png`.
+use std::path::{Path, PathBuf};
+
+use crate::{Image, RendererError};
+
+/// Environment variable that makes [GoldenImages::check] write the reference images.
+pub const UPDATE_ENV: &str = "CAC_UPDATE_GOLDEN";
+
+/// How much a rendered image may differ from its reference, to absorb rounding differences
+/// between drivers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Tolerance {
+    /// Largest difference of a single channel for the pixel to still count as matching.
+    pub per_channel: u8,
+    /// Number of pixels that may exceed the per channel difference, e.g. at triangle edges.
+    pub max_mismatched_pixels: usize,
+}
+
+impl Tolerance {
+    pub const EXACT: Self = Self::new(0, 0);
+
+    pub const fn new(per_channel: u8, max_mismatched_pixels: usize) -> Self {
+        Self {
+            per_channel,
+            max_mismatched_pixels,
+        }
+    }
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self::new(2, 0)
+    }
+}
+
+/// Result of [compare]ing two images of the same size.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Comparison {
+    /// Pixels with at least one channel that differs by more than the tolerance.
+    pub mismatched_pixels: usize,
+    /// Largest difference of any channel.
+    pub max_difference: u8,
+    /// Mismatched pixels in red, the matching ones as dimmed gray.
+    pub diff: Image,
+}
+
+/// Compares the pixels of both images, or None if their sizes differ.
+pub fn compare(actual: &Image, expected: &Image, per_channel: u8) -> Option<Comparison> {
+    if actual.width != expected.width
+        || actual.height != expected.height
+        || actual.pixels.len() != expected.pixels.len()
+    {
+        return None;
+    }
+
+    let mut mismatched_pixels = 0;
+    let mut max_difference = 0;
+    let mut diff = Vec::with_capacity(actual.pixels.len());
+    for (actual, expected) in actual
+        .pixels
+        .chunks_exact(4)
+        .zip(expected.pixels.chunks_exact(4))
+    {
+        let difference = actual
+            .iter()
+            .zip(expected)
+            .map(|(actual, expected)| actual.abs_diff(*expected))
+            .max()
+            .unwrap_or(0);
+        max_difference = max_difference.max(difference);
+
+        if difference > per_channel {
+            mismatched_pixels += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let luma = (expected[0] as u32 * 3 + expected[1] as u32 * 6 + expected[2] as u32) / 10;
+            let gray = (luma / 4) as u8;
+            diff.extend_from_slice(&[gray, gray, gray, 255]);
+        }
+    }
+
+    Some(Comparison {
+        mismatched_pixels,
+        max_difference,
+        diff: Image {
+            width: actual.width,
+            height: actual.height,
+            pixels: diff,
+        },
+    })
+}
+
+/// Directory of reference images, stored as `<name>.png`.
+#[derive(Clone, Debug)]
+pub struct GoldenImages {
+    directory: PathBuf,
+    tolerance: Tolerance,
+}
+
+impl GoldenImages {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            tolerance: Tolerance::default(),
+        }
+    }
+
+    pub fn with_tolerance(mut self, tolerance: Tolerance) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn reference_path(&self, name: &str) -> PathBuf {
+        self.directory.join(format!("{name}.png"))
+    }
+
+    /// Compares the image against the reference with the name.
+    /// Writes the reference instead, if [UPDATE_ENV] is set.
+    pub fn check(&self, name: &str, image: &Image) -> Result<(), RendererError> {
+        let reference = self.reference_path(name);
+        if std::env::var_os(UPDATE_ENV).is_some() {
+            create_directory(&self.directory)?;
+            return image.save_png(&reference);
+        }
+
+        if !reference.exists() {
+            return Err(RendererError::ImageMismatch {
+                name: name.to_string(),
+                error: format!(
+                    "{} doesn't exist, run with {UPDATE_ENV}=1 to create it",
+                    reference.display()
+                ),
+            });
+        }
+        let expected = Image::open_png(&reference)?;
+
+        let error = match compare(image, &expected, self.tolerance.per_channel) {
+            None => format!(
+                "size is {}x{}, expected {}x{}",
+                image.width, image.height, expected.width, expected.height
+            ),
+            Some(comparison)
+                if comparison.mismatched_pixels > self.tolerance.max_mismatched_pixels =>
+            {
+                let diff = self.directory.join(format!("{name}.diff.png"));
+                comparison.diff.save_png(&diff)?;
+                format!(
+                    "{} pixels differ by up to {}, see {}",
+                    comparison.mismatched_pixels,
+                    comparison.max_difference,
+                    diff.display()
+                )
+            }
+            Some(_) => return Ok(()),
+        };
+
+        image.save_png(self.directory.join(format!("{name}.actual.png")))?;
+        Err(RendererError::ImageMismatch {
+            name: name.to_string(),
+            error,
+        })
+    }
+}
+
+fn create_directory(directory: &Path) -> Result<(), RendererError> {
+    std::fs::create_dir_all(directory).map_err(|error| RendererError::FailedToWriteFile {
+        path: directory.display().to_string(),
+        error: error.to_string(),
+    })
+}
+
+/// Renders the draws queued by `draw` into an offscreen RGBA8 texture and reads it back.
+/// Afterwards, the screen is the active target again.
+#[cfg(feature = "opengl")]
+pub fn render_offscreen(
+    ctx: &mut crate::Renderer<crate::OpenGLContext>,
+    width: u32,
+    height: u32,
+    draw: impl FnOnce(&mut crate::Renderer<crate::OpenGLContext>),
+) -> Result<Image, RendererError> {
+    use crate::{Backend, Texture, TextureFormat, TextureRegion};
+
+    let pixels = vec![0; width as usize * height as usize * 4];
+    let texture = Texture::new(ctx, &pixels, width, height, TextureFormat::Rgba8)?;
+    let target = match ctx.texture_target(texture) {
+        Ok(target) => target,
+        Err(error) => {
+            ctx.textures.remove(texture);
+            return Err(error);
+        }
+    };
+
+    ctx.set_external_target(Some(target));
+    draw(ctx);
+    ctx.update();
+
+    let pixels = ctx
+        .external_target(target)
+        .map(|target| {
+            target.read_pixels(TextureRegion {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            })
+        })
+        .unwrap_or_default();
+
+    ctx.remove_external_target(target);
+    ctx.textures.remove(texture);
+
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn image(width: u32, height: u32, color: [u8; 4]) -> Image {
+        Image {
+            width,
+            height,
+            pixels: color.repeat((width * height) as usize),
+        }
+    }
+
+    #[test]
+    fn tolerance() {
+        let expected = image(4, 4, [100, 100, 100, 255]);
+        let mut actual = expected.clone();
+        actual.pixels[0] = 102;
+        actual.pixels[4] = 110;
+
+        let comparison = compare(&actual, &expected, 2).unwrap();
+        assert_eq!(comparison.mismatched_pixels, 1);
+        assert_eq!(comparison.max_difference, 10);
+        assert_eq!(comparison.diff.pixel(1, 0), Some([255, 0, 0, 255]));
+        assert_eq!(comparison.diff.pixel(0, 0), Some([25, 25, 25, 255]));
+
+        assert_eq!(
+            compare(&actual, &expected, 10).unwrap().mismatched_pixels,
+            0
+        );
+        assert_eq!(compare(&actual, &image(2, 8, [0; 4]), 0), None);
+    }
+
+    #[test]
+    fn check() {
+        let directory = std::env::temp_dir().join(format!("cac_golden_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let golden = GoldenImages::new(&directory);
+
+        let expected = image(8, 8, [10, 20, 30, 255]);
+        assert!(matches!(
+            golden.check("scene", &expected),
+            Err(RendererError::ImageMismatch { .. })
+        ));
+
+        expected.save_png(golden.reference_path("scene")).unwrap();
+        golden.check("scene", &expected).unwrap();
+
+        let mut actual = expected.clone();
+        actual.pixels[0] = 200;
+        assert!(golden.check("scene", &actual).is_err());
+        assert_eq!(
+            Image::open_png(directory.join("scene.actual.png")).unwrap(),
+            actual
+        );
+        assert!(directory.join("scene.diff.png").exists());
+
+        golden
+            .clone()
+            .with_tolerance(Tolerance::new(0, 1))
+            .check("scene", &actual)
+            .unwrap();
+        assert!(golden.check("scene", &image(4, 4, [0; 4])).is_err());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,290 @@
+//! Parent/child hierarchy of transforms, whose world matrices are only recomputed when they or
+//! one of their parents changed.
+//!
+//! ```
+//! # use cac_renderer::{math::*, transform::TransformGraph};
+//! let mut graph = TransformGraph::default();
+//! let body = graph.add(Mat4::from_translation(vec3(0.0, 1.0, 0.0)), None);
+//! let arm = graph.add(Mat4::from_translation(vec3(1.0, 0.0, 0.0)), Some(body));
+//! graph.update();
+//! assert_eq!(graph.world(arm).unwrap().w_axis, vec4(1.0, 1.0, 0.0, 1.0));
+//!
+//! // only the body and its children are recomputed
+//! graph.set_local(body, Mat4::IDENTITY);
+//! assert_eq!(graph.update(), 2);
+//! assert_eq!(graph.world(arm).unwrap().w_axis, vec4(1.0, 0.0, 0.0, 1.0));
+//! ```
+//!
+//! The world matrices feed the model matrices of the draws, e.g. through
+//! [Scene::apply_transforms][crate::scene::Scene::apply_transforms].
+use crate::{generation_vec::GenerationVec, math::Mat4, Handle};
+
+/// A transform of a [TransformGraph].
+#[derive(Debug, Clone)]
+pub struct Transform {
+    local: Mat4,
+    world: Mat4,
+    parent: Option<Handle<Transform>>,
+    children: Vec<Handle<Transform>>,
+    /// The local matrix or the parent changed since the last update.
+    dirty: bool,
+    /// Counts the recomputations of the world matrix.
+    version: u32,
+    /// Version of the parent the world matrix was computed with, so changes of the parents reach
+    /// their children.
+    parent_version: u32,
+}
+
+impl Transform {
+    /// Relative to the parent.
+    pub fn local(&self) -> Mat4 {
+        self.local
+    }
+
+    /// The local matrix with the ones of all parents applied, as of the last
+    /// [update][TransformGraph::update].
+    pub fn world(&self) -> Mat4 {
+        self.world
+    }
+
+    pub fn parent(&self) -> Option<Handle<Transform>> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[Handle<Transform>] {
+        &self.children
+    }
+}
+
+/// Transforms with parents, whose world matrices are recomputed in one batch by
+/// [update][Self::update], see the [module documentation][self].
+#[derive(Default)]
+pub struct TransformGraph {
+    transforms: GenerationVec<Transform, Transform>,
+    /// Parents before their children, rebuilt on the next update after the hierarchy changed.
+    order: Vec<Handle<Transform>>,
+    order_dirty: bool,
+}
+
+impl TransformGraph {
+    /// Adds the transform as child of the parent, or as root if there is none or the parent
+    /// doesn't exist.
+    pub fn add(&mut self, local: Mat4, parent: Option<Handle<Transform>>) -> Handle<Transform> {
+        let parent = parent.filter(|parent| self.transforms.get(*parent).is_some());
+        let handle = self.transforms.push(Transform {
+            local,
+            world: local,
+            parent,
+            children: Vec::new(),
+            dirty: true,
+            version: 0,
+            parent_version: 0,
+        });
+        if let Some(parent) = parent.and_then(|parent| self.transforms.get_mut(parent)) {
+            parent.children.push(handle);
+        }
+        self.order_dirty = true;
+        handle
+    }
+
+    /// Removes the transform and all of its children.
+    pub fn remove(&mut self, transform: Handle<Transform>) {
+        let Some(removed) = self.transforms.get(transform) else {
+            return;
+        };
+        if let Some(parent) = removed
+            .parent
+            .and_then(|parent| self.transforms.get_mut(parent))
+        {
+            parent.children.retain(|child| *child != transform);
+        }
+
+        let mut pending = vec![transform];
+        while let Some(handle) = pending.pop() {
+            if let Some(removed) = self.transforms.get(handle) {
+                pending.extend_from_slice(&removed.children);
+            }
+            self.transforms.remove(handle);
+        }
+        self.order_dirty = true;
+    }
+
+    pub fn get(&self, transform: Handle<Transform>) -> Option<&Transform> {
+        self.transforms.get(transform)
+    }
+
+    /// The world matrix as of the last [update][Self::update].
+    pub fn world(&self, transform: Handle<Transform>) -> Option<Mat4> {
+        self.transforms.get(transform).map(Transform::world)
+    }
+
+    pub fn set_local(&mut self, transform: Handle<Transform>, local: Mat4) {
+        if let Some(stored) = self.transforms.get_mut(transform) {
+            stored.local = local;
+            stored.dirty = true;
+        }
+    }
+
+    /// Moves the transform to the new parent, or makes it a root. Returns false without changing
+    /// anything if one of them doesn't exist, or if the parent is the transform itself or one of
+    /// its children.
+    pub fn set_parent(
+        &mut self,
+        transform: Handle<Transform>,
+        parent: Option<Handle<Transform>>,
+    ) -> bool {
+        let Some(old_parent) = self.transforms.get(transform).map(Transform::parent) else {
+            return false;
+        };
+        if let Some(parent) = parent {
+            // walking up from the new parent must not reach the transform
+            let mut ancestor = Some(parent);
+            while let Some(handle) = ancestor {
+                if handle == transform {
+                    return false;
+                }
+                let Some(stored) = self.transforms.get(handle) else {
+                    return false;
+                };
+                ancestor = stored.parent;
+            }
+        }
+
+        if let Some(old_parent) = old_parent.and_then(|parent| self.transforms.get_mut(parent)) {
+            old_parent.children.retain(|child| *child != transform);
+        }
+        if let Some(parent) = parent.and_then(|parent| self.transforms.get_mut(parent)) {
+            parent.children.push(transform);
+        }
+        if let Some(stored) = self.transforms.get_mut(transform) {
+            stored.parent = parent;
+            stored.dirty = true;
+        }
+        self.order_dirty = true;
+        true
+    }
+
+    /// Recomputes the world matrices of the changed transforms and their children, parents
+    /// first. Returns how many were recomputed.
+    pub fn update(&mut self) -> usize {
+        if self.order_dirty {
+            self.rebuild_order();
+        }
+
+        let mut updated = 0;
+        for handle in &self.order {
+            let Some(transform) = self.transforms.get(*handle) else {
+                continue;
+            };
+            let parent = transform
+                .parent
+                .and_then(|parent| self.transforms.get(parent));
+            let parent_version = parent.map_or(0, |parent| parent.version);
+            if !transform.dirty && parent_version == transform.parent_version {
+                continue;
+            }
+
+            let world = match parent {
+                Some(parent) => parent.world * transform.local,
+                None => transform.local,
+            };
+            if let Some(transform) = self.transforms.get_mut(*handle) {
+                transform.world = world;
+                transform.dirty = false;
+                transform.version = transform.version.wrapping_add(1);
+                transform.parent_version = parent_version;
+            }
+            updated += 1;
+        }
+        updated
+    }
+
+    fn rebuild_order(&mut self) {
+        self.order.clear();
+        let roots = self
+            .transforms
+            .handles()
+            .zip(self.transforms.iter())
+            .filter(|(_, transform)| transform.parent.is_none())
+            .map(|(handle, _)| handle);
+        self.order.extend(roots);
+
+        // breadth first, so every parent comes before its children
+        let mut next = 0;
+        while next < self.order.len() {
+            if let Some(transform) = self.transforms.get(self.order[next]) {
+                self.order.extend_from_slice(&transform.children);
+            }
+            next += 1;
+        }
+        self.order_dirty = false;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::{vec3, Vec3};
+
+    fn translation(x: f32) -> Mat4 {
+        Mat4::from_translation(vec3(x, 0.0, 0.0))
+    }
+
+    fn world_x(graph: &TransformGraph, transform: Handle<Transform>) -> f32 {
+        graph.world(transform).unwrap().w_axis.x
+    }
+
+    #[test]
+    fn dirty_propagation() {
+        let mut graph = TransformGraph::default();
+        let root = graph.add(translation(1.0), None);
+        let child = graph.add(translation(2.0), Some(root));
+        let grandchild = graph.add(translation(4.0), Some(child));
+        let other = graph.add(translation(8.0), None);
+        assert_eq!(graph.update(), 4);
+        assert_eq!(world_x(&graph, grandchild), 7.0);
+        assert_eq!(graph.update(), 0);
+
+        graph.set_local(child, translation(0.0));
+        assert_eq!(graph.update(), 2);
+        assert_eq!(world_x(&graph, grandchild), 5.0);
+        assert_eq!(world_x(&graph, other), 8.0);
+
+        // children keep their local transforms under the new parent
+        assert!(graph.set_parent(child, Some(other)));
+        assert_eq!(graph.update(), 2);
+        assert_eq!(world_x(&graph, grandchild), 12.0);
+        assert!(graph.get(root).unwrap().children().is_empty());
+        assert_eq!(graph.get(other).unwrap().children(), [child]);
+    }
+
+    #[test]
+    fn reject_cycles() {
+        let mut graph = TransformGraph::default();
+        let root = graph.add(Mat4::IDENTITY, None);
+        let child = graph.add(Mat4::IDENTITY, Some(root));
+
+        assert!(!graph.set_parent(root, Some(child)));
+        assert!(!graph.set_parent(root, Some(root)));
+        assert!(graph.set_parent(child, None));
+        assert!(graph.set_parent(root, Some(child)));
+        graph.update();
+        assert_eq!(graph.get(root).unwrap().parent(), Some(child));
+    }
+
+    #[test]
+    fn remove_subtree() {
+        let mut graph = TransformGraph::default();
+        let root = graph.add(Mat4::from_scale(Vec3::splat(2.0)), None);
+        let child = graph.add(translation(1.0), Some(root));
+        let grandchild = graph.add(translation(1.0), Some(child));
+        graph.update();
+        assert_eq!(world_x(&graph, grandchild), 4.0);
+
+        graph.remove(child);
+        assert!(graph.get(child).is_none());
+        assert!(graph.get(grandchild).is_none());
+        assert!(graph.get(root).unwrap().children().is_empty());
+        assert_eq!(graph.update(), 0);
+    }
+}
@@ -0,0 +1,161 @@
+//! Runs the scenes of the examples with the headless backend, so every example doubles as a test
+//! of the subsystems it shows.
+#![cfg(feature = "headless")]
+
+#[path = "../examples/scenes/capture.rs"]
+mod capture;
+#[path = "../examples/scenes/cubes.rs"]
+mod cubes;
+#[path = "../examples/scenes/post_process.rs"]
+mod post_process;
+#[path = "../examples/scenes/textured_quad.rs"]
+mod textured_quad;
+
+use cac_renderer::{
+    Backend, Handle, HeadlessContext, Material, RecordedCommand, Renderer, Sampler,
+    SamplerDescription, Texture, TextureFormat,
+};
+use cubes::Cubes;
+use post_process::PostProcess;
+use textured_quad::{checkerboard, TexturedQuad};
+
+#[test]
+fn textured_quad() {
+    let mut ctx = Renderer::new_headless().unwrap();
+    let texels = checkerboard(8, 2);
+    assert_eq!(&texels[..4], &texels[4..8]);
+    assert_ne!(&texels[..4], &texels[16..20]);
+
+    let texture = Texture::new(&mut ctx, &texels, 8, 8, TextureFormat::Rgba8).unwrap();
+    let sampler = Sampler::new(&mut ctx, &SamplerDescription::nearest()).unwrap();
+    let quad = TexturedQuad::new(&mut ctx, texture, Some(sampler)).unwrap();
+
+    ctx.clear_recorded_commands();
+    quad.draw(&mut ctx, 1.0, 0.5);
+    ctx.update();
+
+    let commands = ctx.recorded_commands();
+    assert!(commands.contains(&RecordedCommand::BindTexture {
+        unit: 0,
+        texture,
+        sampler: Some(sampler),
+    }));
+    assert!(commands.iter().any(|command| matches!(
+        command,
+        RecordedCommand::Draw { mesh, indexed: true, .. } if *mesh == quad.mesh
+    )));
+}
+
+#[test]
+fn cubes_stress() {
+    const CUBES: usize = 100_000;
+
+    let mut ctx = Renderer::new_headless().unwrap();
+    let cubes = Cubes::new(&mut ctx, CUBES, 1.0).unwrap();
+    assert_eq!(cubes.positions.len(), CUBES);
+
+    ctx.clear_recorded_commands();
+    cubes.draw(&mut ctx, 1.0);
+    ctx.update();
+
+    assert_eq!(ctx.frame_stats().draw_calls, CUBES as u32);
+
+    // back to front, so the nearer cubes are drawn over the ones behind them. The sort key only
+    // keeps 7 bits of the depth's mantissa, so close depths can be in any order.
+    let depths: Vec<f32> = ctx
+        .recorded_commands()
+        .iter()
+        .filter_map(|command| match command {
+            RecordedCommand::Draw { depth, .. } => Some(*depth),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(depths.len(), CUBES);
+    assert!(depths
+        .windows(2)
+        .all(|pair| pair[0] >= pair[1] * (1.0 - 1.0 / 128.0)));
+}
+
+/// Material and whether it was indexed, of every draw of the last update.
+fn draws(ctx: &Renderer<HeadlessContext>) -> Vec<(Handle<Material>, bool)> {
+    ctx.recorded_commands()
+        .iter()
+        .filter_map(|command| match command {
+            RecordedCommand::Draw {
+                material, indexed, ..
+            } => Some((*material, *indexed)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether the last update bound the texture to the first unit.
+fn binds_texture(ctx: &Renderer<HeadlessContext>, texture: Handle<Texture>) -> bool {
+    ctx.recorded_commands()
+        .contains(&RecordedCommand::BindTexture {
+            unit: 0,
+            texture,
+            sampler: None,
+        })
+}
+
+#[test]
+fn render_to_texture() {
+    let mut ctx = Renderer::new_headless().unwrap();
+    let texels = vec![0; 8 * 8 * 4];
+    let texture = Texture::new(&mut ctx, &texels, 8, 8, TextureFormat::Rgba8).unwrap();
+    let cubes = Cubes::new(&mut ctx, 27, 1.0).unwrap();
+    let quad = TexturedQuad::new(&mut ctx, texture, None).unwrap();
+
+    // the headless backend has no texture targets, so the update of the cubes that would go into
+    // the texture is recorded into the screen target
+    ctx.clear_recorded_commands();
+    cubes.draw(&mut ctx, 1.0);
+    ctx.update();
+    assert_eq!(draws(&ctx), vec![(cubes.material, true); 27]);
+    assert!(!binds_texture(&ctx, texture));
+
+    ctx.clear_recorded_commands();
+    quad.draw(&mut ctx, 1.5, 0.2);
+    ctx.update();
+    assert_eq!(draws(&ctx), [(quad.material, true)]);
+    assert!(binds_texture(&ctx, texture));
+}
+
+#[test]
+fn post_processing() {
+    let mut ctx = Renderer::new_headless().unwrap();
+    let texels = vec![0; 4 * 4 * 4];
+    let texture = Texture::new(&mut ctx, &texels, 4, 4, TextureFormat::Rgba8).unwrap();
+    let cubes = Cubes::new(&mut ctx, 8, 1.0).unwrap();
+    let post_process = PostProcess::new(&mut ctx, texture).unwrap();
+
+    // the headless backend has no texture targets, so the update of the cubes that would go into
+    // the texture is recorded into the screen target
+    ctx.clear_recorded_commands();
+    cubes.draw(&mut ctx, 1.0);
+    ctx.update();
+    assert_eq!(draws(&ctx), vec![(cubes.material, true); 8]);
+    assert!(!binds_texture(&ctx, texture));
+
+    // the fullscreen pass samples the texture, without an index buffer
+    ctx.clear_recorded_commands();
+    post_process.draw(&mut ctx, 0.5);
+    ctx.update();
+    assert_eq!(draws(&ctx), [(post_process.material, false)]);
+    assert!(binds_texture(&ctx, texture));
+}
+
+#[test]
+fn headless_capture() {
+    let ctx = capture::capture().unwrap();
+    let commands = ctx.recorded_commands();
+
+    // only the frame is captured, not the uploads of the setup
+    assert!(!commands.iter().any(|command| matches!(
+        command,
+        RecordedCommand::UploadVertices { .. } | RecordedCommand::UploadIndices { .. }
+    )));
+    assert_eq!(draws(&ctx).len(), capture::CUBES + 1);
+    assert_eq!(ctx.frame_stats().draw_calls, capture::CUBES as u32 + 1);
+}